@@ -0,0 +1,159 @@
+//! Block devices.
+//!
+//! The `BlockDevice` trait is implemented by the storage drivers (e.g. NVMe
+//! and AHCI), so the code on top of them (e.g. filesystems) does not depend
+//! on a specific driver. The devices are arrays of fixed-size blocks, which
+//! are read and written in whole blocks.
+//!
+//! This crate also provides `RamDisk`, a block device backed by memory.
+
+#![no_std]
+
+use ticket_mutex::TicketMutex;
+
+/// Block device errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The length of the buffer is not a multiple of the block size.
+    InvalidBufferSize(usize),
+
+    /// The blocks are past the end of the device.
+    OutOfRange,
+
+    /// The device failed to transfer the data. The drivers report the
+    /// details.
+    Io,
+}
+
+/// Represents a device that stores an array of fixed-size blocks.
+pub trait BlockDevice: Sync {
+    /// Returns the size of a block in bytes.
+    fn block_size(&self) -> usize;
+
+    /// Returns the number of blocks.
+    fn blocks(&self) -> u64;
+
+    /// Reads the blocks starting at `lba` into `buf`, whose length must be a
+    /// multiple of the block size.
+    fn read(&self, lba: u64, buf: &mut [u8]) -> Result<(), Error>;
+
+    /// Writes `buf`, whose length must be a multiple of the block size, into
+    /// the blocks starting at `lba`.
+    fn write(&self, lba: u64, buf: &[u8]) -> Result<(), Error>;
+
+    /// Makes the written data persistent.
+    fn flush(&self) -> Result<(), Error>;
+}
+
+/// Checks that `len` bytes starting at the block `lba` are a whole number
+/// of blocks of `block_size` bytes inside a device with `blocks` blocks.
+pub fn check_range(
+    block_size: usize,
+    blocks: u64,
+    lba: u64,
+    len: usize,
+) -> Result<(), Error> {
+    if block_size == 0 || len % block_size != 0 {
+        return Err(Error::InvalidBufferSize(len));
+    }
+    let count = (len / block_size) as u64;
+    match lba.checked_add(count) {
+        Some(end) if end <= blocks => Ok(()),
+        _ => Err(Error::OutOfRange),
+    }
+}
+
+/// Block device backed by memory. The trailing bytes that do not fill a
+/// block are not used.
+pub struct RamDisk<'a> {
+    data: TicketMutex<&'a mut [u8]>,
+    block_size: usize,
+    blocks: u64,
+}
+
+impl<'a> RamDisk<'a> {
+    /// Returns a `RamDisk` with blocks of `block_size` bytes stored in
+    /// `data`.
+    pub fn new(data: &'a mut [u8], block_size: usize) -> Self {
+        let blocks = data.len().checked_div(block_size).unwrap_or(0) as u64;
+        RamDisk {
+            data: TicketMutex::new(data),
+            block_size,
+            blocks,
+        }
+    }
+
+    /// Returns the byte range of `len` bytes starting at the block `lba`.
+    fn range(
+        &self,
+        lba: u64,
+        len: usize,
+    ) -> Result<core::ops::Range<usize>, Error> {
+        check_range(self.block_size, self.blocks, lba, len)?;
+        let start = lba as usize * self.block_size;
+        Ok(start..start + len)
+    }
+}
+
+impl BlockDevice for RamDisk<'_> {
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn blocks(&self) -> u64 {
+        self.blocks
+    }
+
+    fn read(&self, lba: u64, buf: &mut [u8]) -> Result<(), Error> {
+        let range = self.range(lba, buf.len())?;
+        buf.copy_from_slice(&self.data.lock()[range]);
+        Ok(())
+    }
+
+    fn write(&self, lba: u64, buf: &[u8]) -> Result<(), Error> {
+        let range = self.range(lba, buf.len())?;
+        self.data.lock()[range].copy_from_slice(buf);
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_range() {
+        assert_eq!(check_range(512, 4, 0, 2048), Ok(()));
+        assert_eq!(check_range(512, 4, 3, 512), Ok(()));
+        assert_eq!(check_range(512, 4, 3, 1024), Err(Error::OutOfRange));
+        assert_eq!(check_range(512, 4, u64::MAX, 512), Err(Error::OutOfRange));
+        assert_eq!(
+            check_range(512, 4, 0, 100),
+            Err(Error::InvalidBufferSize(100))
+        );
+    }
+
+    #[test]
+    fn test_ram_disk() {
+        let mut data = [0u8; 1100];
+        let disk = RamDisk::new(&mut data, 512);
+        assert_eq!(disk.blocks(), 2);
+
+        let block = [0xaa; 512];
+        disk.write(1, &block).unwrap();
+        let mut buf = [0; 1024];
+        disk.read(0, &mut buf).unwrap();
+        assert!(buf[..512].iter().all(|&b| b == 0));
+        assert!(buf[512..].iter().all(|&b| b == 0xaa));
+
+        assert_eq!(disk.write(2, &block), Err(Error::OutOfRange));
+        assert_eq!(
+            disk.read(0, &mut buf[..10]),
+            Err(Error::InvalidBufferSize(10))
+        );
+    }
+}
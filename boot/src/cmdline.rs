@@ -0,0 +1,46 @@
+//! Kernel command line.
+//!
+//! The command line is taken from the load options of the bootloader, so it
+//! can be set in the boot option or passed from the UEFI Shell. In the
+//! latter case, it starts with the path of the bootloader, which the kernel
+//! ignores as an unknown argument. If there are no load options, the command
+//! line is read from `CMDLINE_PATH` in the volume the bootloader was loaded
+//! from.
+
+use uefi::{fs, image, BootServices, Error, Handle, StatusError};
+
+/// Path of the command line file in the volume the bootloader was loaded
+/// from.
+const CMDLINE_PATH: &str = "\\expos\\cmdline.txt";
+
+/// Reads the kernel command line into `buf` and returns it. The command line
+/// is empty if there are neither load options nor command line file.
+pub fn read<'b>(
+    boot_services: &BootServices,
+    image_handle: Handle,
+    buf: &'b mut [u8],
+) -> Result<&'b str, Error> {
+    let loaded_image = image::loaded_image(boot_services, image_handle)?;
+    match loaded_image.load_options() {
+        Some(options) if options.chars().any(|c| !c.is_whitespace()) => {
+            options.to_str(buf)
+        }
+        _ => read_file(boot_services, image_handle, buf),
+    }
+}
+
+/// Reads the command line file at `CMDLINE_PATH` into `buf` and returns it.
+fn read_file<'b>(
+    boot_services: &BootServices,
+    image_handle: Handle,
+    buf: &'b mut [u8],
+) -> Result<&'b str, Error> {
+    let root = fs::open_volume(boot_services, image_handle)?;
+    let mut file = match root.open(CMDLINE_PATH) {
+        Ok(file) => file,
+        Err(Error::StatusError(StatusError::NotFound)) => return Ok(""),
+        Err(err) => return Err(err),
+    };
+    let len = file.read_to_end(buf)?;
+    core::str::from_utf8(&buf[..len]).or(Err(Error::InvalidFileData))
+}
@@ -0,0 +1,59 @@
+//! Serial console of the bootloader.
+//!
+//! The bootloader writes its output to COM1, which is usually the firmware
+//! console. The serial port keeps working after exiting the boot services,
+//! so the bootloader can report errors until it jumps to the kernel.
+
+use core::fmt::{self, Write};
+
+use serial::{SerialConfig, SerialPort};
+use ticket_mutex::TicketMutex;
+
+/// Typically, COM1's IO port address.
+const COM1_ADDRESS: u16 = 0x3f8;
+
+/// Line settings. They match the usual firmware console settings.
+const CONFIG: SerialConfig = SerialConfig::new().baud(115200);
+
+/// Serial port used by `print!`. It is `None` if it was not initialized
+/// successfully.
+static SERIAL: TicketMutex<Option<SerialPort>> = TicketMutex::new(None);
+
+/// Initializes the serial port used by `print!`.
+pub fn init() {
+    // Some virtual UARTs fail the loopback test even though they can
+    // transmit. Try again without it instead of staying silent.
+    let serial = match unsafe { SerialPort::with_config(COM1_ADDRESS, CONFIG) }
+    {
+        Err(serial::Error::LoopbackFailed) => unsafe {
+            SerialPort::with_config(COM1_ADDRESS, CONFIG.loopback_test(false))
+        },
+        ret => ret,
+    };
+    *SERIAL.lock() = serial.ok();
+}
+
+/// Writes `args` into the serial port. It is used by `print!`.
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    // Writing into a `SerialPort` cannot fail.
+    if let Some(serial) = SERIAL.lock().as_mut() {
+        serial.write_fmt(args).ok();
+    }
+}
+
+/// Prints to the serial console.
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => {
+        $crate::console::_print(format_args!($($arg)*))
+    }
+}
+
+/// Prints to the serial console, with a newline.
+#[macro_export]
+macro_rules! println {
+    ($($arg:tt)*) => {
+        $crate::print!("{}\n", format_args!($($arg)*))
+    }
+}
@@ -0,0 +1,47 @@
+//! Initial RAM disk.
+//!
+//! The initial RAM disk is a CPIO archive read from `INITRD_PATH` in the
+//! volume the bootloader was loaded from. It is optional. It is loaded into
+//! loader data pages, which the kernel does not reuse, so the kernel can
+//! read its files without any filesystem driver.
+
+use uefi::{
+    fs, AllocateType, BootServices, Error, Handle, MemoryType, StatusError,
+};
+
+use crate::pages;
+
+/// Path of the initial RAM disk in the volume the bootloader was loaded
+/// from.
+const INITRD_PATH: &str = "\\expos\\initrd.cpio";
+
+/// Reads the initial RAM disk into loader data pages and returns it. It
+/// returns `None` if there is no initial RAM disk.
+pub fn read(
+    boot_services: &BootServices,
+    image_handle: Handle,
+) -> Result<Option<&'static [u8]>, Error> {
+    let root = fs::open_volume(boot_services, image_handle)?;
+    let mut file = match root.open(INITRD_PATH) {
+        Ok(file) => file,
+        Err(Error::StatusError(StatusError::NotFound)) => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let size = file.info()?.size();
+    if size == 0 {
+        return Ok(None);
+    }
+
+    let buf = boot_services.allocate_pages(
+        AllocateType::AnyPages,
+        MemoryType::LoaderData,
+        pages(size),
+    )?;
+
+    // The pages were just allocated and they are identity mapped.
+    let buf = unsafe {
+        core::slice::from_raw_parts_mut(buf.as_u64() as *mut u8, size as usize)
+    };
+    let len = file.read_to_end(buf)?;
+    Ok(Some(&buf[..len]))
+}
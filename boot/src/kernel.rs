@@ -0,0 +1,100 @@
+//! Kernel image loading.
+//!
+//! The kernel is a static ELF64 executable for x86_64. Every loadable
+//! segment is copied into pages allocated from the firmware with the memory
+//! type `memory::KERNEL_MEMORY_TYPE`, so the kernel can tell them apart in
+//! the memory map. The segments are mapped at their virtual addresses by the
+//! initial page tables.
+
+use boot_info::{KernelSegment, SegmentFlags};
+use elf::{Elf, LoadError, ProgramHeader, PF_R, PF_W, PF_X};
+use uefi::{AllocateType, BootServices};
+
+use crate::memory::{KERNEL_MEMORY_TYPE, PAGE_SIZE};
+
+/// Kernel loading errors.
+#[derive(Debug)]
+pub enum Error {
+    /// The image is not a valid x86_64 ELF64 executable.
+    Elf(elf::Error),
+
+    /// The image has more loadable segments than the bootloader supports.
+    TooManySegments,
+
+    /// The memory of a segment could not be allocated or it is too small.
+    Load(LoadError<uefi::Error>),
+}
+
+/// Allocates the memory of the kernel segments and records them.
+struct SegmentLoader<'a, 'b> {
+    boot_services: &'a BootServices,
+
+    /// Loaded segments.
+    segments: &'b mut [KernelSegment],
+
+    /// Number of loaded segments.
+    len: usize,
+}
+
+impl elf::Loader for SegmentLoader<'_, '_> {
+    type Error = uefi::Error;
+
+    fn allocate(
+        &mut self,
+        header: &ProgramHeader,
+    ) -> Result<&mut [u8], Self::Error> {
+        // `load` checks the number of segments in advance.
+        let segment = &mut self.segments[self.len];
+
+        let size = header.load_size();
+        let phys = self.boot_services.allocate_pages(
+            AllocateType::AnyPages,
+            KERNEL_MEMORY_TYPE,
+            (size / PAGE_SIZE) as usize,
+        )?;
+
+        // The values of `SegmentFlags` match the ELF ones.
+        *segment = KernelSegment {
+            virt: header.load_start(),
+            phys: phys.as_u64(),
+            size,
+            flags: SegmentFlags::from_bits(
+                header.flags & (PF_R | PF_W | PF_X),
+            ),
+            reserved: 0,
+        };
+        self.len += 1;
+
+        // The pages were just allocated and they are identity mapped.
+        Ok(unsafe {
+            core::slice::from_raw_parts_mut(
+                phys.as_u64() as *mut u8,
+                size as usize,
+            )
+        })
+    }
+}
+
+/// Loads the kernel ELF image `image`. It fills `segments` with the loaded
+/// segments and returns a tuple with the entry point and the number of
+/// segments. This tuple has the form `(entry, len)`.
+pub fn load(
+    boot_services: &BootServices,
+    image: &[u8],
+    segments: &mut [KernelSegment],
+) -> Result<(u64, usize), Error> {
+    let elf = Elf::parse(image).map_err(Error::Elf)?;
+    if elf.program_headers().filter(ProgramHeader::is_load).count()
+        > segments.len()
+    {
+        return Err(Error::TooManySegments);
+    }
+
+    let mut loader = SegmentLoader {
+        boot_services,
+        segments,
+        len: 0,
+    };
+    let entry = elf.load(&mut loader).map_err(Error::Load)?;
+    Ok((entry, loader.len))
+}
@@ -0,0 +1,438 @@
+//! expOS bootloader.
+//!
+//! It is a UEFI application that loads the kernel ELF image from the volume
+//! it was loaded from (e.g. the EFI System Partition), exits the boot
+//! services and jumps to the kernel entry point with a `BootInfo`.
+//!
+//! The kernel is entered with the interrupts disabled, on a stack allocated
+//! for it and with page tables that identity map the physical memory and map
+//! the kernel segments at their virtual addresses.
+
+#![no_std]
+#![cfg_attr(not(test), no_main)]
+#![feature(abi_efiapi)]
+#![feature(asm)]
+
+use boot_info::{
+    BootInfo, Framebuffer, KernelEntry, KernelSegment, MemoryAttributes,
+    MemoryKind, MemoryRegion, PixelFormat, SegmentFlags, Slice,
+    ENTROPY_SEED_LEN,
+};
+use uefi::mem::MemoryAttributesTable;
+use uefi::{gop, rng, smbios, tcg2, vars, AllocateType, MemoryType};
+
+mod cmdline;
+mod console;
+mod initrd;
+mod kernel;
+mod memory;
+mod paging;
+
+#[cfg(not(test))]
+mod panic;
+
+use memory::PAGE_SIZE;
+
+/// Path of the kernel image in the volume the bootloader was loaded from.
+const KERNEL_PATH: &str = "\\expos\\kernel.elf";
+
+/// PCR the kernel image is measured into. It is the PCR used by GRUB for the
+/// kernel.
+const KERNEL_PCR: u32 = 9;
+
+/// Maximum number of loadable segments of the kernel image.
+const MAX_KERNEL_SEGMENTS: usize = 16;
+
+/// Maximum length of the kernel command line in bytes.
+const MAX_CMDLINE_LEN: usize = 4096;
+
+/// Size of the stack the kernel is entered with.
+const KERNEL_STACK_SIZE: u64 = 64 * 1024;
+
+/// Time, in microseconds, given to the UART to send the pending output before
+/// exiting the boot services.
+const SERIAL_DRAIN_US: usize = 1000;
+
+/// Boot information passed to the kernel. It is part of the bootloader
+/// image, so it is preserved as bootloader memory.
+static mut BOOT_INFO: BootInfo = BootInfo::new();
+
+/// Loaded segments of the kernel image, referenced by `BOOT_INFO`.
+static mut KERNEL_SEGMENTS: [KernelSegment; MAX_KERNEL_SEGMENTS] =
+    [KernelSegment {
+        virt: 0,
+        phys: 0,
+        size: 0,
+        flags: SegmentFlags::from_bits(0),
+        reserved: 0,
+    }; MAX_KERNEL_SEGMENTS];
+
+/// Kernel command line, referenced by `BOOT_INFO`.
+static mut CMDLINE: [u8; MAX_CMDLINE_LEN] = [0; MAX_CMDLINE_LEN];
+
+/// UEFI entry point.
+#[no_mangle]
+extern "efiapi" fn efi_main(
+    image_handle: uefi::Handle,
+    system_table_ptr: uefi::Ptr,
+) -> ! {
+    console::init();
+
+    // `efi_main` is only called once, so nobody else references the boot
+    // information.
+    let boot_info = unsafe { &mut BOOT_INFO };
+    let kernel_segments = unsafe { &mut KERNEL_SEGMENTS };
+    let cmdline_buf = unsafe { &mut CMDLINE };
+
+    // Parse UEFI's system table.
+    let system_table =
+        unsafe { uefi::SystemTable::new(system_table_ptr).unwrap() };
+    boot_info.uefi_system_table = system_table_ptr.0 as u64;
+
+    // Report the firmware.
+    if let Ok(vendor) = system_table.firmware_vendor() {
+        println!(
+            "firmware: {} ({:#x})",
+            vendor,
+            system_table.firmware_revision()
+        );
+    }
+
+    // Report the boot configuration.
+    if let Ok(runtime_services) = system_table.runtime_services() {
+        println!("boot current: {:?}", vars::boot_current(&runtime_services));
+        println!("secure boot: {:?}", vars::secure_boot(&runtime_services));
+    }
+
+    // Report the hardware inventory.
+    match parse_smbios(&system_table) {
+        Ok(smbios) => print_smbios(&smbios),
+        Err(err) => println!("smbios: not available: {}", err),
+    }
+
+    // Prefer the ACPI 2.0+ RSDP, but fall back to the ACPI 1.0 one, that
+    // only provides the RSDT.
+    let config_tables = system_table.configuration_tables().ok();
+    match config_tables.as_ref().and_then(|config_tables| {
+        config_tables
+            .acpi_rsdp20_ptr()
+            .or_else(|_| config_tables.acpi_rsdp_ptr())
+            .ok()
+    }) {
+        Some(ptr) => boot_info.acpi_rsdp = ptr.0 as u64,
+        None => println!("acpi: rsdp not available"),
+    }
+
+    let memory_attributes = config_tables
+        .as_ref()
+        .and_then(|config_tables| config_tables.memory_attributes_ptr().ok())
+        .and_then(|ptr| unsafe { MemoryAttributesTable::new(ptr).ok() });
+
+    let boot_services = system_table.boot_services().unwrap();
+
+    // The firmware watchdog resets the machine if the loader is stopped in a
+    // debugger for too long.
+    if let Err(err) = boot_services.set_watchdog_timer(0, 0) {
+        println!("watchdog: could not be disabled: {}", err);
+    }
+
+    match entropy_seed(&boot_services) {
+        Some(seed) => {
+            boot_info.entropy_seed = seed;
+            boot_info.entropy_seed_len = ENTROPY_SEED_LEN as u32;
+        }
+        None => println!("entropy seed: not available"),
+    }
+
+    match framebuffer(&boot_services) {
+        Ok(framebuffer) => boot_info.framebuffer = framebuffer,
+        Err(err) => println!("gop: framebuffer not available: {}", err),
+    }
+
+    match cmdline::read(&boot_services, image_handle, cmdline_buf) {
+        Ok(cmdline) => {
+            println!("cmdline: {:?}", cmdline);
+            boot_info.cmdline = Slice::new(cmdline.as_bytes());
+        }
+        Err(err) => println!("cmdline: not available: {}", err),
+    }
+
+    match initrd::read(&boot_services, image_handle) {
+        Ok(Some(initrd)) => {
+            println!(
+                "initrd: {:#x}, {} bytes",
+                initrd.as_ptr() as u64,
+                initrd.len()
+            );
+            boot_info.initrd = Slice::new(initrd);
+        }
+        Ok(None) => println!("initrd: not available"),
+        Err(err) => println!("initrd: not available: {}", err),
+    }
+
+    // Read the kernel image.
+    let image = read_kernel(&boot_services, image_handle).unwrap();
+
+    // Measure the kernel before the firmware hands over the platform.
+    match measure_kernel(&boot_services, image) {
+        Ok(event_log) => {
+            boot_info.tpm_event_log = event_log.location().as_u64();
+            boot_info.tpm_event_log_format = event_log.format();
+        }
+        Err(err) => println!("tpm: kernel not measured: {}", err),
+    }
+
+    let (entry, len) =
+        kernel::load(&boot_services, image, kernel_segments).unwrap();
+    let kernel_segments = &kernel_segments[..len];
+    boot_info.kernel_segments = Slice::new(kernel_segments);
+    for segment in kernel_segments {
+        println!(
+            "kernel: segment {:#x} -> {:#x}, {} bytes, flags {:#x}",
+            segment.virt,
+            segment.phys,
+            segment.size,
+            segment.flags.bits(),
+        );
+    }
+
+    // The kernel image is not needed anymore once it is loaded.
+    unsafe {
+        boot_services
+            .free_pages(
+                mm::PhysAddr::new(image.as_ptr() as u64),
+                pages(image.len() as u64),
+            )
+            .unwrap();
+    }
+
+    let stack = boot_services
+        .allocate_pages(
+            AllocateType::AnyPages,
+            MemoryType::LoaderData,
+            pages(KERNEL_STACK_SIZE),
+        )
+        .unwrap();
+    let stack_top = stack.as_u64() + KERNEL_STACK_SIZE;
+
+    // Allocate the memory regions before exiting the boot services. The
+    // memory map can grow until then, so some extra regions are allocated.
+    let (memory_map, _) = uefi::mem::get_memory_map(&boot_services).unwrap();
+    let phys_end = memory_map
+        .iter()
+        .map(|descriptor| {
+            descriptor.physical_start().as_u64()
+                + descriptor.number_of_pages() * PAGE_SIZE
+        })
+        .max()
+        .unwrap_or(0)
+        .max(
+            boot_info
+                .framebuffer()
+                .map_or(0, |framebuffer| framebuffer.base + framebuffer.size),
+        );
+    let max_regions = memory_map.len()
+        + memory_attributes
+            .as_ref()
+            .map_or(0, |table| table.iter().count())
+        + memory::REGIONS_SLACK;
+    memory_map.free(&boot_services).unwrap();
+    let regions = boot_services
+        .allocate_pages(
+            AllocateType::AnyPages,
+            MemoryType::LoaderData,
+            pages((max_regions * core::mem::size_of::<MemoryRegion>()) as u64),
+        )
+        .unwrap();
+    let regions = unsafe {
+        let regions = regions.as_u64() as *mut MemoryRegion;
+        for idx in 0..max_regions {
+            regions.add(idx).write(MemoryRegion {
+                start: 0,
+                size: 0,
+                kind: MemoryKind::RESERVED,
+                reserved: 0,
+                attributes: MemoryAttributes::from_bits(0),
+            });
+        }
+        core::slice::from_raw_parts_mut(regions, max_regions)
+    };
+
+    let pml4 =
+        unsafe { paging::init(&boot_services, phys_end, kernel_segments) }
+            .unwrap();
+
+    // Give the UART time to send the pending output before the firmware
+    // hands over the platform.
+    boot_services.stall(SERIAL_DRAIN_US).ok();
+
+    // Get the final memory map and exit UEFI boot services.
+    let (memory_map, exit_warning) =
+        uefi::mem::exit_boot_services_with_memory_map(
+            &boot_services,
+            image_handle,
+        )
+        .unwrap();
+    if let Some(warn) = exit_warning {
+        println!("exit boot services: {}", warn);
+    }
+    if let Some(warn) = memory_map.warning() {
+        println!("memory map: {}", warn);
+    }
+    let regions =
+        memory::fill_regions(&memory_map, memory_attributes.as_ref(), regions)
+            .unwrap();
+    boot_info.memory_regions = Slice::new(regions);
+
+    // Load the kernel page tables, switch to the kernel stack and jump to
+    // the kernel. The page tables identity map the code of the bootloader,
+    // so it keeps running after loading them.
+    let entry: KernelEntry = unsafe { core::mem::transmute(entry) };
+    unsafe {
+        cpu::cli();
+        asm!(
+            "mov cr3, {pml4}",
+            "mov rsp, {stack}",
+            "xor ebp, ebp",
+            "call {entry}",
+            "ud2",
+            pml4 = in(reg) pml4.as_u64(),
+            stack = in(reg) stack_top,
+            entry = in(reg) entry,
+            in("rdi") &*boot_info as *const BootInfo,
+            options(noreturn),
+        );
+    }
+}
+
+/// Returns the number of pages needed to hold `size` bytes.
+fn pages(size: u64) -> usize {
+    ((size + PAGE_SIZE - 1) / PAGE_SIZE) as usize
+}
+
+/// Reads the kernel image at `KERNEL_PATH` into loader data pages and
+/// returns it.
+fn read_kernel(
+    boot_services: &uefi::BootServices,
+    image_handle: uefi::Handle,
+) -> Result<&'static [u8], uefi::Error> {
+    let root = uefi::fs::open_volume(boot_services, image_handle)?;
+    let mut file = root.open(KERNEL_PATH)?;
+    let size = file.info()?.size();
+
+    let buf = boot_services.allocate_pages(
+        AllocateType::AnyPages,
+        MemoryType::LoaderData,
+        pages(size),
+    )?;
+
+    // The pages were just allocated and they are identity mapped.
+    let buf = unsafe {
+        core::slice::from_raw_parts_mut(buf.as_u64() as *mut u8, size as usize)
+    };
+    let len = file.read_to_end(buf)?;
+    Ok(&buf[..len])
+}
+
+/// Returns the framebuffer of the current graphics mode. Only 32-bit RGB
+/// and BGR pixels are supported.
+fn framebuffer(
+    boot_services: &uefi::BootServices,
+) -> Result<Framebuffer, uefi::Error> {
+    let gop = boot_services.locate_protocol::<gop::GraphicsOutput>()?;
+    let mode = gop.mode()?;
+    let format = match mode.pixel_format() {
+        gop::PixelFormat::Rgb => PixelFormat::RGB,
+        gop::PixelFormat::Bgr => PixelFormat::BGR,
+        _ => return Err(uefi::Error::NotFound),
+    };
+    Ok(Framebuffer {
+        base: mode.framebuffer_base(),
+        size: mode.framebuffer_size(),
+        width: mode.width(),
+        height: mode.height(),
+        stride: mode.stride(),
+        format,
+    })
+}
+
+/// Returns a seed gathered from the RNG protocol or, if it is not available,
+/// from the `rdrand` instruction.
+fn entropy_seed(
+    boot_services: &uefi::BootServices,
+) -> Option<[u8; ENTROPY_SEED_LEN]> {
+    let mut seed = [0u8; ENTROPY_SEED_LEN];
+
+    match boot_services
+        .locate_protocol::<rng::Rng>()
+        .and_then(|mut rng| rng.get_rng(&mut seed))
+    {
+        Ok(()) => return Some(seed),
+        Err(err) => println!("rng: protocol not available: {}", err),
+    }
+
+    if !cpu::rdrand_supported() {
+        return None;
+    }
+    for chunk in seed.chunks_exact_mut(8) {
+        let val = unsafe { cpu::rdrand()? };
+        chunk.copy_from_slice(&val.to_le_bytes());
+    }
+    Some(seed)
+}
+
+/// Measures the kernel image `image` into the PCR `KERNEL_PCR` and returns
+/// the location of the TPM event log.
+fn measure_kernel(
+    boot_services: &uefi::BootServices,
+    image: &[u8],
+) -> Result<tcg2::EventLog, uefi::Error> {
+    let mut tcg2 = boot_services.locate_protocol::<tcg2::Tcg2>()?;
+    if !tcg2.capability()?.tpm_present() {
+        return Err(uefi::Error::NotFound);
+    }
+
+    tcg2.hash_log_extend_event(KERNEL_PCR, tcg2::EV_IPL, image, b"expos")?;
+
+    tcg2.event_log()
+}
+
+/// Returns the SMBIOS structure table. The 64-bit entry point is preferred
+/// over the 32-bit one.
+fn parse_smbios(
+    system_table: &uefi::SystemTable,
+) -> Result<smbios::Smbios, uefi::Error> {
+    let config_tables = system_table.configuration_tables()?;
+    match config_tables.smbios3_ptr() {
+        Ok(ptr) => unsafe { smbios::Smbios::new64(ptr) },
+        Err(_) => unsafe { smbios::Smbios::new(config_tables.smbios_ptr()?) },
+    }
+}
+
+/// Prints the BIOS, system and memory devices information.
+fn print_smbios(smbios: &smbios::Smbios) {
+    let (major, minor) = smbios.version();
+    println!("smbios: version {}.{}", major, minor);
+    if let Ok(bios) = smbios.bios_info() {
+        println!(
+            "smbios: bios: {:?} {:?} {:?}",
+            bios.vendor(),
+            bios.version(),
+            bios.release_date(),
+        );
+    }
+    if let Ok(system) = smbios.system_info() {
+        println!(
+            "smbios: system: {:?} {:?}",
+            system.manufacturer(),
+            system.product_name(),
+        );
+    }
+    for device in smbios.memory_devices().flatten() {
+        println!(
+            "smbios: memory device: {:?} {:?} bytes",
+            device.device_locator(),
+            device.size(),
+        );
+    }
+}
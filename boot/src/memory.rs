@@ -0,0 +1,130 @@
+//! Memory map passed to the kernel.
+//!
+//! The UEFI memory map is converted into `MemoryRegion`s once the boot
+//! services have been exited:
+//!
+//! - The boot services memory is usable by the kernel.
+//! - The memory allocated by the bootloader (e.g. the boot information, the
+//!   initial page tables and the kernel stack) must be preserved until the
+//!   kernel does not use it anymore.
+//! - The memory of the kernel segments is allocated with the memory type
+//!   `KERNEL_MEMORY_TYPE`, from the range reserved for the OS loaders.
+//! - The runtime services regions are described by the Memory Attributes
+//!   Table, if the firmware provides it, so the kernel can map them with
+//!   the right permissions. Otherwise, only the runtime data is
+//!   marked as not executable.
+
+use boot_info::{MemoryAttributes, MemoryKind, MemoryRegion};
+use uefi::mem::{MemoryAttributesTable, MemoryDescriptor, MemoryMap};
+use uefi::{MemoryAttribute, MemoryType};
+
+/// Size of a page.
+pub const PAGE_SIZE: u64 = mm::PAGE_SIZE;
+
+/// Memory type of the pages holding the kernel segments. The memory types
+/// starting at 0x80000000 are reserved for the OS loaders.
+pub const KERNEL_MEMORY_TYPE: MemoryType = MemoryType::Unknown(0x8000_0000);
+
+/// Number of extra regions allocated for the memory map. The memory map can
+/// grow between allocating the regions and exiting the boot services.
+pub const REGIONS_SLACK: usize = 64;
+
+/// Memory map errors.
+#[derive(Debug)]
+pub enum Error {
+    /// The memory map does not fit in the regions.
+    TooManyRegions,
+}
+
+/// Fills `regions` with the memory map `memory_map`, sorted by address. The
+/// runtime services regions are replaced by the entries of
+/// `memory_attributes`, if it is not `None`. It returns the filled regions.
+pub fn fill_regions<'a>(
+    memory_map: &MemoryMap,
+    memory_attributes: Option<&MemoryAttributesTable>,
+    regions: &'a mut [MemoryRegion],
+) -> Result<&'a [MemoryRegion], Error> {
+    let descriptors = memory_map
+        .iter()
+        .filter(|descriptor| {
+            memory_attributes.is_none()
+                || !is_runtime_type(descriptor.memory_type())
+        })
+        .chain(memory_attributes.into_iter().flat_map(|table| table.iter()));
+
+    let mut len = 0;
+    for descriptor in descriptors {
+        let region = regions.get_mut(len).ok_or(Error::TooManyRegions)?;
+        *region = MemoryRegion {
+            start: descriptor.physical_start().as_u64(),
+            size: descriptor.number_of_pages() * PAGE_SIZE,
+            kind: kind(descriptor.memory_type()),
+            reserved: 0,
+            attributes: attributes(&descriptor, memory_attributes.is_some()),
+        };
+        len += 1;
+    }
+
+    let regions = &mut regions[..len];
+    regions.sort_unstable_by_key(|region| region.start);
+    Ok(regions)
+}
+
+/// Returns the kind of the memory of type `memory_type` once the kernel is
+/// entered.
+fn kind(memory_type: MemoryType) -> MemoryKind {
+    match memory_type {
+        MemoryType::BootServicesCode
+        | MemoryType::BootServicesData
+        | MemoryType::ConventionalMemory => MemoryKind::USABLE,
+        MemoryType::LoaderCode | MemoryType::LoaderData => {
+            MemoryKind::BOOTLOADER
+        }
+        MemoryType::RuntimeServicesCode => MemoryKind::RUNTIME_CODE,
+        MemoryType::RuntimeServicesData => MemoryKind::RUNTIME_DATA,
+        MemoryType::ACPIReclaimMemory => MemoryKind::ACPI_RECLAIMABLE,
+        MemoryType::ACPIMemoryNVS => MemoryKind::ACPI_NVS,
+        MemoryType::MemoryMappedIO | MemoryType::MemoryMappedIOPortSpace => {
+            MemoryKind::MMIO
+        }
+        MemoryType::UnusableMemory => MemoryKind::UNUSABLE,
+        MemoryType::PersistentMemory => MemoryKind::PERSISTENT,
+        ty if ty == KERNEL_MEMORY_TYPE => MemoryKind::KERNEL,
+        _ => MemoryKind::RESERVED,
+    }
+}
+
+/// Returns the protections that can be applied to the region described by
+/// `descriptor`. If `from_attributes_table` is `true`, the descriptor is an
+/// entry of the Memory Attributes Table.
+fn attributes(
+    descriptor: &MemoryDescriptor,
+    from_attributes_table: bool,
+) -> MemoryAttributes {
+    if !from_attributes_table || !is_runtime_type(descriptor.memory_type()) {
+        // Without the Memory Attributes Table, the runtime code is left
+        // executable and writable.
+        return match descriptor.memory_type() {
+            MemoryType::RuntimeServicesCode => MemoryAttributes::from_bits(0),
+            _ => MemoryAttributes::NO_EXECUTE,
+        };
+    }
+
+    let attribute = descriptor.attribute();
+    let mut attributes = MemoryAttributes::from_bits(0);
+    if attribute.contains(MemoryAttribute::RO) {
+        attributes = attributes | MemoryAttributes::READ_ONLY;
+    }
+    if attribute.contains(MemoryAttribute::XP) {
+        attributes = attributes | MemoryAttributes::NO_EXECUTE;
+    }
+    attributes
+}
+
+/// Returns `true` if `memory_type` is a runtime services memory type.
+fn is_runtime_type(memory_type: MemoryType) -> bool {
+    matches!(
+        memory_type,
+        MemoryType::RuntimeServicesCode | MemoryType::RuntimeServicesData
+    )
+}
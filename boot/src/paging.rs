@@ -0,0 +1,85 @@
+//! Initial page tables of the kernel.
+//!
+//! They identity map the physical memory, so the bootloader keeps running
+//! after loading them and the kernel can access the boot information, and
+//! they map the kernel segments at their virtual addresses. The page tables
+//! are allocated as loader data, so the kernel can replace them and reclaim
+//! their memory later.
+//!
+//! The permissions are not enforced yet: IA32_EFER.NXE and CR0.WP are
+//! configured by the kernel when it builds its own page tables.
+
+use boot_info::{KernelSegment, SegmentFlags};
+use mm::paging::{self, FrameAllocator, Mapper, PageSize, PageTableFlags};
+use mm::{PhysAddr, VirtAddr};
+use uefi::{AllocateType, BootServices, MemoryType};
+
+/// Size of the physical memory that is identity mapped even if it is not
+/// described by the memory map. It covers the memory-mapped devices below
+/// 4GiB (e.g. the local APIC and the IO APICs).
+const MIN_PHYS_MAP_SIZE: u64 = 0x1_0000_0000;
+
+/// Allocates the frames of the page tables from the firmware.
+struct LoaderFrames<'a>(&'a BootServices);
+
+impl FrameAllocator for LoaderFrames<'_> {
+    fn alloc_frame(&mut self) -> Option<PhysAddr> {
+        self.0
+            .allocate_pages(AllocateType::AnyPages, MemoryType::LoaderData, 1)
+            .ok()
+    }
+}
+
+/// Builds the initial page tables and returns the physical address of the
+/// PML4. The physical memory up to `phys_end` is identity mapped and
+/// `kernel_segments` are mapped at their virtual addresses.
+///
+/// # Safety
+///
+/// This function must be called before exiting the boot services, while
+/// running on the identity-mapped firmware page tables.
+pub unsafe fn init(
+    boot_services: &BootServices,
+    phys_end: u64,
+    kernel_segments: &[KernelSegment],
+) -> Result<PhysAddr, paging::Error> {
+    let mut frames = LoaderFrames(boot_services);
+    let pml4 = frames.alloc_frame().ok_or(paging::Error::NoMemory)?;
+    (pml4.as_u64() as *mut paging::PageTable).write(paging::PageTable::new());
+
+    // The firmware page tables are identity mapped.
+    let mut mapper = Mapper::new(pml4, 0);
+
+    let page_size = PageSize::Size2MiB.bytes();
+    let phys_end =
+        (phys_end.max(MIN_PHYS_MAP_SIZE) + page_size - 1) & !(page_size - 1);
+    for phys in (0..phys_end).step_by(page_size as usize) {
+        mapper.map_to(
+            VirtAddr::new(phys),
+            PhysAddr::new(phys),
+            PageSize::Size2MiB,
+            PageTableFlags::WRITABLE,
+            &mut frames,
+        )?;
+    }
+
+    for segment in kernel_segments {
+        let flags = if segment.flags.contains(SegmentFlags::WRITE) {
+            PageTableFlags::WRITABLE
+        } else {
+            PageTableFlags::from_bits(0)
+        };
+        let page_size = PageSize::Size4KiB.bytes();
+        for offset in (0..segment.size).step_by(page_size as usize) {
+            mapper.map_to(
+                VirtAddr::new(segment.virt + offset),
+                PhysAddr::new(segment.phys + offset),
+                PageSize::Size4KiB,
+                flags,
+                &mut frames,
+            )?;
+        }
+    }
+
+    Ok(pml4)
+}
@@ -0,0 +1,20 @@
+//! Panic handling.
+
+use core::panic::PanicInfo;
+
+use crate::println;
+
+/// Panic handler. The bootloader cannot recover, so it reports the panic
+/// and halts.
+#[panic_handler]
+fn panic_handler(panic_info: &PanicInfo) -> ! {
+    println!("====== PANIC ======");
+    println!("{}", panic_info);
+
+    loop {
+        unsafe {
+            cpu::cli();
+            cpu::hlt();
+        }
+    }
+}
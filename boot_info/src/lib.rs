@@ -0,0 +1,467 @@
+//! Boot information passed by the bootloader to the kernel.
+//!
+//! The bootloader and the kernel are built and updated independently, so
+//! `BootInfo` and all the types it references have a stable `#[repr(C)]`
+//! layout. The layout is identified by `VERSION`, which must be bumped on
+//! every incompatible change. The kernel must check `BootInfo::is_valid`
+//! before using any other field.
+//!
+//! All the addresses are physical. The kernel is entered with the physical
+//! memory identity mapped, so they can be dereferenced directly until the
+//! kernel replaces the page tables of the bootloader.
+
+#![no_std]
+
+use core::marker::PhantomData;
+
+/// Magic value at the start of a `BootInfo`.
+pub const MAGIC: u64 = u64::from_le_bytes(*b"EXPOSBI\0");
+
+/// Version of the layout of `BootInfo`.
+pub const VERSION: u32 = 2;
+
+/// Size of the entropy seed passed to the kernel.
+pub const ENTROPY_SEED_LEN: usize = 32;
+
+/// Signature of the kernel entry point. The bootloader calls it with the
+/// interrupts disabled, on a stack allocated for the kernel.
+pub type KernelEntry = extern "sysv64" fn(&'static BootInfo) -> !;
+
+/// Boot information.
+#[derive(Debug)]
+#[repr(C)]
+pub struct BootInfo {
+    /// It must be `MAGIC`.
+    pub magic: u64,
+
+    /// Version of the layout. It must be `VERSION`.
+    pub version: u32,
+
+    /// Size of the structure in bytes.
+    pub size: u32,
+
+    /// Memory map, sorted by address.
+    pub memory_regions: Slice<MemoryRegion>,
+
+    /// Loaded segments of the kernel image.
+    pub kernel_segments: Slice<KernelSegment>,
+
+    /// Address of the ACPI RSDP. It is zero if the firmware does not provide
+    /// the ACPI tables.
+    pub acpi_rsdp: u64,
+
+    /// Address of the UEFI system table.
+    pub uefi_system_table: u64,
+
+    /// Framebuffer set up by the bootloader. Its base is zero if there is no
+    /// framebuffer.
+    pub framebuffer: Framebuffer,
+
+    /// Kernel command line, encoded as UTF-8.
+    pub cmdline: Slice<u8>,
+
+    /// Initial RAM disk, a CPIO archive loaded by the bootloader. It is
+    /// empty if there is no initial RAM disk.
+    pub initrd: Slice<u8>,
+
+    /// Seed for the kernel random number generators. It is only valid if
+    /// `entropy_seed_len` is `ENTROPY_SEED_LEN`.
+    pub entropy_seed: [u8; ENTROPY_SEED_LEN],
+
+    /// Number of valid bytes of `entropy_seed`.
+    pub entropy_seed_len: u32,
+
+    /// Format of the TPM event log. It is only valid if `tpm_event_log` is
+    /// not zero.
+    pub tpm_event_log_format: u32,
+
+    /// Address of the TPM event log. It is zero if there is no TPM 2.0 or
+    /// the kernel was not measured.
+    pub tpm_event_log: u64,
+}
+
+impl BootInfo {
+    /// Returns a `BootInfo` with the current magic, version and size, and
+    /// the rest of the fields empty.
+    pub const fn new() -> Self {
+        BootInfo {
+            magic: MAGIC,
+            version: VERSION,
+            size: core::mem::size_of::<BootInfo>() as u32,
+            memory_regions: Slice::empty(),
+            kernel_segments: Slice::empty(),
+            acpi_rsdp: 0,
+            uefi_system_table: 0,
+            framebuffer: Framebuffer::empty(),
+            cmdline: Slice::empty(),
+            initrd: Slice::empty(),
+            entropy_seed: [0; ENTROPY_SEED_LEN],
+            entropy_seed_len: 0,
+            tpm_event_log_format: 0,
+            tpm_event_log: 0,
+        }
+    }
+
+    /// Returns `true` if the magic, version and size of the `BootInfo`
+    /// match the ones of this crate.
+    pub fn is_valid(&self) -> bool {
+        self.magic == MAGIC
+            && self.version == VERSION
+            && self.size as usize == core::mem::size_of::<BootInfo>()
+    }
+
+    /// Returns the entropy seed, if any.
+    pub fn entropy_seed(&self) -> Option<&[u8; ENTROPY_SEED_LEN]> {
+        if self.entropy_seed_len as usize == ENTROPY_SEED_LEN {
+            Some(&self.entropy_seed)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the framebuffer, if any.
+    pub fn framebuffer(&self) -> Option<&Framebuffer> {
+        if self.framebuffer.base != 0 {
+            Some(&self.framebuffer)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for BootInfo {
+    fn default() -> Self {
+        BootInfo::new()
+    }
+}
+
+/// Array of `T` referenced by its physical address.
+#[derive(Debug)]
+#[repr(C)]
+pub struct Slice<T> {
+    /// Address of the first element.
+    pub addr: u64,
+
+    /// Number of elements.
+    pub len: u64,
+
+    _elem: PhantomData<T>,
+}
+
+impl<T> Slice<T> {
+    /// Returns an empty `Slice`.
+    pub const fn empty() -> Self {
+        Slice {
+            addr: 0,
+            len: 0,
+            _elem: PhantomData,
+        }
+    }
+
+    /// Returns a `Slice` that references `slice`.
+    pub fn new(slice: &[T]) -> Self {
+        Slice {
+            addr: slice.as_ptr() as u64,
+            len: slice.len() as u64,
+            _elem: PhantomData,
+        }
+    }
+
+    /// Returns the number of elements.
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Returns `true` if there are no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the referenced elements.
+    ///
+    /// # Safety
+    ///
+    /// The `Slice` must reference valid, identity-mapped elements of type
+    /// `T`, which are not modified while the returned slice is in use.
+    pub unsafe fn as_slice(&self) -> &[T] {
+        if self.len == 0 {
+            return &[];
+        }
+        core::slice::from_raw_parts(self.addr as *const T, self.len as usize)
+    }
+}
+
+/// Describes a region of physical memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct MemoryRegion {
+    /// Address of the first byte of the region.
+    pub start: u64,
+
+    /// Size of the region in bytes.
+    pub size: u64,
+
+    /// Kind of memory.
+    pub kind: MemoryKind,
+
+    /// Reserved. It must be zero.
+    pub reserved: u32,
+
+    /// Protections that can be applied to the region.
+    pub attributes: MemoryAttributes,
+}
+
+impl MemoryRegion {
+    /// Returns the address right after the end of the region.
+    pub fn end(&self) -> u64 {
+        self.start + self.size
+    }
+}
+
+/// Kind of a memory region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct MemoryKind(u32);
+
+impl MemoryKind {
+    /// Memory that is free once the kernel is entered.
+    pub const USABLE: MemoryKind = MemoryKind(0);
+
+    /// Memory that must not be used.
+    pub const RESERVED: MemoryKind = MemoryKind(1);
+
+    /// Memory holding ACPI tables. It can be reused after parsing them.
+    pub const ACPI_RECLAIMABLE: MemoryKind = MemoryKind(2);
+
+    /// Memory reserved for the firmware, which must be preserved in the
+    /// ACPI sleep states.
+    pub const ACPI_NVS: MemoryKind = MemoryKind(3);
+
+    /// Memory-mapped IO.
+    pub const MMIO: MemoryKind = MemoryKind(4);
+
+    /// Code of the UEFI runtime services.
+    pub const RUNTIME_CODE: MemoryKind = MemoryKind(5);
+
+    /// Data of the UEFI runtime services.
+    pub const RUNTIME_DATA: MemoryKind = MemoryKind(6);
+
+    /// Memory allocated by the bootloader for the kernel (e.g. the boot
+    /// information, the initial page tables and the initial stack).
+    pub const BOOTLOADER: MemoryKind = MemoryKind(7);
+
+    /// Memory holding the kernel image.
+    pub const KERNEL: MemoryKind = MemoryKind(8);
+
+    /// Memory with errors.
+    pub const UNUSABLE: MemoryKind = MemoryKind(9);
+
+    /// Persistent memory.
+    pub const PERSISTENT: MemoryKind = MemoryKind(10);
+
+    /// Returns a `MemoryKind` from its raw representation.
+    pub const fn from_raw(raw: u32) -> Self {
+        MemoryKind(raw)
+    }
+
+    /// Returns the raw representation of the `MemoryKind`.
+    pub const fn raw(&self) -> u32 {
+        self.0
+    }
+}
+
+/// Protections that can be applied to a memory region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(transparent)]
+pub struct MemoryAttributes(u64);
+
+impl MemoryAttributes {
+    /// The region can be mapped read-only.
+    pub const READ_ONLY: MemoryAttributes = MemoryAttributes(1 << 0);
+
+    /// The region can be mapped not executable.
+    pub const NO_EXECUTE: MemoryAttributes = MemoryAttributes(1 << 1);
+
+    /// Returns a `MemoryAttributes` from its raw representation.
+    pub const fn from_bits(bits: u64) -> Self {
+        MemoryAttributes(bits)
+    }
+
+    /// Returns the raw representation of the `MemoryAttributes`.
+    pub const fn bits(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns `true` if all the attributes in `other` are set.
+    pub const fn contains(&self, other: MemoryAttributes) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl core::ops::BitOr for MemoryAttributes {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        MemoryAttributes(self.0 | rhs.0)
+    }
+}
+
+/// Describes a loaded segment of the kernel image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct KernelSegment {
+    /// Virtual address of the segment. It is aligned to 4KiB.
+    pub virt: u64,
+
+    /// Physical address of the segment. It is aligned to 4KiB.
+    pub phys: u64,
+
+    /// Size of the segment in bytes.
+    pub size: u64,
+
+    /// Permissions of the segment.
+    pub flags: SegmentFlags,
+
+    /// Reserved. It must be zero.
+    pub reserved: u32,
+}
+
+/// Permissions of a kernel segment. The values match the ELF `p_flags`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(transparent)]
+pub struct SegmentFlags(u32);
+
+impl SegmentFlags {
+    /// The segment is executable.
+    pub const EXECUTE: SegmentFlags = SegmentFlags(1 << 0);
+
+    /// The segment is writable.
+    pub const WRITE: SegmentFlags = SegmentFlags(1 << 1);
+
+    /// The segment is readable.
+    pub const READ: SegmentFlags = SegmentFlags(1 << 2);
+
+    /// Returns a `SegmentFlags` from its raw representation.
+    pub const fn from_bits(bits: u32) -> Self {
+        SegmentFlags(bits)
+    }
+
+    /// Returns the raw representation of the `SegmentFlags`.
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Returns `true` if all the flags in `other` are set.
+    pub const fn contains(&self, other: SegmentFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+/// Describes a linear framebuffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct Framebuffer {
+    /// Address of the framebuffer.
+    pub base: u64,
+
+    /// Size of the framebuffer in bytes.
+    pub size: u64,
+
+    /// Horizontal resolution in pixels.
+    pub width: u32,
+
+    /// Vertical resolution in pixels.
+    pub height: u32,
+
+    /// Number of pixels per scan line. It can be bigger than `width`.
+    pub stride: u32,
+
+    /// Layout of the pixels. Every pixel takes 4 bytes.
+    pub format: PixelFormat,
+}
+
+impl Framebuffer {
+    /// Returns a `Framebuffer` that describes no framebuffer.
+    pub const fn empty() -> Self {
+        Framebuffer {
+            base: 0,
+            size: 0,
+            width: 0,
+            height: 0,
+            stride: 0,
+            format: PixelFormat::RGB,
+        }
+    }
+}
+
+/// Layout of the pixels of a framebuffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct PixelFormat(u32);
+
+impl PixelFormat {
+    /// Byte 0 is red, byte 1 is green and byte 2 is blue.
+    pub const RGB: PixelFormat = PixelFormat(0);
+
+    /// Byte 0 is blue, byte 1 is green and byte 2 is red.
+    pub const BGR: PixelFormat = PixelFormat(1);
+
+    /// Returns a `PixelFormat` from its raw representation.
+    pub const fn from_raw(raw: u32) -> Self {
+        PixelFormat(raw)
+    }
+
+    /// Returns the raw representation of the `PixelFormat`.
+    pub const fn raw(&self) -> u32 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid() {
+        let mut boot_info = BootInfo::new();
+        assert!(boot_info.is_valid());
+
+        boot_info.version = VERSION + 1;
+        assert!(!boot_info.is_valid());
+
+        boot_info.version = VERSION;
+        boot_info.magic = 0;
+        assert!(!boot_info.is_valid());
+    }
+
+    #[test]
+    fn test_slice() {
+        let regions = [MemoryRegion {
+            start: 0x1000,
+            size: 0x2000,
+            kind: MemoryKind::USABLE,
+            reserved: 0,
+            attributes: MemoryAttributes::default(),
+        }];
+        let slice = Slice::new(&regions);
+        assert_eq!(slice.len(), 1);
+        assert_eq!(unsafe { slice.as_slice() }, &regions);
+        assert_eq!(unsafe { slice.as_slice() }[0].end(), 0x3000);
+
+        let empty = Slice::<MemoryRegion>::empty();
+        assert!(empty.is_empty());
+        assert!(unsafe { empty.as_slice() }.is_empty());
+    }
+
+    #[test]
+    fn test_optional_fields() {
+        let mut boot_info = BootInfo::new();
+        assert_eq!(boot_info.entropy_seed(), None);
+        assert_eq!(boot_info.framebuffer(), None);
+
+        boot_info.entropy_seed_len = ENTROPY_SEED_LEN as u32;
+        boot_info.framebuffer.base = 0x8000_0000;
+        assert!(boot_info.entropy_seed().is_some());
+        assert!(boot_info.framebuffer().is_some());
+    }
+}
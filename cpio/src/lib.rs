@@ -0,0 +1,332 @@
+//! CPIO archive parsing.
+//!
+//! Only the portable ASCII format ("newc", as written by `cpio -H newc`) is
+//! supported, with or without checksums. It is the format of the Linux
+//! initramfs. The archive is parsed in place, so the names and the data of
+//! the entries borrow from it.
+//!
+//! Reference:
+//! - [cpio(5)](https://man.freebsd.org/cgi/man.cgi?query=cpio&sektion=5)
+
+#![no_std]
+
+/// Magic number of the newc headers.
+const MAGIC_NEWC: &[u8; 6] = b"070701";
+
+/// Magic number of the newc headers with checksums.
+const MAGIC_NEWC_CRC: &[u8; 6] = b"070702";
+
+/// Size of a header.
+const HEADER_SIZE: usize = 110;
+
+/// Length of a hexadecimal field of the header.
+const FIELD_LEN: usize = 8;
+
+/// Offset of the mode field.
+const FIELD_MODE: usize = 14;
+
+/// Offset of the file size field.
+const FIELD_FILESIZE: usize = 54;
+
+/// Offset of the name size field.
+const FIELD_NAMESIZE: usize = 94;
+
+/// Name of the entry that marks the end of the archive.
+const TRAILER: &str = "TRAILER!!!";
+
+/// Alignment of the names and the data.
+const ALIGN: usize = 4;
+
+/// Mask of the file type in the mode.
+const S_IFMT: u32 = 0o170_000;
+
+/// File type of the regular files.
+const S_IFREG: u32 = 0o100_000;
+
+/// File type of the directories.
+const S_IFDIR: u32 = 0o040_000;
+
+/// CPIO errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The header at the given offset does not start with a supported magic
+    /// number.
+    InvalidMagic(usize),
+
+    /// A field of the header at the given offset is not hexadecimal.
+    InvalidHeader(usize),
+
+    /// The name of the entry at the given offset is not a valid
+    /// NUL-terminated UTF-8 string.
+    InvalidName(usize),
+
+    /// The archive ends before the trailer.
+    Truncated,
+}
+
+/// Represents a CPIO archive.
+#[derive(Debug, Clone, Copy)]
+pub struct Archive<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Archive<'a> {
+    /// Returns an `Archive` stored in `data`. The headers are validated
+    /// while iterating the entries.
+    pub const fn new(data: &'a [u8]) -> Self {
+        Archive { data }
+    }
+
+    /// Returns the raw archive.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Returns an iterator over the entries of the archive. The iteration
+    /// ends at the trailer or after the first error.
+    pub fn entries(&self) -> Entries<'a> {
+        Entries {
+            data: self.data,
+            offset: 0,
+            done: false,
+        }
+    }
+
+    /// Returns the entry at `path`. The leading `/` and `./` of `path` and
+    /// of the names in the archive are ignored.
+    pub fn get(&self, path: &str) -> Result<Option<Entry<'a>>, Error> {
+        let path = normalize(path);
+        for entry in self.entries() {
+            let entry = entry?;
+            if normalize(entry.name) == path {
+                return Ok(Some(entry));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Represents an entry of a CPIO archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry<'a> {
+    /// Path of the entry, as stored in the archive.
+    pub name: &'a str,
+
+    /// File type and permissions.
+    pub mode: u32,
+
+    /// Contents of the file. The target of the symbolic links is stored as
+    /// their contents.
+    pub data: &'a [u8],
+}
+
+impl Entry<'_> {
+    /// Returns `true` if the entry is a regular file.
+    pub fn is_file(&self) -> bool {
+        self.mode & S_IFMT == S_IFREG
+    }
+
+    /// Returns `true` if the entry is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+}
+
+/// Iterator over the entries of a CPIO archive.
+pub struct Entries<'a> {
+    data: &'a [u8],
+    offset: usize,
+    done: bool,
+}
+
+impl<'a> Entries<'a> {
+    /// Parses the entry at the current offset. It returns `None` if it is
+    /// the trailer.
+    fn parse(&mut self) -> Result<Option<Entry<'a>>, Error> {
+        let start = self.offset;
+        let header = self
+            .data
+            .get(start..start + HEADER_SIZE)
+            .ok_or(Error::Truncated)?;
+        if &header[..MAGIC_NEWC.len()] != MAGIC_NEWC
+            && &header[..MAGIC_NEWC_CRC.len()] != MAGIC_NEWC_CRC
+        {
+            return Err(Error::InvalidMagic(start));
+        }
+        let field = |offset| {
+            parse_hex(&header[offset..offset + FIELD_LEN])
+                .ok_or(Error::InvalidHeader(start))
+        };
+        let mode = field(FIELD_MODE)?;
+        let filesize = field(FIELD_FILESIZE)? as usize;
+        let namesize = field(FIELD_NAMESIZE)? as usize;
+
+        let name_start = start + HEADER_SIZE;
+        let name = name_start
+            .checked_add(namesize)
+            .and_then(|name_end| self.data.get(name_start..name_end))
+            .ok_or(Error::Truncated)?;
+        let name = match name.split_last() {
+            Some((0, name)) => core::str::from_utf8(name)
+                .or(Err(Error::InvalidName(start)))?,
+            _ => return Err(Error::InvalidName(start)),
+        };
+
+        let data_start = align_up(name_start + namesize);
+        let data = data_start
+            .checked_add(filesize)
+            .and_then(|data_end| self.data.get(data_start..data_end))
+            .ok_or(Error::Truncated)?;
+        if name == TRAILER {
+            return Ok(None);
+        }
+
+        self.offset = align_up(data_start + filesize);
+        Ok(Some(Entry { name, mode, data }))
+    }
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = Result<Entry<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.parse() {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Returns `path` without the leading `/` and `./`.
+fn normalize(mut path: &str) -> &str {
+    loop {
+        if let Some(rest) = path.strip_prefix('/') {
+            path = rest;
+        } else if let Some(rest) = path.strip_prefix("./") {
+            path = rest;
+        } else {
+            return path;
+        }
+    }
+}
+
+/// Rounds `offset` up to `ALIGN`.
+fn align_up(offset: usize) -> usize {
+    (offset + ALIGN - 1) & !(ALIGN - 1)
+}
+
+/// Parses the hexadecimal number `digits`.
+fn parse_hex(digits: &[u8]) -> Option<u32> {
+    let digits = core::str::from_utf8(digits).ok()?;
+    if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(digits, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+
+    /// Appends an entry to `archive`.
+    fn push(archive: &mut Vec<u8>, name: &str, mode: u32, data: &[u8]) {
+        let header = std::format!(
+            "070701{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}\
+             {:08x}{:08x}{:08x}{:08x}",
+            1,
+            mode,
+            0,
+            0,
+            1,
+            0,
+            data.len(),
+            0,
+            0,
+            0,
+            0,
+            name.len() + 1,
+            0,
+        );
+        archive.extend_from_slice(header.as_bytes());
+        archive.extend_from_slice(name.as_bytes());
+        archive.push(0);
+        archive.resize(align_up(archive.len()), 0);
+        archive.extend_from_slice(data);
+        archive.resize(align_up(archive.len()), 0);
+    }
+
+    /// Returns an archive with a directory, two files and the trailer.
+    fn test_archive() -> Vec<u8> {
+        let mut archive = Vec::new();
+        push(&mut archive, ".", S_IFDIR | 0o755, &[]);
+        push(&mut archive, "etc", S_IFDIR | 0o755, &[]);
+        push(&mut archive, "etc/motd", S_IFREG | 0o644, b"hello\n");
+        push(&mut archive, "./init", S_IFREG | 0o755, b"\x7fELF");
+        push(&mut archive, TRAILER, 0, &[]);
+        archive
+    }
+
+    #[test]
+    fn test_entries() {
+        let archive = test_archive();
+        let archive = Archive::new(&archive);
+        let entries: Vec<_> =
+            archive.entries().map(|entry| entry.unwrap()).collect();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[1].name, "etc");
+        assert!(entries[1].is_dir());
+        assert_eq!(entries[2].name, "etc/motd");
+        assert!(entries[2].is_file());
+        assert_eq!(entries[2].data, b"hello\n");
+        assert_eq!(entries[3].data, b"\x7fELF");
+    }
+
+    #[test]
+    fn test_get() {
+        let archive = test_archive();
+        let archive = Archive::new(&archive);
+        assert_eq!(
+            archive.get("/etc/motd").unwrap().unwrap().data,
+            b"hello\n"
+        );
+        assert_eq!(archive.get("init").unwrap().unwrap().data, b"\x7fELF");
+        assert_eq!(archive.get("/missing"), Ok(None));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut archive = test_archive();
+        archive.truncate(archive.len() - 4);
+        let last = Archive::new(&archive).entries().last().unwrap();
+        assert_eq!(last, Err(Error::Truncated));
+
+        let mut archive = test_archive();
+        archive[0] = b'1';
+        let last = Archive::new(&archive).entries().last().unwrap();
+        assert_eq!(last, Err(Error::InvalidMagic(0)));
+
+        let mut archive = test_archive();
+        archive[FIELD_MODE] = b'x';
+        let last = Archive::new(&archive).entries().last().unwrap();
+        assert_eq!(last, Err(Error::InvalidHeader(0)));
+
+        let mut archive = test_archive();
+        archive[HEADER_SIZE + 1] = b'x';
+        let last = Archive::new(&archive).entries().last().unwrap();
+        assert_eq!(last, Err(Error::InvalidName(0)));
+    }
+}
@@ -0,0 +1,117 @@
+//! Processor features reported by the `cpuid` instruction.
+
+use crate::cpuid;
+
+/// CPUID leaf that reports the highest basic leaf.
+const LEAF_BASIC_MAX: u32 = 0;
+
+/// CPUID leaf with the feature information.
+const LEAF_FEATURES: u32 = 1;
+
+/// CPUID leaf that reports the highest extended leaf.
+const LEAF_EXT_MAX: u32 = 0x8000_0000;
+
+/// CPUID leaf with the extended feature information.
+const LEAF_EXT_FEATURES: u32 = 0x8000_0001;
+
+/// CPUID leaf with the advanced power management information.
+const LEAF_EXT_POWER: u32 = 0x8000_0007;
+
+/// Represents the features supported by the processor. Unavailable CPUID
+/// leaves are considered to report no features.
+#[derive(Debug, Clone, Copy)]
+pub struct CpuFeatures {
+    /// ECX returned by the feature information leaf.
+    features_ecx: u32,
+
+    /// EDX returned by the feature information leaf.
+    features_edx: u32,
+
+    /// EDX returned by the extended feature information leaf.
+    ext_features_edx: u32,
+
+    /// EDX returned by the advanced power management leaf.
+    ext_power_edx: u32,
+}
+
+impl CpuFeatures {
+    /// Returns the features supported by the current processor.
+    pub fn detect() -> Self {
+        let leaf = |leaf, max| {
+            if leaf <= max {
+                cpuid(leaf, 0)
+            } else {
+                (0, 0, 0, 0)
+            }
+        };
+
+        let (basic_max, _, _, _) = cpuid(LEAF_BASIC_MAX, 0);
+        let (_, _, features_ecx, features_edx) =
+            leaf(LEAF_FEATURES, basic_max);
+
+        let (ext_max, _, _, _) = cpuid(LEAF_EXT_MAX, 0);
+        let (_, _, _, ext_features_edx) = leaf(LEAF_EXT_FEATURES, ext_max);
+        let (_, _, _, ext_power_edx) = leaf(LEAF_EXT_POWER, ext_max);
+
+        CpuFeatures {
+            features_ecx,
+            features_edx,
+            ext_features_edx,
+            ext_power_edx,
+        }
+    }
+
+    /// Returns `true` if the processor has a Time Stamp Counter.
+    pub fn tsc(&self) -> bool {
+        self.features_edx & (1 << 4) != 0
+    }
+
+    /// Returns `true` if the processor has an on-chip APIC.
+    pub fn apic(&self) -> bool {
+        self.features_edx & (1 << 9) != 0
+    }
+
+    /// Returns `true` if the processor supports the x2APIC mode.
+    pub fn x2apic(&self) -> bool {
+        self.features_ecx & (1 << 21) != 0
+    }
+
+    /// Returns `true` if the local APIC timer supports the TSC-deadline
+    /// mode.
+    pub fn tsc_deadline(&self) -> bool {
+        self.features_ecx & (1 << 24) != 0
+    }
+
+    /// Returns `true` if the processor supports the `rdrand` instruction.
+    pub fn rdrand(&self) -> bool {
+        self.features_ecx & (1 << 30) != 0
+    }
+
+    /// Returns `true` if the processor supports AVX. The OS must also
+    /// enable it via XSETBV before it can be used.
+    pub fn avx(&self) -> bool {
+        self.features_ecx & (1 << 28) != 0
+    }
+
+    /// Returns `true` if the processor supports the `rdtscp` instruction.
+    pub fn rdtscp(&self) -> bool {
+        self.ext_features_edx & (1 << 27) != 0
+    }
+
+    /// Returns `true` if the processor supports the execute-disable bit in
+    /// the page tables (NX).
+    pub fn nx(&self) -> bool {
+        self.ext_features_edx & (1 << 20) != 0
+    }
+
+    /// Returns `true` if the processor supports 1-GiB pages.
+    pub fn pages_1gb(&self) -> bool {
+        self.ext_features_edx & (1 << 26) != 0
+    }
+
+    /// Returns `true` if the TSC runs at a constant rate in all the ACPI
+    /// P-, C- and T-states.
+    pub fn invariant_tsc(&self) -> bool {
+        self.ext_power_edx & (1 << 8) != 0
+    }
+}
@@ -3,6 +3,61 @@
 #![no_std]
 #![feature(asm)]
 
+#[cfg(target_arch = "x86_64")]
+mod features;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub mod mtrr;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub mod pat;
+#[cfg(target_arch = "x86_64")]
+mod registers;
+
+#[cfg(target_arch = "x86_64")]
+pub use features::CpuFeatures;
+#[cfg(target_arch = "x86_64")]
+pub use registers::Registers;
+
+/// Memory types used by the MTRRs and the PAT to describe the caching policy
+/// of a memory region.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[repr(u8)]
+pub enum CacheType {
+    /// Uncacheable (UC).
+    Uncacheable = 0,
+
+    /// Write Combining (WC).
+    WriteCombining = 1,
+
+    /// Write-through (WT).
+    WriteThrough = 4,
+
+    /// Write-protected (WP).
+    WriteProtected = 5,
+
+    /// Writeback (WB).
+    WriteBack = 6,
+
+    /// Uncached (UC-). It is only valid in the PAT. It can be overridden by
+    /// WC in the MTRRs.
+    UncachedMinus = 7,
+}
+
+impl CacheType {
+    /// Returns the `CacheType` corresponding to the encoding `bits`, or `None`
+    /// if it is reserved.
+    pub fn from_bits(bits: u8) -> Option<CacheType> {
+        match bits {
+            0 => Some(CacheType::Uncacheable),
+            1 => Some(CacheType::WriteCombining),
+            4 => Some(CacheType::WriteThrough),
+            5 => Some(CacheType::WriteProtected),
+            6 => Some(CacheType::WriteBack),
+            7 => Some(CacheType::UncachedMinus),
+            _ => None,
+        }
+    }
+}
+
 /// Reads an `u8` from the specified IO port address.
 ///
 /// # Safety
@@ -39,6 +94,154 @@ pub unsafe fn out8(port_addr: u16, val: u8) {
     );
 }
 
+/// Reads an `u16` from the specified IO port address.
+///
+/// # Safety
+///
+/// This function executes an `in` instruction passing the provided
+/// `port_addr`. Thus, it is considered unsafe.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[inline]
+pub unsafe fn in16(port_addr: u16) -> u16 {
+    let retval: u16;
+
+    asm!(
+        "in ax, dx",
+        out("ax") retval,
+        in("dx") port_addr,
+    );
+
+    retval
+}
+
+/// Writes an `u16` to the specified IO port address.
+///
+/// # Safety
+///
+/// This function executes an `out` instruction passing the provided
+/// `port_addr`. Thus, it is considered unsafe.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[inline]
+pub unsafe fn out16(port_addr: u16, val: u16) {
+    asm!(
+        "out dx, ax",
+        in("dx") port_addr,
+        in("ax") val,
+    );
+}
+
+/// Reads an `u32` from the specified IO port address.
+///
+/// # Safety
+///
+/// This function executes an `in` instruction passing the provided
+/// `port_addr`. Thus, it is considered unsafe.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[inline]
+pub unsafe fn in32(port_addr: u16) -> u32 {
+    let retval: u32;
+
+    asm!(
+        "in eax, dx",
+        out("eax") retval,
+        in("dx") port_addr,
+    );
+
+    retval
+}
+
+/// Writes an `u32` to the specified IO port address.
+///
+/// # Safety
+///
+/// This function executes an `out` instruction passing the provided
+/// `port_addr`. Thus, it is considered unsafe.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[inline]
+pub unsafe fn out32(port_addr: u16, val: u32) {
+    asm!(
+        "out dx, eax",
+        in("dx") port_addr,
+        in("eax") val,
+    );
+}
+
+/// Reads `buf.len()` bytes from the specified IO port address into `buf`.
+///
+/// # Safety
+///
+/// This function executes a `rep insb` instruction passing the provided
+/// `port_addr`. Thus, it is considered unsafe.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn insb(port_addr: u16, buf: &mut [u8]) {
+    // The direction flag is clear as required by the ABI, so `rdi` is
+    // incremented.
+    asm!(
+        "rep insb",
+        in("dx") port_addr,
+        inout("rdi") buf.as_mut_ptr() => _,
+        inout("rcx") buf.len() => _,
+    );
+}
+
+/// Writes the bytes of `buf` to the specified IO port address.
+///
+/// # Safety
+///
+/// This function executes a `rep outsb` instruction passing the provided
+/// `port_addr`. Thus, it is considered unsafe.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn outsb(port_addr: u16, buf: &[u8]) {
+    // The direction flag is clear as required by the ABI, so `rsi` is
+    // incremented.
+    asm!(
+        "rep outsb",
+        in("dx") port_addr,
+        inout("rsi") buf.as_ptr() => _,
+        inout("rcx") buf.len() => _,
+    );
+}
+
+/// Reads `buf.len()` words from the specified IO port address into `buf`.
+///
+/// # Safety
+///
+/// This function executes a `rep insw` instruction passing the provided
+/// `port_addr`. Thus, it is considered unsafe.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn insw(port_addr: u16, buf: &mut [u16]) {
+    // The direction flag is clear as required by the ABI, so `rdi` is
+    // incremented.
+    asm!(
+        "rep insw",
+        in("dx") port_addr,
+        inout("rdi") buf.as_mut_ptr() => _,
+        inout("rcx") buf.len() => _,
+    );
+}
+
+/// Writes the words of `buf` to the specified IO port address.
+///
+/// # Safety
+///
+/// This function executes a `rep outsw` instruction passing the provided
+/// `port_addr`. Thus, it is considered unsafe.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn outsw(port_addr: u16, buf: &[u16]) {
+    // The direction flag is clear as required by the ABI, so `rsi` is
+    // incremented.
+    asm!(
+        "rep outsw",
+        in("dx") port_addr,
+        inout("rsi") buf.as_ptr() => _,
+        inout("rcx") buf.len() => _,
+    );
+}
+
 /// Stops instruction execution and places the processor in a HALT state.
 ///
 /// # Safety
@@ -49,3 +252,535 @@ pub unsafe fn out8(port_addr: u16, val: u8) {
 pub unsafe fn hlt() {
     asm!("hlt");
 }
+
+/// Operand of the `lgdt` and `lidt` instructions. It describes the location
+/// and size of a descriptor table.
+#[repr(C, packed)]
+pub struct DescriptorTablePointer {
+    /// Size of the table in bytes minus one.
+    limit: u16,
+
+    /// Linear address of the table.
+    base: u64,
+}
+
+impl DescriptorTablePointer {
+    /// Returns a `DescriptorTablePointer` to the descriptor table at the
+    /// linear address `base` with size `size` in bytes.
+    pub fn new(base: u64, size: usize) -> Self {
+        DescriptorTablePointer {
+            limit: (size - 1) as u16,
+            base,
+        }
+    }
+}
+
+/// Loads the Global Descriptor Table described by `gdtr`.
+///
+/// # Safety
+///
+/// The table must stay valid while it is loaded and the segment registers
+/// must be reloaded with valid selectors afterwards. Thus, this function is
+/// considered unsafe.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn lgdt(gdtr: &DescriptorTablePointer) {
+    asm!("lgdt [{}]", in(reg) gdtr, options(readonly, nostack));
+}
+
+/// Loads the Interrupt Descriptor Table described by `idtr`.
+///
+/// # Safety
+///
+/// The table must stay valid while it is loaded and its handlers are called
+/// on every exception and interrupt. Thus, this function is considered
+/// unsafe.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn lidt(idtr: &DescriptorTablePointer) {
+    asm!("lidt [{}]", in(reg) idtr, options(readonly, nostack));
+}
+
+/// Loads the Task Register with the TSS selector `selector`.
+///
+/// # Safety
+///
+/// The selector must reference an available TSS descriptor in the GDT. Thus,
+/// this function is considered unsafe.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn ltr(selector: u16) {
+    asm!("ltr {:x}", in(reg) selector, options(nomem, nostack));
+}
+
+/// Loads the code segment register with `selector`. It is done with a far
+/// return to the next instruction.
+///
+/// # Safety
+///
+/// The selector must reference a 64-bit code segment descriptor in the GDT.
+/// Thus, this function is considered unsafe.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn load_cs(selector: u16) {
+    asm!(
+        "push {sel}",
+        "lea {tmp}, [rip + 2f]",
+        "push {tmp}",
+        "retfq",
+        "2:",
+        sel = in(reg) u64::from(selector),
+        tmp = lateout(reg) _,
+    );
+}
+
+/// Loads the DS, ES and SS segment registers with `selector`. FS and GS are
+/// not modified, given that loading them would clear their base addresses.
+///
+/// # Safety
+///
+/// The selector must reference a data segment descriptor in the GDT. Thus,
+/// this function is considered unsafe.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn load_data_segments(selector: u16) {
+    asm!(
+        "mov ds, {0:x}",
+        "mov es, {0:x}",
+        "mov ss, {0:x}",
+        in(reg) selector,
+        options(nostack, preserves_flags),
+    );
+}
+
+/// Interrupt Enable Flag (IF) bit of RFLAGS.
+#[cfg(target_arch = "x86_64")]
+const RFLAGS_IF: u64 = 1 << 9;
+
+/// Clears the interrupt flag, so maskable interrupts are ignored.
+///
+/// # Safety
+///
+/// This function executes a `cli` instruction. Thus, it is considered unsafe.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn cli() {
+    // Without `nomem`, the compiler does not move memory accesses across the
+    // instruction, so they stay inside or outside the critical section.
+    asm!("cli", options(nostack));
+}
+
+/// Sets the interrupt flag, so maskable interrupts are handled.
+///
+/// # Safety
+///
+/// This function executes a `sti` instruction. The interrupt handlers must be
+/// ready to run. Thus, it is considered unsafe.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn sti() {
+    // It is a compiler barrier, like `cli`.
+    asm!("sti", options(nostack));
+}
+
+/// Exchanges the GS base with the value of the IA32_KERNEL_GS_BASE MSR.
+///
+/// # Safety
+///
+/// This function executes a `swapgs` instruction, which changes the memory
+/// accessed through the GS segment. Thus, it is considered unsafe.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn swapgs() {
+    // It is a compiler barrier, so the GS accesses are not moved across it.
+    asm!("swapgs", options(nostack, preserves_flags));
+}
+
+/// Returns the value of the RFLAGS register.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub fn rflags() -> u64 {
+    let val: u64;
+    unsafe { asm!("pushfq", "pop {}", out(reg) val, options(nomem)) };
+    val
+}
+
+/// Returns `true` if maskable interrupts are enabled.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub fn interrupts_enabled() -> bool {
+    rflags() & RFLAGS_IF != 0
+}
+
+/// Calls `f` with maskable interrupts disabled and returns its result. The
+/// interrupt flag is restored afterwards, so calls can be nested.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub fn without_interrupts<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let enabled = interrupts_enabled();
+    if enabled {
+        unsafe { cli() };
+    }
+
+    let ret = f();
+
+    if enabled {
+        unsafe { sti() };
+    }
+    ret
+}
+
+/// Reads the model-specific register `msr`.
+///
+/// # Safety
+///
+/// This function executes a `rdmsr` instruction. Reading a reserved or
+/// unimplemented MSR raises a general protection fault. Thus, it is
+/// considered unsafe.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[inline]
+pub unsafe fn rdmsr(msr: u32) -> u64 {
+    let lo: u32;
+    let hi: u32;
+
+    asm!(
+        "rdmsr",
+        in("ecx") msr,
+        out("eax") lo,
+        out("edx") hi,
+    );
+
+    ((hi as u64) << 32) | lo as u64
+}
+
+/// Writes `val` into the model-specific register `msr`.
+///
+/// # Safety
+///
+/// This function executes a `wrmsr` instruction, which can change the
+/// behavior of the processor in arbitrary ways. Thus, it is considered unsafe.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[inline]
+pub unsafe fn wrmsr(msr: u32, val: u64) {
+    asm!(
+        "wrmsr",
+        in("ecx") msr,
+        in("eax") val as u32,
+        in("edx") (val >> 32) as u32,
+    );
+}
+
+/// IA32_EFER MSR.
+#[cfg(target_arch = "x86_64")]
+pub const IA32_EFER: u32 = 0xc000_0080;
+
+/// No-Execute Enable (NXE) bit of IA32_EFER.
+#[cfg(target_arch = "x86_64")]
+const EFER_NXE: u64 = 1 << 11;
+
+/// Write Protect (WP) bit of CR0.
+#[cfg(target_arch = "x86_64")]
+const CR0_WP: u64 = 1 << 16;
+
+/// Not Write-through (NW) bit of CR0.
+#[cfg(target_arch = "x86_64")]
+const CR0_NW: u64 = 1 << 29;
+
+/// Cache Disable (CD) bit of CR0.
+#[cfg(target_arch = "x86_64")]
+const CR0_CD: u64 = 1 << 30;
+
+/// Enables the execute-disable bit of the page table entries by setting
+/// IA32_EFER.NXE.
+///
+/// # Safety
+///
+/// The NX feature must be supported by the processor, otherwise writing
+/// IA32_EFER raises a general protection fault. Page table entries with
+/// the execute-disable bit set stop being executable. Thus, this function
+/// is considered unsafe.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn enable_nx() {
+    wrmsr(IA32_EFER, rdmsr(IA32_EFER) | EFER_NXE);
+}
+
+/// Sets CR0.WP, so read-only pages cannot be written from ring 0.
+///
+/// # Safety
+///
+/// Kernel writes to read-only pages fault afterwards. Thus, this function is
+/// considered unsafe.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn enable_write_protect() {
+    write_cr0(read_cr0() | CR0_WP);
+}
+
+/// Page Global Enable (PGE) bit of CR4.
+#[cfg(target_arch = "x86_64")]
+const CR4_PGE: u64 = 1 << 7;
+
+/// Returns the value of the control register CR0.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub fn read_cr0() -> u64 {
+    let val: u64;
+    unsafe { asm!("mov {}, cr0", out(reg) val, options(nomem, nostack)) };
+    val
+}
+
+/// Writes `val` into the control register CR0.
+///
+/// # Safety
+///
+/// CR0 controls the operating mode of the processor (e.g. paging, protection
+/// and caching). Thus, this function is considered unsafe.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn write_cr0(val: u64) {
+    asm!("mov cr0, {}", in(reg) val, options(nostack));
+}
+
+/// Returns the value of the control register CR2. It contains the linear
+/// address that caused the last page fault.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub fn read_cr2() -> u64 {
+    let val: u64;
+    unsafe { asm!("mov {}, cr2", out(reg) val, options(nomem, nostack)) };
+    val
+}
+
+/// Returns the value of the control register CR3. It contains the physical
+/// address of the top-level page table and the PCID or the cache flags.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub fn read_cr3() -> u64 {
+    let val: u64;
+    unsafe { asm!("mov {}, cr3", out(reg) val, options(nomem, nostack)) };
+    val
+}
+
+/// Writes `val` into the control register CR3. It switches to the page
+/// tables pointed by `val` and flushes the non-global TLB entries.
+///
+/// # Safety
+///
+/// The new page tables must map the code being executed and all the memory
+/// in use. Thus, this function is considered unsafe.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn write_cr3(val: u64) {
+    asm!("mov cr3, {}", in(reg) val, options(nostack));
+}
+
+/// Returns the value of the control register CR4.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub fn read_cr4() -> u64 {
+    let val: u64;
+    unsafe { asm!("mov {}, cr4", out(reg) val, options(nomem, nostack)) };
+    val
+}
+
+/// Writes `val` into the control register CR4.
+///
+/// # Safety
+///
+/// CR4 enables architectural extensions (e.g. PAE, global pages and SMEP).
+/// Changing them can alter the translation of the memory in use. Thus, this
+/// function is considered unsafe.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn write_cr4(val: u64) {
+    asm!("mov cr4, {}", in(reg) val, options(nostack));
+}
+
+/// Invalidates the TLB entries of the page containing the linear address
+/// `addr`.
+///
+/// # Safety
+///
+/// This function executes an `invlpg` instruction. It is only required to
+/// keep the TLB coherent after modifying the page tables, but it is
+/// considered unsafe like the rest of privileged instructions.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn invlpg(addr: u64) {
+    asm!("invlpg [{}]", in(reg) addr, options(nostack));
+}
+
+/// Invalidates all the TLB entries, including the global ones.
+///
+/// # Safety
+///
+/// This function writes the control registers CR3 or CR4 with their current
+/// values. Thus, it is considered unsafe.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn flush_tlb() {
+    let cr4 = read_cr4();
+    if cr4 & CR4_PGE != 0 {
+        // Toggling CR4.PGE flushes all the TLB entries, including the global
+        // ones.
+        write_cr4(cr4 & !CR4_PGE);
+        write_cr4(cr4);
+    } else {
+        // Without global pages, reloading CR3 flushes all the TLB entries.
+        write_cr3(read_cr3());
+    }
+}
+
+/// Writes back all modified cache lines to main memory and invalidates the
+/// internal caches.
+///
+/// # Safety
+///
+/// This function executes a `wbinvd` instruction. Thus, it is considered
+/// unsafe.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[inline]
+pub unsafe fn wbinvd() {
+    asm!("wbinvd");
+}
+
+/// Executes the `cpuid` instruction for the leaf `leaf` and the subleaf
+/// `subleaf`. It returns a tuple with the form `(eax, ebx, ecx, edx)`.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub fn cpuid(leaf: u32, subleaf: u32) -> (u32, u32, u32, u32) {
+    let eax: u32;
+    let ebx: u64;
+    let ecx: u32;
+    let edx: u32;
+
+    // `rbx` is reserved by LLVM, so it is preserved manually.
+    unsafe {
+        asm!(
+            "mov {tmp}, rbx",
+            "cpuid",
+            "xchg {tmp}, rbx",
+            tmp = out(reg) ebx,
+            inout("eax") leaf => eax,
+            inout("ecx") subleaf => ecx,
+            out("edx") edx,
+        );
+    }
+
+    (eax, ebx as u32, ecx, edx)
+}
+
+/// Returns the initial APIC ID of the current processor.
+#[cfg(target_arch = "x86_64")]
+pub fn apic_id() -> u8 {
+    let (_, ebx, _, _) = cpuid(1, 0);
+    (ebx >> 24) as u8
+}
+
+/// Returns `true` if the processor supports the `rdrand` instruction.
+#[cfg(target_arch = "x86_64")]
+pub fn rdrand_supported() -> bool {
+    CpuFeatures::detect().rdrand()
+}
+
+/// Returns a random `u64` generated by the hardware random number generator.
+/// It returns `None` if no random number is available after 10 attempts, as
+/// recommended by Intel.
+///
+/// # Safety
+///
+/// The caller must ensure that the processor supports the `rdrand`
+/// instruction (see `rdrand_supported`). Otherwise, it raises an invalid
+/// opcode exception.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn rdrand() -> Option<u64> {
+    for _ in 0..10 {
+        let val: u64;
+        let ok: u8;
+
+        asm!(
+            "rdrand {val}",
+            "setc {ok}",
+            val = out(reg) val,
+            ok = out(reg_byte) ok,
+        );
+
+        if ok != 0 {
+            return Some(val);
+        }
+    }
+
+    None
+}
+
+/// Returns the value of the Time Stamp Counter.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub fn rdtsc() -> u64 {
+    let lo: u32;
+    let hi: u32;
+    unsafe {
+        asm!(
+            "rdtsc",
+            out("eax") lo,
+            out("edx") hi,
+            options(nomem, nostack, preserves_flags),
+        );
+    }
+    u64::from(hi) << 32 | u64::from(lo)
+}
+
+/// Returns the value of the Time Stamp Counter and the IA32_TSC_AUX MSR,
+/// which usually holds the index of the CPU. The counter is read after all
+/// the previous instructions have executed, but later instructions can start
+/// before it is read.
+///
+/// # Safety
+///
+/// The processor must support the `rdtscp` instruction (see
+/// `CpuFeatures::rdtscp`). Otherwise, an invalid opcode exception is raised.
+/// Thus, this function is considered unsafe.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn rdtscp() -> (u64, u32) {
+    let lo: u32;
+    let hi: u32;
+    let aux: u32;
+    asm!(
+        "rdtscp",
+        out("eax") lo,
+        out("edx") hi,
+        out("ecx") aux,
+        options(nomem, nostack, preserves_flags),
+    );
+    (u64::from(hi) << 32 | u64::from(lo), aux)
+}
+
+/// Returns the value of the Time Stamp Counter. Unlike `rdtsc`, the read is
+/// ordered with respect to the surrounding instructions by `lfence`: the
+/// counter is read after the previous instructions have executed locally,
+/// and before any later instruction starts. So, it can be used to delimit
+/// the code being measured. It is not fully serializing, given that the
+/// previous stores may not be globally visible yet.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub fn rdtsc_serialized() -> u64 {
+    let lo: u32;
+    let hi: u32;
+    unsafe {
+        asm!(
+            "lfence",
+            "rdtsc",
+            "lfence",
+            out("eax") lo,
+            out("edx") hi,
+            options(nostack, preserves_flags),
+        );
+    }
+    u64::from(hi) << 32 | u64::from(lo)
+}
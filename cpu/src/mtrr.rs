@@ -0,0 +1,213 @@
+//! Memory Type Range Registers (MTRRs).
+//!
+//! Reference:
+//! - Intel SDM Vol. 3A, Section 11.11 "Memory Type Range Registers (MTRRs)"
+
+use crate::{rdmsr, CacheType};
+
+/// IA32_MTRRCAP MSR.
+const IA32_MTRRCAP: u32 = 0xfe;
+
+/// IA32_MTRR_DEF_TYPE MSR.
+const IA32_MTRR_DEF_TYPE: u32 = 0x2ff;
+
+/// IA32_MTRR_PHYSBASE0 MSR. The base MSR of the variable range `n` is
+/// `IA32_MTRR_PHYSBASE0 + 2 * n`.
+const IA32_MTRR_PHYSBASE0: u32 = 0x200;
+
+/// IA32_MTRR_PHYSMASK0 MSR. The mask MSR of the variable range `n` is
+/// `IA32_MTRR_PHYSMASK0 + 2 * n`.
+const IA32_MTRR_PHYSMASK0: u32 = 0x201;
+
+/// Fixed range MSRs in address order, together with the start address and the
+/// size of each one of the 8 ranges they describe.
+const FIXED_RANGE_MSRS: [(u32, u64, u64); 11] = [
+    (0x250, 0x00000, 0x10000),
+    (0x258, 0x80000, 0x4000),
+    (0x259, 0xa0000, 0x4000),
+    (0x268, 0xc0000, 0x1000),
+    (0x269, 0xc8000, 0x1000),
+    (0x26a, 0xd0000, 0x1000),
+    (0x26b, 0xd8000, 0x1000),
+    (0x26c, 0xe0000, 0x1000),
+    (0x26d, 0xe8000, 0x1000),
+    (0x26e, 0xf0000, 0x1000),
+    (0x26f, 0xf8000, 0x1000),
+];
+
+/// Number of fixed ranges.
+pub const FIXED_RANGES_LEN: usize = FIXED_RANGE_MSRS.len() * 8;
+
+/// MTRR capabilities of the processor, as reported by IA32_MTRRCAP.
+#[derive(Debug, Clone, Copy)]
+pub struct Capabilities(u64);
+
+impl Capabilities {
+    /// Number of variable ranges implemented by the processor.
+    pub fn variable_count(&self) -> u8 {
+        self.0 as u8
+    }
+
+    /// Returns `true` if the fixed range registers are supported.
+    pub fn fixed_supported(&self) -> bool {
+        self.0 & (1 << 8) != 0
+    }
+
+    /// Returns `true` if the write-combining memory type is supported.
+    pub fn wc_supported(&self) -> bool {
+        self.0 & (1 << 10) != 0
+    }
+
+    /// Returns `true` if the system-management range register interface is
+    /// supported.
+    pub fn smrr_supported(&self) -> bool {
+        self.0 & (1 << 11) != 0
+    }
+}
+
+/// Returns the MTRR capabilities of the processor.
+///
+/// # Safety
+///
+/// The caller must ensure that the processor supports MTRRs. Otherwise, the
+/// MSR read raises a general protection fault.
+pub unsafe fn capabilities() -> Capabilities {
+    Capabilities(rdmsr(IA32_MTRRCAP))
+}
+
+/// Default memory type and enable flags, as reported by IA32_MTRR_DEF_TYPE.
+#[derive(Debug, Clone, Copy)]
+pub struct DefaultType(u64);
+
+impl DefaultType {
+    /// Memory type used for the physical memory not covered by an MTRR.
+    pub fn cache_type(&self) -> Option<CacheType> {
+        CacheType::from_bits(self.0 as u8)
+    }
+
+    /// Returns `true` if the fixed range MTRRs are enabled.
+    pub fn fixed_enabled(&self) -> bool {
+        self.0 & (1 << 10) != 0
+    }
+
+    /// Returns `true` if the MTRRs are enabled.
+    pub fn enabled(&self) -> bool {
+        self.0 & (1 << 11) != 0
+    }
+}
+
+/// Returns the default memory type and the MTRR enable flags.
+///
+/// # Safety
+///
+/// The caller must ensure that the processor supports MTRRs. Otherwise, the
+/// MSR read raises a general protection fault.
+pub unsafe fn default_type() -> DefaultType {
+    DefaultType(rdmsr(IA32_MTRR_DEF_TYPE))
+}
+
+/// Represents a valid variable range MTRR.
+#[derive(Debug, Clone, Copy)]
+pub struct VariableRange {
+    base: u64,
+    mask: u64,
+    cache_type: Option<CacheType>,
+}
+
+impl VariableRange {
+    /// Base physical address of the range.
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    /// Physical address mask of the range.
+    pub fn mask(&self) -> u64 {
+        self.mask
+    }
+
+    /// Size of the range. It assumes that the mask is contiguous, which is
+    /// the only configuration with a well-defined size. It is `None` if the
+    /// mask is zero.
+    pub fn size(&self) -> Option<u64> {
+        1u64.checked_shl(self.mask.trailing_zeros())
+    }
+
+    /// Memory type of the range. It is `None` if the encoding is reserved.
+    pub fn cache_type(&self) -> Option<CacheType> {
+        self.cache_type
+    }
+
+    /// Returns `true` if the physical address `addr` is covered by the range.
+    pub fn contains(&self, addr: u64) -> bool {
+        addr & self.mask == self.base & self.mask
+    }
+}
+
+/// Returns the variable range MTRR `index`, or `None` if it is not valid.
+///
+/// # Safety
+///
+/// The caller must ensure that the processor supports MTRRs and `index` is
+/// lower than `Capabilities::variable_count`. Otherwise, the MSR read raises
+/// a general protection fault.
+pub unsafe fn variable_range(index: u8) -> Option<VariableRange> {
+    let base = rdmsr(IA32_MTRR_PHYSBASE0 + 2 * index as u32);
+    let mask = rdmsr(IA32_MTRR_PHYSMASK0 + 2 * index as u32);
+
+    // Check the "Valid" flag.
+    if mask & (1 << 11) == 0 {
+        return None;
+    }
+
+    Some(VariableRange {
+        base: base & !0xfff,
+        mask: mask & !0xfff,
+        cache_type: CacheType::from_bits(base as u8),
+    })
+}
+
+/// Represents a fixed range MTRR.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedRange {
+    start: u64,
+    size: u64,
+    cache_type: Option<CacheType>,
+}
+
+impl FixedRange {
+    /// Start physical address of the range.
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// Size of the range.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Memory type of the range. It is `None` if the encoding is reserved.
+    pub fn cache_type(&self) -> Option<CacheType> {
+        self.cache_type
+    }
+}
+
+/// Returns the fixed range MTRR `index`, or `None` if `index` is greater or
+/// equal than `FIXED_RANGES_LEN`. Fixed ranges are sorted by address and
+/// cover the first MiB of physical memory.
+///
+/// # Safety
+///
+/// The caller must ensure that the processor supports fixed range MTRRs.
+/// Otherwise, the MSR read raises a general protection fault.
+pub unsafe fn fixed_range(index: usize) -> Option<FixedRange> {
+    let (msr, start, size) = *FIXED_RANGE_MSRS.get(index / 8)?;
+    let sub = index % 8;
+
+    let val = rdmsr(msr);
+
+    Some(FixedRange {
+        start: start + size * sub as u64,
+        size,
+        cache_type: CacheType::from_bits((val >> (sub * 8)) as u8),
+    })
+}
@@ -0,0 +1,164 @@
+//! Page Attribute Table (PAT).
+//!
+//! The PAT has 8 entries. The entry used by a page is selected by the PAT,
+//! PCD and PWT flags of the paging structure entry that maps it.
+//!
+//! Reference:
+//! - Intel SDM Vol. 3A, Section 11.12 "Page Attribute Table (PAT)"
+
+#[cfg(target_arch = "x86_64")]
+use crate::{
+    flush_tlb, read_cr0, wbinvd, without_interrupts, write_cr0, wrmsr, CR0_CD,
+    CR0_NW,
+};
+use crate::{rdmsr, CacheType};
+
+/// IA32_PAT MSR.
+const IA32_PAT: u32 = 0x277;
+
+/// Page-level write-through flag (PWT) of a paging structure entry.
+const PTE_PWT: u64 = 1 << 3;
+
+/// Page-level cache disable flag (PCD) of a paging structure entry.
+const PTE_PCD: u64 = 1 << 4;
+
+/// PAT flag of a page table entry that maps a 4KiB page.
+const PTE_PAT: u64 = 1 << 7;
+
+/// PAT flag of a page directory or PDPT entry that maps a 2MiB or 1GiB page.
+const PTE_PAT_LARGE: u64 = 1 << 12;
+
+/// Index of a PAT entry.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct PatIndex(u8);
+
+impl PatIndex {
+    /// Returns the PAT, PCD and PWT flags of a paging structure entry that
+    /// select this PAT entry. `large` must be `true` if the entry maps a 2MiB
+    /// or 1GiB page, because the PAT flag is located at a different position.
+    pub fn pte_flags(&self, large: bool) -> u64 {
+        let mut flags = 0;
+
+        if self.0 & 0b001 != 0 {
+            flags |= PTE_PWT;
+        }
+        if self.0 & 0b010 != 0 {
+            flags |= PTE_PCD;
+        }
+        if self.0 & 0b100 != 0 {
+            flags |= if large { PTE_PAT_LARGE } else { PTE_PAT };
+        }
+
+        flags
+    }
+}
+
+/// Represents the memory types of the 8 PAT entries.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct PatLayout([CacheType; 8]);
+
+/// PAT layout set by the processor on power-up or reset.
+pub const DEFAULT_LAYOUT: PatLayout = PatLayout([
+    CacheType::WriteBack,
+    CacheType::WriteThrough,
+    CacheType::UncachedMinus,
+    CacheType::Uncacheable,
+    CacheType::WriteBack,
+    CacheType::WriteThrough,
+    CacheType::UncachedMinus,
+    CacheType::Uncacheable,
+]);
+
+/// PAT layout used by the kernel. The first four entries match the default
+/// layout, so mappings that do not set the PAT flag keep their meaning. The
+/// remaining entries provide the write-combining and write-protected memory
+/// types.
+pub const KERNEL_LAYOUT: PatLayout = PatLayout([
+    CacheType::WriteBack,
+    CacheType::WriteThrough,
+    CacheType::UncachedMinus,
+    CacheType::Uncacheable,
+    CacheType::WriteCombining,
+    CacheType::WriteProtected,
+    CacheType::UncachedMinus,
+    CacheType::Uncacheable,
+]);
+
+impl PatLayout {
+    /// Returns a new `PatLayout` with the provided memory types.
+    pub const fn new(entries: [CacheType; 8]) -> Self {
+        PatLayout(entries)
+    }
+
+    /// Returns the memory types of the PAT entries.
+    pub fn entries(&self) -> &[CacheType; 8] {
+        &self.0
+    }
+
+    /// Returns the index of the first PAT entry with the memory type
+    /// `cache_type`, or `None` if there is no such entry.
+    pub fn index_of(&self, cache_type: CacheType) -> Option<PatIndex> {
+        self.0
+            .iter()
+            .position(|&ty| ty == cache_type)
+            .map(|idx| PatIndex(idx as u8))
+    }
+
+    /// Returns the value of the IA32_PAT MSR corresponding to this layout.
+    fn to_msr(self) -> u64 {
+        self.0
+            .iter()
+            .enumerate()
+            .fold(0, |acc, (i, &ty)| acc | (ty as u64) << (i * 8))
+    }
+
+    /// Returns the layout described by the IA32_PAT MSR value `val`, or
+    /// `None` if any of its entries uses a reserved encoding.
+    fn from_msr(val: u64) -> Option<PatLayout> {
+        let mut entries = [CacheType::Uncacheable; 8];
+        for (i, it) in entries.iter_mut().enumerate() {
+            *it = CacheType::from_bits((val >> (i * 8)) as u8)?;
+        }
+        Some(PatLayout(entries))
+    }
+}
+
+/// Returns the current PAT layout, or `None` if any of its entries uses a
+/// reserved encoding.
+///
+/// # Safety
+///
+/// The caller must ensure that the processor supports the PAT. Otherwise, the
+/// MSR read raises a general protection fault.
+pub unsafe fn layout() -> Option<PatLayout> {
+    PatLayout::from_msr(rdmsr(IA32_PAT))
+}
+
+/// Programs the PAT with the provided `layout`.
+///
+/// It follows the procedure of the Intel SDM Vol. 3A, Section 11.11.8 "MTRR
+/// Considerations in MP Systems". The interrupts are disabled and the caches
+/// are disabled (CR0.CD=1, CR0.NW=0) while the MSR is written. The caches and
+/// the TLBs are flushed before and after writing it. The caller is
+/// responsible for programming the same layout on every processor.
+///
+/// # Safety
+///
+/// The caller must ensure that the processor supports the PAT. Besides,
+/// changing the memory type of pages that are currently mapped can lead to
+/// memory corruption. Thus, this function is considered unsafe.
+#[cfg(target_arch = "x86_64")]
+pub unsafe fn program(layout: &PatLayout) {
+    without_interrupts(|| {
+        let cr0 = read_cr0();
+        write_cr0((cr0 | CR0_CD) & !CR0_NW);
+        wbinvd();
+        flush_tlb();
+
+        wrmsr(IA32_PAT, layout.to_msr());
+
+        wbinvd();
+        flush_tlb();
+        write_cr0(cr0 & !(CR0_CD | CR0_NW));
+    });
+}
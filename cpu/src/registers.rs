@@ -0,0 +1,106 @@
+//! Snapshot of the general-purpose registers.
+
+use core::fmt;
+
+/// Values of the general-purpose registers, RIP and RFLAGS at the point where
+/// `Registers::capture` was called.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Registers {
+    rax: u64,
+    rbx: u64,
+    rcx: u64,
+    rdx: u64,
+    rsi: u64,
+    rdi: u64,
+    rbp: u64,
+    rsp: u64,
+    r8: u64,
+    r9: u64,
+    r10: u64,
+    r11: u64,
+    r12: u64,
+    r13: u64,
+    r14: u64,
+    r15: u64,
+    rip: u64,
+    rflags: u64,
+}
+
+impl Registers {
+    /// Returns the current value of the registers. The register chosen by
+    /// the compiler to hold the address of the snapshot contains that
+    /// address instead of its previous value.
+    #[inline(always)]
+    pub fn capture() -> Registers {
+        let mut regs = Registers::default();
+        unsafe {
+            asm!(
+                "mov [{regs}], rax",
+                "mov [{regs} + 0x08], rbx",
+                "mov [{regs} + 0x10], rcx",
+                "mov [{regs} + 0x18], rdx",
+                "mov [{regs} + 0x20], rsi",
+                "mov [{regs} + 0x28], rdi",
+                "mov [{regs} + 0x30], rbp",
+                "mov [{regs} + 0x38], rsp",
+                "mov [{regs} + 0x40], r8",
+                "mov [{regs} + 0x48], r9",
+                "mov [{regs} + 0x50], r10",
+                "mov [{regs} + 0x58], r11",
+                "mov [{regs} + 0x60], r12",
+                "mov [{regs} + 0x68], r13",
+                "mov [{regs} + 0x70], r14",
+                "mov [{regs} + 0x78], r15",
+                "lea {tmp}, [rip]",
+                "mov [{regs} + 0x80], {tmp}",
+                "pushfq",
+                "pop {tmp}",
+                "mov [{regs} + 0x88], {tmp}",
+                regs = in(reg) &mut regs as *mut Registers,
+                tmp = out(reg) _,
+            );
+        }
+        regs
+    }
+
+    /// Returns the value of RBP. If the code is built with frame pointers,
+    /// it points to the frame of the function that captured the registers.
+    pub fn rbp(&self) -> u64 {
+        self.rbp
+    }
+
+    /// Returns the value of RSP.
+    pub fn rsp(&self) -> u64 {
+        self.rsp
+    }
+
+    /// Returns the value of RIP.
+    pub fn rip(&self) -> u64 {
+        self.rip
+    }
+}
+
+impl fmt::Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows = [
+            [("RAX", self.rax), ("RBX", self.rbx), ("RCX", self.rcx)],
+            [("RDX", self.rdx), ("RSI", self.rsi), ("RDI", self.rdi)],
+            [("RBP", self.rbp), ("RSP", self.rsp), ("R8 ", self.r8)],
+            [("R9 ", self.r9), ("R10", self.r10), ("R11", self.r11)],
+            [("R12", self.r12), ("R13", self.r13), ("R14", self.r14)],
+        ];
+        for row in rows.iter() {
+            for (i, (name, val)) in row.iter().enumerate() {
+                let sep = if i == 0 { "" } else { "  " };
+                write!(f, "{}{}: {:#018x}", sep, name, val)?;
+            }
+            writeln!(f)?;
+        }
+        write!(
+            f,
+            "R15: {:#018x}  RIP: {:#018x}  RFL: {:#018x}",
+            self.r15, self.rip, self.rflags
+        )
+    }
+}
@@ -0,0 +1,131 @@
+//! Deferred work queue.
+//!
+//! Interrupt handlers must be short and bounded. Any work that does not need
+//! to be done in interrupt context can be queued into a `WorkQueue`, and run
+//! later in task context with interrupts enabled.
+//!
+//! The queue is protected by a `TicketMutex` that is only held while pushing
+//! or popping a single item. An interrupt handler that queues work on a CPU
+//! that is holding the lock would spin forever, so the lock is always taken
+//! with interrupts disabled.
+
+#![no_std]
+
+use ticket_mutex::TicketMutex;
+
+/// Error representing that the `WorkQueue` is full.
+#[derive(Debug)]
+pub struct Error;
+
+/// Represents a work item. It is composed by the function to be executed and
+/// its argument.
+#[derive(Debug, Clone, Copy)]
+struct Work {
+    func: fn(usize),
+    arg: usize,
+}
+
+/// Fixed size ring buffer of work items.
+struct Ring<const N: usize> {
+    /// Queued work items.
+    items: [Option<Work>; N],
+
+    /// Index of the oldest work item.
+    head: usize,
+
+    /// Number of queued work items.
+    len: usize,
+}
+
+impl<const N: usize> Ring<N> {
+    /// Appends `work` to the ring buffer.
+    fn push(&mut self, work: Work) -> Result<(), Error> {
+        if self.len >= N {
+            return Err(Error);
+        }
+
+        self.items[(self.head + self.len) % N] = Some(work);
+        self.len += 1;
+
+        Ok(())
+    }
+
+    /// Removes the oldest work item from the ring buffer and returns it.
+    fn pop(&mut self) -> Option<Work> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let work = self.items[self.head].take();
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+
+        work
+    }
+}
+
+/// Represents a queue of deferred work items with capacity for `N` items.
+pub struct WorkQueue<const N: usize> {
+    ring: TicketMutex<Ring<N>>,
+}
+
+impl<const N: usize> WorkQueue<N> {
+    /// Returns an empty `WorkQueue`.
+    pub const fn new() -> Self {
+        WorkQueue {
+            ring: TicketMutex::new(Ring {
+                items: [None; N],
+                head: 0,
+                len: 0,
+            }),
+        }
+    }
+
+    /// Queues the function `func`, that will be called with the argument
+    /// `arg` the next time the queue is run.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Error` if the queue is full.
+    pub fn queue(&self, func: fn(usize), arg: usize) -> Result<(), Error> {
+        self.with_ring(|ring| ring.push(Work { func, arg }))
+    }
+
+    /// Calls `f` with the ring buffer locked and interrupts disabled.
+    fn with_ring<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Ring<N>) -> R,
+    {
+        cpu::without_interrupts(|| self.ring.with(f))
+    }
+
+    /// Returns the number of queued work items.
+    pub fn len(&self) -> usize {
+        self.with_ring(|ring| ring.len)
+    }
+
+    /// Returns `true` if there are no queued work items.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Runs the queued work items in FIFO order, including the ones queued
+    /// while running. It must be called in task context.
+    pub fn run_pending(&self) {
+        // The lock is released before running each work item. So, new items
+        // can be queued meanwhile.
+        loop {
+            let work = self.with_ring(|ring| ring.pop());
+            match work {
+                Some(work) => (work.func)(work.arg),
+                None => break,
+            }
+        }
+    }
+}
+
+impl<const N: usize> Default for WorkQueue<N> {
+    fn default() -> Self {
+        WorkQueue::new()
+    }
+}
@@ -0,0 +1,594 @@
+//! ELF64 parsing and loading.
+//!
+//! Only little-endian x86_64 executables are supported. The headers are
+//! validated when the image is parsed, so the program headers returned by an
+//! `Elf` are always within the bounds of the image.
+//!
+//! The memory of the loadable segments is provided by a `Loader`, so the same
+//! code loads the kernel from the bootloader and the user programs from the
+//! kernel.
+//!
+//! Reference:
+//! - System V Application Binary Interface, Chapter 4 "Object Files" and
+//!   Chapter 5 "Program Loading and Dynamic Linking"
+
+#![no_std]
+
+use core::convert::TryFrom;
+
+/// Magic number at the start of an ELF file.
+const ELF_MAGIC: [u8; 4] = *b"\x7fELF";
+
+/// `EI_CLASS` value of 64-bit objects.
+const ELFCLASS64: u8 = 2;
+
+/// `EI_DATA` value of little-endian objects.
+const ELFDATA2LSB: u8 = 1;
+
+/// `EI_VERSION` and `e_version` value of the current version.
+const EV_CURRENT: u8 = 1;
+
+/// `e_machine` value of x86_64.
+const EM_X86_64: u16 = 62;
+
+/// Size of the ELF header.
+const EHDR_SIZE: usize = 64;
+
+/// Size of a program header.
+const PHDR_SIZE: usize = 56;
+
+/// Size of a page. The loaded segments are extended to the page boundaries.
+pub const PAGE_SIZE: u64 = 0x1000;
+
+/// `e_type` value of executable files.
+pub const ET_EXEC: u16 = 2;
+
+/// `p_type` value of loadable segments.
+pub const PT_LOAD: u32 = 1;
+
+/// `p_flags` bit set if the segment is executable.
+pub const PF_X: u32 = 1;
+
+/// `p_flags` bit set if the segment is writable.
+pub const PF_W: u32 = 2;
+
+/// `p_flags` bit set if the segment is readable.
+pub const PF_R: u32 = 4;
+
+/// ELF errors.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The image does not start with the ELF magic number.
+    InvalidMagic,
+
+    /// The image is not a 64-bit object.
+    InvalidClass,
+
+    /// The image is not little-endian.
+    InvalidEndianness,
+
+    /// The ELF version is not supported.
+    InvalidVersion,
+
+    /// The image is not an executable.
+    InvalidType,
+
+    /// The image is not for x86_64.
+    InvalidMachine,
+
+    /// The headers or the segments are out of the bounds of the image.
+    Truncated,
+
+    /// The program header at the given index is malformed (e.g. the size in
+    /// memory of the segment is smaller than its size in the file).
+    InvalidSegment(usize),
+
+    /// The alignment of the segment at the given index is not a power of
+    /// two or its address is not congruent with its file offset.
+    InvalidAlignment(usize),
+
+    /// The entry point is not in an executable loadable segment.
+    InvalidEntry(u64),
+}
+
+/// Represents a program header of an ELF64 image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgramHeader {
+    /// Index of the program header.
+    pub index: usize,
+
+    /// Type of the segment (e.g. `PT_LOAD`).
+    pub p_type: u32,
+
+    /// Permissions of the segment. A combination of `PF_R`, `PF_W` and
+    /// `PF_X`.
+    pub flags: u32,
+
+    /// Offset of the segment in the file.
+    pub offset: u64,
+
+    /// Virtual address of the segment.
+    pub vaddr: u64,
+
+    /// Physical address of the segment. It is not used by the loader.
+    pub paddr: u64,
+
+    /// Size of the segment in the file.
+    pub filesz: u64,
+
+    /// Size of the segment in memory. The bytes after `filesz` are zeroed.
+    pub memsz: u64,
+
+    /// Alignment of the segment.
+    pub align: u64,
+}
+
+impl ProgramHeader {
+    /// Returns `true` if the segment is loadable.
+    pub fn is_load(&self) -> bool {
+        self.p_type == PT_LOAD
+    }
+
+    /// Returns `true` if the segment is executable.
+    pub fn is_executable(&self) -> bool {
+        self.flags & PF_X != 0
+    }
+
+    /// Returns `true` if the segment is writable.
+    pub fn is_writable(&self) -> bool {
+        self.flags & PF_W != 0
+    }
+
+    /// Returns the page-aligned virtual address where the segment is loaded.
+    pub fn load_start(&self) -> u64 {
+        self.vaddr & !(PAGE_SIZE - 1)
+    }
+
+    /// Returns the size of the segment in memory, extended to the page
+    /// boundaries.
+    pub fn load_size(&self) -> u64 {
+        let end = self.vaddr + self.memsz;
+        ((end + PAGE_SIZE - 1) & !(PAGE_SIZE - 1)) - self.load_start()
+    }
+}
+
+/// Provides the memory where the loadable segments are copied.
+pub trait Loader {
+    /// Loader specific error.
+    type Error;
+
+    /// Returns the memory where the segment `header` is loaded. The returned
+    /// buffer backs the `header.load_size()` bytes starting at the virtual
+    /// address `header.load_start()`. It is zeroed by `Elf::load`.
+    fn allocate(
+        &mut self,
+        header: &ProgramHeader,
+    ) -> Result<&mut [u8], Self::Error>;
+}
+
+/// Errors returned by `Elf::load`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum LoadError<E> {
+    /// The memory returned by the loader is smaller than the segment at the
+    /// given index.
+    BufferTooSmall(usize),
+
+    /// The loader failed.
+    Loader(E),
+}
+
+/// Represents a validated ELF64 image.
+#[derive(Debug, Clone, Copy)]
+pub struct Elf<'a> {
+    /// The image.
+    data: &'a [u8],
+
+    /// Entry point.
+    entry: u64,
+
+    /// Offset of the program header table.
+    phoff: usize,
+
+    /// Size of every entry of the program header table.
+    phentsize: usize,
+
+    /// Number of program headers.
+    phnum: usize,
+}
+
+impl<'a> Elf<'a> {
+    /// Parses and validates the ELF64 image `data`.
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        if data.len() < EHDR_SIZE {
+            return Err(Error::Truncated);
+        }
+        if data[..4] != ELF_MAGIC {
+            return Err(Error::InvalidMagic);
+        }
+        if data[4] != ELFCLASS64 {
+            return Err(Error::InvalidClass);
+        }
+        if data[5] != ELFDATA2LSB {
+            return Err(Error::InvalidEndianness);
+        }
+        if data[6] != EV_CURRENT || read_u32(data, 20)? != EV_CURRENT.into() {
+            return Err(Error::InvalidVersion);
+        }
+        if read_u16(data, 16)? != ET_EXEC {
+            return Err(Error::InvalidType);
+        }
+        if read_u16(data, 18)? != EM_X86_64 {
+            return Err(Error::InvalidMachine);
+        }
+
+        let elf = Elf {
+            data,
+            entry: read_u64(data, 24)?,
+            phoff: read_u64(data, 32)? as usize,
+            phentsize: read_u16(data, 54)?.into(),
+            phnum: read_u16(data, 56)?.into(),
+        };
+        if elf.phnum != 0 && elf.phentsize < PHDR_SIZE {
+            return Err(Error::InvalidSegment(0));
+        }
+        let phdrs_end = elf
+            .phentsize
+            .checked_mul(elf.phnum)
+            .and_then(|size| size.checked_add(elf.phoff))
+            .ok_or(Error::Truncated)?;
+        if phdrs_end > data.len() {
+            return Err(Error::Truncated);
+        }
+
+        for header in elf.program_headers().filter(ProgramHeader::is_load) {
+            elf.validate_segment(&header)?;
+        }
+
+        let entry_segment = elf.program_headers().find(|header| {
+            header.is_load()
+                && header.vaddr <= elf.entry
+                && elf.entry - header.vaddr < header.memsz
+        });
+        if !entry_segment.map_or(false, |header| header.is_executable()) {
+            return Err(Error::InvalidEntry(elf.entry));
+        }
+
+        Ok(elf)
+    }
+
+    /// Returns the entry point.
+    pub fn entry(&self) -> u64 {
+        self.entry
+    }
+
+    /// Returns an iterator over the program headers.
+    pub fn program_headers(&self) -> impl Iterator<Item = ProgramHeader> + '_ {
+        (0..self.phnum).map(move |index| {
+            // The program header table was checked by `parse`.
+            let phdr = &self.data[self.phoff + index * self.phentsize..];
+            ProgramHeader {
+                index,
+                p_type: read_u32(phdr, 0).unwrap(),
+                flags: read_u32(phdr, 4).unwrap(),
+                offset: read_u64(phdr, 8).unwrap(),
+                vaddr: read_u64(phdr, 16).unwrap(),
+                paddr: read_u64(phdr, 24).unwrap(),
+                filesz: read_u64(phdr, 32).unwrap(),
+                memsz: read_u64(phdr, 40).unwrap(),
+                align: read_u64(phdr, 48).unwrap(),
+            }
+        })
+    }
+
+    /// Returns the part of the segment `header` that is stored in the file.
+    pub fn segment_data(&self, header: &ProgramHeader) -> Option<&'a [u8]> {
+        let start = usize::try_from(header.offset).ok()?;
+        let end = start.checked_add(usize::try_from(header.filesz).ok()?)?;
+        self.data.get(start..end)
+    }
+
+    /// Loads the loadable segments into the memory provided by `loader` and
+    /// returns the entry point. The bytes of every segment that are not
+    /// stored in the file (e.g. `.bss`) are zeroed.
+    pub fn load<L: Loader>(
+        &self,
+        loader: &mut L,
+    ) -> Result<u64, LoadError<L::Error>> {
+        for header in self.program_headers().filter(ProgramHeader::is_load) {
+            // The segment was validated by `parse`.
+            let data = self.segment_data(&header).unwrap();
+            let offset = (header.vaddr - header.load_start()) as usize;
+            let size = header.load_size() as usize;
+
+            let buf = loader.allocate(&header).map_err(LoadError::Loader)?;
+            let buf = buf
+                .get_mut(..size)
+                .ok_or(LoadError::BufferTooSmall(header.index))?;
+            buf.fill(0);
+            buf[offset..offset + data.len()].copy_from_slice(data);
+        }
+        Ok(self.entry)
+    }
+
+    /// Checks that the loadable segment `header` is within the bounds of the
+    /// image and the address space, and that it is aligned.
+    fn validate_segment(&self, header: &ProgramHeader) -> Result<(), Error> {
+        if header.filesz > header.memsz {
+            return Err(Error::InvalidSegment(header.index));
+        }
+        if self.segment_data(header).is_none() {
+            return Err(Error::Truncated);
+        }
+        header
+            .vaddr
+            .checked_add(header.memsz)
+            .and_then(|end| end.checked_add(PAGE_SIZE - 1))
+            .ok_or(Error::InvalidSegment(header.index))?;
+
+        // An alignment of 0 or 1 means no alignment.
+        let align = header.align.max(1);
+        if !align.is_power_of_two()
+            || header.vaddr % align != header.offset % align
+        {
+            return Err(Error::InvalidAlignment(header.index));
+        }
+        Ok(())
+    }
+}
+
+/// Reads a little-endian `u16` at the offset `offset` of `data`.
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, Error> {
+    let bytes = data.get(offset..offset + 2).ok_or(Error::Truncated)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+/// Reads a little-endian `u32` at the offset `offset` of `data`.
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, Error> {
+    let bytes = data.get(offset..offset + 4).ok_or(Error::Truncated)?;
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(bytes);
+    Ok(u32::from_le_bytes(buf))
+}
+
+/// Reads a little-endian `u64` at the offset `offset` of `data`.
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, Error> {
+    let bytes = data.get(offset..offset + 8).ok_or(Error::Truncated)?;
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(bytes);
+    Ok(u64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Virtual address of the test images.
+    const BASE: u64 = 0x40_0000;
+
+    /// Size of the test images.
+    const IMAGE_SIZE: usize = 0x2000;
+
+    /// Writes an ELF header with `phnum` program headers right after it.
+    fn write_header(image: &mut [u8], entry: u64, phnum: u16) {
+        image[..4].copy_from_slice(&ELF_MAGIC);
+        image[4] = ELFCLASS64;
+        image[5] = ELFDATA2LSB;
+        image[6] = EV_CURRENT;
+        image[16..18].copy_from_slice(&ET_EXEC.to_le_bytes());
+        image[18..20].copy_from_slice(&EM_X86_64.to_le_bytes());
+        image[20..24].copy_from_slice(&1u32.to_le_bytes());
+        image[24..32].copy_from_slice(&entry.to_le_bytes());
+        image[32..40].copy_from_slice(&(EHDR_SIZE as u64).to_le_bytes());
+        image[52..54].copy_from_slice(&(EHDR_SIZE as u16).to_le_bytes());
+        image[54..56].copy_from_slice(&(PHDR_SIZE as u16).to_le_bytes());
+        image[56..58].copy_from_slice(&phnum.to_le_bytes());
+    }
+
+    /// Writes the program header at `index`.
+    fn write_phdr(image: &mut [u8], index: usize, header: &ProgramHeader) {
+        let phdr = &mut image[EHDR_SIZE + index * PHDR_SIZE..];
+        phdr[0..4].copy_from_slice(&header.p_type.to_le_bytes());
+        phdr[4..8].copy_from_slice(&header.flags.to_le_bytes());
+        phdr[8..16].copy_from_slice(&header.offset.to_le_bytes());
+        phdr[16..24].copy_from_slice(&header.vaddr.to_le_bytes());
+        phdr[24..32].copy_from_slice(&header.paddr.to_le_bytes());
+        phdr[32..40].copy_from_slice(&header.filesz.to_le_bytes());
+        phdr[40..48].copy_from_slice(&header.memsz.to_le_bytes());
+        phdr[48..56].copy_from_slice(&header.align.to_le_bytes());
+    }
+
+    /// Returns a program header of a loadable segment.
+    fn load_phdr(
+        index: usize,
+        flags: u32,
+        offset: u64,
+        vaddr: u64,
+        filesz: u64,
+        memsz: u64,
+    ) -> ProgramHeader {
+        ProgramHeader {
+            index,
+            p_type: PT_LOAD,
+            flags,
+            offset,
+            vaddr,
+            paddr: vaddr,
+            filesz,
+            memsz,
+            align: PAGE_SIZE,
+        }
+    }
+
+    /// Returns an image with a code segment at `BASE` and a data segment,
+    /// with a `.bss`, at `BASE + 0x1000`.
+    fn test_image() -> [u8; IMAGE_SIZE] {
+        let mut image = [0u8; IMAGE_SIZE];
+        write_header(&mut image, BASE + 0x10, 2);
+        write_phdr(
+            &mut image,
+            0,
+            &load_phdr(0, PF_R | PF_X, 0, BASE, 0x100, 0x100),
+        );
+        write_phdr(
+            &mut image,
+            1,
+            &load_phdr(1, PF_R | PF_W, 0x1000, BASE + 0x1000, 0x10, 0x1800),
+        );
+        image[0x1000..0x1010].copy_from_slice(&[0xaa; 0x10]);
+        image
+    }
+
+    /// Loads the segments into a fixed buffer that covers `BASE` to
+    /// `BASE + 0x4000`.
+    struct TestLoader {
+        memory: [u8; 0x4000],
+    }
+
+    impl Loader for TestLoader {
+        type Error = ();
+
+        fn allocate(
+            &mut self,
+            header: &ProgramHeader,
+        ) -> Result<&mut [u8], Self::Error> {
+            let start = header.load_start().checked_sub(BASE).ok_or(())?;
+            let end = start + header.load_size();
+            self.memory.get_mut(start as usize..end as usize).ok_or(())
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let image = test_image();
+        let elf = Elf::parse(&image).unwrap();
+        assert_eq!(elf.entry(), BASE + 0x10);
+
+        let mut headers = elf.program_headers();
+        let code = headers.next().unwrap();
+        assert!(code.is_load() && code.is_executable() && !code.is_writable());
+        let data = headers.next().unwrap();
+        assert!(data.is_writable() && !data.is_executable());
+        assert_eq!(data.load_start(), BASE + 0x1000);
+        assert_eq!(data.load_size(), 0x2000);
+        assert_eq!(elf.segment_data(&data), Some(&image[0x1000..0x1010]));
+        assert!(headers.next().is_none());
+    }
+
+    #[test]
+    fn test_parse_invalid_header() {
+        let mut image = test_image();
+        image[0] = 0;
+        assert_eq!(Elf::parse(&image).unwrap_err(), Error::InvalidMagic);
+
+        let mut image = test_image();
+        image[4] = 1;
+        assert_eq!(Elf::parse(&image).unwrap_err(), Error::InvalidClass);
+
+        let mut image = test_image();
+        image[5] = 2;
+        assert_eq!(Elf::parse(&image).unwrap_err(), Error::InvalidEndianness);
+
+        let mut image = test_image();
+        image[16] = 3;
+        assert_eq!(Elf::parse(&image).unwrap_err(), Error::InvalidType);
+
+        let mut image = test_image();
+        image[18] = 3;
+        assert_eq!(Elf::parse(&image).unwrap_err(), Error::InvalidMachine);
+
+        let image = test_image();
+        assert_eq!(Elf::parse(&image[..32]).unwrap_err(), Error::Truncated);
+        assert_eq!(Elf::parse(&image[..100]).unwrap_err(), Error::Truncated);
+    }
+
+    #[test]
+    fn test_parse_invalid_segment() {
+        let mut image = test_image();
+        write_phdr(
+            &mut image,
+            1,
+            &load_phdr(1, PF_R, 0x1000, BASE, 0x20, 0x10),
+        );
+        assert_eq!(Elf::parse(&image).unwrap_err(), Error::InvalidSegment(1));
+
+        let mut image = test_image();
+        write_phdr(
+            &mut image,
+            1,
+            &load_phdr(1, PF_R, 0x1000, BASE + 0x1000, 0x2000, 0x2000),
+        );
+        assert_eq!(Elf::parse(&image).unwrap_err(), Error::Truncated);
+
+        let mut image = test_image();
+        write_phdr(
+            &mut image,
+            1,
+            &load_phdr(1, PF_R, 0x1000, BASE + 0x1008, 0x10, 0x10),
+        );
+        assert_eq!(
+            Elf::parse(&image).unwrap_err(),
+            Error::InvalidAlignment(1)
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_entry() {
+        let mut image = test_image();
+        write_header(&mut image, BASE + 0x1000, 2);
+        assert_eq!(
+            Elf::parse(&image).unwrap_err(),
+            Error::InvalidEntry(BASE + 0x1000)
+        );
+
+        let mut image = test_image();
+        write_header(&mut image, BASE + 0x100, 2);
+        assert_eq!(
+            Elf::parse(&image).unwrap_err(),
+            Error::InvalidEntry(BASE + 0x100)
+        );
+    }
+
+    #[test]
+    fn test_load() {
+        let image = test_image();
+        let elf = Elf::parse(&image).unwrap();
+        let mut loader = TestLoader {
+            memory: [0xff; 0x4000],
+        };
+        assert_eq!(elf.load(&mut loader), Ok(BASE + 0x10));
+
+        // The code segment is copied and the rest of its page is zeroed.
+        assert_eq!(loader.memory[..0x100], image[..0x100]);
+        assert!(loader.memory[0x100..0x1000].iter().all(|&b| b == 0));
+
+        // The `.bss` of the data segment is zeroed.
+        assert!(loader.memory[0x1000..0x1010].iter().all(|&b| b == 0xaa));
+        assert!(loader.memory[0x1010..0x3000].iter().all(|&b| b == 0));
+
+        // The memory after the segments is not touched.
+        assert!(loader.memory[0x3000..].iter().all(|&b| b == 0xff));
+    }
+
+    #[test]
+    fn test_load_buffer_too_small() {
+        struct SmallLoader([u8; 0x100]);
+
+        impl Loader for SmallLoader {
+            type Error = ();
+
+            fn allocate(
+                &mut self,
+                _header: &ProgramHeader,
+            ) -> Result<&mut [u8], Self::Error> {
+                Ok(&mut self.0)
+            }
+        }
+
+        let image = test_image();
+        let elf = Elf::parse(&image).unwrap();
+        assert_eq!(
+            elf.load(&mut SmallLoader([0; 0x100])),
+            Err(LoadError::BufferTooSmall(0))
+        );
+    }
+}
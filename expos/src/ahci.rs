@@ -0,0 +1,771 @@
+//! AHCI driver.
+//!
+//! The host bus adapters (HBAs) are found by their PCI class. Every port
+//! with a SATA drive attached is exposed as a disk. The ports are stopped
+//! and configured with a command list and a received FIS area in DMA
+//! memory, so they forget the structures of the firmware.
+//!
+//! Like the NVMe driver, the commands are synchronous: only the command
+//! slot 0 is used and the command issue register is polled until the
+//! command completes, so the interrupts of the HBAs are disabled. The data
+//! is copied through a bounce buffer, which is described by a single PRD
+//! entry. The drives are accessed with the 48-bit LBA DMA commands.
+//!
+//! Reference:
+//! - Serial ATA AHCI 1.3.1 Specification
+//! - ATA/ATAPI Command Set (ACS-3)
+//! - [OSDev article](https://wiki.osdev.org/AHCI)
+
+use core::time::Duration;
+
+use block::BlockDevice;
+use ticket_mutex::TicketMutex;
+use uefi::acpi;
+
+use crate::dma::{self, DmaBuffer};
+use crate::pci::{self, Class, Command as PciCommand};
+use crate::{info, time, warn};
+
+/// PCI class of the AHCI HBAs: mass storage, SATA, AHCI 1.0.
+const CLASS_AHCI: Class = Class::new(0x01, 0x06, 0x01);
+
+/// Index of the BAR with the HBA registers (ABAR).
+const ABAR_INDEX: usize = 5;
+
+/// Maximum number of disks.
+const MAX_DISKS: usize = 8;
+
+/// Maximum number of ports of an HBA.
+const MAX_PORTS: usize = 32;
+
+/// HBA Capabilities register.
+const REG_CAP: usize = 0x00;
+
+/// Global HBA Control register.
+const REG_GHC: usize = 0x04;
+
+/// Ports Implemented register.
+const REG_PI: usize = 0x0c;
+
+/// HBA Capabilities Extended register.
+const REG_CAP2: usize = 0x24;
+
+/// BIOS/OS Handoff Control and Status register.
+const REG_BOHC: usize = 0x28;
+
+/// Offset of the registers of the first port.
+const REG_PORTS: usize = 0x100;
+
+/// Size of the registers of a port.
+const PORT_REGS_SIZE: usize = 0x80;
+
+/// Port Command List Base Address register.
+const PORT_CLB: usize = 0x00;
+
+/// Port FIS Base Address register.
+const PORT_FB: usize = 0x08;
+
+/// Port Interrupt Status register.
+const PORT_IS: usize = 0x10;
+
+/// Port Interrupt Enable register.
+const PORT_IE: usize = 0x14;
+
+/// Port Command and Status register.
+const PORT_CMD: usize = 0x18;
+
+/// Port Task File Data register.
+const PORT_TFD: usize = 0x20;
+
+/// Port Signature register.
+const PORT_SIG: usize = 0x24;
+
+/// Port SATA Status register.
+const PORT_SSTS: usize = 0x28;
+
+/// Port SATA Error register.
+const PORT_SERR: usize = 0x30;
+
+/// Port Command Issue register.
+const PORT_CI: usize = 0x38;
+
+/// CAP flag: the HBA can access 64-bit addresses.
+const CAP_S64A: u32 = 1 << 31;
+
+/// GHC flag: interrupts enabled.
+const GHC_IE: u32 = 1 << 1;
+
+/// GHC flag: AHCI mode enabled.
+const GHC_AE: u32 = 1 << 31;
+
+/// CAP2 flag: the HBA supports the BIOS/OS handoff.
+const CAP2_BOH: u32 = 1 << 0;
+
+/// BOHC flag: the BIOS owns the HBA.
+const BOHC_BOS: u32 = 1 << 0;
+
+/// BOHC flag: the OS requests the ownership of the HBA.
+const BOHC_OOS: u32 = 1 << 1;
+
+/// BOHC flag: the BIOS is busy cleaning up.
+const BOHC_BB: u32 = 1 << 4;
+
+/// Port CMD flag: start processing the command list.
+const CMD_ST: u32 = 1 << 0;
+
+/// Port CMD flag: the received FISes are written into the FIS area.
+const CMD_FRE: u32 = 1 << 4;
+
+/// Port CMD flag: the FIS receive DMA engine is running.
+const CMD_FR: u32 = 1 << 14;
+
+/// Port CMD flag: the command list DMA engine is running.
+const CMD_CR: u32 = 1 << 15;
+
+/// Port IS flag: task file error.
+const IS_TFES: u32 = 1 << 30;
+
+/// Port TFD flag: error.
+const TFD_ERR: u32 = 1 << 0;
+
+/// Port TFD flag: data transfer requested.
+const TFD_DRQ: u32 = 1 << 3;
+
+/// Port TFD flag: busy.
+const TFD_BSY: u32 = 1 << 7;
+
+/// Mask of the device detection field of SSTS.
+const SSTS_DET_MASK: u32 = 0xf;
+
+/// SSTS device detection: a device is present and the communication is
+/// established.
+const SSTS_DET_PRESENT: u32 = 3;
+
+/// Signature of the SATA drives.
+const SIG_ATA: u32 = 0x0000_0101;
+
+/// FIS type: register, host to device.
+const FIS_TYPE_REG_H2D: u8 = 0x27;
+
+/// Register FIS flag: the FIS updates the command register.
+const FIS_COMMAND: u8 = 1 << 7;
+
+/// Device register flag: the address is an LBA.
+const DEVICE_LBA: u8 = 1 << 6;
+
+/// ATA command: read DMA with 48-bit LBA.
+const ATA_READ_DMA_EXT: u8 = 0x25;
+
+/// ATA command: write DMA with 48-bit LBA.
+const ATA_WRITE_DMA_EXT: u8 = 0x35;
+
+/// ATA command: flush the write cache with 48-bit LBA.
+const ATA_FLUSH_CACHE_EXT: u8 = 0xea;
+
+/// ATA command: identify device.
+const ATA_IDENTIFY_DEVICE: u8 = 0xec;
+
+/// Command header flag: the data is written into the device.
+const HEADER_WRITE: u32 = 1 << 6;
+
+/// Length of the register FIS in double words.
+const FIS_REG_H2D_LEN: u32 = 5;
+
+/// Offset of the command list in the memory of a port. It holds 32 command
+/// headers and must be aligned to 1KiB.
+const COMMAND_LIST_OFFSET: usize = 0x000;
+
+/// Offset of the received FIS area in the memory of a port. It must be
+/// aligned to 256 bytes.
+const RECEIVED_FIS_OFFSET: usize = 0x400;
+
+/// Offset of the command table of the slot 0 in the memory of a port. It
+/// must be aligned to 128 bytes.
+const COMMAND_TABLE_OFFSET: usize = 0x500;
+
+/// Offset of the PRD table in a command table.
+const PRDT_OFFSET: usize = 0x80;
+
+/// Size of the memory of a port.
+const PORT_MEMORY_SIZE: usize = 0x1000;
+
+/// Size of the bounce buffer.
+const BOUNCE_SIZE: usize = 0x2000;
+
+/// Size of the data returned by IDENTIFY DEVICE.
+const IDENTIFY_SIZE: usize = 512;
+
+/// Size of a sector, unless the drive reports a bigger logical sector.
+const DEFAULT_SECTOR_SIZE: usize = 512;
+
+/// Maximum time the drive may stay busy.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Interval between the checks for the completion of a command.
+const COMMAND_POLL_INTERVAL: Duration = Duration::from_micros(10);
+
+/// Number of checks for the completion of a command, so a command may take
+/// up to 5 seconds. The checks are delayed with `time::busy_sleep`, which
+/// falls back to the PIT if the TSC is not calibrated.
+const COMMAND_TIMEOUT_POLLS: usize = 500_000;
+
+/// Interval between the checks of `wait_clear`.
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Maximum time the DMA engines of a port may take to stop.
+const STOP_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Maximum time the BIOS may take to release the HBA.
+const HANDOFF_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Names of the disks in the disk registry.
+const NAMES: [&str; MAX_DISKS] = [
+    "ahci0", "ahci1", "ahci2", "ahci3", "ahci4", "ahci5", "ahci6", "ahci7",
+];
+
+/// AHCI disks.
+static DISKS: [AhciDisk; MAX_DISKS] = [
+    AhciDisk::new(),
+    AhciDisk::new(),
+    AhciDisk::new(),
+    AhciDisk::new(),
+    AhciDisk::new(),
+    AhciDisk::new(),
+    AhciDisk::new(),
+    AhciDisk::new(),
+];
+
+/// AHCI errors.
+#[derive(Debug)]
+pub enum Error {
+    /// The memory of a port or the bounce buffer could not be allocated.
+    Dma(dma::Error),
+
+    /// The HBA or a port did not change its state or complete a command in
+    /// time.
+    Timeout,
+
+    /// A command failed with the provided task file data.
+    TaskFile(u32),
+
+    /// The drive does not support 48-bit LBA.
+    NoLba48,
+
+    /// The sector size of the drive is not supported.
+    UnsupportedSectorSize(u64),
+}
+
+/// Represents an initialized port with a SATA drive attached.
+struct Port {
+    /// Registers of the port.
+    regs: *mut u8,
+
+    /// Command list, received FIS area and command table.
+    memory: DmaBuffer,
+
+    /// Bounce buffer for the data of the commands.
+    buffer: DmaBuffer,
+
+    /// Maximum number of bytes transferred by a command. It is a multiple of
+    /// the sector size.
+    max_transfer: usize,
+
+    /// Size of a sector in bytes.
+    sector_size: usize,
+
+    /// Number of sectors of the drive.
+    sectors: u64,
+
+    /// Model number reported by the drive, padded with spaces.
+    model: [u8; 40],
+}
+
+// The registers and the memory of the port are owned by the `Port`.
+unsafe impl Send for Port {}
+
+impl Port {
+    /// Returns `true` if there is a SATA drive attached to the port `regs`
+    /// of an HBA.
+    ///
+    /// # Safety
+    ///
+    /// `regs` must point to the registers of a port.
+    unsafe fn is_present(regs: *mut u8) -> bool {
+        let ssts = read32(regs, PORT_SSTS);
+        ssts & SSTS_DET_MASK == SSTS_DET_PRESENT
+            && read32(regs, PORT_SIG) == SIG_ATA
+    }
+
+    /// Initializes the port `regs` of an HBA, which can address 64-bit
+    /// memory if `s64a` is `true`. A SATA drive must be attached to the
+    /// port. On failure, the port is stopped and its DMA memory is freed,
+    /// unless the port does not stop and the HBA could still access it.
+    ///
+    /// # Safety
+    ///
+    /// Nothing else may be accessing the port.
+    unsafe fn init(regs: *mut u8, s64a: bool) -> Result<Port, Error> {
+        // Stop the port, so it forgets the structures of the firmware.
+        stop(regs)?;
+
+        let limit = if s64a {
+            dma::LIMIT_64BIT
+        } else {
+            dma::LIMIT_32BIT
+        };
+        let memory =
+            dma::alloc(PORT_MEMORY_SIZE, 0x1000, limit).map_err(Error::Dma)?;
+        let buffer = match dma::alloc(BOUNCE_SIZE, 0x1000, limit) {
+            Ok(buffer) => buffer,
+            Err(err) => {
+                // The port does not know about the memory yet.
+                dma::free(memory);
+                return Err(Error::Dma(err));
+            }
+        };
+
+        let phys = memory.phys_addr().as_u64();
+        write64(regs, PORT_CLB, phys + COMMAND_LIST_OFFSET as u64);
+        write64(regs, PORT_FB, phys + RECEIVED_FIS_OFFSET as u64);
+
+        // The command header of the slot 0 points to its command table,
+        // which has a single PRD entry.
+        let header = memory.as_ptr().add(COMMAND_LIST_OFFSET) as *mut u32;
+        header.add(1).write_volatile(0);
+        header
+            .add(2)
+            .write_volatile((phys + COMMAND_TABLE_OFFSET as u64) as u32);
+        header.add(3).write_volatile(
+            ((phys + COMMAND_TABLE_OFFSET as u64) >> 32) as u32,
+        );
+
+        // Clear the pending errors and interrupts. The interrupts of the port
+        // are disabled, because the commands are polled.
+        write32(regs, PORT_SERR, u32::MAX);
+        write32(regs, PORT_IS, u32::MAX);
+        write32(regs, PORT_IE, 0);
+
+        let mut port = Port {
+            regs,
+            memory,
+            buffer,
+            max_transfer: BOUNCE_SIZE,
+            sector_size: DEFAULT_SECTOR_SIZE,
+            sectors: 0,
+            model: [b' '; 40],
+        };
+        let result = start(regs, true).and_then(|()| port.identify());
+        if let Err(err) = result {
+            if stop(regs).is_ok() {
+                dma::free(port.memory);
+                dma::free(port.buffer);
+            }
+            return Err(err);
+        }
+        Ok(port)
+    }
+
+    /// Restarts the command list DMA engine, so the port processes new
+    /// commands after a failed one. The failed command is discarded.
+    unsafe fn restart(&mut self) -> Result<(), Error> {
+        write32(self.regs, PORT_CMD, read32(self.regs, PORT_CMD) & !CMD_ST);
+        wait_clear(self.regs, PORT_CMD, CMD_CR, STOP_TIMEOUT)?;
+        write32(self.regs, PORT_SERR, u32::MAX);
+        write32(self.regs, PORT_IS, u32::MAX);
+        start(self.regs, false)
+    }
+
+    /// Reads the identify data of the drive.
+    unsafe fn identify(&mut self) -> Result<(), Error> {
+        self.execute(ATA_IDENTIFY_DEVICE, 0, 0, IDENTIFY_SIZE, false)?;
+        let data = &self.buffer.as_slice()[..IDENTIFY_SIZE];
+        let word =
+            |n: usize| u16::from_le_bytes([data[2 * n], data[2 * n + 1]]);
+
+        // The strings are stored as big endian words.
+        for (i, c) in self.model.iter_mut().enumerate() {
+            *c = data[54 + (i ^ 1)];
+        }
+
+        if word(83) & (1 << 10) == 0 {
+            return Err(Error::NoLba48);
+        }
+        self.sectors = (100..104)
+            .rev()
+            .fold(0, |acc, n| acc << 16 | u64::from(word(n)));
+
+        // Word 106 is valid if its bit 14 is set and its bit 15 is clear.
+        // Then, its bit 12 tells if the logical sector size, in words, is
+        // in words 117 and 118.
+        let info = word(106);
+        if info & 0xc000 == 0x4000 && info & (1 << 12) != 0 {
+            let words = u64::from(word(118)) << 16 | u64::from(word(117));
+            let sector_size = words * 2;
+            if sector_size < 512 || sector_size > BOUNCE_SIZE as u64 {
+                return Err(Error::UnsupportedSectorSize(sector_size));
+            }
+            self.sector_size = sector_size as usize;
+        }
+        self.max_transfer -= self.max_transfer % self.sector_size;
+        Ok(())
+    }
+
+    /// Runs the ATA command `command` for `count` sectors starting at
+    /// `lba`. `len` bytes are transferred between the drive and the bounce
+    /// buffer, from the bounce buffer if `write` is `true`.
+    unsafe fn execute(
+        &mut self,
+        command: u8,
+        lba: u64,
+        count: u16,
+        len: usize,
+        write: bool,
+    ) -> Result<(), Error> {
+        let table = self.memory.as_ptr().add(COMMAND_TABLE_OFFSET);
+
+        let fis = [
+            FIS_TYPE_REG_H2D,
+            FIS_COMMAND,
+            command,
+            0,
+            lba as u8,
+            (lba >> 8) as u8,
+            (lba >> 16) as u8,
+            DEVICE_LBA,
+            (lba >> 24) as u8,
+            (lba >> 32) as u8,
+            (lba >> 40) as u8,
+            0,
+            count as u8,
+            (count >> 8) as u8,
+            0,
+            0,
+        ];
+        for (i, &b) in fis.iter().enumerate() {
+            table.add(i).write_volatile(b);
+        }
+
+        let mut prdtl = 0;
+        if len != 0 {
+            let prd = table.add(PRDT_OFFSET) as *mut u32;
+            let phys = self.buffer.phys_addr().as_u64();
+            prd.write_volatile(phys as u32);
+            prd.add(1).write_volatile((phys >> 32) as u32);
+            prd.add(2).write_volatile(0);
+            prd.add(3).write_volatile(len as u32 - 1);
+            prdtl = 1;
+        }
+
+        let header = self.memory.as_ptr().add(COMMAND_LIST_OFFSET) as *mut u32;
+        let mut dw0 = prdtl << 16 | FIS_REG_H2D_LEN;
+        if write {
+            dw0 |= HEADER_WRITE;
+        }
+        header.write_volatile(dw0);
+        header.add(1).write_volatile(0);
+
+        write32(self.regs, PORT_IS, u32::MAX);
+        write32(self.regs, PORT_CI, 1);
+
+        // The port stops processing commands after a task file error, so
+        // it is restarted.
+        let mut polls = 0;
+        loop {
+            if read32(self.regs, PORT_IS) & IS_TFES != 0 {
+                let tfd = read32(self.regs, PORT_TFD);
+                self.restart()?;
+                return Err(Error::TaskFile(tfd));
+            }
+            if read32(self.regs, PORT_CI) & 1 == 0 {
+                break;
+            }
+            if polls >= COMMAND_TIMEOUT_POLLS {
+                self.restart()?;
+                return Err(Error::Timeout);
+            }
+            time::busy_sleep(COMMAND_POLL_INTERVAL);
+            polls += 1;
+        }
+
+        let tfd = read32(self.regs, PORT_TFD);
+        if tfd & TFD_ERR != 0 {
+            return Err(Error::TaskFile(tfd));
+        }
+        Ok(())
+    }
+
+    /// Checks that `len` bytes starting at the sector `lba` are a whole
+    /// number of sectors inside the drive.
+    fn check_range(&self, lba: u64, len: usize) -> Result<(), block::Error> {
+        block::check_range(self.sector_size, self.sectors, lba, len)
+    }
+
+    /// Reads the sectors starting at `lba` into `buf`. The range must be
+    /// checked by the caller.
+    fn read(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), Error> {
+        let mut lba = lba;
+        for chunk in buf.chunks_mut(self.max_transfer) {
+            let count = (chunk.len() / self.sector_size) as u16;
+            unsafe {
+                self.execute(
+                    ATA_READ_DMA_EXT,
+                    lba,
+                    count,
+                    chunk.len(),
+                    false,
+                )?;
+                chunk.copy_from_slice(&self.buffer.as_slice()[..chunk.len()]);
+            }
+            lba += u64::from(count);
+        }
+        Ok(())
+    }
+
+    /// Writes `buf` into the sectors starting at `lba`. The range must be
+    /// checked by the caller.
+    fn write(&mut self, lba: u64, buf: &[u8]) -> Result<(), Error> {
+        let mut lba = lba;
+        for chunk in buf.chunks(self.max_transfer) {
+            let count = (chunk.len() / self.sector_size) as u16;
+            unsafe {
+                self.buffer.as_mut_slice()[..chunk.len()]
+                    .copy_from_slice(chunk);
+                self.execute(
+                    ATA_WRITE_DMA_EXT,
+                    lba,
+                    count,
+                    chunk.len(),
+                    true,
+                )?;
+            }
+            lba += u64::from(count);
+        }
+        Ok(())
+    }
+
+    /// Makes the written data persistent.
+    fn flush(&mut self) -> Result<(), Error> {
+        unsafe { self.execute(ATA_FLUSH_CACHE_EXT, 0, 0, 0, false) }
+    }
+}
+
+/// Represents a SATA drive attached to an AHCI port.
+pub struct AhciDisk {
+    /// Port. It is `None` if it was not initialized successfully.
+    port: TicketMutex<Option<Port>>,
+}
+
+impl AhciDisk {
+    /// Returns an `AhciDisk` without port.
+    const fn new() -> Self {
+        AhciDisk {
+            port: TicketMutex::new(None),
+        }
+    }
+
+    /// Runs `f` with the port. It fails with `block::Error::Io` if the port
+    /// was not initialized.
+    fn with<T>(
+        &self,
+        f: impl FnOnce(&mut Port) -> Result<T, block::Error>,
+    ) -> Result<T, block::Error> {
+        self.port
+            .lock()
+            .as_mut()
+            .ok_or(block::Error::Io)
+            .and_then(f)
+    }
+}
+
+impl BlockDevice for AhciDisk {
+    fn block_size(&self) -> usize {
+        self.port.lock().as_ref().map_or(0, |port| port.sector_size)
+    }
+
+    fn blocks(&self) -> u64 {
+        self.port.lock().as_ref().map_or(0, |port| port.sectors)
+    }
+
+    fn read(&self, lba: u64, buf: &mut [u8]) -> Result<(), block::Error> {
+        self.with(|port| {
+            port.check_range(lba, buf.len())?;
+            port.read(lba, buf).map_err(io_error)
+        })
+    }
+
+    fn write(&self, lba: u64, buf: &[u8]) -> Result<(), block::Error> {
+        self.with(|port| {
+            port.check_range(lba, buf.len())?;
+            port.write(lba, buf).map_err(io_error)
+        })
+    }
+
+    fn flush(&self) -> Result<(), block::Error> {
+        self.with(|port| port.flush().map_err(io_error))
+    }
+}
+
+/// Reports the driver error `err` and converts it into a block device
+/// error.
+fn io_error(err: Error) -> block::Error {
+    warn!("io error: {:?}", err);
+    block::Error::Io
+}
+
+/// Takes the ownership of the HBA `abar` from the BIOS and enables the AHCI
+/// mode with the interrupts disabled.
+///
+/// # Safety
+///
+/// Nothing else may be accessing the HBA.
+unsafe fn init_hba(abar: *mut u8) -> Result<(), Error> {
+    if read32(abar, REG_CAP2) & CAP2_BOH != 0 {
+        write32(abar, REG_BOHC, read32(abar, REG_BOHC) | BOHC_OOS);
+        wait_clear(abar, REG_BOHC, BOHC_BOS | BOHC_BB, HANDOFF_TIMEOUT)?;
+    }
+
+    write32(abar, REG_GHC, read32(abar, REG_GHC) | GHC_AE);
+    write32(abar, REG_GHC, read32(abar, REG_GHC) & !GHC_IE);
+    Ok(())
+}
+
+/// Initializes the AHCI HBAs found in the PCI functions described by `mcfg`,
+/// registers their disks in the disk registry and returns the number of
+/// disks. The ports that fail to initialize are reported and skipped.
+///
+/// # Safety
+///
+/// This function must be called once, after `pci::init`.
+pub unsafe fn init(mcfg: &acpi::Mcfg) -> usize {
+    let mut count = 0;
+
+    for function in pci::functions(mcfg) {
+        if function.class() != CLASS_AHCI {
+            continue;
+        }
+
+        let bar = match function.map_bar(ABAR_INDEX) {
+            Ok(bar) => bar,
+            Err(err) => {
+                warn!("{}: {:?}", function, err);
+                continue;
+            }
+        };
+        function.enable(
+            PciCommand::MEMORY_SPACE
+                | PciCommand::BUS_MASTER
+                | PciCommand::INTERRUPT_DISABLE,
+        );
+        let abar = bar.addr.as_u64() as *mut u8;
+        if let Err(err) = init_hba(abar) {
+            warn!("{}: {:?}", function, err);
+            continue;
+        }
+
+        let s64a = read32(abar, REG_CAP) & CAP_S64A != 0;
+        let implemented = read32(abar, REG_PI);
+        for index in 0..MAX_PORTS {
+            let offset = REG_PORTS + index * PORT_REGS_SIZE;
+            if implemented & (1 << index) == 0
+                || (offset + PORT_REGS_SIZE) as u64 > bar.size
+            {
+                continue;
+            }
+
+            let regs = abar.add(offset);
+            if !Port::is_present(regs) {
+                continue;
+            }
+
+            // The port is left untouched if it cannot be registered.
+            let (disk, name) = match DISKS.get(count) {
+                Some(disk) => (disk, NAMES[count]),
+                None => {
+                    warn!("{} port {}: too many disks", function, index);
+                    continue;
+                }
+            };
+            let port = match Port::init(regs, s64a) {
+                Ok(port) => port,
+                Err(err) => {
+                    warn!("{} port {}: {:?}", function, index, err);
+                    continue;
+                }
+            };
+
+            info!(
+                "{}: {} port {}: {}, {} sectors of {} bytes",
+                name,
+                function,
+                index,
+                core::str::from_utf8(&port.model).unwrap_or("?").trim_end(),
+                port.sectors,
+                port.sector_size,
+            );
+            *disk.port.lock() = Some(port);
+            count += 1;
+            if let Err(err) = crate::disk::register(name, disk) {
+                warn!("{}: {:?}", name, err);
+            }
+        }
+    }
+    count
+}
+
+/// Reads the 32-bit register at `offset`.
+unsafe fn read32(regs: *mut u8, offset: usize) -> u32 {
+    (regs.add(offset) as *const u32).read_volatile()
+}
+
+/// Writes the 32-bit register at `offset`.
+unsafe fn write32(regs: *mut u8, offset: usize, value: u32) {
+    (regs.add(offset) as *mut u32).write_volatile(value)
+}
+
+/// Writes the 64-bit register at `offset`, as two 32-bit writes.
+unsafe fn write64(regs: *mut u8, offset: usize, value: u64) {
+    write32(regs, offset, value as u32);
+    write32(regs, offset + 4, (value >> 32) as u32);
+}
+
+/// Stops the DMA engines of the port `regs`.
+unsafe fn stop(regs: *mut u8) -> Result<(), Error> {
+    let cmd = read32(regs, PORT_CMD);
+    write32(regs, PORT_CMD, cmd & !CMD_ST);
+    wait_clear(regs, PORT_CMD, CMD_CR, STOP_TIMEOUT)?;
+    write32(regs, PORT_CMD, cmd & !(CMD_ST | CMD_FRE));
+    wait_clear(regs, PORT_CMD, CMD_FR, STOP_TIMEOUT)
+}
+
+/// Starts the command list DMA engine of the port `regs` once the drive is
+/// not busy. The FIS receive DMA engine is started before if `fis_receive`
+/// is `true`.
+unsafe fn start(regs: *mut u8, fis_receive: bool) -> Result<(), Error> {
+    if fis_receive {
+        write32(regs, PORT_CMD, read32(regs, PORT_CMD) | CMD_FRE);
+    }
+    wait_clear(regs, PORT_TFD, TFD_BSY | TFD_DRQ, BUSY_TIMEOUT)?;
+    write32(regs, PORT_CMD, read32(regs, PORT_CMD) | CMD_ST);
+    Ok(())
+}
+
+/// Waits until the bits `mask` of the register at `offset` are clear, for
+/// at most `timeout`. The checks are delayed with `time::busy_sleep`, which
+/// falls back to the PIT if the TSC is not calibrated.
+unsafe fn wait_clear(
+    regs: *mut u8,
+    offset: usize,
+    mask: u32,
+    timeout: Duration,
+) -> Result<(), Error> {
+    let max_polls = timeout.as_millis() / WAIT_POLL_INTERVAL.as_millis();
+    let mut polls = 0;
+    while read32(regs, offset) & mask != 0 {
+        if polls >= max_polls {
+            return Err(Error::Timeout);
+        }
+        time::busy_sleep(WAIT_POLL_INTERVAL);
+        polls += 1;
+    }
+    Ok(())
+}
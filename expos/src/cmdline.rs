@@ -0,0 +1,120 @@
+//! Kernel command line.
+//!
+//! The command line is passed by the bootloader in the boot information. It
+//! sets the kernel parameters declared by the subsystems (e.g.
+//! `console.serial`) and it can also be queried directly with typed lookups
+//! (e.g. `get_bool("smp")`). The syntax of the arguments and their values is
+//! the one accepted by the `param` crate.
+
+use param::{BoolParam, Param, U64Param};
+use ticket_mutex::TicketMutex;
+
+use crate::{disk, fbcon, log, serial};
+
+/// Command line passed by the bootloader. It is empty until `init` is
+/// called.
+static CMDLINE: TicketMutex<&'static str> = TicketMutex::new("");
+
+/// Command line errors.
+#[derive(Debug)]
+pub enum Error {
+    /// The command line is not valid UTF-8.
+    InvalidUtf8,
+
+    /// The value of a kernel parameter is not valid.
+    Param(param::Error),
+}
+
+/// Takes the command line from the boot information `loader` and sets the
+/// kernel parameters. The valid parameters are set even if an error is
+/// returned.
+pub fn init(loader: &'static boot_info::BootInfo) -> Result<(), Error> {
+    // The command line lives in bootloader memory, which is never reused.
+    let bytes = unsafe { loader.cmdline.as_slice() };
+    let cmdline = core::str::from_utf8(bytes).or(Err(Error::InvalidUtf8))?;
+    *CMDLINE.lock() = cmdline;
+
+    param::parse(
+        cmdline,
+        &[
+            &serial::SERIAL_ENABLED,
+            &serial::AUX_SERIAL_ENABLED,
+            &fbcon::FBCON_ENABLED,
+            &disk::RAMDISK_SIZE,
+            &log::LOG_LEVEL,
+        ],
+    )
+    .map_err(Error::Param)
+}
+
+/// Returns the command line.
+pub fn get() -> &'static str {
+    *CMDLINE.lock()
+}
+
+/// Returns the value of the boolean argument `name`. It returns `None` if
+/// the argument is missing or its value is not valid.
+pub fn get_bool(name: &'static str) -> Option<bool> {
+    lookup_bool(get(), name)
+}
+
+/// Returns the value of the unsigned integer argument `name`. It returns
+/// `None` if the argument is missing or its value is not valid.
+// No argument is an integer yet.
+#[allow(dead_code)]
+pub fn get_u64(name: &'static str) -> Option<u64> {
+    lookup_u64(get(), name)
+}
+
+/// Returns the value of the boolean argument `name` in `cmdline`.
+fn lookup_bool(cmdline: &'static str, name: &'static str) -> Option<bool> {
+    let param = BoolParam::new(name, false);
+    if lookup(cmdline, &param) {
+        Some(param.get())
+    } else {
+        None
+    }
+}
+
+/// Returns the value of the unsigned integer argument `name` in `cmdline`.
+fn lookup_u64(cmdline: &'static str, name: &'static str) -> Option<u64> {
+    let param = U64Param::new(name, 0);
+    if lookup(cmdline, &param) {
+        Some(param.get())
+    } else {
+        None
+    }
+}
+
+/// Sets `param` from the last argument in `cmdline` with its name, so the
+/// arguments appended to a command line override the previous ones. It
+/// returns `true` if the argument exists and its value is valid.
+fn lookup(cmdline: &'static str, param: &dyn Param) -> bool {
+    param::args(cmdline)
+        .filter(|(name, _)| *name == param.name())
+        .last()
+        .map_or(false, |(_, value)| param.set(value).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_bool() {
+        let cmdline = "BOOTX64.EFI smp=off smp=on nosmp x=maybe";
+        assert_eq!(lookup_bool(cmdline, "smp"), Some(true));
+        assert_eq!(lookup_bool(cmdline, "nosmp"), Some(true));
+        assert_eq!(lookup_bool(cmdline, "x"), None);
+        assert_eq!(lookup_bool(cmdline, "missing"), None);
+    }
+
+    #[test]
+    fn test_lookup_u64() {
+        let cmdline = "loglevel=7 mem=0x1000 loglevel mem=x";
+        assert_eq!(lookup_u64(cmdline, "loglevel"), None);
+        assert_eq!(lookup_u64(cmdline, "mem"), None);
+        assert_eq!(lookup_u64("loglevel=7 mem=0x1000", "mem"), Some(0x1000));
+        assert_eq!(lookup_u64("loglevel=7", "loglevel"), Some(7));
+    }
+}
@@ -0,0 +1,157 @@
+//! Console multiplexer.
+//!
+//! The output of `print!` is written to every enabled sink registered in the
+//! console (e.g. serial ports). Every sink is enabled by a boolean kernel
+//! parameter, so the output can be redirected at boot when one of them is not
+//! reliable.
+
+use core::fmt::{self, Write};
+
+use param::BoolParam;
+use ticket_mutex::TicketMutex;
+
+/// Maximum number of sinks registered in the console.
+const MAX_SINKS: usize = 4;
+
+/// Static variable that provides access to the console used by `print!`.
+static CONSOLE: TicketMutex<Console> = TicketMutex::new(Console::new());
+
+/// Console errors.
+#[derive(Debug)]
+pub enum Error {
+    /// The console is full.
+    Full,
+
+    /// A sink with the same name is already registered.
+    Duplicated,
+}
+
+/// Output device of the console.
+pub trait Sink: Sync {
+    /// Writes `s` to the sink. If `wait` is `false`, the output is discarded
+    /// instead of waiting if the sink is busy.
+    fn write_str(&self, s: &str, wait: bool);
+}
+
+/// Represents a sink registered in the console.
+struct Entry {
+    /// Name of the sink.
+    name: &'static str,
+
+    /// Output device.
+    sink: &'static dyn Sink,
+
+    /// The output is only written to the sink if this parameter is `true`.
+    enabled: &'static BoolParam,
+}
+
+/// Represents a console composed by multiple sinks.
+struct Console {
+    /// Registered sinks, in registration order.
+    entries: [Option<Entry>; MAX_SINKS],
+}
+
+impl Console {
+    /// Returns a `Console` without sinks.
+    const fn new() -> Self {
+        Console {
+            entries: [None, None, None, None],
+        }
+    }
+
+    /// Returns `true` if a sink called `name` is registered.
+    fn contains(&self, name: &str) -> bool {
+        self.entries
+            .iter()
+            .flatten()
+            .any(|entry| entry.name == name)
+    }
+
+    /// Writes `s` to all the enabled sinks.
+    fn write_str(&self, s: &str, wait: bool) {
+        for entry in self.entries.iter().flatten() {
+            if entry.enabled.get() {
+                entry.sink.write_str(s, wait);
+            }
+        }
+    }
+}
+
+/// Registers `sink` in the console with the name `name`. The output is
+/// written to it while the parameter `enabled` is `true`.
+pub fn register(
+    name: &'static str,
+    sink: &'static dyn Sink,
+    enabled: &'static BoolParam,
+) -> Result<(), Error> {
+    let mut console = CONSOLE.lock();
+
+    if console.contains(name) {
+        return Err(Error::Duplicated);
+    }
+
+    let slot = console
+        .entries
+        .iter_mut()
+        .find(|entry| entry.is_none())
+        .ok_or(Error::Full)?;
+    *slot = Some(Entry {
+        name,
+        sink,
+        enabled,
+    });
+
+    Ok(())
+}
+
+/// Writes the formatted arguments `args` to the console. It is used by
+/// `print!`. The console is locked while writing, so the output of
+/// concurrent calls is not interleaved.
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    let console = CONSOLE.lock();
+    // The writer does not fail, so the result can be ignored.
+    Writer(&console).write_fmt(args).ok();
+}
+
+/// Adapter that implements the `Write` trait for a locked console.
+struct Writer<'a>(&'a Console);
+
+impl Write for Writer<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write_str(s, true);
+        Ok(())
+    }
+}
+
+/// The type `PanicWriter` implements the `Write` trait for the console, but
+/// the output is discarded instead of waiting if the console or a sink is
+/// locked. The panicking context could be the one holding the lock.
+#[cfg(not(test))]
+pub struct PanicWriter;
+
+#[cfg(not(test))]
+impl Write for PanicWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if let Some(console) = CONSOLE.try_lock() {
+            console.write_str(s, false);
+        }
+        Ok(())
+    }
+}
+
+/// Prints to the console.
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => {
+        $crate::console::_print(format_args!($($arg)*))
+    }
+}
+
+/// Prints to the console, with a newline.
+#[macro_export]
+macro_rules! println {
+    ($($arg:tt)*) => {
+        $crate::print!("{}\n", format_args!($($arg)*))
+    }
+}
@@ -0,0 +1,172 @@
+//! Disk registry.
+//!
+//! The storage drivers register their disks by name (e.g. `nvme0` or
+//! `ahci0`), so the code on top of them (e.g. filesystems) looks them up and
+//! accesses them through the `BlockDevice` trait without depending on a
+//! specific driver.
+//!
+//! The registry also provides a RAM disk, which is created at boot if the
+//! kernel parameter `ramdisk.size` is not zero.
+
+use block::{BlockDevice, RamDisk};
+use param::U64Param;
+use ticket_mutex::TicketMutex;
+
+use crate::pmm::{self, FRAME_SIZE};
+
+/// Maximum number of disks registered in the registry.
+const MAX_DISKS: usize = 16;
+
+/// Size of a block of the RAM disk.
+const RAMDISK_BLOCK_SIZE: usize = 512;
+
+/// Size of the RAM disk in bytes. The RAM disk is not created if it is zero.
+pub static RAMDISK_SIZE: U64Param = U64Param::new("ramdisk.size", 0);
+
+/// Static variable that provides access to the registered disks.
+static DISKS: TicketMutex<[Option<Entry>; MAX_DISKS]> =
+    TicketMutex::new([None; MAX_DISKS]);
+
+/// RAM disk. It is registered as `ram0`.
+static RAMDISK: Ram = Ram::new();
+
+/// Disk registry errors.
+#[derive(Debug)]
+pub enum Error {
+    /// The registry is full.
+    Full,
+
+    /// A disk with the same name is already registered.
+    Duplicated,
+
+    /// The memory of the RAM disk could not be allocated.
+    NoMemory,
+}
+
+/// Represents a disk registered in the registry.
+#[derive(Clone, Copy)]
+struct Entry {
+    /// Name of the disk.
+    name: &'static str,
+
+    /// Block device.
+    device: &'static dyn BlockDevice,
+}
+
+/// Registers `device` in the registry with the name `name`.
+pub fn register(
+    name: &'static str,
+    device: &'static dyn BlockDevice,
+) -> Result<(), Error> {
+    let mut disks = DISKS.lock();
+
+    if disks.iter().flatten().any(|entry| entry.name == name) {
+        return Err(Error::Duplicated);
+    }
+
+    let slot = disks
+        .iter_mut()
+        .find(|entry| entry.is_none())
+        .ok_or(Error::Full)?;
+    *slot = Some(Entry { name, device });
+
+    Ok(())
+}
+
+/// Returns the disk called `name`.
+// Nothing looks up the disks by name yet.
+#[allow(dead_code)]
+pub fn get(name: &str) -> Option<&'static dyn BlockDevice> {
+    DISKS
+        .lock()
+        .iter()
+        .flatten()
+        .find(|entry| entry.name == name)
+        .map(|entry| entry.device)
+}
+
+/// Calls `f` with the name and the device of every registered disk, in
+/// registration order. The registry is not locked while `f` runs.
+pub fn for_each(mut f: impl FnMut(&'static str, &'static dyn BlockDevice)) {
+    let disks = *DISKS.lock();
+    for entry in disks.iter().flatten() {
+        f(entry.name, entry.device);
+    }
+}
+
+/// RAM disk that is created at boot.
+struct Ram {
+    /// Disk. It is `None` if it was not created.
+    disk: TicketMutex<Option<RamDisk<'static>>>,
+}
+
+impl Ram {
+    /// Returns a `Ram` without disk.
+    const fn new() -> Self {
+        Ram {
+            disk: TicketMutex::new(None),
+        }
+    }
+}
+
+impl BlockDevice for Ram {
+    fn block_size(&self) -> usize {
+        self.disk
+            .lock()
+            .as_ref()
+            .map_or(0, |disk| disk.block_size())
+    }
+
+    fn blocks(&self) -> u64 {
+        self.disk.lock().as_ref().map_or(0, |disk| disk.blocks())
+    }
+
+    fn read(&self, lba: u64, buf: &mut [u8]) -> Result<(), block::Error> {
+        match self.disk.lock().as_ref() {
+            Some(disk) => disk.read(lba, buf),
+            None => Err(block::Error::Io),
+        }
+    }
+
+    fn write(&self, lba: u64, buf: &[u8]) -> Result<(), block::Error> {
+        match self.disk.lock().as_ref() {
+            Some(disk) => disk.write(lba, buf),
+            None => Err(block::Error::Io),
+        }
+    }
+
+    fn flush(&self) -> Result<(), block::Error> {
+        Ok(())
+    }
+}
+
+/// Creates the RAM disk with the size set by the kernel parameter
+/// `ramdisk.size`, rounded up to a multiple of `FRAME_SIZE`, and registers
+/// it. It returns the size of the disk in bytes, which is zero if it was
+/// not created.
+///
+/// # Safety
+///
+/// This function must be called once, after `pmm::init`.
+pub unsafe fn init_ramdisk() -> Result<u64, Error> {
+    let size = RAMDISK_SIZE.get();
+    if size == 0 {
+        return Ok(0);
+    }
+
+    let frames =
+        size.checked_add(FRAME_SIZE - 1).ok_or(Error::NoMemory)? / FRAME_SIZE;
+    let phys = pmm::alloc_contiguous(frames as usize, FRAME_SIZE)
+        .ok_or(Error::NoMemory)?;
+
+    // The frames are mapped at the offset of the linear mapping and they are
+    // never freed.
+    let virt = mm::phys_to_virt(phys).as_u64() as *mut u8;
+    let size = frames * FRAME_SIZE;
+    virt.write_bytes(0, size as usize);
+    let data = core::slice::from_raw_parts_mut(virt, size as usize);
+
+    *RAMDISK.disk.lock() = Some(RamDisk::new(data, RAMDISK_BLOCK_SIZE));
+    register("ram0", &RAMDISK)?;
+    Ok(size)
+}
@@ -0,0 +1,130 @@
+//! DMA buffers.
+//!
+//! The devices access the memory by its physical address, so the DMA
+//! buffers are physically contiguous blocks of frames taken from the frame
+//! allocator. Some devices can only address part of the physical memory
+//! (e.g. 32-bit addresses), so the buffers can be allocated below an address
+//! limit.
+//!
+//! The CPU accesses the buffers through the linear mapping of the physical
+//! memory. DMA is cache coherent on x86, so the buffers are mapped as
+//! write-back memory like the rest of the physical memory.
+
+use core::ptr::NonNull;
+use core::slice;
+
+use mm::PhysAddr;
+
+use crate::pmm::{self, FRAME_SIZE};
+
+/// Address limit of the devices that can only use 32-bit addresses.
+pub const LIMIT_32BIT: u64 = 0x1_0000_0000;
+
+/// Address limit of the devices that can use 64-bit addresses.
+pub const LIMIT_64BIT: u64 = u64::MAX;
+
+/// DMA errors.
+#[derive(Debug)]
+pub enum Error {
+    /// The size is zero.
+    InvalidSize,
+
+    /// The alignment is not a power of two.
+    InvalidAlignment(u64),
+
+    /// There is no free block of frames that satisfies the constraints.
+    NoMemory,
+}
+
+/// Represents a physically contiguous buffer that can be used for DMA.
+#[derive(Debug)]
+pub struct DmaBuffer {
+    /// Physical address of the buffer.
+    phys: PhysAddr,
+
+    /// Pointer to the buffer in the linear mapping of the physical memory.
+    virt: NonNull<u8>,
+
+    /// Size of the buffer in bytes. It is a multiple of `FRAME_SIZE`.
+    size: usize,
+}
+
+// The buffer is owned by the `DmaBuffer`.
+unsafe impl Send for DmaBuffer {}
+
+impl DmaBuffer {
+    /// Returns the physical address of the buffer, which is the address
+    /// used by the devices.
+    pub fn phys_addr(&self) -> PhysAddr {
+        self.phys
+    }
+
+    /// Returns a pointer to the buffer, which is used by the CPU.
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.virt.as_ptr()
+    }
+
+    /// Returns the size of the buffer in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the buffer as a slice.
+    ///
+    /// # Safety
+    ///
+    /// No device may write the buffer while the slice is in use.
+    pub unsafe fn as_slice(&self) -> &[u8] {
+        slice::from_raw_parts(self.virt.as_ptr(), self.size)
+    }
+
+    /// Returns the buffer as a mutable slice.
+    ///
+    /// # Safety
+    ///
+    /// No device may access the buffer while the slice is in use.
+    pub unsafe fn as_mut_slice(&mut self) -> &mut [u8] {
+        slice::from_raw_parts_mut(self.virt.as_ptr(), self.size)
+    }
+}
+
+/// Allocates a zeroed DMA buffer of at least `size` bytes, aligned to
+/// `align` bytes, which ends at or below the physical address `limit`. The
+/// buffer is always aligned to `FRAME_SIZE` and its size is rounded up to
+/// a multiple of `FRAME_SIZE`.
+pub fn alloc(size: usize, align: u64, limit: u64) -> Result<DmaBuffer, Error> {
+    if size == 0 {
+        return Err(Error::InvalidSize);
+    }
+    if !align.is_power_of_two() {
+        return Err(Error::InvalidAlignment(align));
+    }
+
+    let frames = (size as u64 + FRAME_SIZE - 1) / FRAME_SIZE;
+    let phys = pmm::alloc_contiguous_below(frames as usize, align, limit)
+        .ok_or(Error::NoMemory)?;
+
+    // The frames are mapped at the offset of the linear mapping and they are
+    // not used by anyone else.
+    let virt = mm::phys_to_virt(phys).as_u64() as *mut u8;
+    let size = (frames * FRAME_SIZE) as usize;
+    unsafe { virt.write_bytes(0, size) };
+
+    Ok(DmaBuffer {
+        phys,
+        virt: NonNull::new(virt).ok_or(Error::NoMemory)?,
+        size,
+    })
+}
+
+/// Frees the DMA buffer `buffer`.
+///
+/// # Safety
+///
+/// The devices must not access the buffer anymore.
+pub unsafe fn free(buffer: DmaBuffer) {
+    for offset in (0..buffer.size as u64).step_by(FRAME_SIZE as usize) {
+        // The frames were allocated by `alloc`, so they are valid.
+        pmm::free_frame(PhysAddr::new(buffer.phys.as_u64() + offset)).ok();
+    }
+}
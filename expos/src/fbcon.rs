@@ -0,0 +1,492 @@
+//! Framebuffer text console.
+//!
+//! The framebuffer set up by the bootloader is used as a text console with
+//! the 8x16 font in `font`, so the console output is visible on machines
+//! without accessible serial ports. When the cursor goes past the last line,
+//! the screen is scrolled up.
+//!
+//! The colors are set with the SGR escape sequences (e.g. `"\x1b[31m"`) of
+//! the 16 ANSI colors. The other escape sequences are discarded.
+
+mod font;
+
+use boot_info::{Framebuffer, PixelFormat};
+use param::BoolParam;
+use ticket_mutex::TicketMutex;
+
+use crate::console;
+
+/// Framebuffer console.
+static FBCON: FbConSink = FbConSink::new();
+
+/// Enables the console output to the framebuffer.
+pub static FBCON_ENABLED: BoolParam = BoolParam::new("console.fbcon", true);
+
+/// Maximum number of parameters of an escape sequence. The rest are
+/// discarded.
+const MAX_PARAMS: usize = 4;
+
+/// Number of columns between tab stops.
+const TAB_WIDTH: usize = 8;
+
+/// Color of the text by default.
+const DEFAULT_FG: u8 = 7;
+
+/// Color of the background by default.
+const DEFAULT_BG: u8 = 0;
+
+/// ANSI colors as `(red, green, blue)`, with the normal colors followed by
+/// the bright ones. They match the VGA text mode palette.
+const PALETTE: [(u8, u8, u8); 16] = [
+    (0x00, 0x00, 0x00),
+    (0xaa, 0x00, 0x00),
+    (0x00, 0xaa, 0x00),
+    (0xaa, 0x55, 0x00),
+    (0x00, 0x00, 0xaa),
+    (0xaa, 0x00, 0xaa),
+    (0x00, 0xaa, 0xaa),
+    (0xaa, 0xaa, 0xaa),
+    (0x55, 0x55, 0x55),
+    (0xff, 0x55, 0x55),
+    (0x55, 0xff, 0x55),
+    (0xff, 0xff, 0x55),
+    (0x55, 0x55, 0xff),
+    (0xff, 0x55, 0xff),
+    (0x55, 0xff, 0xff),
+    (0xff, 0xff, 0xff),
+];
+
+/// Framebuffer console errors.
+#[derive(Debug)]
+pub enum Error {
+    /// The bootloader did not set up a framebuffer.
+    NotAvailable,
+
+    /// The pixel format with the provided raw representation is not
+    /// supported.
+    UnsupportedFormat(u32),
+
+    /// The framebuffer is smaller than its resolution or than a single
+    /// character.
+    InvalidSize,
+}
+
+/// Console sink that writes into the framebuffer.
+struct FbConSink {
+    /// Screen. It is `None` if the framebuffer console was not initialized
+    /// successfully.
+    screen: TicketMutex<Option<Screen>>,
+}
+
+impl FbConSink {
+    /// Returns a `FbConSink` without screen.
+    const fn new() -> Self {
+        FbConSink {
+            screen: TicketMutex::new(None),
+        }
+    }
+}
+
+impl console::Sink for FbConSink {
+    fn write_str(&self, s: &str, wait: bool) {
+        let mut screen = if wait {
+            Some(self.screen.lock())
+        } else {
+            self.screen.try_lock()
+        };
+
+        if let Some(screen) = screen.as_mut().and_then(|scr| scr.as_mut()) {
+            s.chars().for_each(|c| screen.write_char(c));
+        }
+    }
+}
+
+/// Represents the framebuffer as a grid of characters.
+struct Screen {
+    /// Address of the first pixel. The framebuffer is identity mapped.
+    base: *mut u32,
+
+    /// Number of pixels per scan line.
+    stride: usize,
+
+    /// Layout of the pixels.
+    format: PixelFormat,
+
+    /// Number of columns of characters.
+    cols: usize,
+
+    /// Number of rows of characters.
+    rows: usize,
+
+    /// Column of the cursor. It is `cols` if the line is full, so the cursor
+    /// only moves to the next line when another character is written.
+    col: usize,
+
+    /// Row of the cursor.
+    row: usize,
+
+    /// Current colors.
+    attrs: Attributes,
+
+    /// Escape sequence parser.
+    parser: Parser,
+}
+
+// The framebuffer is only accessed through the `Screen`.
+unsafe impl Send for Screen {}
+
+impl Screen {
+    /// Returns a `Screen` that covers `framebuffer` and clears it.
+    ///
+    /// # Safety
+    ///
+    /// The framebuffer must be identity mapped and it must not be accessed
+    /// by anything else.
+    unsafe fn new(framebuffer: &Framebuffer) -> Result<Screen, Error> {
+        let format = framebuffer.format;
+        if format != PixelFormat::RGB && format != PixelFormat::BGR {
+            return Err(Error::UnsupportedFormat(format.raw()));
+        }
+
+        let width = framebuffer.width as usize;
+        let height = framebuffer.height as usize;
+        let stride = framebuffer.stride as usize;
+        let size = (stride * height * 4) as u64;
+        if width > stride || size > framebuffer.size {
+            return Err(Error::InvalidSize);
+        }
+
+        let cols = width / font::WIDTH;
+        let rows = height / font::HEIGHT;
+        if cols == 0 || rows == 0 {
+            return Err(Error::InvalidSize);
+        }
+
+        let mut screen = Screen {
+            base: framebuffer.base as *mut u32,
+            stride,
+            format,
+            cols,
+            rows,
+            col: 0,
+            row: 0,
+            attrs: Attributes::new(),
+            parser: Parser::new(),
+        };
+        screen.clear_lines(0, height);
+        Ok(screen)
+    }
+
+    /// Writes the character `c` at the cursor, interpreting the control
+    /// characters and the escape sequences.
+    fn write_char(&mut self, c: char) {
+        match self.parser.push(c) {
+            Action::None => {}
+            Action::Print(c) => self.print(c),
+            Action::Sgr(params, len) => {
+                if len == 0 {
+                    // "\x1b[m" is equivalent to "\x1b[0m".
+                    self.attrs.apply_sgr(0);
+                }
+                params[..len].iter().for_each(|&p| self.attrs.apply_sgr(p));
+            }
+        }
+    }
+
+    /// Prints the character `c`, which is not part of an escape sequence.
+    fn print(&mut self, c: char) {
+        match c {
+            '\n' => self.new_line(),
+            '\r' => self.col = 0,
+            '\t' => {
+                let spaces = TAB_WIDTH - self.col % TAB_WIDTH;
+                for _ in 0..spaces.min(self.cols - self.col) {
+                    self.print(' ');
+                }
+            }
+            '\x08' => self.col = self.col.saturating_sub(1),
+            c if c.is_control() => {}
+            c => {
+                if self.col == self.cols {
+                    self.new_line();
+                }
+                self.draw_glyph(c);
+                self.col += 1;
+            }
+        }
+    }
+
+    /// Moves the cursor to the start of the next line, scrolling the screen
+    /// up if it is at the last line.
+    fn new_line(&mut self) {
+        self.col = 0;
+        if self.row + 1 < self.rows {
+            self.row += 1;
+            return;
+        }
+
+        // Move every line of text, but the first one, one line up and clear
+        // the last one.
+        let line_pixels = font::HEIGHT * self.stride;
+        let last_line = (self.rows - 1) * font::HEIGHT;
+        unsafe {
+            core::ptr::copy(
+                self.base.add(line_pixels),
+                self.base,
+                last_line * self.stride,
+            );
+        }
+        self.clear_lines(last_line, last_line + font::HEIGHT);
+    }
+
+    /// Draws the glyph of `c` at the cursor.
+    fn draw_glyph(&mut self, c: char) {
+        let fg = self.pixel(self.attrs.fg);
+        let bg = self.pixel(self.attrs.bg);
+        let x = self.col * font::WIDTH;
+        let y = self.row * font::HEIGHT;
+
+        for (dy, bits) in font::glyph(c).iter().enumerate() {
+            let line = unsafe { self.base.add((y + dy) * self.stride + x) };
+            for dx in 0..font::WIDTH {
+                let pixel = if bits & (0x80 >> dx) != 0 { fg } else { bg };
+                unsafe { line.add(dx).write_volatile(pixel) };
+            }
+        }
+    }
+
+    /// Fills the scan lines from `start` to `end` (exclusive) with the
+    /// background color.
+    fn clear_lines(&mut self, start: usize, end: usize) {
+        let bg = self.pixel(self.attrs.bg);
+        for i in start * self.stride..end * self.stride {
+            unsafe { self.base.add(i).write_volatile(bg) };
+        }
+    }
+
+    /// Returns the value of a pixel with the color `color` of the palette.
+    fn pixel(&self, color: u8) -> u32 {
+        let (r, g, b) = PALETTE[color as usize];
+        let (r, g, b) = (r as u32, g as u32, b as u32);
+        if self.format == PixelFormat::RGB {
+            r | g << 8 | b << 16
+        } else {
+            b | g << 8 | r << 16
+        }
+    }
+}
+
+/// Colors of the text, as indexes into `PALETTE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Attributes {
+    /// Color of the text.
+    fg: u8,
+
+    /// Color of the background.
+    bg: u8,
+}
+
+impl Attributes {
+    /// Returns the default `Attributes`.
+    const fn new() -> Self {
+        Attributes {
+            fg: DEFAULT_FG,
+            bg: DEFAULT_BG,
+        }
+    }
+
+    /// Applies the SGR parameter `param`. The parameters that do not set a
+    /// color, other than the reset, are ignored.
+    fn apply_sgr(&mut self, param: u16) {
+        match param {
+            0 => *self = Attributes::new(),
+            30..=37 => self.fg = (param - 30) as u8,
+            39 => self.fg = DEFAULT_FG,
+            40..=47 => self.bg = (param - 40) as u8,
+            49 => self.bg = DEFAULT_BG,
+            90..=97 => self.fg = (param - 90 + 8) as u8,
+            100..=107 => self.bg = (param - 100 + 8) as u8,
+            _ => {}
+        }
+    }
+}
+
+/// Result of pushing a character into a `Parser`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    /// The character is part of an escape sequence.
+    None,
+
+    /// The character must be printed.
+    Print(char),
+
+    /// An SGR escape sequence finished with the provided parameters. Only
+    /// the first elements, as many as the second field, are valid.
+    Sgr([u16; MAX_PARAMS], usize),
+}
+
+/// State of a `Parser`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Regular characters.
+    Ground,
+
+    /// After ESC.
+    Escape,
+
+    /// After ESC and `[`, reading the parameters of a control sequence.
+    Csi,
+}
+
+/// Parser of the escape sequences embedded in the console output.
+#[derive(Debug)]
+struct Parser {
+    state: State,
+
+    /// Parameters of the current control sequence.
+    params: [u16; MAX_PARAMS],
+
+    /// Number of parameters of the current control sequence, including the
+    /// one being read.
+    len: usize,
+}
+
+impl Parser {
+    /// Returns a `Parser` in the ground state.
+    const fn new() -> Self {
+        Parser {
+            state: State::Ground,
+            params: [0; MAX_PARAMS],
+            len: 0,
+        }
+    }
+
+    /// Pushes the character `c` and returns what must be done with it.
+    fn push(&mut self, c: char) -> Action {
+        match self.state {
+            State::Ground => {
+                if c == '\x1b' {
+                    self.state = State::Escape;
+                    Action::None
+                } else {
+                    Action::Print(c)
+                }
+            }
+            State::Escape => {
+                if c == '[' {
+                    self.state = State::Csi;
+                    self.params = [0; MAX_PARAMS];
+                    self.len = 0;
+                } else {
+                    // Two-character escape sequences are discarded.
+                    self.state = State::Ground;
+                }
+                Action::None
+            }
+            State::Csi => match c {
+                '0'..='9' => {
+                    if self.len == 0 {
+                        self.len = 1;
+                    }
+                    if let Some(param) = self.params.get_mut(self.len - 1) {
+                        let digit = c as u16 - '0' as u16;
+                        *param =
+                            param.saturating_mul(10).saturating_add(digit);
+                    }
+                    Action::None
+                }
+                ';' => {
+                    // An empty parameter is zero.
+                    self.len = self.len.max(1) + 1;
+                    Action::None
+                }
+                '\x40'..='\x7e' => {
+                    self.state = State::Ground;
+                    if c == 'm' {
+                        Action::Sgr(self.params, self.len.min(MAX_PARAMS))
+                    } else {
+                        Action::None
+                    }
+                }
+                _ => Action::None,
+            },
+        }
+    }
+}
+
+/// Initializes the framebuffer console and registers it in the console.
+///
+/// # Safety
+///
+/// This function must be called once. The framebuffer must be identity
+/// mapped.
+pub unsafe fn init(framebuffer: Option<&Framebuffer>) -> Result<(), Error> {
+    let framebuffer = framebuffer.ok_or(Error::NotAvailable)?;
+    let screen = Screen::new(framebuffer)?;
+    *FBCON.screen.lock() = Some(screen);
+
+    console::register("fbcon", &FBCON, &FBCON_ENABLED).ok();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pushes `s` into `parser` and returns the SGR parameters of the last
+    /// sequence, if any, and the printed characters.
+    fn parse(
+        parser: &mut Parser,
+        s: &str,
+    ) -> (Option<[u16; MAX_PARAMS]>, usize) {
+        let mut sgr = None;
+        let mut printed = 0;
+        for c in s.chars() {
+            match parser.push(c) {
+                Action::None => {}
+                Action::Print(_) => printed += 1,
+                Action::Sgr(params, _) => sgr = Some(params),
+            }
+        }
+        (sgr, printed)
+    }
+
+    #[test]
+    fn test_parser() {
+        let mut parser = Parser::new();
+        assert_eq!(parse(&mut parser, "abc"), (None, 3));
+        assert_eq!(
+            parse(&mut parser, "\x1b[1;31mx"),
+            (Some([1, 31, 0, 0]), 1)
+        );
+        assert_eq!(parse(&mut parser, "\x1b[2Jx\x1b7y"), (None, 2));
+        assert_eq!(parser.push('\x1b'), Action::None);
+        assert_eq!(parser.push('['), Action::None);
+        assert_eq!(parser.push('m'), Action::Sgr([0; MAX_PARAMS], 0));
+        assert_eq!(
+            parse(&mut parser, "\x1b[;1;2;3;4;5m"),
+            (Some([0, 1, 2, 3]), 0)
+        );
+    }
+
+    #[test]
+    fn test_apply_sgr() {
+        let mut attrs = Attributes::new();
+        attrs.apply_sgr(31);
+        attrs.apply_sgr(104);
+        assert_eq!(attrs, Attributes { fg: 1, bg: 12 });
+        attrs.apply_sgr(97);
+        attrs.apply_sgr(1);
+        assert_eq!(attrs, Attributes { fg: 15, bg: 12 });
+        attrs.apply_sgr(49);
+        assert_eq!(
+            attrs,
+            Attributes {
+                fg: 15,
+                bg: DEFAULT_BG
+            }
+        );
+        attrs.apply_sgr(0);
+        assert_eq!(attrs, Attributes::new());
+    }
+}
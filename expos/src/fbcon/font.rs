@@ -0,0 +1,505 @@
+//! 8x16 bitmap font.
+//!
+//! It covers the printable ASCII characters. Every glyph is a row of 8 pixels
+//! per byte, from top to bottom, with the most significant bit as the
+//! leftmost pixel. The last column and the top rows are blank, so the
+//! characters of consecutive cells do not touch.
+
+/// Width of a glyph in pixels.
+pub const WIDTH: usize = 8;
+
+/// Height of a glyph in pixels.
+pub const HEIGHT: usize = 16;
+
+/// Glyph of the characters without one.
+const REPLACEMENT: [u8; HEIGHT] = [
+    0x00, 0x00, 0x00, 0xfe, 0x82, 0x82, 0x82, 0x82, 0x82, 0x82, 0x82, 0xfe,
+    0x00, 0x00, 0x00, 0x00,
+];
+
+/// Glyphs of the printable ASCII characters, from `' '` to `'~'`.
+static GLYPHS: [[u8; HEIGHT]; 95] = [
+    // ' '
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '!'
+    [
+        0x00, 0x00, 0x00, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x00, 0x10,
+        0x10, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '"'
+    [
+        0x00, 0x00, 0x00, 0x28, 0x28, 0x28, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '#'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x28, 0x28, 0xfe, 0x28, 0x28, 0xfe, 0x28,
+        0x28, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '$'
+    [
+        0x00, 0x00, 0x00, 0x10, 0x7c, 0x90, 0x90, 0x7c, 0x12, 0x12, 0x7c,
+        0x10, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '%'
+    [
+        0x00, 0x00, 0x00, 0x62, 0x92, 0x64, 0x08, 0x10, 0x20, 0x4c, 0x92,
+        0x8c, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '&'
+    [
+        0x00, 0x00, 0x00, 0x30, 0x48, 0x48, 0x30, 0x60, 0x92, 0x8c, 0x88,
+        0x76, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '\''
+    [
+        0x00, 0x00, 0x00, 0x10, 0x10, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '('
+    [
+        0x00, 0x00, 0x00, 0x08, 0x10, 0x20, 0x20, 0x20, 0x20, 0x20, 0x10,
+        0x08, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // ')'
+    [
+        0x00, 0x00, 0x00, 0x20, 0x10, 0x08, 0x08, 0x08, 0x08, 0x08, 0x10,
+        0x20, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '*'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x92, 0x7c, 0x92, 0x10, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '+'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x10, 0xfe, 0x10, 0x10, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // ','
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18,
+        0x18, 0x08, 0x10, 0x00, 0x00,
+    ],
+    // '-'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xfe, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '.'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18,
+        0x18, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '/'
+    [
+        0x00, 0x00, 0x00, 0x02, 0x04, 0x04, 0x08, 0x10, 0x20, 0x40, 0x40,
+        0x80, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '0'
+    [
+        0x00, 0x00, 0x00, 0x7c, 0x82, 0x86, 0x8a, 0x92, 0xa2, 0xc2, 0x82,
+        0x7c, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '1'
+    [
+        0x00, 0x00, 0x00, 0x10, 0x30, 0x50, 0x10, 0x10, 0x10, 0x10, 0x10,
+        0x7c, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '2'
+    [
+        0x00, 0x00, 0x00, 0x7c, 0x82, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40,
+        0xfe, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '3'
+    [
+        0x00, 0x00, 0x00, 0x7c, 0x82, 0x02, 0x02, 0x3c, 0x02, 0x02, 0x82,
+        0x7c, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '4'
+    [
+        0x00, 0x00, 0x00, 0x04, 0x0c, 0x14, 0x24, 0x44, 0xfe, 0x04, 0x04,
+        0x04, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '5'
+    [
+        0x00, 0x00, 0x00, 0xfe, 0x80, 0x80, 0xfc, 0x02, 0x02, 0x02, 0x82,
+        0x7c, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '6'
+    [
+        0x00, 0x00, 0x00, 0x3c, 0x40, 0x80, 0xfc, 0x82, 0x82, 0x82, 0x82,
+        0x7c, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '7'
+    [
+        0x00, 0x00, 0x00, 0xfe, 0x02, 0x04, 0x08, 0x10, 0x10, 0x10, 0x10,
+        0x10, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '8'
+    [
+        0x00, 0x00, 0x00, 0x7c, 0x82, 0x82, 0x82, 0x7c, 0x82, 0x82, 0x82,
+        0x7c, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '9'
+    [
+        0x00, 0x00, 0x00, 0x7c, 0x82, 0x82, 0x82, 0x7e, 0x02, 0x02, 0x04,
+        0x78, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // ':'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x00, 0x00, 0x18,
+        0x18, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // ';'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x00, 0x00, 0x18,
+        0x18, 0x08, 0x10, 0x00, 0x00,
+    ],
+    // '<'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x04, 0x08, 0x10, 0x20, 0x10, 0x08, 0x04,
+        0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '='
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xfe, 0x00, 0xfe, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '>'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x40, 0x20, 0x10, 0x08, 0x10, 0x20, 0x40,
+        0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '?'
+    [
+        0x00, 0x00, 0x00, 0x7c, 0x82, 0x02, 0x04, 0x08, 0x10, 0x10, 0x00,
+        0x10, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '@'
+    [
+        0x00, 0x00, 0x00, 0x7c, 0x82, 0x9e, 0xa2, 0xa2, 0xa6, 0x9a, 0x80,
+        0x7e, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'A'
+    [
+        0x00, 0x00, 0x00, 0x10, 0x28, 0x44, 0x82, 0x82, 0xfe, 0x82, 0x82,
+        0x82, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'B'
+    [
+        0x00, 0x00, 0x00, 0xfc, 0x82, 0x82, 0x82, 0xfc, 0x82, 0x82, 0x82,
+        0xfc, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'C'
+    [
+        0x00, 0x00, 0x00, 0x7c, 0x82, 0x80, 0x80, 0x80, 0x80, 0x80, 0x82,
+        0x7c, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'D'
+    [
+        0x00, 0x00, 0x00, 0xf8, 0x84, 0x82, 0x82, 0x82, 0x82, 0x82, 0x84,
+        0xf8, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'E'
+    [
+        0x00, 0x00, 0x00, 0xfe, 0x80, 0x80, 0x80, 0xf8, 0x80, 0x80, 0x80,
+        0xfe, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'F'
+    [
+        0x00, 0x00, 0x00, 0xfe, 0x80, 0x80, 0x80, 0xf8, 0x80, 0x80, 0x80,
+        0x80, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'G'
+    [
+        0x00, 0x00, 0x00, 0x7c, 0x82, 0x80, 0x80, 0x9e, 0x82, 0x82, 0x82,
+        0x7c, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'H'
+    [
+        0x00, 0x00, 0x00, 0x82, 0x82, 0x82, 0x82, 0xfe, 0x82, 0x82, 0x82,
+        0x82, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'I'
+    [
+        0x00, 0x00, 0x00, 0x7c, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10,
+        0x7c, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'J'
+    [
+        0x00, 0x00, 0x00, 0x3e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x84, 0x84,
+        0x78, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'K'
+    [
+        0x00, 0x00, 0x00, 0x82, 0x84, 0x88, 0x90, 0xe0, 0x90, 0x88, 0x84,
+        0x82, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'L'
+    [
+        0x00, 0x00, 0x00, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80,
+        0xfe, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'M'
+    [
+        0x00, 0x00, 0x00, 0x82, 0xc6, 0xaa, 0x92, 0x92, 0x82, 0x82, 0x82,
+        0x82, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'N'
+    [
+        0x00, 0x00, 0x00, 0x82, 0xc2, 0xa2, 0xa2, 0x92, 0x8a, 0x8a, 0x86,
+        0x82, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'O'
+    [
+        0x00, 0x00, 0x00, 0x7c, 0x82, 0x82, 0x82, 0x82, 0x82, 0x82, 0x82,
+        0x7c, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'P'
+    [
+        0x00, 0x00, 0x00, 0xfc, 0x82, 0x82, 0x82, 0xfc, 0x80, 0x80, 0x80,
+        0x80, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'Q'
+    [
+        0x00, 0x00, 0x00, 0x7c, 0x82, 0x82, 0x82, 0x82, 0x82, 0x8a, 0x84,
+        0x7a, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'R'
+    [
+        0x00, 0x00, 0x00, 0xfc, 0x82, 0x82, 0x82, 0xfc, 0x90, 0x88, 0x84,
+        0x82, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'S'
+    [
+        0x00, 0x00, 0x00, 0x7c, 0x82, 0x80, 0x80, 0x7c, 0x02, 0x02, 0x82,
+        0x7c, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'T'
+    [
+        0x00, 0x00, 0x00, 0xfe, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10,
+        0x10, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'U'
+    [
+        0x00, 0x00, 0x00, 0x82, 0x82, 0x82, 0x82, 0x82, 0x82, 0x82, 0x82,
+        0x7c, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'V'
+    [
+        0x00, 0x00, 0x00, 0x82, 0x82, 0x82, 0x82, 0x44, 0x44, 0x28, 0x28,
+        0x10, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'W'
+    [
+        0x00, 0x00, 0x00, 0x82, 0x82, 0x82, 0x82, 0x92, 0x92, 0xaa, 0xc6,
+        0x82, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'X'
+    [
+        0x00, 0x00, 0x00, 0x82, 0x82, 0x44, 0x28, 0x10, 0x28, 0x44, 0x82,
+        0x82, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'Y'
+    [
+        0x00, 0x00, 0x00, 0x82, 0x82, 0x44, 0x28, 0x10, 0x10, 0x10, 0x10,
+        0x10, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'Z'
+    [
+        0x00, 0x00, 0x00, 0xfe, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80,
+        0xfe, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '['
+    [
+        0x00, 0x00, 0x00, 0x3c, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20,
+        0x3c, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '\\'
+    [
+        0x00, 0x00, 0x00, 0x80, 0x40, 0x40, 0x20, 0x10, 0x08, 0x04, 0x04,
+        0x02, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // ']'
+    [
+        0x00, 0x00, 0x00, 0x78, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08,
+        0x78, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '^'
+    [
+        0x00, 0x00, 0x00, 0x10, 0x28, 0x44, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '_'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0xfe, 0x00, 0x00,
+    ],
+    // '`'
+    [
+        0x00, 0x00, 0x00, 0x20, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'a'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7c, 0x02, 0x7e, 0x82, 0x86,
+        0x7a, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'b'
+    [
+        0x00, 0x00, 0x00, 0x80, 0x80, 0x80, 0xbc, 0xc2, 0x82, 0x82, 0xc2,
+        0xbc, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'c'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7c, 0x82, 0x80, 0x80, 0x82,
+        0x7c, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'd'
+    [
+        0x00, 0x00, 0x00, 0x02, 0x02, 0x02, 0x7a, 0x86, 0x82, 0x82, 0x86,
+        0x7a, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'e'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7c, 0x82, 0xfe, 0x80, 0x82,
+        0x7c, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'f'
+    [
+        0x00, 0x00, 0x00, 0x1c, 0x22, 0x20, 0xfc, 0x20, 0x20, 0x20, 0x20,
+        0x20, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'g'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7a, 0x86, 0x82, 0x82, 0x86,
+        0x7a, 0x02, 0x82, 0x7c, 0x00,
+    ],
+    // 'h'
+    [
+        0x00, 0x00, 0x00, 0x80, 0x80, 0x80, 0xbc, 0xc2, 0x82, 0x82, 0x82,
+        0x82, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'i'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x30, 0x10, 0x10, 0x10, 0x10,
+        0x38, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'j'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x04, 0x00, 0x0c, 0x04, 0x04, 0x04, 0x04,
+        0x04, 0x04, 0x84, 0x78, 0x00,
+    ],
+    // 'k'
+    [
+        0x00, 0x00, 0x00, 0x80, 0x80, 0x80, 0x84, 0x88, 0xb0, 0xc8, 0x84,
+        0x82, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'l'
+    [
+        0x00, 0x00, 0x00, 0x30, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10,
+        0x38, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'm'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xec, 0x92, 0x92, 0x92, 0x92,
+        0x92, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'n'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xbc, 0xc2, 0x82, 0x82, 0x82,
+        0x82, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'o'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7c, 0x82, 0x82, 0x82, 0x82,
+        0x7c, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'p'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xbc, 0xc2, 0x82, 0x82, 0xc2,
+        0xbc, 0x80, 0x80, 0x80, 0x00,
+    ],
+    // 'q'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7a, 0x86, 0x82, 0x82, 0x86,
+        0x7a, 0x02, 0x02, 0x02, 0x00,
+    ],
+    // 'r'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xbc, 0xc2, 0x80, 0x80, 0x80,
+        0x80, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 's'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7e, 0x80, 0x7c, 0x02, 0x02,
+        0xfc, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 't'
+    [
+        0x00, 0x00, 0x00, 0x20, 0x20, 0x20, 0xfc, 0x20, 0x20, 0x20, 0x22,
+        0x1c, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'u'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x82, 0x82, 0x82, 0x82, 0x86,
+        0x7a, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'v'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x82, 0x82, 0x44, 0x44, 0x28,
+        0x10, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'w'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x82, 0x82, 0x92, 0x92, 0xaa,
+        0x44, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'x'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x82, 0x44, 0x28, 0x28, 0x44,
+        0x82, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // 'y'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x82, 0x82, 0x82, 0x82, 0x86,
+        0x7a, 0x02, 0x82, 0x7c, 0x00,
+    ],
+    // 'z'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xfe, 0x04, 0x08, 0x20, 0x40,
+        0xfe, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '{'
+    [
+        0x00, 0x00, 0x00, 0x0c, 0x10, 0x10, 0x10, 0x60, 0x10, 0x10, 0x10,
+        0x0c, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '|'
+    [
+        0x00, 0x00, 0x00, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10,
+        0x10, 0x10, 0x10, 0x00, 0x00,
+    ],
+    // '}'
+    [
+        0x00, 0x00, 0x00, 0x60, 0x10, 0x10, 0x10, 0x0c, 0x10, 0x10, 0x10,
+        0x60, 0x00, 0x00, 0x00, 0x00,
+    ],
+    // '~'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x62, 0x9c, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+];
+
+/// Returns the glyph of `c`.
+pub fn glyph(c: char) -> &'static [u8; HEIGHT] {
+    match c {
+        ' '..='~' => &GLYPHS[c as usize - ' ' as usize],
+        _ => &REPLACEMENT,
+    }
+}
@@ -0,0 +1,236 @@
+//! Global Descriptor Table and Task State Segment.
+//!
+//! In 64-bit mode, segmentation is mostly disabled. Nevertheless, the kernel
+//! needs its own GDT to hold a TSS, which provides the Interrupt Stack Table.
+//! The exceptions that can be caused by a corrupted stack (e.g. the double
+//! fault) and the NMIs, which can be raised before the system call entry
+//! switches stacks, are handled on known good stacks taken from it. The TSS
+//! also provides the stack used when an interrupt is raised in user mode.
+//!
+//! The user segments follow the kernel ones, in the order required by
+//! `sysret`: the user data segment at `SYSRET_BASE_SELECTOR + 8` and the
+//! 64-bit user code segment at `SYSRET_BASE_SELECTOR + 16`.
+//!
+//! Every CPU has its own GDT and TSS, given that a TSS is marked as busy when
+//! it is loaded.
+
+use core::cell::UnsafeCell;
+use core::mem;
+
+use cpu::DescriptorTablePointer;
+use percpu::MAX_CPUS;
+
+/// Kernel code segment selector.
+pub const KERNEL_CODE_SELECTOR: u16 = 0x08;
+
+/// Kernel data segment selector.
+pub const KERNEL_DATA_SELECTOR: u16 = 0x10;
+
+/// Selector used as base by `sysret` to compute the user selectors.
+pub const SYSRET_BASE_SELECTOR: u16 = 0x10;
+
+/// User data segment selector, with RPL 3.
+pub const USER_DATA_SELECTOR: u16 = 0x18 | 3;
+
+/// User code segment selector, with RPL 3.
+pub const USER_CODE_SELECTOR: u16 = 0x20 | 3;
+
+/// TSS selector.
+const TSS_SELECTOR: u16 = 0x28;
+
+/// Interrupt Stack Table index used by the double fault handler. The IST
+/// indexes start at 1, 0 means that the current stack is used.
+pub const DOUBLE_FAULT_IST: u8 = 1;
+
+/// Interrupt Stack Table index used by the NMI handler. An NMI can be raised
+/// at any instruction, including the system call entry before switching to
+/// the kernel stack.
+pub const NMI_IST: u8 = 2;
+
+/// Size of the interrupt stacks.
+const IST_STACK_SIZE: usize = 16 * 1024;
+
+/// 64-bit kernel code segment descriptor.
+const KERNEL_CODE_DESCRIPTOR: u64 = 0x00af_9a00_0000_ffff;
+
+/// Kernel data segment descriptor.
+const KERNEL_DATA_DESCRIPTOR: u64 = 0x00cf_9200_0000_ffff;
+
+/// User data segment descriptor.
+const USER_DATA_DESCRIPTOR: u64 = 0x00cf_f200_0000_ffff;
+
+/// 64-bit user code segment descriptor.
+const USER_CODE_DESCRIPTOR: u64 = 0x00af_fa00_0000_ffff;
+
+/// Number of entries of the GDT. The TSS descriptor takes two entries.
+const GDT_ENTRIES: usize = 7;
+
+/// Per-CPU descriptor tables. The tables at index `n` belong to CPU `n`.
+static TABLES: Tables = Tables(UnsafeCell::new([CpuTables::NEW; MAX_CPUS]));
+
+/// 64-bit Task State Segment.
+#[repr(C, packed)]
+struct TaskStateSegment {
+    reserved0: u32,
+
+    /// Stack pointers loaded on privilege level changes. `rsp[n]` is loaded
+    /// when an interrupt switches to ring `n`.
+    rsp: [u64; 3],
+
+    reserved1: u64,
+
+    /// Interrupt Stack Table. `ist[n]` is the stack with IST index `n + 1`.
+    ist: [u64; 7],
+
+    reserved2: u64,
+    reserved3: u16,
+
+    /// Offset of the IO permission bitmap from the base of the TSS.
+    iomap_base: u16,
+}
+
+/// Stack used by the exception handlers.
+#[repr(C, align(16))]
+struct Stack([u8; IST_STACK_SIZE]);
+
+/// Global Descriptor Table.
+#[repr(C, align(8))]
+struct Gdt([u64; GDT_ENTRIES]);
+
+/// Descriptor tables of a CPU.
+struct CpuTables {
+    /// Global Descriptor Table.
+    gdt: Gdt,
+
+    /// Task State Segment referenced by the GDT.
+    tss: TaskStateSegment,
+
+    /// Stack used by the double fault handler.
+    double_fault_stack: Stack,
+
+    /// Stack used by the NMI handler.
+    nmi_stack: Stack,
+}
+
+impl CpuTables {
+    /// Descriptor tables before initialization.
+    const NEW: CpuTables = CpuTables {
+        gdt: Gdt([0; GDT_ENTRIES]),
+        tss: TaskStateSegment {
+            reserved0: 0,
+            rsp: [0; 3],
+            reserved1: 0,
+            ist: [0; 7],
+            reserved2: 0,
+            reserved3: 0,
+            // There is no IO permission bitmap, so user mode cannot access
+            // the IO ports.
+            iomap_base: mem::size_of::<TaskStateSegment>() as u16,
+        },
+        double_fault_stack: Stack([0; IST_STACK_SIZE]),
+        nmi_stack: Stack([0; IST_STACK_SIZE]),
+    };
+}
+
+/// Descriptor tables of all the CPUs.
+struct Tables(UnsafeCell<[CpuTables; MAX_CPUS]>);
+
+// Every CPU only accesses its own tables.
+unsafe impl Sync for Tables {}
+
+/// Returns the 64-bit TSS descriptor of the TSS at the address `base`. It
+/// takes two GDT entries.
+fn tss_descriptor(base: u64) -> [u64; 2] {
+    let limit = (mem::size_of::<TaskStateSegment>() - 1) as u64;
+
+    let low = (limit & 0xffff)
+        | (base & 0xff_ffff) << 16
+        // Present, 64-bit TSS (available).
+        | 0x89 << 40
+        | (limit >> 16 & 0xf) << 48
+        | (base >> 24 & 0xff) << 56;
+    let high = base >> 32;
+
+    [low, high]
+}
+
+/// Loads the GDT and the TSS of the current CPU and reloads the segment
+/// registers.
+///
+/// # Safety
+///
+/// This function must be called once on every CPU, after `percpu::init`.
+/// The current stack must stay mapped, given that SS is reloaded.
+pub unsafe fn init() {
+    // Every CPU only accesses its own tables and this function is called
+    // once per CPU. So, there are no other references to them.
+    let tables = TABLES.0.get() as *mut CpuTables;
+    let tables = &mut *tables.add(percpu::cpu_index());
+
+    let stack = &tables.double_fault_stack as *const Stack;
+    tables.tss.ist[usize::from(DOUBLE_FAULT_IST - 1)] =
+        stack as u64 + mem::size_of::<Stack>() as u64;
+    let stack = &tables.nmi_stack as *const Stack;
+    tables.tss.ist[usize::from(NMI_IST - 1)] =
+        stack as u64 + mem::size_of::<Stack>() as u64;
+
+    let tss = &tables.tss as *const TaskStateSegment;
+    let [tss_low, tss_high] = tss_descriptor(tss as u64);
+    tables.gdt.0 = [
+        0,
+        KERNEL_CODE_DESCRIPTOR,
+        KERNEL_DATA_DESCRIPTOR,
+        USER_DATA_DESCRIPTOR,
+        USER_CODE_DESCRIPTOR,
+        tss_low,
+        tss_high,
+    ];
+
+    let gdtr = DescriptorTablePointer::new(
+        &tables.gdt as *const Gdt as u64,
+        mem::size_of::<Gdt>(),
+    );
+    cpu::lgdt(&gdtr);
+    cpu::load_cs(KERNEL_CODE_SELECTOR);
+    cpu::load_data_segments(KERNEL_DATA_SELECTOR);
+    cpu::ltr(TSS_SELECTOR);
+}
+
+/// Sets the stack loaded when an interrupt is raised while the current CPU
+/// runs in user mode. `top` is the initial stack pointer.
+///
+/// # Safety
+///
+/// The stack must be mapped and not in use. `init` must have been called on
+/// the current CPU.
+pub unsafe fn set_kernel_stack(top: u64) {
+    // Every CPU only accesses its own tables, and the processor only reads
+    // the stack pointer on privilege level changes.
+    let tables = TABLES.0.get() as *mut CpuTables;
+    let tss = &mut (*tables.add(percpu::cpu_index())).tss;
+    tss.rsp[0] = top;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tss_descriptor() {
+        assert_eq!(
+            tss_descriptor(0xffff_8000_1234_5678),
+            [0x1200_8934_5678_0067, 0xffff_8000]
+        );
+    }
+
+    #[test]
+    fn test_sysret_selectors() {
+        assert_eq!(USER_DATA_SELECTOR & !3, SYSRET_BASE_SELECTOR + 8);
+        assert_eq!(USER_CODE_SELECTOR & !3, SYSRET_BASE_SELECTOR + 16);
+    }
+
+    #[test]
+    fn test_tss_size() {
+        assert_eq!(mem::size_of::<TaskStateSegment>(), 0x68);
+    }
+}
@@ -0,0 +1,71 @@
+//! Kernel heap.
+//!
+//! The heap lives in a reserved virtual region, which is backed by frames
+//! from the frame allocator when the heap is initialized. It is the global
+//! allocator of the kernel, so the `alloc` crate can be used afterwards.
+
+use mm::heap::LockedHeap;
+use mm::paging::PageSize;
+use mm::VirtAddr;
+
+use crate::{paging, pmm};
+
+/// Start of the virtual region reserved for the heap.
+const HEAP_START: u64 = 0xffff_9000_0000_0000;
+
+/// Size of the heap.
+const HEAP_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Kernel heap. The allocator of the host is used in the tests.
+#[cfg_attr(not(test), global_allocator)]
+static HEAP: LockedHeap = LockedHeap::empty();
+
+/// Heap errors.
+#[derive(Debug)]
+pub enum Error {
+    /// There are no free frames to back the heap.
+    NoMemory,
+
+    /// The heap could not be mapped.
+    Map(mm::paging::Error),
+}
+
+/// Maps the heap region and hands it over to the global allocator. The
+/// allocations fail until this function is called.
+///
+/// # Safety
+///
+/// This function must be called once, after `pmm::init`. The current page
+/// tables must map the physical memory at `mm::phys_offset()`.
+pub unsafe fn init() -> Result<(), Error> {
+    let mut mapper = paging::current_mapper();
+
+    let page_size = PageSize::Size4KiB.bytes();
+    for offset in (0..HEAP_SIZE).step_by(page_size as usize) {
+        let frame = pmm::alloc_frame().ok_or(Error::NoMemory)?;
+        mapper
+            .map_to(
+                VirtAddr::new(HEAP_START + offset),
+                frame,
+                PageSize::Size4KiB,
+                paging::data_flags(),
+                &mut pmm::PageTableFrames,
+            )
+            .map_err(Error::Map)?;
+    }
+
+    HEAP.add_region(HEAP_START as usize, HEAP_SIZE as usize);
+    Ok(())
+}
+
+/// Returns the size of the heap and the number of bytes allocated.
+pub fn stats() -> (usize, usize) {
+    HEAP.stats()
+}
+
+/// Handler of the allocation failures.
+#[cfg(not(test))]
+#[alloc_error_handler]
+fn alloc_error(layout: core::alloc::Layout) -> ! {
+    panic!("heap: allocation of {:?} failed", layout);
+}
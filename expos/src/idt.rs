@@ -0,0 +1,717 @@
+//! Interrupt Descriptor Table and CPU exception handlers.
+//!
+//! The `InterruptDescriptorTable` has one entry per architectural exception,
+//! typed after the signature of its handler, followed by the entries of the
+//! external interrupts. `init` loads a table whose exception entries point to
+//! default handlers that report the faulting context and panic. So, faults
+//! are reported instead of resetting the machine with a triple fault. The
+//! faults raised in user mode only terminate the faulting task.
+//!
+//! The handlers that can interrupt user mode and access per-CPU data must
+//! load the kernel GS base first (see `KernelGs`).
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+
+use cpu::DescriptorTablePointer;
+use ticket_mutex::TicketMutex;
+
+use crate::{gdt, stack, task, warn};
+
+/// Number of vectors reserved for the architectural exceptions.
+const EXCEPTION_VECTORS: u8 = 32;
+
+/// Number of external interrupt vectors.
+const INTERRUPT_VECTORS: usize = 224;
+
+/// Vector of the IRQ 0 of the legacy PICs. The PIC IRQs take the vectors from
+/// `PIC_VECTOR_BASE` to `PIC_VECTOR_BASE + 15`.
+pub const PIC_VECTOR_BASE: u8 = 0x20;
+
+/// Vector of the local APIC timer.
+pub const TIMER_VECTOR: u8 = 0x30;
+
+/// Vector of the keyboard IRQ when it is routed through the IO APIC.
+pub const KEYBOARD_VECTOR: u8 = 0x31;
+
+/// Vector of the TLB shootdown IPIs.
+pub const TLB_SHOOTDOWN_VECTOR: u8 = 0xf0;
+
+/// Vector of the spurious interrupts of the local APIC.
+pub const SPURIOUS_VECTOR: u8 = 0xff;
+
+/// Page fault error code bit that is set if the fault was caused by a
+/// protection violation instead of a non-present page.
+const PF_PROTECTION_VIOLATION: u64 = 1 << 0;
+
+/// Page fault error code bit that is set if the fault was caused by a write.
+const PF_WRITE: u64 = 1 << 1;
+
+/// Page fault error code bit that is set if the fault was caused by an
+/// instruction fetch.
+const PF_INSTRUCTION_FETCH: u64 = 1 << 4;
+
+/// Exit status of the user tasks terminated by an exception.
+const USER_FAULT_STATUS: i32 = -1;
+
+/// Interrupt Descriptor Table shared by all the CPUs.
+static IDT: TicketMutex<InterruptDescriptorTable> =
+    TicketMutex::new(InterruptDescriptorTable::new());
+
+/// Handler of an exception or interrupt without error code.
+pub type HandlerFunc = extern "x86-interrupt" fn(InterruptStackFrame);
+
+/// Handler of an exception with error code.
+pub type HandlerFuncWithErrCode =
+    extern "x86-interrupt" fn(InterruptStackFrame, u64);
+
+/// Handler of an abort without error code. It cannot return.
+pub type DivergingHandlerFunc =
+    extern "x86-interrupt" fn(InterruptStackFrame) -> !;
+
+/// Handler of an abort with error code. It cannot return.
+pub type DivergingHandlerFuncWithErrCode =
+    extern "x86-interrupt" fn(InterruptStackFrame, u64) -> !;
+
+/// Stack frame pushed by the CPU when an exception or interrupt is raised.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct InterruptStackFrame {
+    /// Instruction pointer. It points to the faulting instruction on faults
+    /// and to the next instruction on traps.
+    rip: u64,
+
+    /// Code segment selector.
+    cs: u64,
+
+    /// RFLAGS register.
+    rflags: u64,
+
+    /// Stack pointer.
+    rsp: u64,
+
+    /// Stack segment selector.
+    ss: u64,
+}
+
+impl InterruptStackFrame {
+    /// Returns the instruction pointer of the interrupted context.
+    pub fn rip(&self) -> u64 {
+        self.rip
+    }
+
+    /// Returns the code segment selector of the interrupted context.
+    pub fn cs(&self) -> u64 {
+        self.cs
+    }
+
+    /// Returns the RFLAGS register of the interrupted context.
+    pub fn rflags(&self) -> u64 {
+        self.rflags
+    }
+
+    /// Returns the stack pointer of the interrupted context.
+    pub fn rsp(&self) -> u64 {
+        self.rsp
+    }
+
+    /// Returns the stack segment selector of the interrupted context.
+    pub fn ss(&self) -> u64 {
+        self.ss
+    }
+
+    /// Returns `true` if the interrupted context runs in user mode.
+    pub fn is_user(&self) -> bool {
+        self.cs & 3 == 3
+    }
+}
+
+impl fmt::Display for InterruptStackFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "RIP:    {:#018x}  CS: {:#06x}", self.rip(), self.cs())?;
+        writeln!(f, "RSP:    {:#018x}  SS: {:#06x}", self.rsp(), self.ss())?;
+        writeln!(f, "RFLAGS: {:#018x}", self.rflags())?;
+        writeln!(
+            f,
+            "CR0:    {:#018x}  CR3: {:#018x}",
+            cpu::read_cr0(),
+            cpu::read_cr3()
+        )?;
+        write!(
+            f,
+            "CR2:    {:#018x}  CR4: {:#018x}",
+            cpu::read_cr2(),
+            cpu::read_cr4()
+        )
+    }
+}
+
+/// Guard that loads the kernel GS base while an interrupt handler runs. The
+/// GS base of the interrupted context is restored when it is dropped.
+///
+/// The kernel GS base points to the per-CPU area, but it is kept in
+/// IA32_KERNEL_GS_BASE while user mode runs.
+pub struct KernelGs {
+    /// `swapgs` was executed on entry, so it must be executed on exit.
+    swapped: bool,
+}
+
+impl KernelGs {
+    /// Loads the kernel GS base if the interrupted context `frame` runs in
+    /// user mode. It must be called before accessing per-CPU data.
+    pub fn enter(frame: &InterruptStackFrame) -> KernelGs {
+        let swapped = frame.is_user();
+        if swapped {
+            unsafe { cpu::swapgs() };
+        }
+        KernelGs { swapped }
+    }
+
+    /// Loads the kernel GS base if it is not loaded. Unlike `enter`, it does
+    /// not rely on the privilege level of the interrupted context. So, it is
+    /// used by the handlers that can interrupt the kernel entry and exit
+    /// paths before `swapgs`, like the NMI handler.
+    pub fn enter_paranoid() -> KernelGs {
+        let swapped = !percpu::is_area_loaded();
+        if swapped {
+            unsafe { cpu::swapgs() };
+        }
+        KernelGs { swapped }
+    }
+}
+
+impl Drop for KernelGs {
+    fn drop(&mut self) {
+        if self.swapped {
+            unsafe { cpu::swapgs() };
+        }
+    }
+}
+
+/// Entry of the IDT. `F` is the type of its handler.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Entry<F> {
+    /// Bits 0..16 of the handler address.
+    offset_low: u16,
+
+    /// Code segment selector of the handler.
+    selector: u16,
+
+    /// IST index (bits 0..3), gate type (bits 8..12), DPL (bits 13..15) and
+    /// present (bit 15) fields.
+    options: u16,
+
+    /// Bits 16..32 of the handler address.
+    offset_mid: u16,
+
+    /// Bits 32..64 of the handler address.
+    offset_high: u32,
+
+    reserved: u32,
+
+    /// Type of the handler.
+    _handler: PhantomData<F>,
+}
+
+impl<F> Entry<F> {
+    /// Returns a non-present entry. Raising its vector causes a general
+    /// protection fault.
+    const fn missing() -> Self {
+        Entry {
+            offset_low: 0,
+            selector: 0,
+            options: 0,
+            offset_mid: 0,
+            offset_high: 0,
+            reserved: 0,
+            _handler: PhantomData,
+        }
+    }
+
+    /// Sets the handler address to `addr`. The entry is a present interrupt
+    /// gate, so interrupts are disabled while running the handler.
+    fn set_handler_addr(&mut self, addr: u64) {
+        self.offset_low = addr as u16;
+        self.offset_mid = (addr >> 16) as u16;
+        self.offset_high = (addr >> 32) as u32;
+        self.selector = gdt::KERNEL_CODE_SELECTOR;
+        // Present, DPL 0, 64-bit interrupt gate.
+        self.options = (self.options & 0x7) | 0x8e00;
+    }
+
+    /// Runs the handler on the stack with IST index `ist`, instead of the
+    /// current stack. The index 0 disables the IST switch.
+    pub fn set_ist(&mut self, ist: u8) {
+        self.options = (self.options & !0x7) | u16::from(ist & 0x7);
+    }
+}
+
+impl Entry<HandlerFunc> {
+    /// Sets the handler of the entry.
+    pub fn set_handler(&mut self, handler: HandlerFunc) {
+        self.set_handler_addr(handler as usize as u64);
+    }
+}
+
+impl Entry<HandlerFuncWithErrCode> {
+    /// Sets the handler of the entry.
+    pub fn set_handler(&mut self, handler: HandlerFuncWithErrCode) {
+        self.set_handler_addr(handler as usize as u64);
+    }
+}
+
+impl Entry<DivergingHandlerFunc> {
+    /// Sets the handler of the entry.
+    pub fn set_handler(&mut self, handler: DivergingHandlerFunc) {
+        self.set_handler_addr(handler as usize as u64);
+    }
+}
+
+impl Entry<DivergingHandlerFuncWithErrCode> {
+    /// Sets the handler of the entry.
+    pub fn set_handler(&mut self, handler: DivergingHandlerFuncWithErrCode) {
+        self.set_handler_addr(handler as usize as u64);
+    }
+}
+
+/// Interrupt Descriptor Table. The first 32 vectors are reserved for the
+/// architectural exceptions.
+#[repr(C, align(16))]
+pub struct InterruptDescriptorTable {
+    /// Divide error (#DE), vector 0.
+    pub divide_error: Entry<HandlerFunc>,
+
+    /// Debug exception (#DB), vector 1.
+    pub debug: Entry<HandlerFunc>,
+
+    /// Non-maskable interrupt, vector 2.
+    pub nmi: Entry<HandlerFunc>,
+
+    /// Breakpoint (#BP), vector 3.
+    pub breakpoint: Entry<HandlerFunc>,
+
+    /// Overflow (#OF), vector 4.
+    pub overflow: Entry<HandlerFunc>,
+
+    /// BOUND range exceeded (#BR), vector 5.
+    pub bound_range_exceeded: Entry<HandlerFunc>,
+
+    /// Invalid opcode (#UD), vector 6.
+    pub invalid_opcode: Entry<HandlerFunc>,
+
+    /// Device not available (#NM), vector 7.
+    pub device_not_available: Entry<HandlerFunc>,
+
+    /// Double fault (#DF), vector 8.
+    pub double_fault: Entry<DivergingHandlerFuncWithErrCode>,
+
+    /// Coprocessor segment overrun, vector 9. It is not raised by modern
+    /// processors.
+    coprocessor_segment_overrun: Entry<HandlerFunc>,
+
+    /// Invalid TSS (#TS), vector 10.
+    pub invalid_tss: Entry<HandlerFuncWithErrCode>,
+
+    /// Segment not present (#NP), vector 11.
+    pub segment_not_present: Entry<HandlerFuncWithErrCode>,
+
+    /// Stack-segment fault (#SS), vector 12.
+    pub stack_segment_fault: Entry<HandlerFuncWithErrCode>,
+
+    /// General protection fault (#GP), vector 13.
+    pub general_protection_fault: Entry<HandlerFuncWithErrCode>,
+
+    /// Page fault (#PF), vector 14. The faulting address is in CR2.
+    pub page_fault: Entry<HandlerFuncWithErrCode>,
+
+    /// Reserved, vector 15.
+    reserved0: Entry<HandlerFunc>,
+
+    /// x87 floating-point exception (#MF), vector 16.
+    pub x87_floating_point: Entry<HandlerFunc>,
+
+    /// Alignment check (#AC), vector 17.
+    pub alignment_check: Entry<HandlerFuncWithErrCode>,
+
+    /// Machine check (#MC), vector 18.
+    pub machine_check: Entry<DivergingHandlerFunc>,
+
+    /// SIMD floating-point exception (#XM), vector 19.
+    pub simd_floating_point: Entry<HandlerFunc>,
+
+    /// Virtualization exception (#VE), vector 20.
+    pub virtualization: Entry<HandlerFunc>,
+
+    /// Control protection exception (#CP), vector 21.
+    pub control_protection: Entry<HandlerFuncWithErrCode>,
+
+    /// Reserved, vectors 22 to 31.
+    reserved1: [Entry<HandlerFunc>; 10],
+
+    /// External interrupts, vectors 32 to 255.
+    pub interrupts: [Entry<HandlerFunc>; INTERRUPT_VECTORS],
+}
+
+impl InterruptDescriptorTable {
+    /// Returns an `InterruptDescriptorTable` without present entries.
+    pub const fn new() -> Self {
+        InterruptDescriptorTable {
+            divide_error: Entry::missing(),
+            debug: Entry::missing(),
+            nmi: Entry::missing(),
+            breakpoint: Entry::missing(),
+            overflow: Entry::missing(),
+            bound_range_exceeded: Entry::missing(),
+            invalid_opcode: Entry::missing(),
+            device_not_available: Entry::missing(),
+            double_fault: Entry::missing(),
+            coprocessor_segment_overrun: Entry::missing(),
+            invalid_tss: Entry::missing(),
+            segment_not_present: Entry::missing(),
+            stack_segment_fault: Entry::missing(),
+            general_protection_fault: Entry::missing(),
+            page_fault: Entry::missing(),
+            reserved0: Entry::missing(),
+            x87_floating_point: Entry::missing(),
+            alignment_check: Entry::missing(),
+            machine_check: Entry::missing(),
+            simd_floating_point: Entry::missing(),
+            virtualization: Entry::missing(),
+            control_protection: Entry::missing(),
+            reserved1: [Entry::missing(); 10],
+            interrupts: [Entry::missing(); INTERRUPT_VECTORS],
+        }
+    }
+
+    /// Sets the default handlers of the architectural exceptions. The double
+    /// fault handler runs on the IST stack `gdt::DOUBLE_FAULT_IST`, so a
+    /// stack overflow does not escalate into a triple fault. The NMI handler
+    /// runs on the IST stack `gdt::NMI_IST`, given that the stack can be the
+    /// user one at the system call entry.
+    pub fn set_default_handlers(&mut self) {
+        self.divide_error.set_handler(divide_error_handler);
+        self.debug.set_handler(debug_handler);
+        self.nmi.set_handler(nmi_handler);
+        self.nmi.set_ist(gdt::NMI_IST);
+        self.breakpoint.set_handler(breakpoint_handler);
+        self.overflow.set_handler(overflow_handler);
+        self.bound_range_exceeded
+            .set_handler(bound_range_exceeded_handler);
+        self.invalid_opcode.set_handler(invalid_opcode_handler);
+        self.device_not_available
+            .set_handler(device_not_available_handler);
+        self.double_fault.set_handler(double_fault_handler);
+        self.double_fault.set_ist(gdt::DOUBLE_FAULT_IST);
+        self.invalid_tss.set_handler(invalid_tss_handler);
+        self.segment_not_present
+            .set_handler(segment_not_present_handler);
+        self.stack_segment_fault
+            .set_handler(stack_segment_fault_handler);
+        self.general_protection_fault
+            .set_handler(general_protection_fault_handler);
+        self.page_fault.set_handler(page_fault_handler);
+        self.x87_floating_point
+            .set_handler(x87_floating_point_handler);
+        self.alignment_check.set_handler(alignment_check_handler);
+        self.machine_check.set_handler(machine_check_handler);
+        self.simd_floating_point
+            .set_handler(simd_floating_point_handler);
+        self.virtualization.set_handler(virtualization_handler);
+        self.control_protection
+            .set_handler(control_protection_handler);
+
+        // The legacy PICs are masked, but they can still deliver spurious
+        // interrupts.
+        self.interrupts[usize::from(PIC_VECTOR_BASE - EXCEPTION_VECTORS + 7)]
+            .set_handler(pic_irq7_handler);
+        self.interrupts[usize::from(PIC_VECTOR_BASE - EXCEPTION_VECTORS + 15)]
+            .set_handler(pic_irq15_handler);
+
+        self.interrupts[usize::from(SPURIOUS_VECTOR - EXCEPTION_VECTORS)]
+            .set_handler(spurious_handler);
+    }
+}
+
+impl Default for InterruptDescriptorTable {
+    fn default() -> Self {
+        InterruptDescriptorTable::new()
+    }
+}
+
+/// Sets the default exception handlers and loads the IDT on the current CPU.
+///
+/// # Safety
+///
+/// `gdt::init` must have been called on the current CPU, given that the
+/// entries reference the kernel code segment and the IST.
+pub unsafe fn init() {
+    IDT.lock().set_default_handlers();
+    load();
+}
+
+/// Loads the IDT on the current CPU. It is used by the CPUs started after
+/// `init`.
+///
+/// # Safety
+///
+/// `gdt::init` must have been called on the current CPU, given that the
+/// entries reference the kernel code segment and the IST.
+pub unsafe fn load() {
+    let idt = IDT.lock();
+
+    // The IDT is static, so it stays valid after releasing the lock.
+    let idtr = DescriptorTablePointer::new(
+        &*idt as *const InterruptDescriptorTable as u64,
+        mem::size_of::<InterruptDescriptorTable>(),
+    );
+    cpu::lidt(&idtr);
+}
+
+/// Sets `handler` as the handler of the external interrupt vector `vector`.
+///
+/// # Panics
+///
+/// This function panics if `vector` is reserved for the exceptions.
+pub fn set_interrupt_handler(vector: u8, handler: HandlerFunc) {
+    let index = vector
+        .checked_sub(EXCEPTION_VECTORS)
+        .expect("idt: exception vector");
+    IDT.lock().interrupts[usize::from(index)].set_handler(handler);
+}
+
+/// Defines a default handler of an exception without error code. It panics
+/// reporting the faulting context. If the exception was raised in user mode,
+/// the faulting task is terminated instead.
+macro_rules! exception_handler {
+    ($name:ident, $desc:expr) => {
+        extern "x86-interrupt" fn $name(frame: InterruptStackFrame) {
+            let _gs = KernelGs::enter(&frame);
+            if frame.is_user() {
+                kill_user_task(format_args!("{}", $desc), &frame);
+            }
+            panic!("exception: {}\n{}", $desc, frame);
+        }
+    };
+}
+
+/// Defines a default handler of an exception with error code. It panics
+/// reporting the faulting context and the error code. If the exception was
+/// raised in user mode, the faulting task is terminated instead.
+macro_rules! exception_handler_with_err_code {
+    ($name:ident, $desc:expr) => {
+        extern "x86-interrupt" fn $name(
+            frame: InterruptStackFrame,
+            error_code: u64,
+        ) {
+            let _gs = KernelGs::enter(&frame);
+            if frame.is_user() {
+                kill_user_task(
+                    format_args!("{} (error code {:#x})", $desc, error_code),
+                    &frame,
+                );
+            }
+            panic!(
+                "exception: {} (error code {:#x})\n{}",
+                $desc, error_code, frame
+            );
+        }
+    };
+}
+
+exception_handler!(divide_error_handler, "divide error");
+exception_handler!(debug_handler, "debug");
+exception_handler!(overflow_handler, "overflow");
+exception_handler!(bound_range_exceeded_handler, "bound range exceeded");
+exception_handler!(invalid_opcode_handler, "invalid opcode");
+exception_handler!(device_not_available_handler, "device not available");
+exception_handler_with_err_code!(invalid_tss_handler, "invalid TSS");
+exception_handler_with_err_code!(
+    segment_not_present_handler,
+    "segment not present"
+);
+exception_handler_with_err_code!(
+    stack_segment_fault_handler,
+    "stack-segment fault"
+);
+exception_handler_with_err_code!(
+    general_protection_fault_handler,
+    "general protection fault"
+);
+exception_handler!(x87_floating_point_handler, "x87 floating-point");
+exception_handler_with_err_code!(alignment_check_handler, "alignment check");
+exception_handler!(simd_floating_point_handler, "SIMD floating-point");
+exception_handler!(virtualization_handler, "virtualization");
+exception_handler_with_err_code!(
+    control_protection_handler,
+    "control protection"
+);
+
+/// Terminates the current task, which raised the exception described by
+/// `desc` in user mode. The fault does not affect the kernel, so it keeps
+/// running.
+fn kill_user_task(desc: fmt::Arguments, frame: &InterruptStackFrame) -> ! {
+    warn!(
+        target: "exception",
+        "{} in user mode, terminating task\n{}", desc, frame
+    );
+    task::exit(USER_FAULT_STATUS)
+}
+
+/// NMI handler. It runs on its own stack, given that an NMI can be raised at
+/// the system call entry, before switching to the kernel stack.
+extern "x86-interrupt" fn nmi_handler(frame: InterruptStackFrame) {
+    let _gs = KernelGs::enter_paranoid();
+    panic!("exception: non-maskable interrupt\n{}", frame);
+}
+
+/// Breakpoint handler. The breakpoint is reported and the execution
+/// continues after the `int3` instruction.
+extern "x86-interrupt" fn breakpoint_handler(frame: InterruptStackFrame) {
+    let _gs = KernelGs::enter(&frame);
+    warn!(target: "exception", "breakpoint\n{}", frame);
+}
+
+/// Page fault handler. It reports the faulting address and the cause of the
+/// fault decoded from the error code. The accesses to a guard page are
+/// reported as stack overflows. The page faults raised in user mode
+/// terminate the faulting task.
+extern "x86-interrupt" fn page_fault_handler(
+    frame: InterruptStackFrame,
+    error_code: u64,
+) {
+    let _gs = KernelGs::enter(&frame);
+    let addr = cpu::read_cr2();
+    if frame.is_user() {
+        kill_user_task(
+            format_args!(
+                "page fault at {:#x} (error code {:#x})",
+                addr, error_code
+            ),
+            &frame,
+        );
+    }
+
+    if error_code & PF_PROTECTION_VIOLATION == 0 {
+        if let Some(owner) = stack::guard_owner(addr) {
+            panic!(
+                "exception: kernel stack overflow in task {} at {:#x}\n{}",
+                owner, addr, frame
+            );
+        }
+    }
+
+    let access = if error_code & PF_INSTRUCTION_FETCH != 0 {
+        "instruction fetch"
+    } else if error_code & PF_WRITE != 0 {
+        "write"
+    } else {
+        "read"
+    };
+    let cause = if error_code & PF_PROTECTION_VIOLATION != 0 {
+        "protection violation"
+    } else {
+        "page not present"
+    };
+    panic!(
+        "exception: page fault at {:#x}: {} ({}, error code {:#x})\n{}",
+        addr, access, cause, error_code, frame
+    );
+}
+
+/// Double fault handler. It runs on its own stack, given that the fault
+/// could have been caused by a stack overflow. If the stack pointer reached
+/// a guard page, the page fault frame could not be pushed and CR2 holds the
+/// address in the guard page.
+extern "x86-interrupt" fn double_fault_handler(
+    frame: InterruptStackFrame,
+    error_code: u64,
+) -> ! {
+    let _gs = KernelGs::enter_paranoid();
+    let addr = cpu::read_cr2();
+    if let Some(owner) = stack::guard_owner(addr) {
+        panic!(
+            "exception: kernel stack overflow in task {} at {:#x}\n{}",
+            owner, addr, frame
+        );
+    }
+
+    panic!(
+        "exception: double fault (error code {:#x})\n{}",
+        error_code, frame
+    );
+}
+
+/// Machine check handler. The state of the processor is not reliable, so it
+/// cannot return.
+extern "x86-interrupt" fn machine_check_handler(
+    frame: InterruptStackFrame,
+) -> ! {
+    let _gs = KernelGs::enter_paranoid();
+    panic!("exception: machine check\n{}", frame);
+}
+
+/// Handler of the IRQ 7 of the legacy PICs. It is only expected to receive
+/// spurious interrupts, which do not require an EOI.
+extern "x86-interrupt" fn pic_irq7_handler(_frame: InterruptStackFrame) {
+    unsafe {
+        if !pic8259::is_spurious(7) {
+            pic8259::end_of_interrupt(7);
+        }
+    }
+}
+
+/// Handler of the IRQ 15 of the legacy PICs. It is only expected to receive
+/// spurious interrupts, which require an EOI for the cascade IRQ of the
+/// master PIC.
+extern "x86-interrupt" fn pic_irq15_handler(_frame: InterruptStackFrame) {
+    unsafe {
+        if pic8259::is_spurious(15) {
+            pic8259::end_of_interrupt(pic8259::CASCADE_IRQ);
+        } else {
+            pic8259::end_of_interrupt(15);
+        }
+    }
+}
+
+/// Handler of the spurious interrupts of the local APIC. They do not require
+/// an EOI.
+extern "x86-interrupt" fn spurious_handler(_frame: InterruptStackFrame) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idt_layout() {
+        assert_eq!(mem::size_of::<Entry<HandlerFunc>>(), 16);
+        assert_eq!(mem::size_of::<InterruptDescriptorTable>(), 256 * 16);
+    }
+
+    #[test]
+    fn test_frame_is_user() {
+        let frame = |cs| InterruptStackFrame {
+            rip: 0,
+            cs,
+            rflags: 0,
+            rsp: 0,
+            ss: 0,
+        };
+        assert!(!frame(u64::from(gdt::KERNEL_CODE_SELECTOR)).is_user());
+        assert!(frame(u64::from(gdt::USER_CODE_SELECTOR)).is_user());
+    }
+
+    #[test]
+    fn test_entry_set_handler_addr() {
+        let mut entry = Entry::<HandlerFunc>::missing();
+        entry.set_ist(gdt::DOUBLE_FAULT_IST);
+        entry.set_handler_addr(0xffff_8000_1234_5678);
+
+        assert_eq!(entry.offset_low, 0x5678);
+        assert_eq!(entry.offset_mid, 0x1234);
+        assert_eq!(entry.offset_high, 0xffff_8000);
+        assert_eq!(entry.selector, gdt::KERNEL_CODE_SELECTOR);
+        assert_eq!(entry.options, 0x8e01);
+    }
+}
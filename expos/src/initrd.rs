@@ -0,0 +1,53 @@
+//! Initial RAM disk.
+//!
+//! The bootloader passes a CPIO archive in the boot information, so the
+//! early programs and the test binaries can be read without any storage or
+//! filesystem driver. The archive lives in bootloader memory, which is never
+//! reused, so its files are borrowed for the lifetime of the kernel.
+
+use boot_info::BootInfo;
+use cpio::Archive;
+use ticket_mutex::TicketMutex;
+
+/// Initial RAM disk passed by the bootloader. It is empty until `init` is
+/// called or if the archive is not valid.
+static INITRD: TicketMutex<Archive<'static>> =
+    TicketMutex::new(Archive::new(&[]));
+
+/// Takes the initial RAM disk from the boot information `loader`, validates
+/// it and returns the number of regular files. It returns zero if there is
+/// no initial RAM disk.
+pub fn init(loader: &'static BootInfo) -> Result<usize, cpio::Error> {
+    if loader.initrd.is_empty() {
+        return Ok(0);
+    }
+
+    // The archive lives in bootloader memory, which is never reused.
+    let archive = Archive::new(unsafe { loader.initrd.as_slice() });
+
+    let mut files = 0;
+    for entry in archive.entries() {
+        if entry?.is_file() {
+            files += 1;
+        }
+    }
+    *INITRD.lock() = archive;
+    Ok(files)
+}
+
+/// Returns the initial RAM disk.
+pub fn archive() -> Archive<'static> {
+    *INITRD.lock()
+}
+
+/// Returns the contents of the regular file at `path`.
+// Nothing reads the files yet.
+#[allow(dead_code)]
+pub fn get(path: &str) -> Option<&'static [u8]> {
+    archive()
+        .get(path)
+        .ok()
+        .flatten()
+        .filter(|entry| entry.is_file())
+        .map(|entry| entry.data)
+}
@@ -0,0 +1,166 @@
+//! PS/2 keyboard.
+//!
+//! The IRQ 1 handler drains the scancodes received by the PS/2 controller,
+//! decodes them and queues the resulting key events, which are consumed with
+//! `read_event`. The IRQ is routed through the IO APIC if it is available.
+//! Otherwise, it is delivered by the legacy PICs.
+
+use core::convert::TryFrom;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use ps2::keyboard::{KeyEvent, Keyboard};
+use ticket_mutex::TicketMutex;
+
+use crate::idt::{self, InterruptStackFrame, KernelGs};
+
+/// ISA IRQ of the keyboard.
+const KEYBOARD_IRQ: u8 = 1;
+
+/// Maximum number of queued key events.
+const MAX_EVENTS: usize = 64;
+
+/// Scancode decoder. It is `None` until `init` is called.
+static KEYBOARD: TicketMutex<Option<Keyboard>> = TicketMutex::new(None);
+
+/// Key events not read yet. The lock is always taken with interrupts
+/// disabled, given that the interrupt handler pushes into it.
+static EVENTS: TicketMutex<Events> = TicketMutex::new(Events {
+    events: [None; MAX_EVENTS],
+    head: 0,
+    len: 0,
+});
+
+/// Number of key events dropped because the queue was full.
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+/// Keyboard errors.
+#[derive(Debug)]
+pub enum Error {
+    /// The PS/2 controller or the keyboard could not be initialized.
+    Ps2(ps2::Error),
+}
+
+/// Controller the keyboard IRQ is delivered by.
+#[derive(Debug)]
+pub enum Route {
+    /// IO APIC, at `idt::KEYBOARD_VECTOR`.
+    IoApic,
+
+    /// Legacy PICs.
+    Pic,
+}
+
+/// Fixed size ring buffer of key events.
+struct Events {
+    /// Queued events.
+    events: [Option<KeyEvent>; MAX_EVENTS],
+
+    /// Index of the oldest event.
+    head: usize,
+
+    /// Number of queued events.
+    len: usize,
+}
+
+impl Events {
+    /// Appends `event`. It returns `false` if the queue is full.
+    fn push(&mut self, event: KeyEvent) -> bool {
+        if self.len >= MAX_EVENTS {
+            return false;
+        }
+        self.events[(self.head + self.len) % MAX_EVENTS] = Some(event);
+        self.len += 1;
+        true
+    }
+
+    /// Removes the oldest event and returns it.
+    fn pop(&mut self) -> Option<KeyEvent> {
+        if self.len == 0 {
+            return None;
+        }
+        let event = self.events[self.head].take();
+        self.head = (self.head + 1) % MAX_EVENTS;
+        self.len -= 1;
+        event
+    }
+}
+
+/// Initializes the PS/2 controller and the keyboard, and routes the keyboard
+/// IRQ. If `ioapic` is `true`, the IRQ is routed through the IO APIC to the
+/// local APIC of the current CPU. It returns the controller the IRQ is
+/// delivered by.
+///
+/// # Safety
+///
+/// The IDT must be loaded and nothing else must be accessing the PS/2
+/// controller. If `ioapic` is `true`, the local APIC and the IO APICs must be
+/// initialized. Otherwise, the legacy PICs must be initialized.
+pub unsafe fn init(ioapic: bool) -> Result<Route, Error> {
+    let controller = ps2::Controller::init().map_err(Error::Ps2)?;
+    *KEYBOARD.lock() = Some(Keyboard::new(controller.scancode_set()));
+
+    // Both controllers use 8-bit destination IDs.
+    if ioapic {
+        if let Ok(dest) = u8::try_from(lapic::id()) {
+            idt::set_interrupt_handler(idt::KEYBOARD_VECTOR, ioapic_handler);
+            if ioapic::route_isa_irq(KEYBOARD_IRQ, idt::KEYBOARD_VECTOR, dest)
+                .is_ok()
+            {
+                return Ok(Route::IoApic);
+            }
+        }
+    }
+
+    idt::set_interrupt_handler(
+        idt::PIC_VECTOR_BASE + KEYBOARD_IRQ,
+        pic_handler,
+    );
+    pic8259::set_masked(KEYBOARD_IRQ, false);
+    Ok(Route::Pic)
+}
+
+/// Returns the oldest key event not read yet, or `None` if there are no
+/// pending events.
+pub fn read_event() -> Option<KeyEvent> {
+    cpu::without_interrupts(|| EVENTS.lock().pop())
+}
+
+/// Returns the number of key events dropped because they were not read in
+/// time.
+pub fn dropped() -> usize {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+/// Decodes the scancodes received by the PS/2 controller and queues the key
+/// events. It is called with interrupts disabled.
+fn handle_irq() {
+    let mut keyboard = KEYBOARD.lock();
+    let keyboard = match keyboard.as_mut() {
+        Some(keyboard) => keyboard,
+        None => return,
+    };
+
+    // The controller was initialized by `init`, and only the interrupt
+    // handler reads from it afterwards.
+    while let Some(byte) = unsafe { ps2::read_data() } {
+        if let Some(event) = keyboard.push(byte) {
+            if !EVENTS.lock().push(event) {
+                DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Handler of the keyboard IRQ routed through the IO APIC.
+extern "x86-interrupt" fn ioapic_handler(frame: InterruptStackFrame) {
+    let _gs = KernelGs::enter(&frame);
+    handle_irq();
+    lapic::end_of_interrupt();
+}
+
+/// Handler of the keyboard IRQ delivered by the legacy PICs.
+extern "x86-interrupt" fn pic_handler(frame: InterruptStackFrame) {
+    let _gs = KernelGs::enter(&frame);
+    handle_irq();
+    unsafe { pic8259::end_of_interrupt(KEYBOARD_IRQ) };
+}
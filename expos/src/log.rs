@@ -0,0 +1,296 @@
+//! Kernel log.
+//!
+//! The log macros (e.g. `info!`) write records to the console, prefixed with
+//! the time since the clocks were calibrated, the level and the target of the
+//! record. The target is the name of the module the record comes from,
+//! unless it is set explicitly (e.g. `info!(target: "acpi", "...")`).
+//!
+//! The records are filtered by level. The `log.level` kernel parameter is a
+//! comma-separated list of directives. A level sets the maximum level and
+//! `<target>=<level>` overrides it for a target (e.g.
+//! `log.level=warn,smp=debug`). The levels are given by name or by number,
+//! from `0` (off) to `5` (trace).
+
+use core::fmt;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use param::StrParam;
+use ticket_mutex::TicketMutex;
+
+use crate::{console, time};
+
+/// Log levels, set from the command line.
+pub static LOG_LEVEL: StrParam = StrParam::new("log.level", "");
+
+/// Maximum number of targets with their own maximum level.
+const MAX_FILTERS: usize = 8;
+
+/// Maximum level of the targets without filter. It is `Level::Info` by
+/// default.
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+/// Maximum levels set for specific targets.
+static FILTERS: TicketMutex<[Option<Filter>; MAX_FILTERS]> =
+    TicketMutex::new([None; MAX_FILTERS]);
+
+/// Log errors.
+#[derive(Debug)]
+pub enum Error {
+    /// The level of the provided directive is not valid.
+    InvalidLevel(&'static str),
+
+    /// There are more than `MAX_FILTERS` targets with their own level.
+    TooManyFilters,
+}
+
+/// Level of a log record, from the most to the least severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// The operation failed.
+    Error = 1,
+
+    /// Something unexpected happened, but the operation went on (e.g. a
+    /// fallback was used).
+    Warn,
+
+    /// Regular report of the kernel operation.
+    Info,
+
+    /// Detailed report, useful to diagnose problems.
+    Debug,
+
+    /// Very verbose report (e.g. on every interrupt).
+    Trace,
+}
+
+impl Level {
+    /// Returns the name of the level as printed in the records.
+    fn name(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+}
+
+/// Maximum level of the records of a target.
+#[derive(Clone, Copy)]
+struct Filter {
+    target: &'static str,
+    max_level: u8,
+}
+
+/// Sets the maximum levels from the `log.level` kernel parameter, so the
+/// command line must have been parsed. All the directives are processed,
+/// even if some of them are not valid. Then, the first error is returned.
+pub fn init() -> Result<(), Error> {
+    let mut ret = Ok(());
+    let mut filters = FILTERS.lock();
+
+    let directives = LOG_LEVEL.get().split(',');
+    for directive in directives.filter(|directive| !directive.is_empty()) {
+        let (target, max_level) = match parse_directive(directive) {
+            Some(parsed) => parsed,
+            None => {
+                if ret.is_ok() {
+                    ret = Err(Error::InvalidLevel(directive));
+                }
+                continue;
+            }
+        };
+
+        let target = match target {
+            Some(target) => target,
+            None => {
+                MAX_LEVEL.store(max_level, Ordering::Relaxed);
+                continue;
+            }
+        };
+
+        // The filters are stored without gaps, so a filter of the same
+        // target is always found before the first free slot.
+        let slot = filters.iter_mut().find(|slot| match slot {
+            Some(filter) => filter.target == target,
+            None => true,
+        });
+        match slot {
+            Some(slot) => *slot = Some(Filter { target, max_level }),
+            None => {
+                if ret.is_ok() {
+                    ret = Err(Error::TooManyFilters);
+                }
+            }
+        }
+    }
+
+    ret
+}
+
+/// Returns `true` if the records of level `level` and target `target` are
+/// written.
+pub fn enabled(level: Level, target: &str) -> bool {
+    let target = short_target(target);
+    let max_level = FILTERS
+        .lock()
+        .iter()
+        .flatten()
+        .find(|filter| filter.target == target)
+        .map_or_else(|| MAX_LEVEL.load(Ordering::Relaxed), |f| f.max_level);
+    level as u8 <= max_level
+}
+
+/// Writes a record with the level `level`, the target `target` and the
+/// formatted arguments `args` to the console. It is used by the log macros.
+#[doc(hidden)]
+pub fn _log(level: Level, target: &'static str, args: fmt::Arguments) {
+    if !enabled(level, target) {
+        return;
+    }
+
+    let ns = time::now_ns();
+    console::_print(format_args!(
+        "[{:>5}.{:06}] {:<5} {}: {}\n",
+        ns / 1_000_000_000,
+        ns % 1_000_000_000 / 1000,
+        level.name(),
+        short_target(target),
+        args,
+    ));
+}
+
+/// Returns the last component of the module path `target`, so the records
+/// of a module are identified by its name.
+fn short_target(target: &str) -> &str {
+    target.rsplit("::").next().unwrap_or(target)
+}
+
+/// Parses the directive `directive`, which is either a level or
+/// `<target>=<level>`. It returns the target, if any, and the level, or
+/// `None` if the directive is not valid.
+fn parse_directive(directive: &str) -> Option<(Option<&str>, u8)> {
+    let mut parts = directive.splitn(2, '=');
+    let first = parts.next()?;
+    match parts.next() {
+        Some(level) if !first.is_empty() => {
+            Some((Some(first), parse_level(level)?))
+        }
+        Some(_) => None,
+        None => Some((None, parse_level(first)?)),
+    }
+}
+
+/// Parses the level `value`, by name or by number. It returns `None` if the
+/// level is not valid.
+fn parse_level(value: &str) -> Option<u8> {
+    let level = match value {
+        "off" | "0" => 0,
+        "error" | "1" => Level::Error as u8,
+        "warn" | "2" => Level::Warn as u8,
+        "info" | "3" => Level::Info as u8,
+        "debug" | "4" => Level::Debug as u8,
+        "trace" | "5" => Level::Trace as u8,
+        _ => return None,
+    };
+    Some(level)
+}
+
+/// Writes a log record with the provided level. The target is the current
+/// module, unless it is set with `target: "name"` before the level.
+#[macro_export]
+macro_rules! log {
+    (target: $target:expr, $level:expr, $($arg:tt)+) => {
+        $crate::log::_log($level, $target, format_args!($($arg)+))
+    };
+    ($level:expr, $($arg:tt)+) => {
+        $crate::log!(target: module_path!(), $level, $($arg)+)
+    };
+}
+
+/// Writes a log record with the level `Level::Error`.
+#[macro_export]
+macro_rules! error {
+    (target: $target:expr, $($arg:tt)+) => {
+        $crate::log!(target: $target, $crate::log::Level::Error, $($arg)+)
+    };
+    ($($arg:tt)+) => {
+        $crate::log!($crate::log::Level::Error, $($arg)+)
+    };
+}
+
+/// Writes a log record with the level `Level::Warn`.
+#[macro_export]
+macro_rules! warn {
+    (target: $target:expr, $($arg:tt)+) => {
+        $crate::log!(target: $target, $crate::log::Level::Warn, $($arg)+)
+    };
+    ($($arg:tt)+) => {
+        $crate::log!($crate::log::Level::Warn, $($arg)+)
+    };
+}
+
+/// Writes a log record with the level `Level::Info`.
+#[macro_export]
+macro_rules! info {
+    (target: $target:expr, $($arg:tt)+) => {
+        $crate::log!(target: $target, $crate::log::Level::Info, $($arg)+)
+    };
+    ($($arg:tt)+) => {
+        $crate::log!($crate::log::Level::Info, $($arg)+)
+    };
+}
+
+/// Writes a log record with the level `Level::Debug`.
+#[macro_export]
+macro_rules! debug {
+    (target: $target:expr, $($arg:tt)+) => {
+        $crate::log!(target: $target, $crate::log::Level::Debug, $($arg)+)
+    };
+    ($($arg:tt)+) => {
+        $crate::log!($crate::log::Level::Debug, $($arg)+)
+    };
+}
+
+/// Writes a log record with the level `Level::Trace`.
+#[macro_export]
+macro_rules! trace {
+    (target: $target:expr, $($arg:tt)+) => {
+        $crate::log!(target: $target, $crate::log::Level::Trace, $($arg)+)
+    };
+    ($($arg:tt)+) => {
+        $crate::log!($crate::log::Level::Trace, $($arg)+)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!(parse_level("off"), Some(0));
+        assert_eq!(parse_level("warn"), Some(Level::Warn as u8));
+        assert_eq!(parse_level("5"), Some(Level::Trace as u8));
+        assert_eq!(parse_level("6"), None);
+        assert_eq!(parse_level("verbose"), None);
+    }
+
+    #[test]
+    fn test_parse_directive() {
+        assert_eq!(parse_directive("debug"), Some((None, 4)));
+        assert_eq!(parse_directive("smp=trace"), Some((Some("smp"), 5)));
+        assert_eq!(parse_directive("smp=2"), Some((Some("smp"), 2)));
+        assert_eq!(parse_directive("smp"), None);
+        assert_eq!(parse_directive("smp=verbose"), None);
+        assert_eq!(parse_directive("=debug"), None);
+    }
+
+    #[test]
+    fn test_short_target() {
+        assert_eq!(short_target("expos::smp"), "smp");
+        assert_eq!(short_target("expos"), "expos");
+        assert_eq!(short_target("acpi"), "acpi");
+    }
+}
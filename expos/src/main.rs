@@ -2,64 +2,663 @@
 
 #![no_std]
 #![cfg_attr(not(test), no_main)]
+#![feature(abi_x86_interrupt)]
+#![feature(global_asm)]
 #![feature(panic_info_message)]
+#![cfg_attr(feature = "heap", feature(alloc_error_handler))]
 
-use range::RangeSet;
+#[cfg(feature = "heap")]
+extern crate alloc;
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+
+use boot_info::MemoryKind;
+use deferred::WorkQueue;
+use range::{Range, RangeSet};
 use uefi::acpi;
 
+mod ahci;
+mod cmdline;
+mod console;
+mod disk;
+// The DMA buffers are only used by the device drivers.
+#[allow(dead_code)]
+mod dma;
+mod fbcon;
+mod gdt;
+#[cfg(feature = "heap")]
+mod heap;
+mod idt;
+mod initrd;
+mod keyboard;
+mod log;
+mod nvme;
+mod pci;
+mod phases;
+mod pmm;
+
 #[cfg(not(test))]
 mod panic;
 
+mod paging;
 mod serial;
+mod smp;
+mod stack;
+mod syscall;
+mod task;
+mod time;
+mod tlb;
+mod user;
+
+/// Work deferred by the interrupt handlers. It is run in task context.
+static DEFERRED_WORK: WorkQueue<64> = WorkQueue::new();
+
+/// Number of ticks since the periodic tick was started.
+static TICKS: AtomicU64 = AtomicU64::new(0);
 
 struct BootInfo {
+    /// Boot information passed by the bootloader. It lives in bootloader
+    /// memory, which is not part of `available_memory`.
+    loader: &'static boot_info::BootInfo,
+
     available_memory: RangeSet,
-    acpi_madt: acpi::Madt,
+
+    /// MADT. It is `None` if the ACPI tables are missing or malformed. In that
+    /// case, the kernel must fall back to single-CPU, legacy PIC and PIT
+    /// operation.
+    acpi_madt: Option<acpi::Madt>,
+
+    /// HPET. It is `None` if the ACPI tables are missing or the platform does
+    /// not have an HPET.
+    acpi_hpet: Option<acpi::Hpet>,
+
+    /// MCFG. It is `None` if the ACPI tables are missing or the platform does
+    /// not support memory-mapped PCIe configuration access.
+    acpi_mcfg: Option<acpi::Mcfg>,
+
+    /// FADT. It is `None` if the ACPI tables are missing or malformed.
+    acpi_fadt: Option<acpi::Fadt>,
 }
 
-/// UEFI entry point.
+/// Kernel entry point. It is called by the bootloader with the interrupts
+/// disabled and the physical memory identity mapped.
 #[no_mangle]
-extern "C" fn efi_main(
-    image_handle: uefi::Handle,
-    system_table_ptr: uefi::Ptr,
-) -> ! {
+extern "sysv64" fn _start(loader: &'static boot_info::BootInfo) -> ! {
+    // Set up the per-CPU area of the bootstrap processor, which is CPU 0.
+    // Neither the firmware nor the bootloader use the GS base.
+    unsafe { percpu::init(0).unwrap() };
+
+    // Let the mutexes detect deadlocks in debug builds.
+    ticket_mutex::set_cpu_id(smp::cpu_index);
+
+    // Origin of the boot phase timestamps.
+    phases::mark("entry");
+
+    // The rest of the fields cannot be trusted if the bootloader uses a
+    // different layout. The panic is reported on COM1.
+    if !loader.is_valid() {
+        serial::init_serial(None, None);
+        panic!(
+            "boot info: unsupported version {} (expected {})",
+            loader.version,
+            boot_info::VERSION
+        );
+    }
+
+    // The command line configures the console and the log, so it is parsed
+    // before initializing serial.
+    let cmdline = cmdline::init(loader);
+    let log = log::init();
+
+    // Parse the ACPI tables. They describe the serial port used by the
+    // firmware console, so serial is initialized afterwards.
+    let root_sdt = parse_root_sdt(loader);
+
     // Initialize serial.
-    serial::init_serial();
+    serial::init_serial(root_sdt.as_ref().ok(), None);
+    phases::mark("serial");
 
-    // Parse UEFI's system table.
-    let system_table =
-        unsafe { uefi::SystemTable::new(system_table_ptr).unwrap() };
+    // Initialize the framebuffer console. The bootloader maps the
+    // framebuffer, so it can be used before the kernel page tables are
+    // built.
+    let fbcon = unsafe { fbcon::init(loader.framebuffer()) };
+    phases::mark("fbcon");
 
-    // Get LAPIC data.
-    let config_tables = system_table.configuration_tables().unwrap();
-    let rsdp20_ptr = config_tables.acpi_rsdp20_ptr().unwrap();
-    let rsdp20 = unsafe { acpi::Rsdp20::new(rsdp20_ptr).unwrap() };
-    let xsdt = rsdp20.xsdt().unwrap();
-    let madt = xsdt.madt().unwrap();
+    match cmdline {
+        Ok(()) => info!(target: "cmdline", "{:?}", cmdline::get()),
+        Err(err) => warn!(target: "cmdline", "{:?}", err),
+    }
+    if let Err(err) = log {
+        warn!(target: "log", "{:?}", err);
+    }
+    if let Err(err) = fbcon {
+        warn!(target: "fbcon", "not available: {:?}", err);
+    }
 
-    // Get available memory.
-    let boot_services = system_table.boot_services().unwrap();
-    let (available_memory, map_key) =
-        uefi::mem::get_available_memory(&boot_services).unwrap();
+    // The initial RAM disk is in bootloader memory, so it can be read at any
+    // time.
+    match initrd::init(loader) {
+        Ok(_) if loader.initrd.is_empty() => {
+            info!(target: "initrd", "not available")
+        }
+        Ok(files) => {
+            info!(
+                target: "initrd",
+                "{} files, {} bytes",
+                files,
+                loader.initrd.len(),
+            );
+            for entry in initrd::archive().entries().flatten() {
+                if entry.is_file() {
+                    debug!(
+                        target: "initrd",
+                        "{}: {} bytes",
+                        entry.name,
+                        entry.data.len(),
+                    );
+                }
+            }
+        }
+        Err(err) => warn!(target: "initrd", "{:?}", err),
+    }
 
-    // Exit UEFI boot services.
-    boot_services
-        .exit_boot_services(image_handle, map_key)
-        .unwrap();
+    // Report the processor features the kernel depends on.
+    let features = cpu::CpuFeatures::detect();
+    info!(
+        target: "cpu",
+        "x2apic {}, tsc-deadline {}, invariant tsc {}, nx {}, 1g pages {}",
+        features.x2apic(),
+        features.tsc_deadline(),
+        features.invariant_tsc(),
+        features.nx(),
+        features.pages_1gb(),
+    );
+
+    // Get LAPIC and HPET data.
+    let root_sdt = match root_sdt {
+        Ok(root_sdt) => Some(root_sdt),
+        Err(err) => {
+            warn!(target: "acpi", "not available: {}", err);
+            None
+        }
+    };
+    if let Some(root_sdt) = &root_sdt {
+        print_sdts(root_sdt);
+    }
+    let madt = match root_sdt.as_ref().map(|root_sdt| root_sdt.madt()) {
+        Some(Ok(madt)) => Some(madt),
+        Some(Err(err)) => {
+            warn!(target: "acpi", "{}", err);
+            None
+        }
+        None => None,
+    };
+    if madt.is_none() {
+        warn!(target: "acpi", "falling back to single-CPU, legacy PIC and PIT");
+    }
+    let hpet = root_sdt.as_ref().and_then(|root_sdt| root_sdt.hpet().ok());
+    let mcfg = root_sdt.as_ref().and_then(|root_sdt| root_sdt.mcfg().ok());
+    let fadt = root_sdt.as_ref().and_then(|root_sdt| root_sdt.fadt().ok());
+    if let Some(bgrt) =
+        root_sdt.as_ref().and_then(|root_sdt| root_sdt.bgrt().ok())
+    {
+        debug!(
+            target: "bgrt",
+            "image at {:#x}, offset {:?}, displayed {}",
+            bgrt.image_address(),
+            bgrt.image_offset(),
+            bgrt.displayed(),
+        );
+    }
+    let dmar = root_sdt.and_then(|root_sdt| root_sdt.dmar().ok());
+
+    // The ACPI tables are parsed while the kernel runs, so the reclaimable
+    // ACPI memory is not made available. The regions that wrap around or do
+    // not fit in the set are skipped, so the boot goes on with less memory.
+    let mut available_memory = RangeSet::new();
+    let regions = unsafe { loader.memory_regions.as_slice() };
+    for region in regions
+        .iter()
+        .filter(|region| region.kind == MemoryKind::USABLE && region.size != 0)
+    {
+        let result = region
+            .start
+            .checked_add(region.size - 1)
+            .ok_or(range::Error::InvalidBoundaries)
+            .and_then(|end| Range::new(region.start, end))
+            .and_then(|range| available_memory.insert(range));
+        if let Err(err) = result {
+            warn!(
+                target: "memory",
+                "region {:#x} ({} bytes) skipped: {:?}",
+                region.start, region.size, err
+            );
+        }
+    }
+    phases::mark("memory map");
+
+    // Devices can perform DMA to the RMRR regions at any time. So, they must
+    // not be allocated. Malformed regions are skipped.
+    if let Some(dmar) = &dmar {
+        for rmrr in dmar.rmrr_regions() {
+            let result = rmrr.range().and_then(|range| {
+                available_memory.remove(range).map_err(uefi::Error::from)
+            });
+            if let Err(err) = result {
+                warn!(
+                    target: "acpi",
+                    "RMRR {:#x}-{:#x}: {}",
+                    rmrr.base_address(),
+                    rmrr.limit_address(),
+                    err
+                );
+            }
+        }
+    }
 
     // Fill `BootInfo` structure and call kernel's entrypoint.
     let boot_info = BootInfo {
+        loader,
         available_memory,
         acpi_madt: madt,
+        acpi_hpet: hpet,
+        acpi_mcfg: mcfg,
+        acpi_fadt: fadt,
     };
     os_main(boot_info)
 }
 
+/// Logs the System Description Tables referenced by the root SDT at the
+/// debug level.
+fn print_sdts(root_sdt: &acpi::RootSdt) {
+    for (signature, ptr) in root_sdt.tables() {
+        let signature = core::str::from_utf8(&signature).unwrap_or("????");
+        match unsafe { acpi::validate_sdt(ptr) } {
+            Ok(hdr) => debug!(
+                target: "acpi",
+                "{} at {:#x}, revision {}, length {}",
+                signature,
+                ptr.0,
+                hdr.revision(),
+                hdr.length(),
+            ),
+            Err(err) => {
+                warn!(target: "acpi", "{} at {:#x}: {}", signature, ptr.0, err)
+            }
+        }
+    }
+}
+
+/// Returns the root System Description Table referenced by the RSDP passed
+/// by the bootloader.
+fn parse_root_sdt(
+    loader: &boot_info::BootInfo,
+) -> Result<acpi::RootSdt, uefi::Error> {
+    if loader.acpi_rsdp == 0 {
+        return Err(uefi::Error::NotFound);
+    }
+    let rsdp_ptr = uefi::Ptr(loader.acpi_rsdp as usize);
+    let rsdp = unsafe {
+        acpi::Rsdp::new(rsdp_ptr)
+            .map_err(|err| err.context("RSDP", rsdp_ptr.0))?
+    };
+    rsdp.root_sdt()
+}
+
 /// Kernel entry point.
-fn os_main(boot_info: BootInfo) -> ! {
-    println!("lapic: {:#x?}", boot_info.acpi_madt.lapic());
-    println!("memory map: {:#x?}", boot_info.available_memory.ranges());
-    println!("memory size: {}", boot_info.available_memory.size());
+fn os_main(mut boot_info: BootInfo) -> ! {
+    // Interrupts stay disabled until the interrupt controllers are set up.
+    // Then, load the kernel descriptor tables, so exceptions are reported
+    // instead of resetting the machine.
+    unsafe {
+        cpu::cli();
+        gdt::init();
+        idt::init();
+
+        // Move the legacy PICs away from the exception vectors and mask
+        // them. The IRQs are routed through the IO APIC, if there is one.
+        // Otherwise, they are unmasked as their drivers are initialized.
+        pic8259::init(idt::PIC_VECTOR_BASE);
+    }
+
+    // Initialize the wall-clock service.
+    time::init(boot_info.acpi_fadt.as_ref().and_then(|fadt| fadt.century()));
+
+    // Calibrate the clocks and start the periodic tick. Then, the interrupts
+    // can be enabled.
+    let lapic_enabled = match unsafe { lapic::init(idt::SPURIOUS_VECTOR) } {
+        Ok(()) => {
+            init_clocks(&boot_info);
+            true
+        }
+        Err(err) => {
+            warn!(target: "lapic", "{:?}", err);
+            false
+        }
+    };
+    phases::mark("clocks");
+
+    info!(
+        target: "time",
+        "wallclock {:?} ({})",
+        time::now(),
+        time::wallclock()
+    );
+
+    // The AP trampoline must be below 1MiB, so its page is reserved before
+    // the frame allocator takes over the available memory.
+    let trampoline = smp::reserve_trampoline(&mut boot_info.available_memory);
+    match unsafe { pmm::init(&mut boot_info.available_memory) } {
+        Ok(n) => info!(target: "pmm", "{} free frames", n),
+        Err(err) => error!(target: "pmm", "{:?}", err),
+    }
+
+    // Switch to the kernel page tables. The page tables of the bootloader
+    // keep mapping the memory of the boot information.
+    match unsafe { paging::init(boot_info.loader) } {
+        Ok(pml4) => {
+            info!(target: "paging", "kernel page tables at {:#x}", pml4)
+        }
+        Err(err) => error!(target: "paging", "{:?}", err),
+    }
+    phases::mark("paging");
+
+    // Set up the kernel heap on top of the frame allocator.
+    #[cfg(feature = "heap")]
+    match unsafe { heap::init() } {
+        Ok(()) => info!(target: "heap", "{} KiB", heap::stats().0 / 1024),
+        Err(err) => error!(target: "heap", "{:?}", err),
+    }
+
+    let mut ioapic_enabled = false;
+    match &boot_info.acpi_madt {
+        Some(madt) => {
+            info!(target: "lapic", "addr {:#x}", madt.lapic_addr64());
+            for entry in madt.entries() {
+                debug!(target: "madt", "{:#x?}", entry);
+            }
+
+            // The kernel page tables identity map the IO APICs.
+            match unsafe { ioapic::init(madt) } {
+                Ok(n) => {
+                    info!(target: "ioapic", "{} IO APICs, all masked", n);
+                    ioapic_enabled = true;
+                }
+                Err(err) => error!(target: "ioapic", "{:?}", err),
+            }
+
+            // The APs are started with the INIT-SIPI-SIPI sequence, which is
+            // sent by the local APIC of the BSP. The kernel page tables
+            // identity map the low memory.
+            if !cmdline::get_bool("smp").unwrap_or(true) {
+                info!(target: "smp", "disabled by the command line");
+            } else if lapic_enabled {
+                // The APs share the kernel page tables, so the unmapped
+                // pages must be invalidated on all of them.
+                tlb::init();
+                let result = trampoline
+                    .and_then(|addr| unsafe { smp::start_aps(madt, addr) });
+                match result {
+                    Ok(n) => info!(target: "smp", "{} CPUs online", n),
+                    Err(err) => warn!(
+                        target: "smp",
+                        "{:?}, {} CPUs online",
+                        err,
+                        smp::cpu_count()
+                    ),
+                }
+                phases::mark("smp");
+            }
+        }
+        None => warn!(target: "lapic", "not available"),
+    }
+
+    // The keyboard IRQ is only routed through the IO APIC if the local APIC
+    // can receive it.
+    match unsafe { keyboard::init(ioapic_enabled && lapic_enabled) } {
+        Ok(route) => info!(target: "keyboard", "IRQ via {:?}", route),
+        Err(err) => warn!(target: "keyboard", "{:?}", err),
+    }
+    phases::mark("keyboard");
+
+    // The PCI devices are only enumerated through the ECAM.
+    match &boot_info.acpi_mcfg {
+        Some(mcfg) => {
+            match unsafe { pci::init(mcfg) } {
+                Ok(()) => {
+                    let disks = unsafe { nvme::init(mcfg) };
+                    info!(target: "nvme", "{} disks", disks);
+                    let disks = unsafe { ahci::init(mcfg) };
+                    info!(target: "ahci", "{} disks", disks);
+                }
+                Err(err) => warn!(target: "pci", "{:?}", err),
+            }
+            phases::mark("pci");
+        }
+        None => warn!(target: "pci", "ecam not available"),
+    }
+
+    match unsafe { disk::init_ramdisk() } {
+        Ok(0) => {}
+        Ok(size) => info!(target: "ramdisk", "{} bytes", size),
+        Err(err) => warn!(target: "ramdisk", "{:?}", err),
+    }
+    disk::for_each(|name, device| {
+        info!(
+            target: "disk",
+            "{}: {} blocks of {} bytes",
+            name,
+            device.blocks(),
+            device.block_size(),
+        );
+        // The partition tables are not parsed yet, so only the volumes that
+        // start at the first block are found.
+        match fat::FileSystem::new(device) {
+            Ok(fs) => info!(target: "fat", "{}: {:?}", name, fs.kind()),
+            Err(err) => debug!(target: "fat", "{}: {:?}", name, err),
+        }
+    });
+    phases::mark("disks");
+
+    if boot_info.loader.entropy_seed().is_some() {
+        info!(target: "entropy", "seed available");
+    } else {
+        warn!(target: "entropy", "seed not available");
+    }
+    match boot_info.loader.tpm_event_log {
+        0 => info!(target: "tpm", "event log not available"),
+        location => info!(
+            target: "tpm",
+            "event log at {:#x} (format {})",
+            location, boot_info.loader.tpm_event_log_format,
+        ),
+    }
+    match &boot_info.acpi_hpet {
+        Some(hpet) => info!(
+            target: "hpet",
+            "base {:#x}, {} comparators, minimum tick {}",
+            hpet.base_address(),
+            hpet.comparator_count(),
+            hpet.minimum_tick(),
+        ),
+        None => info!(target: "hpet", "not available"),
+    }
+    if let Some(fadt) = &boot_info.acpi_fadt {
+        debug!(target: "acpi", "sci irq {}", fadt.sci_int());
+        debug!(target: "acpi", "pm timer {:#x?}", fadt.pm_timer());
+        debug!(target: "acpi", "reset register {:#x?}", fadt.reset_register());
+    }
+    match &boot_info.acpi_mcfg {
+        Some(mcfg) => info!(target: "pcie", "ecam {:#x?}", mcfg.allocations()),
+        None => info!(target: "pcie", "ecam not available"),
+    }
+    match boot_info.loader.framebuffer() {
+        Some(fb) => info!(
+            target: "framebuffer",
+            "{:#x}, {}x{}, stride {}, format {}",
+            fb.base,
+            fb.width,
+            fb.height,
+            fb.stride,
+            fb.format.raw(),
+        ),
+        None => info!(target: "framebuffer", "not available"),
+    }
+    print_memory(&boot_info);
+    info!(target: "memory", "size {}", boot_info.available_memory.size());
+
+    phases::mark("boot report");
+    phases::print();
+
+    // Check that the periodic tick is running.
+    let ticks = TICKS.load(Ordering::Relaxed);
+    time::busy_sleep(Duration::from_millis(100));
+    info!(
+        target: "time",
+        "{} ticks in 100 ms, uptime {} ms",
+        TICKS.load(Ordering::Relaxed) - ticks,
+        time::now_ns() / 1_000_000,
+    );
+
+    // Check that the tasks are scheduled and run the first user program.
+    // The scheduler needs the periodic tick.
+    if lapic_enabled {
+        match unsafe { task::init() } {
+            Ok(()) => {
+                check_tasks();
+
+                // The tasks only run on the BSP, so it is the only CPU that
+                // enters user mode.
+                unsafe { syscall::init() };
+                run_init();
+            }
+            Err(err) => error!(target: "task", "{:?}", err),
+        }
+    }
+
+    // Report the keys pressed during boot.
+    while let Some(event) = keyboard::read_event() {
+        debug!(target: "keyboard", "{:?} ({:?})", event, event.char());
+    }
+    if keyboard::dropped() != 0 {
+        warn!(target: "keyboard", "{} events dropped", keyboard::dropped());
+    }
+
+    // Run the work deferred by the interrupt handlers.
+    DEFERRED_WORK.run_pending();
 
     panic!("end");
 }
+
+/// Number of worker tasks spawned by `check_tasks`.
+const CHECK_WORKERS: usize = 3;
+
+/// Spawns worker tasks that sleep and yield, and waits for them to exit.
+fn check_tasks() {
+    let start = time::now_ns();
+    let mut workers = [None; CHECK_WORKERS];
+    for (n, worker) in workers.iter_mut().enumerate() {
+        match task::spawn("worker", worker_task, n) {
+            Ok(id) => *worker = Some(id),
+            Err(err) => warn!(target: "task", "worker {}: {:?}", n, err),
+        }
+    }
+
+    let mut joined = 0;
+    for (n, id) in workers.iter().enumerate() {
+        let id = match id {
+            Some(id) => *id,
+            None => continue,
+        };
+        match task::join(id) {
+            Ok(status) => {
+                debug!(target: "task", "worker {}: status {}", n, status);
+                joined += 1;
+            }
+            Err(err) => warn!(target: "task", "worker {}: {:?}", n, err),
+        }
+    }
+    info!(
+        target: "task",
+        "{} workers joined in {} ms",
+        joined,
+        (time::now_ns() - start) / 1_000_000,
+    );
+}
+
+/// Worker task spawned by `check_tasks`. Every worker sleeps for a
+/// different time, so they exit in order. It returns `n`.
+fn worker_task(n: usize) -> i32 {
+    for _ in 0..3 {
+        task::sleep(Duration::from_millis(10 * (n as u64 + 1)));
+        task::yield_now();
+    }
+    n as i32
+}
+
+/// Runs the user program embedded in the kernel and waits for it to exit.
+fn run_init() {
+    let result = user::spawn("init", user::init_program())
+        .and_then(|id| task::join(id).map_err(user::Error::Task));
+    match result {
+        Ok(status) => {
+            info!(target: "user", "init exited with status {}", status)
+        }
+        Err(err) => error!(target: "user", "{:?}", err),
+    }
+}
+
+/// Logs the memory map, the kernel segments and the available memory at the
+/// debug level.
+fn print_memory(boot_info: &BootInfo) {
+    for region in unsafe { boot_info.loader.memory_regions.as_slice() } {
+        debug!(
+            target: "memory",
+            "map {:#x} {} pages kind {} attributes {:#x}",
+            region.start,
+            region.size / mm::PAGE_SIZE,
+            region.kind.raw(),
+            region.attributes.bits(),
+        );
+    }
+    for segment in unsafe { boot_info.loader.kernel_segments.as_slice() } {
+        debug!(
+            target: "memory",
+            "kernel segment {:#x} -> {:#x} {} pages flags {:#x}",
+            segment.virt,
+            segment.phys,
+            segment.size / mm::PAGE_SIZE,
+            segment.flags.bits(),
+        );
+    }
+    debug!(
+        target: "memory",
+        "available {:#x?}",
+        boot_info.available_memory.ranges()
+    );
+}
+
+/// Calibrates the clocks, starts the periodic tick and enables the
+/// interrupts.
+fn init_clocks(boot_info: &BootInfo) {
+    // The HPET is identity mapped by the page tables of the bootloader.
+    let calibration = unsafe { time::calibrate(boot_info.acpi_hpet.as_ref()) };
+    info!(
+        target: "time",
+        "tsc {} Hz, lapic timer {} Hz ({:?} reference)",
+        calibration.tsc_hz(),
+        calibration.lapic_timer_hz(),
+        calibration.reference(),
+    );
+
+    time::register_tick(|| {
+        TICKS.fetch_add(1, Ordering::Relaxed);
+    })
+    .unwrap();
+
+    match unsafe { time::start_tick() } {
+        Ok(()) => unsafe { cpu::sti() },
+        Err(err) => warn!(target: "time", "tick not started: {:?}", err),
+    }
+}
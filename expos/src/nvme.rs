@@ -0,0 +1,823 @@
+//! NVMe driver.
+//!
+//! The controllers are found by their PCI class and reset, so they are
+//! configured from a known state. Every controller gets an admin queue pair
+//! and a single IO queue pair in DMA memory. The first active namespace is
+//! exposed as a disk.
+//!
+//! The commands are synchronous: they are submitted one at a time and the
+//! completion queue is polled until they complete, so the interrupts of the
+//! controllers are masked. The data is copied through a bounce buffer of two
+//! pages, which is described by the two PRP entries of the commands without
+//! a PRP list.
+//!
+//! Reference:
+//! - NVM Express Base Specification, Revision 1.4
+//! - [OSDev article](https://wiki.osdev.org/NVMe)
+
+use core::time::Duration;
+
+use block::BlockDevice;
+use ticket_mutex::TicketMutex;
+use uefi::acpi;
+
+use crate::dma::{self, DmaBuffer};
+use crate::pci::{self, Class, Command as PciCommand};
+use crate::{info, time, warn};
+
+/// PCI class of the NVMe controllers: mass storage, non-volatile memory,
+/// NVM Express.
+const CLASS_NVME: Class = Class::new(0x01, 0x08, 0x02);
+
+/// Maximum number of controllers.
+const MAX_CONTROLLERS: usize = 4;
+
+/// Controller Capabilities register.
+const REG_CAP: usize = 0x00;
+
+/// Interrupt Mask Set register.
+const REG_INTMS: usize = 0x0c;
+
+/// Controller Configuration register.
+const REG_CC: usize = 0x14;
+
+/// Controller Status register.
+const REG_CSTS: usize = 0x1c;
+
+/// Admin Queue Attributes register.
+const REG_AQA: usize = 0x24;
+
+/// Admin Submission Queue Base Address register.
+const REG_ASQ: usize = 0x28;
+
+/// Admin Completion Queue Base Address register.
+const REG_ACQ: usize = 0x30;
+
+/// Offset of the first doorbell register.
+const REG_DOORBELLS: usize = 0x1000;
+
+/// CAP flag: the controller supports the NVM command set.
+const CAP_CSS_NVM: u64 = 1 << 37;
+
+/// CC flag: the controller is enabled.
+const CC_EN: u32 = 1 << 0;
+
+/// CC value: the IO submission queue entries are 64 (2^6) bytes.
+const CC_IOSQES: u32 = 6 << 16;
+
+/// CC value: the IO completion queue entries are 16 (2^4) bytes.
+const CC_IOCQES: u32 = 4 << 20;
+
+/// CSTS flag: the controller is ready to process commands.
+const CSTS_RDY: u32 = 1 << 0;
+
+/// CSTS flag: the controller hit a fatal error.
+const CSTS_CFS: u32 = 1 << 1;
+
+/// Admin command: create an IO submission queue.
+const ADMIN_CREATE_IO_SQ: u8 = 0x01;
+
+/// Admin command: create an IO completion queue.
+const ADMIN_CREATE_IO_CQ: u8 = 0x05;
+
+/// Admin command: identify.
+const ADMIN_IDENTIFY: u8 = 0x06;
+
+/// NVM command: flush the volatile write cache.
+const IO_FLUSH: u8 = 0x00;
+
+/// NVM command: write.
+const IO_WRITE: u8 = 0x01;
+
+/// NVM command: read.
+const IO_READ: u8 = 0x02;
+
+/// Identify: namespace data structure of the provided namespace.
+const CNS_NAMESPACE: u32 = 0x00;
+
+/// Identify: controller data structure.
+const CNS_CONTROLLER: u32 = 0x01;
+
+/// Identify: list of active namespace IDs.
+const CNS_ACTIVE_NAMESPACES: u32 = 0x02;
+
+/// Create IO queue flag: the queue is physically contiguous.
+const QUEUE_PHYS_CONTIGUOUS: u32 = 1 << 0;
+
+/// Completion queue entry flag: phase tag.
+const STATUS_PHASE: u16 = 1 << 0;
+
+/// ID of the admin queue pair.
+const ADMIN_QUEUE_ID: u16 = 0;
+
+/// ID of the IO queue pair.
+const IO_QUEUE_ID: u16 = 1;
+
+/// Maximum number of entries of a queue.
+const QUEUE_ENTRIES: u16 = 64;
+
+/// Memory page size used by the controller. It is the minimum page size of
+/// the NVMe specification.
+const PAGE_SIZE: usize = 0x1000;
+
+/// Size of the bounce buffer. It is limited to two pages, so it can be
+/// described without a PRP list.
+const BOUNCE_SIZE: usize = 2 * PAGE_SIZE;
+
+/// Maximum size of a block.
+const MAX_BLOCK_SIZE: usize = PAGE_SIZE;
+
+/// Interval between the checks for the completion of a command.
+const COMMAND_POLL_INTERVAL: Duration = Duration::from_micros(10);
+
+/// Number of checks for the completion of a command, so a command may take
+/// up to 5 seconds. The checks are delayed with `time::busy_sleep`, which
+/// falls back to the PIT if the TSC is not calibrated.
+const COMMAND_TIMEOUT_POLLS: usize = 500_000;
+
+/// Interval between the checks for a change of CSTS.RDY.
+const READY_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Unit of the reset timeout in CAP.TO.
+const CAP_TO_UNIT: Duration = Duration::from_millis(500);
+
+/// Names of the disks in the disk registry.
+const NAMES: [&str; MAX_CONTROLLERS] = ["nvme0", "nvme1", "nvme2", "nvme3"];
+
+/// NVMe disks.
+static DISKS: [NvmeDisk; MAX_CONTROLLERS] = [
+    NvmeDisk::new(),
+    NvmeDisk::new(),
+    NvmeDisk::new(),
+    NvmeDisk::new(),
+];
+
+/// NVMe errors.
+#[derive(Debug)]
+pub enum Error {
+    /// The registers of the controller could not be mapped.
+    Pci(pci::Error),
+
+    /// The queues or the bounce buffer could not be allocated.
+    Dma(dma::Error),
+
+    /// The controller does not support the NVM command set.
+    NoNvmCommandSet,
+
+    /// The minimum memory page size of the controller is bigger than 4KiB.
+    UnsupportedPageSize,
+
+    /// The controller did not change its state or complete a command in
+    /// time.
+    Timeout,
+
+    /// The controller hit a fatal error.
+    Fatal,
+
+    /// A command failed with the provided status field (without the phase
+    /// tag).
+    Command(u16),
+
+    /// The controller has no active namespace.
+    NoNamespace,
+
+    /// The block size of the namespace is not supported.
+    UnsupportedBlockSize(u64),
+}
+
+/// Submission queue entry.
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+struct SubmissionEntry {
+    /// Opcode and command identifier.
+    cdw0: u32,
+    nsid: u32,
+    cdw2: u32,
+    cdw3: u32,
+    mptr: u64,
+    prp1: u64,
+    prp2: u64,
+    cdw10: u32,
+    cdw11: u32,
+    cdw12: u32,
+    cdw13: u32,
+    cdw14: u32,
+    cdw15: u32,
+}
+
+impl SubmissionEntry {
+    /// Returns a `SubmissionEntry` with the opcode `opcode`.
+    fn new(opcode: u8) -> Self {
+        SubmissionEntry {
+            cdw0: u32::from(opcode),
+            ..SubmissionEntry::default()
+        }
+    }
+}
+
+/// Completion queue entry.
+// The controller writes all the fields, but only some of them are used.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct CompletionEntry {
+    /// Command specific result.
+    dw0: u32,
+    dw1: u32,
+    sq_head: u16,
+    sq_id: u16,
+    cid: u16,
+
+    /// Status field and phase tag.
+    status: u16,
+}
+
+/// Represents a submission queue and its completion queue.
+struct QueuePair {
+    /// Submission queue entries.
+    sq: DmaBuffer,
+
+    /// Completion queue entries.
+    cq: DmaBuffer,
+
+    /// Number of entries of both queues.
+    entries: u16,
+
+    /// Submission queue tail doorbell.
+    sq_doorbell: *mut u32,
+
+    /// Completion queue head doorbell.
+    cq_doorbell: *mut u32,
+
+    sq_tail: u16,
+    cq_head: u16,
+
+    /// Phase tag of the new completion queue entries. It is inverted every
+    /// time the queue wraps around.
+    phase: bool,
+
+    /// Identifier of the next command.
+    next_cid: u16,
+}
+
+impl QueuePair {
+    /// Allocates a queue pair with `entries` entries and the ID `id`.
+    /// `regs` are the registers of the controller and `doorbell_stride` the
+    /// distance between the doorbell registers.
+    fn new(
+        regs: *mut u8,
+        doorbell_stride: usize,
+        id: u16,
+        entries: u16,
+    ) -> Result<QueuePair, Error> {
+        let sq_size =
+            entries as usize * core::mem::size_of::<SubmissionEntry>();
+        let cq_size =
+            entries as usize * core::mem::size_of::<CompletionEntry>();
+        let sq = dma::alloc(sq_size, PAGE_SIZE as u64, dma::LIMIT_64BIT)
+            .map_err(Error::Dma)?;
+        let cq = match dma::alloc(cq_size, PAGE_SIZE as u64, dma::LIMIT_64BIT)
+        {
+            Ok(cq) => cq,
+            Err(err) => {
+                // The controller does not know about the queue yet.
+                unsafe { dma::free(sq) };
+                return Err(Error::Dma(err));
+            }
+        };
+
+        let doorbell = |n: usize| unsafe {
+            regs.add(REG_DOORBELLS + n * doorbell_stride) as *mut u32
+        };
+        Ok(QueuePair {
+            sq,
+            cq,
+            entries,
+            sq_doorbell: doorbell(2 * id as usize),
+            cq_doorbell: doorbell(2 * id as usize + 1),
+            sq_tail: 0,
+            cq_head: 0,
+            phase: true,
+            next_cid: 0,
+        })
+    }
+
+    /// Submits the command `cmd` and waits for its completion. It returns
+    /// the command specific result.
+    ///
+    /// # Safety
+    ///
+    /// The memory referenced by the command must be valid for the command.
+    unsafe fn execute(
+        &mut self,
+        mut cmd: SubmissionEntry,
+    ) -> Result<u32, Error> {
+        let cid = self.next_cid;
+        self.next_cid = self.next_cid.wrapping_add(1);
+        cmd.cdw0 |= u32::from(cid) << 16;
+
+        let sq = self.sq.as_ptr() as *mut SubmissionEntry;
+        sq.add(self.sq_tail as usize).write_volatile(cmd);
+        self.sq_tail = (self.sq_tail + 1) % self.entries;
+        self.sq_doorbell.write_volatile(u32::from(self.sq_tail));
+
+        let cq = self.cq.as_ptr() as *const CompletionEntry;
+        let mut polls = 0;
+        loop {
+            let entry = cq.add(self.cq_head as usize).read_volatile();
+            if (entry.status & STATUS_PHASE != 0) != self.phase {
+                if polls >= COMMAND_TIMEOUT_POLLS {
+                    return Err(Error::Timeout);
+                }
+                time::busy_sleep(COMMAND_POLL_INTERVAL);
+                polls += 1;
+                continue;
+            }
+
+            self.cq_head = (self.cq_head + 1) % self.entries;
+            if self.cq_head == 0 {
+                self.phase = !self.phase;
+            }
+            self.cq_doorbell.write_volatile(u32::from(self.cq_head));
+
+            // The completions of the commands that timed out are discarded.
+            if entry.cid == cid {
+                return match entry.status >> 1 {
+                    0 => Ok(entry.dw0),
+                    status => Err(Error::Command(status)),
+                };
+            }
+        }
+    }
+
+    /// Frees the queues.
+    ///
+    /// # Safety
+    ///
+    /// The controller must not access the queues anymore.
+    unsafe fn free(self) {
+        dma::free(self.sq);
+        dma::free(self.cq);
+    }
+}
+
+/// Represents an initialized NVMe controller and its first namespace.
+struct Controller {
+    /// Admin queue pair.
+    admin: QueuePair,
+
+    /// IO queue pair.
+    io: QueuePair,
+
+    /// Bounce buffer for the data of the commands.
+    buffer: DmaBuffer,
+
+    /// Maximum number of bytes transferred by a command. It is a multiple of
+    /// the block size.
+    max_transfer: usize,
+
+    /// ID of the namespace.
+    nsid: u32,
+
+    /// Size of a block in bytes.
+    block_size: usize,
+
+    /// Number of blocks of the namespace.
+    blocks: u64,
+
+    /// Model number reported by the controller, padded with spaces.
+    model: [u8; 40],
+}
+
+// The registers and the queues are owned by the `Controller`.
+unsafe impl Send for Controller {}
+
+impl Controller {
+    /// Resets and initializes the NVMe controller `function`. On failure,
+    /// the controller is disabled and its DMA memory is freed.
+    ///
+    /// # Safety
+    ///
+    /// Nothing else may be accessing the controller.
+    unsafe fn init(function: &pci::Function) -> Result<Controller, Error> {
+        let bar = function.map_bar(0).map_err(Error::Pci)?;
+        function.enable(
+            PciCommand::MEMORY_SPACE
+                | PciCommand::BUS_MASTER
+                | PciCommand::INTERRUPT_DISABLE,
+        );
+        let regs = bar.addr.as_u64() as *mut u8;
+
+        let cap = read64(regs, REG_CAP);
+        if cap & CAP_CSS_NVM == 0 {
+            return Err(Error::NoNvmCommandSet);
+        }
+        if (cap >> 48) & 0xf != 0 {
+            return Err(Error::UnsupportedPageSize);
+        }
+        let max_entries = ((cap & 0xffff) as u16).saturating_add(1);
+        let doorbell_stride = 4 << ((cap >> 32) & 0xf);
+        let timeout = CAP_TO_UNIT * ((cap >> 24) & 0xff).max(1) as u32;
+
+        // The BAR must cover the doorbells of both queue pairs.
+        let doorbells_end = REG_DOORBELLS + 4 * doorbell_stride;
+        if bar.size < doorbells_end as u64 {
+            return Err(Error::Pci(pci::Error::InvalidBar(0)));
+        }
+
+        // Reset the controller, so it forgets the queues of the firmware.
+        let cc = read32(regs, REG_CC);
+        if cc & CC_EN != 0 {
+            write32(regs, REG_CC, cc & !CC_EN);
+        }
+        wait_ready(regs, false, timeout)?;
+
+        let entries = QUEUE_ENTRIES.min(max_entries);
+        let admin =
+            QueuePair::new(regs, doorbell_stride, ADMIN_QUEUE_ID, entries)?;
+        // The controller does not know about the memory yet.
+        let io = QueuePair::new(regs, doorbell_stride, IO_QUEUE_ID, entries);
+        let io = match io {
+            Ok(io) => io,
+            Err(err) => {
+                admin.free();
+                return Err(err);
+            }
+        };
+        let buffer =
+            dma::alloc(BOUNCE_SIZE, PAGE_SIZE as u64, dma::LIMIT_64BIT);
+        let buffer = match buffer {
+            Ok(buffer) => buffer,
+            Err(err) => {
+                admin.free();
+                io.free();
+                return Err(Error::Dma(err));
+            }
+        };
+
+        let mut controller = Controller {
+            admin,
+            io,
+            buffer,
+            max_transfer: BOUNCE_SIZE,
+            nsid: 0,
+            block_size: 0,
+            blocks: 0,
+            model: [b' '; 40],
+        };
+        if let Err(err) = controller.start(regs, timeout) {
+            controller.stop(function, regs, timeout);
+            return Err(err);
+        }
+        Ok(controller)
+    }
+
+    /// Enables the controller, whose registers are at `regs`, with the
+    /// admin queue pair. Then, it identifies the controller and creates the
+    /// IO queues. `timeout` is the maximum time the controller takes to
+    /// become ready.
+    unsafe fn start(
+        &mut self,
+        regs: *mut u8,
+        timeout: Duration,
+    ) -> Result<(), Error> {
+        let entries = u32::from(self.admin.entries);
+        write32(regs, REG_AQA, (entries - 1) << 16 | (entries - 1));
+        write64(regs, REG_ASQ, self.admin.sq.phys_addr().as_u64());
+        write64(regs, REG_ACQ, self.admin.cq.phys_addr().as_u64());
+
+        // The completions are polled.
+        write32(regs, REG_INTMS, u32::MAX);
+
+        write32(regs, REG_CC, CC_EN | CC_IOSQES | CC_IOCQES);
+        wait_ready(regs, true, timeout)?;
+
+        self.identify()?;
+        self.create_io_queues()
+    }
+
+    /// Disables the controller `function`, whose registers are at `regs`,
+    /// and frees its DMA memory.
+    unsafe fn stop(
+        self,
+        function: &pci::Function,
+        regs: *mut u8,
+        timeout: Duration,
+    ) {
+        // A disabled controller does not process its queues. Besides, it
+        // cannot access the memory without bus mastering, even if it does
+        // not complete the reset.
+        write32(regs, REG_CC, read32(regs, REG_CC) & !CC_EN);
+        wait_ready(regs, false, timeout).ok();
+        function.disable(PciCommand::BUS_MASTER);
+
+        self.admin.free();
+        self.io.free();
+        dma::free(self.buffer);
+    }
+
+    /// Reads the identify data structures of the controller and its first
+    /// active namespace.
+    unsafe fn identify(&mut self) -> Result<(), Error> {
+        self.identify_cmd(CNS_CONTROLLER, 0)?;
+        let data = self.buffer.as_slice();
+        self.model.copy_from_slice(&data[24..64]);
+        // The maximum data transfer size is a power of two in units of the
+        // minimum page size. Zero means no limit.
+        let mdts = data[77];
+        if mdts != 0 && mdts < 16 {
+            self.max_transfer = self.max_transfer.min(PAGE_SIZE << mdts);
+        }
+
+        self.identify_cmd(CNS_ACTIVE_NAMESPACES, 0)?;
+        let data = self.buffer.as_slice();
+        self.nsid = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        if self.nsid == 0 {
+            return Err(Error::NoNamespace);
+        }
+
+        self.identify_cmd(CNS_NAMESPACE, self.nsid)?;
+        let data = self.buffer.as_slice();
+        let mut nsze = [0; 8];
+        nsze.copy_from_slice(&data[0..8]);
+        self.blocks = u64::from_le_bytes(nsze);
+
+        // The formatted LBA size selects one of the LBA formats, whose
+        // LBADS field is the block size as a power of two.
+        let format = (data[26] & 0xf) as usize;
+        let lbads = data[128 + format * 4 + 2];
+        let block_size = 1u64.checked_shl(u32::from(lbads)).unwrap_or(0);
+        if block_size < 512 || block_size > MAX_BLOCK_SIZE as u64 {
+            return Err(Error::UnsupportedBlockSize(block_size));
+        }
+        self.block_size = block_size as usize;
+        self.max_transfer -= self.max_transfer % self.block_size;
+        Ok(())
+    }
+
+    /// Runs the identify command with the CNS value `cns` for the namespace
+    /// `nsid`. The data structure is written into the bounce buffer.
+    unsafe fn identify_cmd(
+        &mut self,
+        cns: u32,
+        nsid: u32,
+    ) -> Result<(), Error> {
+        let mut cmd = SubmissionEntry::new(ADMIN_IDENTIFY);
+        cmd.nsid = nsid;
+        cmd.prp1 = self.buffer.phys_addr().as_u64();
+        cmd.cdw10 = cns;
+        self.admin.execute(cmd)?;
+        Ok(())
+    }
+
+    /// Creates the IO completion queue and the IO submission queue.
+    unsafe fn create_io_queues(&mut self) -> Result<(), Error> {
+        let size = (u32::from(self.io.entries) - 1) << 16;
+
+        let mut cmd = SubmissionEntry::new(ADMIN_CREATE_IO_CQ);
+        cmd.prp1 = self.io.cq.phys_addr().as_u64();
+        cmd.cdw10 = size | u32::from(IO_QUEUE_ID);
+        // The interrupts of the queue are disabled.
+        cmd.cdw11 = QUEUE_PHYS_CONTIGUOUS;
+        self.admin.execute(cmd)?;
+
+        let mut cmd = SubmissionEntry::new(ADMIN_CREATE_IO_SQ);
+        cmd.prp1 = self.io.sq.phys_addr().as_u64();
+        cmd.cdw10 = size | u32::from(IO_QUEUE_ID);
+        cmd.cdw11 = u32::from(IO_QUEUE_ID) << 16 | QUEUE_PHYS_CONTIGUOUS;
+        self.admin.execute(cmd)?;
+        Ok(())
+    }
+
+    /// Transfers `len` bytes, which fit in the bounce buffer, between the
+    /// bounce buffer and the blocks starting at `lba`.
+    unsafe fn transfer(
+        &mut self,
+        opcode: u8,
+        lba: u64,
+        len: usize,
+    ) -> Result<(), Error> {
+        let phys = self.buffer.phys_addr().as_u64();
+        let mut cmd = SubmissionEntry::new(opcode);
+        cmd.nsid = self.nsid;
+        cmd.prp1 = phys;
+        if len > PAGE_SIZE {
+            cmd.prp2 = phys + PAGE_SIZE as u64;
+        }
+        cmd.cdw10 = lba as u32;
+        cmd.cdw11 = (lba >> 32) as u32;
+        cmd.cdw12 = (len / self.block_size - 1) as u32;
+        self.io.execute(cmd)?;
+        Ok(())
+    }
+
+    /// Checks that `len` bytes starting at the block `lba` are a whole
+    /// number of blocks inside the namespace.
+    fn check_range(&self, lba: u64, len: usize) -> Result<(), block::Error> {
+        block::check_range(self.block_size, self.blocks, lba, len)
+    }
+
+    /// Reads the blocks starting at `lba` into `buf`. The range must be
+    /// checked by the caller.
+    fn read(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), Error> {
+        let mut lba = lba;
+        for chunk in buf.chunks_mut(self.max_transfer) {
+            unsafe {
+                self.transfer(IO_READ, lba, chunk.len())?;
+                chunk.copy_from_slice(&self.buffer.as_slice()[..chunk.len()]);
+            }
+            lba += (chunk.len() / self.block_size) as u64;
+        }
+        Ok(())
+    }
+
+    /// Writes `buf` into the blocks starting at `lba`. The range must be
+    /// checked by the caller.
+    fn write(&mut self, lba: u64, buf: &[u8]) -> Result<(), Error> {
+        let mut lba = lba;
+        for chunk in buf.chunks(self.max_transfer) {
+            unsafe {
+                self.buffer.as_mut_slice()[..chunk.len()]
+                    .copy_from_slice(chunk);
+                self.transfer(IO_WRITE, lba, chunk.len())?;
+            }
+            lba += (chunk.len() / self.block_size) as u64;
+        }
+        Ok(())
+    }
+
+    /// Makes the written data persistent.
+    fn flush(&mut self) -> Result<(), Error> {
+        let mut cmd = SubmissionEntry::new(IO_FLUSH);
+        cmd.nsid = self.nsid;
+        unsafe { self.io.execute(cmd)? };
+        Ok(())
+    }
+}
+
+/// Represents the first namespace of an NVMe controller.
+pub struct NvmeDisk {
+    /// Controller. It is `None` if it was not initialized successfully.
+    controller: TicketMutex<Option<Controller>>,
+}
+
+impl NvmeDisk {
+    /// Returns an `NvmeDisk` without controller.
+    const fn new() -> Self {
+        NvmeDisk {
+            controller: TicketMutex::new(None),
+        }
+    }
+
+    /// Runs `f` with the controller. It fails with `block::Error::Io` if
+    /// the controller was not initialized.
+    fn with<T>(
+        &self,
+        f: impl FnOnce(&mut Controller) -> Result<T, block::Error>,
+    ) -> Result<T, block::Error> {
+        self.controller
+            .lock()
+            .as_mut()
+            .ok_or(block::Error::Io)
+            .and_then(f)
+    }
+}
+
+impl BlockDevice for NvmeDisk {
+    fn block_size(&self) -> usize {
+        self.controller
+            .lock()
+            .as_ref()
+            .map_or(0, |controller| controller.block_size)
+    }
+
+    fn blocks(&self) -> u64 {
+        self.controller
+            .lock()
+            .as_ref()
+            .map_or(0, |controller| controller.blocks)
+    }
+
+    fn read(&self, lba: u64, buf: &mut [u8]) -> Result<(), block::Error> {
+        self.with(|controller| {
+            controller.check_range(lba, buf.len())?;
+            controller.read(lba, buf).map_err(io_error)
+        })
+    }
+
+    fn write(&self, lba: u64, buf: &[u8]) -> Result<(), block::Error> {
+        self.with(|controller| {
+            controller.check_range(lba, buf.len())?;
+            controller.write(lba, buf).map_err(io_error)
+        })
+    }
+
+    fn flush(&self) -> Result<(), block::Error> {
+        self.with(|controller| controller.flush().map_err(io_error))
+    }
+}
+
+/// Reports the driver error `err` and converts it into a block device
+/// error.
+fn io_error(err: Error) -> block::Error {
+    warn!("io error: {:?}", err);
+    block::Error::Io
+}
+
+/// Initializes the NVMe controllers found in the PCI functions described by
+/// `mcfg`, registers their disks in the disk registry and returns the number
+/// of disks. The controllers that fail to initialize are reported and
+/// skipped.
+///
+/// # Safety
+///
+/// This function must be called once, after `pci::init`.
+pub unsafe fn init(mcfg: &acpi::Mcfg) -> usize {
+    let mut count = 0;
+
+    for function in pci::functions(mcfg) {
+        if function.class() != CLASS_NVME {
+            continue;
+        }
+        let (disk, name) = match DISKS.get(count) {
+            Some(disk) => (disk, NAMES[count]),
+            None => {
+                warn!("{}: too many controllers", function);
+                continue;
+            }
+        };
+
+        match Controller::init(&function) {
+            Ok(controller) => {
+                info!(
+                    "{}: {} [{:04x}:{:04x}]: {}, {} blocks of {} bytes",
+                    name,
+                    function,
+                    function.vendor_id(),
+                    function.device_id(),
+                    core::str::from_utf8(&controller.model)
+                        .unwrap_or("?")
+                        .trim_end(),
+                    controller.blocks,
+                    controller.block_size,
+                );
+                *disk.controller.lock() = Some(controller);
+                count += 1;
+                if let Err(err) = crate::disk::register(name, disk) {
+                    warn!("{}: {:?}", name, err);
+                }
+            }
+            Err(err) => warn!("{}: {:?}", function, err),
+        }
+    }
+    count
+}
+
+/// Reads the 32-bit register at `offset`.
+unsafe fn read32(regs: *mut u8, offset: usize) -> u32 {
+    (regs.add(offset) as *const u32).read_volatile()
+}
+
+/// Writes the 32-bit register at `offset`.
+unsafe fn write32(regs: *mut u8, offset: usize, value: u32) {
+    (regs.add(offset) as *mut u32).write_volatile(value)
+}
+
+/// Reads the 64-bit register at `offset`, as two 32-bit reads.
+unsafe fn read64(regs: *mut u8, offset: usize) -> u64 {
+    let low = read32(regs, offset);
+    let high = read32(regs, offset + 4);
+    u64::from(high) << 32 | u64::from(low)
+}
+
+/// Writes the 64-bit register at `offset`, as two 32-bit writes.
+unsafe fn write64(regs: *mut u8, offset: usize, value: u64) {
+    write32(regs, offset, value as u32);
+    write32(regs, offset + 4, (value >> 32) as u32);
+}
+
+/// Waits until CSTS.RDY is `ready`, for at most `timeout`. The checks are
+/// delayed with `time::busy_sleep`, which falls back to the PIT if the TSC is
+/// not calibrated.
+unsafe fn wait_ready(
+    regs: *mut u8,
+    ready: bool,
+    timeout: Duration,
+) -> Result<(), Error> {
+    let max_polls = timeout.as_millis() / READY_POLL_INTERVAL.as_millis();
+    let mut polls = 0;
+    loop {
+        let csts = read32(regs, REG_CSTS);
+        if csts != u32::MAX && (csts & CSTS_RDY != 0) == ready {
+            return Ok(());
+        }
+        if ready && csts & CSTS_CFS != 0 {
+            return Err(Error::Fatal);
+        }
+        if polls >= max_polls {
+            return Err(Error::Timeout);
+        }
+        time::busy_sleep(READY_POLL_INTERVAL);
+        polls += 1;
+    }
+}
@@ -0,0 +1,431 @@
+//! Kernel page tables.
+//!
+//! The page tables of the bootloader are replaced by the kernel ones, which
+//! map:
+//!
+//! - The physical memory at `mm::PHYS_MAP_BASE`, so any frame can be
+//!   accessed through `mm::phys_to_virt`.
+//! - The kernel segments at their virtual addresses, which are in the
+//!   higher half (see `kernel.ld`).
+//! - The physical memory identity mapped, like the page tables of the
+//!   bootloader. It covers the memory-mapped devices, the firmware tables
+//!   and the boot information, which are referenced by their physical
+//!   address.
+//!
+//! The kernel page tables are allocated from the frame allocator. The page
+//! tables of the bootloader are in bootloader memory, which is not released
+//! to the frame allocator.
+//!
+//! Every PML4 entry of the higher half points to a page table from the
+//! start, so they never change. Thus, the page tables that copy them (e.g.
+//! the user ones) see the kernel mappings added later, like the heap and the
+//! kernel stacks.
+//!
+//! No page is writable and executable, except the low memory, where the AP
+//! trampoline lives:
+//!
+//! - The kernel segments are mapped with the permissions of their ELF
+//!   program headers, which must not be writable and executable. Their
+//!   aliases in the identity and linear mappings are never executable.
+//! - The runtime services regions are mapped with the permissions described
+//!   by the bootloader, which come from the Memory Attributes Table if the
+//!   firmware provides it.
+//! - The rest of the physical memory is mapped writable and not executable.
+//!   The linear mapping at `mm::PHYS_MAP_BASE` is never executable.
+//!
+//! The huge pages overlapping a region with different permissions are split
+//! into smaller pages.
+
+use boot_info::{
+    BootInfo, KernelSegment, MemoryAttributes, MemoryKind, MemoryRegion,
+    SegmentFlags,
+};
+use mm::paging::{self, Mapper, PageSize, PageTableFlags};
+use mm::{PhysAddr, VirtAddr};
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::pmm;
+
+/// Size of the physical memory that is mapped even if it is not described
+/// by the memory map. It covers the memory-mapped devices below 4GiB (e.g.
+/// the local APIC and the IO APICs).
+const MIN_PHYS_MAP_SIZE: u64 = 0x1_0000_0000;
+
+/// End of the low memory. It is mapped writable and executable, because the
+/// AP trampoline is copied to it and runs from it.
+const LOW_MEMORY_END: u64 = 0x10_0000;
+
+/// Index of the first PML4 entry of the higher half.
+const HIGHER_HALF_PML4_INDEX: usize = paging::ENTRY_COUNT / 2;
+
+/// Mask of the physical address of the PML4 in CR3.
+const CR3_ADDR_MASK: u64 = 0x000f_ffff_ffff_f000;
+
+/// Set when IA32_EFER.NXE is enabled, so the `NO_EXECUTE` flag can be used.
+static NX_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Kernel page table errors.
+#[derive(Debug)]
+pub enum Error {
+    /// There is no free frame for the PML4 or the page tables of the higher
+    /// half.
+    NoMemory,
+
+    /// A region could not be mapped.
+    Map(paging::Error),
+
+    /// The kernel segment at the given virtual address is writable and
+    /// executable.
+    WritableCode(u64),
+
+    /// The kernel segment at the given virtual address is not aligned to a
+    /// page.
+    UnalignedSegment(u64),
+
+    /// The device page at the given physical address cannot be identity
+    /// mapped, because its address is not canonical or it is already mapped
+    /// to another address.
+    InvalidMmio(u64),
+}
+
+/// Returns the flags of the kernel data mappings. They are not executable
+/// if IA32_EFER.NXE is enabled.
+pub fn data_flags() -> PageTableFlags {
+    if NX_ENABLED.load(Ordering::Relaxed) {
+        PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE
+    } else {
+        PageTableFlags::WRITABLE
+    }
+}
+
+/// Returns a `Mapper` that manages the page tables loaded on the current
+/// CPU.
+///
+/// # Safety
+///
+/// The current page tables must map the physical memory at
+/// `mm::phys_offset()`.
+pub unsafe fn current_mapper() -> Mapper {
+    let pml4 = PhysAddr::new(cpu::read_cr3() & CR3_ADDR_MASK);
+    Mapper::new(pml4, mm::phys_offset())
+}
+
+/// Identity maps the device registers between the physical addresses
+/// `phys` and `phys + size` (exclusive), which can be above the physical
+/// memory mapped at boot (e.g. in a 64-bit BAR). The pages that are already
+/// identity mapped are kept and the new ones are not cacheable.
+///
+/// # Safety
+///
+/// The range must only contain device registers. The current page tables
+/// must map the physical memory at `mm::phys_offset()`.
+pub unsafe fn map_mmio(phys: PhysAddr, size: u64) -> Result<(), Error> {
+    let page_size = PageSize::Size4KiB.bytes();
+    let start = phys.align_down(page_size).as_u64();
+    let end = phys
+        .as_u64()
+        .checked_add(size)
+        .ok_or(Error::InvalidMmio(phys.as_u64()))?;
+
+    let mut mapper = current_mapper();
+    for page in (start..end).step_by(page_size as usize) {
+        match mapper.translate(VirtAddr::new(page)) {
+            Some(mapped) if mapped.as_u64() == page => continue,
+            Some(_) => return Err(Error::InvalidMmio(page)),
+            None => {}
+        }
+        mapper
+            .map_to(
+                VirtAddr::new(page),
+                PhysAddr::new(page),
+                PageSize::Size4KiB,
+                data_flags() | PageTableFlags::NO_CACHE,
+                &mut pmm::PageTableFrames,
+            )
+            .map_err(|err| match err {
+                paging::Error::NonCanonical => Error::InvalidMmio(page),
+                err => Error::Map(err),
+            })?;
+    }
+    Ok(())
+}
+
+/// Builds the kernel page tables and loads them on the current CPU. It also
+/// enables IA32_EFER.NXE, if supported, and CR0.WP. It returns the physical
+/// address of the PML4.
+///
+/// # Safety
+///
+/// This function must be called once, by the BSP, after `pmm::init` and
+/// while running on the identity-mapped page tables of the bootloader.
+/// `boot_info` must be the valid boot information passed by the bootloader.
+pub unsafe fn init(boot_info: &BootInfo) -> Result<PhysAddr, Error> {
+    let regions = boot_info.memory_regions.as_slice();
+    let kernel_segments = boot_info.kernel_segments.as_slice();
+
+    let page_size = PageSize::Size4KiB.bytes();
+    for segment in kernel_segments {
+        if segment.flags.contains(SegmentFlags::WRITE)
+            && segment.flags.contains(SegmentFlags::EXECUTE)
+        {
+            return Err(Error::WritableCode(segment.virt));
+        }
+        if segment.virt % page_size != 0 || segment.phys % page_size != 0 {
+            return Err(Error::UnalignedSegment(segment.virt));
+        }
+    }
+
+    let features = cpu::CpuFeatures::detect();
+    let no_execute = if features.nx() {
+        PageTableFlags::NO_EXECUTE
+    } else {
+        PageTableFlags::from_bits(0)
+    };
+    let permissions = Permissions {
+        regions,
+        kernel_segments,
+        no_execute,
+    };
+
+    let pml4 = pmm::alloc_frame().ok_or(Error::NoMemory)?;
+    (pml4.as_u64() as *mut paging::PageTable).write(paging::PageTable::new());
+    alloc_higher_half(pml4)?;
+
+    // The page tables of the bootloader are identity mapped.
+    let mut mapper = Mapper::new(pml4, 0);
+
+    // Map all the physical memory with the biggest pages supported.
+    let page_size = if features.pages_1gb() {
+        PageSize::Size1GiB
+    } else {
+        PageSize::Size2MiB
+    };
+    // The framebuffer can be above the end of the memory (e.g. in a 64-bit
+    // BAR), but it is also identity mapped for the framebuffer console.
+    let framebuffer_end = boot_info
+        .framebuffer()
+        .map_or(0, |framebuffer| framebuffer.base + framebuffer.size);
+    let phys_end = regions
+        .iter()
+        .map(MemoryRegion::end)
+        .max()
+        .unwrap_or(0)
+        .max(framebuffer_end)
+        .max(MIN_PHYS_MAP_SIZE);
+    map_range(
+        &mut mapper,
+        &permissions,
+        0,
+        0,
+        phys_end,
+        page_size,
+        PageTableFlags::from_bits(0),
+    )?;
+    map_range(
+        &mut mapper,
+        &permissions,
+        mm::PHYS_MAP_BASE,
+        0,
+        phys_end,
+        page_size,
+        no_execute,
+    )?;
+
+    for segment in kernel_segments {
+        map_segment(&mut mapper, segment, no_execute)?;
+    }
+
+    // The NX bit is reserved until IA32_EFER.NXE is set, so it must be
+    // enabled before loading the page tables.
+    if features.nx() {
+        cpu::enable_nx();
+        NX_ENABLED.store(true, Ordering::Relaxed);
+    }
+    cpu::write_cr3(pml4.as_u64());
+    cpu::enable_write_protect();
+    mm::set_phys_offset(mm::PHYS_MAP_BASE);
+    Ok(pml4)
+}
+
+/// Points every PML4 entry of the higher half of the PML4 at `pml4` to a new
+/// empty page table. The entries are not user accessible and, as the
+/// missing tables allocated by `Mapper`, they are writable and executable,
+/// so the leaf entries decide the permissions.
+///
+/// # Safety
+///
+/// The PML4 must be empty and identity mapped.
+unsafe fn alloc_higher_half(pml4: PhysAddr) -> Result<(), Error> {
+    let table = &mut *(pml4.as_u64() as *mut paging::PageTable);
+    for index in HIGHER_HALF_PML4_INDEX..paging::ENTRY_COUNT {
+        let pdpt = pmm::alloc_frame().ok_or(Error::NoMemory)?;
+        (pdpt.as_u64() as *mut paging::PageTable)
+            .write(paging::PageTable::new());
+        table[index] = paging::PageTableEntry::new(
+            pdpt,
+            PageTableFlags::PRESENT | PageTableFlags::WRITABLE,
+        );
+    }
+    Ok(())
+}
+
+/// Maps the kernel segment `segment` at its virtual address, with the
+/// permissions of its program header. `no_execute` is `NO_EXECUTE` if it is
+/// supported, otherwise empty.
+unsafe fn map_segment(
+    mapper: &mut Mapper,
+    segment: &KernelSegment,
+    no_execute: PageTableFlags,
+) -> Result<(), Error> {
+    let mut flags = PageTableFlags::from_bits(0);
+    if segment.flags.contains(SegmentFlags::WRITE) {
+        flags = flags | PageTableFlags::WRITABLE;
+    }
+    if !segment.flags.contains(SegmentFlags::EXECUTE) {
+        flags = flags | no_execute;
+    }
+
+    let page_size = PageSize::Size4KiB.bytes();
+    for offset in (0..segment.size).step_by(page_size as usize) {
+        mapper
+            .map_to(
+                VirtAddr::new(segment.virt + offset),
+                PhysAddr::new(segment.phys + offset),
+                PageSize::Size4KiB,
+                flags,
+                &mut pmm::PageTableFrames,
+            )
+            .map_err(Error::Map)?;
+    }
+    Ok(())
+}
+
+/// Permissions of the physical memory.
+struct Permissions<'a> {
+    regions: &'a [MemoryRegion],
+    kernel_segments: &'a [KernelSegment],
+
+    /// `NO_EXECUTE` if it is supported, otherwise empty.
+    no_execute: PageTableFlags,
+}
+
+impl Permissions<'_> {
+    /// Returns the regions with permissions other than writable and not
+    /// executable, as `(start, end, flags)` tuples. `end` is exclusive. If
+    /// the regions overlap, the first one takes precedence.
+    fn regions(
+        &self,
+    ) -> impl Iterator<Item = (u64, u64, PageTableFlags)> + '_ {
+        let no_execute = self.no_execute;
+
+        let low_memory =
+            core::iter::once((0, LOW_MEMORY_END, PageTableFlags::WRITABLE));
+
+        // The kernel segments are only executable at their virtual
+        // addresses.
+        let kernel_segments =
+            self.kernel_segments.iter().map(move |segment| {
+                let flags = if segment.flags.contains(SegmentFlags::WRITE) {
+                    PageTableFlags::WRITABLE | no_execute
+                } else {
+                    no_execute
+                };
+                (segment.phys, segment.phys + segment.size, flags)
+            });
+
+        // The bootloader describes the permissions of the runtime regions.
+        let runtime = self
+            .regions
+            .iter()
+            .filter(|region| {
+                region.kind == MemoryKind::RUNTIME_CODE
+                    || region.kind == MemoryKind::RUNTIME_DATA
+            })
+            .map(move |region| {
+                let mut flags = PageTableFlags::from_bits(0);
+                if !region.attributes.contains(MemoryAttributes::READ_ONLY) {
+                    flags = flags | PageTableFlags::WRITABLE;
+                }
+                if region.attributes.contains(MemoryAttributes::NO_EXECUTE) {
+                    flags = flags | no_execute;
+                }
+                (region.start, region.end(), flags)
+            });
+
+        low_memory.chain(kernel_segments).chain(runtime)
+    }
+
+    /// Returns `true` if the memory between `start` and `end` (exclusive)
+    /// overlaps a region with special permissions.
+    fn is_special(&self, start: u64, end: u64) -> bool {
+        self.regions().any(|(region_start, region_end, _)| {
+            region_start < end && start < region_end
+        })
+    }
+
+    /// Returns the flags of the page at `phys`.
+    fn flags(&self, phys: u64) -> PageTableFlags {
+        self.regions()
+            .find(|&(start, end, _)| start <= phys && phys < end)
+            .map_or(PageTableFlags::WRITABLE | self.no_execute, |r| r.2)
+    }
+}
+
+/// Maps `size` bytes of physical memory starting at `phys` to the virtual
+/// address `virt`, using pages of size `page_size`. The region is extended
+/// to the page boundaries. The pages get the flags in `permissions` and
+/// `extra_flags`. The pages overlapping a region with special permissions
+/// are split into smaller pages.
+unsafe fn map_range(
+    mapper: &mut Mapper,
+    permissions: &Permissions,
+    virt: u64,
+    phys: u64,
+    size: u64,
+    page_size: PageSize,
+    extra_flags: PageTableFlags,
+) -> Result<(), Error> {
+    let page_mask = page_size.bytes() - 1;
+    let start = phys & !page_mask;
+    let end = (phys + size + page_mask) & !page_mask;
+
+    for offset in (0..end - start).step_by(page_size.bytes() as usize) {
+        let virt = (virt & !page_mask) + offset;
+        let phys = start + offset;
+
+        let smaller = match page_size {
+            PageSize::Size1GiB => Some(PageSize::Size2MiB),
+            PageSize::Size2MiB => Some(PageSize::Size4KiB),
+            PageSize::Size4KiB => None,
+        };
+        match smaller {
+            Some(smaller)
+                if permissions.is_special(phys, phys + page_size.bytes()) =>
+            {
+                map_range(
+                    mapper,
+                    permissions,
+                    virt,
+                    phys,
+                    page_size.bytes(),
+                    smaller,
+                    extra_flags,
+                )?;
+            }
+            _ => {
+                let flags = permissions.flags(phys) | extra_flags;
+                mapper
+                    .map_to(
+                        VirtAddr::new(virt),
+                        PhysAddr::new(phys),
+                        page_size,
+                        flags,
+                        &mut pmm::PageTableFrames,
+                    )
+                    .map_err(Error::Map)?;
+            }
+        }
+    }
+    Ok(())
+}
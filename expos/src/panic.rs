@@ -1,28 +1,86 @@
 //! Panic handling.
 
+use core::fmt::Write;
 use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
 
-use cpu::hlt;
+use cpu::{hlt, Registers};
 
-use crate::println;
+use crate::console::PanicWriter;
+
+/// Maximum number of frames printed in the backtrace.
+const MAX_FRAMES: usize = 32;
+
+/// Set when the first panic starts. Reporting a panic reads memory that could
+/// be corrupted (e.g. the stack frames), so a nested panic only halts.
+static PANICKING: AtomicBool = AtomicBool::new(false);
 
 /// Panic handler.
 #[panic_handler]
 fn panic_handler(panic_info: &PanicInfo) -> ! {
-    println!("====== PANIC ======");
+    let regs = Registers::capture();
+
+    // The output is best effort, so write errors are ignored.
+    let mut out = PanicWriter;
+
+    if PANICKING.swap(true, Ordering::Relaxed) {
+        writeln!(out, "====== NESTED PANIC ======").ok();
+        halt();
+    }
+
+    writeln!(out, "====== PANIC ======").ok();
 
     if let Some(message) = panic_info.message() {
-        println!("{}", message);
+        writeln!(out, "{}", message).ok();
     }
 
     if let Some(payload) = panic_info.payload().downcast_ref::<&str>() {
-        println!("{}", payload);
+        writeln!(out, "{}", payload).ok();
     }
 
     if let Some(location) = panic_info.location() {
-        println!("Panic ocurred in {}", location);
+        writeln!(out, "Panic ocurred in {}", location).ok();
     }
 
+    writeln!(out, "Registers:\n{}", regs).ok();
+
+    writeln!(out, "Backtrace:").ok();
+    print_backtrace(&mut out, regs.rbp());
+
+    halt();
+}
+
+/// Prints the return addresses found by walking the frame pointer chain that
+/// starts at `rbp`. It is only reliable if the kernel is built with frame
+/// pointers. Otherwise, the walk stops at the first frame that does not look
+/// valid.
+fn print_backtrace<W: Write>(out: &mut W, mut rbp: u64) {
+    for i in 0..MAX_FRAMES {
+        if rbp == 0 || rbp % 8 != 0 {
+            break;
+        }
+
+        // A frame starts with the RBP of the caller followed by the return
+        // address.
+        let frame = rbp as *const u64;
+        let (caller_rbp, ret_addr) =
+            unsafe { (frame.read_volatile(), frame.add(1).read_volatile()) };
+        if ret_addr == 0 {
+            break;
+        }
+        writeln!(out, "  #{:<2} {:#018x}", i, ret_addr).ok();
+
+        // The stack grows downwards, so the frame of the caller is always at
+        // a higher address.
+        if caller_rbp <= rbp {
+            break;
+        }
+        rbp = caller_rbp;
+    }
+}
+
+/// Halts the current CPU forever.
+fn halt() -> ! {
     loop {
         unsafe { hlt() };
     }
@@ -0,0 +1,377 @@
+//! PCI configuration space access.
+//!
+//! The functions are enumerated through the enhanced configuration access
+//! mechanism (ECAM) described by the ACPI MCFG table, which exposes the
+//! configuration space of every function as a 4KiB block of memory. The
+//! legacy IO port mechanism is not supported.
+//!
+//! Reference:
+//! - PCI Express Base Specification, Section 7.2.2
+//! - [OSDev article](https://wiki.osdev.org/PCI)
+
+use mm::PhysAddr;
+use uefi::acpi;
+
+use crate::paging;
+
+/// Offset of the vendor ID.
+const REG_VENDOR_ID: usize = 0x00;
+
+/// Offset of the device ID.
+const REG_DEVICE_ID: usize = 0x02;
+
+/// Offset of the command register.
+const REG_COMMAND: usize = 0x04;
+
+/// Offset of the programming interface.
+const REG_PROG_IF: usize = 0x09;
+
+/// Offset of the subclass.
+const REG_SUBCLASS: usize = 0x0a;
+
+/// Offset of the class code.
+const REG_CLASS: usize = 0x0b;
+
+/// Offset of the header type.
+const REG_HEADER_TYPE: usize = 0x0e;
+
+/// Offset of the first base address register.
+const REG_BAR0: usize = 0x10;
+
+/// Vendor ID read from the functions that do not exist.
+const INVALID_VENDOR_ID: u16 = 0xffff;
+
+/// Header type flag: the device has more than one function.
+const HEADER_MULTIFUNCTION: u8 = 0x80;
+
+/// Mask of the layout in the header type.
+const HEADER_TYPE_MASK: u8 = 0x7f;
+
+/// Header type of the regular functions (i.e. not bridges).
+const HEADER_TYPE_GENERAL: u8 = 0x00;
+
+/// Number of base address registers of the regular functions.
+const BAR_COUNT: usize = 6;
+
+/// BAR flag: the BAR is in IO space.
+const BAR_IO: u32 = 0x1;
+
+/// Mask of the BAR memory type.
+const BAR_TYPE_MASK: u32 = 0x6;
+
+/// BAR memory type: 64-bit address.
+const BAR_TYPE_64: u32 = 0x4;
+
+/// Mask of the address of a memory BAR.
+const BAR_MEM_ADDR_MASK: u32 = !0xf;
+
+/// Number of devices per bus.
+const DEVICES_PER_BUS: u8 = 32;
+
+/// Number of functions per device.
+const FUNCTIONS_PER_DEVICE: u8 = 8;
+
+/// Size of the configuration space of a function.
+const CONFIG_SIZE: u64 = 0x1000;
+
+/// PCI errors.
+#[derive(Debug)]
+pub enum Error {
+    /// The BAR with the provided index does not exist or it is not a memory
+    /// BAR.
+    InvalidBar(usize),
+
+    /// The configuration space or a BAR could not be mapped.
+    Map(paging::Error),
+}
+
+/// Command register flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Command(u16);
+
+impl Command {
+    /// The function responds to memory space accesses.
+    pub const MEMORY_SPACE: Command = Command(1 << 1);
+
+    /// The function can issue memory requests (i.e. DMA).
+    pub const BUS_MASTER: Command = Command(1 << 2);
+
+    /// The function cannot assert its INTx# pin.
+    pub const INTERRUPT_DISABLE: Command = Command(1 << 10);
+
+    /// Returns the raw representation of the flags.
+    pub const fn bits(self) -> u16 {
+        self.0
+    }
+}
+
+impl core::ops::BitOr for Command {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Command(self.0 | rhs.0)
+    }
+}
+
+/// Class, subclass and programming interface of a function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Class {
+    pub class: u8,
+    pub subclass: u8,
+    pub prog_if: u8,
+}
+
+impl Class {
+    /// Returns a `Class` from its components.
+    pub const fn new(class: u8, subclass: u8, prog_if: u8) -> Self {
+        Class {
+            class,
+            subclass,
+            prog_if,
+        }
+    }
+}
+
+/// Memory region decoded by a function.
+#[derive(Debug, Clone, Copy)]
+pub struct Bar {
+    /// Physical address of the region.
+    pub addr: PhysAddr,
+
+    /// Size of the region in bytes.
+    pub size: u64,
+}
+
+/// Represents a PCI function.
+#[derive(Debug)]
+pub struct Function {
+    /// Physical address of the configuration space. It is identity mapped.
+    config: u64,
+
+    /// PCI segment group number.
+    segment: u16,
+
+    bus: u8,
+    device: u8,
+    function: u8,
+}
+
+impl Function {
+    /// Returns the function `function` of the device `device` on the bus
+    /// `bus` of the ECAM allocation `alloc`. It returns `None` if the
+    /// function does not exist.
+    fn new(
+        alloc: &acpi::McfgAllocation,
+        bus: u8,
+        device: u8,
+        function: u8,
+    ) -> Option<Function> {
+        let function = Function {
+            config: alloc.config_address(bus, device, function)?,
+            segment: alloc.segment(),
+            bus,
+            device,
+            function,
+        };
+        if function.vendor_id() == INVALID_VENDOR_ID {
+            return None;
+        }
+        Some(function)
+    }
+
+    /// Reads the byte at `offset` in the configuration space.
+    pub fn read8(&self, offset: usize) -> u8 {
+        unsafe {
+            ((self.config as usize + offset) as *const u8).read_volatile()
+        }
+    }
+
+    /// Reads the word at `offset` in the configuration space. `offset` must
+    /// be aligned to 2 bytes.
+    pub fn read16(&self, offset: usize) -> u16 {
+        unsafe {
+            ((self.config as usize + offset) as *const u16).read_volatile()
+        }
+    }
+
+    /// Reads the double word at `offset` in the configuration space.
+    /// `offset` must be aligned to 4 bytes.
+    pub fn read32(&self, offset: usize) -> u32 {
+        unsafe {
+            ((self.config as usize + offset) as *const u32).read_volatile()
+        }
+    }
+
+    /// Writes the word `value` at `offset` in the configuration space.
+    /// `offset` must be aligned to 2 bytes.
+    ///
+    /// # Safety
+    ///
+    /// Writing the configuration space can change how the function decodes
+    /// the memory and whether it accesses it.
+    pub unsafe fn write16(&self, offset: usize, value: u16) {
+        ((self.config as usize + offset) as *mut u16).write_volatile(value)
+    }
+
+    /// Writes the double word `value` at `offset` in the configuration
+    /// space. `offset` must be aligned to 4 bytes.
+    ///
+    /// # Safety
+    ///
+    /// Writing the configuration space can change how the function decodes
+    /// the memory and whether it accesses it.
+    pub unsafe fn write32(&self, offset: usize, value: u32) {
+        ((self.config as usize + offset) as *mut u32).write_volatile(value)
+    }
+
+    /// Returns the vendor ID.
+    pub fn vendor_id(&self) -> u16 {
+        self.read16(REG_VENDOR_ID)
+    }
+
+    /// Returns the device ID.
+    pub fn device_id(&self) -> u16 {
+        self.read16(REG_DEVICE_ID)
+    }
+
+    /// Returns the class of the function.
+    pub fn class(&self) -> Class {
+        Class::new(
+            self.read8(REG_CLASS),
+            self.read8(REG_SUBCLASS),
+            self.read8(REG_PROG_IF),
+        )
+    }
+
+    /// Returns `true` if the device has more than one function.
+    fn multifunction(&self) -> bool {
+        self.read8(REG_HEADER_TYPE) & HEADER_MULTIFUNCTION != 0
+    }
+
+    /// Sets the flags `flags` in the command register.
+    ///
+    /// # Safety
+    ///
+    /// The function must be ready to decode its BARs and to access the
+    /// memory, depending on `flags`.
+    pub unsafe fn enable(&self, flags: Command) {
+        let command = self.read16(REG_COMMAND);
+        self.write16(REG_COMMAND, command | flags.bits());
+    }
+
+    /// Clears the flags `flags` in the command register.
+    ///
+    /// # Safety
+    ///
+    /// The drivers of the function must not depend on `flags`.
+    pub unsafe fn disable(&self, flags: Command) {
+        let command = self.read16(REG_COMMAND);
+        self.write16(REG_COMMAND, command & !flags.bits());
+    }
+
+    /// Returns the memory BAR with the index `index` and identity maps it.
+    /// The memory decoding is disabled while the size of the BAR is probed.
+    ///
+    /// # Safety
+    ///
+    /// Nothing else may be accessing the function.
+    pub unsafe fn map_bar(&self, index: usize) -> Result<Bar, Error> {
+        let header = self.read8(REG_HEADER_TYPE) & HEADER_TYPE_MASK;
+        if header != HEADER_TYPE_GENERAL || index >= BAR_COUNT {
+            return Err(Error::InvalidBar(index));
+        }
+
+        let offset = REG_BAR0 + index * 4;
+        let low = self.read32(offset);
+        if low & BAR_IO != 0 {
+            return Err(Error::InvalidBar(index));
+        }
+        let is_64 = low & BAR_TYPE_MASK == BAR_TYPE_64;
+        if is_64 && index + 1 >= BAR_COUNT {
+            return Err(Error::InvalidBar(index));
+        }
+
+        // The size is probed by writing all ones and reading back the bits
+        // that the function lets change.
+        let command = self.read16(REG_COMMAND);
+        self.write16(REG_COMMAND, command & !Command::MEMORY_SPACE.bits());
+        self.write32(offset, u32::MAX);
+        let low_mask = self.read32(offset) & BAR_MEM_ADDR_MASK;
+        self.write32(offset, low);
+        let (high, high_mask) = if is_64 {
+            let high = self.read32(offset + 4);
+            self.write32(offset + 4, u32::MAX);
+            let high_mask = self.read32(offset + 4);
+            self.write32(offset + 4, high);
+            (high, high_mask)
+        } else {
+            (0, u32::MAX)
+        };
+        self.write16(REG_COMMAND, command);
+
+        let addr = u64::from(high) << 32 | u64::from(low & BAR_MEM_ADDR_MASK);
+        let mask = u64::from(high_mask) << 32 | u64::from(low_mask);
+        let size = (!mask).wrapping_add(1);
+        if addr == 0 || size == 0 {
+            return Err(Error::InvalidBar(index));
+        }
+
+        let addr = PhysAddr::new(addr);
+        paging::map_mmio(addr, size).map_err(Error::Map)?;
+        Ok(Bar { addr, size })
+    }
+}
+
+impl core::fmt::Display for Function {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            f,
+            "{:04x}:{:02x}:{:02x}.{}",
+            self.segment, self.bus, self.device, self.function
+        )
+    }
+}
+
+/// Identity maps the configuration space described by `mcfg`, so it can be
+/// accessed even if it is above the physical memory mapped at boot.
+///
+/// # Safety
+///
+/// This function must be called after the kernel page tables are loaded.
+pub unsafe fn init(mcfg: &acpi::Mcfg) -> Result<(), Error> {
+    for alloc in mcfg.allocations() {
+        let start = alloc
+            .config_address(alloc.start_bus(), 0, 0)
+            .unwrap_or_else(|| alloc.base_address());
+        let buses = u64::from(alloc.end_bus() - alloc.start_bus()) + 1;
+        let size = buses
+            * u64::from(DEVICES_PER_BUS)
+            * u64::from(FUNCTIONS_PER_DEVICE)
+            * CONFIG_SIZE;
+        paging::map_mmio(PhysAddr::new(start), size).map_err(Error::Map)?;
+    }
+    Ok(())
+}
+
+/// Returns an iterator over the functions described by `mcfg`. `init` must
+/// be called first.
+pub fn functions(mcfg: &acpi::Mcfg) -> impl Iterator<Item = Function> + '_ {
+    mcfg.allocations().iter().flat_map(|alloc| {
+        (alloc.start_bus()..=alloc.end_bus()).flat_map(move |bus| {
+            (0..DEVICES_PER_BUS).flat_map(move |device| {
+                (0..FUNCTIONS_PER_DEVICE).filter_map(move |function| {
+                    // Some single-function devices answer on every function
+                    // number, so only the function 0 is checked if the
+                    // device is not multi-function.
+                    if function != 0
+                        && !Function::new(alloc, bus, device, 0)
+                            .map_or(false, |f| f.multifunction())
+                    {
+                        return None;
+                    }
+                    Function::new(alloc, bus, device, function)
+                })
+            })
+        })
+    })
+}
@@ -0,0 +1,69 @@
+//! Boot phase timestamps.
+//!
+//! The end of every boot phase is marked with an ordered read of the TSC.
+//! The duration of the phases is logged at the end of boot, when the TSC
+//! frequency is known.
+
+use ticket_mutex::TicketMutex;
+
+use crate::{info, time};
+
+/// Maximum number of boot phases.
+const MAX_PHASES: usize = 16;
+
+/// Boot phases marked so far.
+static PHASES: TicketMutex<Phases> = TicketMutex::new(Phases {
+    phases: [None; MAX_PHASES],
+});
+
+/// Represents the end of a boot phase.
+#[derive(Clone, Copy)]
+struct Phase {
+    /// Name of the phase.
+    name: &'static str,
+
+    /// Value of the TSC at the end of the phase.
+    tsc: u64,
+}
+
+/// Boot phases, in the order they were marked.
+struct Phases {
+    phases: [Option<Phase>; MAX_PHASES],
+}
+
+/// Marks the end of the boot phase `name`. The phases that do not fit are
+/// ignored.
+pub fn mark(name: &'static str) {
+    let tsc = cpu::rdtsc_serialized();
+    let mut phases = PHASES.lock();
+    if let Some(slot) = phases.phases.iter_mut().find(|slot| slot.is_none()) {
+        *slot = Some(Phase { name, tsc });
+    }
+}
+
+/// Logs the duration of every boot phase, measured from the end of the
+/// previous one. The first phase is only used as origin.
+pub fn print() {
+    let tsc_hz = time::tsc_hz();
+    let phases = PHASES.lock();
+    let mut phases = phases.phases.iter().flatten();
+
+    let mut prev = match phases.next() {
+        Some(phase) => *phase,
+        None => return,
+    };
+    for phase in phases {
+        let cycles = phase.tsc.wrapping_sub(prev.tsc);
+        match tsc_hz {
+            Some(tsc_hz) => info!(
+                target: "boot",
+                "{}: {} cycles ({} us)",
+                phase.name,
+                cycles,
+                u128::from(cycles) * 1_000_000 / u128::from(tsc_hz),
+            ),
+            None => info!(target: "boot", "{}: {} cycles", phase.name, cycles),
+        }
+        prev = *phase;
+    }
+}
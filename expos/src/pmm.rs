@@ -0,0 +1,413 @@
+//! Physical memory manager.
+//!
+//! The physical memory is handed out in frames of `FRAME_SIZE` bytes by a
+//! bitmap allocator. Every frame below the end of the available memory has a
+//! bit in the bitmap, which is set if the frame is free. The bitmap itself is
+//! allocated from the available memory when the allocator is initialized.
+//!
+//! Frame 0 is never handed out, so a null `PhysAddr` is never valid.
+
+use mm::{paging, PhysAddr, PhysFrame};
+use range::{Range, RangeSet};
+use ticket_mutex::TicketMutex;
+
+/// Size of a frame.
+pub const FRAME_SIZE: u64 = mm::PAGE_SIZE;
+
+/// Number of frames tracked by every word of the bitmap.
+const FRAMES_PER_WORD: usize = 64;
+
+/// Frame allocator used by the kernel. It is `None` until `init` is called.
+static PMM: TicketMutex<Option<FrameAllocator<'static>>> =
+    TicketMutex::new(None);
+
+/// Physical memory manager errors.
+#[derive(Debug)]
+pub enum Error {
+    /// The allocator is already initialized.
+    AlreadyInitialized,
+
+    /// There is no available memory.
+    NoMemory,
+
+    /// There is no free block big enough for the bitmap.
+    NoBitmapMemory,
+
+    /// The address is not aligned to `FRAME_SIZE`.
+    Unaligned(PhysAddr),
+
+    /// The frame is not tracked by the allocator.
+    OutOfRange(PhysAddr),
+
+    /// The frame is already free.
+    DoubleFree(PhysAddr),
+}
+
+/// Bitmap frame allocator. A set bit means that the frame is free.
+pub struct FrameAllocator<'a> {
+    /// Bitmap. The bit `n` of the word `m` belongs to the frame
+    /// `m * FRAMES_PER_WORD + n`.
+    bitmap: &'a mut [u64],
+
+    /// Number of free frames.
+    free: usize,
+
+    /// Index of the word where the search of the next free frame starts.
+    next: usize,
+}
+
+impl<'a> FrameAllocator<'a> {
+    /// Returns a `FrameAllocator` that tracks `bitmap.len() * 64` frames.
+    /// All of them are initially in use.
+    pub fn new(bitmap: &'a mut [u64]) -> Self {
+        bitmap.iter_mut().for_each(|word| *word = 0);
+        FrameAllocator {
+            bitmap,
+            free: 0,
+            next: 0,
+        }
+    }
+
+    /// Returns the number of frames tracked by the allocator.
+    pub fn frames(&self) -> usize {
+        self.bitmap.len() * FRAMES_PER_WORD
+    }
+
+    /// Returns the number of free frames.
+    pub fn free_frames(&self) -> usize {
+        self.free
+    }
+
+    /// Marks as free the frames completely contained by `range`. The frames
+    /// that are not tracked by the allocator and frame 0 are ignored.
+    pub fn add_range(&mut self, range: Range) {
+        let start = match range.start().checked_add(FRAME_SIZE - 1) {
+            Some(start) => (start / FRAME_SIZE).max(1),
+            None => return,
+        };
+        let end = match range.end().checked_add(1) {
+            Some(end) => end / FRAME_SIZE,
+            None => u64::MAX / FRAME_SIZE + 1,
+        };
+        let end = end.min(self.frames() as u64);
+
+        for frame in start..end {
+            if !self.is_free(frame as usize) {
+                self.set_free(frame as usize, true);
+            }
+        }
+    }
+
+    /// Allocates a frame and returns its address. It returns `None` if there
+    /// are no free frames.
+    pub fn alloc_frame(&mut self) -> Option<PhysAddr> {
+        let words = self.bitmap.len();
+        for i in 0..words {
+            let idx = (self.next + i) % words;
+            let word = self.bitmap[idx];
+            if word == 0 {
+                continue;
+            }
+
+            let frame = idx * FRAMES_PER_WORD + word.trailing_zeros() as usize;
+            self.set_free(frame, false);
+            self.next = idx;
+            return Some(frame_addr(frame));
+        }
+        None
+    }
+
+    /// Allocates `n` contiguous frames and returns the address of the first
+    /// one, which is aligned to `align`. It returns `None` if `n` is zero,
+    /// `align` is not a power of two or there is no suitable block.
+    pub fn alloc_contiguous(
+        &mut self,
+        n: usize,
+        align: u64,
+    ) -> Option<PhysAddr> {
+        self.alloc_contiguous_below(n, align, u64::MAX)
+    }
+
+    /// Allocates `n` contiguous frames that end at or below the physical
+    /// address `limit` and returns the address of the first one, which is
+    /// aligned to `align`. It returns `None` if `n` is zero, `align` is not
+    /// a power of two or there is no suitable block.
+    pub fn alloc_contiguous_below(
+        &mut self,
+        n: usize,
+        align: u64,
+        limit: u64,
+    ) -> Option<PhysAddr> {
+        if n == 0 || !align.is_power_of_two() {
+            return None;
+        }
+        let step = (align / FRAME_SIZE).max(1) as usize;
+        let end = self.frames().min((limit / FRAME_SIZE) as usize);
+
+        let mut start = step;
+        while start.checked_add(n)? <= end {
+            match (start..start + n).find(|&frame| !self.is_free(frame)) {
+                Some(used) => {
+                    // None of the blocks starting before the used frame fit.
+                    start = (used / step + 1) * step;
+                }
+                None => {
+                    (start..start + n).for_each(|f| self.set_free(f, false));
+                    return Some(frame_addr(start));
+                }
+            }
+        }
+        None
+    }
+
+    /// Frees the frame at `addr`.
+    pub fn free_frame(&mut self, addr: PhysAddr) -> Result<(), Error> {
+        let frame = match PhysFrame::from_start_address(addr) {
+            Some(frame) => frame.number() as usize,
+            None => return Err(Error::Unaligned(addr)),
+        };
+        if frame == 0 || frame >= self.frames() {
+            return Err(Error::OutOfRange(addr));
+        }
+        if self.is_free(frame) {
+            return Err(Error::DoubleFree(addr));
+        }
+
+        self.set_free(frame, true);
+        Ok(())
+    }
+
+    /// Returns `true` if the frame `frame` is free.
+    fn is_free(&self, frame: usize) -> bool {
+        let bit = 1 << (frame % FRAMES_PER_WORD);
+        self.bitmap[frame / FRAMES_PER_WORD] & bit != 0
+    }
+
+    /// Marks the frame `frame` as free or in use. Its current state must be
+    /// the opposite one.
+    fn set_free(&mut self, frame: usize, free: bool) {
+        let bit = 1 << (frame % FRAMES_PER_WORD);
+        let word = &mut self.bitmap[frame / FRAMES_PER_WORD];
+        if free {
+            *word |= bit;
+            self.free += 1;
+        } else {
+            *word &= !bit;
+            self.free -= 1;
+        }
+    }
+}
+
+/// Returns the address of the frame `frame`.
+fn frame_addr(frame: usize) -> PhysAddr {
+    PhysFrame::from_number(frame as u64).start_address()
+}
+
+/// Initializes the frame allocator with the memory in `available_memory`.
+/// The bitmap is allocated from `available_memory` and the rest of it is
+/// handed over to the allocator. It returns the number of free frames.
+///
+/// # Safety
+///
+/// The memory in `available_memory` must be identity mapped and unused.
+/// After calling this function, `available_memory` must not be used to
+/// allocate memory.
+pub unsafe fn init(available_memory: &mut RangeSet) -> Result<usize, Error> {
+    let mut pmm = PMM.lock();
+    if pmm.is_some() {
+        return Err(Error::AlreadyInitialized);
+    }
+
+    let end = available_memory
+        .iter()
+        .map(|range| range.end())
+        .max()
+        .ok_or(Error::NoMemory)?;
+    let frames = (end / FRAME_SIZE + 1) as usize;
+    let words = (frames + FRAMES_PER_WORD - 1) / FRAMES_PER_WORD;
+
+    let size = (words * core::mem::size_of::<u64>()) as u64;
+    let bitmap = available_memory
+        .allocate(size, FRAME_SIZE)
+        .ok_or(Error::NoBitmapMemory)?;
+    let bitmap = core::slice::from_raw_parts_mut(bitmap as *mut u64, words);
+
+    let mut allocator = FrameAllocator::new(bitmap);
+    for range in available_memory.iter() {
+        allocator.add_range(*range);
+    }
+
+    let free = allocator.free_frames();
+    *pmm = Some(allocator);
+    Ok(free)
+}
+
+/// Allocates a frame and returns its address. It returns `None` if there are
+/// no free frames or the allocator is not initialized.
+pub fn alloc_frame() -> Option<PhysAddr> {
+    PMM.lock().as_mut()?.alloc_frame()
+}
+
+/// Allocates `n` contiguous frames and returns the address of the first one,
+/// which is aligned to `align`. It returns `None` if there is no suitable
+/// block or the allocator is not initialized.
+pub fn alloc_contiguous(n: usize, align: u64) -> Option<PhysAddr> {
+    PMM.lock().as_mut()?.alloc_contiguous(n, align)
+}
+
+/// Allocates `n` contiguous frames that end at or below the physical address
+/// `limit` and returns the address of the first one, which is aligned to
+/// `align`. It returns `None` if there is no suitable block or the allocator
+/// is not initialized.
+pub fn alloc_contiguous_below(
+    n: usize,
+    align: u64,
+    limit: u64,
+) -> Option<PhysAddr> {
+    PMM.lock().as_mut()?.alloc_contiguous_below(n, align, limit)
+}
+
+/// Frees the frame at `addr`, which must have been returned by
+/// `alloc_frame`, `alloc_contiguous` or `alloc_contiguous_below`.
+pub fn free_frame(addr: PhysAddr) -> Result<(), Error> {
+    match PMM.lock().as_mut() {
+        Some(pmm) => pmm.free_frame(addr),
+        None => Err(Error::OutOfRange(addr)),
+    }
+}
+
+/// Returns the number of free frames. It returns 0 if the allocator is not
+/// initialized.
+pub fn free_frames() -> usize {
+    PMM.lock().as_ref().map_or(0, |pmm| pmm.free_frames())
+}
+
+/// Hands out the frames used for new page tables from the kernel frame
+/// allocator.
+pub struct PageTableFrames;
+
+impl paging::FrameAllocator for PageTableFrames {
+    fn alloc_frame(&mut self) -> Option<PhysAddr> {
+        alloc_frame()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: u64, end: u64) -> Range {
+        Range::new(start, end).unwrap()
+    }
+
+    #[test]
+    fn test_add_range() {
+        let mut bitmap = [0u64; 2];
+        let mut pmm = FrameAllocator::new(&mut bitmap);
+        assert_eq!(pmm.frames(), 128);
+
+        // Frame 0 is skipped.
+        pmm.add_range(range(0, 0x3fff));
+        assert_eq!(pmm.free_frames(), 3);
+
+        // Partial frames are skipped.
+        pmm.add_range(range(0x10800, 0x13fff));
+        assert_eq!(pmm.free_frames(), 6);
+
+        // Frames past the bitmap are skipped.
+        pmm.add_range(range(0x7f000, 0xfffff));
+        assert_eq!(pmm.free_frames(), 7);
+
+        // Free frames are not counted twice.
+        pmm.add_range(range(0x1000, 0x1fff));
+        assert_eq!(pmm.free_frames(), 7);
+    }
+
+    #[test]
+    fn test_alloc_free_frame() {
+        let mut bitmap = [0u64; 2];
+        let mut pmm = FrameAllocator::new(&mut bitmap);
+        pmm.add_range(range(0x41000, 0x42fff));
+
+        assert_eq!(pmm.alloc_frame(), Some(PhysAddr::new(0x41000)));
+        assert_eq!(pmm.alloc_frame(), Some(PhysAddr::new(0x42000)));
+        assert_eq!(pmm.alloc_frame(), None);
+
+        pmm.free_frame(PhysAddr::new(0x41000)).unwrap();
+        assert_eq!(pmm.free_frames(), 1);
+        assert_eq!(pmm.alloc_frame(), Some(PhysAddr::new(0x41000)));
+    }
+
+    #[test]
+    fn test_free_frame_errors() {
+        let mut bitmap = [0u64; 1];
+        let mut pmm = FrameAllocator::new(&mut bitmap);
+        pmm.add_range(range(0x1000, 0x1fff));
+
+        assert!(matches!(
+            pmm.free_frame(PhysAddr::new(0x1800)),
+            Err(Error::Unaligned(_))
+        ));
+        assert!(matches!(
+            pmm.free_frame(PhysAddr::new(0)),
+            Err(Error::OutOfRange(_))
+        ));
+        assert!(matches!(
+            pmm.free_frame(PhysAddr::new(0x40000)),
+            Err(Error::OutOfRange(_))
+        ));
+        assert!(matches!(
+            pmm.free_frame(PhysAddr::new(0x1000)),
+            Err(Error::DoubleFree(_))
+        ));
+    }
+
+    #[test]
+    fn test_alloc_contiguous() {
+        let mut bitmap = [0u64; 2];
+        let mut pmm = FrameAllocator::new(&mut bitmap);
+        pmm.add_range(range(0x3000, 0x5fff));
+        pmm.add_range(range(0x9000, 0x1ffff));
+
+        assert_eq!(pmm.alloc_contiguous(0, 0x1000), None);
+        assert_eq!(pmm.alloc_contiguous(1, 0x1800), None);
+        assert_eq!(
+            pmm.alloc_contiguous(3, 0x1000),
+            Some(PhysAddr::new(0x3000))
+        );
+        assert_eq!(
+            pmm.alloc_contiguous(2, 0x4000),
+            Some(PhysAddr::new(0xc000))
+        );
+        assert_eq!(
+            pmm.alloc_contiguous(4, 0x1000),
+            Some(PhysAddr::new(0xe000))
+        );
+        assert_eq!(
+            pmm.alloc_contiguous(3, 0x1000),
+            Some(PhysAddr::new(0x9000))
+        );
+        assert_eq!(pmm.alloc_contiguous(16, 0x1000), None);
+        assert_eq!(pmm.free_frames(), 14);
+    }
+
+    #[test]
+    fn test_alloc_contiguous_below() {
+        let mut bitmap = [0u64; 2];
+        let mut pmm = FrameAllocator::new(&mut bitmap);
+        pmm.add_range(range(0x1000, 0x3fff));
+        pmm.add_range(range(0x10000, 0x1ffff));
+
+        assert_eq!(pmm.alloc_contiguous_below(4, 0x1000, 0x10000), None);
+        assert_eq!(
+            pmm.alloc_contiguous_below(3, 0x1000, 0x4000),
+            Some(PhysAddr::new(0x1000))
+        );
+        assert_eq!(
+            pmm.alloc_contiguous_below(4, 0x1000, 0x14000),
+            Some(PhysAddr::new(0x10000))
+        );
+        assert_eq!(pmm.alloc_contiguous_below(1, 0x1000, 0x14000), None);
+        assert_eq!(pmm.free_frames(), 12);
+    }
+}
@@ -1,55 +1,122 @@
 //! Primitives to output and read data via serial port.
 
-use core::fmt::{self, Write};
+use core::fmt::Write;
 
-use serial::SerialPort;
+use param::BoolParam;
+use serial::{SerialConfig, SerialPort};
 use ticket_mutex::TicketMutex;
+use uefi::acpi;
 
-/// Static variable that provides access to the COM1 serial port.
-static COM1: TicketMutex<Option<SerialPort>> = TicketMutex::new(None);
+use crate::console;
 
-/// Typically, COM1's IO port address.
-/// FIXME(rm): Do not use a fixed address. Can we get it from UEFI?
+/// Serial port used by the firmware console.
+static SERIAL: SerialSink = SerialSink::new();
+
+/// The other legacy serial port (COM2 if the firmware console is COM1 and
+/// COM1 otherwise).
+static AUX_SERIAL: SerialSink = SerialSink::new();
+
+/// Enables the console output to `SERIAL`.
+pub static SERIAL_ENABLED: BoolParam = BoolParam::new("console.serial", true);
+
+/// Enables the console output to `AUX_SERIAL`. It is disabled by default.
+pub static AUX_SERIAL_ENABLED: BoolParam =
+    BoolParam::new("console.serial_aux", false);
+
+/// Typically, COM1's IO port address. It is used if the firmware does not
+/// describe its console.
 const COM1_ADDRESS: u16 = 0x3f8;
 
-/// Initialize COM1 serial. It is used by `print!`.
-pub fn init_serial() {
-    let mut com = COM1.lock();
-    unsafe {
-        *com = SerialPort::new(COM1_ADDRESS).ok();
+/// Typically, COM2's IO port address.
+const COM2_ADDRESS: u16 = 0x2f8;
+
+/// Default line settings. They match the usual firmware console settings, so
+/// the output of the firmware and the kernel can be read with the same
+/// terminal.
+const DEFAULT_CONFIG: SerialConfig = SerialConfig::new().baud(115200);
+
+/// Console sink that writes into a serial port.
+pub struct SerialSink {
+    /// Serial port. It is `None` if it was not initialized successfully.
+    port: TicketMutex<Option<SerialPort>>,
+}
+
+impl SerialSink {
+    /// Returns a `SerialSink` without serial port.
+    const fn new() -> Self {
+        SerialSink {
+            port: TicketMutex::new(None),
+        }
+    }
+
+    /// Initializes the serial port at `port_addr` with the line settings
+    /// `config`. It returns `true` on success.
+    fn init(&self, port_addr: u16, config: SerialConfig) -> bool {
+        // Some virtual UARTs fail the loopback test even though they can
+        // transmit. Try again without it instead of staying silent.
+        let serial = match unsafe {
+            SerialPort::with_config(port_addr, config)
+        } {
+            Err(serial::Error::LoopbackFailed) => unsafe {
+                SerialPort::with_config(port_addr, config.loopback_test(false))
+            },
+            ret => ret,
+        };
+
+        let mut port = self.port.lock();
+        *port = serial.ok();
+        port.is_some()
     }
 }
 
-/// The type `SerialWriter` implements the `Write` trait for serial.
-pub struct SerialWriter;
+impl console::Sink for SerialSink {
+    fn write_str(&self, s: &str, wait: bool) {
+        let mut port = if wait {
+            Some(self.port.lock())
+        } else {
+            self.port.try_lock()
+        };
 
-impl Write for SerialWriter {
-    fn write_str(&mut self, s: &str) -> fmt::Result {
-        let com = COM1.lock();
-        if let Some(serial) = com.as_ref() {
-            serial.write(s);
+        // Writing into a `SerialPort` cannot fail.
+        if let Some(serial) = port.as_mut().and_then(|port| port.as_mut()) {
+            serial.write_str(s).ok();
         }
-        Ok(())
     }
 }
 
-/// Prints to the serial port.
-#[macro_export]
-macro_rules! print {
-    ($($arg:tt)*) => {
-        // In the case of a `SeriaWriter`, `write_str` cannot fail, so
-        // we can safely unwrap the returned result.
-        core::fmt::Write::write_fmt(
-            &mut $crate::serial::SerialWriter,
-            format_args!($($arg)*)
-        ).unwrap()
+/// Initialize serial and register it in the console.
+///
+/// The serial port is the firmware console described by the ACPI SPCR table,
+/// falling back to COM1. If `port_addr` is not `None`, it overrides the IO
+/// port address. The other legacy serial port is also registered as
+/// "serial_aux", but it is disabled by default.
+///
+/// The legacy BIOS Data Area is not used for discovery. It is at the first
+/// page of memory, which may be unmapped by the firmware to catch null
+/// pointer dereferences.
+pub fn init_serial(root_sdt: Option<&acpi::RootSdt>, port_addr: Option<u16>) {
+    let spcr = root_sdt
+        .and_then(|root_sdt| root_sdt.spcr().ok())
+        .filter(|spcr| spcr.pc_compatible());
+
+    let mut config = DEFAULT_CONFIG;
+    if let Some(baud) = spcr.as_ref().and_then(|spcr| spcr.baud_rate()) {
+        config = config.baud(baud);
+    }
+    let port_addr = port_addr
+        .or_else(|| spcr.as_ref().and_then(|spcr| spcr.io_port()))
+        .unwrap_or(COM1_ADDRESS);
+
+    if SERIAL.init(port_addr, config) {
+        console::register("serial", &SERIAL, &SERIAL_ENABLED).ok();
     }
-}
 
-/// Prints to the serial port, with a newline.
-#[macro_export]
-macro_rules! println {
-    ($($arg:tt)*) => {
-        $crate::print!("{}\n", format_args!($($arg)*))
+    let aux_addr = if port_addr == COM1_ADDRESS {
+        COM2_ADDRESS
+    } else {
+        COM1_ADDRESS
+    };
+    if AUX_SERIAL.init(aux_addr, DEFAULT_CONFIG) {
+        console::register("serial_aux", &AUX_SERIAL, &AUX_SERIAL_ENABLED).ok();
     }
 }
@@ -0,0 +1,287 @@
+//! Application processor start-up.
+//!
+//! The bootstrap processor (BSP) starts every enabled application processor
+//! (AP) described by the MADT, one at a time, with the INIT-SIPI-SIPI
+//! sequence. The APs run a trampoline copied to low memory, which switches
+//! them to long mode with the page tables of the BSP. Then, they set up
+//! their per-CPU state, register their local APIC ID and wait in an idle
+//! loop.
+//!
+//! CPU indexes are assigned in start-up order. The BSP is CPU 0. Once a CPU
+//! is online, the others can interrupt it with `send_ipi`.
+//!
+//! Reference:
+//! - Intel SDM Vol. 3A, Section 8.4 "Multiple-Processor (MP) Initialization"
+
+use core::ptr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+
+use percpu::MAX_CPUS;
+use range::RangeSet;
+use ticket_mutex::TicketMutex;
+use uefi::acpi;
+
+use crate::{gdt, idt, stack, time};
+
+global_asm!(include_str!("smp/trampoline.s"), options(att_syntax));
+
+extern "C" {
+    /// Start of the AP trampoline.
+    static ap_trampoline_start: u8;
+
+    /// Trampoline data. Its layout is described by `TrampolineData`.
+    static ap_trampoline_data: u8;
+
+    /// End of the AP trampoline.
+    static ap_trampoline_end: u8;
+}
+
+/// Size of a page.
+const PAGE_SIZE: u64 = 0x1000;
+
+/// End of the memory reachable by the Start-Up IPI vector.
+const LOW_MEMORY_END: u64 = 0x10_0000;
+
+/// Local APIC flag that is set if the processor is enabled.
+const MADT_LAPIC_ENABLED: u32 = 1 << 0;
+
+/// IA32_EFER bit that is set while long mode is active. It is read-only.
+const EFER_LMA: u64 = 1 << 10;
+
+/// IA32_EFER bit that enables long mode.
+const EFER_LME: u64 = 1 << 8;
+
+/// CR4 bits copied from the BSP to the APs before enabling paging: PAE, PGE,
+/// OSFXSR and OSXMMEXCPT. The rest of the bits cannot be set yet or require
+/// extra set-up.
+const CR4_AP_MASK: u64 = 1 << 5 | 1 << 7 | 1 << 9 | 1 << 10;
+
+/// CR4 bit that enables PAE paging.
+const CR4_PAE: u64 = 1 << 5;
+
+/// Wait between the INIT IPI and the first Start-Up IPI.
+const INIT_DELAY: Duration = Duration::from_millis(10);
+
+/// Wait between the Start-Up IPIs.
+const STARTUP_DELAY: Duration = Duration::from_micros(200);
+
+/// Interval between the checks for an AP coming online.
+const ONLINE_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Number of checks for an AP coming online after the Start-Up IPIs. The
+/// delays use `time::busy_sleep`, which falls back to the PIT if the TSC is
+/// not calibrated.
+const ONLINE_TIMEOUT_POLLS: usize = 100;
+
+/// Number of CPUs online, including the BSP.
+static ONLINE: AtomicUsize = AtomicUsize::new(1);
+
+/// Local APIC IDs of the online CPUs. The ID at index `n` belongs to CPU
+/// `n`.
+static APIC_IDS: TicketMutex<[Option<u32>; MAX_CPUS]> =
+    TicketMutex::new([None; MAX_CPUS]);
+
+/// SMP errors.
+#[derive(Debug)]
+pub enum Error {
+    /// There is no free page below 1MiB for the trampoline.
+    NoLowMemory,
+
+    /// The page tables are above 4GiB, so they cannot be loaded by the
+    /// trampoline in protected mode.
+    HighPageTables,
+
+    /// There are more CPUs than `MAX_CPUS`.
+    TooManyCpus,
+
+    /// The AP with the given local APIC ID did not come online.
+    Timeout(u32),
+
+    /// The stack of an AP could not be allocated.
+    Stack(stack::Error),
+
+    /// The CPU with the given index is not online.
+    Offline(usize),
+}
+
+/// Data passed to an AP via the trampoline. It must match the data section
+/// of `smp/trampoline.s`.
+#[repr(C)]
+struct TrampolineData {
+    /// CR0 loaded to enable paging.
+    cr0: u32,
+
+    /// Physical address of the page tables.
+    cr3: u32,
+
+    /// CR4 loaded before enabling paging.
+    cr4: u32,
+
+    /// Bits set in IA32_EFER before enabling paging.
+    efer: u32,
+
+    /// Initial stack pointer.
+    stack: u64,
+
+    /// Address of the entry point.
+    entry: u64,
+
+    /// Argument passed to the entry point.
+    arg: u64,
+}
+
+/// Allocates the page used by the trampoline from `available_memory` and
+/// returns its physical address. It must be below 1MiB, so it is reserved
+/// before the frame allocator takes over the available memory.
+pub fn reserve_trampoline(
+    available_memory: &mut RangeSet,
+) -> Result<u64, Error> {
+    // Page 0 is skipped, given that it may be unmapped to catch null pointer
+    // dereferences.
+    (PAGE_SIZE..LOW_MEMORY_END)
+        .step_by(PAGE_SIZE as usize)
+        .find_map(|addr| available_memory.allocate_at(addr, PAGE_SIZE))
+        .ok_or(Error::NoLowMemory)
+}
+
+/// Starts the enabled APs described by `madt`, using the page at
+/// `trampoline` returned by `reserve_trampoline`. It returns the number of
+/// online CPUs, including the BSP.
+///
+/// # Safety
+///
+/// This function must be called once, by the BSP, after initializing its
+/// local APIC and its descriptor tables. The low memory must be identity
+/// mapped and the physical memory must be mapped at `mm::phys_offset()`,
+/// given that the AP stacks are allocated with guard pages.
+pub unsafe fn start_aps(
+    madt: &acpi::Madt,
+    trampoline: u64,
+) -> Result<usize, Error> {
+    let bsp_apic_id = lapic::id();
+    APIC_IDS.lock()[0] = Some(bsp_apic_id);
+
+    let cr3 = cpu::read_cr3();
+    if cr3 > u32::MAX.into() {
+        return Err(Error::HighPageTables);
+    }
+
+    let start = &ap_trampoline_start as *const u8;
+    let size = &ap_trampoline_end as *const u8 as usize - start as usize;
+    ptr::copy_nonoverlapping(start, trampoline as *mut u8, size);
+
+    let data_offset =
+        &ap_trampoline_data as *const u8 as usize - start as usize;
+    let data = (trampoline as usize + data_offset) as *mut TrampolineData;
+
+    let efer = cpu::rdmsr(cpu::IA32_EFER) & !EFER_LMA | EFER_LME;
+    let cr4 = cpu::read_cr4() & CR4_AP_MASK | CR4_PAE;
+    let vector = (trampoline / PAGE_SIZE) as u8;
+
+    let aps = madt.lapic().filter(|lapic| {
+        lapic.flags() & MADT_LAPIC_ENABLED != 0
+            && u32::from(lapic.acpi_id()) != bsp_apic_id
+    });
+    for lapic in aps {
+        let apic_id = u32::from(lapic.acpi_id());
+        let index = ONLINE.load(Ordering::Acquire);
+        if index >= MAX_CPUS {
+            return Err(Error::TooManyCpus);
+        }
+
+        // The stack is used by the AP forever, so it is never freed.
+        let stack = stack::alloc("ap idle", stack::DEFAULT_STACK_SIZE)
+            .map_err(Error::Stack)?;
+        data.write_volatile(TrampolineData {
+            cr0: cpu::read_cr0() as u32,
+            cr3: cr3 as u32,
+            cr4: cr4 as u32,
+            efer: efer as u32,
+            stack: stack.top().as_u64(),
+            entry: ap_entry as usize as u64,
+            arg: index as u64,
+        });
+
+        lapic::send_init(apic_id);
+        time::busy_sleep(INIT_DELAY);
+        for _ in 0..2 {
+            lapic::send_startup(apic_id, vector);
+            time::busy_sleep(STARTUP_DELAY);
+            if ONLINE.load(Ordering::Acquire) > index {
+                break;
+            }
+        }
+
+        // The trampoline data is reused by the next AP, so give up if this
+        // one is still starting.
+        let mut polls = 0;
+        while ONLINE.load(Ordering::Acquire) == index {
+            if polls >= ONLINE_TIMEOUT_POLLS {
+                return Err(Error::Timeout(apic_id));
+            }
+            time::busy_sleep(ONLINE_POLL_INTERVAL);
+            polls += 1;
+        }
+    }
+
+    Ok(ONLINE.load(Ordering::Acquire))
+}
+
+/// Returns the number of online CPUs, including the BSP.
+pub fn cpu_count() -> usize {
+    ONLINE.load(Ordering::Acquire)
+}
+
+/// Returns the index of the current CPU.
+pub fn cpu_index() -> usize {
+    // Every CPU calls `percpu::init` before running any other kernel code,
+    // and the kernel entry paths load the kernel GS base.
+    unsafe { percpu::cpu_index() }
+}
+
+/// Returns the local APIC ID of the CPU `index`, or `None` if it is not
+/// online.
+pub fn apic_id(index: usize) -> Option<u32> {
+    APIC_IDS.with(|apic_ids| apic_ids.get(index).copied().flatten())
+}
+
+/// Sends an IPI with the vector `vector` to the CPU `index`. The vector
+/// must have a handler in the IDT.
+pub fn send_ipi(index: usize, vector: u8) -> Result<(), Error> {
+    let apic_id = apic_id(index).ok_or(Error::Offline(index))?;
+
+    // In xAPIC mode, the IPI is sent by writing two registers, so an
+    // interrupt handler sending another IPI in between would change the
+    // destination.
+    cpu::without_interrupts(|| lapic::send_fixed(apic_id, vector));
+    Ok(())
+}
+
+/// Entry point of the APs. It is called by the trampoline in long mode with
+/// the index of the CPU.
+extern "sysv64" fn ap_entry(index: usize) -> ! {
+    unsafe {
+        percpu::init(index).unwrap();
+        gdt::init();
+        idt::load();
+        lapic::init(idt::SPURIOUS_VECTOR).unwrap();
+    }
+
+    APIC_IDS.lock()[index] = Some(lapic::id());
+
+    // Let the BSP start the next AP.
+    ONLINE.fetch_add(1, Ordering::Release);
+
+    idle()
+}
+
+/// Idle loop. The CPU sleeps until the next interrupt.
+fn idle() -> ! {
+    loop {
+        unsafe {
+            cpu::sti();
+            cpu::hlt();
+        }
+    }
+}
@@ -0,0 +1,120 @@
+/*
+ * Application processor start-up trampoline.
+ *
+ * It is copied to a page below 1MiB, whose number is the Start-Up IPI
+ * vector. The application processor starts in real mode at the beginning of
+ * the page, switches to long mode with the page tables of the bootstrap
+ * processor and calls the entry point stored in the trampoline data, passing
+ * the argument stored in it.
+ *
+ * The code is position independent. In real mode, the segment registers
+ * point to the trampoline. Then, %esi holds its physical address.
+ */
+
+	.global ap_trampoline_start
+	.global ap_trampoline_data
+	.global ap_trampoline_end
+
+	.code16
+ap_trampoline_start:
+	cli
+	cld
+
+	movw %cs, %ax
+	movw %ax, %ds
+	movzwl %ax, %esi
+	shll $4, %esi
+
+	/* Patch the absolute addresses used to leave real mode. */
+	leal (gdt - ap_trampoline_start)(%esi), %eax
+	movl %eax, (gdtr - ap_trampoline_start + 2)
+	leal (protected_mode - ap_trampoline_start)(%esi), %eax
+	movl %eax, (protected_mode_ptr - ap_trampoline_start)
+	leal (long_mode - ap_trampoline_start)(%esi), %eax
+	movl %eax, (long_mode_ptr - ap_trampoline_start)
+
+	lgdtl (gdtr - ap_trampoline_start)
+
+	/* Enable protected mode. */
+	movl %cr0, %eax
+	orl $1, %eax
+	movl %eax, %cr0
+	ljmpl *(protected_mode_ptr - ap_trampoline_start)
+
+	.code32
+protected_mode:
+	movw $0x10, %ax
+	movw %ax, %ds
+	movw %ax, %es
+	movw %ax, %ss
+
+	/* Enable PAE and load the page tables. */
+	movl (data_cr4 - ap_trampoline_start)(%esi), %eax
+	movl %eax, %cr4
+	movl (data_cr3 - ap_trampoline_start)(%esi), %eax
+	movl %eax, %cr3
+
+	/* Enable long mode. */
+	movl $0xc0000080, %ecx
+	rdmsr
+	orl (data_efer - ap_trampoline_start)(%esi), %eax
+	wrmsr
+
+	/* Enable paging. */
+	movl (data_cr0 - ap_trampoline_start)(%esi), %eax
+	movl %eax, %cr0
+	ljmpl *(long_mode_ptr - ap_trampoline_start)(%esi)
+
+	.code64
+long_mode:
+	/* The upper half of the registers is undefined after the switch. */
+	movl %esi, %esi
+
+	movq (data_stack - ap_trampoline_start)(%rsi), %rsp
+	movq (data_arg - ap_trampoline_start)(%rsi), %rdi
+	movq (data_entry - ap_trampoline_start)(%rsi), %rax
+	xorl %ebp, %ebp
+	callq *%rax
+	ud2
+
+	.balign 8
+gdt:
+	.quad 0
+	/* 0x08: 64-bit code segment. */
+	.quad 0x00af9a000000ffff
+	/* 0x10: data segment. */
+	.quad 0x00cf92000000ffff
+	/* 0x18: 32-bit code segment. */
+	.quad 0x00cf9a000000ffff
+gdt_end:
+
+gdtr:
+	.word gdt_end - gdt - 1
+	.long 0
+
+protected_mode_ptr:
+	.long 0
+	.word 0x18
+
+long_mode_ptr:
+	.long 0
+	.word 0x08
+
+	/* Trampoline data. It must match `smp::TrampolineData`. */
+	.balign 8
+ap_trampoline_data:
+data_cr0:
+	.long 0
+data_cr3:
+	.long 0
+data_cr4:
+	.long 0
+data_efer:
+	.long 0
+data_stack:
+	.quad 0
+data_entry:
+	.quad 0
+data_arg:
+	.quad 0
+ap_trampoline_end:
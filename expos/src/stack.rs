@@ -0,0 +1,227 @@
+//! Kernel stacks with guard pages.
+//!
+//! The stacks live in a reserved virtual region, which is split into slots
+//! of `SLOT_SIZE` bytes. Every stack is mapped at the top of its slot and
+//! the rest of the slot is left unmapped, so there is at least one guard
+//! page below every stack. A stack overflow hits the guard page, instead of
+//! silently corrupting the memory below the stack.
+//!
+//! The page fault and double fault handlers use `guard_owner` to report the
+//! stack overflows. If the stack pointer is already in the guard page, the
+//! processor cannot push the page fault frame and a double fault is raised
+//! instead.
+
+use mm::paging::{self, PageSize};
+use mm::{PhysAddr, VirtAddr};
+use ticket_mutex::TicketMutex;
+
+use crate::{pmm, tlb};
+
+/// Start of the virtual region reserved for the stacks.
+const STACKS_START: u64 = 0xffff_a000_0000_0000;
+
+/// Size of the virtual region reserved for every stack, including its guard
+/// pages.
+const SLOT_SIZE: u64 = 1024 * 1024;
+
+/// Maximum number of stacks.
+const MAX_STACKS: usize = 64;
+
+/// Size of a page.
+const PAGE_SIZE: u64 = mm::PAGE_SIZE;
+
+/// Maximum number of pages of a stack. Every slot has at least one guard
+/// page.
+const MAX_STACK_PAGES: usize = (SLOT_SIZE / PAGE_SIZE) as usize - 1;
+
+/// Default size of a kernel stack.
+pub const DEFAULT_STACK_SIZE: u64 = 64 * 1024;
+
+/// Allocated stacks. The stack at index `n` is in the slot `n`.
+static SLOTS: TicketMutex<[Option<Slot>; MAX_STACKS]> =
+    TicketMutex::new([None; MAX_STACKS]);
+
+/// Describes the stack in a slot.
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    /// Task that owns the stack.
+    owner: &'static str,
+
+    /// Size of the stack in bytes.
+    size: u64,
+}
+
+/// Stack errors.
+#[derive(Debug)]
+pub enum Error {
+    /// The size of the stack is zero or it does not fit in a slot with its
+    /// guard page.
+    InvalidSize(u64),
+
+    /// All the slots are in use.
+    NoSlot,
+
+    /// There are no free frames to back the stack.
+    NoMemory,
+
+    /// The stack could not be mapped or unmapped.
+    Map(paging::Error),
+}
+
+/// Represents a kernel stack with a guard page below it.
+#[derive(Debug)]
+pub struct KernelStack {
+    /// Slot of the stack.
+    slot: usize,
+
+    /// Size of the stack in bytes.
+    size: u64,
+}
+
+impl KernelStack {
+    /// Returns the address right after the end of the stack, which is the
+    /// initial stack pointer.
+    pub fn top(&self) -> VirtAddr {
+        VirtAddr::new(slot_start(self.slot) + SLOT_SIZE)
+    }
+
+    /// Returns the lowest address of the stack.
+    pub fn bottom(&self) -> VirtAddr {
+        VirtAddr::new(slot_start(self.slot) + SLOT_SIZE - self.size)
+    }
+
+    /// Returns the size of the stack in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+/// Returns the start address of the slot `slot`.
+fn slot_start(slot: usize) -> u64 {
+    STACKS_START + slot as u64 * SLOT_SIZE
+}
+
+/// Allocates a kernel stack of `size` bytes owned by the task `owner`. The
+/// size is rounded up to a multiple of the page size.
+///
+/// # Safety
+///
+/// The current page tables must map the physical memory at
+/// `mm::phys_offset()`.
+pub unsafe fn alloc(
+    owner: &'static str,
+    size: u64,
+) -> Result<KernelStack, Error> {
+    let size = size
+        .checked_add(PAGE_SIZE - 1)
+        .map(|size| size & !(PAGE_SIZE - 1))
+        .filter(|&size| size != 0 && size < SLOT_SIZE)
+        .ok_or(Error::InvalidSize(size))?;
+
+    let slot = {
+        let mut slots = SLOTS.lock();
+        let slot = slots
+            .iter()
+            .position(|slot| slot.is_none())
+            .ok_or(Error::NoSlot)?;
+        slots[slot] = Some(Slot { owner, size });
+        slot
+    };
+    let stack = KernelStack { slot, size };
+
+    // The pages are mapped from the top, so the pages mapped so far can be
+    // released as a smaller stack on failure.
+    let mut mapper = crate::paging::current_mapper();
+    let top = stack.top().as_u64();
+    for page in (1..=size / PAGE_SIZE).map(|n| top - n * PAGE_SIZE) {
+        let result =
+            pmm::alloc_frame().ok_or(Error::NoMemory).and_then(|frame| {
+                mapper
+                    .map_to(
+                        VirtAddr::new(page),
+                        frame,
+                        PageSize::Size4KiB,
+                        crate::paging::data_flags(),
+                        &mut pmm::PageTableFrames,
+                    )
+                    .map_err(|err| {
+                        pmm::free_frame(frame).ok();
+                        Error::Map(err)
+                    })
+            });
+        if let Err(err) = result {
+            free(KernelStack {
+                slot,
+                size: top - page - PAGE_SIZE,
+            })
+            .ok();
+            return Err(err);
+        }
+    }
+    Ok(stack)
+}
+
+/// Unmaps the kernel stack `stack`, frees its frames and releases its slot.
+///
+/// # Safety
+///
+/// The stack must not be in use. The current page tables must map the
+/// physical memory at `mm::phys_offset()`. The requirements of
+/// `tlb::flush_pages` must be met.
+pub unsafe fn free(stack: KernelStack) -> Result<(), Error> {
+    let mut mapper = crate::paging::current_mapper();
+    let bottom = stack.bottom().as_u64();
+    let top = stack.top().as_u64();
+
+    let mut frames = [PhysAddr::new(0); MAX_STACK_PAGES];
+    let mut unmapped = 0;
+    let mut result = Ok(());
+    for page in (bottom..top).step_by(PAGE_SIZE as usize) {
+        match mapper.unmap(VirtAddr::new(page)) {
+            Ok((frame, _)) => {
+                frames[unmapped] = frame;
+                unmapped += 1;
+            }
+            Err(err) => {
+                result = Err(Error::Map(err));
+                break;
+            }
+        }
+    }
+
+    // The frames are only freed once no CPU can access them through a stale
+    // translation.
+    tlb::flush_pages(bottom, top);
+    for &frame in &frames[..unmapped] {
+        pmm::free_frame(frame).ok();
+    }
+    result?;
+
+    SLOTS.lock()[stack.slot] = None;
+    Ok(())
+}
+
+/// Returns the owner of the stack whose guard pages contain `addr`, or
+/// `None` if `addr` is not in a guard page.
+///
+/// It does not wait for the lock of the slots, so it can be called from the
+/// exception handlers. If the lock is held, the owner is reported as
+/// unknown.
+pub fn guard_owner(addr: u64) -> Option<&'static str> {
+    let offset = addr.checked_sub(STACKS_START)?;
+    if offset / SLOT_SIZE >= MAX_STACKS as u64 {
+        return None;
+    }
+    let slot = (offset / SLOT_SIZE) as usize;
+
+    let slots = match SLOTS.try_lock() {
+        Some(slots) => slots,
+        None => return Some("<unknown>"),
+    };
+    let stack = slots[slot]?;
+    if offset % SLOT_SIZE < SLOT_SIZE - stack.size {
+        Some(stack.owner)
+    } else {
+        None
+    }
+}
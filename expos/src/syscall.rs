@@ -0,0 +1,153 @@
+//! System calls.
+//!
+//! User mode enters the kernel with the `syscall` instruction. The number of
+//! the system call is passed in RAX and its arguments in RDI, RSI, RDX, R10
+//! and R8. The result is returned in RAX: a negative value is the error code
+//! and the rest are the value returned by the system call. RCX and R11 are
+//! clobbered by the instruction, the rest of the registers are preserved.
+//!
+//! | Number | System call                       |
+//! |--------|-----------------------------------|
+//! | 0      | `write(fd, buf, len) -> written`  |
+//! | 1      | `exit(status) -> !`               |
+//! | 2      | `yield() -> 0`                    |
+//!
+//! Reference:
+//! - Intel SDM Vol. 2B, "SYSCALL - Fast System Call"
+
+use crate::{gdt, task, user};
+
+global_asm!(include_str!("syscall/entry.s"), options(att_syntax));
+
+extern "C" {
+    /// Entry point of the system calls.
+    fn syscall_entry();
+}
+
+/// IA32_EFER bit that enables the `syscall` and `sysret` instructions.
+const EFER_SCE: u64 = 1 << 0;
+
+/// IA32_STAR MSR. It holds the segment selectors loaded by `syscall` and
+/// `sysret`.
+const IA32_STAR: u32 = 0xc000_0081;
+
+/// IA32_LSTAR MSR. It holds the entry point of `syscall` in 64-bit mode.
+const IA32_LSTAR: u32 = 0xc000_0082;
+
+/// IA32_FMASK MSR. The RFLAGS bits set in it are cleared by `syscall`.
+const IA32_FMASK: u32 = 0xc000_0084;
+
+/// RFLAGS cleared on entry: TF, IF, DF and AC.
+const FMASK: u64 = 1 << 8 | 1 << 9 | 1 << 10 | 1 << 18;
+
+/// File descriptor of the standard output.
+const STDOUT: u64 = 1;
+
+/// File descriptor of the standard error.
+const STDERR: u64 = 2;
+
+/// System call handler. It receives the arguments of the system call.
+type Handler = fn([u64; 5]) -> Result<u64, Error>;
+
+/// System call table. The handler of the system call `n` is at index `n`.
+const SYSCALLS: [Handler; 3] = [sys_write, sys_exit, sys_yield];
+
+/// System call errors. They are returned to user mode as the negative value
+/// of their code, which matches the Linux one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Bad file descriptor.
+    BadFd,
+
+    /// Bad address.
+    Fault,
+
+    /// Invalid argument.
+    Invalid,
+
+    /// Unknown system call.
+    NoSys,
+}
+
+impl Error {
+    /// Returns the error code.
+    fn code(self) -> i64 {
+        match self {
+            Error::BadFd => 9,
+            Error::Fault => 14,
+            Error::Invalid => 22,
+            Error::NoSys => 38,
+        }
+    }
+}
+
+/// Enables the `syscall` instruction on the current CPU.
+///
+/// # Safety
+///
+/// `gdt::init` must have been called on the current CPU.
+pub unsafe fn init() {
+    let star = u64::from(gdt::SYSRET_BASE_SELECTOR) << 48
+        | u64::from(gdt::KERNEL_CODE_SELECTOR) << 32;
+    cpu::wrmsr(IA32_STAR, star);
+    cpu::wrmsr(IA32_LSTAR, syscall_entry as usize as u64);
+    cpu::wrmsr(IA32_FMASK, FMASK);
+    cpu::wrmsr(cpu::IA32_EFER, cpu::rdmsr(cpu::IA32_EFER) | EFER_SCE);
+}
+
+/// Sets the stack loaded when user mode executes `syscall` on the current
+/// CPU. `top` is the initial stack pointer.
+///
+/// # Safety
+///
+/// The stack must be mapped and not in use. `percpu::init` must have been
+/// called on the current CPU.
+pub unsafe fn set_kernel_stack(top: u64) {
+    percpu::set_kernel_rsp(top);
+}
+
+/// Runs the system call `number` with the arguments `arg0` to `arg4`. It is
+/// called by `syscall_entry` with the interrupts enabled.
+#[no_mangle]
+extern "sysv64" fn syscall_dispatch(
+    number: u64,
+    arg0: u64,
+    arg1: u64,
+    arg2: u64,
+    arg3: u64,
+    arg4: u64,
+) -> i64 {
+    let result = SYSCALLS
+        .get(number as usize)
+        .ok_or(Error::NoSys)
+        .and_then(|handler| handler([arg0, arg1, arg2, arg3, arg4]));
+    match result {
+        Ok(val) => val as i64,
+        Err(err) => -err.code(),
+    }
+}
+
+/// Writes `len` bytes from the user buffer at `buf` to the file descriptor
+/// `fd`. Only the standard output and the standard error are supported,
+/// which are the console. The buffer must be UTF-8.
+fn sys_write([fd, buf, len, _, _]: [u64; 5]) -> Result<u64, Error> {
+    if fd != STDOUT && fd != STDERR {
+        return Err(Error::BadFd);
+    }
+    // The buffer is only used while the task runs.
+    let buf = unsafe { user::slice(buf, len) }.ok_or(Error::Fault)?;
+    let s = core::str::from_utf8(buf).or(Err(Error::Invalid))?;
+    crate::print!("{}", s);
+    Ok(len)
+}
+
+/// Terminates the current task with the exit status `status`.
+fn sys_exit([status, _, _, _, _]: [u64; 5]) -> Result<u64, Error> {
+    task::exit(status as i32)
+}
+
+/// Gives up the CPU.
+fn sys_yield(_args: [u64; 5]) -> Result<u64, Error> {
+    task::yield_now();
+    Ok(0)
+}
@@ -0,0 +1,68 @@
+/*
+ * System call entry point.
+ *
+ * The syscall instruction jumps here in ring 0 with the user stack, the
+ * return address in %rcx and the user RFLAGS in %r11. The interrupts are
+ * masked by IA32_FMASK. The number of the system call is in %rax and its
+ * arguments in %rdi, %rsi, %rdx, %r10 and %r8.
+ *
+ * The entry point loads the kernel GS base with swapgs, switches to the
+ * kernel stack of the current task, which is taken from the per-CPU area
+ * together with the scratch slot for the user stack pointer, saves the user registers that are not
+ * clobbered by the system call and calls syscall_dispatch(number, arg0,
+ * arg1, arg2, arg3, arg4) with the interrupts enabled. Its return value is
+ * returned in %rax. Only %rax, %rcx and %r11 are clobbered. The GS base of
+ * user mode is loaded again before returning.
+ */
+
+	.global syscall_entry
+
+/* Offsets in the per-CPU area (see percpu::KERNEL_RSP_OFFSET). */
+	.set PERCPU_KERNEL_RSP, 8
+	.set PERCPU_USER_RSP, 16
+
+	.code64
+syscall_entry:
+	swapgs
+	movq %rsp, %gs:PERCPU_USER_RSP
+	movq %gs:PERCPU_KERNEL_RSP, %rsp
+
+	/* The stack is aligned to 16 bytes after these pushes. */
+	pushq %gs:PERCPU_USER_RSP
+	pushq %rcx
+	pushq %r11
+	pushq %rdi
+	pushq %rsi
+	pushq %rdx
+	pushq %r8
+	pushq %r9
+	pushq %r10
+	subq $8, %rsp
+
+	/* The user stack pointer is saved, so the interrupts can be enabled. */
+	sti
+
+	movq %r8, %r9
+	movq %r10, %r8
+	movq %rdx, %rcx
+	movq %rsi, %rdx
+	movq %rdi, %rsi
+	movq %rax, %rdi
+	call syscall_dispatch
+
+	/* The user stack pointer is restored, so the interrupts are masked. */
+	cli
+
+	addq $8, %rsp
+	popq %r10
+	popq %r9
+	popq %r8
+	popq %rdx
+	popq %rsi
+	popq %rdi
+	popq %r11
+	popq %rcx
+	popq %rsp
+	swapgs
+	sysretq
+
@@ -0,0 +1,506 @@
+//! Kernel tasks and scheduler.
+//!
+//! A task is a kernel thread with its own stack. The tasks are scheduled in
+//! round-robin order on the bootstrap processor: the periodic tick preempts
+//! the running task, and the tasks give up the CPU with `yield_now`, `sleep`
+//! and `join`. The application processors do not run tasks, they stay in
+//! their idle loop.
+//!
+//! The code that calls `init` becomes the `main` task. The `idle` task halts
+//! the CPU until the next interrupt when no other task is ready.
+//!
+//! The tasks run on the kernel page tables, unless they switch to their own
+//! user page tables to run in user mode (see `user`). In that case, their
+//! kernel stack is used by the interrupts and the system calls raised in
+//! user mode.
+//!
+//! The scheduler state is only accessed with the interrupts disabled, so the
+//! tick handler never finds it locked by the task it interrupted. The lock
+//! is released before switching tasks, but the interrupts stay disabled
+//! until the switch is completed.
+
+use core::mem;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use core::time::Duration;
+
+use mm::PhysAddr;
+use percpu::MAX_CPUS;
+use ticket_mutex::{TicketMutex, TicketMutexGuard};
+
+use crate::stack::{self, KernelStack};
+use crate::{gdt, smp, syscall, time, user, warn};
+
+global_asm!(include_str!("task/switch.s"), options(att_syntax));
+
+extern "C" {
+    /// Saves the callee-saved registers and the stack pointer of the current
+    /// task in `old_rsp` and resumes the task whose stack pointer is
+    /// `new_rsp`.
+    fn task_switch(old_rsp: *mut u64, new_rsp: u64);
+
+    /// First code run by a new task. It calls `task_start`.
+    static task_entry: u8;
+}
+
+/// Maximum number of tasks, including the main and the idle tasks.
+const MAX_TASKS: usize = 32;
+
+/// CPU that runs the tasks.
+const SCHED_CPU: usize = 0;
+
+/// Task running the code that called `init`.
+const MAIN_TASK: usize = 0;
+
+/// Task that runs when no other task is ready.
+const IDLE_TASK: usize = 1;
+
+/// Empty task slot.
+const NO_TASK: Option<Task> = None;
+
+/// Tasks. The task with ID `n` is at index `n`.
+static TASKS: TicketMutex<Tasks> = TicketMutex::new([NO_TASK; MAX_TASKS]);
+
+/// ID of the running task.
+static CURRENT: AtomicUsize = AtomicUsize::new(MAIN_TASK);
+
+/// Set once the scheduler is initialized.
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Physical address of the kernel PML4.
+static KERNEL_PML4: AtomicU64 = AtomicU64::new(0);
+
+/// Task slots.
+type Tasks = [Option<Task>; MAX_TASKS];
+
+/// Task errors.
+#[derive(Debug)]
+pub enum Error {
+    /// The scheduler is not running on the current CPU.
+    NotRunning,
+
+    /// There is no room for more tasks.
+    Full,
+
+    /// The stack of the task could not be allocated.
+    Stack(stack::Error),
+
+    /// There is no task with the given ID.
+    NotFound,
+
+    /// The task cannot be joined. It is the current or the idle task, or
+    /// another task is already joining it.
+    InvalidJoin,
+}
+
+/// Identifies a task. The ID of a task can be reused once it is joined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskId(usize);
+
+/// Scheduling state of a task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// The task is running.
+    Running,
+
+    /// The task is waiting to be scheduled.
+    Ready,
+
+    /// The task sleeps until the monotonic clock reaches the given time, in
+    /// nanoseconds.
+    Sleeping(u64),
+
+    /// The task waits for another task to exit.
+    Joining,
+
+    /// The task exited with the given status and waits to be joined.
+    Exited(i32),
+}
+
+/// Represents a task.
+struct Task {
+    /// Name of the task.
+    name: &'static str,
+
+    /// Scheduling state.
+    state: State,
+
+    /// Stack of the task. It is `None` for the main task, which runs on the
+    /// boot stack.
+    stack: Option<KernelStack>,
+
+    /// Stack pointer saved when the task was switched out.
+    rsp: u64,
+
+    /// Task joining this one.
+    joiner: Option<usize>,
+
+    /// PML4 of the user page tables. It is `None` if the task runs on the
+    /// kernel page tables.
+    user_pml4: Option<PhysAddr>,
+}
+
+impl Task {
+    /// Returns the value of CR3 while the task runs.
+    fn cr3(&self) -> u64 {
+        self.user_pml4
+            .map_or(KERNEL_PML4.load(Ordering::Relaxed), PhysAddr::as_u64)
+    }
+}
+
+/// Returns a ready task called `name`, which runs `entry(arg)` on a new
+/// stack.
+///
+/// # Safety
+///
+/// The current page tables must map the physical memory at
+/// `mm::phys_offset()`.
+unsafe fn new_task(
+    name: &'static str,
+    entry: fn(usize) -> i32,
+    arg: usize,
+) -> Result<Task, Error> {
+    let stack =
+        stack::alloc(name, stack::DEFAULT_STACK_SIZE).map_err(Error::Stack)?;
+
+    // Frame popped by `task_switch` when the task is scheduled for the first
+    // time: the callee-saved registers, with the entry point in r12 and its
+    // argument in r13, and the return address. The padding keeps the stack
+    // aligned to 16 bytes when `task_entry` calls `task_start`.
+    let frame: [u64; 9] = [
+        0,
+        0,
+        arg as u64,
+        entry as usize as u64,
+        0,
+        0,
+        &task_entry as *const u8 as u64,
+        0,
+        0,
+    ];
+    let rsp = stack.top().as_u64() - mem::size_of_val(&frame) as u64;
+    (rsp as *mut [u64; 9]).write(frame);
+
+    Ok(Task {
+        name,
+        state: State::Ready,
+        stack: Some(stack),
+        rsp,
+        joiner: None,
+        user_pml4: None,
+    })
+}
+
+/// Initializes the scheduler. The caller becomes the main task.
+///
+/// # Safety
+///
+/// This function must be called once, on the BSP, after `paging::init`.
+/// The periodic tick must be running. Otherwise, the tasks are not
+/// preempted and the sleeping tasks are never woken up.
+pub unsafe fn init() -> Result<(), Error> {
+    KERNEL_PML4.store(cpu::read_cr3(), Ordering::Relaxed);
+
+    let idle = new_task("idle", idle, 0)?;
+    TASKS.with(|tasks| {
+        tasks[MAIN_TASK] = Some(Task {
+            name: "main",
+            state: State::Running,
+            stack: None,
+            rsp: 0,
+            joiner: None,
+            user_pml4: None,
+        });
+        tasks[IDLE_TASK] = Some(idle);
+    });
+
+    // A task can be preempted while it holds a mutex, so another task
+    // locking it on the same CPU is not a deadlock. Thus, the holders of
+    // the mutexes are identified by task.
+    ticket_mutex::set_cpu_id(owner_id);
+
+    STARTED.store(true, Ordering::Release);
+    Ok(())
+}
+
+/// Creates a task called `name` that runs `entry(arg)`. The task exits
+/// with the status returned by `entry` and it must be joined to release its
+/// resources.
+pub fn spawn(
+    name: &'static str,
+    entry: fn(usize) -> i32,
+    arg: usize,
+) -> Result<TaskId, Error> {
+    if !STARTED.load(Ordering::Acquire) {
+        return Err(Error::NotRunning);
+    }
+
+    // The kernel page tables are loaded before initializing the scheduler.
+    let task = unsafe { new_task(name, entry, arg)? };
+
+    let result = cpu::without_interrupts(|| {
+        let mut tasks = TASKS.lock();
+        match tasks.iter().position(Option::is_none) {
+            Some(id) => {
+                tasks[id] = Some(task);
+                Ok(TaskId(id))
+            }
+            None => Err(task),
+        }
+    });
+    result.map_err(|task| {
+        // The task never ran, so its stack is not in use.
+        if let Some(stack) = task.stack {
+            unsafe { stack::free(stack) }.ok();
+        }
+        Error::Full
+    })
+}
+
+/// Gives up the CPU, so the next ready task runs. It returns immediately if
+/// no other task is ready or the scheduler is not running.
+pub fn yield_now() {
+    if !scheduling() {
+        return;
+    }
+
+    cpu::without_interrupts(|| unsafe { schedule(TASKS.lock()) });
+}
+
+/// Suspends the current task for, at least, `duration`. The sleeping tasks
+/// are woken up by the periodic tick. If the scheduler is not running, it
+/// busy waits instead.
+pub fn sleep(duration: Duration) {
+    if !scheduling() {
+        time::busy_sleep(duration);
+        return;
+    }
+
+    let deadline = time::now_ns().saturating_add(duration.as_nanos() as u64);
+    cpu::without_interrupts(|| {
+        let mut tasks = TASKS.lock();
+        current_task(&mut tasks).state = State::Sleeping(deadline);
+        unsafe { schedule(tasks) };
+    });
+}
+
+/// Terminates the current task with the exit status `status`.
+///
+/// # Panics
+///
+/// This function panics if the scheduler is not running on the current
+/// CPU.
+pub fn exit(status: i32) -> ! {
+    assert!(scheduling(), "task: scheduler not running");
+
+    unsafe { cpu::cli() };
+    let mut tasks = TASKS.lock();
+    let task = current_task(&mut tasks);
+    task.state = State::Exited(status);
+    if let Some(joiner) = task.joiner {
+        if let Some(joiner) = tasks[joiner].as_mut() {
+            joiner.state = State::Ready;
+        }
+    }
+
+    // Interrupts are disabled.
+    unsafe { schedule(tasks) };
+    unreachable!("task: exited task resumed");
+}
+
+/// Waits for the task `id` to exit, releases its resources and returns its
+/// exit status.
+pub fn join(id: TaskId) -> Result<i32, Error> {
+    if !scheduling() {
+        return Err(Error::NotRunning);
+    }
+
+    let (task, status) = cpu::without_interrupts(|| loop {
+        let mut tasks = TASKS.lock();
+        let current = CURRENT.load(Ordering::Relaxed);
+
+        let task = tasks
+            .get_mut(id.0)
+            .and_then(Option::as_mut)
+            .ok_or(Error::NotFound)?;
+        if let State::Exited(status) = task.state {
+            return Ok((tasks[id.0].take().unwrap(), status));
+        }
+        if id.0 == current
+            || id.0 == IDLE_TASK
+            || matches!(task.joiner, Some(joiner) if joiner != current)
+        {
+            return Err(Error::InvalidJoin);
+        }
+
+        // The task wakes us up when it exits.
+        task.joiner = Some(current);
+        current_task(&mut tasks).state = State::Joining;
+        unsafe { schedule(tasks) };
+    })?;
+
+    // An exited task is never resumed, so its stack and its page tables are
+    // not in use.
+    if let Some(pml4) = task.user_pml4 {
+        unsafe { user::free_page_tables(pml4) };
+    }
+    if let Some(stack) = task.stack {
+        if let Err(err) = unsafe { stack::free(stack) } {
+            warn!(
+                target: "task",
+                "{}: stack not freed: {:?}",
+                task.name,
+                err,
+            );
+        }
+    }
+    Ok(status)
+}
+
+/// Wakes up the sleeping tasks whose deadline has passed and preempts the
+/// running task. It is called by the tick handler, after acknowledging the
+/// interrupt.
+pub fn tick() {
+    if !scheduling() {
+        return;
+    }
+
+    let now = time::now_ns();
+    let mut tasks = TASKS.lock();
+    for task in tasks.iter_mut().flatten() {
+        if let State::Sleeping(deadline) = task.state {
+            if deadline <= now {
+                task.state = State::Ready;
+            }
+        }
+    }
+
+    // Interrupts are disabled in the interrupt handlers.
+    unsafe { schedule(tasks) };
+}
+
+/// Switches to the next ready task in round-robin order. If no task is
+/// ready, the current task keeps running or, if it is not running anymore,
+/// the idle task runs.
+///
+/// # Safety
+///
+/// Interrupts must be disabled and the current CPU must be `SCHED_CPU`.
+unsafe fn schedule(mut tasks: TicketMutexGuard<Tasks>) {
+    let current = CURRENT.load(Ordering::Relaxed);
+    let is_ready = |id: usize| {
+        tasks[id]
+            .as_ref()
+            .map_or(false, |task| task.state == State::Ready)
+    };
+    let next = (1..=MAX_TASKS)
+        .map(|n| (current + n) % MAX_TASKS)
+        .find(|&id| id != IDLE_TASK && is_ready(id));
+    let next = match next {
+        Some(next) => next,
+        None if current_task(&mut tasks).state == State::Running => return,
+        None => IDLE_TASK,
+    };
+    if next == current {
+        return;
+    }
+
+    let task = current_task(&mut tasks);
+    if task.state == State::Running {
+        task.state = State::Ready;
+    }
+    let old_rsp = &mut task.rsp as *mut u64;
+
+    let task = tasks[next].as_mut().unwrap();
+    task.state = State::Running;
+    let new_rsp = task.rsp;
+    if task.user_pml4.is_some() {
+        set_kernel_stack(task);
+    }
+    if task.cr3() != cpu::read_cr3() {
+        cpu::write_cr3(task.cr3());
+    }
+
+    // Only this CPU accesses the slot of the current task until it is
+    // resumed, so the stack pointer can be saved after unlocking.
+    CURRENT.store(next, Ordering::Relaxed);
+    drop(tasks);
+    task_switch(old_rsp, new_rsp);
+}
+
+/// Switches the current task to the user page tables whose PML4 is at
+/// `pml4`. They are freed when the task is joined.
+///
+/// # Safety
+///
+/// The page tables must map the kernel like the kernel page tables. The
+/// scheduler must be running on the current CPU.
+pub unsafe fn set_user_page_tables(pml4: PhysAddr) {
+    cpu::without_interrupts(|| {
+        let mut tasks = TASKS.lock();
+        let task = current_task(&mut tasks);
+        task.user_pml4 = Some(pml4);
+        set_kernel_stack(task);
+        cpu::write_cr3(pml4.as_u64());
+    });
+}
+
+/// Sets the stack of `task` as the stack used by the interrupts and the
+/// system calls raised in user mode.
+///
+/// # Safety
+///
+/// Interrupts must be disabled and `task` must be about to run on the
+/// current CPU.
+unsafe fn set_kernel_stack(task: &Task) {
+    if let Some(stack) = &task.stack {
+        gdt::set_kernel_stack(stack.top().as_u64());
+        syscall::set_kernel_stack(stack.top().as_u64());
+    }
+}
+
+/// Returns the running task.
+fn current_task(tasks: &mut Tasks) -> &mut Task {
+    tasks[CURRENT.load(Ordering::Relaxed)]
+        .as_mut()
+        .expect("task: current task not found")
+}
+
+/// Returns `true` if the scheduler is initialized and the current CPU runs
+/// the tasks.
+fn scheduling() -> bool {
+    STARTED.load(Ordering::Acquire) && smp::cpu_index() == SCHED_CPU
+}
+
+/// Returns the ID of the holder of a mutex locked by the current code: the
+/// running task on `SCHED_CPU` and the CPU index on the rest of the CPUs.
+fn owner_id() -> usize {
+    let cpu = smp::cpu_index();
+    if cpu == SCHED_CPU && STARTED.load(Ordering::Acquire) {
+        MAX_CPUS + CURRENT.load(Ordering::Relaxed)
+    } else {
+        cpu
+    }
+}
+
+/// Entry point of the new tasks. It is called by `task_entry` with the
+/// interrupts disabled, given that the tasks are switched with the
+/// interrupts disabled. `entry` is the address of the function run by the
+/// task.
+#[no_mangle]
+extern "sysv64" fn task_start(entry: usize, arg: usize) -> ! {
+    unsafe { cpu::sti() };
+
+    // The address was taken from a `fn(usize) -> i32` by `new_task`.
+    let entry = unsafe { mem::transmute::<usize, fn(usize) -> i32>(entry) };
+    exit(entry(arg))
+}
+
+/// Idle task. The CPU sleeps until the next interrupt.
+fn idle(_arg: usize) -> i32 {
+    loop {
+        unsafe {
+            cpu::sti();
+            cpu::hlt();
+        }
+    }
+}
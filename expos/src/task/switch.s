@@ -0,0 +1,40 @@
+/*
+ * Context switch between kernel tasks.
+ *
+ * task_switch(old_rsp: *mut u64, new_rsp: u64) saves the callee-saved
+ * registers of the current task on its stack, stores its stack pointer in
+ * old_rsp and resumes the task whose stack pointer is new_rsp. The rest of
+ * the registers are saved by the caller, as defined by the System V ABI.
+ *
+ * The stack of a new task is prepared so task_switch returns to task_entry
+ * with the entry point of the task in %r12 and its argument in %r13.
+ */
+
+	.global task_switch
+	.global task_entry
+
+	.code64
+task_switch:
+	pushq %rbp
+	pushq %rbx
+	pushq %r12
+	pushq %r13
+	pushq %r14
+	pushq %r15
+
+	movq %rsp, (%rdi)
+	movq %rsi, %rsp
+
+	popq %r15
+	popq %r14
+	popq %r13
+	popq %r12
+	popq %rbx
+	popq %rbp
+	ret
+
+task_entry:
+	movq %r12, %rdi
+	movq %r13, %rsi
+	call task_start
+	ud2
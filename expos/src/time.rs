@@ -0,0 +1,533 @@
+//! Time keeping.
+//!
+//! The monotonic clock is based on the TSC. Its frequency, and the one of
+//! the local APIC timer, are calibrated against a reference clock: the HPET
+//! if the platform has one and the PIT otherwise. The local APIC timer
+//! provides a periodic tick to the registered callbacks and the scheduler.
+//!
+//! The wall-clock time is obtained from the CMOS Real-Time Clock (RTC) and,
+//! once the TSC is calibrated, advanced with the monotonic clock.
+//!
+//! Reference:
+//! - [OSDev article](https://wiki.osdev.org/CMOS)
+
+mod hpet;
+mod pit;
+
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use core::time::Duration;
+
+use cpu::{in8, out8};
+use ticket_mutex::TicketMutex;
+use uefi::acpi;
+
+use crate::idt::{self, InterruptStackFrame, KernelGs};
+use crate::task;
+use hpet::Hpet;
+
+/// CMOS register selection port.
+const CMOS_ADDRESS: u16 = 0x70;
+
+/// CMOS data port.
+const CMOS_DATA: u16 = 0x71;
+
+/// RTC registers.
+const RTC_SECONDS: u8 = 0x00;
+const RTC_MINUTES: u8 = 0x02;
+const RTC_HOURS: u8 = 0x04;
+const RTC_DAY: u8 = 0x07;
+const RTC_MONTH: u8 = 0x08;
+const RTC_YEAR: u8 = 0x09;
+const RTC_STATUS_A: u8 = 0x0a;
+const RTC_STATUS_B: u8 = 0x0b;
+
+/// Serializes the accesses to the CMOS, which require writing the address
+/// port before reading the data port.
+static CMOS: TicketMutex<()> = TicketMutex::new(());
+
+/// CMOS register holding the century, as reported by the FADT. Zero means
+/// that the RTC does not provide it.
+static CENTURY_REG: AtomicU8 = AtomicU8::new(0);
+
+/// Century assumed when the RTC does not provide it.
+const DEFAULT_CENTURY: u64 = 20;
+
+/// Length of the interval used to calibrate the TSC and the local APIC
+/// timer, in microseconds.
+const CALIBRATION_US: u64 = 10_000;
+
+/// Frequency of the periodic tick in Hz.
+pub const TICK_HZ: u64 = 100;
+
+/// Maximum number of tick callbacks.
+const MAX_TICK_CALLBACKS: usize = 8;
+
+/// Frequency of the TSC in Hz. It is zero until the TSC is calibrated.
+static TSC_HZ: AtomicU64 = AtomicU64::new(0);
+
+/// Value of the TSC when it was calibrated. It is the origin of the
+/// monotonic clock.
+static TSC_BASE: AtomicU64 = AtomicU64::new(0);
+
+/// Frequency of the local APIC timer in Hz.
+static LAPIC_TIMER_HZ: AtomicU64 = AtomicU64::new(0);
+
+/// Wall-clock time, in seconds since the Unix epoch, at the origin of the
+/// monotonic clock.
+static BOOT_WALLCLOCK: AtomicU64 = AtomicU64::new(0);
+
+/// Serializes the accesses to the channel 2 of the PIT.
+static PIT: TicketMutex<()> = TicketMutex::new(());
+
+/// Functions called on every tick.
+static TICK_CALLBACKS: TicketMutex<TickCallbacks> =
+    TicketMutex::new([None; MAX_TICK_CALLBACKS]);
+
+/// Registered tick callbacks.
+type TickCallbacks = [Option<fn()>; MAX_TICK_CALLBACKS];
+
+/// Time errors.
+#[derive(Debug)]
+pub enum Error {
+    /// The clocks are not calibrated.
+    NotCalibrated,
+
+    /// There is no room for more tick callbacks.
+    TooManyCallbacks,
+}
+
+/// Clock used as reference to calibrate the TSC and the local APIC timer.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Reference {
+    /// High Precision Event Timer.
+    Hpet,
+
+    /// Programmable Interval Timer.
+    Pit,
+}
+
+/// Result of the calibration of the clocks.
+#[derive(Debug, Clone, Copy)]
+pub struct Calibration {
+    /// Reference clock.
+    reference: Reference,
+
+    /// Frequency of the TSC in Hz.
+    tsc_hz: u64,
+
+    /// Frequency of the local APIC timer in Hz.
+    lapic_timer_hz: u64,
+}
+
+impl Calibration {
+    /// Returns the reference clock.
+    pub fn reference(&self) -> Reference {
+        self.reference
+    }
+
+    /// Returns the frequency of the TSC in Hz.
+    pub fn tsc_hz(&self) -> u64 {
+        self.tsc_hz
+    }
+
+    /// Returns the frequency of the local APIC timer in Hz.
+    pub fn lapic_timer_hz(&self) -> u64 {
+        self.lapic_timer_hz
+    }
+}
+
+/// Represents a date and time in UTC.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct DateTime {
+    /// Year, including the century.
+    pub year: u64,
+
+    /// Month of the year, starting at 1.
+    pub month: u64,
+
+    /// Day of the month, starting at 1.
+    pub day: u64,
+
+    /// Hour in 24-hour format.
+    pub hour: u64,
+
+    /// Minutes.
+    pub minute: u64,
+
+    /// Seconds.
+    pub second: u64,
+}
+
+impl DateTime {
+    /// Returns `true` if all the fields are in range and the date is not
+    /// before the Unix epoch. The number of days of the month is not
+    /// checked.
+    pub fn is_valid(&self) -> bool {
+        self.year >= 1970
+            && (1..=12).contains(&self.month)
+            && (1..=31).contains(&self.day)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+    }
+
+    /// Returns the number of seconds elapsed since the Unix epoch
+    /// (1970-01-01T00:00:00Z), or `None` if the date and time are not valid
+    /// (see `is_valid`) or the result overflows.
+    pub fn unix_timestamp(&self) -> Option<u64> {
+        if !self.is_valid() {
+            return None;
+        }
+
+        // Shift the year so it starts in March. Then, the leap day is the
+        // last day of the year. The year is at least 1970, so it does not
+        // underflow.
+        let year = if self.month <= 2 {
+            self.year - 1
+        } else {
+            self.year
+        };
+        let era = year / 400;
+        let year_of_era = year % 400;
+        let month = (self.month + 9) % 12;
+        let day_of_year = (153 * month + 2) / 5 + self.day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4
+            - year_of_era / 100
+            + day_of_year;
+
+        // 719468 is the number of days from 0000-03-01 to 1970-01-01.
+        let days = era
+            .checked_mul(146097)?
+            .checked_add(day_of_era)?
+            .checked_sub(719468)?;
+
+        days.checked_mul(86400)?
+            .checked_add(self.hour * 3600 + self.minute * 60 + self.second)
+    }
+}
+
+/// Initializes the wall-clock service. `century_reg` is the CMOS register
+/// holding the century, as reported by the FADT.
+pub fn init(century_reg: Option<u8>) {
+    CENTURY_REG.store(century_reg.unwrap_or(0), Ordering::SeqCst);
+}
+
+/// Returns the current date and time, or `None` if the RTC holds an invalid
+/// value.
+pub fn now() -> Option<DateTime> {
+    read_rtc()
+}
+
+/// Returns the current wall-clock time as the number of seconds elapsed since
+/// the Unix epoch. Once the TSC is calibrated, it is advanced with the
+/// monotonic clock instead of reading the RTC. If the RTC holds an invalid
+/// value, the wall-clock time starts at the Unix epoch.
+pub fn wallclock() -> u64 {
+    if TSC_HZ.load(Ordering::Acquire) == 0 {
+        return rtc_timestamp();
+    }
+    BOOT_WALLCLOCK.load(Ordering::Relaxed) + now_ns() / 1_000_000_000
+}
+
+/// Calibrates the TSC and the local APIC timer of the current CPU against
+/// the HPET described by `hpet` or, if it is `None` or not usable, the PIT.
+/// The monotonic clock starts at zero afterwards.
+///
+/// # Safety
+///
+/// The local APIC must be initialized and the HPET registers must be
+/// identity mapped. Interrupts must be disabled, so the measurement is not
+/// delayed.
+pub unsafe fn calibrate(hpet: Option<&acpi::Hpet>) -> Calibration {
+    let hpet = hpet.and_then(|hpet| Hpet::new(hpet));
+    let reference = if hpet.is_some() {
+        Reference::Hpet
+    } else {
+        Reference::Pit
+    };
+
+    let _pit = PIT.lock();
+
+    lapic::start_timer(None, lapic::TimerMode::OneShot, u32::MAX);
+    let tsc_start = cpu::rdtsc();
+    match &hpet {
+        Some(hpet) => hpet.wait_us(CALIBRATION_US),
+        None => pit::wait_us(CALIBRATION_US),
+    }
+    let tsc_end = cpu::rdtsc();
+    let lapic_count = lapic::timer_count();
+    lapic::stop_timer();
+
+    let tsc_hz = (tsc_end - tsc_start) * 1_000_000 / CALIBRATION_US;
+    let lapic_timer_hz =
+        u64::from(u32::MAX - lapic_count) * 1_000_000 / CALIBRATION_US;
+
+    BOOT_WALLCLOCK.store(rtc_timestamp(), Ordering::Relaxed);
+    LAPIC_TIMER_HZ.store(lapic_timer_hz, Ordering::Relaxed);
+    TSC_BASE.store(cpu::rdtsc(), Ordering::Relaxed);
+    TSC_HZ.store(tsc_hz, Ordering::Release);
+
+    Calibration {
+        reference,
+        tsc_hz,
+        lapic_timer_hz,
+    }
+}
+
+/// Returns the number of nanoseconds elapsed since the TSC was calibrated.
+/// It returns 0 if it is not calibrated.
+pub fn now_ns() -> u64 {
+    let tsc_hz = TSC_HZ.load(Ordering::Acquire);
+    if tsc_hz == 0 {
+        return 0;
+    }
+
+    let ticks = cpu::rdtsc().wrapping_sub(TSC_BASE.load(Ordering::Relaxed));
+    (u128::from(ticks) * 1_000_000_000 / u128::from(tsc_hz)) as u64
+}
+
+/// Returns the frequency of the TSC in Hz, or `None` if it is not
+/// calibrated.
+pub fn tsc_hz() -> Option<u64> {
+    match TSC_HZ.load(Ordering::Acquire) {
+        0 => None,
+        tsc_hz => Some(tsc_hz),
+    }
+}
+
+/// Busy waits for `duration`. If the TSC is not calibrated, the PIT is used
+/// instead.
+pub fn busy_sleep(duration: Duration) {
+    if TSC_HZ.load(Ordering::Acquire) == 0 {
+        let _pit = PIT.lock();
+        let mut us = duration.as_micros() as u64;
+        while us > 0 {
+            let chunk = us.min(pit::MAX_WAIT_US);
+            // The channel 2 of the PIT is protected by `PIT`.
+            unsafe { pit::wait_us(chunk) };
+            us -= chunk;
+        }
+        return;
+    }
+
+    let deadline = now_ns().saturating_add(duration.as_nanos() as u64);
+    while now_ns() < deadline {
+        core::hint::spin_loop();
+    }
+}
+
+/// Registers `callback` to be called on every tick. It is called in
+/// interrupt context, so it must be short and must not take locks that are
+/// held with interrupts enabled.
+pub fn register_tick(callback: fn()) -> Result<(), Error> {
+    // The tick handler takes the lock, so interrupts must be disabled while
+    // holding it.
+    cpu::without_interrupts(|| {
+        TICK_CALLBACKS.with(|callbacks| {
+            let slot = callbacks
+                .iter_mut()
+                .find(|slot| slot.is_none())
+                .ok_or(Error::TooManyCallbacks)?;
+            *slot = Some(callback);
+            Ok(())
+        })
+    })
+}
+
+/// Starts the periodic tick on the current CPU, at `TICK_HZ`, using its
+/// local APIC timer.
+///
+/// # Safety
+///
+/// The IDT must be loaded, given that the tick handler is registered in it.
+pub unsafe fn start_tick() -> Result<(), Error> {
+    let lapic_timer_hz = LAPIC_TIMER_HZ.load(Ordering::Relaxed);
+    if lapic_timer_hz == 0 {
+        return Err(Error::NotCalibrated);
+    }
+
+    idt::set_interrupt_handler(idt::TIMER_VECTOR, tick_handler);
+    lapic::start_timer(
+        Some(idt::TIMER_VECTOR),
+        lapic::TimerMode::Periodic,
+        (lapic_timer_hz / TICK_HZ) as u32,
+    );
+    Ok(())
+}
+
+/// Handler of the local APIC timer interrupt. It calls the tick callbacks
+/// and lets the scheduler preempt the running task.
+extern "x86-interrupt" fn tick_handler(frame: InterruptStackFrame) {
+    let _gs = KernelGs::enter(&frame);
+
+    // Copy the callbacks, so they can register new ones.
+    let callbacks = *TICK_CALLBACKS.lock();
+    for callback in callbacks.iter().flatten() {
+        callback();
+    }
+
+    // The handler does not return until the interrupted task is scheduled
+    // again, so the interrupt is acknowledged before switching tasks.
+    lapic::end_of_interrupt();
+    task::tick();
+}
+
+/// Reads the CMOS register `reg`.
+fn read_cmos(reg: u8) -> u8 {
+    // The CMOS ports are always present on the IBM PC.
+    unsafe {
+        out8(CMOS_ADDRESS, reg);
+        in8(CMOS_DATA)
+    }
+}
+
+/// Reads the raw RTC registers, waiting for any update in progress to finish.
+/// The returned array contains the seconds, minutes, hours, day, month, year
+/// and century registers.
+fn read_rtc_registers(century_reg: u8) -> [u8; 7] {
+    // Wait until the "Update in progress" flag is clear.
+    while read_cmos(RTC_STATUS_A) & 0x80 != 0 {
+        core::hint::spin_loop();
+    }
+
+    [
+        read_cmos(RTC_SECONDS),
+        read_cmos(RTC_MINUTES),
+        read_cmos(RTC_HOURS),
+        read_cmos(RTC_DAY),
+        read_cmos(RTC_MONTH),
+        read_cmos(RTC_YEAR),
+        if century_reg != 0 {
+            read_cmos(century_reg)
+        } else {
+            0
+        },
+    ]
+}
+
+/// Returns the RTC time as the number of seconds elapsed since the Unix
+/// epoch, or zero if it is not valid.
+fn rtc_timestamp() -> u64 {
+    now()
+        .and_then(|datetime| datetime.unix_timestamp())
+        .unwrap_or(0)
+}
+
+/// Converts a BCD value into binary.
+fn bcd_to_bin(val: u8) -> u8 {
+    (val & 0x0f) + (val >> 4) * 10
+}
+
+/// Reads the current date and time from the RTC. It returns `None` if the
+/// RTC holds an invalid value (see `DateTime::is_valid`).
+fn read_rtc() -> Option<DateTime> {
+    let _cmos = CMOS.lock();
+
+    let century_reg = CENTURY_REG.load(Ordering::SeqCst);
+
+    // An update can start right after checking the "Update in progress"
+    // flag. Read the registers until two consecutive readings match.
+    let mut regs = read_rtc_registers(century_reg);
+    loop {
+        let next = read_rtc_registers(century_reg);
+        if next == regs {
+            break;
+        }
+        regs = next;
+    }
+    let [second, minute, hour, day, month, year, century] = regs;
+
+    // Status Register B describes the format of the values.
+    let status_b = read_cmos(RTC_STATUS_B);
+    let binary = status_b & 0x04 != 0;
+    let hour_24 = status_b & 0x02 != 0;
+
+    // In 12-hour mode, the highest bit of the hour is set for PM.
+    let pm = !hour_24 && hour & 0x80 != 0;
+    let hour = hour & 0x7f;
+
+    let conv = |val| if binary { val } else { bcd_to_bin(val) };
+    let (second, minute, mut hour, day, month, year, century) = (
+        conv(second) as u64,
+        conv(minute) as u64,
+        conv(hour) as u64,
+        conv(day) as u64,
+        conv(month) as u64,
+        conv(year) as u64,
+        conv(century) as u64,
+    );
+
+    // Convert 12-hour values. 12AM is 0 and 12PM is 12.
+    if !hour_24 {
+        hour %= 12;
+        if pm {
+            hour += 12;
+        }
+    }
+
+    let century = if century_reg != 0 {
+        century
+    } else {
+        DEFAULT_CENTURY
+    };
+
+    let datetime = DateTime {
+        year: century * 100 + year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+    };
+    Some(datetime).filter(DateTime::is_valid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the `DateTime` with the given fields.
+    fn datetime(date: (u64, u64, u64), time: (u64, u64, u64)) -> DateTime {
+        DateTime {
+            year: date.0,
+            month: date.1,
+            day: date.2,
+            hour: time.0,
+            minute: time.1,
+            second: time.2,
+        }
+    }
+
+    #[test]
+    fn test_unix_timestamp() {
+        let epoch = datetime((1970, 1, 1), (0, 0, 0));
+        assert_eq!(epoch.unix_timestamp(), Some(0));
+        let march = datetime((2000, 3, 1), (0, 0, 0));
+        assert_eq!(march.unix_timestamp(), Some(951868800));
+        let leap_day = datetime((2024, 2, 29), (12, 34, 56));
+        assert_eq!(leap_day.unix_timestamp(), Some(1709210096));
+    }
+
+    #[test]
+    fn test_unix_timestamp_invalid() {
+        for invalid in &[
+            datetime((1969, 12, 31), (23, 59, 59)),
+            datetime((0, 0, 0), (0, 0, 0)),
+            datetime((2024, 0, 1), (0, 0, 0)),
+            datetime((2024, 13, 1), (0, 0, 0)),
+            datetime((2024, 1, 0), (0, 0, 0)),
+            datetime((2024, 1, 32), (0, 0, 0)),
+            datetime((2024, 1, 1), (24, 0, 0)),
+            datetime((2024, 1, 1), (0, 60, 0)),
+            datetime((2024, 1, 1), (0, 0, 60)),
+        ] {
+            assert_eq!(invalid.unix_timestamp(), None, "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_unix_timestamp_overflow() {
+        let far = datetime((u64::MAX, 12, 31), (23, 59, 59));
+        assert_eq!(far.unix_timestamp(), None);
+    }
+}
@@ -0,0 +1,89 @@
+//! High Precision Event Timer (HPET).
+//!
+//! Only the main counter is used, as a reference clock.
+//!
+//! Reference:
+//! - [IA-PC HPET Specification](https://www.intel.com/content/dam/www/public/us/en/documents/technical-specifications/software-developers-hpet-spec-1-0a.pdf)
+
+use uefi::acpi;
+
+/// General Capabilities and ID register.
+const REG_CAPABILITIES: u64 = 0x00;
+
+/// General Configuration register.
+const REG_CONFIG: u64 = 0x10;
+
+/// Main Counter Value register.
+const REG_COUNTER: u64 = 0xf0;
+
+/// General Configuration bit that enables the main counter.
+const CONFIG_ENABLE: u64 = 1 << 0;
+
+/// Maximum period of the main counter allowed by the specification, in
+/// femtoseconds.
+const MAX_PERIOD_FS: u64 = 100_000_000;
+
+/// Represents an HPET whose main counter is running.
+pub struct Hpet {
+    /// Physical address of the registers. They are identity mapped.
+    base: u64,
+
+    /// Period of the main counter in femtoseconds.
+    period_fs: u64,
+
+    /// Mask of the valid bits of the main counter.
+    counter_mask: u64,
+}
+
+impl Hpet {
+    /// Enables the main counter of the HPET described by `hpet`. It returns
+    /// `None` if it reports an invalid period.
+    ///
+    /// # Safety
+    ///
+    /// The HPET registers must be identity mapped and nothing else must be
+    /// using the HPET.
+    pub unsafe fn new(hpet: &acpi::Hpet) -> Option<Self> {
+        let base = hpet.base_address();
+        let hpet = Hpet {
+            base,
+            period_fs: 0,
+            counter_mask: if hpet.counter_64bit() {
+                u64::MAX
+            } else {
+                u32::MAX.into()
+            },
+        };
+
+        let period_fs = hpet.read(REG_CAPABILITIES) >> 32;
+        if period_fs == 0 || period_fs > MAX_PERIOD_FS {
+            return None;
+        }
+
+        let config = hpet.read(REG_CONFIG);
+        hpet.write(REG_CONFIG, config | CONFIG_ENABLE);
+
+        Some(Hpet { period_fs, ..hpet })
+    }
+
+    /// Reads the register `reg`.
+    fn read(&self, reg: u64) -> u64 {
+        unsafe { ((self.base + reg) as *const u64).read_volatile() }
+    }
+
+    /// Writes `val` into the register `reg`.
+    fn write(&self, reg: u64, val: u64) {
+        unsafe { ((self.base + reg) as *mut u64).write_volatile(val) }
+    }
+
+    /// Busy waits `us` microseconds.
+    pub fn wait_us(&self, us: u64) {
+        let ticks = us * 1_000_000_000 / self.period_fs;
+        let start = self.read(REG_COUNTER);
+        while (self.read(REG_COUNTER).wrapping_sub(start) & self.counter_mask)
+            < ticks
+        {
+            core::hint::spin_loop();
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//! Programmable Interval Timer (PIT).
+//!
+//! The channel 2 of the PIT is used as a reference clock. Its gate and
+//! output are controlled via the port B of the keyboard controller, so it can
+//! be polled without interrupts.
+//!
+//! Reference:
+//! - [OSDev article](https://wiki.osdev.org/Programmable_Interval_Timer)
+
+use cpu::{in8, out8};
+
+/// Frequency of the PIT input clock in Hz.
+const PIT_FREQUENCY: u64 = 1_193_182;
+
+/// Channel 2 data port.
+const PIT_CHANNEL2: u16 = 0x42;
+
+/// Mode/Command register.
+const PIT_COMMAND: u16 = 0x43;
+
+/// Port B of the keyboard controller.
+const PORT_B: u16 = 0x61;
+
+/// Port B bit that enables the gate of the channel 2.
+const PORT_B_GATE2: u8 = 0x01;
+
+/// Port B bit that connects the output of the channel 2 to the speaker.
+const PORT_B_SPEAKER: u8 = 0x02;
+
+/// Port B bit that reflects the output of the channel 2.
+const PORT_B_OUT2: u8 = 0x20;
+
+/// Channel 2, lobyte/hibyte access, mode 0 (interrupt on terminal count),
+/// binary counter.
+const CHANNEL2_MODE0: u8 = 0xb0;
+
+/// Maximum wait supported by `wait_us`, given that the counter is 16-bit.
+pub const MAX_WAIT_US: u64 = 0xffff * 1_000_000 / PIT_FREQUENCY;
+
+/// Busy waits `us` microseconds, up to `MAX_WAIT_US`.
+///
+/// # Safety
+///
+/// The caller must ensure that nothing else is using the channel 2 of the
+/// PIT.
+pub unsafe fn wait_us(us: u64) {
+    let count = (PIT_FREQUENCY * us.min(MAX_WAIT_US) / 1_000_000) as u16;
+
+    // Enable the gate and disconnect the speaker.
+    let port_b = in8(PORT_B);
+    out8(PORT_B, (port_b & !PORT_B_SPEAKER) | PORT_B_GATE2);
+
+    // In mode 0, the output goes high when the count reaches zero.
+    out8(PIT_COMMAND, CHANNEL2_MODE0);
+    out8(PIT_CHANNEL2, count as u8);
+    out8(PIT_CHANNEL2, (count >> 8) as u8);
+    while in8(PORT_B) & PORT_B_OUT2 == 0 {
+        core::hint::spin_loop();
+    }
+
+    out8(PORT_B, port_b);
+}
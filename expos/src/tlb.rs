@@ -0,0 +1,119 @@
+//! TLB shootdown.
+//!
+//! Every CPU caches the translations in its own TLB. After unmapping a page
+//! or reducing its permissions, the stale translations must be invalidated
+//! on all the CPUs that share the page tables, not only on the current one.
+//! The kernel page tables are shared by all the CPUs, so `flush_pages`
+//! invalidates the pages locally and sends an IPI to the rest of the online
+//! CPUs. It waits until all of them have invalidated the pages.
+//!
+//! Only one shootdown is in flight at a time. A CPU waiting to start a
+//! shootdown keeps serving the requests sent to it, so two CPUs starting a
+//! shootdown at the same time do not wait for each other forever.
+//!
+//! Reference:
+//! - Intel SDM Vol. 3A, Section 4.10.5 "Propagation of Paging-Structure
+//!   Changes to Multiple Processors"
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use ticket_mutex::TicketMutex;
+
+use crate::idt::{self, InterruptStackFrame, KernelGs};
+use crate::smp;
+
+/// Size of a page.
+const PAGE_SIZE: u64 = mm::PAGE_SIZE;
+
+/// Number of pages above which the whole TLB is flushed instead of
+/// invalidating the pages one by one.
+const MAX_INVLPG_PAGES: u64 = 32;
+
+/// Serializes the shootdowns.
+static SHOOTDOWN: TicketMutex<()> = TicketMutex::new(());
+
+/// Start of the pages to invalidate.
+static START: AtomicU64 = AtomicU64::new(0);
+
+/// End of the pages to invalidate, exclusive.
+static END: AtomicU64 = AtomicU64::new(0);
+
+/// Pending requests. The bit `n` is set while the CPU `n` has to invalidate
+/// the pages between `START` and `END`. There are at most 64 CPUs.
+static PENDING: AtomicU64 = AtomicU64::new(0);
+
+/// Registers the handler of the shootdown IPIs.
+pub fn init() {
+    idt::set_interrupt_handler(idt::TLB_SHOOTDOWN_VECTOR, shootdown_handler);
+}
+
+/// Invalidates the translations of the pages between `start` and `end`
+/// (exclusive) on all the online CPUs.
+///
+/// # Safety
+///
+/// The interrupts must be enabled on the rest of the online CPUs or they
+/// must start a shootdown themselves, otherwise this function never
+/// returns.
+pub unsafe fn flush_pages(start: u64, end: u64) {
+    let start = start & !(PAGE_SIZE - 1);
+    flush_local(start, end);
+
+    let cpus = smp::cpu_count();
+    if cpus == 1 {
+        return;
+    }
+
+    let _shootdown = SHOOTDOWN.lock_with_idle(serve_request);
+    START.store(start, Ordering::Relaxed);
+    END.store(end, Ordering::Relaxed);
+
+    // The `Release` operation publishes the range to the CPU, which reads
+    // its bit with `Acquire` before the range.
+    let current = smp::cpu_index();
+    for cpu in (0..cpus).filter(|&cpu| cpu != current) {
+        PENDING.fetch_or(1 << cpu, Ordering::Release);
+        if smp::send_ipi(cpu, idt::TLB_SHOOTDOWN_VECTOR).is_err() {
+            PENDING.fetch_and(!(1 << cpu), Ordering::Relaxed);
+        }
+    }
+    while PENDING.load(Ordering::Acquire) != 0 {
+        core::hint::spin_loop();
+    }
+}
+
+/// Invalidates the translations of the pages between `start` and `end`
+/// (exclusive) on the current CPU.
+unsafe fn flush_local(start: u64, end: u64) {
+    if end.saturating_sub(start) / PAGE_SIZE > MAX_INVLPG_PAGES {
+        cpu::flush_tlb();
+        return;
+    }
+    for page in (start..end).step_by(PAGE_SIZE as usize) {
+        cpu::invlpg(page);
+    }
+}
+
+/// Serves the pending request of the current CPU, if any.
+fn serve_request() {
+    let bit = 1 << smp::cpu_index();
+    if PENDING.load(Ordering::Acquire) & bit == 0 {
+        return;
+    }
+
+    let start = START.load(Ordering::Relaxed);
+    let end = END.load(Ordering::Relaxed);
+    // The page tables no longer map the pages.
+    unsafe { flush_local(start, end) };
+
+    // The initiator does not change the range until all the CPUs have
+    // served the request.
+    PENDING.fetch_and(!bit, Ordering::Release);
+}
+
+/// Handler of the shootdown IPIs.
+extern "x86-interrupt" fn shootdown_handler(frame: InterruptStackFrame) {
+    let _gs = KernelGs::enter(&frame);
+    serve_request();
+    lapic::end_of_interrupt();
+}
@@ -0,0 +1,257 @@
+//! User mode.
+//!
+//! Every user task has its own page tables. They share the kernel mappings
+//! with the kernel page tables, whose PML4 entries are copied, and map the
+//! user memory under the PML4 entry of `USER_START`, which is not used by
+//! the kernel. The kernel mappings are not accessible from user mode. The
+//! PML4 entries of the higher half never change (see `paging`), so the
+//! kernel mappings added there later are visible too. Only the devices
+//! identity mapped under new PML4 entries of the lower half after creating
+//! the user page tables are not visible while they are loaded.
+//!
+//! A user program is a flat binary, which is copied to `USER_START` and
+//! runs with its stack at the end of the user memory. The first program is
+//! embedded in the kernel (see `init_program`).
+//!
+//! The kernel runs with the GS base pointing to the per-CPU area. The entry
+//! and exit paths between user mode and the kernel exchange it with the GS
+//! base of user mode using `swapgs` (see `idt::KernelGs`).
+
+use mm::paging::{
+    self, Mapper, PageSize, PageTable, PageTableEntry, PageTableFlags,
+};
+use mm::{PhysAddr, VirtAddr};
+
+use crate::task::{self, TaskId};
+use crate::{gdt, pmm};
+
+global_asm!(include_str!("user/enter.s"), options(att_syntax));
+global_asm!(include_str!("user/init.s"), options(att_syntax));
+
+extern "C" {
+    /// Returns to user mode at `rip` with the stack pointer `rsp` and the
+    /// segment selectors `cs` and `ss`.
+    fn user_enter(rip: u64, rsp: u64, cs: u64, ss: u64) -> !;
+
+    /// Start of the program embedded in the kernel.
+    static user_init_start: u8;
+
+    /// End of the program embedded in the kernel.
+    static user_init_end: u8;
+}
+
+/// Start of the user memory. It is the load address of the programs.
+const USER_START: u64 = 0x0000_4000_0000_0000;
+
+/// End of the user memory. It is the top of the user stack.
+const USER_END: u64 = 0x0000_4080_0000_0000;
+
+/// Index of the PML4 entry that maps the user memory.
+const USER_PML4_INDEX: usize = VirtAddr::new(USER_START).table_index(4);
+
+/// Size of the user stack.
+const USER_STACK_SIZE: u64 = 16 * 1024;
+
+/// Size of a page.
+const PAGE_SIZE: u64 = mm::PAGE_SIZE;
+
+/// User mode errors.
+#[derive(Debug)]
+pub enum Error {
+    /// The program is empty or it does not fit in the user memory.
+    InvalidSize(usize),
+
+    /// There are no free frames for the user page tables.
+    NoMemory,
+
+    /// A user page could not be mapped.
+    Map(paging::Error),
+
+    /// The task could not be created or joined.
+    Task(task::Error),
+}
+
+/// Returns the program embedded in the kernel.
+pub fn init_program() -> &'static [u8] {
+    // The program is between the two symbols.
+    unsafe {
+        let start = &user_init_start as *const u8;
+        let end = &user_init_end as *const u8;
+        core::slice::from_raw_parts(start, end as usize - start as usize)
+    }
+}
+
+/// Creates a task called `name` that runs `program` in user mode.
+pub fn spawn(name: &'static str, program: &[u8]) -> Result<TaskId, Error> {
+    // The tasks run on page tables that map the physical memory at the
+    // offset of the kernel page tables.
+    let pml4 = unsafe { new_page_tables(program)? };
+    task::spawn(name, user_task, pml4.as_u64() as usize).map_err(|err| {
+        // The task was not created, so the page tables are not in use.
+        unsafe { free_page_tables(pml4) };
+        Error::Task(err)
+    })
+}
+
+/// Entry point of the user tasks. It switches to the user page tables whose
+/// PML4 is at `pml4` and enters user mode at the start of the program.
+fn user_task(pml4: usize) -> i32 {
+    // The page tables were created by `spawn` for this task.
+    unsafe {
+        task::set_user_page_tables(PhysAddr::new(pml4 as u64));
+        user_enter(
+            USER_START,
+            USER_END,
+            u64::from(gdt::USER_CODE_SELECTOR),
+            u64::from(gdt::USER_DATA_SELECTOR),
+        )
+    }
+}
+
+/// Returns new user page tables with `program` mapped at `USER_START` and
+/// the user stack mapped below `USER_END`.
+///
+/// # Safety
+///
+/// The current page tables must map the physical memory at
+/// `mm::phys_offset()`.
+unsafe fn new_page_tables(program: &[u8]) -> Result<PhysAddr, Error> {
+    let size = program.len() as u64;
+    if size == 0 || size > USER_END - USER_STACK_SIZE - USER_START {
+        return Err(Error::InvalidSize(program.len()));
+    }
+
+    // Share the kernel mappings. The user memory of the current page tables
+    // is skipped, in case they belong to another user task.
+    let current = crate::paging::current_mapper().pml4();
+    let mut table =
+        (mm::phys_to_virt(current).as_u64() as *const PageTable).read();
+    table[USER_PML4_INDEX] = PageTableEntry::UNUSED;
+
+    let pml4 = pmm::alloc_frame().ok_or(Error::NoMemory)?;
+    (mm::phys_to_virt(pml4).as_u64() as *mut PageTable).write(table);
+
+    let mut mapper = Mapper::new(pml4, mm::phys_offset());
+    let result = map_program(&mut mapper, program)
+        .and_then(|()| map_stack(&mut mapper));
+    if let Err(err) = result {
+        free_page_tables(pml4);
+        return Err(err);
+    }
+    Ok(pml4)
+}
+
+/// Maps `program` at `USER_START`. The pages are executable and not
+/// writable.
+///
+/// # Safety
+///
+/// `mapper` must manage user page tables.
+unsafe fn map_program(
+    mapper: &mut Mapper,
+    program: &[u8],
+) -> Result<(), Error> {
+    for (n, chunk) in program.chunks(PAGE_SIZE as usize).enumerate() {
+        let page = USER_START + n as u64 * PAGE_SIZE;
+        let frame = map_page(mapper, page, PageTableFlags::USER)?;
+        let frame = mm::phys_to_virt(frame).as_u64() as *mut u8;
+        frame.copy_from_nonoverlapping(chunk.as_ptr(), chunk.len());
+    }
+    Ok(())
+}
+
+/// Maps the user stack below `USER_END`. The pages are writable and not
+/// executable.
+///
+/// # Safety
+///
+/// `mapper` must manage user page tables.
+unsafe fn map_stack(mapper: &mut Mapper) -> Result<(), Error> {
+    let flags = crate::paging::data_flags() | PageTableFlags::USER;
+    for page in
+        (USER_END - USER_STACK_SIZE..USER_END).step_by(PAGE_SIZE as usize)
+    {
+        map_page(mapper, page, flags)?;
+    }
+    Ok(())
+}
+
+/// Maps a zeroed frame at the user page `page` with the flags `flags` and
+/// returns the physical address of the frame.
+///
+/// # Safety
+///
+/// `mapper` must manage user page tables.
+unsafe fn map_page(
+    mapper: &mut Mapper,
+    page: u64,
+    flags: PageTableFlags,
+) -> Result<PhysAddr, Error> {
+    let frame = pmm::alloc_frame().ok_or(Error::NoMemory)?;
+    (mm::phys_to_virt(frame).as_u64() as *mut u8)
+        .write_bytes(0, PAGE_SIZE as usize);
+    mapper
+        .map_to(
+            VirtAddr::new(page),
+            frame,
+            PageSize::Size4KiB,
+            flags,
+            &mut pmm::PageTableFrames,
+        )
+        .map_err(|err| {
+            pmm::free_frame(frame).ok();
+            Error::Map(err)
+        })?;
+    Ok(frame)
+}
+
+/// Frees the user page tables whose PML4 is at `pml4`, including the frames
+/// of the user memory.
+///
+/// # Safety
+///
+/// The page tables must have been created by `spawn` and they must not be
+/// in use.
+pub unsafe fn free_page_tables(pml4: PhysAddr) {
+    let table = &*(mm::phys_to_virt(pml4).as_u64() as *const PageTable);
+    let entry = table[USER_PML4_INDEX];
+    if !entry.is_unused() {
+        free_table(entry.addr(), 3);
+    }
+    pmm::free_frame(pml4).ok();
+}
+
+/// Frees the page table at `table`, whose level is `level`, the page tables
+/// under it and the frames mapped by them. The user memory is only mapped
+/// with 4KiB pages.
+unsafe fn free_table(table: PhysAddr, level: usize) {
+    let entries = &*(mm::phys_to_virt(table).as_u64() as *const PageTable);
+    for entry in entries.iter().filter(|entry| !entry.is_unused()) {
+        if level > 1 {
+            free_table(entry.addr(), level - 1);
+        } else {
+            pmm::free_frame(entry.addr()).ok();
+        }
+    }
+    pmm::free_frame(table).ok();
+}
+
+/// Returns the `len` bytes of user memory at `addr`, or `None` if they are
+/// not mapped in the user page tables of the current task.
+///
+/// # Safety
+///
+/// The returned slice must not be used once the current task exits.
+pub unsafe fn slice<'a>(addr: u64, len: u64) -> Option<&'a [u8]> {
+    let end = addr.checked_add(len)?;
+    if addr < USER_START || end > USER_END {
+        return None;
+    }
+
+    let mapper = crate::paging::current_mapper();
+    let mut pages = (addr & !(PAGE_SIZE - 1)..end).step_by(PAGE_SIZE as usize);
+    if pages.any(|page| mapper.translate(VirtAddr::new(page)).is_none()) {
+        return None;
+    }
+    Some(core::slice::from_raw_parts(addr as *const u8, len as usize))
+}
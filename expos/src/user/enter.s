@@ -0,0 +1,37 @@
+/*
+ * Entry to user mode.
+ *
+ * user_enter(rip: u64, rsp: u64, cs: u64, ss: u64) returns to user mode at
+ * rip, with the stack pointer rsp and the segment selectors cs and ss. The
+ * interrupts are enabled in user mode and the general-purpose registers are
+ * cleared, so no kernel data is leaked. The GS base of user mode is loaded
+ * with swapgs, which keeps the kernel GS base in IA32_KERNEL_GS_BASE.
+ */
+
+	.global user_enter
+
+	.code64
+user_enter:
+	pushq %rcx
+	pushq %rsi
+	pushq $0x202
+	pushq %rdx
+	pushq %rdi
+
+	xorl %eax, %eax
+	xorl %ebx, %ebx
+	xorl %ecx, %ecx
+	xorl %edx, %edx
+	xorl %esi, %esi
+	xorl %edi, %edi
+	xorl %ebp, %ebp
+	xorl %r8d, %r8d
+	xorl %r9d, %r9d
+	xorl %r10d, %r10d
+	xorl %r11d, %r11d
+	xorl %r12d, %r12d
+	xorl %r13d, %r13d
+	xorl %r14d, %r14d
+	xorl %r15d, %r15d
+	swapgs
+	iretq
@@ -0,0 +1,42 @@
+/*
+ * First user program.
+ *
+ * It is embedded in the kernel and copied to the user page tables of its
+ * task, so it must be position independent. It writes a message three
+ * times, yielding the CPU after every write, and exits with status 0.
+ */
+
+	.pushsection .rodata.user_init, "a"
+	.global user_init_start
+	.global user_init_end
+
+	.code64
+user_init_start:
+	movl $3, %ebx
+
+1:
+	/* write(1, message, message_len) */
+	movl $0, %eax
+	movl $1, %edi
+	leaq message(%rip), %rsi
+	movl $(message_end - message), %edx
+	syscall
+
+	/* yield() */
+	movl $2, %eax
+	syscall
+
+	decl %ebx
+	jnz 1b
+
+	/* exit(0) */
+	movl $1, %eax
+	xorl %edi, %edi
+	syscall
+	ud2
+
+message:
+	.ascii "init: hello from user mode\n"
+message_end:
+user_init_end:
+	.popsection
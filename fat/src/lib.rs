@@ -0,0 +1,1299 @@
+//! Read-only FAT filesystem.
+//!
+//! FAT16 and FAT32 volumes are read on top of any `BlockDevice`, so the
+//! kernel can read the files of the EFI System Partition after exiting the
+//! boot services. The volume must start at the first block of the device.
+//!
+//! The directories are iterated with their long file names (VFAT) and the
+//! names are matched ignoring the ASCII case, like the FAT drivers of the
+//! firmware. Nothing is cached: every access reads the sectors it needs into
+//! a buffer on the stack, so no heap is required.
+//!
+//! Reference:
+//! - Microsoft FAT32 File System Specification, Version 1.03
+//! - [OSDev article](https://wiki.osdev.org/FAT)
+
+#![no_std]
+
+use core::fmt;
+
+use block::BlockDevice;
+
+/// Signature at the end of the boot sector.
+const BOOT_SIGNATURE: [u8; 2] = [0x55, 0xaa];
+
+/// Offset of the boot sector signature.
+const BOOT_SIGNATURE_OFFSET: usize = 510;
+
+/// Minimum size of a sector.
+const MIN_SECTOR_SIZE: usize = 512;
+
+/// Maximum size of a sector.
+const MAX_SECTOR_SIZE: usize = 4096;
+
+/// Size of a directory entry.
+const DIR_ENTRY_SIZE: usize = 32;
+
+/// Minimum number of clusters of a FAT16 volume. Smaller volumes are FAT12.
+const MIN_FAT16_CLUSTERS: u32 = 4085;
+
+/// Number of the first data cluster.
+const FIRST_CLUSTER: u32 = 2;
+
+/// FAT16 entries equal or greater than this value mark the end of a chain.
+const FAT16_EOC: u32 = 0xfff8;
+
+/// FAT32 entries equal or greater than this value mark the end of a chain.
+const FAT32_EOC: u32 = 0x0fff_fff8;
+
+/// Mask of the cluster number in a FAT32 entry. The top 4 bits are
+/// reserved.
+const FAT32_MASK: u32 = 0x0fff_ffff;
+
+/// First byte of the entry that marks the end of a directory.
+const ENTRY_END: u8 = 0x00;
+
+/// First byte of the deleted entries.
+const ENTRY_DELETED: u8 = 0xe5;
+
+/// First byte of the entries whose name starts with 0xe5.
+const ENTRY_E5: u8 = 0x05;
+
+/// Attributes of the long name entries.
+const ATTR_LONG_NAME: u8 = 0x0f;
+
+/// Mask of the attributes checked to identify the long name entries.
+const ATTR_LONG_NAME_MASK: u8 = 0x3f;
+
+/// Sequence number flag of the last long name entry of a name, which is
+/// the first one in the directory.
+const LFN_LAST: u8 = 0x40;
+
+/// Mask of the sequence number of a long name entry.
+const LFN_SEQ_MASK: u8 = 0x1f;
+
+/// Maximum number of long name entries of a name.
+const MAX_LFN_ENTRIES: usize = 20;
+
+/// Offsets of the UCS-2 characters in a long name entry.
+const LFN_OFFSETS: [usize; 13] =
+    [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+
+/// Flag of the reserved byte set if the base of a short name is lowercase.
+const NT_LOWER_BASE: u8 = 0x08;
+
+/// Flag of the reserved byte set if the extension of a short name is
+/// lowercase.
+const NT_LOWER_EXT: u8 = 0x10;
+
+/// Maximum length of a short name with the dot.
+const SHORT_NAME_LEN: usize = 12;
+
+/// Maximum length in bytes of a name encoded as UTF-8.
+pub const MAX_NAME_LEN: usize = 255 * 3;
+
+/// Attribute of the read-only files.
+pub const ATTR_READ_ONLY: u8 = 0x01;
+
+/// Attribute of the hidden files.
+pub const ATTR_HIDDEN: u8 = 0x02;
+
+/// Attribute of the system files.
+pub const ATTR_SYSTEM: u8 = 0x04;
+
+/// Attribute of the volume label entry.
+pub const ATTR_VOLUME_ID: u8 = 0x08;
+
+/// Attribute of the directories.
+pub const ATTR_DIRECTORY: u8 = 0x10;
+
+/// Attribute of the files modified since the last backup.
+pub const ATTR_ARCHIVE: u8 = 0x20;
+
+/// FAT errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The device failed to read the sectors.
+    Device(block::Error),
+
+    /// The block size of the device is not supported.
+    UnsupportedBlockSize(usize),
+
+    /// The sector size of the volume is not supported or it is not a
+    /// multiple of the block size of the device.
+    UnsupportedSectorSize(usize),
+
+    /// The boot sector is not a valid FAT boot sector or the volume does not
+    /// fit in the device.
+    InvalidBootSector,
+
+    /// The volume is FAT12, which is not supported.
+    Fat12,
+
+    /// The cluster is free, bad or out of range, or a cluster chain is
+    /// shorter than expected or loops.
+    InvalidCluster(u32),
+
+    /// The file does not exist.
+    NotFound,
+
+    /// A component of the path is not a directory.
+    NotADirectory,
+
+    /// The entry is a directory.
+    IsADirectory,
+}
+
+/// FAT variant of a volume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Fat16,
+    Fat32,
+}
+
+/// Represents a FAT volume.
+pub struct FileSystem<'a> {
+    /// Device that contains the volume.
+    device: &'a dyn BlockDevice,
+
+    /// FAT variant.
+    kind: Kind,
+
+    /// Size of a sector in bytes.
+    sector_size: usize,
+
+    /// Number of device blocks per sector.
+    blocks_per_sector: u64,
+
+    /// Number of sectors per cluster.
+    sectors_per_cluster: u32,
+
+    /// First sector of the first FAT.
+    fat_start: u64,
+
+    /// First sector of the FAT16 root directory.
+    root_start: u64,
+
+    /// Number of sectors of the FAT16 root directory. It is zero on FAT32.
+    root_sectors: u64,
+
+    /// First sector of the data region.
+    data_start: u64,
+
+    /// Number of data clusters.
+    clusters: u32,
+
+    /// First cluster of the FAT32 root directory. It is zero on FAT16.
+    root_cluster: u32,
+}
+
+impl<'a> FileSystem<'a> {
+    /// Returns a `FileSystem` that reads the FAT volume stored in `device`.
+    /// The volume is FAT32 if the BPB has no 16-bit FAT size, like Linux
+    /// does, so small FAT32 volumes are accepted.
+    pub fn new(device: &'a dyn BlockDevice) -> Result<Self, Error> {
+        let block_size = device.block_size();
+        if block_size == 0 || block_size > MAX_SECTOR_SIZE {
+            return Err(Error::UnsupportedBlockSize(block_size));
+        }
+
+        // The BPB is in the first 512 bytes, whatever the sector size is.
+        let mut buf = [0; MAX_SECTOR_SIZE];
+        let len = (MIN_SECTOR_SIZE + block_size - 1) / block_size * block_size;
+        device.read(0, &mut buf[..len]).map_err(Error::Device)?;
+        let bpb = &buf[..MIN_SECTOR_SIZE];
+        if bpb[BOOT_SIGNATURE_OFFSET..] != BOOT_SIGNATURE {
+            return Err(Error::InvalidBootSector);
+        }
+
+        let sector_size = usize::from(read_u16(bpb, 11));
+        if !sector_size.is_power_of_two()
+            || !(MIN_SECTOR_SIZE..=MAX_SECTOR_SIZE).contains(&sector_size)
+            || sector_size % block_size != 0
+        {
+            return Err(Error::UnsupportedSectorSize(sector_size));
+        }
+        let sectors_per_cluster = u32::from(bpb[13]);
+        let reserved_sectors = u64::from(read_u16(bpb, 14));
+        let fats = u64::from(bpb[16]);
+        let root_entries = u64::from(read_u16(bpb, 17));
+        let total_sectors = match read_u16(bpb, 19) {
+            0 => u64::from(read_u32(bpb, 32)),
+            sectors => u64::from(sectors),
+        };
+        let (kind, fat_size) = match read_u16(bpb, 22) {
+            0 => (Kind::Fat32, u64::from(read_u32(bpb, 36))),
+            sectors => (Kind::Fat16, u64::from(sectors)),
+        };
+        if !sectors_per_cluster.is_power_of_two()
+            || reserved_sectors == 0
+            || fats == 0
+            || fat_size == 0
+            || (kind == Kind::Fat32 && root_entries != 0)
+        {
+            return Err(Error::InvalidBootSector);
+        }
+
+        let sector_bytes = sector_size as u64;
+        let root_sectors =
+            (root_entries * DIR_ENTRY_SIZE as u64 + sector_bytes - 1)
+                / sector_bytes;
+        let fat_start = reserved_sectors;
+        let root_start = fat_start + fats * fat_size;
+        let data_start = root_start + root_sectors;
+        let data_sectors = total_sectors
+            .checked_sub(data_start)
+            .ok_or(Error::InvalidBootSector)?;
+        let clusters = (data_sectors / u64::from(sectors_per_cluster)) as u32;
+        if kind == Kind::Fat16 && clusters < MIN_FAT16_CLUSTERS {
+            return Err(Error::Fat12);
+        }
+
+        // Every cluster must have an entry in the FAT and every sector must
+        // be in the device.
+        let entry_size = match kind {
+            Kind::Fat16 => 2,
+            Kind::Fat32 => 4,
+        };
+        let blocks_per_sector = (sector_size / block_size) as u64;
+        if u64::from(clusters) + u64::from(FIRST_CLUSTER)
+            > fat_size * sector_bytes / entry_size
+            || total_sectors * blocks_per_sector > device.blocks()
+        {
+            return Err(Error::InvalidBootSector);
+        }
+
+        let fs = FileSystem {
+            device,
+            kind,
+            sector_size,
+            blocks_per_sector,
+            sectors_per_cluster,
+            fat_start,
+            root_start,
+            root_sectors,
+            data_start,
+            clusters,
+            root_cluster: match kind {
+                Kind::Fat16 => 0,
+                Kind::Fat32 => read_u32(bpb, 44),
+            },
+        };
+        if kind == Kind::Fat32 {
+            fs.check_cluster(fs.root_cluster)?;
+        }
+        Ok(fs)
+    }
+
+    /// Returns the FAT variant of the volume.
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    /// Returns the root directory.
+    pub fn root(&self) -> Dir<'_> {
+        Dir {
+            fs: self,
+            start: self.root_location(),
+        }
+    }
+
+    /// Returns the entry at `path`, whose components are separated by `/`.
+    /// The empty path and `/` refer to the root directory.
+    pub fn open(&self, path: &str) -> Result<DirEntry, Error> {
+        let mut entry = DirEntry::root();
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            entry = self.dir(&entry)?.find(name)?;
+        }
+        Ok(entry)
+    }
+
+    /// Returns the directory described by `entry`.
+    pub fn dir(&self, entry: &DirEntry) -> Result<Dir<'_>, Error> {
+        if !entry.is_dir() {
+            return Err(Error::NotADirectory);
+        }
+
+        // The entry ".." of the subdirectories of the root directory points
+        // to the cluster 0.
+        let start = match entry.cluster {
+            0 => self.root_location(),
+            cluster => {
+                self.check_cluster(cluster)?;
+                Location::Cluster(cluster)
+            }
+        };
+        Ok(Dir { fs: self, start })
+    }
+
+    /// Returns the file described by `entry`.
+    pub fn file(&self, entry: &DirEntry) -> Result<File<'_>, Error> {
+        if entry.is_dir() {
+            return Err(Error::IsADirectory);
+        }
+        Ok(File {
+            fs: self,
+            cluster: entry.cluster,
+            size: entry.size(),
+        })
+    }
+
+    /// Returns the location of the root directory.
+    fn root_location(&self) -> Location {
+        match self.kind {
+            Kind::Fat16 => Location::Root,
+            Kind::Fat32 => Location::Cluster(self.root_cluster),
+        }
+    }
+
+    /// Returns the size of a cluster in bytes.
+    fn cluster_size(&self) -> u64 {
+        self.sector_size as u64 * u64::from(self.sectors_per_cluster)
+    }
+
+    /// Returns the first sector of the data cluster `cluster`.
+    fn cluster_sector(&self, cluster: u32) -> u64 {
+        self.data_start
+            + u64::from(cluster - FIRST_CLUSTER)
+                * u64::from(self.sectors_per_cluster)
+    }
+
+    /// Checks that `cluster` is a data cluster of the volume.
+    fn check_cluster(&self, cluster: u32) -> Result<(), Error> {
+        if cluster < FIRST_CLUSTER
+            || u64::from(cluster)
+                >= u64::from(self.clusters) + u64::from(FIRST_CLUSTER)
+        {
+            return Err(Error::InvalidCluster(cluster));
+        }
+        Ok(())
+    }
+
+    /// Returns the cluster after `cluster` in its chain, or `None` if it is
+    /// the last one.
+    fn next_cluster(&self, cluster: u32) -> Result<Option<u32>, Error> {
+        self.check_cluster(cluster)?;
+
+        let entry_size = match self.kind {
+            Kind::Fat16 => 2,
+            Kind::Fat32 => 4,
+        };
+        let offset = u64::from(cluster) * entry_size;
+        let sector = self.fat_start + offset / self.sector_size as u64;
+        let offset = (offset % self.sector_size as u64) as usize;
+
+        // The entries never cross a sector boundary.
+        let mut buf = [0; MAX_SECTOR_SIZE];
+        let buf = &mut buf[..self.sector_size];
+        self.read_sector(sector, buf)?;
+        let (next, eoc) = match self.kind {
+            Kind::Fat16 => (u32::from(read_u16(buf, offset)), FAT16_EOC),
+            Kind::Fat32 => (read_u32(buf, offset) & FAT32_MASK, FAT32_EOC),
+        };
+        if next >= eoc {
+            return Ok(None);
+        }
+        self.check_cluster(next)?;
+        Ok(Some(next))
+    }
+
+    /// Reads the sector `sector` of the volume into `buf`, whose length must
+    /// be the sector size.
+    fn read_sector(&self, sector: u64, buf: &mut [u8]) -> Result<(), Error> {
+        self.device
+            .read(sector * self.blocks_per_sector, buf)
+            .map_err(Error::Device)
+    }
+}
+
+/// Location of the entries of a directory.
+#[derive(Debug, Clone, Copy)]
+enum Location {
+    /// FAT16 root directory, which is stored in its own region.
+    Root,
+
+    /// Directory stored in the cluster chain starting at the provided
+    /// cluster.
+    Cluster(u32),
+}
+
+/// Represents a directory.
+pub struct Dir<'a> {
+    fs: &'a FileSystem<'a>,
+    start: Location,
+}
+
+impl<'a> Dir<'a> {
+    /// Returns an iterator over the entries of the directory, including `.`
+    /// and `..`. The deleted entries and the volume label are skipped. The
+    /// iteration ends after the first error.
+    pub fn entries(&self) -> Entries<'a> {
+        Entries {
+            fs: self.fs,
+            location: self.start,
+            sector: 0,
+            index: 0,
+            buf: [0; MAX_SECTOR_SIZE],
+            loaded: false,
+            visited: 0,
+            done: false,
+            long_name: LongName::new(),
+        }
+    }
+
+    /// Returns the entry called `name`. The long and the short names are
+    /// matched ignoring the ASCII case.
+    pub fn find(&self, name: &str) -> Result<DirEntry, Error> {
+        for entry in self.entries() {
+            let entry = entry?;
+            if entry.matches(name) {
+                return Ok(entry);
+            }
+        }
+        Err(Error::NotFound)
+    }
+}
+
+/// Iterator over the entries of a directory.
+pub struct Entries<'a> {
+    fs: &'a FileSystem<'a>,
+
+    /// Location of the current sector.
+    location: Location,
+
+    /// Index of the current sector in the root directory or in the current
+    /// cluster.
+    sector: u64,
+
+    /// Index of the next entry in the current sector.
+    index: usize,
+
+    /// Current sector.
+    buf: [u8; MAX_SECTOR_SIZE],
+
+    /// `true` if `buf` contains the current sector.
+    loaded: bool,
+
+    /// Number of clusters visited after the first one. It is used to detect
+    /// loops in the cluster chain.
+    visited: u32,
+
+    /// `true` if the iteration ended.
+    done: bool,
+
+    /// Long name of the next short name entry.
+    long_name: LongName,
+}
+
+impl Entries<'_> {
+    /// Returns the next entry, processing the long name entries found
+    /// before it.
+    fn next_entry(&mut self) -> Result<Option<DirEntry>, Error> {
+        while let Some(raw) = self.next_raw()? {
+            match raw[0] {
+                ENTRY_END => return Ok(None),
+                ENTRY_DELETED => {
+                    self.long_name.reset();
+                    continue;
+                }
+                _ => {}
+            }
+
+            let attributes = raw[11];
+            if attributes & ATTR_LONG_NAME_MASK == ATTR_LONG_NAME {
+                self.long_name.push(&raw);
+                continue;
+            }
+            if attributes & ATTR_VOLUME_ID != 0 {
+                self.long_name.reset();
+                continue;
+            }
+
+            let long_name = self.long_name.get(checksum(&raw));
+            let entry = DirEntry::new(&raw, long_name);
+            self.long_name.reset();
+            return Ok(Some(entry));
+        }
+        Ok(None)
+    }
+
+    /// Returns the next raw directory entry, following the cluster chain.
+    fn next_raw(&mut self) -> Result<Option<[u8; DIR_ENTRY_SIZE]>, Error> {
+        let fs = self.fs;
+
+        if self.index == fs.sector_size / DIR_ENTRY_SIZE {
+            self.index = 0;
+            self.sector += 1;
+            self.loaded = false;
+        }
+
+        let first_sector = match self.location {
+            Location::Root => {
+                if self.sector == fs.root_sectors {
+                    return Ok(None);
+                }
+                fs.root_start
+            }
+            Location::Cluster(mut cluster) => {
+                if self.sector == u64::from(fs.sectors_per_cluster) {
+                    cluster = match fs.next_cluster(cluster)? {
+                        Some(next) => next,
+                        None => return Ok(None),
+                    };
+                    self.visited += 1;
+                    if self.visited > fs.clusters {
+                        return Err(Error::InvalidCluster(cluster));
+                    }
+                    self.location = Location::Cluster(cluster);
+                    self.sector = 0;
+                }
+                fs.cluster_sector(cluster)
+            }
+        };
+
+        let buf = &mut self.buf[..fs.sector_size];
+        if !self.loaded {
+            fs.read_sector(first_sector + self.sector, buf)?;
+            self.loaded = true;
+        }
+
+        let offset = self.index * DIR_ENTRY_SIZE;
+        self.index += 1;
+        let mut raw = [0; DIR_ENTRY_SIZE];
+        raw.copy_from_slice(&buf[offset..offset + DIR_ENTRY_SIZE]);
+        Ok(Some(raw))
+    }
+}
+
+impl Iterator for Entries<'_> {
+    type Item = Result<DirEntry, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.next_entry() {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Long name assembled from the long name entries that precede a short
+/// name entry.
+struct LongName {
+    /// UCS-2 characters of the name, padded with 0x0000 and 0xffff.
+    chars: [u16; MAX_LFN_ENTRIES * LFN_OFFSETS.len()],
+
+    /// Number of characters stored in `chars`.
+    len: usize,
+
+    /// Sequence number of the next expected entry. It is `None` if no name
+    /// is being assembled and `Some(0)` if the name is complete.
+    next: Option<u8>,
+
+    /// Checksum of the short name the long name belongs to.
+    checksum: u8,
+}
+
+impl LongName {
+    /// Returns an empty `LongName`.
+    fn new() -> Self {
+        LongName {
+            chars: [0; MAX_LFN_ENTRIES * LFN_OFFSETS.len()],
+            len: 0,
+            next: None,
+            checksum: 0,
+        }
+    }
+
+    /// Discards the name.
+    fn reset(&mut self) {
+        self.next = None;
+    }
+
+    /// Adds the long name entry `raw`. The entries must be found in
+    /// descending sequence order with the same checksum, otherwise the
+    /// name is discarded.
+    fn push(&mut self, raw: &[u8; DIR_ENTRY_SIZE]) {
+        let seq = raw[0] & LFN_SEQ_MASK;
+        if raw[0] & LFN_LAST != 0 {
+            if seq == 0 || usize::from(seq) > MAX_LFN_ENTRIES {
+                self.reset();
+                return;
+            }
+            self.len = usize::from(seq) * LFN_OFFSETS.len();
+            self.next = Some(seq);
+            self.checksum = raw[13];
+        }
+        if seq == 0 || self.next != Some(seq) || raw[13] != self.checksum {
+            self.reset();
+            return;
+        }
+
+        let start = usize::from(seq - 1) * LFN_OFFSETS.len();
+        for (i, &offset) in LFN_OFFSETS.iter().enumerate() {
+            self.chars[start + i] = read_u16(raw, offset);
+        }
+        self.next = Some(seq - 1);
+    }
+
+    /// Returns the characters of the name if it is complete and it belongs
+    /// to the short name with the checksum `checksum`.
+    fn get(&self, checksum: u8) -> Option<&[u16]> {
+        if self.next != Some(0) || self.checksum != checksum {
+            return None;
+        }
+        let chars = &self.chars[..self.len];
+        let len = chars.iter().position(|&c| c == 0).unwrap_or(chars.len());
+        Some(&chars[..len])
+    }
+}
+
+/// Represents a directory entry.
+#[derive(Clone)]
+pub struct DirEntry {
+    /// Long name, or the short name if there is no long name, encoded as
+    /// UTF-8.
+    name: [u8; MAX_NAME_LEN],
+    name_len: usize,
+
+    /// Short name with the dot.
+    short_name: [u8; SHORT_NAME_LEN],
+    short_name_len: usize,
+
+    /// Attributes (e.g. `ATTR_DIRECTORY`).
+    attributes: u8,
+
+    /// First cluster of the data. It is zero if the file is empty or the
+    /// entry refers to the root directory.
+    cluster: u32,
+
+    /// Size of the file in bytes. It is zero for the directories.
+    size: u32,
+}
+
+impl DirEntry {
+    /// Returns a `DirEntry` from the short name entry `raw` and the long name
+    /// `long_name`. The bytes outside ASCII of the short name, which depend
+    /// on the OEM code page, are replaced by `?`.
+    fn new(raw: &[u8; DIR_ENTRY_SIZE], long_name: Option<&[u16]>) -> Self {
+        let mut entry = DirEntry {
+            name: [0; MAX_NAME_LEN],
+            name_len: 0,
+            short_name: [0; SHORT_NAME_LEN],
+            short_name_len: 0,
+            attributes: raw[11],
+            cluster: u32::from(read_u16(raw, 20)) << 16
+                | u32::from(read_u16(raw, 26)),
+            size: read_u32(raw, 28),
+        };
+
+        let base = trim_spaces(&raw[..8]);
+        let ext = trim_spaces(&raw[8..11]);
+        let mut push = |byte: u8, lower: bool| {
+            let byte = match byte {
+                b if !b.is_ascii() => b'?',
+                b if lower => b.to_ascii_lowercase(),
+                b => b,
+            };
+            entry.short_name[entry.short_name_len] = byte;
+            entry.short_name_len += 1;
+        };
+        for (i, &byte) in base.iter().enumerate() {
+            let byte = if i == 0 && byte == ENTRY_E5 {
+                ENTRY_DELETED
+            } else {
+                byte
+            };
+            push(byte, raw[12] & NT_LOWER_BASE != 0);
+        }
+        if !ext.is_empty() {
+            push(b'.', false);
+            for &byte in ext {
+                push(byte, raw[12] & NT_LOWER_EXT != 0);
+            }
+        }
+
+        match long_name {
+            Some(chars) => {
+                for c in core::char::decode_utf16(chars.iter().copied()) {
+                    let c = c.unwrap_or(core::char::REPLACEMENT_CHARACTER);
+                    let start = entry.name_len;
+                    if start + c.len_utf8() > MAX_NAME_LEN {
+                        break;
+                    }
+                    entry.name_len +=
+                        c.encode_utf8(&mut entry.name[start..]).len();
+                }
+            }
+            None => {
+                let len = entry.short_name_len;
+                entry.name[..len].copy_from_slice(&entry.short_name[..len]);
+                entry.name_len = len;
+            }
+        }
+        entry
+    }
+
+    /// Returns the entry of the root directory, which has no entry in the
+    /// volume.
+    fn root() -> Self {
+        DirEntry {
+            name: [0; MAX_NAME_LEN],
+            name_len: 0,
+            short_name: [0; SHORT_NAME_LEN],
+            short_name_len: 0,
+            attributes: ATTR_DIRECTORY,
+            cluster: 0,
+            size: 0,
+        }
+    }
+
+    /// Returns the name of the entry. It is the long name if the entry has
+    /// one.
+    pub fn name(&self) -> &str {
+        // The name is built from `char`s and ASCII bytes.
+        core::str::from_utf8(&self.name[..self.name_len]).unwrap_or("")
+    }
+
+    /// Returns the short (8.3) name of the entry.
+    pub fn short_name(&self) -> &str {
+        // The short name is built from ASCII bytes.
+        core::str::from_utf8(&self.short_name[..self.short_name_len])
+            .unwrap_or("")
+    }
+
+    /// Returns the attributes of the entry (e.g. `ATTR_DIRECTORY`).
+    pub fn attributes(&self) -> u8 {
+        self.attributes
+    }
+
+    /// Returns `true` if the entry is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.attributes & ATTR_DIRECTORY != 0
+    }
+
+    /// Returns the size of the file in bytes.
+    pub fn size(&self) -> u64 {
+        u64::from(self.size)
+    }
+
+    /// Returns `true` if the long or the short name of the entry is `name`,
+    /// ignoring the ASCII case.
+    fn matches(&self, name: &str) -> bool {
+        self.name().eq_ignore_ascii_case(name)
+            || self.short_name().eq_ignore_ascii_case(name)
+    }
+}
+
+impl fmt::Debug for DirEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DirEntry")
+            .field("name", &self.name())
+            .field("short_name", &self.short_name())
+            .field("attributes", &self.attributes)
+            .field("cluster", &self.cluster)
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
+/// Represents a regular file.
+pub struct File<'a> {
+    fs: &'a FileSystem<'a>,
+
+    /// First cluster of the data.
+    cluster: u32,
+
+    /// Size of the file in bytes.
+    size: u64,
+}
+
+impl File<'_> {
+    /// Returns the size of the file in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Reads the file starting at `offset` into `buf` and returns the number
+    /// of bytes read, which is smaller than the length of `buf` if the end
+    /// of the file is reached.
+    pub fn read_at(
+        &self,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Result<usize, Error> {
+        if offset >= self.size {
+            return Ok(0);
+        }
+        let len = (self.size - offset).min(buf.len() as u64) as usize;
+        let fs = self.fs;
+        let sector_size = fs.sector_size;
+        let cluster_size = fs.cluster_size();
+
+        fs.check_cluster(self.cluster)?;
+        let mut cluster = self.cluster;
+        for _ in 0..offset / cluster_size {
+            cluster = fs
+                .next_cluster(cluster)?
+                .ok_or(Error::InvalidCluster(cluster))?;
+        }
+
+        // The whole sectors are read directly into `buf`.
+        let mut sector_buf = [0; MAX_SECTOR_SIZE];
+        let sector_buf = &mut sector_buf[..sector_size];
+        let mut pos = offset % cluster_size;
+        let mut done = 0;
+        while done < len {
+            if pos == cluster_size {
+                cluster = fs
+                    .next_cluster(cluster)?
+                    .ok_or(Error::InvalidCluster(cluster))?;
+                pos = 0;
+            }
+
+            let sector = fs.cluster_sector(cluster) + pos / sector_size as u64;
+            let start = (pos % sector_size as u64) as usize;
+            let n = (sector_size - start).min(len - done);
+            if n == sector_size {
+                fs.read_sector(sector, &mut buf[done..done + n])?;
+            } else {
+                fs.read_sector(sector, sector_buf)?;
+                buf[done..done + n]
+                    .copy_from_slice(&sector_buf[start..start + n]);
+            }
+            done += n;
+            pos += n as u64;
+        }
+        Ok(len)
+    }
+}
+
+/// Computes the checksum of the short name of the directory entry `raw`,
+/// which is stored in its long name entries.
+fn checksum(raw: &[u8; DIR_ENTRY_SIZE]) -> u8 {
+    raw[..11]
+        .iter()
+        .fold(0u8, |sum, &b| sum.rotate_right(1).wrapping_add(b))
+}
+
+/// Returns `bytes` without the trailing spaces.
+fn trim_spaces(bytes: &[u8]) -> &[u8] {
+    let len = bytes.iter().rposition(|&b| b != b' ').map_or(0, |i| i + 1);
+    &bytes[..len]
+}
+
+/// Reads a little-endian `u16` at `offset`.
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+/// Reads a little-endian `u32` at `offset`.
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    let mut buf = [0; 4];
+    buf.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::string::String;
+    use std::vec::Vec;
+
+    use block::RamDisk;
+
+    use super::*;
+
+    /// Sector size of the test images. The clusters have one sector.
+    const SECTOR_SIZE: usize = 512;
+
+    /// Number of FATs of the test images.
+    const FATS: usize = 2;
+
+    /// FAT volume built in memory.
+    struct Image {
+        data: Vec<u8>,
+        kind: Kind,
+        fat_start: usize,
+        fat_size: usize,
+        root_start: usize,
+        data_start: usize,
+    }
+
+    impl Image {
+        /// Returns an empty volume with the common BPB fields.
+        fn new(
+            kind: Kind,
+            reserved: usize,
+            fat_size: usize,
+            root_sectors: usize,
+            clusters: usize,
+        ) -> Self {
+            let root_start = reserved + FATS * fat_size;
+            let data_start = root_start + root_sectors;
+            let mut image = Image {
+                data: std::vec![0; (data_start + clusters) * SECTOR_SIZE],
+                kind,
+                fat_start: reserved,
+                fat_size,
+                root_start,
+                data_start,
+            };
+            image.write(11, &(SECTOR_SIZE as u16).to_le_bytes());
+            image.data[13] = 1;
+            image.write(14, &(reserved as u16).to_le_bytes());
+            image.data[16] = FATS as u8;
+            image.write(BOOT_SIGNATURE_OFFSET, &BOOT_SIGNATURE);
+            image
+        }
+
+        /// Returns an empty FAT16 volume with 4100 clusters and a root
+        /// directory of 64 entries.
+        fn fat16() -> Self {
+            let mut image = Image::new(Kind::Fat16, 1, 17, 4, 4100);
+            let total = image.data.len() / SECTOR_SIZE;
+            image.write(17, &64u16.to_le_bytes());
+            image.write(19, &(total as u16).to_le_bytes());
+            image.write(22, &17u16.to_le_bytes());
+            image
+        }
+
+        /// Returns an empty FAT32 volume with 64 clusters and the root
+        /// directory in the cluster 2.
+        fn fat32() -> Self {
+            let mut image = Image::new(Kind::Fat32, 32, 1, 0, 64);
+            let total = image.data.len() / SECTOR_SIZE;
+            image.write(32, &(total as u32).to_le_bytes());
+            image.write(36, &1u32.to_le_bytes());
+            image.write(44, &2u32.to_le_bytes());
+            image.chain(&[2]);
+            image
+        }
+
+        /// Writes `bytes` at `offset`.
+        fn write(&mut self, offset: usize, bytes: &[u8]) {
+            self.data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+
+        /// Sets the FAT entry of `cluster` to `value` in every FAT.
+        fn set_fat(&mut self, cluster: u32, value: u32) {
+            for fat in 0..FATS {
+                let start =
+                    (self.fat_start + fat * self.fat_size) * SECTOR_SIZE;
+                match self.kind {
+                    Kind::Fat16 => self.write(
+                        start + cluster as usize * 2,
+                        &(value as u16).to_le_bytes(),
+                    ),
+                    Kind::Fat32 => self.write(
+                        start + cluster as usize * 4,
+                        &value.to_le_bytes(),
+                    ),
+                }
+            }
+        }
+
+        /// Links `clusters` in a chain.
+        fn chain(&mut self, clusters: &[u32]) {
+            for pair in clusters.windows(2) {
+                self.set_fat(pair[0], pair[1]);
+            }
+            let eoc = match self.kind {
+                Kind::Fat16 => 0xffff,
+                Kind::Fat32 => FAT32_MASK,
+            };
+            self.set_fat(*clusters.last().unwrap(), eoc);
+        }
+
+        /// Returns the offset of the cluster `cluster`.
+        fn cluster_offset(&self, cluster: u32) -> usize {
+            (self.data_start + cluster as usize - 2) * SECTOR_SIZE
+        }
+
+        /// Returns the offset of the FAT16 root directory.
+        fn root_offset(&self) -> usize {
+            self.root_start * SECTOR_SIZE
+        }
+
+        /// Writes the directory entries `entries` at `offset`.
+        fn write_entries(&mut self, offset: usize, entries: &[[u8; 32]]) {
+            for (i, entry) in entries.iter().enumerate() {
+                self.write(offset + i * DIR_ENTRY_SIZE, entry);
+            }
+        }
+    }
+
+    /// Returns a short name entry.
+    fn short_entry(
+        name: &[u8; 11],
+        attributes: u8,
+        cluster: u32,
+        size: u32,
+    ) -> [u8; 32] {
+        let mut raw = [0; 32];
+        raw[..11].copy_from_slice(name);
+        raw[11] = attributes;
+        raw[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+        raw[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+        raw[28..32].copy_from_slice(&size.to_le_bytes());
+        raw
+    }
+
+    /// Returns the long name entries of `name` in directory order, followed
+    /// by the short name entry `short`.
+    fn long_entries(name: &str, short: [u8; 32]) -> Vec<[u8; 32]> {
+        let mut chars: Vec<u16> = name.encode_utf16().collect();
+        let count = (chars.len() + 12) / 13;
+        if chars.len() % 13 != 0 {
+            chars.push(0);
+        }
+        chars.resize(count * 13, 0xffff);
+
+        let mut entries = Vec::new();
+        for seq in (1..=count).rev() {
+            let mut raw = [0; 32];
+            raw[0] = seq as u8;
+            if seq == count {
+                raw[0] |= LFN_LAST;
+            }
+            raw[11] = ATTR_LONG_NAME;
+            raw[13] = checksum(&short);
+            for (i, &offset) in LFN_OFFSETS.iter().enumerate() {
+                let c = chars[(seq - 1) * 13 + i];
+                raw[offset..offset + 2].copy_from_slice(&c.to_le_bytes());
+            }
+            entries.push(raw);
+        }
+        entries.push(short);
+        entries
+    }
+
+    /// Returns the names of the entries of `dir`.
+    fn names(dir: &Dir) -> Vec<String> {
+        dir.entries()
+            .map(|entry| entry.unwrap().name().into())
+            .collect()
+    }
+
+    #[test]
+    fn test_new() {
+        let mut image = Image::fat16();
+        let disk = RamDisk::new(&mut image.data, SECTOR_SIZE);
+        assert_eq!(FileSystem::new(&disk).unwrap().kind(), Kind::Fat16);
+
+        let mut image = Image::fat32();
+        let disk = RamDisk::new(&mut image.data, SECTOR_SIZE);
+        assert_eq!(FileSystem::new(&disk).unwrap().kind(), Kind::Fat32);
+
+        let mut data = [0; 4 * SECTOR_SIZE];
+        let disk = RamDisk::new(&mut data, SECTOR_SIZE);
+        assert_eq!(
+            FileSystem::new(&disk).err(),
+            Some(Error::InvalidBootSector)
+        );
+
+        let mut image = Image::fat32();
+        let len = image.data.len() / 2;
+        let disk = RamDisk::new(&mut image.data[..len], SECTOR_SIZE);
+        assert_eq!(
+            FileSystem::new(&disk).err(),
+            Some(Error::InvalidBootSector)
+        );
+
+        let mut image = Image::fat16();
+        image.write(19, &1000u16.to_le_bytes());
+        let disk = RamDisk::new(&mut image.data, SECTOR_SIZE);
+        assert_eq!(FileSystem::new(&disk).err(), Some(Error::Fat12));
+
+        let mut image = Image::fat16();
+        image.write(11, &256u16.to_le_bytes());
+        let disk = RamDisk::new(&mut image.data, SECTOR_SIZE);
+        assert_eq!(
+            FileSystem::new(&disk).err(),
+            Some(Error::UnsupportedSectorSize(256))
+        );
+    }
+
+    #[test]
+    fn test_entries() {
+        let mut image = Image::fat16();
+        let mut entries = std::vec![
+            short_entry(b"EXPOS      ", ATTR_VOLUME_ID, 0, 0),
+            short_entry(b"README  TXT", ATTR_ARCHIVE, 0, 0),
+        ];
+        entries[1][12] = NT_LOWER_BASE | NT_LOWER_EXT;
+        let mut deleted = long_entries(
+            "Deleted file.txt",
+            short_entry(b"DELETE~1TXT", ATTR_ARCHIVE, 0, 0),
+        );
+        for entry in deleted.iter_mut() {
+            entry[0] = ENTRY_DELETED;
+        }
+        entries.extend(deleted);
+        entries.extend(long_entries(
+            "Long File Name.cfg",
+            short_entry(b"LONGFI~1CFG", ATTR_ARCHIVE, 0, 0),
+        ));
+        let mut broken = long_entries(
+            "Broken name",
+            short_entry(b"OTHER   TXT", ATTR_ARCHIVE, 0, 0),
+        );
+        *broken.last_mut().unwrap() =
+            short_entry(b"BROKEN  TXT", ATTR_ARCHIVE, 0, 0);
+        entries.extend(broken);
+        entries.push(short_entry(b"\x05SC     TXT", ATTR_ARCHIVE, 0, 0));
+        entries.push(short_entry(b"BOOT       ", ATTR_DIRECTORY, 0, 0));
+        let offset = image.root_offset();
+        image.write_entries(offset, &entries);
+
+        let disk = RamDisk::new(&mut image.data, SECTOR_SIZE);
+        let fs = FileSystem::new(&disk).unwrap();
+        let root = fs.root();
+        assert_eq!(
+            names(&root),
+            [
+                "readme.txt",
+                "Long File Name.cfg",
+                "BROKEN.TXT",
+                "?SC.TXT",
+                "BOOT"
+            ]
+        );
+
+        let entry = root.find("long file name.CFG").unwrap();
+        assert_eq!(entry.short_name(), "LONGFI~1.CFG");
+        assert!(root.find("longfi~1.cfg").is_ok());
+        assert!(root.find("boot").unwrap().is_dir());
+        assert_eq!(root.find("Deleted file.txt").err(), Some(Error::NotFound));
+    }
+
+    #[test]
+    fn test_read() {
+        let mut image = Image::fat32();
+        let data: Vec<u8> = (0..1124).map(|i| (i % 251) as u8).collect();
+        let clusters = [5, 3, 9];
+        image.chain(&clusters);
+        for (chunk, &cluster) in data.chunks(SECTOR_SIZE).zip(&clusters) {
+            let offset = image.cluster_offset(cluster);
+            image.write(offset, chunk);
+        }
+        image.chain(&[10]);
+        let offset = image.cluster_offset(2);
+        image.write_entries(
+            offset,
+            &[
+                short_entry(b"KERNEL  BIN", ATTR_ARCHIVE, 5, 1124),
+                short_entry(b"SHORT   BIN", ATTR_ARCHIVE, 10, 2000),
+                short_entry(b"EMPTY   BIN", ATTR_ARCHIVE, 0, 0),
+            ],
+        );
+
+        let disk = RamDisk::new(&mut image.data, SECTOR_SIZE);
+        let fs = FileSystem::new(&disk).unwrap();
+        let file = fs.file(&fs.open("/kernel.bin").unwrap()).unwrap();
+        assert_eq!(file.size(), 1124);
+
+        let mut buf = [0; 2048];
+        assert_eq!(file.read_at(0, &mut buf), Ok(1124));
+        assert_eq!(&buf[..1124], &data[..]);
+        assert_eq!(file.read_at(500, &mut buf[..600]), Ok(600));
+        assert_eq!(&buf[..600], &data[500..1100]);
+        assert_eq!(file.read_at(1100, &mut buf), Ok(24));
+        assert_eq!(&buf[..24], &data[1100..]);
+        assert_eq!(file.read_at(2000, &mut buf), Ok(0));
+
+        let file = fs.file(&fs.open("short.bin").unwrap()).unwrap();
+        assert_eq!(file.read_at(0, &mut buf), Err(Error::InvalidCluster(10)));
+
+        let file = fs.file(&fs.open("empty.bin").unwrap()).unwrap();
+        assert_eq!(file.read_at(0, &mut buf), Ok(0));
+    }
+
+    #[test]
+    fn test_open() {
+        let mut image = Image::fat32();
+        image.chain(&[3, 6]);
+        image.chain(&[4]);
+        image.chain(&[5]);
+
+        let offset = image.cluster_offset(2);
+        image.write_entries(
+            offset,
+            &[short_entry(b"EFI        ", ATTR_DIRECTORY, 3, 0)],
+        );
+        let offset = image.cluster_offset(3);
+        let mut entries = std::vec![
+            short_entry(b".          ", ATTR_DIRECTORY, 3, 0),
+            short_entry(b"..         ", ATTR_DIRECTORY, 0, 0),
+        ];
+        entries.resize(16, short_entry(b"FILE    TXT", ATTR_ARCHIVE, 0, 0));
+        image.write_entries(offset, &entries);
+        let offset = image.cluster_offset(6);
+        image.write_entries(
+            offset,
+            &[short_entry(b"BOOT       ", ATTR_DIRECTORY, 4, 0)],
+        );
+        let offset = image.cluster_offset(4);
+        image.write_entries(
+            offset,
+            &[
+                short_entry(b"..         ", ATTR_DIRECTORY, 3, 0),
+                short_entry(b"BOOTX64 EFI", ATTR_ARCHIVE, 5, 10),
+            ],
+        );
+
+        let disk = RamDisk::new(&mut image.data, SECTOR_SIZE);
+        let fs = FileSystem::new(&disk).unwrap();
+        let entry = fs.open("/efi/boot/bootx64.efi").unwrap();
+        assert_eq!(entry.name(), "BOOTX64.EFI");
+        assert_eq!(entry.size(), 10);
+        assert!(fs.open("EFI/BOOT/../BOOT").unwrap().is_dir());
+        assert_eq!(
+            names(&fs.dir(&fs.open("/efi/..").unwrap()).unwrap()),
+            ["EFI"]
+        );
+        assert_eq!(names(&fs.dir(&fs.open("/").unwrap()).unwrap()), ["EFI"]);
+
+        assert_eq!(
+            fs.open("/efi/boot/bootx64.efi/x").err(),
+            Some(Error::NotADirectory)
+        );
+        assert_eq!(fs.open("/efi/missing").err(), Some(Error::NotFound));
+        assert_eq!(
+            fs.file(&fs.open("/efi").unwrap()).err(),
+            Some(Error::IsADirectory)
+        );
+    }
+
+    #[test]
+    fn test_cluster_loop() {
+        let mut image = Image::fat32();
+        image.set_fat(7, 7);
+        let offset = image.cluster_offset(2);
+        image.write_entries(
+            offset,
+            &[short_entry(b"LOOP       ", ATTR_DIRECTORY, 7, 0)],
+        );
+        let offset = image.cluster_offset(7);
+        let entries = [short_entry(b"FILE    TXT", ATTR_ARCHIVE, 0, 0); 16];
+        image.write_entries(offset, &entries);
+
+        let disk = RamDisk::new(&mut image.data, SECTOR_SIZE);
+        let fs = FileSystem::new(&disk).unwrap();
+        let dir = fs.dir(&fs.open("loop").unwrap()).unwrap();
+        assert_eq!(
+            dir.entries().last().unwrap().err(),
+            Some(Error::InvalidCluster(7))
+        );
+    }
+}
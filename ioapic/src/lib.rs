@@ -0,0 +1,400 @@
+//! IO APIC driver.
+//!
+//! The IO APICs described by the MADT receive the external interrupts and
+//! deliver them to the local APICs. Every IO APIC handles a range of Global
+//! System Interrupts (GSI), starting at its GSI base. The legacy ISA IRQs are
+//! identity mapped to GSIs, unless the MADT has an Interrupt Source Override
+//! for them.
+//!
+//! The IO APIC registers are memory mapped and accessed indirectly: the index
+//! of the register is written into IOREGSEL and its value is read or written
+//! through IOWIN.
+//!
+//! Reference:
+//! - [82093AA IO APIC datasheet](https://pdos.csail.mit.edu/6.828/2018/readings/ia32/ioapic.pdf)
+
+#![no_std]
+
+use ticket_mutex::TicketMutex;
+use uefi::acpi;
+
+/// Maximum number of IO APICs supported.
+const MAX_IOAPICS: usize = 8;
+
+/// Number of legacy ISA IRQs.
+const ISA_IRQS: usize = 16;
+
+/// Offset of the IOREGSEL register.
+const IOREGSEL: u64 = 0x00;
+
+/// Offset of the IOWIN register.
+const IOWIN: u64 = 0x10;
+
+/// IO APIC Version register.
+const IOAPICVER: u32 = 0x01;
+
+/// First IO APIC Redirection Table register. Every entry takes two
+/// registers.
+const IOREDTBL: u32 = 0x10;
+
+/// Redirection entry bit that selects active low polarity.
+const REDIR_ACTIVE_LOW: u64 = 1 << 13;
+
+/// Redirection entry bit that selects level triggered mode.
+const REDIR_LEVEL: u64 = 1 << 15;
+
+/// Redirection entry bit that masks the interrupt.
+const REDIR_MASKED: u64 = 1 << 16;
+
+/// IO APICs of the system.
+static IOAPICS: TicketMutex<IoApics> = TicketMutex::new(IoApics::new());
+
+/// IO APIC errors.
+#[derive(Debug)]
+pub enum Error {
+    /// There are more IO APICs than `MAX_IOAPICS`.
+    TooManyIoApics,
+
+    /// No IO APIC handles the GSI.
+    InvalidGsi,
+}
+
+/// Trigger mode of an interrupt.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TriggerMode {
+    /// Edge triggered.
+    Edge,
+
+    /// Level triggered.
+    Level,
+}
+
+/// Polarity of an interrupt.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Polarity {
+    /// Active high.
+    ActiveHigh,
+
+    /// Active low.
+    ActiveLow,
+}
+
+/// Describes the GSI signaled by an ISA IRQ.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct IsaRoute {
+    /// Global System Interrupt.
+    gsi: u32,
+
+    /// Trigger mode.
+    trigger: TriggerMode,
+
+    /// Polarity.
+    polarity: Polarity,
+}
+
+impl IsaRoute {
+    /// Returns the route of the ISA IRQ `irq` when there is no Interrupt
+    /// Source Override: same GSI, edge triggered and active high.
+    const fn identity(irq: u8) -> Self {
+        IsaRoute {
+            gsi: irq as u32,
+            trigger: TriggerMode::Edge,
+            polarity: Polarity::ActiveHigh,
+        }
+    }
+
+    /// Returns the route described by the Interrupt Source Override `iso`.
+    fn from_override(iso: &acpi::MadtInterruptOverride) -> Self {
+        let (trigger, polarity) = decode_inti_flags(iso.flags());
+        IsaRoute {
+            gsi: iso.gsi(),
+            trigger,
+            polarity,
+        }
+    }
+
+    /// Global System Interrupt.
+    pub fn gsi(&self) -> u32 {
+        self.gsi
+    }
+
+    /// Trigger mode.
+    pub fn trigger(&self) -> TriggerMode {
+        self.trigger
+    }
+
+    /// Polarity.
+    pub fn polarity(&self) -> Polarity {
+        self.polarity
+    }
+}
+
+/// Returns the trigger mode and polarity encoded in the MPS INTI flags
+/// `flags`. The values that conform to the specifications of the bus are
+/// resolved for the ISA bus: edge triggered and active high.
+fn decode_inti_flags(flags: u16) -> (TriggerMode, Polarity) {
+    let polarity = match flags & 0x3 {
+        0x3 => Polarity::ActiveLow,
+        _ => Polarity::ActiveHigh,
+    };
+    let trigger = match (flags >> 2) & 0x3 {
+        0x3 => TriggerMode::Level,
+        _ => TriggerMode::Edge,
+    };
+    (trigger, polarity)
+}
+
+/// Returns the redirection entry that delivers an interrupt with vector
+/// `vector` to the local APIC `dest_apic`, using fixed delivery mode and
+/// physical destination mode.
+fn redirection_entry(
+    vector: u8,
+    dest_apic: u8,
+    trigger: TriggerMode,
+    polarity: Polarity,
+) -> u64 {
+    let mut entry = u64::from(vector) | u64::from(dest_apic) << 56;
+    if trigger == TriggerMode::Level {
+        entry |= REDIR_LEVEL;
+    }
+    if polarity == Polarity::ActiveLow {
+        entry |= REDIR_ACTIVE_LOW;
+    }
+    entry
+}
+
+/// Represents an IO APIC.
+#[derive(Debug, Clone, Copy)]
+struct IoApic {
+    /// Physical address of the registers. They are identity mapped.
+    addr: u64,
+
+    /// First GSI handled by the IO APIC.
+    gsi_base: u32,
+
+    /// Number of redirection entries.
+    entries: u32,
+}
+
+impl IoApic {
+    /// Returns the IO APIC at the physical address `addr`, whose first GSI is
+    /// `gsi_base`.
+    ///
+    /// # Safety
+    ///
+    /// `addr` must be the address of an IO APIC.
+    unsafe fn new(addr: u64, gsi_base: u32) -> Self {
+        let mut ioapic = IoApic {
+            addr,
+            gsi_base,
+            entries: 0,
+        };
+        ioapic.entries = ((ioapic.read(IOAPICVER) >> 16) & 0xff) + 1;
+        ioapic
+    }
+
+    /// Returns `true` if the IO APIC handles `gsi`.
+    fn handles(&self, gsi: u32) -> bool {
+        gsi >= self.gsi_base && gsi - self.gsi_base < self.entries
+    }
+
+    /// Reads the register `reg`.
+    fn read(&self, reg: u32) -> u32 {
+        unsafe {
+            ((self.addr + IOREGSEL) as *mut u32).write_volatile(reg);
+            ((self.addr + IOWIN) as *const u32).read_volatile()
+        }
+    }
+
+    /// Writes `val` into the register `reg`.
+    fn write(&self, reg: u32, val: u32) {
+        unsafe {
+            ((self.addr + IOREGSEL) as *mut u32).write_volatile(reg);
+            ((self.addr + IOWIN) as *mut u32).write_volatile(val);
+        }
+    }
+
+    /// Reads the redirection entry of `gsi`.
+    fn read_entry(&self, gsi: u32) -> u64 {
+        let reg = IOREDTBL + (gsi - self.gsi_base) * 2;
+        u64::from(self.read(reg)) | u64::from(self.read(reg + 1)) << 32
+    }
+
+    /// Writes `entry` into the redirection entry of `gsi`. The low half,
+    /// which holds the mask bit, is written last, so the entry is not
+    /// unmasked before it is complete.
+    fn write_entry(&self, gsi: u32, entry: u64) {
+        let reg = IOREDTBL + (gsi - self.gsi_base) * 2;
+        self.write(reg, REDIR_MASKED as u32);
+        self.write(reg + 1, (entry >> 32) as u32);
+        self.write(reg, entry as u32);
+    }
+}
+
+/// IO APICs and ISA IRQ routes of the system.
+struct IoApics {
+    /// Registered IO APICs.
+    ioapics: [Option<IoApic>; MAX_IOAPICS],
+
+    /// Routes of the ISA IRQs. The route at index `n` belongs to IRQ `n`.
+    isa_routes: [IsaRoute; ISA_IRQS],
+}
+
+impl IoApics {
+    /// Returns an `IoApics` without IO APICs.
+    const fn new() -> Self {
+        let mut isa_routes = [IsaRoute::identity(0); ISA_IRQS];
+        let mut irq = 0;
+        while irq < ISA_IRQS {
+            isa_routes[irq] = IsaRoute::identity(irq as u8);
+            irq += 1;
+        }
+
+        IoApics {
+            ioapics: [None; MAX_IOAPICS],
+            isa_routes,
+        }
+    }
+
+    /// Returns the IO APIC that handles `gsi`.
+    fn find(&self, gsi: u32) -> Result<&IoApic, Error> {
+        self.ioapics
+            .iter()
+            .flatten()
+            .find(|ioapic| ioapic.handles(gsi))
+            .ok_or(Error::InvalidGsi)
+    }
+}
+
+/// Registers the IO APICs and the Interrupt Source Overrides described by
+/// `madt` and masks all the interrupts. It returns the number of IO APICs.
+///
+/// # Safety
+///
+/// The IO APIC registers must be identity mapped.
+pub unsafe fn init(madt: &acpi::Madt) -> Result<usize, Error> {
+    let mut ioapics = IOAPICS.lock();
+
+    for (i, entry) in madt.ioapics().enumerate() {
+        let slot = ioapics.ioapics.get_mut(i).ok_or(Error::TooManyIoApics)?;
+        let ioapic = IoApic::new(entry.ioapic_addr().into(), entry.gsi_base());
+        for gsi in ioapic.gsi_base..ioapic.gsi_base + ioapic.entries {
+            ioapic.write_entry(gsi, REDIR_MASKED);
+        }
+        *slot = Some(ioapic);
+    }
+
+    for iso in madt.interrupt_overrides() {
+        if iso.bus() != 0 {
+            continue;
+        }
+        if let Some(route) = ioapics.isa_routes.get_mut(iso.source() as usize)
+        {
+            *route = IsaRoute::from_override(&iso);
+        }
+    }
+
+    Ok(ioapics.ioapics.iter().flatten().count())
+}
+
+/// Routes `gsi` to the vector `vector` of the local APIC `dest_apic` and
+/// unmasks it.
+pub fn route(
+    gsi: u32,
+    vector: u8,
+    dest_apic: u8,
+    trigger: TriggerMode,
+    polarity: Polarity,
+) -> Result<(), Error> {
+    let ioapics = IOAPICS.lock();
+    let ioapic = ioapics.find(gsi)?;
+    ioapic.write_entry(
+        gsi,
+        redirection_entry(vector, dest_apic, trigger, polarity),
+    );
+    Ok(())
+}
+
+/// Routes the ISA IRQ `irq` to the vector `vector` of the local APIC
+/// `dest_apic` and unmasks it. The GSI, trigger mode and polarity are taken
+/// from `isa_route`.
+pub fn route_isa_irq(irq: u8, vector: u8, dest_apic: u8) -> Result<(), Error> {
+    let route = isa_route(irq).ok_or(Error::InvalidGsi)?;
+    self::route(
+        route.gsi(),
+        vector,
+        dest_apic,
+        route.trigger(),
+        route.polarity(),
+    )
+}
+
+/// Returns the route of the ISA IRQ `irq`, or `None` if it is not an ISA
+/// IRQ.
+pub fn isa_route(irq: u8) -> Option<IsaRoute> {
+    IOAPICS.with(|ioapics| ioapics.isa_routes.get(irq as usize).copied())
+}
+
+/// Masks or unmasks `gsi`, keeping the rest of its redirection entry.
+pub fn set_masked(gsi: u32, masked: bool) -> Result<(), Error> {
+    let ioapics = IOAPICS.lock();
+    let ioapic = ioapics.find(gsi)?;
+    let entry = ioapic.read_entry(gsi);
+    let entry = if masked {
+        entry | REDIR_MASKED
+    } else {
+        entry & !REDIR_MASKED
+    };
+    ioapic.write_entry(gsi, entry);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_inti_flags() {
+        assert_eq!(
+            decode_inti_flags(0x0),
+            (TriggerMode::Edge, Polarity::ActiveHigh)
+        );
+        assert_eq!(
+            decode_inti_flags(0xd),
+            (TriggerMode::Level, Polarity::ActiveHigh)
+        );
+        assert_eq!(
+            decode_inti_flags(0xf),
+            (TriggerMode::Level, Polarity::ActiveLow)
+        );
+    }
+
+    #[test]
+    fn test_redirection_entry() {
+        assert_eq!(
+            redirection_entry(
+                0x30,
+                1,
+                TriggerMode::Edge,
+                Polarity::ActiveHigh
+            ),
+            0x0100_0000_0000_0030
+        );
+        assert_eq!(
+            redirection_entry(
+                0x31,
+                2,
+                TriggerMode::Level,
+                Polarity::ActiveLow
+            ),
+            0x0200_0000_0000_a031
+        );
+    }
+
+    #[test]
+    fn test_ioapics_isa_routes() {
+        let ioapics = IoApics::new();
+        assert_eq!(ioapics.isa_routes[9], IsaRoute::identity(9));
+        assert_eq!(ioapics.isa_routes[9].gsi(), 9);
+    }
+}
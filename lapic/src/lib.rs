@@ -0,0 +1,247 @@
+//! Local APIC driver.
+//!
+//! Every CPU has a local APIC that receives its interrupts, including the
+//! ones of its timer. The local APIC is accessed via MMIO in xAPIC mode and
+//! via MSRs in x2APIC mode. The mode enabled by the firmware is kept, so the
+//! same driver works on both.
+//!
+//! Reference:
+//! - Intel SDM Vol. 3A, Chapter 10 "Advanced Programmable Interrupt
+//!   Controller (APIC)"
+
+#![no_std]
+
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// IA32_APIC_BASE MSR.
+const IA32_APIC_BASE: u32 = 0x1b;
+
+/// IA32_APIC_BASE bit that is set if the local APIC is globally enabled.
+const APIC_BASE_ENABLE: u64 = 1 << 11;
+
+/// IA32_APIC_BASE bit that is set if the local APIC is in x2APIC mode.
+const APIC_BASE_EXTD: u64 = 1 << 10;
+
+/// First MSR of the x2APIC registers.
+const X2APIC_MSR_BASE: u32 = 0x800;
+
+/// Local APIC ID register.
+const REG_ID: u32 = 0x20;
+
+/// End Of Interrupt register.
+const REG_EOI: u32 = 0xb0;
+
+/// Spurious Interrupt Vector register.
+const REG_SVR: u32 = 0xf0;
+
+/// Interrupt Command register (bits 0..32).
+const REG_ICR_LOW: u32 = 0x300;
+
+/// Interrupt Command register (bits 32..64). It is only used in xAPIC mode.
+const REG_ICR_HIGH: u32 = 0x310;
+
+/// LVT Timer register.
+const REG_LVT_TIMER: u32 = 0x320;
+
+/// Timer Initial Count register.
+const REG_TIMER_INITIAL: u32 = 0x380;
+
+/// Timer Current Count register.
+const REG_TIMER_CURRENT: u32 = 0x390;
+
+/// Timer Divide Configuration register.
+const REG_TIMER_DIVIDE: u32 = 0x3e0;
+
+/// Spurious Interrupt Vector register bit that software enables the local
+/// APIC.
+const SVR_ENABLE: u32 = 1 << 8;
+
+/// LVT bit that masks the interrupt.
+const LVT_MASKED: u32 = 1 << 16;
+
+/// LVT Timer bit that selects periodic mode.
+const LVT_TIMER_PERIODIC: u32 = 1 << 17;
+
+/// Interrupt Command register value that selects the Fixed delivery mode.
+const ICR_FIXED: u32 = 0x0 << 8;
+
+/// Interrupt Command register value that selects the INIT delivery mode.
+const ICR_INIT: u32 = 0x5 << 8;
+
+/// Interrupt Command register value that selects the Start-Up delivery mode.
+const ICR_STARTUP: u32 = 0x6 << 8;
+
+/// Interrupt Command register bit that is set while the IPI is being sent.
+/// It is only used in xAPIC mode.
+const ICR_SEND_PENDING: u32 = 1 << 12;
+
+/// Interrupt Command register bit that selects the assert level.
+const ICR_LEVEL_ASSERT: u32 = 1 << 14;
+
+/// Timer Divide Configuration value that divides the bus clock by 16.
+const TIMER_DIVIDE_16: u32 = 0x3;
+
+/// Divisor applied to the bus clock to obtain the timer frequency.
+pub const TIMER_DIVISOR: u64 = 16;
+
+/// Set if the local APICs are in x2APIC mode.
+static X2APIC: AtomicBool = AtomicBool::new(false);
+
+/// Physical address of the local APIC registers in xAPIC mode. They are
+/// identity mapped.
+static BASE: AtomicU64 = AtomicU64::new(0);
+
+/// Local APIC errors.
+#[derive(Debug)]
+pub enum Error {
+    /// The local APIC is globally disabled.
+    Disabled,
+}
+
+/// Mode of the local APIC timer.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TimerMode {
+    /// The timer fires once, when the count reaches zero.
+    OneShot,
+
+    /// The timer is reloaded with the initial count every time it fires.
+    Periodic,
+}
+
+/// Reads the local APIC register `reg`.
+fn read(reg: u32) -> u32 {
+    unsafe {
+        if X2APIC.load(Ordering::Relaxed) {
+            cpu::rdmsr(X2APIC_MSR_BASE + (reg >> 4)) as u32
+        } else {
+            let addr = BASE.load(Ordering::Relaxed) + u64::from(reg);
+            (addr as *const u32).read_volatile()
+        }
+    }
+}
+
+/// Writes `val` into the local APIC register `reg`.
+fn write(reg: u32, val: u32) {
+    unsafe {
+        if X2APIC.load(Ordering::Relaxed) {
+            cpu::wrmsr(X2APIC_MSR_BASE + (reg >> 4), val.into());
+        } else {
+            let addr = BASE.load(Ordering::Relaxed) + u64::from(reg);
+            (addr as *mut u32).write_volatile(val);
+        }
+    }
+}
+
+/// Software enables the local APIC of the current CPU, delivering its
+/// spurious interrupts at `spurious_vector`. The timer is masked.
+///
+/// # Safety
+///
+/// In xAPIC mode, the local APIC registers must be identity mapped. The
+/// vector `spurious_vector` must have a handler, which must not send an EOI.
+pub unsafe fn init(spurious_vector: u8) -> Result<(), Error> {
+    let apic_base = cpu::rdmsr(IA32_APIC_BASE);
+    if apic_base & APIC_BASE_ENABLE == 0 {
+        return Err(Error::Disabled);
+    }
+    X2APIC.store(apic_base & APIC_BASE_EXTD != 0, Ordering::Relaxed);
+    BASE.store(apic_base & 0x000f_ffff_ffff_f000, Ordering::Relaxed);
+
+    write(REG_LVT_TIMER, LVT_MASKED);
+    write(REG_SVR, SVR_ENABLE | u32::from(spurious_vector));
+    Ok(())
+}
+
+/// Returns `true` if the local APICs are in x2APIC mode.
+pub fn is_x2apic() -> bool {
+    X2APIC.load(Ordering::Relaxed)
+}
+
+/// Returns the ID of the local APIC of the current CPU.
+pub fn id() -> u32 {
+    if is_x2apic() {
+        read(REG_ID)
+    } else {
+        read(REG_ID) >> 24
+    }
+}
+
+/// Signals the end of the interrupt being handled by the current CPU.
+pub fn end_of_interrupt() {
+    write(REG_EOI, 0);
+}
+
+/// Sends the IPI described by the low half of the Interrupt Command register
+/// `icr` to the local APIC `dest`, and waits until it is sent.
+fn send_ipi(dest: u32, icr: u32) {
+    if is_x2apic() {
+        unsafe {
+            cpu::wrmsr(
+                X2APIC_MSR_BASE + (REG_ICR_LOW >> 4),
+                u64::from(dest) << 32 | u64::from(icr),
+            );
+        }
+        return;
+    }
+
+    write(REG_ICR_HIGH, dest << 24);
+    write(REG_ICR_LOW, icr);
+    while read(REG_ICR_LOW) & ICR_SEND_PENDING != 0 {
+        core::hint::spin_loop();
+    }
+}
+
+/// Sends an INIT IPI to the local APIC `dest`. It resets the CPU, which
+/// waits for a Start-Up IPI.
+///
+/// # Safety
+///
+/// The CPU with local APIC `dest` must not be in use.
+pub unsafe fn send_init(dest: u32) {
+    send_ipi(dest, ICR_INIT | ICR_LEVEL_ASSERT);
+}
+
+/// Sends a Start-Up IPI to the local APIC `dest`. A CPU waiting for it
+/// starts executing in real mode at the physical address `vector << 12`.
+///
+/// # Safety
+///
+/// The code at the start-up address must bring up the CPU.
+pub unsafe fn send_startup(dest: u32, vector: u8) {
+    send_ipi(dest, ICR_STARTUP | ICR_LEVEL_ASSERT | u32::from(vector));
+}
+
+/// Sends an IPI with the vector `vector` to the local APIC `dest`. The
+/// vector must be handled by the destination CPU.
+pub fn send_fixed(dest: u32, vector: u8) {
+    send_ipi(dest, ICR_FIXED | ICR_LEVEL_ASSERT | u32::from(vector));
+}
+
+/// Starts the timer of the current CPU. It counts down from `initial_count`
+/// at the bus frequency divided by `TIMER_DIVISOR`, and raises the vector
+/// `vector` when it reaches zero. If `vector` is `None`, the interrupt is
+/// masked, which is useful to measure the frequency of the timer.
+pub fn start_timer(vector: Option<u8>, mode: TimerMode, initial_count: u32) {
+    let mut lvt = match vector {
+        Some(vector) => u32::from(vector),
+        None => LVT_MASKED,
+    };
+    if mode == TimerMode::Periodic {
+        lvt |= LVT_TIMER_PERIODIC;
+    }
+
+    write(REG_TIMER_DIVIDE, TIMER_DIVIDE_16);
+    write(REG_LVT_TIMER, lvt);
+    write(REG_TIMER_INITIAL, initial_count);
+}
+
+/// Stops the timer of the current CPU.
+pub fn stop_timer() {
+    write(REG_TIMER_INITIAL, 0);
+    write(REG_LVT_TIMER, LVT_MASKED);
+}
+
+/// Returns the current count of the timer of the current CPU.
+pub fn timer_count() -> u32 {
+    read(REG_TIMER_CURRENT)
+}
@@ -0,0 +1,242 @@
+//! Physical and virtual addresses, frames and pages.
+
+use core::fmt;
+
+use range::Point;
+
+/// Size of a frame and a page.
+pub const PAGE_SIZE: u64 = 0x1000;
+
+/// Implements the methods shared by `PhysAddr` and `VirtAddr`.
+macro_rules! impl_addr {
+    ($($ty:ident),*) => {
+        $(
+            impl $ty {
+                /// Returns an address from its `u64` value.
+                pub const fn new(addr: u64) -> Self {
+                    $ty(addr)
+                }
+
+                /// Returns the `u64` value of the address.
+                pub const fn as_u64(self) -> u64 {
+                    self.0
+                }
+
+                /// Returns `true` if the address is a multiple of `align`,
+                /// which must be a power of two.
+                pub const fn is_aligned(self, align: u64) -> bool {
+                    self.0 & (align - 1) == 0
+                }
+
+                /// Returns the address aligned down to `align`, which must
+                /// be a power of two.
+                pub const fn align_down(self, align: u64) -> Self {
+                    $ty(self.0 & !(align - 1))
+                }
+
+                /// Returns the address aligned up to `align`, which must be
+                /// a power of two. It returns `None` on overflow.
+                pub fn align_up(self, align: u64) -> Option<Self> {
+                    let addr = self.0.checked_add(align - 1)?;
+                    Some($ty(addr & !(align - 1)))
+                }
+
+                /// Returns the address `offset` bytes after this one. It
+                /// returns `None` on overflow.
+                pub fn offset(self, offset: u64) -> Option<Self> {
+                    self.0.checked_add(offset).map($ty)
+                }
+            }
+
+            impl fmt::LowerHex for $ty {
+                fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    fmt::LowerHex::fmt(&self.0, f)
+                }
+            }
+
+            impl Point for $ty {
+                fn to_u64(self) -> u64 {
+                    self.0
+                }
+
+                fn from_u64(value: u64) -> Self {
+                    $ty(value)
+                }
+            }
+        )*
+    };
+}
+
+/// Represents a physical memory address.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct PhysAddr(u64);
+
+/// Represents a virtual memory address.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct VirtAddr(u64);
+
+impl_addr!(PhysAddr, VirtAddr);
+
+impl VirtAddr {
+    /// Returns `true` if the address is canonical, i.e. its bits 48 to 63
+    /// are copies of the bit 47.
+    pub const fn is_canonical(self) -> bool {
+        ((self.0 << 16) as i64 >> 16) as u64 == self.0
+    }
+
+    /// Returns the offset of the address inside its 4KiB page.
+    pub const fn page_offset(self) -> u64 {
+        self.0 & (PAGE_SIZE - 1)
+    }
+
+    /// Returns the index into the PML4 used to translate the address.
+    pub const fn p4_index(self) -> usize {
+        self.table_index(4)
+    }
+
+    /// Returns the index into the PDPT used to translate the address.
+    pub const fn p3_index(self) -> usize {
+        self.table_index(3)
+    }
+
+    /// Returns the index into the PD used to translate the address.
+    pub const fn p2_index(self) -> usize {
+        self.table_index(2)
+    }
+
+    /// Returns the index into the PT used to translate the address.
+    pub const fn p1_index(self) -> usize {
+        self.table_index(1)
+    }
+
+    /// Returns the index into the page table of level `level` used to
+    /// translate the address. The PT is level 1 and the PML4 is level 4.
+    pub const fn table_index(self, level: usize) -> usize {
+        (self.0 >> (12 + 9 * (level - 1)) & 0x1ff) as usize
+    }
+}
+
+/// Represents a 4KiB physical frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PhysFrame(PhysAddr);
+
+impl PhysFrame {
+    /// Returns the frame that starts at `addr`, or `None` if `addr` is not
+    /// aligned to `PAGE_SIZE`.
+    pub const fn from_start_address(addr: PhysAddr) -> Option<Self> {
+        if addr.is_aligned(PAGE_SIZE) {
+            Some(PhysFrame(addr))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the frame that contains `addr`.
+    pub const fn containing_address(addr: PhysAddr) -> Self {
+        PhysFrame(addr.align_down(PAGE_SIZE))
+    }
+
+    /// Returns the frame with number `number`.
+    pub const fn from_number(number: u64) -> Self {
+        PhysFrame(PhysAddr(number * PAGE_SIZE))
+    }
+
+    /// Returns the start address of the frame.
+    pub const fn start_address(self) -> PhysAddr {
+        self.0
+    }
+
+    /// Returns the number of the frame, i.e. its start address divided by
+    /// `PAGE_SIZE`.
+    pub const fn number(self) -> u64 {
+        self.0.as_u64() / PAGE_SIZE
+    }
+}
+
+/// Represents a 4KiB virtual page.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct VirtPage(VirtAddr);
+
+impl VirtPage {
+    /// Returns the page that starts at `addr`, or `None` if `addr` is not
+    /// aligned to `PAGE_SIZE`.
+    pub const fn from_start_address(addr: VirtAddr) -> Option<Self> {
+        if addr.is_aligned(PAGE_SIZE) {
+            Some(VirtPage(addr))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the page that contains `addr`.
+    pub const fn containing_address(addr: VirtAddr) -> Self {
+        VirtPage(addr.align_down(PAGE_SIZE))
+    }
+
+    /// Returns the start address of the page.
+    pub const fn start_address(self) -> VirtAddr {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_align() {
+        let addr = PhysAddr::new(0x1234);
+        assert!(!addr.is_aligned(0x1000));
+        assert!(addr.is_aligned(4));
+        assert_eq!(addr.align_down(0x1000), PhysAddr::new(0x1000));
+        assert_eq!(addr.align_up(0x1000), Some(PhysAddr::new(0x2000)));
+        assert_eq!(
+            PhysAddr::new(0x2000).align_up(0x1000),
+            Some(PhysAddr::new(0x2000))
+        );
+        assert_eq!(PhysAddr::new(u64::MAX).align_up(0x1000), None);
+    }
+
+    #[test]
+    fn test_offset() {
+        assert_eq!(
+            VirtAddr::new(0x1000).offset(0x234),
+            Some(VirtAddr::new(0x1234))
+        );
+        assert_eq!(VirtAddr::new(u64::MAX).offset(1), None);
+    }
+
+    #[test]
+    fn test_table_indexes() {
+        let virt = VirtAddr::new(0xffff_8080_4060_3123);
+        assert_eq!(virt.p4_index(), 0x101);
+        assert_eq!(virt.p3_index(), 0x001);
+        assert_eq!(virt.p2_index(), 0x003);
+        assert_eq!(virt.p1_index(), 0x003);
+        assert_eq!(virt.page_offset(), 0x123);
+    }
+
+    #[test]
+    fn test_is_canonical() {
+        assert!(VirtAddr::new(0x0000_7fff_ffff_ffff).is_canonical());
+        assert!(VirtAddr::new(0xffff_8000_0000_0000).is_canonical());
+        assert!(!VirtAddr::new(0x0000_8000_0000_0000).is_canonical());
+        assert!(!VirtAddr::new(0xfff0_0000_0000_0000).is_canonical());
+    }
+
+    #[test]
+    fn test_frames_and_pages() {
+        let frame = PhysFrame::containing_address(PhysAddr::new(0x5123));
+        assert_eq!(frame.start_address(), PhysAddr::new(0x5000));
+        assert_eq!(frame.number(), 5);
+        assert_eq!(PhysFrame::from_number(5), frame);
+        assert_eq!(PhysFrame::from_start_address(PhysAddr::new(0x5123)), None);
+
+        let page = VirtPage::containing_address(VirtAddr::new(0x7fff));
+        assert_eq!(page.start_address(), VirtAddr::new(0x7000));
+        assert_eq!(
+            VirtPage::from_start_address(VirtAddr::new(0x7000)),
+            Some(page)
+        );
+    }
+}
@@ -0,0 +1,342 @@
+//! Linked-list heap allocator.
+//!
+//! The free memory is kept in a list of free blocks sorted by address. Every
+//! free block stores its size and a pointer to the next one in its first
+//! bytes. Allocations take the first block that fits, splitting off the
+//! unused memory before and after the allocation. Freed memory is merged
+//! with the adjacent free blocks.
+//!
+//! All the allocations are rounded up to the size and alignment of a free
+//! block, so freeing them always leaves room for the block header.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::mem;
+use core::ptr::{self, NonNull};
+
+use ticket_mutex::TicketMutex;
+
+/// Header of a free block.
+struct FreeBlock {
+    /// Size of the block, including the header.
+    size: usize,
+
+    /// Next free block, which is at a higher address. It is null for the
+    /// last block.
+    next: *mut FreeBlock,
+}
+
+/// Minimum size of a block.
+const MIN_BLOCK_SIZE: usize = mem::size_of::<FreeBlock>();
+
+/// Minimum alignment of a block.
+const MIN_BLOCK_ALIGN: usize = mem::align_of::<FreeBlock>();
+
+/// Returns `addr` aligned up to `align`, which must be a power of two. It
+/// returns `None` on overflow.
+fn align_up(addr: usize, align: usize) -> Option<usize> {
+    Some(addr.checked_add(align - 1)? & !(align - 1))
+}
+
+/// Returns the size and alignment of the block used for an allocation with
+/// layout `layout`.
+fn block_layout(layout: Layout) -> Option<(usize, usize)> {
+    let size = align_up(layout.size().max(MIN_BLOCK_SIZE), MIN_BLOCK_ALIGN)?;
+    Some((size, layout.align().max(MIN_BLOCK_ALIGN)))
+}
+
+/// Linked-list heap.
+pub struct Heap {
+    /// First free block. It is null if there is no free memory.
+    head: *mut FreeBlock,
+
+    /// Total size of the memory handed over to the heap.
+    size: usize,
+
+    /// Number of bytes allocated.
+    used: usize,
+}
+
+// The free blocks are only accessed through the `Heap`.
+unsafe impl Send for Heap {}
+
+impl Heap {
+    /// Returns a `Heap` without memory.
+    pub const fn empty() -> Self {
+        Heap {
+            head: ptr::null_mut(),
+            size: 0,
+            used: 0,
+        }
+    }
+
+    /// Hands over the memory region of `size` bytes at `addr` to the heap.
+    /// The bytes that do not fit in an aligned block are ignored.
+    ///
+    /// # Safety
+    ///
+    /// The memory region must be valid for writes, unused and must not
+    /// overlap with the memory already handed over to the heap.
+    pub unsafe fn add_region(&mut self, addr: usize, size: usize) {
+        let start = match align_up(addr, MIN_BLOCK_ALIGN) {
+            Some(start) => start,
+            None => return,
+        };
+        let end = match addr.checked_add(size) {
+            Some(end) => end & !(MIN_BLOCK_ALIGN - 1),
+            None => return,
+        };
+        if end <= start || end - start < MIN_BLOCK_SIZE {
+            return;
+        }
+
+        self.size += end - start;
+        self.insert(start, end - start);
+    }
+
+    /// Returns the total size of the memory handed over to the heap.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the number of bytes allocated.
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    /// Allocates a block of memory with layout `layout`. It returns `None`
+    /// if there is no free block big enough.
+    pub fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        let (size, align) = block_layout(layout)?;
+
+        let mut prev: *mut FreeBlock = ptr::null_mut();
+        let mut cur = self.head;
+        while !cur.is_null() {
+            // The free blocks are owned by the heap.
+            let block = unsafe { &mut *cur };
+            let block_start = cur as usize;
+            let block_end = block_start + block.size;
+
+            if let Some((start, end)) =
+                Self::fit(block_start, block_end, size, align)
+            {
+                // Unlink the block and give back the unused memory before
+                // and after the allocation.
+                let next = block.next;
+                if prev.is_null() {
+                    self.head = next;
+                } else {
+                    unsafe { (*prev).next = next };
+                }
+                unsafe {
+                    if start > block_start {
+                        self.insert(block_start, start - block_start);
+                    }
+                    if block_end > end {
+                        self.insert(end, block_end - end);
+                    }
+                }
+
+                self.used += size;
+                return NonNull::new(start as *mut u8);
+            }
+
+            prev = cur;
+            cur = block.next;
+        }
+        None
+    }
+
+    /// Frees the block of memory at `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by `allocate` with the same `layout`
+    /// and must not be used afterwards.
+    pub unsafe fn deallocate(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        // `allocate` succeeded with the same layout, so it does not
+        // overflow.
+        let (size, _) = block_layout(layout).unwrap();
+        self.used -= size;
+        self.insert(ptr.as_ptr() as usize, size);
+    }
+
+    /// Returns the start and end addresses of an allocation of `size` bytes
+    /// aligned to `align` inside the free block between `block_start` and
+    /// `block_end`. The unused memory before and after the allocation must
+    /// be able to hold a free block.
+    fn fit(
+        block_start: usize,
+        block_end: usize,
+        size: usize,
+        align: usize,
+    ) -> Option<(usize, usize)> {
+        let mut start = align_up(block_start, align)?;
+        if start != block_start && start - block_start < MIN_BLOCK_SIZE {
+            start = align_up(block_start + MIN_BLOCK_SIZE, align)?;
+        }
+
+        let end = start.checked_add(size)?;
+        if end > block_end {
+            return None;
+        }
+        if end != block_end && block_end - end < MIN_BLOCK_SIZE {
+            return None;
+        }
+        Some((start, end))
+    }
+
+    /// Inserts the free block of `size` bytes at `addr` into the list,
+    /// merging it with the adjacent blocks.
+    ///
+    /// # Safety
+    ///
+    /// The block must be valid for writes, aligned to `MIN_BLOCK_ALIGN` and
+    /// at least `MIN_BLOCK_SIZE` bytes long.
+    unsafe fn insert(&mut self, addr: usize, size: usize) {
+        let mut prev: *mut FreeBlock = ptr::null_mut();
+        let mut next = self.head;
+        while !next.is_null() && (next as usize) < addr {
+            prev = next;
+            next = (*next).next;
+        }
+
+        let block = addr as *mut FreeBlock;
+        block.write(FreeBlock { size, next });
+
+        // Merge with the next block.
+        if !next.is_null() && addr + size == next as usize {
+            (*block).size += (*next).size;
+            (*block).next = (*next).next;
+        }
+
+        // Merge with the previous block.
+        if prev.is_null() {
+            self.head = block;
+        } else if prev as usize + (*prev).size == addr {
+            (*prev).size += (*block).size;
+            (*prev).next = (*block).next;
+        } else {
+            (*prev).next = block;
+        }
+    }
+}
+
+/// Heap protected by a `TicketMutex`, which can be used as global
+/// allocator.
+///
+/// The allocator must not be used in interrupt context if it can be used
+/// with interrupts enabled, given that the lock is not reentrant.
+pub struct LockedHeap(TicketMutex<Heap>);
+
+impl LockedHeap {
+    /// Returns a `LockedHeap` without memory.
+    pub const fn empty() -> Self {
+        LockedHeap(TicketMutex::new(Heap::empty()))
+    }
+
+    /// Hands over the memory region of `size` bytes at `addr` to the heap.
+    ///
+    /// # Safety
+    ///
+    /// See `Heap::add_region`.
+    pub unsafe fn add_region(&self, addr: usize, size: usize) {
+        self.0.lock().add_region(addr, size);
+    }
+
+    /// Returns the total size of the memory handed over to the heap and the
+    /// number of bytes allocated.
+    pub fn stats(&self) -> (usize, usize) {
+        self.0.with(|heap| (heap.size(), heap.used()))
+    }
+}
+
+unsafe impl GlobalAlloc for LockedHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.0
+            .lock()
+            .allocate(layout)
+            .map_or(ptr::null_mut(), |ptr| ptr.as_ptr())
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if let Some(ptr) = NonNull::new(ptr) {
+            self.0.lock().deallocate(ptr, layout);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Memory handed over to the heaps in the tests.
+    #[repr(C, align(4096))]
+    struct Arena([u8; 4096]);
+
+    #[test]
+    fn test_allocate_deallocate() {
+        let mut arena = Arena([0; 4096]);
+        let base = arena.0.as_mut_ptr() as usize;
+        let mut heap = Heap::empty();
+        unsafe { heap.add_region(base, 4096) };
+        assert_eq!(heap.size(), 4096);
+
+        let layout = Layout::from_size_align(100, 8).unwrap();
+        let a = heap.allocate(layout).unwrap();
+        let b = heap.allocate(layout).unwrap();
+        assert_eq!(a.as_ptr() as usize, base);
+        assert_eq!(b.as_ptr() as usize, base + 104);
+        assert_eq!(heap.used(), 208);
+
+        unsafe { heap.deallocate(a, layout) };
+        assert_eq!(heap.allocate(layout), Some(a));
+
+        unsafe {
+            heap.deallocate(a, layout);
+            heap.deallocate(b, layout);
+        }
+        assert_eq!(heap.used(), 0);
+
+        // The freed blocks are merged back into a single block.
+        let all = Layout::from_size_align(4096, 8).unwrap();
+        assert_eq!(
+            heap.allocate(all).map(|ptr| ptr.as_ptr() as usize),
+            Some(base)
+        );
+        assert_eq!(heap.allocate(layout), None);
+    }
+
+    #[test]
+    fn test_allocate_aligned() {
+        let mut arena = Arena([0; 4096]);
+        let base = arena.0.as_mut_ptr() as usize;
+        let mut heap = Heap::empty();
+        unsafe { heap.add_region(base + 8, 4096 - 8) };
+
+        let layout = Layout::from_size_align(64, 256).unwrap();
+        let a = heap.allocate(layout).unwrap();
+        assert_eq!(a.as_ptr() as usize, base + 256);
+
+        // The memory before the aligned block is still available.
+        let small = Layout::from_size_align(16, 8).unwrap();
+        let b = heap.allocate(small).unwrap();
+        assert_eq!(b.as_ptr() as usize, base + 8);
+
+        unsafe {
+            heap.deallocate(a, layout);
+            heap.deallocate(b, small);
+        }
+        assert_eq!(heap.used(), 0);
+    }
+
+    #[test]
+    fn test_allocate_exhausted() {
+        let mut arena = Arena([0; 4096]);
+        let base = arena.0.as_mut_ptr() as usize;
+        let mut heap = Heap::empty();
+        unsafe { heap.add_region(base, 64) };
+
+        let layout = Layout::from_size_align(128, 8).unwrap();
+        assert_eq!(heap.allocate(layout), None);
+    }
+}
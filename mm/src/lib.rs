@@ -1,11 +1,53 @@
 //! Memory management library.
+//!
+//! The physical memory is accessed through a linear mapping, which maps
+//! every physical address at a fixed offset. The offset is 0 while running
+//! on the identity-mapped firmware page tables and `PHYS_MAP_BASE` once the
+//! kernel page tables are loaded.
 
 #![no_std]
 
-/// Represents a physical memory address.
-#[derive(Debug, Copy, Clone)]
-pub struct PhysAddr(pub u64);
+mod addr;
+#[cfg(feature = "heap")]
+pub mod heap;
+pub mod paging;
 
-/// Represents a virtual memory address.
-#[derive(Debug, Copy, Clone)]
-pub struct VirtAddr(pub u64);
+use core::sync::atomic::{AtomicU64, Ordering};
+
+pub use addr::{PhysAddr, PhysFrame, VirtAddr, VirtPage, PAGE_SIZE};
+
+/// Start of the linear mapping of the physical memory in the kernel page
+/// tables.
+pub const PHYS_MAP_BASE: u64 = 0xffff_8000_0000_0000;
+
+/// Offset of the linear mapping of the physical memory.
+static PHYS_OFFSET: AtomicU64 = AtomicU64::new(0);
+
+/// Returns the offset of the linear mapping of the physical memory.
+pub fn phys_offset() -> u64 {
+    PHYS_OFFSET.load(Ordering::Relaxed)
+}
+
+/// Sets the offset of the linear mapping of the physical memory.
+///
+/// # Safety
+///
+/// The physical memory must be mapped at `offset` in the current page tables
+/// of all the CPUs.
+pub unsafe fn set_phys_offset(offset: u64) {
+    PHYS_OFFSET.store(offset, Ordering::Relaxed);
+}
+
+/// Returns the virtual address of the physical address `phys` in the linear
+/// mapping of the physical memory.
+pub fn phys_to_virt(phys: PhysAddr) -> VirtAddr {
+    VirtAddr::new(phys.as_u64().wrapping_add(phys_offset()))
+}
+
+/// Returns the physical address of the virtual address `virt`, which must
+/// belong to the linear mapping of the physical memory. It returns `None`
+/// if `virt` is below the start of the mapping. Other virtual addresses must
+/// be translated with `paging::Mapper::translate`.
+pub fn virt_to_phys(virt: VirtAddr) -> Option<PhysAddr> {
+    virt.as_u64().checked_sub(phys_offset()).map(PhysAddr::new)
+}
@@ -0,0 +1,604 @@
+//! x86_64 four-level page tables.
+//!
+//! A virtual address is translated by walking four levels of page tables:
+//! PML4, PDPT, PD and PT. Every level is indexed by 9 bits of the address.
+//! The walk stops early at PDPT and PD entries with the `HUGE_PAGE` flag,
+//! which map 1GiB and 2MiB pages respectively.
+//!
+//! The page tables are accessed through their physical address plus a fixed
+//! offset, so they must be mapped at that offset. With identity mapping, the
+//! offset is 0.
+//!
+//! Reference:
+//! - Intel SDM Vol. 3A, Section 4.5 "4-Level Paging and 5-Level Paging"
+
+use core::ops::{Index, IndexMut};
+
+use crate::{PhysAddr, VirtAddr};
+
+/// Number of entries in a page table.
+pub const ENTRY_COUNT: usize = 512;
+
+/// Mask of the physical address in a page table entry.
+const ENTRY_ADDR_MASK: u64 = 0x000f_ffff_ffff_f000;
+
+/// Page table errors.
+#[derive(Debug)]
+pub enum Error {
+    /// The virtual or physical address is not aligned to the page size.
+    Unaligned,
+
+    /// The virtual address is not canonical.
+    NonCanonical,
+
+    /// The virtual address is already mapped.
+    AlreadyMapped,
+
+    /// The virtual address is not mapped.
+    NotMapped,
+
+    /// The virtual address is covered by a bigger page.
+    HugePage,
+
+    /// There is no free frame for a page table.
+    NoMemory,
+}
+
+/// Size of a page.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PageSize {
+    /// 4KiB page, mapped by a PT entry.
+    Size4KiB,
+
+    /// 2MiB page, mapped by a PD entry.
+    Size2MiB,
+
+    /// 1GiB page, mapped by a PDPT entry.
+    Size1GiB,
+}
+
+impl PageSize {
+    /// Returns the size of the page in bytes.
+    pub const fn bytes(self) -> u64 {
+        match self {
+            PageSize::Size4KiB => 0x1000,
+            PageSize::Size2MiB => 0x20_0000,
+            PageSize::Size1GiB => 0x4000_0000,
+        }
+    }
+
+    /// Returns the level of the page table entries that map pages of this
+    /// size. The PT is level 1 and the PML4 is level 4.
+    const fn level(self) -> usize {
+        match self {
+            PageSize::Size4KiB => 1,
+            PageSize::Size2MiB => 2,
+            PageSize::Size1GiB => 3,
+        }
+    }
+
+    /// Returns the size of the pages mapped by the entries of level `level`.
+    fn from_level(level: usize) -> Option<PageSize> {
+        match level {
+            1 => Some(PageSize::Size4KiB),
+            2 => Some(PageSize::Size2MiB),
+            3 => Some(PageSize::Size1GiB),
+            _ => None,
+        }
+    }
+}
+
+/// Flags of a page table entry.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct PageTableFlags(u64);
+
+impl PageTableFlags {
+    /// The entry is valid.
+    pub const PRESENT: PageTableFlags = PageTableFlags(1 << 0);
+
+    /// Writes are allowed.
+    pub const WRITABLE: PageTableFlags = PageTableFlags(1 << 1);
+
+    /// User-mode accesses are allowed.
+    pub const USER: PageTableFlags = PageTableFlags(1 << 2);
+
+    /// Write-through caching.
+    pub const WRITE_THROUGH: PageTableFlags = PageTableFlags(1 << 3);
+
+    /// Caching is disabled.
+    pub const NO_CACHE: PageTableFlags = PageTableFlags(1 << 4);
+
+    /// Set by the CPU when the entry is used in a translation.
+    pub const ACCESSED: PageTableFlags = PageTableFlags(1 << 5);
+
+    /// Set by the CPU when the page is written.
+    pub const DIRTY: PageTableFlags = PageTableFlags(1 << 6);
+
+    /// The entry maps a 2MiB or 1GiB page instead of referencing a page
+    /// table. It is only valid in PD and PDPT entries.
+    pub const HUGE_PAGE: PageTableFlags = PageTableFlags(1 << 7);
+
+    /// The translation is not flushed from the TLB on CR3 writes.
+    pub const GLOBAL: PageTableFlags = PageTableFlags(1 << 8);
+
+    /// Instruction fetches are not allowed. It requires IA32_EFER.NXE.
+    pub const NO_EXECUTE: PageTableFlags = PageTableFlags(1 << 63);
+
+    /// Returns a `PageTableFlags` from its raw representation. The bits of
+    /// the physical address are ignored.
+    pub const fn from_bits(bits: u64) -> Self {
+        PageTableFlags(bits & !ENTRY_ADDR_MASK)
+    }
+
+    /// Returns the raw representation of the `PageTableFlags`.
+    pub const fn bits(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns `true` if no flag is set.
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if all the flags in `other` are set.
+    pub const fn contains(&self, other: PageTableFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the flags that are set in `self` but not in `other`.
+    pub const fn difference(&self, other: PageTableFlags) -> Self {
+        PageTableFlags(self.0 & !other.0)
+    }
+}
+
+impl core::ops::BitOr for PageTableFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        PageTableFlags(self.0 | rhs.0)
+    }
+}
+
+impl core::ops::BitAnd for PageTableFlags {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        PageTableFlags(self.0 & rhs.0)
+    }
+}
+
+/// Represents a page table entry.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[repr(transparent)]
+pub struct PageTableEntry(u64);
+
+impl PageTableEntry {
+    /// Unused entry.
+    pub const UNUSED: PageTableEntry = PageTableEntry(0);
+
+    /// Returns a `PageTableEntry` pointing to `addr` with the flags `flags`.
+    pub const fn new(addr: PhysAddr, flags: PageTableFlags) -> Self {
+        PageTableEntry(addr.as_u64() & ENTRY_ADDR_MASK | flags.0)
+    }
+
+    /// Returns `true` if the entry is zero.
+    pub const fn is_unused(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns the physical address of the page or page table referenced by
+    /// the entry.
+    pub const fn addr(&self) -> PhysAddr {
+        PhysAddr::new(self.0 & ENTRY_ADDR_MASK)
+    }
+
+    /// Returns the flags of the entry.
+    pub const fn flags(&self) -> PageTableFlags {
+        PageTableFlags::from_bits(self.0)
+    }
+
+    /// Returns `true` if the entry is present and maps a page, instead of
+    /// referencing a page table. `level` is the level of the entry.
+    fn is_leaf(&self, level: usize) -> bool {
+        let flags = self.flags();
+        flags.contains(PageTableFlags::PRESENT)
+            && (level == 1 || flags.contains(PageTableFlags::HUGE_PAGE))
+    }
+}
+
+/// Represents a page table of any level.
+#[derive(Debug, Clone)]
+#[repr(C, align(4096))]
+pub struct PageTable {
+    entries: [PageTableEntry; ENTRY_COUNT],
+}
+
+impl PageTable {
+    /// Returns an empty `PageTable`.
+    pub const fn new() -> Self {
+        PageTable {
+            entries: [PageTableEntry::UNUSED; ENTRY_COUNT],
+        }
+    }
+
+    /// Returns an iterator over the entries of the page table.
+    pub fn iter(&self) -> core::slice::Iter<'_, PageTableEntry> {
+        self.entries.iter()
+    }
+}
+
+impl Default for PageTable {
+    fn default() -> Self {
+        PageTable::new()
+    }
+}
+
+impl Index<usize> for PageTable {
+    type Output = PageTableEntry;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.entries[index]
+    }
+}
+
+impl IndexMut<usize> for PageTable {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.entries[index]
+    }
+}
+
+/// Represents an allocator of the frames used for new page tables.
+pub trait FrameAllocator {
+    /// Allocates a 4KiB frame and returns its physical address. It returns
+    /// `None` if there are no free frames.
+    fn alloc_frame(&mut self) -> Option<PhysAddr>;
+}
+
+/// Manages the mappings of a hierarchy of page tables.
+pub struct Mapper {
+    /// Physical address of the PML4.
+    pml4: PhysAddr,
+
+    /// Offset of the virtual address of the page tables from their physical
+    /// address.
+    phys_offset: u64,
+}
+
+impl Mapper {
+    /// Returns a `Mapper` that manages the page tables whose PML4 is at the
+    /// physical address `pml4`.
+    ///
+    /// # Safety
+    ///
+    /// `pml4` must point to a valid PML4 and all the physical memory used by
+    /// the page tables must be mapped at `phys_offset`.
+    pub unsafe fn new(pml4: PhysAddr, phys_offset: u64) -> Self {
+        Mapper { pml4, phys_offset }
+    }
+
+    /// Returns the physical address of the PML4.
+    pub fn pml4(&self) -> PhysAddr {
+        self.pml4
+    }
+
+    /// Returns a pointer to the page table at the physical address `phys`.
+    fn table(&self, phys: PhysAddr) -> *mut PageTable {
+        phys.as_u64().wrapping_add(self.phys_offset) as *mut PageTable
+    }
+
+    /// Returns the leaf entry that translates the virtual address `virt` and
+    /// its level. The missing page tables are allocated from `allocator`, if
+    /// it is not `None`.
+    unsafe fn walk(
+        &self,
+        virt: VirtAddr,
+        level: usize,
+        mut allocator: Option<&mut dyn FrameAllocator>,
+        flags: PageTableFlags,
+    ) -> Result<*mut PageTableEntry, Error> {
+        if !virt.is_canonical() {
+            return Err(Error::NonCanonical);
+        }
+
+        let mut table = self.table(self.pml4);
+        for cur in (level + 1..=4).rev() {
+            let entry = &mut (&mut *table)[virt.table_index(cur)];
+            if entry.is_unused() {
+                let allocator = allocator.as_mut().ok_or(Error::NotMapped)?;
+                let frame = allocator.alloc_frame().ok_or(Error::NoMemory)?;
+                self.table(frame).write(PageTable::new());
+                *entry = PageTableEntry::new(frame, flags);
+            } else if entry.is_leaf(cur) {
+                return Err(Error::HugePage);
+            } else if !entry.flags().contains(flags) {
+                // The access rights are the intersection of the rights of
+                // all the levels, so the intermediate entries must allow
+                // the rights of any of the pages under them.
+                *entry =
+                    PageTableEntry::new(entry.addr(), entry.flags() | flags);
+            }
+            table = self.table(entry.addr());
+        }
+        Ok(&mut (&mut *table)[virt.table_index(level)])
+    }
+
+    /// Maps the page of size `size` at the virtual address `virt` to the
+    /// physical address `phys` with the flags `flags`. `PRESENT` is always
+    /// set and `HUGE_PAGE` is set depending on `size`. The missing page
+    /// tables are allocated from `allocator`.
+    ///
+    /// The TLB is not flushed, given that the virtual address was not
+    /// mapped.
+    ///
+    /// # Safety
+    ///
+    /// Mapping a page can break memory safety, e.g. by aliasing memory that
+    /// is already in use. The frames returned by `allocator` must be unused
+    /// and mapped at the offset of the `Mapper`.
+    pub unsafe fn map_to(
+        &mut self,
+        virt: VirtAddr,
+        phys: PhysAddr,
+        size: PageSize,
+        flags: PageTableFlags,
+        allocator: &mut dyn FrameAllocator,
+    ) -> Result<(), Error> {
+        if !virt.is_aligned(size.bytes()) || !phys.is_aligned(size.bytes()) {
+            return Err(Error::Unaligned);
+        }
+
+        let table_flags = PageTableFlags::PRESENT
+            | PageTableFlags::WRITABLE
+            | flags & PageTableFlags::USER;
+        let entry =
+            self.walk(virt, size.level(), Some(allocator), table_flags)?;
+        if !(*entry).is_unused() {
+            return Err(Error::AlreadyMapped);
+        }
+
+        // The bit of `HUGE_PAGE` selects the PAT entry in PT entries.
+        let mut flags = flags.difference(PageTableFlags::HUGE_PAGE)
+            | PageTableFlags::PRESENT;
+        if size != PageSize::Size4KiB {
+            flags = flags | PageTableFlags::HUGE_PAGE;
+        }
+        *entry = PageTableEntry::new(phys, flags);
+        Ok(())
+    }
+
+    /// Unmaps the page at the virtual address `virt` and returns the
+    /// physical address and the size of the page. The page tables are not
+    /// freed.
+    ///
+    /// The caller must flush the TLB entry of `virt`.
+    ///
+    /// # Safety
+    ///
+    /// The page must not be in use.
+    pub unsafe fn unmap(
+        &mut self,
+        virt: VirtAddr,
+    ) -> Result<(PhysAddr, PageSize), Error> {
+        for level in 1..=3 {
+            let entry = match self.walk(virt, level, None, PageTableFlags(0)) {
+                Ok(entry) => entry,
+                Err(Error::HugePage) => continue,
+                Err(err) => return Err(err),
+            };
+            if !(*entry).is_leaf(level) {
+                return Err(Error::NotMapped);
+            }
+
+            let size = PageSize::from_level(level).ok_or(Error::NotMapped)?;
+            if !virt.is_aligned(size.bytes()) {
+                return Err(Error::Unaligned);
+            }
+
+            let phys = (*entry).addr().align_down(size.bytes());
+            *entry = PageTableEntry::UNUSED;
+            return Ok((phys, size));
+        }
+        Err(Error::NotMapped)
+    }
+
+    /// Returns the physical address the virtual address `virt` is mapped
+    /// to, or `None` if it is not mapped.
+    pub fn translate(&self, virt: VirtAddr) -> Option<PhysAddr> {
+        if !virt.is_canonical() {
+            return None;
+        }
+
+        let mut table = self.table(self.pml4);
+        for level in (1..=4).rev() {
+            // The `Mapper` guarantees that the page tables are mapped.
+            let entry = unsafe { (&*table)[virt.table_index(level)] };
+            if !entry.flags().contains(PageTableFlags::PRESENT) {
+                return None;
+            }
+
+            if entry.is_leaf(level) {
+                let size = PageSize::from_level(level)?.bytes();
+                let offset = virt.as_u64() & (size - 1);
+                return entry.addr().align_down(size).offset(offset);
+            }
+            table = self.table(entry.addr());
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frame allocator backed by an array of page tables. It uses the
+    /// address of the page tables as their physical address.
+    struct TestAllocator<'a> {
+        tables: &'a mut [PageTable],
+        used: usize,
+    }
+
+    impl FrameAllocator for TestAllocator<'_> {
+        fn alloc_frame(&mut self) -> Option<PhysAddr> {
+            let table = self.tables.get_mut(self.used)?;
+            self.used += 1;
+            Some(PhysAddr::new(table as *mut PageTable as u64))
+        }
+    }
+
+    fn flags_rw() -> PageTableFlags {
+        PageTableFlags::PRESENT | PageTableFlags::WRITABLE
+    }
+
+    #[test]
+    fn test_entry() {
+        let entry = PageTableEntry::new(
+            PhysAddr::new(0x1234_5000),
+            PageTableFlags::PRESENT | PageTableFlags::NO_EXECUTE,
+        );
+        assert_eq!(entry.addr(), PhysAddr::new(0x1234_5000));
+        assert_eq!(
+            entry.flags(),
+            PageTableFlags::PRESENT | PageTableFlags::NO_EXECUTE
+        );
+        assert!(!entry.is_unused());
+    }
+
+    #[test]
+    fn test_map_translate_unmap() {
+        let mut pml4 = PageTable::new();
+        let mut tables =
+            [PageTable::new(), PageTable::new(), PageTable::new()];
+        let mut allocator = TestAllocator {
+            tables: &mut tables,
+            used: 0,
+        };
+        let mut mapper = unsafe {
+            Mapper::new(PhysAddr::new(&mut pml4 as *mut PageTable as u64), 0)
+        };
+
+        let virt = VirtAddr::new(0xffff_8000_0020_3000);
+        unsafe {
+            mapper
+                .map_to(
+                    virt,
+                    PhysAddr::new(0x7000),
+                    PageSize::Size4KiB,
+                    flags_rw(),
+                    &mut allocator,
+                )
+                .unwrap();
+        }
+        assert_eq!(allocator.used, 3);
+        assert_eq!(
+            mapper.translate(VirtAddr::new(virt.as_u64() + 0x123)),
+            Some(PhysAddr::new(0x7123))
+        );
+        assert_eq!(
+            mapper.translate(VirtAddr::new(virt.as_u64() + 0x1000)),
+            None
+        );
+
+        assert!(matches!(
+            unsafe {
+                mapper.map_to(
+                    virt,
+                    PhysAddr::new(0x8000),
+                    PageSize::Size4KiB,
+                    flags_rw(),
+                    &mut allocator,
+                )
+            },
+            Err(Error::AlreadyMapped)
+        ));
+
+        assert_eq!(
+            unsafe { mapper.unmap(virt).unwrap() },
+            (PhysAddr::new(0x7000), PageSize::Size4KiB)
+        );
+        assert_eq!(mapper.translate(virt), None);
+        assert!(matches!(
+            unsafe { mapper.unmap(virt) },
+            Err(Error::NotMapped)
+        ));
+    }
+
+    #[test]
+    fn test_map_huge_pages() {
+        let mut pml4 = PageTable::new();
+        let mut tables = [PageTable::new(), PageTable::new()];
+        let mut allocator = TestAllocator {
+            tables: &mut tables,
+            used: 0,
+        };
+        let mut mapper = unsafe {
+            Mapper::new(PhysAddr::new(&mut pml4 as *mut PageTable as u64), 0)
+        };
+
+        unsafe {
+            mapper
+                .map_to(
+                    VirtAddr::new(0x4000_0000),
+                    PhysAddr::new(0x1_0000_0000),
+                    PageSize::Size1GiB,
+                    flags_rw(),
+                    &mut allocator,
+                )
+                .unwrap();
+            mapper
+                .map_to(
+                    VirtAddr::new(0x20_0000),
+                    PhysAddr::new(0x60_0000),
+                    PageSize::Size2MiB,
+                    flags_rw(),
+                    &mut allocator,
+                )
+                .unwrap();
+        }
+        assert_eq!(allocator.used, 2);
+        assert_eq!(
+            mapper.translate(VirtAddr::new(0x4123_4567)),
+            Some(PhysAddr::new(0x1_0123_4567))
+        );
+        assert_eq!(
+            mapper.translate(VirtAddr::new(0x21_2345)),
+            Some(PhysAddr::new(0x61_2345))
+        );
+
+        // The 1GiB page cannot be split.
+        assert!(matches!(
+            unsafe {
+                mapper.map_to(
+                    VirtAddr::new(0x4000_1000),
+                    PhysAddr::new(0x1000),
+                    PageSize::Size4KiB,
+                    flags_rw(),
+                    &mut allocator,
+                )
+            },
+            Err(Error::HugePage)
+        ));
+        assert!(matches!(
+            unsafe {
+                mapper.map_to(
+                    VirtAddr::new(0x40_1000),
+                    PhysAddr::new(0x20_0000),
+                    PageSize::Size2MiB,
+                    flags_rw(),
+                    &mut allocator,
+                )
+            },
+            Err(Error::Unaligned)
+        ));
+
+        assert_eq!(
+            unsafe { mapper.unmap(VirtAddr::new(0x4000_0000)).unwrap() },
+            (PhysAddr::new(0x1_0000_0000), PageSize::Size1GiB)
+        );
+        assert_eq!(
+            unsafe { mapper.unmap(VirtAddr::new(0x20_0000)).unwrap() },
+            (PhysAddr::new(0x60_0000), PageSize::Size2MiB)
+        );
+        assert_eq!(mapper.translate(VirtAddr::new(0x21_2345)), None);
+    }
+}
@@ -0,0 +1,271 @@
+//! Kernel parameters.
+//!
+//! Subsystems declare their parameters as statics of one of the typed
+//! parameter types (e.g. `U64Param`). The kernel collects them into a registry
+//! and calls `parse` with the command line before initializing the
+//! subsystems.
+//!
+//! The command line is a list of whitespace-separated arguments with the form
+//! `name=value` or `name`. Values can be enclosed in double quotes in order to
+//! contain whitespace.
+
+#![no_std]
+
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use ticket_mutex::TicketMutex;
+
+/// Represents an error related to the kernel parameters.
+#[derive(Debug)]
+pub enum Error {
+    /// The value of the parameter with the provided name is not valid.
+    InvalidValue(&'static str),
+}
+
+/// A kernel parameter that can be set from the command line.
+pub trait Param: Sync {
+    /// Returns the name of the parameter (e.g. `smp.max_cpus`).
+    fn name(&self) -> &'static str;
+
+    /// Sets the parameter from its command line value. `value` is `None` if
+    /// the argument does not have a value.
+    fn set(&self, value: Option<&'static str>) -> Result<(), Error>;
+}
+
+/// Boolean kernel parameter. An argument without value sets it to `true`.
+/// Besides, `1`, `true`, `yes`, `on` and `0`, `false`, `no`, `off` are
+/// accepted.
+pub struct BoolParam {
+    name: &'static str,
+    value: AtomicBool,
+}
+
+impl BoolParam {
+    /// Returns a new `BoolParam` with the provided name and default value.
+    pub const fn new(name: &'static str, default: bool) -> Self {
+        BoolParam {
+            name,
+            value: AtomicBool::new(default),
+        }
+    }
+
+    /// Returns the value of the parameter.
+    pub fn get(&self) -> bool {
+        self.value.load(Ordering::SeqCst)
+    }
+}
+
+impl Param for BoolParam {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn set(&self, value: Option<&'static str>) -> Result<(), Error> {
+        let value = match value {
+            None | Some("1") | Some("true") | Some("yes") | Some("on") => true,
+            Some("0") | Some("false") | Some("no") | Some("off") => false,
+            _ => return Err(Error::InvalidValue(self.name)),
+        };
+        self.value.store(value, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+/// Unsigned integer kernel parameter. Values with the `0x` prefix are parsed
+/// as hexadecimal.
+pub struct U64Param {
+    name: &'static str,
+    value: AtomicU64,
+}
+
+impl U64Param {
+    /// Returns a new `U64Param` with the provided name and default value.
+    pub const fn new(name: &'static str, default: u64) -> Self {
+        U64Param {
+            name,
+            value: AtomicU64::new(default),
+        }
+    }
+
+    /// Returns the value of the parameter.
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::SeqCst)
+    }
+}
+
+impl Param for U64Param {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn set(&self, value: Option<&'static str>) -> Result<(), Error> {
+        let value = value.ok_or(Error::InvalidValue(self.name))?;
+        let value = match value.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => value.parse(),
+        }
+        .or(Err(Error::InvalidValue(self.name)))?;
+        self.value.store(value, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+/// String kernel parameter. The value borrows from the command line.
+pub struct StrParam {
+    name: &'static str,
+    value: TicketMutex<&'static str>,
+}
+
+impl StrParam {
+    /// Returns a new `StrParam` with the provided name and default value.
+    pub const fn new(name: &'static str, default: &'static str) -> Self {
+        StrParam {
+            name,
+            value: TicketMutex::new(default),
+        }
+    }
+
+    /// Returns the value of the parameter.
+    pub fn get(&self) -> &'static str {
+        *self.value.lock()
+    }
+}
+
+impl Param for StrParam {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn set(&self, value: Option<&'static str>) -> Result<(), Error> {
+        let value = value.ok_or(Error::InvalidValue(self.name))?;
+        *self.value.lock() = value;
+        Ok(())
+    }
+}
+
+/// Sets the parameters in `params` from the command line `cmdline`. Unknown
+/// arguments are ignored.
+///
+/// # Errors
+///
+/// All the arguments are processed, even if some of them are not valid. Then,
+/// the first error is returned.
+pub fn parse(
+    cmdline: &'static str,
+    params: &[&dyn Param],
+) -> Result<(), Error> {
+    let mut ret = Ok(());
+
+    for (name, value) in args(cmdline) {
+        for param in params.iter().filter(|param| param.name() == name) {
+            if let Err(err) = param.set(value) {
+                if ret.is_ok() {
+                    ret = Err(err);
+                }
+            }
+        }
+    }
+
+    ret
+}
+
+/// Returns an iterator over the `(name, value)` pairs of the arguments in the
+/// command line `cmdline`.
+pub fn args(cmdline: &str) -> Args<'_> {
+    Args { cmdline }
+}
+
+/// Iterator over the arguments of a command line.
+pub struct Args<'a> {
+    /// Remaining part of the command line.
+    cmdline: &'a str,
+}
+
+impl<'a> Iterator for Args<'a> {
+    type Item = (&'a str, Option<&'a str>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let cmdline = self.cmdline.trim_start();
+        if cmdline.is_empty() {
+            self.cmdline = cmdline;
+            return None;
+        }
+
+        // Find the end of the argument. Whitespace between double quotes is
+        // part of the argument.
+        let mut in_quotes = false;
+        let mut end = cmdline.len();
+        for (i, c) in cmdline.char_indices() {
+            if c == '"' {
+                in_quotes = !in_quotes;
+            } else if c.is_whitespace() && !in_quotes {
+                end = i;
+                break;
+            }
+        }
+        let (arg, rest) = cmdline.split_at(end);
+        self.cmdline = rest;
+
+        let ret = match arg.find('=') {
+            Some(idx) => {
+                let value = &arg[idx + 1..];
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+                    .unwrap_or(value);
+                (&arg[..idx], Some(value))
+            }
+            None => (arg, None),
+        };
+
+        Some(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_args() {
+        let mut args = args("  a b=1 c=\"x y\"  d= ");
+        assert_eq!(args.next(), Some(("a", None)));
+        assert_eq!(args.next(), Some(("b", Some("1"))));
+        assert_eq!(args.next(), Some(("c", Some("x y"))));
+        assert_eq!(args.next(), Some(("d", Some(""))));
+        assert_eq!(args.next(), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        static MAX_CPUS: U64Param = U64Param::new("smp.max_cpus", 0);
+        static LEVEL: StrParam = StrParam::new("log.level", "info");
+        static NOSMP: BoolParam = BoolParam::new("nosmp", false);
+        static VERBOSE: BoolParam = BoolParam::new("verbose", true);
+
+        parse(
+            "smp.max_cpus=0x10 unknown=1 nosmp log.level=debug verbose=off",
+            &[&MAX_CPUS, &LEVEL, &NOSMP, &VERBOSE],
+        )
+        .unwrap();
+
+        assert_eq!(MAX_CPUS.get(), 16);
+        assert_eq!(LEVEL.get(), "debug");
+        assert!(NOSMP.get());
+        assert!(!VERBOSE.get());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        static A: U64Param = U64Param::new("a", 1);
+        static B: U64Param = U64Param::new("b", 2);
+
+        match parse("a=x b=3", &[&A, &B]) {
+            Err(Error::InvalidValue("a")) => {}
+            ret => panic!("unexpected result: {:?}", ret),
+        }
+
+        assert_eq!(A.get(), 1);
+        assert_eq!(B.get(), 3);
+    }
+}
@@ -0,0 +1,223 @@
+//! Per-CPU data.
+//!
+//! Every CPU has a `CpuArea` pointed by its GS base, which is set up by
+//! `init`. The area holds the index of the CPU, so it can be read with a
+//! single `gs`-relative load. A `PerCpu` stores one slot per CPU and uses
+//! this index to select the slot of the current CPU. The area also holds the
+//! stack pointers used by the kernel entry code, which runs before a kernel
+//! stack is available (see `KERNEL_RSP_OFFSET` and `USER_RSP_OFFSET`).
+//!
+//! `init` must be called on every CPU before accessing per-CPU data. The
+//! bootstrap processor is CPU 0 and the application processors must call it
+//! as part of their bring-up.
+//!
+//! User mode has its own GS base, which is kept in IA32_KERNEL_GS_BASE while
+//! the kernel runs. The kernel entry and exit paths exchange both with
+//! `swapgs`, so the GS base points to the per-CPU area while the kernel runs
+//! and to the GS base of user mode while user mode runs.
+
+#![no_std]
+#![feature(asm)]
+
+use core::{mem, ptr};
+
+/// Maximum number of CPUs supported.
+pub const MAX_CPUS: usize = 64;
+
+/// Offset of the top of the kernel stack in the per-CPU area. It is set by
+/// `set_kernel_rsp`.
+pub const KERNEL_RSP_OFFSET: usize = 8;
+
+/// Offset of the slot where the kernel entry code saves the user stack
+/// pointer in the per-CPU area.
+pub const USER_RSP_OFFSET: usize = 16;
+
+/// IA32_GS_BASE MSR.
+#[cfg(target_arch = "x86_64")]
+const IA32_GS_BASE: u32 = 0xc000_0101;
+
+/// IA32_KERNEL_GS_BASE MSR.
+#[cfg(target_arch = "x86_64")]
+const IA32_KERNEL_GS_BASE: u32 = 0xc000_0102;
+
+/// Error representing that the CPU index is not lower than `MAX_CPUS`.
+#[derive(Debug)]
+pub struct Error;
+
+/// Per-CPU area pointed by the GS base of every CPU. The kernel entry code
+/// accesses its fields at fixed offsets, so their order must not change.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct CpuArea {
+    /// Index of the CPU. It must be the first field, given that `cpu_index`
+    /// reads it at offset 0 of the GS segment.
+    index: usize,
+
+    /// Top of the kernel stack loaded by the kernel entry code. It is at
+    /// `KERNEL_RSP_OFFSET`.
+    // It is only read by the kernel entry code.
+    #[allow(dead_code)]
+    kernel_rsp: u64,
+
+    /// User stack pointer saved by the kernel entry code. It is at
+    /// `USER_RSP_OFFSET`.
+    // It is only accessed by the kernel entry code.
+    #[allow(dead_code)]
+    user_rsp: u64,
+}
+
+/// Per-CPU areas. The area at index `n` belongs to CPU `n`. They are
+/// mutable, given that the kernel entry code stores the user stack pointer
+/// in them. Every CPU only accesses its own area.
+static mut AREAS: [CpuArea; MAX_CPUS] = areas();
+
+/// Returns the per-CPU areas initialized with their index.
+const fn areas() -> [CpuArea; MAX_CPUS] {
+    let mut areas = [CpuArea {
+        index: 0,
+        kernel_rsp: 0,
+        user_rsp: 0,
+    }; MAX_CPUS];
+    let mut i = 0;
+    while i < MAX_CPUS {
+        areas[i].index = i;
+        i += 1;
+    }
+    areas
+}
+
+/// Sets up the per-CPU area of the current CPU, which is identified by
+/// `index`.
+///
+/// # Safety
+///
+/// This function overwrites the GS base of the current CPU, so the caller
+/// must ensure that nothing else uses it. Every CPU must be initialized with
+/// a different index.
+#[cfg(target_arch = "x86_64")]
+pub unsafe fn init(index: usize) -> Result<(), Error> {
+    if index >= MAX_CPUS {
+        return Err(Error);
+    }
+    let area = ptr::addr_of!(AREAS[index]);
+    cpu::wrmsr(IA32_GS_BASE, area as u64);
+    // User mode starts with a null GS base.
+    cpu::wrmsr(IA32_KERNEL_GS_BASE, 0);
+    Ok(())
+}
+
+/// Returns `true` if the GS base of the current CPU points to a per-CPU
+/// area. It is `false` if the CPU was interrupted in user mode or in a kernel
+/// entry or exit path before `swapgs`.
+///
+/// User mode cannot point the GS base to a per-CPU area. `wrgsbase` is not
+/// enabled and loading a segment selector sets the GS base to the base of
+/// its descriptor, which is 0.
+#[cfg(target_arch = "x86_64")]
+pub fn is_area_loaded() -> bool {
+    let (base, start) =
+        unsafe { (cpu::rdmsr(IA32_GS_BASE), ptr::addr_of!(AREAS) as u64) };
+    let end = start + mem::size_of::<[CpuArea; MAX_CPUS]>() as u64;
+    (start..end).contains(&base)
+}
+
+/// Returns the index of the current CPU.
+///
+/// # Safety
+///
+/// `init` must have been called on the current CPU and the GS base must
+/// point to its per-CPU area (see `is_area_loaded`). Otherwise, the index is
+/// read from arbitrary memory.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn cpu_index() -> usize {
+    let index: usize;
+    asm!(
+        "mov {}, gs:[0]",
+        out(reg) index,
+        options(nostack, readonly, preserves_flags),
+    );
+    index
+}
+
+/// Sets the top of the kernel stack loaded by the kernel entry code of the
+/// current CPU.
+///
+/// # Safety
+///
+/// The same requirements as `cpu_index` apply.
+#[cfg(target_arch = "x86_64")]
+pub unsafe fn set_kernel_rsp(top: u64) {
+    // Every CPU only accesses its own area.
+    let area = ptr::addr_of_mut!(AREAS[cpu_index()]);
+    (*area).kernel_rsp = top;
+}
+
+/// Represents a value of type `T` with a separate instance for every CPU.
+///
+/// A `PerCpu` can only be shared if `T` is `Sync`, given that the instance
+/// of a CPU can be accessed by its interrupt handlers while it is in use.
+pub struct PerCpu<T> {
+    /// Instances of the value. The slot at index `n` belongs to CPU `n`.
+    slots: [T; MAX_CPUS],
+}
+
+impl<T> PerCpu<T> {
+    /// Returns a `PerCpu` with the given instances, one per CPU.
+    pub const fn new(slots: [T; MAX_CPUS]) -> Self {
+        PerCpu { slots }
+    }
+
+    /// Returns the instance of the current CPU.
+    ///
+    /// # Safety
+    ///
+    /// The same requirements as `cpu_index` apply.
+    #[cfg(target_arch = "x86_64")]
+    #[inline]
+    pub unsafe fn get(&self) -> &T {
+        &self.slots[cpu_index()]
+    }
+
+    /// Returns an iterator over the instances of all CPUs, sorted by CPU
+    /// index.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.slots.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_areas_index() {
+        let areas = areas();
+        assert!(areas.iter().enumerate().all(|(i, area)| area.index == i));
+    }
+
+    #[test]
+    fn test_area_offsets() {
+        let area = areas()[0];
+        let base = ptr::addr_of!(area) as usize;
+        let kernel_rsp = ptr::addr_of!(area.kernel_rsp) as usize;
+        let user_rsp = ptr::addr_of!(area.user_rsp) as usize;
+        assert_eq!(kernel_rsp - base, KERNEL_RSP_OFFSET);
+        assert_eq!(user_rsp - base, USER_RSP_OFFSET);
+    }
+
+    #[test]
+    fn test_percpu_iter() {
+        let mut slots = [0usize; MAX_CPUS];
+        for (i, slot) in slots.iter_mut().enumerate() {
+            *slot = i;
+        }
+        let percpu = PerCpu::new(slots);
+
+        assert_eq!(percpu.iter().count(), MAX_CPUS);
+        assert_eq!(
+            percpu.iter().sum::<usize>(),
+            MAX_CPUS * (MAX_CPUS - 1) / 2
+        );
+    }
+}
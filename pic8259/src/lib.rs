@@ -0,0 +1,152 @@
+//! Legacy 8259 Programmable Interrupt Controllers (PIC).
+//!
+//! PC compatible systems have two cascaded 8259 PICs. The slave PIC is
+//! connected to the IRQ 2 line of the master, so together they handle the 16
+//! ISA IRQs. At reset, the master PIC delivers its IRQs at vectors 8 to 15,
+//! which overlap with the CPU exceptions. So, the PICs must be remapped
+//! before enabling the interrupts, even if they are not used.
+//!
+//! Masked PICs can still deliver spurious interrupts on IRQ 7 and IRQ 15.
+//! Their handlers must check `is_spurious` before sending an EOI.
+//!
+//! Reference:
+//! - [OSDev article](https://wiki.osdev.org/8259_PIC)
+
+#![no_std]
+
+use cpu::{in8, out8};
+
+/// Master PIC command port.
+const MASTER_COMMAND: u16 = 0x20;
+
+/// Master PIC data port.
+const MASTER_DATA: u16 = 0x21;
+
+/// Slave PIC command port.
+const SLAVE_COMMAND: u16 = 0xa0;
+
+/// Slave PIC data port.
+const SLAVE_DATA: u16 = 0xa1;
+
+/// Unused port used to wait for the PICs to process a command.
+const WAIT_PORT: u16 = 0x80;
+
+/// ICW1: initialization, ICW4 needed.
+const ICW1_INIT: u8 = 0x11;
+
+/// ICW4: 8086/88 mode.
+const ICW4_8086: u8 = 0x01;
+
+/// OCW2: non-specific end of interrupt.
+const OCW2_EOI: u8 = 0x20;
+
+/// OCW3: read the In-Service Register.
+const OCW3_READ_ISR: u8 = 0x0b;
+
+/// IRQ line of the master PIC where the slave PIC is connected.
+pub const CASCADE_IRQ: u8 = 2;
+
+/// Number of IRQs handled by the PICs.
+pub const IRQS: u8 = 16;
+
+/// Remaps the PICs, so IRQ `n` is delivered at vector `vector_base + n`, and
+/// masks all the IRQs. `vector_base` must be a multiple of 8.
+///
+/// # Safety
+///
+/// The caller must ensure that nothing else is accessing the PICs.
+pub unsafe fn init(vector_base: u8) {
+    // ICW1: start the initialization sequence.
+    write(MASTER_COMMAND, ICW1_INIT);
+    write(SLAVE_COMMAND, ICW1_INIT);
+
+    // ICW2: vector offsets.
+    write(MASTER_DATA, vector_base);
+    write(SLAVE_DATA, vector_base + 8);
+
+    // ICW3: the master has a slave on IRQ 2 and the slave has cascade
+    // identity 2.
+    write(MASTER_DATA, 1 << CASCADE_IRQ);
+    write(SLAVE_DATA, CASCADE_IRQ);
+
+    // ICW4: 8086 mode.
+    write(MASTER_DATA, ICW4_8086);
+    write(SLAVE_DATA, ICW4_8086);
+
+    mask_all();
+}
+
+/// Masks all the IRQs.
+///
+/// # Safety
+///
+/// The caller must ensure that nothing else is accessing the PICs.
+pub unsafe fn mask_all() {
+    write(MASTER_DATA, 0xff);
+    write(SLAVE_DATA, 0xff);
+}
+
+/// Masks or unmasks the IRQ `irq`. Unmasking an IRQ of the slave PIC also
+/// unmasks the cascade IRQ of the master.
+///
+/// # Safety
+///
+/// The caller must ensure that nothing else is accessing the PICs and that
+/// the vector of the IRQ has a handler.
+pub unsafe fn set_masked(irq: u8, masked: bool) {
+    let (port, line) = if irq < 8 {
+        (MASTER_DATA, irq)
+    } else {
+        (SLAVE_DATA, irq - 8)
+    };
+
+    let mask = in8(port);
+    let mask = if masked {
+        mask | 1 << line
+    } else {
+        mask & !(1 << line)
+    };
+    write(port, mask);
+
+    if irq >= 8 && !masked {
+        set_masked(CASCADE_IRQ, false);
+    }
+}
+
+/// Returns `true` if the interrupt received on `irq` is spurious. Only IRQ 7
+/// and IRQ 15 can be spurious. The handler of a spurious interrupt must not
+/// send an EOI for it, but a spurious IRQ 15 still requires an EOI for the
+/// cascade IRQ of the master.
+///
+/// # Safety
+///
+/// The caller must ensure that nothing else is accessing the PICs.
+pub unsafe fn is_spurious(irq: u8) -> bool {
+    let (port, line) = match irq {
+        7 => (MASTER_COMMAND, 7),
+        15 => (SLAVE_COMMAND, 7),
+        _ => return false,
+    };
+
+    // A real interrupt is in service. A spurious one is not.
+    out8(port, OCW3_READ_ISR);
+    in8(port) & 1 << line == 0
+}
+
+/// Sends an End Of Interrupt (EOI) for `irq`.
+///
+/// # Safety
+///
+/// The caller must ensure that nothing else is accessing the PICs.
+pub unsafe fn end_of_interrupt(irq: u8) {
+    if irq >= 8 {
+        out8(SLAVE_COMMAND, OCW2_EOI);
+    }
+    out8(MASTER_COMMAND, OCW2_EOI);
+}
+
+/// Writes `val` into `port` and waits for the PIC to process it.
+unsafe fn write(port: u16, val: u8) {
+    out8(port, val);
+    out8(WAIT_PORT, 0);
+}
@@ -0,0 +1,709 @@
+//! Keyboard scancode decoding.
+//!
+//! A `Keyboard` decodes the bytes received from a PS/2 keyboard, in scancode
+//! set 1 or 2, into key events. It keeps track of the modifier keys, so the
+//! events can be translated to characters using the US layout.
+
+use core::ops::BitOr;
+
+/// Scancode set sent by the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScancodeSet {
+    /// Scancode set 1, the one of the IBM PC XT. The controller translates
+    /// set 2 to set 1 when translation is enabled.
+    Set1,
+
+    /// Scancode set 2, the default one of the keyboards.
+    Set2,
+}
+
+impl ScancodeSet {
+    /// Number of bytes that follow the `0xe1` prefix of the Pause key.
+    fn pause_len(self) -> u8 {
+        match self {
+            ScancodeSet::Set1 => 5,
+            ScancodeSet::Set2 => 7,
+        }
+    }
+}
+
+/// Prefix of the extended scancodes.
+const PREFIX_EXTENDED: u8 = 0xe0;
+
+/// Prefix of the Pause key sequence.
+const PREFIX_PAUSE: u8 = 0xe1;
+
+/// Prefix of the break codes in scancode set 2.
+const PREFIX_BREAK: u8 = 0xf0;
+
+/// Set 1 bit that marks a break code.
+const SET1_BREAK: u8 = 0x80;
+
+/// Identifies a physical key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyCode {
+    Escape,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    Backtick,
+    Key1,
+    Key2,
+    Key3,
+    Key4,
+    Key5,
+    Key6,
+    Key7,
+    Key8,
+    Key9,
+    Key0,
+    Minus,
+    Equals,
+    Backspace,
+    Tab,
+    Q,
+    W,
+    E,
+    R,
+    T,
+    Y,
+    U,
+    I,
+    O,
+    P,
+    LeftBracket,
+    RightBracket,
+    Backslash,
+    CapsLock,
+    A,
+    S,
+    D,
+    F,
+    G,
+    H,
+    J,
+    K,
+    L,
+    Semicolon,
+    Quote,
+    Enter,
+    LeftShift,
+    Z,
+    X,
+    C,
+    V,
+    B,
+    N,
+    M,
+    Comma,
+    Period,
+    Slash,
+    RightShift,
+    LeftCtrl,
+    LeftAlt,
+    Space,
+    RightAlt,
+    RightCtrl,
+    Insert,
+    Delete,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Up,
+    Down,
+    Left,
+    Right,
+    NumLock,
+    ScrollLock,
+    Pause,
+
+    /// Key without `KeyCode`. It holds the make code, with the `0xe0` prefix
+    /// in the high byte if it is extended.
+    Unknown(u16),
+}
+
+/// State of the modifier keys when a key event is generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Modifiers(u8);
+
+impl Modifiers {
+    /// A shift key is pressed.
+    pub const SHIFT: Modifiers = Modifiers(1 << 0);
+
+    /// A control key is pressed.
+    pub const CTRL: Modifiers = Modifiers(1 << 1);
+
+    /// An alt key is pressed.
+    pub const ALT: Modifiers = Modifiers(1 << 2);
+
+    /// Caps lock is on.
+    pub const CAPS_LOCK: Modifiers = Modifiers(1 << 3);
+
+    /// Returns a `Modifiers` with the raw value `bits`.
+    pub const fn from_bits(bits: u8) -> Self {
+        Modifiers(bits)
+    }
+
+    /// Returns the raw value of the modifiers.
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    /// Returns `true` if all the modifiers in `other` are set.
+    pub fn contains(&self, other: Modifiers) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Sets or clears the modifiers in `other`.
+    fn set(&mut self, other: Modifiers, value: bool) {
+        if value {
+            self.0 |= other.0;
+        } else {
+            self.0 &= !other.0;
+        }
+    }
+}
+
+impl BitOr for Modifiers {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Modifiers(self.0 | rhs.0)
+    }
+}
+
+/// Represents a key being pressed or released.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    /// Key.
+    pub code: KeyCode,
+
+    /// `true` if the key was pressed and `false` if it was released.
+    pub pressed: bool,
+
+    /// Modifiers after processing the event.
+    pub modifiers: Modifiers,
+}
+
+impl KeyEvent {
+    /// Returns the character typed by the event with the US layout, or
+    /// `None` if the key does not type a character or it was released. The
+    /// control key combined with a letter types the corresponding ASCII
+    /// control character.
+    pub fn char(&self) -> Option<char> {
+        if !self.pressed {
+            return None;
+        }
+
+        let shift = self.modifiers.contains(Modifiers::SHIFT);
+        if let Some(c) = letter(self.code) {
+            if self.modifiers.contains(Modifiers::CTRL) {
+                return Some((c as u8 - b'a' + 1) as char);
+            }
+            let upper = shift != self.modifiers.contains(Modifiers::CAPS_LOCK);
+            return Some(if upper { c.to_ascii_uppercase() } else { c });
+        }
+
+        let (normal, shifted) = match self.code {
+            KeyCode::Backtick => ('`', '~'),
+            KeyCode::Key1 => ('1', '!'),
+            KeyCode::Key2 => ('2', '@'),
+            KeyCode::Key3 => ('3', '#'),
+            KeyCode::Key4 => ('4', '$'),
+            KeyCode::Key5 => ('5', '%'),
+            KeyCode::Key6 => ('6', '^'),
+            KeyCode::Key7 => ('7', '&'),
+            KeyCode::Key8 => ('8', '*'),
+            KeyCode::Key9 => ('9', '('),
+            KeyCode::Key0 => ('0', ')'),
+            KeyCode::Minus => ('-', '_'),
+            KeyCode::Equals => ('=', '+'),
+            KeyCode::LeftBracket => ('[', '{'),
+            KeyCode::RightBracket => (']', '}'),
+            KeyCode::Backslash => ('\\', '|'),
+            KeyCode::Semicolon => (';', ':'),
+            KeyCode::Quote => ('\'', '"'),
+            KeyCode::Comma => (',', '<'),
+            KeyCode::Period => ('.', '>'),
+            KeyCode::Slash => ('/', '?'),
+            KeyCode::Space => (' ', ' '),
+            KeyCode::Tab => ('\t', '\t'),
+            KeyCode::Enter => ('\n', '\n'),
+            KeyCode::Backspace => ('\x08', '\x08'),
+            KeyCode::Escape => ('\x1b', '\x1b'),
+            _ => return None,
+        };
+        Some(if shift { shifted } else { normal })
+    }
+}
+
+/// Decodes the scancodes sent by a keyboard into key events.
+#[derive(Debug)]
+pub struct Keyboard {
+    /// Scancode set sent by the keyboard.
+    set: ScancodeSet,
+
+    /// The previous byte was the `0xe0` prefix.
+    extended: bool,
+
+    /// The previous byte was the set 2 break prefix.
+    release: bool,
+
+    /// Number of bytes of the Pause sequence still to be received.
+    pause: u8,
+
+    /// Shift keys that are pressed. The modifiers are set while any of the
+    /// keys is pressed.
+    shift: [bool; 2],
+
+    /// Control keys that are pressed.
+    ctrl: [bool; 2],
+
+    /// Alt keys that are pressed.
+    alt: [bool; 2],
+
+    /// Caps lock is pressed.
+    caps_held: bool,
+
+    /// Current modifiers.
+    modifiers: Modifiers,
+}
+
+impl Keyboard {
+    /// Returns a `Keyboard` that decodes the scancode set `set`.
+    pub const fn new(set: ScancodeSet) -> Self {
+        Keyboard {
+            set,
+            extended: false,
+            release: false,
+            pause: 0,
+            shift: [false; 2],
+            ctrl: [false; 2],
+            alt: [false; 2],
+            caps_held: false,
+            modifiers: Modifiers::from_bits(0),
+        }
+    }
+
+    /// Processes the byte `byte` received from the keyboard. It returns the
+    /// key event completed by the byte, if any.
+    pub fn push(&mut self, byte: u8) -> Option<KeyEvent> {
+        // The Pause key only has a make code, which is a sequence that would
+        // be decoded as other keys.
+        if self.pause > 0 {
+            self.pause -= 1;
+            if self.pause > 0 {
+                return None;
+            }
+            return Some(self.event(KeyCode::Pause, true));
+        }
+
+        match byte {
+            PREFIX_EXTENDED => {
+                self.extended = true;
+                return None;
+            }
+            PREFIX_PAUSE => {
+                self.pause = self.set.pause_len();
+                return None;
+            }
+            PREFIX_BREAK if self.set == ScancodeSet::Set2 => {
+                self.release = true;
+                return None;
+            }
+            // Errors and command responses.
+            0x00 | 0xfa | 0xfe | 0xff => return None,
+            _ => {}
+        }
+
+        let extended = core::mem::replace(&mut self.extended, false);
+        let (code, pressed) = match self.set {
+            ScancodeSet::Set1 => (
+                set1_code(byte & !SET1_BREAK, extended)?,
+                byte & SET1_BREAK == 0,
+            ),
+            ScancodeSet::Set2 => (
+                set2_code(byte, extended)?,
+                !core::mem::replace(&mut self.release, false),
+            ),
+        };
+
+        self.update_modifiers(code, pressed);
+        Some(self.event(code, pressed))
+    }
+
+    /// Returns the event of the key `code`.
+    fn event(&self, code: KeyCode, pressed: bool) -> KeyEvent {
+        KeyEvent {
+            code,
+            pressed,
+            modifiers: self.modifiers,
+        }
+    }
+
+    /// Updates the modifiers with the key `code`.
+    fn update_modifiers(&mut self, code: KeyCode, pressed: bool) {
+        let (keys, modifier, idx) = match code {
+            KeyCode::LeftShift => (&mut self.shift, Modifiers::SHIFT, 0),
+            KeyCode::RightShift => (&mut self.shift, Modifiers::SHIFT, 1),
+            KeyCode::LeftCtrl => (&mut self.ctrl, Modifiers::CTRL, 0),
+            KeyCode::RightCtrl => (&mut self.ctrl, Modifiers::CTRL, 1),
+            KeyCode::LeftAlt => (&mut self.alt, Modifiers::ALT, 0),
+            KeyCode::RightAlt => (&mut self.alt, Modifiers::ALT, 1),
+            KeyCode::CapsLock => {
+                // The typematic repeat sends make codes while the key is
+                // held, so only the first one toggles caps lock.
+                if pressed && !self.caps_held {
+                    let caps = self.modifiers.contains(Modifiers::CAPS_LOCK);
+                    self.modifiers.set(Modifiers::CAPS_LOCK, !caps);
+                }
+                self.caps_held = pressed;
+                return;
+            }
+            _ => return,
+        };
+        keys[idx] = pressed;
+        let held = keys[0] || keys[1];
+        self.modifiers.set(modifier, held);
+    }
+}
+
+/// Returns the lowercase letter of the key `code`, if it is a letter key.
+fn letter(code: KeyCode) -> Option<char> {
+    let c = match code {
+        KeyCode::A => 'a',
+        KeyCode::B => 'b',
+        KeyCode::C => 'c',
+        KeyCode::D => 'd',
+        KeyCode::E => 'e',
+        KeyCode::F => 'f',
+        KeyCode::G => 'g',
+        KeyCode::H => 'h',
+        KeyCode::I => 'i',
+        KeyCode::J => 'j',
+        KeyCode::K => 'k',
+        KeyCode::L => 'l',
+        KeyCode::M => 'm',
+        KeyCode::N => 'n',
+        KeyCode::O => 'o',
+        KeyCode::P => 'p',
+        KeyCode::Q => 'q',
+        KeyCode::R => 'r',
+        KeyCode::S => 's',
+        KeyCode::T => 't',
+        KeyCode::U => 'u',
+        KeyCode::V => 'v',
+        KeyCode::W => 'w',
+        KeyCode::X => 'x',
+        KeyCode::Y => 'y',
+        KeyCode::Z => 'z',
+        _ => return None,
+    };
+    Some(c)
+}
+
+/// Returns the key of the set 1 make code `code`. It returns `None` for the
+/// fake shifts that surround some extended keys.
+fn set1_code(code: u8, extended: bool) -> Option<KeyCode> {
+    let key = if extended {
+        match code {
+            0x1c => KeyCode::Enter,
+            0x1d => KeyCode::RightCtrl,
+            0x35 => KeyCode::Slash,
+            0x38 => KeyCode::RightAlt,
+            0x47 => KeyCode::Home,
+            0x48 => KeyCode::Up,
+            0x49 => KeyCode::PageUp,
+            0x4b => KeyCode::Left,
+            0x4d => KeyCode::Right,
+            0x4f => KeyCode::End,
+            0x50 => KeyCode::Down,
+            0x51 => KeyCode::PageDown,
+            0x52 => KeyCode::Insert,
+            0x53 => KeyCode::Delete,
+            0x2a | 0x36 => return None,
+            _ => KeyCode::Unknown(0xe000 | u16::from(code)),
+        }
+    } else {
+        match code {
+            0x01 => KeyCode::Escape,
+            0x02 => KeyCode::Key1,
+            0x03 => KeyCode::Key2,
+            0x04 => KeyCode::Key3,
+            0x05 => KeyCode::Key4,
+            0x06 => KeyCode::Key5,
+            0x07 => KeyCode::Key6,
+            0x08 => KeyCode::Key7,
+            0x09 => KeyCode::Key8,
+            0x0a => KeyCode::Key9,
+            0x0b => KeyCode::Key0,
+            0x0c => KeyCode::Minus,
+            0x0d => KeyCode::Equals,
+            0x0e => KeyCode::Backspace,
+            0x0f => KeyCode::Tab,
+            0x10 => KeyCode::Q,
+            0x11 => KeyCode::W,
+            0x12 => KeyCode::E,
+            0x13 => KeyCode::R,
+            0x14 => KeyCode::T,
+            0x15 => KeyCode::Y,
+            0x16 => KeyCode::U,
+            0x17 => KeyCode::I,
+            0x18 => KeyCode::O,
+            0x19 => KeyCode::P,
+            0x1a => KeyCode::LeftBracket,
+            0x1b => KeyCode::RightBracket,
+            0x1c => KeyCode::Enter,
+            0x1d => KeyCode::LeftCtrl,
+            0x1e => KeyCode::A,
+            0x1f => KeyCode::S,
+            0x20 => KeyCode::D,
+            0x21 => KeyCode::F,
+            0x22 => KeyCode::G,
+            0x23 => KeyCode::H,
+            0x24 => KeyCode::J,
+            0x25 => KeyCode::K,
+            0x26 => KeyCode::L,
+            0x27 => KeyCode::Semicolon,
+            0x28 => KeyCode::Quote,
+            0x29 => KeyCode::Backtick,
+            0x2a => KeyCode::LeftShift,
+            0x2b => KeyCode::Backslash,
+            0x2c => KeyCode::Z,
+            0x2d => KeyCode::X,
+            0x2e => KeyCode::C,
+            0x2f => KeyCode::V,
+            0x30 => KeyCode::B,
+            0x31 => KeyCode::N,
+            0x32 => KeyCode::M,
+            0x33 => KeyCode::Comma,
+            0x34 => KeyCode::Period,
+            0x35 => KeyCode::Slash,
+            0x36 => KeyCode::RightShift,
+            0x38 => KeyCode::LeftAlt,
+            0x39 => KeyCode::Space,
+            0x3a => KeyCode::CapsLock,
+            0x3b => KeyCode::F1,
+            0x3c => KeyCode::F2,
+            0x3d => KeyCode::F3,
+            0x3e => KeyCode::F4,
+            0x3f => KeyCode::F5,
+            0x40 => KeyCode::F6,
+            0x41 => KeyCode::F7,
+            0x42 => KeyCode::F8,
+            0x43 => KeyCode::F9,
+            0x44 => KeyCode::F10,
+            0x45 => KeyCode::NumLock,
+            0x46 => KeyCode::ScrollLock,
+            0x57 => KeyCode::F11,
+            0x58 => KeyCode::F12,
+            _ => KeyCode::Unknown(u16::from(code)),
+        }
+    };
+    Some(key)
+}
+
+/// Returns the key of the set 2 make code `code`. It returns `None` for the
+/// fake shifts that surround some extended keys.
+fn set2_code(code: u8, extended: bool) -> Option<KeyCode> {
+    let key = if extended {
+        match code {
+            0x5a => KeyCode::Enter,
+            0x14 => KeyCode::RightCtrl,
+            0x4a => KeyCode::Slash,
+            0x11 => KeyCode::RightAlt,
+            0x6c => KeyCode::Home,
+            0x75 => KeyCode::Up,
+            0x7d => KeyCode::PageUp,
+            0x6b => KeyCode::Left,
+            0x74 => KeyCode::Right,
+            0x69 => KeyCode::End,
+            0x72 => KeyCode::Down,
+            0x7a => KeyCode::PageDown,
+            0x70 => KeyCode::Insert,
+            0x71 => KeyCode::Delete,
+            0x12 | 0x59 => return None,
+            _ => KeyCode::Unknown(0xe000 | u16::from(code)),
+        }
+    } else {
+        match code {
+            0x76 => KeyCode::Escape,
+            0x16 => KeyCode::Key1,
+            0x1e => KeyCode::Key2,
+            0x26 => KeyCode::Key3,
+            0x25 => KeyCode::Key4,
+            0x2e => KeyCode::Key5,
+            0x36 => KeyCode::Key6,
+            0x3d => KeyCode::Key7,
+            0x3e => KeyCode::Key8,
+            0x46 => KeyCode::Key9,
+            0x45 => KeyCode::Key0,
+            0x4e => KeyCode::Minus,
+            0x55 => KeyCode::Equals,
+            0x66 => KeyCode::Backspace,
+            0x0d => KeyCode::Tab,
+            0x15 => KeyCode::Q,
+            0x1d => KeyCode::W,
+            0x24 => KeyCode::E,
+            0x2d => KeyCode::R,
+            0x2c => KeyCode::T,
+            0x35 => KeyCode::Y,
+            0x3c => KeyCode::U,
+            0x43 => KeyCode::I,
+            0x44 => KeyCode::O,
+            0x4d => KeyCode::P,
+            0x54 => KeyCode::LeftBracket,
+            0x5b => KeyCode::RightBracket,
+            0x5a => KeyCode::Enter,
+            0x14 => KeyCode::LeftCtrl,
+            0x1c => KeyCode::A,
+            0x1b => KeyCode::S,
+            0x23 => KeyCode::D,
+            0x2b => KeyCode::F,
+            0x34 => KeyCode::G,
+            0x33 => KeyCode::H,
+            0x3b => KeyCode::J,
+            0x42 => KeyCode::K,
+            0x4b => KeyCode::L,
+            0x4c => KeyCode::Semicolon,
+            0x52 => KeyCode::Quote,
+            0x0e => KeyCode::Backtick,
+            0x12 => KeyCode::LeftShift,
+            0x5d => KeyCode::Backslash,
+            0x1a => KeyCode::Z,
+            0x22 => KeyCode::X,
+            0x21 => KeyCode::C,
+            0x2a => KeyCode::V,
+            0x32 => KeyCode::B,
+            0x31 => KeyCode::N,
+            0x3a => KeyCode::M,
+            0x41 => KeyCode::Comma,
+            0x49 => KeyCode::Period,
+            0x4a => KeyCode::Slash,
+            0x59 => KeyCode::RightShift,
+            0x11 => KeyCode::LeftAlt,
+            0x29 => KeyCode::Space,
+            0x58 => KeyCode::CapsLock,
+            0x05 => KeyCode::F1,
+            0x06 => KeyCode::F2,
+            0x04 => KeyCode::F3,
+            0x0c => KeyCode::F4,
+            0x03 => KeyCode::F5,
+            0x0b => KeyCode::F6,
+            0x83 => KeyCode::F7,
+            0x0a => KeyCode::F8,
+            0x01 => KeyCode::F9,
+            0x09 => KeyCode::F10,
+            0x78 => KeyCode::F11,
+            0x07 => KeyCode::F12,
+            0x77 => KeyCode::NumLock,
+            0x7e => KeyCode::ScrollLock,
+            _ => KeyCode::Unknown(u16::from(code)),
+        }
+    };
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds `bytes` into `keyboard` and returns the last event.
+    fn feed(keyboard: &mut Keyboard, bytes: &[u8]) -> Option<KeyEvent> {
+        bytes.iter().fold(None, |_, &b| keyboard.push(b))
+    }
+
+    #[test]
+    fn test_set1() {
+        let mut kbd = Keyboard::new(ScancodeSet::Set1);
+
+        let event = kbd.push(0x1e).unwrap();
+        assert_eq!((event.code, event.pressed), (KeyCode::A, true));
+        assert_eq!(event.char(), Some('a'));
+
+        let event = kbd.push(0x9e).unwrap();
+        assert_eq!((event.code, event.pressed), (KeyCode::A, false));
+        assert_eq!(event.char(), None);
+
+        let event = feed(&mut kbd, &[0xe0, 0x48]).unwrap();
+        assert_eq!((event.code, event.pressed), (KeyCode::Up, true));
+        let event = feed(&mut kbd, &[0xe0, 0xc8]).unwrap();
+        assert_eq!((event.code, event.pressed), (KeyCode::Up, false));
+    }
+
+    #[test]
+    fn test_set2() {
+        let mut kbd = Keyboard::new(ScancodeSet::Set2);
+
+        let event = kbd.push(0x1c).unwrap();
+        assert_eq!((event.code, event.pressed), (KeyCode::A, true));
+
+        assert_eq!(kbd.push(0xf0), None);
+        let event = kbd.push(0x1c).unwrap();
+        assert_eq!((event.code, event.pressed), (KeyCode::A, false));
+
+        let event = feed(&mut kbd, &[0xe0, 0xf0, 0x14]).unwrap();
+        assert_eq!((event.code, event.pressed), (KeyCode::RightCtrl, false));
+    }
+
+    #[test]
+    fn test_modifiers() {
+        let mut kbd = Keyboard::new(ScancodeSet::Set1);
+
+        // Shift + 1.
+        kbd.push(0x2a);
+        assert_eq!(kbd.push(0x02).unwrap().char(), Some('!'));
+
+        // Both shifts pressed, one released.
+        kbd.push(0x36);
+        kbd.push(0xaa);
+        assert_eq!(kbd.push(0x1e).unwrap().char(), Some('A'));
+        kbd.push(0xb6);
+        assert_eq!(kbd.push(0x1e).unwrap().char(), Some('a'));
+
+        // Caps lock toggles once while held, and shift reverts it.
+        feed(&mut kbd, &[0x3a, 0x3a, 0xba]);
+        assert_eq!(kbd.push(0x1e).unwrap().char(), Some('A'));
+        kbd.push(0x2a);
+        assert_eq!(kbd.push(0x1e).unwrap().char(), Some('a'));
+        kbd.push(0xaa);
+
+        // Ctrl + C.
+        kbd.push(0x1d);
+        let event = kbd.push(0x2e).unwrap();
+        assert!(event.modifiers.contains(Modifiers::CTRL));
+        assert_eq!(event.char(), Some('\x03'));
+    }
+
+    #[test]
+    fn test_special_sequences() {
+        let mut kbd = Keyboard::new(ScancodeSet::Set1);
+
+        // Print Screen is surrounded by fake shifts, which are ignored.
+        assert_eq!(kbd.push(0xe0), None);
+        assert_eq!(kbd.push(0x2a), None);
+        let event = feed(&mut kbd, &[0xe0, 0x37]).unwrap();
+        assert_eq!(event.code, KeyCode::Unknown(0xe037));
+        assert!(!event.modifiers.contains(Modifiers::SHIFT));
+
+        // Pause.
+        let event = feed(&mut kbd, &[0xe1, 0x1d, 0x45, 0xe1, 0x9d, 0xc5]);
+        assert_eq!(event.map(|e| e.code), Some(KeyCode::Pause));
+
+        let mut kbd = Keyboard::new(ScancodeSet::Set2);
+        let event =
+            feed(&mut kbd, &[0xe1, 0x14, 0x77, 0xe1, 0xf0, 0x14, 0xf0, 0x77]);
+        assert_eq!(event.map(|e| e.code), Some(KeyCode::Pause));
+        assert_eq!(kbd.push(0x1c).map(|e| e.code), Some(KeyCode::A));
+    }
+}
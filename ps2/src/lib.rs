@@ -0,0 +1,251 @@
+//! PS/2 controller (Intel 8042) and keyboard support.
+//!
+//! The controller is initialized with the keyboard enabled on the first port
+//! and the second port (usually a mouse) disabled. Once the IRQ 1 is routed,
+//! the interrupt handler reads the received scancodes with `read_data` and
+//! feeds them into a `keyboard::Keyboard`, which decodes them into key
+//! events.
+//!
+//! The controller is polled with a bounded number of reads, so the
+//! initialization fails instead of hanging on machines without a PS/2
+//! controller, where the status register reads as all ones.
+//!
+//! Reference:
+//! - [OSDev article](https://wiki.osdev.org/%228042%22_PS/2_Controller)
+//! - [OSDev article](https://wiki.osdev.org/PS/2_Keyboard)
+
+#![no_std]
+
+use cpu::{in8, out8};
+
+pub mod keyboard;
+
+use keyboard::ScancodeSet;
+
+/// Data port.
+const DATA_PORT: u16 = 0x60;
+
+/// Status register (read) and command register (write).
+const COMMAND_PORT: u16 = 0x64;
+
+/// Status flag: there is data to be read from the data port.
+const STATUS_OUTPUT_FULL: u8 = 0x01;
+
+/// Status flag: the controller has not consumed the last written byte yet.
+const STATUS_INPUT_FULL: u8 = 0x02;
+
+/// Command: read the configuration byte.
+const CMD_READ_CONFIG: u8 = 0x20;
+
+/// Command: write the configuration byte.
+const CMD_WRITE_CONFIG: u8 = 0x60;
+
+/// Command: disable the second port.
+const CMD_DISABLE_PORT2: u8 = 0xa7;
+
+/// Command: test the controller.
+const CMD_SELF_TEST: u8 = 0xaa;
+
+/// Command: test the first port.
+const CMD_TEST_PORT1: u8 = 0xab;
+
+/// Command: disable the first port.
+const CMD_DISABLE_PORT1: u8 = 0xad;
+
+/// Command: enable the first port.
+const CMD_ENABLE_PORT1: u8 = 0xae;
+
+/// Configuration flag: interrupts of the first port.
+const CONFIG_PORT1_IRQ: u8 = 0x01;
+
+/// Configuration flag: interrupts of the second port.
+const CONFIG_PORT2_IRQ: u8 = 0x02;
+
+/// Configuration flag: translation of the scancodes of the first port to
+/// scancode set 1.
+const CONFIG_PORT1_TRANSLATION: u8 = 0x40;
+
+/// Response of the controller to `CMD_SELF_TEST` if the test passed.
+const SELF_TEST_PASSED: u8 = 0x55;
+
+/// Response of the controller to `CMD_TEST_PORT1` if the test passed.
+const PORT_TEST_PASSED: u8 = 0x00;
+
+/// Keyboard command: reset and run the self test.
+const KBD_RESET: u8 = 0xff;
+
+/// Keyboard command: start sending scancodes.
+const KBD_ENABLE_SCANNING: u8 = 0xf4;
+
+/// Keyboard response: the command was accepted.
+const KBD_ACK: u8 = 0xfa;
+
+/// Keyboard response: the command must be sent again.
+const KBD_RESEND: u8 = 0xfe;
+
+/// Keyboard response to `KBD_RESET` if the self test passed.
+const KBD_SELF_TEST_PASSED: u8 = 0xaa;
+
+/// Number of times a keyboard command is sent if the keyboard asks to resend
+/// it.
+const KBD_RETRIES: usize = 3;
+
+/// Number of status register reads before giving up. An IO port read takes
+/// around 1us, so it is about one second, which is enough for the keyboard
+/// self test.
+const TIMEOUT_POLLS: usize = 1_000_000;
+
+/// PS/2 errors.
+#[derive(Debug)]
+pub enum Error {
+    /// The controller or the keyboard did not answer in time. It is also
+    /// returned if there is no controller.
+    Timeout,
+
+    /// The controller self test failed with the provided response.
+    SelfTestFailed(u8),
+
+    /// The test of the first port failed with the provided response.
+    PortTestFailed(u8),
+
+    /// The keyboard answered a command with the provided response.
+    KeyboardError(u8),
+}
+
+/// Represents an initialized PS/2 controller with a keyboard on its first
+/// port.
+#[derive(Debug)]
+pub struct Controller {
+    /// Scancode set received from the keyboard.
+    scancode_set: ScancodeSet,
+}
+
+impl Controller {
+    /// Initializes the controller and resets the keyboard connected to its
+    /// first port. The interrupts of the first port are enabled, so the IRQ 1
+    /// is raised when a scancode is received.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that nothing else is accessing the PS/2
+    /// controller.
+    pub unsafe fn init() -> Result<Controller, Error> {
+        // Disable the devices, so they do not send data during the
+        // initialization, and discard the pending data.
+        command(CMD_DISABLE_PORT1)?;
+        command(CMD_DISABLE_PORT2)?;
+        while read_data().is_some() {}
+
+        command(CMD_READ_CONFIG)?;
+        let config = read()? & !(CONFIG_PORT1_IRQ | CONFIG_PORT2_IRQ);
+        write_config(config)?;
+
+        command(CMD_SELF_TEST)?;
+        match read()? {
+            SELF_TEST_PASSED => {}
+            resp => return Err(Error::SelfTestFailed(resp)),
+        }
+
+        // The self test can reset the controller, so the configuration is
+        // written again.
+        write_config(config)?;
+
+        command(CMD_TEST_PORT1)?;
+        match read()? {
+            PORT_TEST_PASSED => {}
+            resp => return Err(Error::PortTestFailed(resp)),
+        }
+
+        command(CMD_ENABLE_PORT1)?;
+        keyboard_command(KBD_RESET)?;
+        match read()? {
+            KBD_SELF_TEST_PASSED => {}
+            resp => return Err(Error::KeyboardError(resp)),
+        }
+        keyboard_command(KBD_ENABLE_SCANNING)?;
+
+        write_config(config | CONFIG_PORT1_IRQ)?;
+
+        // The keyboard sends scancode set 2 by default, which the controller
+        // translates to set 1 if translation is enabled.
+        let scancode_set = if config & CONFIG_PORT1_TRANSLATION != 0 {
+            ScancodeSet::Set1
+        } else {
+            ScancodeSet::Set2
+        };
+        Ok(Controller { scancode_set })
+    }
+
+    /// Returns the scancode set received from the keyboard.
+    pub fn scancode_set(&self) -> ScancodeSet {
+        self.scancode_set
+    }
+}
+
+/// Returns the next byte received by the controller, or `None` if there is
+/// no data. It is used by the interrupt handler to drain the received
+/// scancodes.
+///
+/// # Safety
+///
+/// The caller must ensure that nothing else is accessing the PS/2
+/// controller.
+pub unsafe fn read_data() -> Option<u8> {
+    if in8(COMMAND_PORT) & STATUS_OUTPUT_FULL != 0 {
+        Some(in8(DATA_PORT))
+    } else {
+        None
+    }
+}
+
+/// Waits until the status register has the flag `flag` set, if `set` is
+/// `true`, or clear otherwise.
+unsafe fn wait_status(flag: u8, set: bool) -> Result<(), Error> {
+    for _ in 0..TIMEOUT_POLLS {
+        if (in8(COMMAND_PORT) & flag != 0) == set {
+            return Ok(());
+        }
+        core::hint::spin_loop();
+    }
+    Err(Error::Timeout)
+}
+
+/// Sends the command `cmd` to the controller.
+unsafe fn command(cmd: u8) -> Result<(), Error> {
+    wait_status(STATUS_INPUT_FULL, false)?;
+    out8(COMMAND_PORT, cmd);
+    Ok(())
+}
+
+/// Writes `data` into the data port.
+unsafe fn write(data: u8) -> Result<(), Error> {
+    wait_status(STATUS_INPUT_FULL, false)?;
+    out8(DATA_PORT, data);
+    Ok(())
+}
+
+/// Reads a byte from the data port.
+unsafe fn read() -> Result<u8, Error> {
+    wait_status(STATUS_OUTPUT_FULL, true)?;
+    Ok(in8(DATA_PORT))
+}
+
+/// Writes the configuration byte `config`.
+unsafe fn write_config(config: u8) -> Result<(), Error> {
+    command(CMD_WRITE_CONFIG)?;
+    write(config)
+}
+
+/// Sends the command `cmd` to the keyboard and waits for its
+/// acknowledgement.
+unsafe fn keyboard_command(cmd: u8) -> Result<(), Error> {
+    for _ in 0..KBD_RETRIES {
+        write(cmd)?;
+        match read()? {
+            KBD_ACK => return Ok(()),
+            KBD_RESEND => continue,
+            resp => return Err(Error::KeyboardError(resp)),
+        }
+    }
+    Err(Error::KeyboardError(KBD_RESEND))
+}
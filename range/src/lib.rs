@@ -2,7 +2,13 @@
 
 #![no_std]
 
-use core::cmp::max;
+use core::cmp::{max, min};
+use core::fmt;
+use core::marker::PhantomData;
+
+mod map;
+
+pub use map::{RangeMap, RangeMapOf};
 
 /// Represents an error related to a `Range` or `RangeSet`.
 #[derive(Debug)]
@@ -13,94 +19,271 @@ pub enum Error {
     /// The fixed size array that backs the `RangeSet` is full. It is not
     /// possible to add more ranges.
     FullRangeSet,
+
+    /// The buffer is too small to hold the encoded `RangeSet`.
+    BufferTooSmall,
+
+    /// The buffer does not contain a valid encoded `RangeSet`.
+    InvalidEncoding,
 }
 
-/// Represents an inclusive range.
+/// Represents the type of the points of a `RangeOf`. The arithmetic on the
+/// points is done on their `u64` value.
+pub trait Point: Copy + Eq + Default + fmt::Debug {
+    /// Returns the `u64` value of the point.
+    fn to_u64(self) -> u64;
+
+    /// Returns the point with a given `u64` value. It is only called with
+    /// values between the values of two existing points.
+    fn from_u64(value: u64) -> Self;
+}
+
+macro_rules! impl_point {
+    ($($ty:ty),*) => {
+        $(
+            impl Point for $ty {
+                fn to_u64(self) -> u64 {
+                    self as u64
+                }
+
+                fn from_u64(value: u64) -> Self {
+                    value as $ty
+                }
+            }
+        )*
+    };
+}
+
+impl_point!(u8, u16, u32, u64, usize);
+
+/// Represents an inclusive range of points of type `P`.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
-pub struct Range {
+pub struct RangeOf<P: Point> {
     start: u64,
     end: u64,
+    _point: PhantomData<P>,
 }
 
-impl Range {
-    /// Returns a new `Range`.
+/// Represents an inclusive range of `u64` points.
+pub type Range = RangeOf<u64>;
+
+impl<P: Point> RangeOf<P> {
+    /// Returns a new `RangeOf`.
     ///
     /// # Errors
     ///
     /// This functions returns `Error::InvalidBoundaries` if the end point is
     /// lower than the start point of the range.
-    pub fn new(start: u64, end: u64) -> Result<Self, Error> {
+    pub fn new(start: P, end: P) -> Result<Self, Error> {
+        RangeOf::from_raw(start.to_u64(), end.to_u64())
+    }
+
+    /// Returns a new `RangeOf` from the `u64` values of its boundaries.
+    fn from_raw(start: u64, end: u64) -> Result<Self, Error> {
         if start <= end {
-            Ok(Range { start, end })
+            Ok(RangeOf {
+                start,
+                end,
+                _point: PhantomData,
+            })
         } else {
             Err(Error::InvalidBoundaries)
         }
     }
 
     /// Returns the start point of the range.
-    pub fn start(&self) -> u64 {
-        self.start
+    pub fn start(&self) -> P {
+        P::from_u64(self.start)
     }
 
     /// Returns the end point of the range.
-    pub fn end(&self) -> u64 {
-        self.end
+    pub fn end(&self) -> P {
+        P::from_u64(self.end)
     }
 
     /// Returns `true` if the range contains a given point.
-    pub fn contains_point(&self, point: u64) -> bool {
+    pub fn contains_point(&self, point: P) -> bool {
+        self.contains_raw(point.to_u64())
+    }
+
+    /// Returns `true` if the range contains the point with a given `u64`
+    /// value.
+    fn contains_raw(&self, point: u64) -> bool {
         point >= self.start && point <= self.end
     }
 
     /// Returns `true` if the range contains a given range.
-    pub fn contains_range(&self, range: Range) -> bool {
-        self.contains_point(range.start) && self.contains_point(range.end)
+    pub fn contains_range(&self, range: RangeOf<P>) -> bool {
+        self.contains_raw(range.start) && self.contains_raw(range.end)
     }
 
     /// Returns `true` if the ranges overlap.
-    pub fn overlaps(&self, range: Range) -> bool {
-        self.contains_point(range.start)
-            || self.contains_point(range.end)
-            || range.contains_point(self.start)
-            || range.contains_point(self.end)
+    pub fn overlaps(&self, range: RangeOf<P>) -> bool {
+        self.contains_raw(range.start)
+            || self.contains_raw(range.end)
+            || range.contains_raw(self.start)
+            || range.contains_raw(self.end)
     }
 
-    /// Returns the size of the range.
+    /// Returns the size of the range. The size of the range `[0, u64::MAX]`
+    /// does not fit in a `u64`, so it saturates to `u64::MAX`. Use
+    /// `RangeOf::try_size` to detect that case.
     pub fn size(&self) -> u64 {
-        self.end - self.start + 1
+        self.try_size().unwrap_or(u64::MAX)
+    }
+
+    /// Returns the size of the range or `None` if it does not fit in a `u64`.
+    /// This only happens for the range `[0, u64::MAX]`.
+    pub fn try_size(&self) -> Option<u64> {
+        (self.end - self.start).checked_add(1)
+    }
+
+    /// Splits the range in two at a given point. The first range ends right
+    /// before `point` and the second one starts at `point`.
+    ///
+    /// It returns `None` if `point` is not contained by the range or it is
+    /// its start point, given that one of the ranges would be empty.
+    pub fn split_at(&self, point: P) -> Option<(RangeOf<P>, RangeOf<P>)> {
+        self.split_raw(point.to_u64())
+    }
+
+    /// Splits the range in two at the point with a given `u64` value.
+    fn split_raw(&self, point: u64) -> Option<(RangeOf<P>, RangeOf<P>)> {
+        if point <= self.start || point > self.end {
+            return None;
+        }
+
+        let lo = RangeOf::from_raw(self.start, point - 1).ok()?;
+        let hi = RangeOf::from_raw(point, self.end).ok()?;
+        Some((lo, hi))
+    }
+
+    /// Returns the range resulting from aligning up the start point to
+    /// `start_align`. The end point is not modified.
+    ///
+    /// It returns `None` if `start_align` is not a power of two or the
+    /// aligned start point is above the end point.
+    pub fn align_up(&self, start_align: u64) -> Option<RangeOf<P>> {
+        if !start_align.is_power_of_two() {
+            return None;
+        }
+
+        let start =
+            self.start.checked_add(start_align - 1)? & !(start_align - 1);
+        RangeOf::from_raw(start, self.end).ok()
+    }
+
+    /// Returns the biggest range contained by this one that is made of whole
+    /// blocks of `align` bytes. That is, the start point is aligned up and
+    /// the end point is aligned down to `align`.
+    ///
+    /// It returns `None` if `align` is not a power of two or the range does
+    /// not contain any whole block.
+    pub fn trim_to_alignment(&self, align: u64) -> Option<RangeOf<P>> {
+        let range = self.align_up(align)?;
+
+        // The end point is inclusive, so the end of the last whole block is
+        // right before an aligned point. If the range ends at `u64::MAX`, the
+        // last block is already whole.
+        let end = match range.end.checked_add(1) {
+            Some(end) => (end & !(align - 1)).checked_sub(1)?,
+            None => u64::MAX,
+        };
+        RangeOf::from_raw(range.start, end).ok()
+    }
+
+    /// Returns the range contained by both ranges, if they overlap.
+    pub fn overlap_with(&self, range: RangeOf<P>) -> Option<RangeOf<P>> {
+        if !self.overlaps(range) {
+            return None;
+        }
+
+        RangeOf::from_raw(
+            max(self.start, range.start),
+            min(self.end, range.end),
+        )
+        .ok()
+    }
+
+    /// Returns `true` if `range` starts right after the end of this range.
+    fn precedes(&self, range: RangeOf<P>) -> bool {
+        self.end.checked_add(1) == Some(range.start)
     }
 }
 
 /// Fixed length of the `RangeSet`.
 const RANGE_SET_LEN: usize = 128;
 
-/// Represents a set of ranges.
+/// Magic of an encoded `RangeSet`.
+const RANGE_SET_MAGIC: [u8; 4] = *b"RSET";
+
+/// Version of the binary layout of an encoded `RangeSet`.
+const RANGE_SET_VERSION: u32 = 1;
+
+/// Size of the header of an encoded `RangeSet`.
+const RANGE_SET_HEADER_SIZE: usize = 16;
+
+/// Size of each encoded range.
+const RANGE_SET_ENTRY_SIZE: usize = 16;
+
+/// Represents a set of ranges of points of type `P`.
 #[derive(Debug)]
-pub struct RangeSet {
+pub struct RangeSetOf<P: Point> {
     /// Ranges within the `RangeSet`.
-    ranges: [Range; RANGE_SET_LEN],
+    ranges: [RangeOf<P>; RANGE_SET_LEN],
 
     /// Number of elements in the fixed size array that are being used.
     in_use: usize,
 }
 
-impl RangeSet {
+/// Represents a set of ranges of `u64` points.
+pub type RangeSet = RangeSetOf<u64>;
+
+impl<P: Point> RangeSetOf<P> {
     /// Returns an empty `RangeSet`.
     pub fn new() -> Self {
-        RangeSet {
-            ranges: [Range::default(); RANGE_SET_LEN],
+        RangeSetOf {
+            ranges: [RangeOf::default(); RANGE_SET_LEN],
             in_use: 0,
         }
     }
 
     /// Returns the ranges in the `RangeSet`.
-    pub fn ranges(&self) -> &[Range] {
+    pub fn ranges(&self) -> &[RangeOf<P>] {
         &self.ranges[..self.in_use]
     }
 
+    /// Returns an iterator over the ranges in the `RangeSet`, sorted by
+    /// start point.
+    pub fn iter(&self) -> core::slice::Iter<'_, RangeOf<P>> {
+        self.ranges().iter()
+    }
+
+    /// Returns the number of ranges in the `RangeSet`.
+    pub fn len(&self) -> usize {
+        self.in_use
+    }
+
+    /// Returns `true` if the `RangeSet` does not contain any range.
+    pub fn is_empty(&self) -> bool {
+        self.in_use == 0
+    }
+
+    /// Returns `true` if the `RangeSet` contains a given point.
+    pub fn contains_point(&self, point: P) -> bool {
+        self.iter().any(|range| range.contains_point(point))
+    }
+
+    /// Returns `true` if the `RangeSet` contains a given range. Given that
+    /// overlapping and contiguous ranges are merged, the range must be
+    /// contained by one of the ranges of the `RangeSet`.
+    pub fn contains_range(&self, range: RangeOf<P>) -> bool {
+        self.iter().any(|r| r.contains_range(range))
+    }
+
     /// Inserts a range into the internal `ranges` array preserving the order
     /// of the array and avoiding duplicated start points.
-    fn sort_insert(&mut self, range: Range) -> Result<(), Error> {
+    fn sort_insert(&mut self, range: RangeOf<P>) -> Result<(), Error> {
         // Find the index of the new range.
         let mut idx = self.in_use;
         for i in 0..self.in_use {
@@ -118,9 +301,22 @@ impl RangeSet {
             }
         }
 
-        // There must be space at least for the new range.
+        // If there is no space for the new range, try to merge it into the
+        // first range that overlaps or is contiguous to it. Any range before
+        // that one ends before the start point of the new range, so the
+        // array stays sorted. The following ranges are merged later.
         if self.in_use >= self.ranges.len() {
-            return Err(Error::FullRangeSet);
+            let touches = |r: &RangeOf<P>| {
+                range.end.saturating_add(1) >= r.start
+                    && r.end.saturating_add(1) >= range.start
+            };
+            let i = self.ranges[..self.in_use]
+                .iter()
+                .position(touches)
+                .ok_or(Error::FullRangeSet)?;
+            self.ranges[i].start = min(range.start, self.ranges[i].start);
+            self.ranges[i].end = max(range.end, self.ranges[i].end);
+            return Ok(());
         }
 
         // Create space for the new range, moving the existing ones forward one
@@ -139,24 +335,19 @@ impl RangeSet {
     fn merge(&mut self) {
         let mut i = 0;
         while i < self.in_use - 1 {
-            // If the ranges are not contiguous or overlapped, advance.
-            if self.ranges[i + 1].start > self.ranges[i].end + 1 {
+            // If the ranges are not contiguous or overlapped, advance. If the
+            // first range ends at `u64::MAX`, the second one is always
+            // contained by it, so `end + 1` can saturate.
+            if self.ranges[i + 1].start > self.ranges[i].end.saturating_add(1)
+            {
                 i += 1;
                 continue;
             }
 
-            // If the ranges are contiguous or the first end point is contained
-            // by the second range, update the first end point with the value
-            // of the second one.
-            //
-            // Note that `end + 1` is used because:
-            // 1. Contiguous ranges must be merged.
-            // 2. If both ranges share the same end point, there is no need to
-            //    udpate it.
-            // This avoids checking one extra condition.
-            if self.ranges[i + 1].contains_point(self.ranges[i].end + 1) {
-                self.ranges[i].end = self.ranges[i + 1].end;
-            }
+            // If the ranges are contiguous or overlapped, the merged range
+            // ends at the greatest end point.
+            self.ranges[i].end =
+                max(self.ranges[i].end, self.ranges[i + 1].end);
 
             // At this point the two ranges have been merged into the first
             // one. Remove the second range from the list and decrement the
@@ -168,7 +359,7 @@ impl RangeSet {
 
     /// Inserts a `Range` into the `RangeSet`. It takes into account possible
     /// overlappings to create, merge or enlarge existing ranges if necessary.
-    pub fn insert(&mut self, range: Range) -> Result<(), Error> {
+    pub fn insert(&mut self, range: RangeOf<P>) -> Result<(), Error> {
         self.sort_insert(range)?;
         self.merge();
         Ok(())
@@ -176,7 +367,13 @@ impl RangeSet {
 
     /// Removes a `Range` from the `RangeSet`. It takes into account possible
     /// overlappings to delete, split or shrink existing ranges if necessary.
-    pub fn remove(&mut self, range: Range) -> Result<(), Error> {
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Error::FullRangeSet` if an existing range must
+    /// be split and the `RangeSet` is full. The ranges of a `RangeSet` are
+    /// always merged, so no slot can be freed by merging them again.
+    pub fn remove(&mut self, range: RangeOf<P>) -> Result<(), Error> {
         let mut i = 0;
         while i < self.in_use {
             // Given that the internal `range` array is sorted, once the start
@@ -218,8 +415,10 @@ impl RangeSet {
                         return Err(Error::FullRangeSet);
                     }
 
-                    let new_range =
-                        Range::new(self.ranges[i].start, range.start - 1)?;
+                    let new_range = RangeOf::from_raw(
+                        self.ranges[i].start,
+                        range.start - 1,
+                    )?;
                     self.ranges.copy_within(i..self.in_use, i + 1);
                     self.ranges[i] = new_range;
                     self.ranges[i + 1].start = range.end + 1;
@@ -232,7 +431,7 @@ impl RangeSet {
                 // the existing range must be removed.
                 self.ranges.copy_within(i + 1..self.in_use, i);
                 self.in_use -= 1;
-            } else if self.ranges[i].contains_point(range.start) {
+            } else if self.ranges[i].contains_raw(range.start) {
                 // The start point of the range to be removed is contained by
                 // the existing range. Then, the end point of the existing
                 // range must be updated.
@@ -252,13 +451,308 @@ impl RangeSet {
 
     /// Returns the sum of the size of all the ranges in the `RangeSet`.
     pub fn size(&self) -> u64 {
-        self.ranges[..self.in_use].iter().map(Range::size).sum()
+        self.try_size().unwrap_or(u64::MAX)
+    }
+
+    /// Returns the sum of the size of all the ranges in the `RangeSet` or
+    /// `None` if it does not fit in a `u64`.
+    pub fn try_size(&self) -> Option<u64> {
+        self.iter()
+            .try_fold(0u64, |acc, range| acc.checked_add(range.try_size()?))
+    }
+
+    /// Returns an iterator over the holes of the `RangeSet` within a given
+    /// range. That is, the ranges contained by `within` that do not overlap
+    /// any range of the `RangeSet`.
+    pub fn gaps(&self, within: RangeOf<P>) -> Gaps<'_, P> {
+        Gaps {
+            ranges: self.iter(),
+            next: Some(within.start),
+            within,
+        }
+    }
+
+    /// Returns the biggest hole between the ranges of the `RangeSet`.
+    pub fn largest_gap(&self) -> Option<RangeOf<P>> {
+        let first = self.ranges().first()?;
+        let last = self.ranges().last()?;
+        let within = RangeOf::from_raw(first.start, last.end).ok()?;
+        self.gaps(within).max_by_key(RangeOf::size)
+    }
+
+    /// Returns the biggest range of the `RangeSet`.
+    pub fn largest_range(&self) -> Option<RangeOf<P>> {
+        self.iter().copied().max_by_key(RangeOf::size)
+    }
+
+    /// Adds the ranges of `other` to the `RangeSet`.
+    ///
+    /// On error, the ranges of `other` that were inserted before the error
+    /// are kept.
+    pub fn union(&mut self, other: &RangeSetOf<P>) -> Result<(), Error> {
+        for range in other.ranges() {
+            self.insert(*range)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the ranges of `other` from the `RangeSet`.
+    ///
+    /// On error, the ranges of `other` that were removed before the error
+    /// are kept removed.
+    pub fn subtract(&mut self, other: &RangeSetOf<P>) -> Result<(), Error> {
+        for range in other.ranges() {
+            self.remove(*range)?;
+        }
+        Ok(())
+    }
+
+    /// Keeps only the points of the `RangeSet` that are also contained by
+    /// `other`. On error, the `RangeSet` is not modified.
+    pub fn intersection(
+        &mut self,
+        other: &RangeSetOf<P>,
+    ) -> Result<(), Error> {
+        let mut ret = RangeSetOf::new();
+        for a in self.ranges() {
+            for b in other.ranges() {
+                if let Some(range) = a.overlap_with(*b) {
+                    ret.insert(range)?;
+                }
+            }
+        }
+        *self = ret;
+        Ok(())
+    }
+
+    /// Replaces the `RangeSet` with the points of `range` that are not
+    /// contained by it. On error, the `RangeSet` is not modified.
+    pub fn complement_within(
+        &mut self,
+        range: RangeOf<P>,
+    ) -> Result<(), Error> {
+        let mut ret = RangeSetOf::new();
+        ret.insert(range)?;
+        ret.subtract(self)?;
+        *self = ret;
+        Ok(())
+    }
+
+    /// Finds the lowest free block of `size` bytes whose start point is
+    /// aligned to `align`, removes it from the `RangeSet` and returns its
+    /// start point.
+    ///
+    /// It returns `None` if `size` is zero, `align` is not a power of two or
+    /// there is no suitable block.
+    pub fn allocate(&mut self, size: u64, align: u64) -> Option<P> {
+        if size == 0 || !align.is_power_of_two() {
+            return None;
+        }
+
+        for i in 0..self.in_use {
+            let range = match self.ranges[i].align_up(align) {
+                Some(range) => range,
+                None => continue,
+            };
+            let start = range.start;
+            let end = match start.checked_add(size - 1) {
+                Some(end) if end <= range.end => end,
+                _ => continue,
+            };
+
+            // Removing the block can split the range, which fails if the
+            // `RangeSet` is full.
+            let block = RangeOf::from_raw(start, end).ok()?;
+            if self.remove(block).is_ok() {
+                return Some(block.start());
+            }
+        }
+
+        None
+    }
+
+    /// Removes the block of `size` bytes starting at `addr` from the
+    /// `RangeSet` and returns its start point.
+    ///
+    /// It returns `None` if `size` is zero or the block is not completely
+    /// contained by one of the ranges of the `RangeSet`.
+    pub fn allocate_at(&mut self, addr: P, size: u64) -> Option<P> {
+        if size == 0 {
+            return None;
+        }
+
+        let start = addr.to_u64();
+        let block =
+            RangeOf::from_raw(start, start.checked_add(size - 1)?).ok()?;
+        if !self.contains_range(block) {
+            return None;
+        }
+
+        self.remove(block).ok()?;
+        Some(addr)
+    }
+}
+
+impl<P: Point> RangeSetOf<P> {
+    /// Returns the size in bytes of the encoded `RangeSet`.
+    pub fn encoded_len(&self) -> usize {
+        RANGE_SET_HEADER_SIZE + self.in_use * RANGE_SET_ENTRY_SIZE
+    }
+
+    /// Encodes the `RangeSet` into `buf` and returns the number of bytes
+    /// written. The layout does not depend on the layout of Rust structures,
+    /// so it can be used to pass the `RangeSet` between binaries. All fields
+    /// are little-endian:
+    ///
+    /// | Offset | Size | Description                        |
+    /// |--------|------|------------------------------------|
+    /// | 0      | 4    | Magic (`RSET`)                     |
+    /// | 4      | 4    | Version of the layout (1)          |
+    /// | 8      | 4    | Number of ranges (N)               |
+    /// | 12     | 4    | Reserved (0)                       |
+    /// | 16     | 16*N | Ranges as `(start: u64, end: u64)` |
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Error::BufferTooSmall` if `buf` is smaller
+    /// than `RangeSet::encoded_len`.
+    pub fn write_to(&self, buf: &mut [u8]) -> Result<usize, Error> {
+        let len = self.encoded_len();
+        let buf = buf.get_mut(..len).ok_or(Error::BufferTooSmall)?;
+
+        let (hdr, entries) = buf.split_at_mut(RANGE_SET_HEADER_SIZE);
+        hdr[0..4].copy_from_slice(&RANGE_SET_MAGIC);
+        hdr[4..8].copy_from_slice(&RANGE_SET_VERSION.to_le_bytes());
+        hdr[8..12].copy_from_slice(&(self.in_use as u32).to_le_bytes());
+        hdr[12..16].copy_from_slice(&0u32.to_le_bytes());
+
+        for (entry, range) in entries
+            .chunks_exact_mut(RANGE_SET_ENTRY_SIZE)
+            .zip(self.iter())
+        {
+            entry[0..8].copy_from_slice(&range.start.to_le_bytes());
+            entry[8..16].copy_from_slice(&range.end.to_le_bytes());
+        }
+
+        Ok(len)
+    }
+
+    /// Decodes a `RangeSet` encoded by `RangeSet::write_to`.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Error::InvalidEncoding` if the magic, the
+    /// version or any of the ranges are not valid, and
+    /// `Error::BufferTooSmall` if `buf` is truncated.
+    pub fn read_from(buf: &[u8]) -> Result<Self, Error> {
+        let hdr = buf
+            .get(..RANGE_SET_HEADER_SIZE)
+            .ok_or(Error::BufferTooSmall)?;
+        let read_u32 = |off: usize| {
+            let mut bytes = [0u8; 4];
+            bytes.copy_from_slice(&hdr[off..off + 4]);
+            u32::from_le_bytes(bytes)
+        };
+        if hdr[0..4] != RANGE_SET_MAGIC || read_u32(4) != RANGE_SET_VERSION {
+            return Err(Error::InvalidEncoding);
+        }
+        let count = read_u32(8) as usize;
+        if count > RANGE_SET_LEN {
+            return Err(Error::InvalidEncoding);
+        }
+
+        let entries = buf
+            .get(RANGE_SET_HEADER_SIZE..)
+            .and_then(|entries| entries.get(..count * RANGE_SET_ENTRY_SIZE))
+            .ok_or(Error::BufferTooSmall)?;
+
+        let mut ret = RangeSetOf::new();
+        for entry in entries.chunks_exact(RANGE_SET_ENTRY_SIZE) {
+            let mut start = [0u8; 8];
+            let mut end = [0u8; 8];
+            start.copy_from_slice(&entry[0..8]);
+            end.copy_from_slice(&entry[8..16]);
+            let range = RangeOf::from_raw(
+                u64::from_le_bytes(start),
+                u64::from_le_bytes(end),
+            )
+            .or(Err(Error::InvalidEncoding))?;
+            ret.insert(range)?;
+        }
+
+        Ok(ret)
     }
 }
 
-impl Default for RangeSet {
+impl<P: Point> Default for RangeSetOf<P> {
     fn default() -> Self {
-        RangeSet::new()
+        RangeSetOf::new()
+    }
+}
+
+/// Iterator over the holes of a `RangeSet` within a given range. It is
+/// returned by `RangeSet::gaps`.
+#[derive(Debug)]
+pub struct Gaps<'a, P: Point> {
+    /// Ranges of the `RangeSet` that have not been visited yet.
+    ranges: core::slice::Iter<'a, RangeOf<P>>,
+
+    /// First point that is not covered by the visited ranges. It is `None`
+    /// once the end of `within` has been reached.
+    next: Option<u64>,
+
+    /// Range in which the holes are searched.
+    within: RangeOf<P>,
+}
+
+impl<P: Point> Iterator for Gaps<'_, P> {
+    type Item = RangeOf<P>;
+
+    fn next(&mut self) -> Option<RangeOf<P>> {
+        loop {
+            let start = self.next?;
+
+            let range = match self.ranges.next() {
+                Some(range) => range,
+                None => {
+                    // There are no more ranges, so the rest of `within` is a
+                    // hole.
+                    self.next = None;
+                    return RangeOf::from_raw(start, self.within.end).ok();
+                }
+            };
+
+            // Skip the ranges before the next uncovered point.
+            if range.end < start {
+                continue;
+            }
+
+            // Compute the next uncovered point, taking into account that
+            // the range can end at the end of `within` or beyond.
+            let next = range
+                .end
+                .checked_add(1)
+                .filter(|&next| next <= self.within.end);
+
+            if range.start > self.within.end {
+                self.next = None;
+                return RangeOf::from_raw(start, self.within.end).ok();
+            } else if range.start > start {
+                self.next = next;
+                return RangeOf::from_raw(start, range.start - 1).ok();
+            }
+
+            self.next = next;
+        }
+    }
+}
+
+impl<'a, P: Point> IntoIterator for &'a RangeSetOf<P> {
+    type Item = &'a RangeOf<P>;
+    type IntoIter = core::slice::Iter<'a, RangeOf<P>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
     }
 }
 
@@ -653,4 +1147,391 @@ mod tests {
         let want = [Range::new(0, 0).unwrap(), Range::new(50, 50).unwrap()];
         assert_eq!(rangeset.ranges(), want);
     }
+
+    #[test]
+    fn test_rangeset_allocate() {
+        let mut rangeset = RangeSet::new();
+        rangeset.insert(Range::new(0x10, 0x1f).unwrap()).unwrap();
+        rangeset.insert(Range::new(0x100, 0x1ff).unwrap()).unwrap();
+
+        assert_eq!(rangeset.allocate(0x20, 0x10), Some(0x100));
+        assert_eq!(rangeset.allocate(0x8, 0x1), Some(0x10));
+
+        let want = [
+            Range::new(0x18, 0x1f).unwrap(),
+            Range::new(0x120, 0x1ff).unwrap(),
+        ];
+        assert_eq!(rangeset.ranges(), want);
+    }
+
+    #[test]
+    fn test_rangeset_allocate_aligned() {
+        let mut rangeset = RangeSet::new();
+        rangeset.insert(Range::new(0x1, 0x2ff).unwrap()).unwrap();
+
+        assert_eq!(rangeset.allocate(0x100, 0x100), Some(0x100));
+
+        let want = [
+            Range::new(0x1, 0xff).unwrap(),
+            Range::new(0x200, 0x2ff).unwrap(),
+        ];
+        assert_eq!(rangeset.ranges(), want);
+    }
+
+    #[test]
+    fn test_rangeset_allocate_no_space() {
+        let mut rangeset = RangeSet::new();
+        rangeset.insert(Range::new(0x1, 0x1ff).unwrap()).unwrap();
+
+        assert_eq!(rangeset.allocate(0x200, 0x1), None);
+        assert_eq!(rangeset.allocate(0x101, 0x100), None);
+        assert_eq!(rangeset.allocate(0, 0x1), None);
+        assert_eq!(rangeset.allocate(0x10, 0x3), None);
+
+        let want = [Range::new(0x1, 0x1ff).unwrap()];
+        assert_eq!(rangeset.ranges(), want);
+    }
+
+    #[test]
+    fn test_rangeset_allocate_at() {
+        let mut rangeset = RangeSet::new();
+        rangeset.insert(Range::new(0, 0xff).unwrap()).unwrap();
+
+        assert_eq!(rangeset.allocate_at(0x10, 0x10), Some(0x10));
+        assert_eq!(rangeset.allocate_at(0x18, 0x10), None);
+        assert_eq!(rangeset.allocate_at(0xf0, 0x20), None);
+
+        let want =
+            [Range::new(0, 0xf).unwrap(), Range::new(0x20, 0xff).unwrap()];
+        assert_eq!(rangeset.ranges(), want);
+    }
+
+    #[test]
+    fn test_rangeset_union() {
+        let mut rangeset = RangeSet::new();
+        rangeset.insert(Range::new(0, 10).unwrap()).unwrap();
+        rangeset.insert(Range::new(30, 40).unwrap()).unwrap();
+
+        let mut other = RangeSet::new();
+        other.insert(Range::new(5, 20).unwrap()).unwrap();
+        other.insert(Range::new(50, 60).unwrap()).unwrap();
+
+        rangeset.union(&other).unwrap();
+
+        let want = [
+            Range::new(0, 20).unwrap(),
+            Range::new(30, 40).unwrap(),
+            Range::new(50, 60).unwrap(),
+        ];
+        assert_eq!(rangeset.ranges(), want);
+    }
+
+    #[test]
+    fn test_rangeset_subtract() {
+        let mut rangeset = RangeSet::new();
+        rangeset.insert(Range::new(0, 100).unwrap()).unwrap();
+
+        let mut other = RangeSet::new();
+        other.insert(Range::new(10, 20).unwrap()).unwrap();
+        other.insert(Range::new(90, 110).unwrap()).unwrap();
+
+        rangeset.subtract(&other).unwrap();
+
+        let want = [Range::new(0, 9).unwrap(), Range::new(21, 89).unwrap()];
+        assert_eq!(rangeset.ranges(), want);
+    }
+
+    #[test]
+    fn test_rangeset_intersection() {
+        let mut rangeset = RangeSet::new();
+        rangeset.insert(Range::new(0, 10).unwrap()).unwrap();
+        rangeset.insert(Range::new(20, 30).unwrap()).unwrap();
+
+        let mut other = RangeSet::new();
+        other.insert(Range::new(5, 25).unwrap()).unwrap();
+        other.insert(Range::new(28, 28).unwrap()).unwrap();
+
+        rangeset.intersection(&other).unwrap();
+
+        let want = [
+            Range::new(5, 10).unwrap(),
+            Range::new(20, 25).unwrap(),
+            Range::new(28, 28).unwrap(),
+        ];
+        assert_eq!(rangeset.ranges(), want);
+    }
+
+    #[test]
+    fn test_rangeset_complement_within() {
+        let mut rangeset = RangeSet::new();
+        rangeset.insert(Range::new(0, 10).unwrap()).unwrap();
+        rangeset.insert(Range::new(20, 30).unwrap()).unwrap();
+
+        rangeset
+            .complement_within(Range::new(5, 40).unwrap())
+            .unwrap();
+
+        let want = [Range::new(11, 19).unwrap(), Range::new(31, 40).unwrap()];
+        assert_eq!(rangeset.ranges(), want);
+    }
+
+    #[test]
+    fn test_rangeset_iter() {
+        let mut rangeset = RangeSet::new();
+        assert!(rangeset.is_empty());
+
+        rangeset.insert(Range::new(20, 30).unwrap()).unwrap();
+        rangeset.insert(Range::new(0, 10).unwrap()).unwrap();
+        assert_eq!(rangeset.len(), 2);
+
+        let mut iter = (&rangeset).into_iter();
+        assert_eq!(iter.next(), Some(&Range::new(0, 10).unwrap()));
+        assert_eq!(iter.next(), Some(&Range::new(20, 30).unwrap()));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn test_rangeset_contains() {
+        let mut rangeset = RangeSet::new();
+        rangeset.insert(Range::new(0, 10).unwrap()).unwrap();
+        rangeset.insert(Range::new(20, 30).unwrap()).unwrap();
+
+        assert!(rangeset.contains_point(10));
+        assert!(!rangeset.contains_point(15));
+        assert!(rangeset.contains_range(Range::new(22, 30).unwrap()));
+        assert!(!rangeset.contains_range(Range::new(5, 25).unwrap()));
+    }
+
+    #[test]
+    fn test_range_split_at() {
+        let range = Range::new(10, 20).unwrap();
+
+        let want = (Range::new(10, 14).unwrap(), Range::new(15, 20).unwrap());
+        assert_eq!(range.split_at(15), Some(want));
+
+        let want = (Range::new(10, 19).unwrap(), Range::new(20, 20).unwrap());
+        assert_eq!(range.split_at(20), Some(want));
+
+        assert_eq!(range.split_at(10), None);
+        assert_eq!(range.split_at(21), None);
+    }
+
+    #[test]
+    fn test_range_align_up() {
+        let range = Range::new(0x1001, 0x3fff).unwrap();
+        assert_eq!(
+            range.align_up(0x1000),
+            Some(Range::new(0x2000, 0x3fff).unwrap())
+        );
+        assert_eq!(range.align_up(0x4000), None);
+        assert_eq!(range.align_up(0x1001), None);
+    }
+
+    #[test]
+    fn test_range_trim_to_alignment() {
+        let range = Range::new(0x1001, 0x4123).unwrap();
+        assert_eq!(
+            range.trim_to_alignment(0x1000),
+            Some(Range::new(0x2000, 0x3fff).unwrap())
+        );
+
+        let range = Range::new(0x1001, 0x2ffe).unwrap();
+        assert_eq!(range.trim_to_alignment(0x1000), None);
+
+        let range = Range::new(0, u64::MAX).unwrap();
+        assert_eq!(range.trim_to_alignment(0x1000), Some(range));
+    }
+
+    #[test]
+    fn test_range_overlap_with() {
+        let range = Range::new(10, 20).unwrap();
+        assert_eq!(
+            range.overlap_with(Range::new(15, 30).unwrap()),
+            Some(Range::new(15, 20).unwrap())
+        );
+        assert_eq!(
+            range.overlap_with(Range::new(0, 40).unwrap()),
+            Some(range)
+        );
+        assert_eq!(range.overlap_with(Range::new(21, 30).unwrap()), None);
+    }
+
+    #[test]
+    fn test_rangeset_insert_full_merge() {
+        let mut rangeset = RangeSet::new();
+
+        for i in 0..RANGE_SET_LEN {
+            let point = 2 * (i as u64);
+            rangeset.insert(Range::new(point, point).unwrap()).unwrap();
+        }
+
+        rangeset.insert(Range::new(1, 1).unwrap()).unwrap();
+        rangeset.insert(Range::new(5, 9).unwrap()).unwrap();
+
+        assert_eq!(rangeset.len(), RANGE_SET_LEN - 4);
+        assert_eq!(rangeset.ranges()[0], Range::new(0, 2).unwrap());
+        assert_eq!(rangeset.ranges()[1], Range::new(4, 10).unwrap());
+        assert_eq!(rangeset.ranges()[2], Range::new(12, 12).unwrap());
+    }
+
+    #[test]
+    fn test_rangeset_insert_full_no_merge() {
+        let mut rangeset = RangeSet::new();
+
+        for i in 0..RANGE_SET_LEN {
+            let point = 3 * (i as u64);
+            rangeset.insert(Range::new(point, point).unwrap()).unwrap();
+        }
+
+        match rangeset.insert(Range::new(1337 * 3, 1337 * 3).unwrap()) {
+            Err(Error::FullRangeSet) => {}
+            ret => panic!("unexpected result: {:?}", ret),
+        }
+    }
+
+    #[test]
+    fn test_range_size_max() {
+        let range = Range::new(0, u64::MAX).unwrap();
+        assert_eq!(range.try_size(), None);
+        assert_eq!(range.size(), u64::MAX);
+
+        let range = Range::new(1, u64::MAX).unwrap();
+        assert_eq!(range.try_size(), Some(u64::MAX));
+    }
+
+    #[test]
+    fn test_rangeset_insert_max() {
+        let mut rangeset = RangeSet::new();
+        rangeset.insert(Range::new(10, u64::MAX).unwrap()).unwrap();
+        rangeset.insert(Range::new(20, 30).unwrap()).unwrap();
+        rangeset
+            .insert(Range::new(u64::MAX, u64::MAX).unwrap())
+            .unwrap();
+        rangeset.insert(Range::new(0, 9).unwrap()).unwrap();
+
+        let want = [Range::new(0, u64::MAX).unwrap()];
+        assert_eq!(rangeset.ranges(), want);
+        assert_eq!(rangeset.try_size(), None);
+    }
+
+    #[test]
+    fn test_rangeset_remove_max() {
+        let mut rangeset = RangeSet::new();
+        rangeset.insert(Range::new(0, u64::MAX).unwrap()).unwrap();
+
+        rangeset.remove(Range::new(0, 9).unwrap()).unwrap();
+        rangeset
+            .remove(Range::new(u64::MAX, u64::MAX).unwrap())
+            .unwrap();
+
+        let want = [Range::new(10, u64::MAX - 1).unwrap()];
+        assert_eq!(rangeset.ranges(), want);
+        assert_eq!(rangeset.try_size(), Some(u64::MAX - 10));
+    }
+
+    #[test]
+    fn test_rangeset_gaps() {
+        let mut rangeset = RangeSet::new();
+        rangeset.insert(Range::new(10, 19).unwrap()).unwrap();
+        rangeset.insert(Range::new(30, 39).unwrap()).unwrap();
+        rangeset.insert(Range::new(45, 49).unwrap()).unwrap();
+
+        let mut gaps = rangeset.gaps(Range::new(0, 100).unwrap());
+        assert_eq!(gaps.next(), Some(Range::new(0, 9).unwrap()));
+        assert_eq!(gaps.next(), Some(Range::new(20, 29).unwrap()));
+        assert_eq!(gaps.next(), Some(Range::new(40, 44).unwrap()));
+        assert_eq!(gaps.next(), Some(Range::new(50, 100).unwrap()));
+        assert_eq!(gaps.next(), None);
+
+        let mut gaps = rangeset.gaps(Range::new(15, 42).unwrap());
+        assert_eq!(gaps.next(), Some(Range::new(20, 29).unwrap()));
+        assert_eq!(gaps.next(), Some(Range::new(40, 42).unwrap()));
+        assert_eq!(gaps.next(), None);
+
+        let mut gaps = rangeset.gaps(Range::new(30, 35).unwrap());
+        assert_eq!(gaps.next(), None);
+    }
+
+    #[test]
+    fn test_rangeset_gaps_max() {
+        let mut rangeset = RangeSet::new();
+        rangeset.insert(Range::new(10, u64::MAX).unwrap()).unwrap();
+
+        let mut gaps = rangeset.gaps(Range::new(0, u64::MAX).unwrap());
+        assert_eq!(gaps.next(), Some(Range::new(0, 9).unwrap()));
+        assert_eq!(gaps.next(), None);
+    }
+
+    #[test]
+    fn test_rangeset_largest() {
+        let mut rangeset = RangeSet::new();
+        assert_eq!(rangeset.largest_gap(), None);
+        assert_eq!(rangeset.largest_range(), None);
+
+        rangeset.insert(Range::new(10, 19).unwrap()).unwrap();
+        rangeset.insert(Range::new(30, 59).unwrap()).unwrap();
+        rangeset.insert(Range::new(100, 109).unwrap()).unwrap();
+
+        assert_eq!(rangeset.largest_gap(), Some(Range::new(60, 99).unwrap()));
+        assert_eq!(
+            rangeset.largest_range(),
+            Some(Range::new(30, 59).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_rangeset_generic_point() {
+        let mut rangeset = RangeSetOf::<u32>::new();
+        rangeset.insert(RangeOf::new(0, u32::MAX).unwrap()).unwrap();
+        rangeset
+            .remove(RangeOf::new(0x1000, 0x1fff).unwrap())
+            .unwrap();
+
+        let want = [
+            RangeOf::new(0, 0xfff).unwrap(),
+            RangeOf::new(0x2000, u32::MAX).unwrap(),
+        ];
+        assert_eq!(rangeset.ranges(), want);
+        assert_eq!(rangeset.ranges()[1].end(), u32::MAX);
+        assert_eq!(rangeset.allocate(0x1000, 0x1000), Some(0));
+    }
+
+    #[test]
+    fn test_rangeset_encode() {
+        let mut rangeset = RangeSet::new();
+        rangeset.insert(Range::new(0, 10).unwrap()).unwrap();
+        rangeset.insert(Range::new(20, u64::MAX).unwrap()).unwrap();
+
+        let mut buf = [0u8; 64];
+        let len = rangeset.write_to(&mut buf).unwrap();
+        assert_eq!(len, 48);
+        assert_eq!(&buf[..12], b"RSET\x01\x00\x00\x00\x02\x00\x00\x00");
+
+        let decoded = RangeSet::read_from(&buf[..len]).unwrap();
+        assert_eq!(decoded.ranges(), rangeset.ranges());
+    }
+
+    #[test]
+    fn test_rangeset_encode_errors() {
+        let mut rangeset = RangeSet::new();
+        rangeset.insert(Range::new(0, 10).unwrap()).unwrap();
+
+        let mut buf = [0u8; 32];
+        match rangeset.write_to(&mut buf[..31]) {
+            Err(Error::BufferTooSmall) => {}
+            ret => panic!("unexpected result: {:?}", ret),
+        }
+        rangeset.write_to(&mut buf).unwrap();
+
+        match RangeSet::read_from(&buf[..31]) {
+            Err(Error::BufferTooSmall) => {}
+            ret => panic!("unexpected result: {:?}", ret),
+        }
+
+        buf[4] = 2;
+        match RangeSet::read_from(&buf) {
+            Err(Error::InvalidEncoding) => {}
+            ret => panic!("unexpected result: {:?}", ret),
+        }
+    }
 }
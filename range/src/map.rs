@@ -0,0 +1,288 @@
+//! Sets of ranges tagged with a value.
+
+use crate::{Error, Point, RangeOf, RangeSetOf, RANGE_SET_LEN};
+
+/// Represents a set of ranges of points of type `P`, each one associated with
+/// a value. Contrary to `RangeSet`, contiguous ranges are only merged if they
+/// have the same value.
+#[derive(Debug, Clone)]
+pub struct RangeMapOf<T: Copy + PartialEq, P: Point> {
+    /// Entries within the `RangeMap`, sorted by start point. Only the first
+    /// `in_use` entries are `Some`.
+    entries: [Option<(RangeOf<P>, T)>; RANGE_SET_LEN],
+
+    /// Number of elements in the fixed size array that are being used.
+    in_use: usize,
+}
+
+/// Represents a set of ranges of `u64` points, each one associated with a
+/// value.
+pub type RangeMap<T> = RangeMapOf<T, u64>;
+
+impl<T: Copy + PartialEq, P: Point> RangeMapOf<T, P> {
+    /// Returns an empty `RangeMap`.
+    pub fn new() -> Self {
+        RangeMapOf {
+            entries: [None; RANGE_SET_LEN],
+            in_use: 0,
+        }
+    }
+
+    /// Returns the entry at index `idx`, which must be lower than `in_use`.
+    fn entry(&self, idx: usize) -> (RangeOf<P>, T) {
+        self.entries[idx].expect("entries in use must not be empty")
+    }
+
+    /// Returns an iterator over the entries of the `RangeMap`, sorted by
+    /// start point. Each item has the form `(range, value)`.
+    pub fn iter(&self) -> impl Iterator<Item = (RangeOf<P>, T)> + '_ {
+        (0..self.in_use).map(move |idx| self.entry(idx))
+    }
+
+    /// Returns the number of entries in the `RangeMap`.
+    pub fn len(&self) -> usize {
+        self.in_use
+    }
+
+    /// Returns `true` if the `RangeMap` does not contain any entry.
+    pub fn is_empty(&self) -> bool {
+        self.in_use == 0
+    }
+
+    /// Returns the value associated with a given point.
+    pub fn get(&self, point: P) -> Option<T> {
+        self.iter()
+            .find(|(range, _)| range.contains_point(point))
+            .map(|(_, value)| value)
+    }
+
+    /// Returns a `RangeSet` with the ranges associated with `value`.
+    pub fn ranges_with(&self, value: T) -> Result<RangeSetOf<P>, Error> {
+        let mut ret = RangeSetOf::new();
+        for (range, _) in self.iter().filter(|(_, v)| *v == value) {
+            ret.insert(range)?;
+        }
+        Ok(ret)
+    }
+
+    /// Associates `range` with `value`. The points of `range` that were
+    /// associated with other values are overwritten. On error, the
+    /// `RangeMap` is not modified.
+    pub fn insert(
+        &mut self,
+        range: RangeOf<P>,
+        value: T,
+    ) -> Result<(), Error> {
+        let mut map = self.clone();
+        map.remove(range)?;
+
+        // Given that `range` was removed, it does not overlap any entry.
+        let idx = map
+            .iter()
+            .position(|(r, _)| r.start > range.end)
+            .unwrap_or(map.in_use);
+
+        let merge_left = idx > 0 && {
+            let (left, left_value) = map.entry(idx - 1);
+            left_value == value && left.precedes(range)
+        };
+        let merge_right = idx < map.in_use && {
+            let (right, right_value) = map.entry(idx);
+            right_value == value && range.precedes(right)
+        };
+
+        match (merge_left, merge_right) {
+            (true, true) => {
+                // The new range fills the hole between two entries. Extend
+                // the left one and remove the right one.
+                let (left, _) = map.entry(idx - 1);
+                let (right, _) = map.entry(idx);
+                map.entries[idx - 1] =
+                    Some((RangeOf::from_raw(left.start, right.end)?, value));
+                map.entries.copy_within(idx + 1..map.in_use, idx);
+                map.in_use -= 1;
+                map.entries[map.in_use] = None;
+            }
+            (true, false) => {
+                let (left, _) = map.entry(idx - 1);
+                map.entries[idx - 1] =
+                    Some((RangeOf::from_raw(left.start, range.end)?, value));
+            }
+            (false, true) => {
+                let (right, _) = map.entry(idx);
+                map.entries[idx] =
+                    Some((RangeOf::from_raw(range.start, right.end)?, value));
+            }
+            (false, false) => {
+                if map.in_use >= map.entries.len() {
+                    return Err(Error::FullRangeSet);
+                }
+                map.entries.copy_within(idx..map.in_use, idx + 1);
+                map.entries[idx] = Some((range, value));
+                map.in_use += 1;
+            }
+        }
+
+        *self = map;
+        Ok(())
+    }
+
+    /// Removes the association of the points of `range`, splitting or
+    /// shrinking existing entries if necessary.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Error::FullRangeSet` if an existing entry must
+    /// be split and the `RangeMap` is full.
+    pub fn remove(&mut self, range: RangeOf<P>) -> Result<(), Error> {
+        let mut i = 0;
+        while i < self.in_use {
+            let (entry, value) = self.entry(i);
+
+            // Given that the entries are sorted, once the start point of an
+            // entry is above the end point of the range to remove, it is not
+            // necessary to continue iterating.
+            if entry.start > range.end {
+                break;
+            }
+
+            let overlap = match entry.overlap_with(range) {
+                Some(overlap) => overlap,
+                None => {
+                    i += 1;
+                    continue;
+                }
+            };
+
+            // Parts of the entry that are kept, at both sides of the range to
+            // remove.
+            let lo = entry.split_raw(overlap.start).map(|(lo, _)| lo);
+            let hi = overlap
+                .end
+                .checked_add(1)
+                .and_then(|point| entry.split_raw(point))
+                .map(|(_, hi)| hi);
+
+            match (lo, hi) {
+                (None, None) => {
+                    self.entries.copy_within(i + 1..self.in_use, i);
+                    self.in_use -= 1;
+                    self.entries[self.in_use] = None;
+                }
+                (Some(kept), None) | (None, Some(kept)) => {
+                    self.entries[i] = Some((kept, value));
+                    i += 1;
+                }
+                (Some(lo), Some(hi)) => {
+                    if self.in_use >= self.entries.len() {
+                        return Err(Error::FullRangeSet);
+                    }
+                    self.entries.copy_within(i..self.in_use, i + 1);
+                    self.entries[i] = Some((lo, value));
+                    self.entries[i + 1] = Some((hi, value));
+                    self.in_use += 1;
+                    i += 2;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: Copy + PartialEq, P: Point> Default for RangeMapOf<T, P> {
+    fn default() -> Self {
+        RangeMapOf::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Range;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Tag {
+        A,
+        B,
+    }
+
+    #[test]
+    fn test_rangemap_insert() {
+        let mut map = RangeMap::new();
+        map.insert(Range::new(0, 9).unwrap(), Tag::A).unwrap();
+        map.insert(Range::new(10, 19).unwrap(), Tag::B).unwrap();
+        map.insert(Range::new(20, 29).unwrap(), Tag::A).unwrap();
+
+        let want = [
+            (Range::new(0, 9).unwrap(), Tag::A),
+            (Range::new(10, 19).unwrap(), Tag::B),
+            (Range::new(20, 29).unwrap(), Tag::A),
+        ];
+        assert!(map.iter().eq(want.iter().copied()));
+    }
+
+    #[test]
+    fn test_rangemap_insert_merge() {
+        let mut map = RangeMap::new();
+        map.insert(Range::new(0, 9).unwrap(), Tag::A).unwrap();
+        map.insert(Range::new(20, 29).unwrap(), Tag::A).unwrap();
+        map.insert(Range::new(10, 19).unwrap(), Tag::A).unwrap();
+
+        let want = [(Range::new(0, 29).unwrap(), Tag::A)];
+        assert!(map.iter().eq(want.iter().copied()));
+    }
+
+    #[test]
+    fn test_rangemap_insert_overwrite() {
+        let mut map = RangeMap::new();
+        map.insert(Range::new(0, 29).unwrap(), Tag::A).unwrap();
+        map.insert(Range::new(10, 19).unwrap(), Tag::B).unwrap();
+
+        let want = [
+            (Range::new(0, 9).unwrap(), Tag::A),
+            (Range::new(10, 19).unwrap(), Tag::B),
+            (Range::new(20, 29).unwrap(), Tag::A),
+        ];
+        assert!(map.iter().eq(want.iter().copied()));
+        assert_eq!(map.get(15), Some(Tag::B));
+        assert_eq!(map.get(30), None);
+
+        let want = [Range::new(0, 9).unwrap(), Range::new(20, 29).unwrap()];
+        assert_eq!(map.ranges_with(Tag::A).unwrap().ranges(), want);
+    }
+
+    #[test]
+    fn test_rangemap_remove() {
+        let mut map = RangeMap::new();
+        map.insert(Range::new(0, 9).unwrap(), Tag::A).unwrap();
+        map.insert(Range::new(10, 19).unwrap(), Tag::B).unwrap();
+        map.remove(Range::new(5, 12).unwrap()).unwrap();
+
+        let want = [
+            (Range::new(0, 4).unwrap(), Tag::A),
+            (Range::new(13, 19).unwrap(), Tag::B),
+        ];
+        assert!(map.iter().eq(want.iter().copied()));
+    }
+
+    #[test]
+    fn test_rangemap_insert_full() {
+        let mut map = RangeMap::new();
+
+        for i in 0..RANGE_SET_LEN {
+            let point = 2 * (i as u64);
+            map.insert(Range::new(point, point).unwrap(), Tag::A)
+                .unwrap();
+        }
+
+        match map.insert(Range::new(1, 1).unwrap(), Tag::B) {
+            Err(Error::FullRangeSet) => {}
+            ret => panic!("unexpected result: {:?}", ret),
+        }
+        assert_eq!(map.len(), RANGE_SET_LEN);
+
+        map.insert(Range::new(1, 1).unwrap(), Tag::A).unwrap();
+        assert_eq!(map.len(), RANGE_SET_LEN - 1);
+    }
+}
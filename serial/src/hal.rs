@@ -0,0 +1,39 @@
+//! Non-blocking serial traits modeled after the ones of `embedded-hal`.
+//!
+//! They allow other crates to use a serial port without depending on the
+//! `SerialPort` type or on the printing macros of a binary.
+
+/// Errors of a non-blocking operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NbError<E> {
+    /// The operation cannot be completed without blocking. It must be
+    /// retried later.
+    WouldBlock,
+
+    /// Other error.
+    Other(E),
+}
+
+/// Result of a non-blocking operation.
+pub type NbResult<T, E> = Result<T, NbError<E>>;
+
+/// Non-blocking read of a single word.
+pub trait Read<Word> {
+    /// Read error.
+    type Error;
+
+    /// Reads a single word from the serial port.
+    fn read(&mut self) -> NbResult<Word, Self::Error>;
+}
+
+/// Non-blocking write of a single word.
+pub trait Write<Word> {
+    /// Write error.
+    type Error;
+
+    /// Writes a single word to the serial port.
+    fn write(&mut self, word: Word) -> NbResult<(), Self::Error>;
+
+    /// Ensures that all the written words have been transmitted.
+    fn flush(&mut self) -> NbResult<(), Self::Error>;
+}
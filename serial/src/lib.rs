@@ -1,22 +1,262 @@
 //! Serial port support for the 8250 UART used in the IBM PC.
 //!
+//! Characters can be received by polling (`read_u8`, `try_read_u8`) or in
+//! interrupt mode. In interrupt mode, the interrupt handler calls
+//! `on_interrupt`, which moves the received characters into a `RxBuffer`
+//! that readers consume without busy-waiting.
+//!
+//! `SerialPort` implements `core::fmt::Write` and the non-blocking traits of
+//! the `hal` module, so other crates can use it directly.
+//!
 //! Reference:
 //! - [Wikipedia article](https://en.wikipedia.org/wiki/8250_UART)
 //! - [Datasheet](https://web.archive.org/web/20160503070506/http://archive.pcjs.org/pubs/pc/datasheets/8250A-UART.pdf)
 
 #![no_std]
 
+use core::convert::{Infallible, TryFrom};
+use core::fmt;
+
 use cpu::{in8, out8};
 
-/// Error representing that the serial port is not operating normally.
+pub mod hal;
+mod rx;
+
+pub use rx::RxBuffer;
+
+/// Frequency, in Hz, of the UART clock divided by 16. It is the baud rate
+/// obtained with a divisor of 1 for a 1.8432 MHz crystal.
+const MAX_BAUD: u32 = 115200;
+
+/// Size of the transmitter and receiver FIFOs of the 16550A UART.
+const FIFO_LEN: usize = 16;
+
+/// Serial errors.
 #[derive(Debug)]
-pub struct Error;
+pub enum Error {
+    /// There is no UART at the port address. Reading from an unused IO port
+    /// returns all ones.
+    NotPresent,
+
+    /// The loopback test failed. Some virtual UARTs fail it even though they
+    /// can transmit (see `SerialConfig::loopback_test`).
+    LoopbackFailed,
+
+    /// The baud rate cannot be obtained with an integer divisor.
+    InvalidBaudRate(u32),
+}
+
+/// Number of data bits per character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataBits {
+    /// 5 data bits.
+    Five,
+
+    /// 6 data bits.
+    Six,
+
+    /// 7 data bits.
+    Seven,
+
+    /// 8 data bits.
+    Eight,
+}
+
+/// Parity bit mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parity {
+    /// No parity bit.
+    None,
+
+    /// The parity bit makes the number of 1s odd.
+    Odd,
+
+    /// The parity bit makes the number of 1s even.
+    Even,
+
+    /// The parity bit is always 1.
+    Mark,
+
+    /// The parity bit is always 0.
+    Space,
+}
+
+/// Number of stop bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopBits {
+    /// 1 stop bit.
+    One,
+
+    /// 2 stop bits. With 5 data bits, 1.5 stop bits are used instead.
+    Two,
+}
+
+/// Number of characters in the receiver FIFO that trigger a "Received Data
+/// Available" interrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FifoTrigger {
+    /// 1 character.
+    One,
+
+    /// 4 characters.
+    Four,
+
+    /// 8 characters.
+    Eight,
+
+    /// 14 characters.
+    Fourteen,
+}
+
+/// Line settings of a serial port. The default configuration is 38400 8N1,
+/// with the FIFOs enabled and a trigger level of 14 characters. The loopback
+/// test is run by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerialConfig {
+    /// Baud rate.
+    baud: u32,
+
+    /// Number of data bits per character.
+    data_bits: DataBits,
+
+    /// Parity bit mode.
+    parity: Parity,
+
+    /// Number of stop bits.
+    stop_bits: StopBits,
+
+    /// Trigger level of the receiver FIFO. If it is `None`, the FIFOs are
+    /// disabled.
+    fifo_trigger: Option<FifoTrigger>,
+
+    /// Run the loopback test when the serial port is constructed.
+    loopback_test: bool,
+}
+
+impl SerialConfig {
+    /// Returns the default `SerialConfig`.
+    pub const fn new() -> Self {
+        SerialConfig {
+            baud: 38400,
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            fifo_trigger: Some(FifoTrigger::Fourteen),
+            loopback_test: true,
+        }
+    }
+
+    /// Sets the baud rate. It must divide 115200.
+    pub const fn baud(mut self, baud: u32) -> Self {
+        self.baud = baud;
+        self
+    }
+
+    /// Sets the number of data bits per character.
+    pub const fn data_bits(mut self, data_bits: DataBits) -> Self {
+        self.data_bits = data_bits;
+        self
+    }
+
+    /// Sets the parity bit mode.
+    pub const fn parity(mut self, parity: Parity) -> Self {
+        self.parity = parity;
+        self
+    }
+
+    /// Sets the number of stop bits.
+    pub const fn stop_bits(mut self, stop_bits: StopBits) -> Self {
+        self.stop_bits = stop_bits;
+        self
+    }
+
+    /// Sets the trigger level of the receiver FIFO. If it is `None`, the
+    /// FIFOs are disabled. The FIFOs are only enabled if the UART supports
+    /// them.
+    pub const fn fifo_trigger(
+        mut self,
+        fifo_trigger: Option<FifoTrigger>,
+    ) -> Self {
+        self.fifo_trigger = fifo_trigger;
+        self
+    }
+
+    /// Enables or disables the loopback test. It must be disabled for UARTs
+    /// that do not implement loop mode, like some virtual ones.
+    pub const fn loopback_test(mut self, loopback_test: bool) -> Self {
+        self.loopback_test = loopback_test;
+        self
+    }
+
+    /// Returns the value of the divisor latch for the configured baud rate.
+    fn divisor(&self) -> Result<u16, Error> {
+        if self.baud == 0 || MAX_BAUD % self.baud != 0 {
+            return Err(Error::InvalidBaudRate(self.baud));
+        }
+        u16::try_from(MAX_BAUD / self.baud)
+            .map_err(|_| Error::InvalidBaudRate(self.baud))
+    }
+
+    /// Returns the value of the Line Control Register (LCR) for the
+    /// configured line settings, with DLAB disabled.
+    fn line_control(&self) -> u8 {
+        let data_bits = match self.data_bits {
+            DataBits::Five => 0x00,
+            DataBits::Six => 0x01,
+            DataBits::Seven => 0x02,
+            DataBits::Eight => 0x03,
+        };
+        let stop_bits = match self.stop_bits {
+            StopBits::One => 0x00,
+            StopBits::Two => 0x04,
+        };
+        let parity = match self.parity {
+            Parity::None => 0x00,
+            Parity::Odd => 0x08,
+            Parity::Even => 0x18,
+            Parity::Mark => 0x28,
+            Parity::Space => 0x38,
+        };
+        data_bits | stop_bits | parity
+    }
+
+    /// Returns the value of the FIFO Control Register (FCR) for the
+    /// configured trigger level. When the FIFOs are enabled, both of them are
+    /// also cleared.
+    fn fifo_control(&self) -> u8 {
+        let trigger = match self.fifo_trigger {
+            Some(FifoTrigger::One) => 0x00,
+            Some(FifoTrigger::Four) => 0x40,
+            Some(FifoTrigger::Eight) => 0x80,
+            Some(FifoTrigger::Fourteen) => 0xc0,
+            None => return 0x00,
+        };
+
+        // Enable FIFOs, clear receiver FIFO and clear transmitter FIFO.
+        trigger | 0x07
+    }
+}
+
+impl Default for SerialConfig {
+    fn default() -> Self {
+        SerialConfig::new()
+    }
+}
 
 /// Represents a serial port.
-pub struct SerialPort(u16);
+pub struct SerialPort {
+    /// IO port address of the UART.
+    port_addr: u16,
+
+    /// Number of characters that can be written after the Transmitter
+    /// Holding Register (THR) becomes empty. It is 1 if the FIFOs are
+    /// disabled.
+    tx_burst: usize,
+}
 
 impl SerialPort {
-    /// Constructs a new `SerialPort`.
+    /// Constructs a new `SerialPort` with the default configuration (38400
+    /// 8N1).
     ///
     /// # Errors
     ///
@@ -26,10 +266,38 @@ impl SerialPort {
     /// # Safety
     ///
     /// The port address is provided by the user, so creating a new
-    /// `SerialPort` is considered unsafe. However, a `SerialPort` is only
-    /// returned if the loopback test succeeded. Thus, we consider its methods
-    /// to be safe.
+    /// `SerialPort` is considered unsafe. See `with_config`.
     pub unsafe fn new(port_addr: u16) -> Result<SerialPort, Error> {
+        SerialPort::with_config(port_addr, SerialConfig::new())
+    }
+
+    /// Constructs a new `SerialPort` with the line settings `config`.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if the baud rate is not supported or
+    /// there is no UART at `port_addr`. Also, unless it is disabled in
+    /// `config`, it performs a loopback test of the serial port. If it fails,
+    /// an `Error` is returned.
+    ///
+    /// # Safety
+    ///
+    /// The port address is provided by the user, so creating a new
+    /// `SerialPort` is considered unsafe. However, a `SerialPort` is only
+    /// returned if a UART is found at the port address. Thus, we consider its
+    /// methods to be safe.
+    pub unsafe fn with_config(
+        port_addr: u16,
+        config: SerialConfig,
+    ) -> Result<SerialPort, Error> {
+        let divisor = config.divisor()?;
+
+        // The Line Status Register always has some bits clear, so all ones
+        // means that nothing answers at the port address.
+        if in8(port_addr + 5) == 0xff {
+            return Err(Error::NotPresent);
+        }
+
         // Disable DLAB.
         out8(port_addr + 3, 0x00);
 
@@ -39,30 +307,83 @@ impl SerialPort {
         // Enable DLAB.
         out8(port_addr + 3, 0x80);
 
-        // Set divisor latch to 3 (38400 bps for a 1.8432 MHz Crystal).
+        // Set divisor latch (115200 / divisor bps for a 1.8432 MHz Crystal).
         // LSB.
-        out8(port_addr, 0x03);
+        out8(port_addr, divisor as u8);
         // MSB.
-        out8(port_addr + 1, 0x00);
+        out8(port_addr + 1, (divisor >> 8) as u8);
 
-        // Disable DLAB. Set 8N1 mode.
-        out8(port_addr + 3, 0x03);
+        // Disable DLAB. Set the line settings.
+        out8(port_addr + 3, config.line_control());
 
-        // Enable loop mode for loopback test.
-        out8(port_addr + 4, 0x10);
+        // Set up the FIFOs. The Interrupt Identification Register (IIR)
+        // reports if they are enabled, which only happens on a 16550A or
+        // newer UART. The FIFOs of the original 16550 are faulty, so they are
+        // disabled in that case.
+        out8(port_addr + 2, config.fifo_control());
+        let tx_burst = if in8(port_addr + 2) & 0xc0 == 0xc0 {
+            FIFO_LEN
+        } else {
+            out8(port_addr + 2, 0x00);
+            1
+        };
 
-        // Check that we received the same byte we sent. If that is not the
-        // case, then return an error because the serial is faulty.
-        out8(port_addr, 0xae);
-        if in8(port_addr) != 0xae {
-            return Err(Error);
+        if config.loopback_test {
+            // Enable loop mode for loopback test.
+            out8(port_addr + 4, 0x10);
+
+            // Check that we received the same byte we sent. If that is not
+            // the case, then return an error because the serial is faulty.
+            out8(port_addr, 0xae);
+            if in8(port_addr) != 0xae {
+                return Err(Error::LoopbackFailed);
+            }
         }
 
         // If the serial is working properly, set it in normal operation mode.
         // Modem Control Register: Disable loop mode.
         out8(port_addr + 4, 0x00);
 
-        Ok(SerialPort(port_addr))
+        Ok(SerialPort {
+            port_addr,
+            tx_burst,
+        })
+    }
+
+    /// Enables the "Received Data Available" interrupt. The Out2 signal is
+    /// also enabled, given that it gates the interrupt line of the UART in
+    /// the IBM PC.
+    pub fn enable_rx_interrupt(&self) {
+        unsafe {
+            // Modem Control Register: Enable Out2.
+            out8(self.port_addr + 4, 0x08);
+
+            // Interrupt Enable Register: Enable "Received Data Available".
+            out8(self.port_addr + 1, 0x01);
+        }
+    }
+
+    /// Disables all the interrupts of the UART.
+    pub fn disable_rx_interrupt(&self) {
+        unsafe {
+            out8(self.port_addr + 1, 0x00);
+            out8(self.port_addr + 4, 0x00);
+        }
+    }
+
+    /// Handles an interrupt of the UART. It moves all the received
+    /// characters into `rx`. It must be called by the interrupt handler once
+    /// interrupt mode is enabled with `enable_rx_interrupt`.
+    pub fn on_interrupt<const N: usize>(&self, rx: &RxBuffer<N>) {
+        // Reading the Receiver Buffer Register until it is empty clears the
+        // interrupt. If other CPU is filling the buffer, it will drain the
+        // register.
+        rx.fill(|| self.try_read_u8());
+    }
+
+    /// Returns `true` if the FIFOs are enabled.
+    pub fn has_fifo(&self) -> bool {
+        self.tx_burst > 1
     }
 
     /// Returns `true` if the Transmitter Holding Register (THR) is empty,
@@ -70,22 +391,35 @@ impl SerialPort {
     /// transmission.
     fn is_thr_empty(&self) -> bool {
         // Check the "Transmitter Holding Register Empty" indicator.
-        unsafe { in8(self.0 + 5) & 0x20 != 0 }
+        unsafe { in8(self.port_addr + 5) & 0x20 != 0 }
+    }
+
+    /// Returns `true` if both the Transmitter Holding Register (THR) and the
+    /// transmitter shift register are empty, indicating that all the written
+    /// characters have been sent.
+    fn is_transmitter_empty(&self) -> bool {
+        // Check the "Transmitter Empty" indicator.
+        unsafe { in8(self.port_addr + 5) & 0x40 != 0 }
     }
 
     /// Writes a single `u8` to the serial port.
     pub fn write_u8(&self, b: u8) {
         while !self.is_thr_empty() {}
 
-        unsafe { out8(self.0, b) };
+        unsafe { out8(self.port_addr, b) };
     }
 
-    /// Writes the buffer `buf` to the serial port.
+    /// Writes the buffer `buf` to the serial port. If the FIFOs are enabled,
+    /// the transmitter FIFO is filled every time it becomes empty.
     pub fn write<B: AsRef<[u8]>>(&self, buf: B) {
         let buf = buf.as_ref();
 
-        for b in buf.iter() {
-            self.write_u8(*b);
+        for chunk in buf.chunks(self.tx_burst) {
+            while !self.is_thr_empty() {}
+
+            for b in chunk.iter() {
+                unsafe { out8(self.port_addr, *b) };
+            }
         }
     }
 
@@ -93,13 +427,116 @@ impl SerialPort {
     /// transferred into the Receiver Buffer Register.
     fn is_data_ready(&self) -> bool {
         // Check the "Data Ready" indicator.
-        unsafe { in8(self.0 + 5) & 0x1 != 0 }
+        unsafe { in8(self.port_addr + 5) & 0x1 != 0 }
     }
 
     /// Reads a single `u8` from the serial port.
     pub fn read_u8(&self) -> u8 {
         while !self.is_data_ready() {}
 
-        unsafe { in8(self.0) }
+        unsafe { in8(self.port_addr) }
+    }
+
+    /// Reads a single `u8` from the serial port without waiting. It returns
+    /// `None` if no character has been received.
+    pub fn try_read_u8(&self) -> Option<u8> {
+        if !self.is_data_ready() {
+            return None;
+        }
+
+        Some(unsafe { in8(self.port_addr) })
+    }
+
+    /// Reads a single `u8` from the serial port, polling it at most `spins`
+    /// times. It returns `None` if no character is received in time.
+    pub fn read_u8_timeout(&self, spins: usize) -> Option<u8> {
+        for _ in 0..spins {
+            if let Some(b) = self.try_read_u8() {
+                return Some(b);
+            }
+            core::hint::spin_loop();
+        }
+        None
+    }
+}
+
+impl fmt::Write for SerialPort {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        SerialPort::write(self, s);
+        Ok(())
+    }
+}
+
+impl hal::Read<u8> for SerialPort {
+    type Error = Infallible;
+
+    fn read(&mut self) -> hal::NbResult<u8, Self::Error> {
+        self.try_read_u8().ok_or(hal::NbError::WouldBlock)
+    }
+}
+
+impl hal::Write<u8> for SerialPort {
+    type Error = Infallible;
+
+    fn write(&mut self, word: u8) -> hal::NbResult<(), Self::Error> {
+        if !self.is_thr_empty() {
+            return Err(hal::NbError::WouldBlock);
+        }
+
+        unsafe { out8(self.port_addr, word) };
+        Ok(())
+    }
+
+    fn flush(&mut self) -> hal::NbResult<(), Self::Error> {
+        if !self.is_transmitter_empty() {
+            return Err(hal::NbError::WouldBlock);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialconfig_default() {
+        let config = SerialConfig::new();
+        assert_eq!(config.divisor().unwrap(), 3);
+        assert_eq!(config.line_control(), 0x03);
+        assert_eq!(config.fifo_control(), 0xc7);
+    }
+
+    #[test]
+    fn test_serialconfig_divisor() {
+        assert_eq!(SerialConfig::new().baud(115200).divisor().unwrap(), 1);
+        assert_eq!(SerialConfig::new().baud(9600).divisor().unwrap(), 12);
+        assert_eq!(SerialConfig::new().baud(50).divisor().unwrap(), 2304);
+
+        // The divisor of 1 baud does not fit in the divisor latch.
+        for baud in [0, 1, 7, 230400].iter() {
+            match SerialConfig::new().baud(*baud).divisor() {
+                Err(Error::InvalidBaudRate(b)) if b == *baud => {}
+                ret => panic!("unexpected result: {:?}", ret),
+            }
+        }
+    }
+
+    #[test]
+    fn test_serialconfig_line_control() {
+        let config = SerialConfig::new()
+            .data_bits(DataBits::Seven)
+            .parity(Parity::Even)
+            .stop_bits(StopBits::Two);
+        assert_eq!(config.line_control(), 0x1e);
+    }
+
+    #[test]
+    fn test_serialconfig_fifo_control() {
+        let config = SerialConfig::new().fifo_trigger(Some(FifoTrigger::Four));
+        assert_eq!(config.fifo_control(), 0x47);
+
+        let config = SerialConfig::new().fifo_trigger(None);
+        assert_eq!(config.fifo_control(), 0x00);
     }
 }
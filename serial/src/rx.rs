@@ -0,0 +1,176 @@
+//! Receive buffer used in interrupt mode.
+//!
+//! The interrupt handler drains the Receiver Buffer Register into a
+//! `RxBuffer`, and readers consume the received characters later. The buffer
+//! is a lock-free single-producer single-consumer ring. Concurrent producers
+//! or consumers do not block either: they give up and try again on the next
+//! call.
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Represents a buffer of received characters with capacity for `N`
+/// characters.
+pub struct RxBuffer<const N: usize> {
+    /// Received characters.
+    data: UnsafeCell<[u8; N]>,
+
+    /// Number of characters read since the creation of the buffer. It is
+    /// only modified by the consumer.
+    head: AtomicUsize,
+
+    /// Number of characters written since the creation of the buffer. It is
+    /// only modified by the producer.
+    tail: AtomicUsize,
+
+    /// Set while a producer is writing into the buffer.
+    producing: AtomicBool,
+
+    /// Set while a consumer is reading from the buffer.
+    consuming: AtomicBool,
+
+    /// Number of characters dropped because the buffer was full.
+    dropped: AtomicUsize,
+}
+
+impl<const N: usize> RxBuffer<N> {
+    /// Returns an empty `RxBuffer`.
+    pub const fn new() -> Self {
+        RxBuffer {
+            data: UnsafeCell::new([0; N]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            producing: AtomicBool::new(false),
+            consuming: AtomicBool::new(false),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// Appends the characters returned by `next` until it returns `None`.
+    /// The characters that do not fit are dropped. It returns `false`
+    /// without calling `next` if other producer is using the buffer.
+    pub(crate) fn fill<F: FnMut() -> Option<u8>>(&self, mut next: F) -> bool {
+        if self.producing.swap(true, Ordering::Acquire) {
+            return false;
+        }
+
+        let mut tail = self.tail.load(Ordering::Relaxed);
+        while let Some(b) = next() {
+            // The `Acquire` load synchronizes with the `Release` store of the
+            // consumer, so the slot is not overwritten before it is read.
+            let head = self.head.load(Ordering::Acquire);
+            if tail.wrapping_sub(head) >= N {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+
+            // The slot is not visible to the consumer until `tail` is
+            // updated, so the producer has exclusive access to it.
+            unsafe { (*self.data.get())[tail % N] = b };
+            tail = tail.wrapping_add(1);
+
+            // Publish the character to the consumer.
+            self.tail.store(tail, Ordering::Release);
+        }
+
+        self.producing.store(false, Ordering::Release);
+        true
+    }
+
+    /// Reads the received characters into `buf` and returns the number of
+    /// characters read. It returns 0 if the buffer is empty or other
+    /// consumer is using it.
+    pub fn read(&self, buf: &mut [u8]) -> usize {
+        if self.consuming.swap(true, Ordering::Acquire) {
+            return 0;
+        }
+
+        // The `Acquire` load synchronizes with the `Release` store of the
+        // producer, so the characters are visible.
+        let mut head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+
+        let mut n = 0;
+        while head != tail && n < buf.len() {
+            // The producer does not write into the slot until `head` is
+            // updated, so the consumer has exclusive access to it.
+            buf[n] = unsafe { (*self.data.get())[head % N] };
+            head = head.wrapping_add(1);
+            n += 1;
+        }
+
+        // Release the slots to the producer.
+        self.head.store(head, Ordering::Release);
+
+        self.consuming.store(false, Ordering::Release);
+        n
+    }
+
+    /// Reads a single received character. It returns `None` if the buffer
+    /// is empty or other consumer is using it.
+    pub fn read_u8(&self) -> Option<u8> {
+        let mut b = [0];
+        if self.read(&mut b) == 0 {
+            return None;
+        }
+        Some(b[0])
+    }
+
+    /// Returns the number of characters dropped because the buffer was full.
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl<const N: usize> Default for RxBuffer<N> {
+    fn default() -> Self {
+        RxBuffer::new()
+    }
+}
+
+unsafe impl<const N: usize> Sync for RxBuffer<N> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rxbuffer_read() {
+        let rx = RxBuffer::<4>::new();
+        let mut input = b"abc".iter().copied();
+        assert!(rx.fill(|| input.next()));
+
+        let mut buf = [0; 2];
+        assert_eq!(rx.read(&mut buf), 2);
+        assert_eq!(&buf, b"ab");
+        assert_eq!(rx.read_u8(), Some(b'c'));
+        assert_eq!(rx.read_u8(), None);
+    }
+
+    #[test]
+    fn test_rxbuffer_wrap() {
+        let rx = RxBuffer::<4>::new();
+        let mut buf = [0; 4];
+
+        for chunk in [&b"abc"[..], b"defg", b"h"].iter() {
+            let mut input = chunk.iter().copied();
+            rx.fill(|| input.next());
+
+            assert_eq!(rx.read(&mut buf), chunk.len());
+            assert_eq!(&buf[..chunk.len()], *chunk);
+        }
+        assert_eq!(rx.dropped(), 0);
+    }
+
+    #[test]
+    fn test_rxbuffer_full() {
+        let rx = RxBuffer::<4>::new();
+        let mut input = b"abcdef".iter().copied();
+        rx.fill(|| input.next());
+
+        let mut buf = [0; 8];
+        assert_eq!(rx.read(&mut buf), 4);
+        assert_eq!(&buf[..4], b"abcd");
+        assert_eq!(rx.dropped(), 2);
+    }
+}
@@ -1,18 +1,39 @@
 //! Mutex based on the Ticket Lock spin lock described in [Algorithms for
 //! Scalable Synchronization on Shared-Memory Multiprocessors][ref].
 //!
-//! This implementation uses `Ordering::SeqCst` for all the atomic operations.
-//! This has performance implications under some circumstances, but correctness
-//! has been put fist.
+//! The atomic operations use the weakest orderings that keep the lock
+//! correct. Taking a ticket only needs to be atomic, so it is `Relaxed`.
+//! The owner publishes its writes to the protected data with a `Release`
+//! store to `now_serving`, and the next owner observes them with the
+//! `Acquire` load that finds its ticket being served.
+//!
+//! Waiters use exponential backoff while polling the ticket being served, so
+//! heavily contended locks do not saturate the memory bus.
+//!
+//! In builds with debug assertions, a CPU that locks a `TicketMutex` it
+//! already holds panics instead of hanging (see `set_cpu_id`).
 //!
 //! [ref]: http://web.mit.edu/6.173/www/currentsemester/readings/R06-scalable-synchronization-1991.pdf
 
 #![no_std]
 
 use core::cell::UnsafeCell;
+use core::marker::PhantomData;
 use core::ops::{Deref, DerefMut};
 use core::sync::atomic::{AtomicUsize, Ordering};
 
+mod owner;
+mod rw;
+
+pub use owner::set_cpu_id;
+pub use rw::{RwTicketLock, RwTicketReadGuard, RwTicketWriteGuard};
+
+#[cfg(debug_assertions)]
+use core::panic::Location;
+
+#[cfg(debug_assertions)]
+use owner::Owner;
+
 /// Represents a mutex based on a Ticket Lock.
 pub struct TicketMutex<T> {
     /// Next ticket.
@@ -21,6 +42,10 @@ pub struct TicketMutex<T> {
     /// Ticket being served.
     now_serving: AtomicUsize,
 
+    /// Holder of the mutex. It is used to detect deadlocks.
+    #[cfg(debug_assertions)]
+    owner: Owner,
+
     /// Protected data.
     data: UnsafeCell<T>,
 }
@@ -31,23 +56,146 @@ impl<T> TicketMutex<T> {
         TicketMutex {
             next_ticket: AtomicUsize::new(0),
             now_serving: AtomicUsize::new(0),
+            #[cfg(debug_assertions)]
+            owner: Owner::new(),
             data: UnsafeCell::new(data),
         }
     }
 
+    /// Returns a mutable reference to the protected data. No locking is
+    /// needed, given that the mutable borrow guarantees exclusive access.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Consumes the `TicketMutex` and returns the protected data.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+
     /// Locks the `TicketMutex` and returns a `TicketMutexGuard` that allows
     /// exclusive access to the protected data.
+    #[cfg_attr(debug_assertions, track_caller)]
     pub fn lock(&self) -> TicketMutexGuard<T> {
-        // Atomically get the next ticket and increment it.
-        let ticket = self.next_ticket.fetch_add(1, Ordering::SeqCst);
+        self.lock_with_idle(|| spin(1 << BACKOFF_MAX_SHIFT))
+    }
+
+    /// Locks the `TicketMutex` like `lock`, but calls `idle` on every poll
+    /// once the exponential backoff has reached its maximum. This allows the
+    /// caller to put an idle CPU to sleep (e.g. `pause` followed by `hlt` with
+    /// interrupts enabled) instead of spinning.
+    ///
+    /// # Panics
+    ///
+    /// In builds with debug assertions, this function panics if the current
+    /// CPU already holds the mutex.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn lock_with_idle<F: FnMut()>(
+        &self,
+        mut idle: F,
+    ) -> TicketMutexGuard<T> {
+        #[cfg(debug_assertions)]
+        self.owner.check(Location::caller());
+
+        // Atomically get the next ticket and increment it. The ticket does
+        // not protect any data, so no ordering is needed.
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
 
         // Wait until our ticket is served and return a `TicketMutexGuard`
-        // for this mutex.
-        while self.now_serving.load(Ordering::SeqCst) != ticket {
-            core::hint::spin_loop()
+        // for this mutex. The `Acquire` load synchronizes with the `Release`
+        // store of the previous owner, so its writes are visible.
+        let mut backoff = Backoff::new();
+        while self.now_serving.load(Ordering::Acquire) != ticket {
+            if backoff.is_saturated() {
+                idle();
+            } else {
+                backoff.spin();
+            }
         }
+        #[cfg(debug_assertions)]
+        self.owner.set(Location::caller());
+
         TicketMutexGuard::new(self)
     }
+
+    /// Locks the `TicketMutex`, calls `f` with a mutable reference to the
+    /// protected data and returns its result. The lock is released before
+    /// returning.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn with<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> R {
+        f(&mut self.lock())
+    }
+
+    /// Tries to lock the `TicketMutex` without waiting. If the mutex is not
+    /// locked, it returns a `TicketMutexGuard` that allows exclusive access to
+    /// the protected data. Otherwise, it returns `None`.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_lock(&self) -> Option<TicketMutexGuard<T>> {
+        // Only take a ticket if it is the one being served. Thus, the ticket
+        // is never taken if there is an owner or other waiters. If the load
+        // returns an outdated value, `next_ticket` is already past it and the
+        // exchange fails. Otherwise, the `Acquire` load synchronizes with the
+        // `Release` store of the previous owner.
+        let ticket = self.now_serving.load(Ordering::Acquire);
+        self.next_ticket
+            .compare_exchange(
+                ticket,
+                ticket.wrapping_add(1),
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            )
+            .ok()?;
+
+        #[cfg(debug_assertions)]
+        self.owner.set(Location::caller());
+
+        Some(TicketMutexGuard::new(self))
+    }
+
+    /// Returns `true` if the `TicketMutex` is locked. The result can be
+    /// outdated as soon as it is returned, so it must only be used as a hint.
+    pub fn is_locked(&self) -> bool {
+        self.next_ticket.load(Ordering::Relaxed)
+            != self.now_serving.load(Ordering::Relaxed)
+    }
+}
+
+/// Maximum exponent of the backoff. Waiters never spin more than
+/// `1 << BACKOFF_MAX_SHIFT` iterations between two polls.
+const BACKOFF_MAX_SHIFT: u32 = 10;
+
+/// Exponential backoff used while waiting for a ticket to be served.
+struct Backoff {
+    /// Exponent of the next burst of spins.
+    shift: u32,
+}
+
+impl Backoff {
+    /// Returns a new `Backoff` starting with a single spin.
+    fn new() -> Self {
+        Backoff { shift: 0 }
+    }
+
+    /// Spins `1 << shift` times and doubles the length of the next burst,
+    /// until `BACKOFF_MAX_SHIFT` is reached.
+    fn spin(&mut self) {
+        spin(1 << self.shift);
+        if self.shift < BACKOFF_MAX_SHIFT {
+            self.shift += 1;
+        }
+    }
+
+    /// Returns `true` if the backoff has reached its maximum.
+    fn is_saturated(&self) -> bool {
+        self.shift >= BACKOFF_MAX_SHIFT
+    }
+}
+
+/// Emits `n` spin loop hints.
+fn spin(n: u32) {
+    for _ in 0..n {
+        core::hint::spin_loop();
+    }
 }
 
 unsafe impl<T: Send> Send for TicketMutex<T> {}
@@ -59,7 +207,8 @@ unsafe impl<T: Send> Sync for TicketMutex<T> {}
 /// The data protected by the mutex can be accessed through this guard via its
 /// `Deref` and `DerefMut` implementations.
 ///
-/// This structure is created by the `lock` method on `TicketMutex`.
+/// This structure is created by the `lock`, `lock_with_idle` and `try_lock`
+/// methods on `TicketMutex`.
 pub struct TicketMutexGuard<'a, T> {
     /// `TicketMutex` associated with this `TicketMutexGuard`. It is used to
     /// provide access to the protected data.
@@ -71,6 +220,53 @@ impl<'a, T> TicketMutexGuard<'a, T> {
     fn new(mutex: &'a TicketMutex<T>) -> Self {
         TicketMutexGuard { mutex }
     }
+
+    /// Makes a `MappedTicketMutexGuard` for a component of the protected
+    /// data. The mutex stays locked until the returned guard is dropped.
+    ///
+    /// This is an associated function, so it does not conflict with the
+    /// methods of the protected data. It must be called as
+    /// `TicketMutexGuard::map(guard, f)`.
+    pub fn map<U, F>(guard: Self, f: F) -> MappedTicketMutexGuard<'a, U>
+    where
+        F: FnOnce(&mut T) -> &mut U,
+    {
+        match TicketMutexGuard::try_map(guard, |data| Some(f(data))) {
+            Ok(mapped) => mapped,
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Makes a `MappedTicketMutexGuard` for a component of the protected
+    /// data that may not exist. If `f` returns `None`, the original guard is
+    /// returned back.
+    pub fn try_map<U, F>(
+        guard: Self,
+        f: F,
+    ) -> Result<MappedTicketMutexGuard<'a, U>, Self>
+    where
+        F: FnOnce(&mut T) -> Option<&mut U>,
+    {
+        // The pointer to the component is valid as long as the mutex is
+        // locked, which is guaranteed by the mapped guard.
+        let data = match f(unsafe { &mut *guard.mutex.data.get() }) {
+            Some(data) => data as *mut U,
+            None => return Err(guard),
+        };
+
+        // The lock is now owned by the mapped guard, so the original one must
+        // not release it.
+        let mutex = guard.mutex;
+        core::mem::forget(guard);
+
+        Ok(MappedTicketMutexGuard {
+            now_serving: &mutex.now_serving,
+            #[cfg(debug_assertions)]
+            owner: &mutex.owner,
+            data,
+            _data: PhantomData,
+        })
+    }
 }
 
 impl<T> Deref for TicketMutexGuard<'_, T> {
@@ -94,7 +290,208 @@ impl<T> DerefMut for TicketMutexGuard<'_, T> {
 
 impl<T> Drop for TicketMutexGuard<'_, T> {
     fn drop(&mut self) {
-        // Release the lock by incrementing the ticket being served.
-        self.mutex.now_serving.fetch_add(1, Ordering::SeqCst);
+        #[cfg(debug_assertions)]
+        self.mutex.owner.clear();
+
+        unlock(&self.mutex.now_serving);
+    }
+}
+
+/// An RAII guard for a component of the data protected by a `TicketMutex`.
+/// When this structure is dropped (falls out of scope), the lock will be
+/// unlocked.
+///
+/// This structure is created by the `map` and `try_map` associated functions
+/// of `TicketMutexGuard`.
+pub struct MappedTicketMutexGuard<'a, U> {
+    /// Ticket being served by the locked `TicketMutex`. It is used to release
+    /// the lock.
+    now_serving: &'a AtomicUsize,
+
+    /// Holder of the locked `TicketMutex`. It is cleared before releasing
+    /// the lock.
+    #[cfg(debug_assertions)]
+    owner: &'a Owner,
+
+    /// Component of the protected data.
+    data: *mut U,
+
+    /// The guard behaves like a mutable borrow of the component.
+    _data: PhantomData<&'a mut U>,
+}
+
+impl<U> Deref for MappedTicketMutexGuard<'_, U> {
+    type Target = U;
+
+    fn deref(&self) -> &Self::Target {
+        // The mutex is locked while the `MappedTicketMutexGuard` exists, so
+        // we have exclusive access to the component.
+        unsafe { &*self.data }
+    }
+}
+
+impl<U> DerefMut for MappedTicketMutexGuard<'_, U> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // Safe because of the same reasons explained in `Deref`.
+        unsafe { &mut *self.data }
+    }
+}
+
+impl<U> Drop for MappedTicketMutexGuard<'_, U> {
+    fn drop(&mut self) {
+        #[cfg(debug_assertions)]
+        self.owner.clear();
+
+        unlock(self.now_serving);
+    }
+}
+
+/// Releases a `TicketMutex` given its `now_serving` ticket.
+fn unlock(now_serving: &AtomicUsize) {
+    // Release the lock by incrementing the ticket being served. Only the
+    // owner modifies `now_serving`, so a load followed by a store is enough.
+    // The `Release` store publishes the writes to the protected data to the
+    // next owner.
+    let ticket = now_serving.load(Ordering::Relaxed);
+    now_serving.store(ticket.wrapping_add(1), Ordering::Release);
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    /// Number of threads used by the stress tests.
+    const THREADS: usize = 4;
+
+    /// Number of iterations of each thread.
+    const ITERS: usize = 1_000;
+
+    #[test]
+    fn test_ticket_mutex_counter() {
+        // The counter is incremented with a non-atomic read-modify-write
+        // under the lock. A missing happens-before edge between owners would
+        // lose increments.
+        let mutex = Arc::new(TicketMutex::new(0usize));
+
+        let handles: std::vec::Vec<_> = (0..THREADS)
+            .map(|_| {
+                let mutex = Arc::clone(&mutex);
+                thread::spawn(move || {
+                    // Yield while waiting, so the test also progresses on a
+                    // single CPU.
+                    for _ in 0..ITERS {
+                        *mutex.lock_with_idle(thread::yield_now) += 1;
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(*mutex.lock(), THREADS * ITERS);
+        assert!(!mutex.is_locked());
+    }
+
+    #[test]
+    fn test_ticket_mutex_try_lock() {
+        let mutex = TicketMutex::new(0usize);
+
+        let guard = mutex.try_lock().unwrap();
+        assert!(mutex.is_locked());
+        assert!(mutex.try_lock().is_none());
+        drop(guard);
+
+        assert!(mutex.try_lock().is_some());
+    }
+
+    #[test]
+    fn test_ticket_mutex_map() {
+        let mutex = TicketMutex::new((0usize, Some(0usize)));
+
+        let mut first =
+            TicketMutexGuard::map(mutex.lock(), |data| &mut data.0);
+        *first = 1;
+        assert!(mutex.is_locked());
+        drop(first);
+        assert!(!mutex.is_locked());
+
+        let mut second =
+            TicketMutexGuard::try_map(mutex.lock(), |data| data.1.as_mut())
+                .ok()
+                .unwrap();
+        *second = 2;
+        drop(second);
+
+        mutex.with(|data| data.1 = None);
+        assert!(TicketMutexGuard::try_map(mutex.lock(), |data| data
+            .1
+            .as_mut())
+        .is_err());
+        assert_eq!(*mutex.lock(), (1, None));
+    }
+
+    /// Returns a unique ID for the current thread. It plays the role of the
+    /// CPU ID in the tests.
+    #[cfg(debug_assertions)]
+    fn thread_id() -> usize {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        std::thread_local! {
+            static ID: usize = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        }
+        ID.with(|id| *id)
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "deadlock")]
+    fn test_ticket_mutex_deadlock() {
+        set_cpu_id(thread_id);
+
+        let mutex = TicketMutex::new(0usize);
+        let _guard = TicketMutexGuard::map(mutex.lock(), |data| data);
+        assert!(mutex.try_lock().is_none());
+        let _ = mutex.lock();
+    }
+
+    #[test]
+    fn test_ticket_mutex_get_mut() {
+        let mut mutex = TicketMutex::new(0usize);
+        *mutex.get_mut() = 1337;
+        assert_eq!(mutex.into_inner(), 1337);
+    }
+
+    #[test]
+    fn test_rw_ticket_lock_counter() {
+        // Writers keep both fields equal. Readers must never observe them in
+        // the middle of an update.
+        let lock = Arc::new(RwTicketLock::new((0usize, 0usize)));
+
+        let handles: std::vec::Vec<_> = (0..THREADS)
+            .map(|i| {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    for _ in 0..ITERS {
+                        if i % 2 == 0 {
+                            let mut data = lock.write();
+                            data.0 += 1;
+                            data.1 += 1;
+                        } else {
+                            let data = lock.read();
+                            assert_eq!(data.0, data.1);
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(lock.read().0, THREADS / 2 * ITERS);
     }
 }
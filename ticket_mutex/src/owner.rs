@@ -0,0 +1,111 @@
+//! Owner tracking used to detect deadlocks in debug builds.
+//!
+//! Every `TicketMutex` records the CPU and the location of its holder. If a
+//! CPU tries to lock a mutex it already holds, it would wait forever for its
+//! own ticket, so it panics reporting both locations instead.
+//!
+//! The crate does not know how to identify the current CPU, so detection is
+//! only enabled after `set_cpu_id` is called. Owner tracking is only compiled
+//! in builds with debug assertions.
+
+#[cfg(debug_assertions)]
+use core::panic::Location;
+use core::ptr;
+#[cfg(debug_assertions)]
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// Value of `Owner::cpu` when the mutex is not locked or the CPU of the
+/// holder is unknown.
+#[cfg(debug_assertions)]
+const NO_CPU: usize = usize::MAX;
+
+/// Function that returns the ID of the current CPU, stored as a pointer. It
+/// is null until `set_cpu_id` is called.
+static CPU_ID: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+/// Sets the function used to get the ID of the current CPU. Deadlock
+/// detection is disabled until this function is called. The returned IDs
+/// must be unique per CPU or, if the holder of a mutex can be preempted,
+/// unique per preemptible context (e.g. a task).
+///
+/// This function has no effect in builds without debug assertions.
+pub fn set_cpu_id(cpu_id: fn() -> usize) {
+    CPU_ID.store(cpu_id as *mut (), Ordering::Relaxed);
+}
+
+/// Returns the ID of the current CPU or `None` if `set_cpu_id` was not
+/// called.
+#[cfg(debug_assertions)]
+fn current_cpu() -> Option<usize> {
+    let cpu_id = CPU_ID.load(Ordering::Relaxed);
+    if cpu_id.is_null() {
+        return None;
+    }
+
+    // The pointer was stored by `set_cpu_id` from a `fn() -> usize`.
+    let cpu_id =
+        unsafe { core::mem::transmute::<*mut (), fn() -> usize>(cpu_id) };
+    Some(cpu_id())
+}
+
+/// Holder of a `TicketMutex`.
+#[cfg(debug_assertions)]
+pub(crate) struct Owner {
+    /// ID of the CPU holding the mutex or `NO_CPU`.
+    cpu: AtomicUsize,
+
+    /// Location where the mutex was locked. It is null if the mutex is not
+    /// locked.
+    location: AtomicPtr<Location<'static>>,
+}
+
+#[cfg(debug_assertions)]
+impl Owner {
+    /// Returns an `Owner` for a mutex that is not locked.
+    pub(crate) const fn new() -> Self {
+        Owner {
+            cpu: AtomicUsize::new(NO_CPU),
+            location: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Panics if the current CPU holds the mutex. `location` is where the
+    /// mutex is being locked again.
+    ///
+    /// Only the holder stores its own ID in `cpu` and it clears it before
+    /// unlocking, so a CPU always sees its own ID if it holds the mutex and
+    /// never sees it otherwise. Thus, `Relaxed` loads are enough.
+    pub(crate) fn check(&self, location: &'static Location<'static>) {
+        let cpu = match current_cpu() {
+            Some(cpu) => cpu,
+            None => return,
+        };
+        if self.cpu.load(Ordering::Relaxed) != cpu {
+            return;
+        }
+
+        // The current CPU stored the location in `set` before its ID, so it
+        // cannot be null. The pointer comes from a `&'static Location`.
+        let owner = unsafe { &*self.location.load(Ordering::Relaxed) };
+        panic!(
+            "deadlock: CPU {} relocked mutex at {}, already locked at {}",
+            cpu, location, owner
+        );
+    }
+
+    /// Records the current CPU as the holder of the mutex, which was locked
+    /// at `location`.
+    pub(crate) fn set(&self, location: &'static Location<'static>) {
+        let cpu = current_cpu().unwrap_or(NO_CPU);
+        self.location
+            .store(location as *const _ as *mut _, Ordering::Relaxed);
+        self.cpu.store(cpu, Ordering::Relaxed);
+    }
+
+    /// Clears the holder of the mutex. It must be called before unlocking.
+    pub(crate) fn clear(&self) {
+        self.cpu.store(NO_CPU, Ordering::Relaxed);
+        self.location.store(ptr::null_mut(), Ordering::Relaxed);
+    }
+}
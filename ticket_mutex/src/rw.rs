@@ -0,0 +1,167 @@
+//! Reader-writer lock based on the phase-fair ticket lock (PF-T) described in
+//! [Reader-Writer Synchronization for Shared-Memory Multiprocessor Real-Time
+//! Systems][ref].
+//!
+//! Readers and writers alternate in phases. A reader that arrives while a
+//! writer is waiting or holding the lock waits for at most one write phase,
+//! and a writer waits for at most one read phase plus the writers queued in
+//! front of it. Thus, neither readers nor writers can starve.
+//!
+//! [ref]: https://www.cs.unc.edu/~anderson/papers/ecrts09b.pdf
+
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::spin;
+
+/// Increment of the reader counters. The lower bits of `rin` are used to
+/// signal the presence of a writer.
+const RINC: usize = 0x100;
+
+/// Mask of the writer bits of `rin`.
+const WBITS: usize = 0x3;
+
+/// Writer present bit.
+const PRES: usize = 0x2;
+
+/// Phase ID bit. It alternates between consecutive writers, so readers can
+/// detect that the writer they were waiting for has left.
+const PHID: usize = 0x1;
+
+/// Represents a reader-writer lock based on a phase-fair ticket lock.
+pub struct RwTicketLock<T> {
+    /// Number of readers that have entered, plus the writer bits.
+    rin: AtomicUsize,
+
+    /// Number of readers that have left.
+    rout: AtomicUsize,
+
+    /// Next writer ticket.
+    win: AtomicUsize,
+
+    /// Writer ticket being served.
+    wout: AtomicUsize,
+
+    /// Protected data.
+    data: UnsafeCell<T>,
+}
+
+impl<T> RwTicketLock<T> {
+    /// Returns a `RwTicketLock` protecting `data`.
+    pub const fn new(data: T) -> Self {
+        RwTicketLock {
+            rin: AtomicUsize::new(0),
+            rout: AtomicUsize::new(0),
+            win: AtomicUsize::new(0),
+            wout: AtomicUsize::new(0),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Locks the `RwTicketLock` with shared read access and returns a
+    /// `RwTicketReadGuard`. Other readers can hold the lock at the same time.
+    pub fn read(&self) -> RwTicketReadGuard<T> {
+        // Announce the reader. If a writer is present, wait until it leaves,
+        // which is detected by a change of the writer bits. The `Acquire`
+        // operations synchronize with the `Release` clear of the writer bits,
+        // so the writes of the last writer are visible.
+        let w = self.rin.fetch_add(RINC, Ordering::Acquire) & WBITS;
+        if w != 0 {
+            while self.rin.load(Ordering::Acquire) & WBITS == w {
+                spin(1);
+            }
+        }
+        RwTicketReadGuard { lock: self }
+    }
+
+    /// Locks the `RwTicketLock` with exclusive write access and returns a
+    /// `RwTicketWriteGuard`.
+    pub fn write(&self) -> RwTicketWriteGuard<T> {
+        // Wait for our turn among the writers. The `Acquire` load
+        // synchronizes with the `Release` increment of the previous writer.
+        let ticket = self.win.fetch_add(1, Ordering::Relaxed);
+        while self.wout.load(Ordering::Acquire) != ticket {
+            spin(1);
+        }
+
+        // Block new readers and wait for the readers that entered before us.
+        // The read-modify-write operations on `rin` are totally ordered, so
+        // `readers` counts exactly the readers that did not see the writer
+        // bits. The `Acquire` load synchronizes with their `Release`
+        // increments of `rout`, so their reads happen before our writes.
+        let w = PRES | (ticket & PHID);
+        let readers = self.rin.fetch_add(w, Ordering::Relaxed);
+        while self.rout.load(Ordering::Acquire) != readers {
+            spin(1);
+        }
+        RwTicketWriteGuard { lock: self }
+    }
+}
+
+unsafe impl<T: Send> Send for RwTicketLock<T> {}
+unsafe impl<T: Send + Sync> Sync for RwTicketLock<T> {}
+
+/// An RAII implementation of a shared read lock of a `RwTicketLock`. When
+/// this structure is dropped (falls out of scope), the read lock is released.
+///
+/// This structure is created by the `read` method on `RwTicketLock`.
+pub struct RwTicketReadGuard<'a, T> {
+    /// `RwTicketLock` associated with this guard.
+    lock: &'a RwTicketLock<T>,
+}
+
+impl<T> Deref for RwTicketReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // Returning a shared reference to the protected data here is safe
+        // because a writer cannot hold the lock while a
+        // `RwTicketReadGuard` exists.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for RwTicketReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.rout.fetch_add(RINC, Ordering::Release);
+    }
+}
+
+/// An RAII implementation of an exclusive write lock of a `RwTicketLock`.
+/// When this structure is dropped (falls out of scope), the write lock is
+/// released.
+///
+/// This structure is created by the `write` method on `RwTicketLock`.
+pub struct RwTicketWriteGuard<'a, T> {
+    /// `RwTicketLock` associated with this guard.
+    lock: &'a RwTicketLock<T>,
+}
+
+impl<T> Deref for RwTicketWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // Returning a reference to the protected data here is safe because a
+        // `RwTicketWriteGuard` can only exist if the lock is held
+        // exclusively.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for RwTicketWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // Returning a mutable reference to the protected data here is safe
+        // because of the same reasons explained in `Deref`.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for RwTicketWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        // Let the waiting readers in and serve the next writer. Both
+        // operations publish the writes to the protected data.
+        self.lock.rin.fetch_and(!WBITS, Ordering::Release);
+        self.lock.wout.fetch_add(1, Ordering::Release);
+    }
+}
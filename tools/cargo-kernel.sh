@@ -0,0 +1,14 @@
+#!/bin/sh
+
+# Directory of the repository.
+root_dir=$(cd "$(dirname "$0")/.." && pwd)
+
+# Cargo kernel configuration. The kernel is a static ELF executable linked in
+# the higher half.
+export CARGO_BUILD_TARGET='x86_64-unknown-none'
+export CARGO_UNSTABLE_BUILD_STD='core,alloc'
+export CARGO_UNSTABLE_BUILD_STD_FEATURES='compiler-builtins-mem'
+export RUSTFLAGS="${RUSTFLAGS:-} -C relocation-model=static -C code-model=kernel -C link-arg=-T${root_dir}/expos/kernel.ld"
+
+# Run Cargo.
+exec cargo "$@"
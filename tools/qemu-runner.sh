@@ -10,11 +10,27 @@ if [ $# -ne 1 ]; then
 fi
 efi_bin=$1
 
-# TFTP parameters for QEMU.
-tftp_dir=$(dirname "${efi_bin}")
-tftp_bootfile=$(basename "${efi_bin}")
+# The kernel is built with the same profile as the bootloader.
+target_dir=$(dirname "$(dirname "$(dirname "${efi_bin}")")")
+profile=$(basename "$(dirname "${efi_bin}")")
+kernel_bin="${target_dir}/x86_64-unknown-none/${profile}/expos"
+if [ ! -f "${kernel_bin}" ]; then
+	echo "$0: kernel not found: ${kernel_bin}" >&2
+	echo "$0: build it with tools/cargo-kernel.sh" >&2
+	exit 1
+fi
+
+# Populate the EFI System Partition exposed to QEMU.
+esp_dir="${target_dir}/esp"
+rm -rf "${esp_dir}"
+mkdir -p "${esp_dir}/EFI/BOOT" "${esp_dir}/expos"
+cp "${efi_bin}" "${esp_dir}/EFI/BOOT/BOOTX64.EFI"
+cp "${kernel_bin}" "${esp_dir}/expos/kernel.elf"
+if [ -n "${EXPOS_INITRD:-}" ]; then
+	cp "${EXPOS_INITRD}" "${esp_dir}/expos/initrd.cpio"
+fi
 
-# Run QEMU downloading the kernel via tftp.
+# Run QEMU booting from the ESP.
 qemu-system-x86_64 \
 	-nodefaults \
 	-nographic \
@@ -22,5 +38,4 @@ qemu-system-x86_64 \
 	-serial mon:stdio \
 	-m 1024 \
 	-bios '/usr/share/ovmf/OVMF.fd' \
-	-device 'e1000,netdev=n0' \
-	-netdev "user,id=n0,tftp=${tftp_dir},bootfile=${tftp_bootfile}"
+	-drive "format=raw,file=fat:rw:${esp_dir}"
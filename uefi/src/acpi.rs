@@ -2,6 +2,8 @@
 
 use core::convert::TryInto;
 
+use range::Range;
+
 use crate::utils;
 use crate::{Error, Ptr};
 
@@ -11,6 +13,10 @@ const ACPI_RSDP_SIGNATURE: &[u8] = b"RSD PTR ";
 /// Size of the SDT header.
 const ACPI_SDT_SIZE: usize = core::mem::size_of::<AcpiSdtHeader>();
 
+/// Size of the RSDP structure of the ACPI 1.0 specification. It is the part of
+/// the structure covered by the first checksum.
+const ACPI_RSDP10_SIZE: usize = 20;
+
 /// Root System Description Pointer (RSDP) structure of the ACPI 2.0 and later
 /// specifications.
 #[derive(Debug, Clone, Copy)]
@@ -76,26 +82,275 @@ impl Rsdp20 {
         // An `Rsdp20` is only created after checking its signature, checksum
         // and revision. Thus, we assume that the pointer to the XSDT
         // will be valid.
-        unsafe { Xsdt::new(self.rsdp20.xsdt_addr.try_into()?) }
+        let xsdt_ptr = self.rsdp20.xsdt_addr.try_into()?;
+        unsafe { parse_sdt(xsdt_ptr, SdtType::Xsdt, Xsdt::new) }
+    }
+}
+
+/// Represents the Root System Description Pointer (RSDP) of any ACPI revision.
+/// Contrary to `Rsdp20`, ACPI 1.0 RSDPs are accepted.
+#[derive(Debug)]
+pub struct Rsdp {
+    /// The RSDP structure. The fields after `rsdt_addr` are only valid if
+    /// the revision is 2 or greater.
+    rsdp: AcpiRsdp20,
+}
+
+impl Rsdp {
+    /// Creates a new `Rsdp` from a given pointer. For ACPI 1.0 RSDPs, only
+    /// the first 20 bytes are validated.
+    ///
+    /// # Errors
+    ///
+    /// This function returns error if the pointer does not point to a valid
+    /// RSDP structure.
+    ///
+    /// # Safety
+    ///
+    /// The `Rsdp` structure is created using a pointer. Thus, this function
+    /// is considered unsafe.
+    pub unsafe fn new(rsdp_ptr: Ptr) -> Result<Self, Error> {
+        let rsdp_ptr = rsdp_ptr.0 as *const AcpiRsdp20;
+
+        // Check table's signature and the checksum of the ACPI 1.0 fields.
+        // They are read byte by byte because an ACPI 1.0 RSDP is smaller
+        // than `AcpiRsdp20`.
+        let signature = core::ptr::read_unaligned(rsdp_ptr as *const [u8; 8]);
+        if signature != ACPI_RSDP_SIGNATURE {
+            return Err(Error::InvalidSignature);
+        }
+        let checksum =
+            utils::add_bytes(rsdp_ptr as *const u8, ACPI_RSDP10_SIZE);
+        if checksum != 0 {
+            return Err(Error::InvalidCheckSum);
+        }
+
+        let revision =
+            core::ptr::read_unaligned((rsdp_ptr as *const u8).add(15));
+        if revision < 2 {
+            // Only copy the ACPI 1.0 fields.
+            let mut rsdp = AcpiRsdp20 {
+                signature,
+                checksum: 0,
+                oem_id: [0; 6],
+                revision,
+                rsdt_addr: 0,
+                length: 0,
+                xsdt_addr: 0,
+                ext_checksum: 0,
+                reserved: [0; 3],
+            };
+            core::ptr::copy_nonoverlapping(
+                rsdp_ptr as *const u8,
+                &mut rsdp as *mut AcpiRsdp20 as *mut u8,
+                ACPI_RSDP10_SIZE,
+            );
+            return Ok(Rsdp { rsdp });
+        }
+
+        // Check the extended checksum of ACPI 2.0+ tables.
+        let rsdp = core::ptr::read_unaligned(rsdp_ptr);
+        let checksum =
+            utils::add_bytes(rsdp_ptr as *const u8, rsdp.length as usize);
+        if checksum != 0 {
+            return Err(Error::InvalidCheckSum);
+        }
+
+        Ok(Rsdp { rsdp })
+    }
+
+    /// Revision of the RSDP. It is 0 for ACPI 1.0 and 2 or greater for ACPI
+    /// 2.0 and later.
+    pub fn revision(&self) -> u8 {
+        self.rsdp.revision
+    }
+
+    /// Returns the Root System Description Table (RSDT).
+    pub fn rsdt(&self) -> Result<Rsdt, Error> {
+        // An `Rsdp` is only created after checking its signature and
+        // checksum. Thus, we assume that the pointer to the RSDT will be
+        // valid.
+        let rsdt_ptr = self.rsdp.rsdt_addr.try_into()?;
+        unsafe { parse_sdt(rsdt_ptr, SdtType::Rsdt, Rsdt::new) }
+    }
+
+    /// Returns the root System Description Table. The XSDT is preferred over
+    /// the RSDT.
+    pub fn root_sdt(&self) -> Result<RootSdt, Error> {
+        match self.xsdt() {
+            Ok(xsdt) => Ok(RootSdt::Xsdt(xsdt)),
+            Err(_) => Ok(RootSdt::Rsdt(self.rsdt()?)),
+        }
+    }
+
+    /// Returns the Extended System Description Table (XSDT).
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Error::NotFound` if the RSDP is an ACPI
+    /// 1.0 one or it does not provide an XSDT.
+    pub fn xsdt(&self) -> Result<Xsdt, Error> {
+        if self.rsdp.revision < 2 || self.rsdp.xsdt_addr == 0 {
+            return Err(Error::NotFound);
+        }
+
+        // An `Rsdp` is only created after checking its signature and
+        // checksums. Thus, we assume that the pointer to the XSDT will be
+        // valid.
+        let xsdt_ptr = self.rsdp.xsdt_addr.try_into()?;
+        unsafe { parse_sdt(xsdt_ptr, SdtType::Xsdt, Xsdt::new) }
+    }
+}
+
+/// Represents the root System Description Table. It is the XSDT when the
+/// firmware provides one, and the RSDT otherwise.
+#[derive(Debug)]
+pub enum RootSdt {
+    /// Extended System Description Table.
+    Xsdt(Xsdt),
+
+    /// Root System Description Table.
+    Rsdt(Rsdt),
+}
+
+impl RootSdt {
+    /// Returns an iterator over the tables referenced by the root SDT. Each
+    /// item has the form `(signature, ptr)`.
+    pub fn tables(&self) -> impl Iterator<Item = ([u8; 4], Ptr)> + '_ {
+        let (xsdt, rsdt) = match self {
+            RootSdt::Xsdt(xsdt) => (Some(xsdt), None),
+            RootSdt::Rsdt(rsdt) => (None, Some(rsdt)),
+        };
+        xsdt.into_iter()
+            .flat_map(Xsdt::tables)
+            .chain(rsdt.into_iter().flat_map(Rsdt::tables))
+    }
+
+    /// Returns a pointer to the table with the signature `signature`.
+    pub fn find(&self, signature: &[u8; 4]) -> Result<Ptr, Error> {
+        match self {
+            RootSdt::Xsdt(xsdt) => xsdt.find(signature),
+            RootSdt::Rsdt(rsdt) => rsdt.find(signature),
+        }
+    }
+
+    /// Returns the Multiple APIC Description Table (MADT).
+    pub fn madt(&self) -> Result<Madt, Error> {
+        match self {
+            RootSdt::Xsdt(xsdt) => xsdt.madt(),
+            RootSdt::Rsdt(rsdt) => rsdt.madt(),
+        }
+    }
+
+    /// Returns the High Precision Event Timer Table (HPET).
+    pub fn hpet(&self) -> Result<Hpet, Error> {
+        match self {
+            RootSdt::Xsdt(xsdt) => xsdt.hpet(),
+            RootSdt::Rsdt(rsdt) => rsdt.hpet(),
+        }
+    }
+
+    /// Returns the PCI Express memory mapped configuration space base address
+    /// description table (MCFG).
+    pub fn mcfg(&self) -> Result<Mcfg, Error> {
+        match self {
+            RootSdt::Xsdt(xsdt) => xsdt.mcfg(),
+            RootSdt::Rsdt(rsdt) => rsdt.mcfg(),
+        }
+    }
+
+    /// Returns the Fixed ACPI Description Table (FADT).
+    pub fn fadt(&self) -> Result<Fadt, Error> {
+        match self {
+            RootSdt::Xsdt(xsdt) => xsdt.fadt(),
+            RootSdt::Rsdt(rsdt) => rsdt.fadt(),
+        }
+    }
+
+    /// Returns the DMA Remapping Reporting Table (DMAR).
+    pub fn dmar(&self) -> Result<Dmar, Error> {
+        match self {
+            RootSdt::Xsdt(xsdt) => xsdt.dmar(),
+            RootSdt::Rsdt(rsdt) => rsdt.dmar(),
+        }
+    }
+
+    /// Returns the Boot Graphics Resource Table (BGRT).
+    pub fn bgrt(&self) -> Result<Bgrt, Error> {
+        match self {
+            RootSdt::Xsdt(xsdt) => xsdt.bgrt(),
+            RootSdt::Rsdt(rsdt) => rsdt.bgrt(),
+        }
+    }
+
+    /// Returns the Serial Port Console Redirection Table (SPCR).
+    pub fn spcr(&self) -> Result<Spcr, Error> {
+        match self {
+            RootSdt::Xsdt(xsdt) => xsdt.spcr(),
+            RootSdt::Rsdt(rsdt) => rsdt.spcr(),
+        }
     }
 }
 
 /// System Description Table types.
 enum SdtType {
+    Rsdt,
     Xsdt,
     Madt,
+    Hpet,
+    Mcfg,
+    Fadt,
+    Dmar,
+    Bgrt,
+    Spcr,
 }
 
 impl SdtType {
     /// Returns the signature of the SDT.
-    fn signature(&self) -> &[u8] {
+    fn signature(&self) -> &'static [u8; 4] {
         match self {
+            SdtType::Rsdt => b"RSDT",
             SdtType::Xsdt => b"XSDT",
             SdtType::Madt => b"APIC",
+            SdtType::Hpet => b"HPET",
+            SdtType::Mcfg => b"MCFG",
+            SdtType::Fadt => b"FACP",
+            SdtType::Dmar => b"DMAR",
+            SdtType::Bgrt => b"BGRT",
+            SdtType::Spcr => b"SPCR",
+        }
+    }
+
+    /// Returns the name of the SDT, used to give context to errors.
+    fn name(&self) -> &'static str {
+        match self {
+            SdtType::Rsdt => "RSDT",
+            SdtType::Xsdt => "XSDT",
+            SdtType::Madt => "MADT",
+            SdtType::Hpet => "HPET",
+            SdtType::Mcfg => "MCFG",
+            SdtType::Fadt => "FADT",
+            SdtType::Dmar => "DMAR",
+            SdtType::Bgrt => "BGRT",
+            SdtType::Spcr => "SPCR",
         }
     }
 }
 
+/// Parses the SDT of type `sdt_type` pointed by `ptr` using `parse`. Parsing
+/// errors are annotated with the name and the address of the table.
+///
+/// # Safety
+///
+/// `ptr` is passed to `parse`, which is expected to dereference it.
+unsafe fn parse_sdt<T>(
+    ptr: Ptr,
+    sdt_type: SdtType,
+    parse: unsafe fn(Ptr) -> Result<T, Error>,
+) -> Result<T, Error> {
+    parse(ptr).map_err(|err| err.context(sdt_type.name(), ptr.0))
+}
+
 /// System Description Table header of the ACPI specification. It is common to
 /// all System Description Tables.
 #[derive(Debug, Clone, Copy)]
@@ -118,36 +373,122 @@ impl AcpiSdtHeader {
     /// # Errors
     ///
     /// This function returns error if the signature of the table does not
-    /// match the provided `SdtType` or the checksum is invalid.
+    /// match the provided `SdtType`, the length is smaller than the header or
+    /// the checksum is invalid.
     unsafe fn new(sdt_ptr: Ptr, sdt_type: SdtType) -> Result<Self, Error> {
         // Parse SDT header.
-        let sdt_ptr = sdt_ptr.0 as *const AcpiSdtHeader;
-        let hdr = core::ptr::read_unaligned(sdt_ptr);
+        let hdr = core::ptr::read_unaligned(sdt_ptr.0 as *const AcpiSdtHeader);
 
         // Check SDT header's signature.
-        if hdr.signature != sdt_type.signature() {
+        if &hdr.signature != sdt_type.signature() {
             return Err(Error::InvalidSignature);
         }
 
+        hdr.check(sdt_ptr)?;
+
+        Ok(hdr)
+    }
+
+    /// Checks the length and the checksum of the table pointed by `sdt_ptr`,
+    /// whose header is `self`.
+    unsafe fn check(&self, sdt_ptr: Ptr) -> Result<(), Error> {
+        // The table must contain, at least, the header.
+        if (self.length as usize) < ACPI_SDT_SIZE {
+            return Err(Error::InvalidAcpiData);
+        }
+
         // Check SDT header's checksum.
         let checksum =
-            utils::add_bytes(sdt_ptr as *const u8, hdr.length as usize);
+            utils::add_bytes(sdt_ptr.0 as *const u8, self.length as usize);
         if checksum != 0 {
             return Err(Error::InvalidCheckSum);
         }
 
-        Ok(hdr)
+        Ok(())
+    }
+}
+
+/// Represents the header of a System Description Table (SDT).
+#[derive(Debug, Clone, Copy)]
+pub struct SdtHeader {
+    hdr: AcpiSdtHeader,
+}
+
+impl SdtHeader {
+    /// Signature of the table (e.g. `APIC` for the MADT).
+    pub fn signature(&self) -> [u8; 4] {
+        self.hdr.signature
+    }
+
+    /// Length of the table in bytes, including the header.
+    pub fn length(&self) -> u32 {
+        self.hdr.length
+    }
+
+    /// Revision of the structure of the table.
+    pub fn revision(&self) -> u8 {
+        self.hdr.revision
+    }
+
+    /// OEM identifier.
+    pub fn oem_id(&self) -> [u8; 6] {
+        self.hdr.oem_id
+    }
+
+    /// OEM table identifier.
+    pub fn oem_table_id(&self) -> [u8; 8] {
+        self.hdr.oem_table_id
+    }
+
+    /// OEM revision of the table.
+    pub fn oem_revision(&self) -> u32 {
+        self.hdr.oem_revision
+    }
+
+    /// Vendor ID of the utility that created the table.
+    pub fn creator_id(&self) -> u32 {
+        self.hdr.creator_id
+    }
+
+    /// Revision of the utility that created the table.
+    pub fn creator_revision(&self) -> u32 {
+        self.hdr.creator_revision
     }
 }
 
-/// Maximum number of entries in the XSDT.
-const ACPI_XSDT_ENTRIES_LEN: usize = 32;
+/// Validates the System Description Table pointed by `sdt_ptr`, whatever its
+/// type, and returns its header.
+///
+/// # Errors
+///
+/// This function returns `Error::InvalidSignature` if the signature contains
+/// non-printable characters, `Error::InvalidAcpiData` if the length is
+/// smaller than the header and `Error::InvalidCheckSum` if the checksum is
+/// invalid.
+///
+/// # Safety
+///
+/// The header is read from a pointer and the checksum is calculated over the
+/// length it reports. Thus, this function is considered unsafe.
+pub unsafe fn validate_sdt(sdt_ptr: Ptr) -> Result<SdtHeader, Error> {
+    // Parse SDT header.
+    let hdr = core::ptr::read_unaligned(sdt_ptr.0 as *const AcpiSdtHeader);
+
+    // Check SDT header's signature.
+    if !hdr.signature.iter().all(u8::is_ascii_graphic) {
+        return Err(Error::InvalidSignature);
+    }
+
+    hdr.check(sdt_ptr)?;
+
+    Ok(SdtHeader { hdr })
+}
 
 /// Represents the Extended System Description Table (XSDT).
 #[derive(Debug)]
 pub struct Xsdt {
-    entries: [u64; ACPI_XSDT_ENTRIES_LEN],
-    num_entries: usize,
+    /// Entries of the table.
+    entries: &'static [u8],
 }
 
 impl Xsdt {
@@ -166,57 +507,247 @@ impl Xsdt {
         // Parse header.
         let hdr = AcpiSdtHeader::new(xsdt_ptr, SdtType::Xsdt)?;
 
-        // Calculate number of entries.
-        let entries_length = hdr.length as usize - ACPI_SDT_SIZE;
+        // Check the length of the entries.
+        let entries_length = (hdr.length as usize)
+            .checked_sub(ACPI_SDT_SIZE)
+            .ok_or(Error::InvalidAcpiData)?;
         if entries_length % 8 != 0 {
             return Err(Error::InvalidAcpiData);
         }
-        let num_entries = entries_length / 8;
 
-        // Check that there is enough room for the entries in the fixed size
-        // array.
-        if num_entries > ACPI_XSDT_ENTRIES_LEN {
-            return Err(Error::BufferTooSmall);
+        let entries = core::slice::from_raw_parts(
+            (xsdt_ptr.0 as *const u8).add(ACPI_SDT_SIZE),
+            entries_length,
+        );
+
+        Ok(Xsdt { entries })
+    }
+
+    /// Returns an iterator over the physical addresses of the tables
+    /// referenced by the XSDT.
+    pub fn entries(&self) -> XsdtEntries {
+        XsdtEntries {
+            entries: self.entries,
         }
+    }
 
-        // Parse entries.
-        let mut entries = [0u64; ACPI_XSDT_ENTRIES_LEN];
-        for (i, it) in entries.iter_mut().take(num_entries).enumerate() {
-            let ptr = (xsdt_ptr.0 as *const u8).add(ACPI_SDT_SIZE + i * 8)
-                as *const u64;
-            *it = core::ptr::read_unaligned(ptr);
+    /// Returns an iterator over the tables referenced by the XSDT. Each item
+    /// has the form `(signature, ptr)`.
+    pub fn tables(&self) -> impl Iterator<Item = ([u8; 4], Ptr)> {
+        // An `Xsdt` is only created after checking its signature and checksum
+        // Thus, we assume that the pointers to the tables will be valid.
+        sdt_tables(self.entries())
+    }
+
+    /// Returns a pointer to the table with the signature `signature`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Error::NotFound` if the XSDT does not
+    /// reference a table with the provided signature.
+    pub fn find(&self, signature: &[u8; 4]) -> Result<Ptr, Error> {
+        self.tables()
+            .find(|(sig, _)| sig == signature)
+            .map(|(_, ptr)| ptr)
+            .ok_or(Error::NotFound)
+    }
+
+    /// Returns the Multiple APIC Description Table (MADT).
+    pub fn madt(&self) -> Result<Madt, Error> {
+        let ptr = self.find(SdtType::Madt.signature())?;
+        unsafe { parse_sdt(ptr, SdtType::Madt, Madt::new) }
+    }
+
+    /// Returns the High Precision Event Timer Table (HPET).
+    pub fn hpet(&self) -> Result<Hpet, Error> {
+        let ptr = self.find(SdtType::Hpet.signature())?;
+        unsafe { parse_sdt(ptr, SdtType::Hpet, Hpet::new) }
+    }
+
+    /// Returns the PCI Express memory mapped configuration space base address
+    /// description table (MCFG).
+    pub fn mcfg(&self) -> Result<Mcfg, Error> {
+        let ptr = self.find(SdtType::Mcfg.signature())?;
+        unsafe { parse_sdt(ptr, SdtType::Mcfg, Mcfg::new) }
+    }
+
+    /// Returns the Fixed ACPI Description Table (FADT).
+    pub fn fadt(&self) -> Result<Fadt, Error> {
+        let ptr = self.find(SdtType::Fadt.signature())?;
+        unsafe { parse_sdt(ptr, SdtType::Fadt, Fadt::new) }
+    }
+
+    /// Returns the DMA Remapping Reporting Table (DMAR).
+    pub fn dmar(&self) -> Result<Dmar, Error> {
+        let ptr = self.find(SdtType::Dmar.signature())?;
+        unsafe { parse_sdt(ptr, SdtType::Dmar, Dmar::new) }
+    }
+
+    /// Returns the Boot Graphics Resource Table (BGRT).
+    pub fn bgrt(&self) -> Result<Bgrt, Error> {
+        let ptr = self.find(SdtType::Bgrt.signature())?;
+        unsafe { parse_sdt(ptr, SdtType::Bgrt, Bgrt::new) }
+    }
+
+    /// Returns the Serial Port Console Redirection Table (SPCR).
+    pub fn spcr(&self) -> Result<Spcr, Error> {
+        let ptr = self.find(SdtType::Spcr.signature())?;
+        unsafe { parse_sdt(ptr, SdtType::Spcr, Spcr::new) }
+    }
+}
+
+/// Iterator over the entries of the XSDT, returned by `Xsdt::entries`.
+#[derive(Debug, Clone)]
+pub struct XsdtEntries {
+    entries: &'static [u8],
+}
+
+impl Iterator for XsdtEntries {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.entries.len() < 8 {
+            return None;
         }
 
-        Ok(Xsdt {
-            entries,
-            num_entries,
+        let entry = unsafe {
+            core::ptr::read_unaligned(self.entries.as_ptr() as *const u64)
+        };
+        self.entries = &self.entries[8..];
+
+        Some(entry)
+    }
+}
+
+/// Represents the Root System Description Table (RSDT). It is the ACPI 1.0
+/// counterpart of the XSDT, with 32-bit entries.
+#[derive(Debug)]
+pub struct Rsdt {
+    /// Entries of the table.
+    entries: &'static [u8],
+}
+
+impl Rsdt {
+    /// Creates a new `Rsdt` from a given pointer.
+    ///
+    /// # Errors
+    ///
+    /// This function returns error if the pointer does not point to a valid
+    /// RSDT.
+    ///
+    /// # Safety
+    ///
+    /// The `Rsdt` structure is created using a pointer. Thus, this function is
+    /// considered unsafe.
+    pub unsafe fn new(rsdt_ptr: Ptr) -> Result<Self, Error> {
+        // Parse header.
+        let hdr = AcpiSdtHeader::new(rsdt_ptr, SdtType::Rsdt)?;
+
+        // Check the length of the entries.
+        let entries_length = (hdr.length as usize)
+            .checked_sub(ACPI_SDT_SIZE)
+            .ok_or(Error::InvalidAcpiData)?;
+        if entries_length % 4 != 0 {
+            return Err(Error::InvalidAcpiData);
+        }
+
+        let entries = core::slice::from_raw_parts(
+            (rsdt_ptr.0 as *const u8).add(ACPI_SDT_SIZE),
+            entries_length,
+        );
+
+        Ok(Rsdt { entries })
+    }
+
+    /// Returns an iterator over the physical addresses of the tables
+    /// referenced by the RSDT.
+    pub fn entries(&self) -> impl Iterator<Item = u32> {
+        self.entries.chunks_exact(4).map(|entry| unsafe {
+            core::ptr::read_unaligned(entry.as_ptr() as *const u32)
         })
     }
 
+    /// Returns an iterator over the tables referenced by the RSDT. Each item
+    /// has the form `(signature, ptr)`.
+    pub fn tables(&self) -> impl Iterator<Item = ([u8; 4], Ptr)> {
+        // An `Rsdt` is only created after checking its signature and checksum
+        // Thus, we assume that the pointers to the tables will be valid.
+        sdt_tables(self.entries().map(|entry| entry.into()))
+    }
+
+    /// Returns a pointer to the table with the signature `signature`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Error::NotFound` if the RSDT does not
+    /// reference a table with the provided signature.
+    pub fn find(&self, signature: &[u8; 4]) -> Result<Ptr, Error> {
+        self.tables()
+            .find(|(sig, _)| sig == signature)
+            .map(|(_, ptr)| ptr)
+            .ok_or(Error::NotFound)
+    }
+
     /// Returns the Multiple APIC Description Table (MADT).
     pub fn madt(&self) -> Result<Madt, Error> {
-        // An `Xsdt` is only created after checking its signature and checksum
-        // Thus, we assume that the pointer to the MADT will be valid.
-
-        for &entry in self.entries.iter().take(self.num_entries) {
-            // Look for a table with the correct signature.
-            let ptr = entry as *const [u8; 4];
-            let signature = unsafe { core::ptr::read_unaligned(ptr) };
-            if signature == SdtType::Madt.signature() {
-                return unsafe { Madt::new(entry.try_into()?) };
-            }
-        }
+        let ptr = self.find(SdtType::Madt.signature())?;
+        unsafe { parse_sdt(ptr, SdtType::Madt, Madt::new) }
+    }
+
+    /// Returns the High Precision Event Timer Table (HPET).
+    pub fn hpet(&self) -> Result<Hpet, Error> {
+        let ptr = self.find(SdtType::Hpet.signature())?;
+        unsafe { parse_sdt(ptr, SdtType::Hpet, Hpet::new) }
+    }
+
+    /// Returns the PCI Express memory mapped configuration space base address
+    /// description table (MCFG).
+    pub fn mcfg(&self) -> Result<Mcfg, Error> {
+        let ptr = self.find(SdtType::Mcfg.signature())?;
+        unsafe { parse_sdt(ptr, SdtType::Mcfg, Mcfg::new) }
+    }
 
-        // If we reach this point, the table could not be found.
-        Err(Error::NotFound)
+    /// Returns the Fixed ACPI Description Table (FADT).
+    pub fn fadt(&self) -> Result<Fadt, Error> {
+        let ptr = self.find(SdtType::Fadt.signature())?;
+        unsafe { parse_sdt(ptr, SdtType::Fadt, Fadt::new) }
+    }
+
+    /// Returns the DMA Remapping Reporting Table (DMAR).
+    pub fn dmar(&self) -> Result<Dmar, Error> {
+        let ptr = self.find(SdtType::Dmar.signature())?;
+        unsafe { parse_sdt(ptr, SdtType::Dmar, Dmar::new) }
+    }
+
+    /// Returns the Boot Graphics Resource Table (BGRT).
+    pub fn bgrt(&self) -> Result<Bgrt, Error> {
+        let ptr = self.find(SdtType::Bgrt.signature())?;
+        unsafe { parse_sdt(ptr, SdtType::Bgrt, Bgrt::new) }
+    }
+
+    /// Returns the Serial Port Console Redirection Table (SPCR).
+    pub fn spcr(&self) -> Result<Spcr, Error> {
+        let ptr = self.find(SdtType::Spcr.signature())?;
+        unsafe { parse_sdt(ptr, SdtType::Spcr, Spcr::new) }
     }
 }
 
-/// Size of the SDT header.
-const ACPI_MADT_FIELDS_SIZE: usize = core::mem::size_of::<AcpiMadtFields>();
+/// Returns an iterator over the signatures and pointers of the System
+/// Description Tables pointed by `entries`. Entries that do not fit in a
+/// pointer are skipped.
+fn sdt_tables(
+    entries: impl Iterator<Item = u64>,
+) -> impl Iterator<Item = ([u8; 4], Ptr)> {
+    entries.filter_map(|entry| {
+        let ptr: Ptr = entry.try_into().ok()?;
+        let signature =
+            unsafe { core::ptr::read_unaligned(ptr.0 as *const [u8; 4]) };
+        Some((signature, ptr))
+    })
+}
 
-/// Maximum number of entries in the MADT.
-const ACPI_MADT_ENTRIES_LEN: usize = 256;
+/// Size of the extra fields of the MADT.
+const ACPI_MADT_FIELDS_SIZE: usize = core::mem::size_of::<AcpiMadtFields>();
 
 /// Extra fields of the Multiple APIC Description Table (MADT) in the ACPI
 /// specification.
@@ -237,6 +768,56 @@ struct AcpiMadtLapic {
     flags: u32,
 }
 
+/// I/O APIC Structure in the ACPI specification.
+#[repr(C, packed)]
+struct AcpiMadtIoApic {
+    ty: u8,
+    length: u8,
+    ioapic_id: u8,
+    reserved: u8,
+    ioapic_addr: u32,
+    gsi_base: u32,
+}
+
+/// Interrupt Source Override Structure in the ACPI specification.
+#[repr(C, packed)]
+struct AcpiMadtInterruptOverride {
+    ty: u8,
+    length: u8,
+    bus: u8,
+    source: u8,
+    gsi: u32,
+    flags: u16,
+}
+
+/// Non-Maskable Interrupt (NMI) Source Structure in the ACPI specification.
+#[repr(C, packed)]
+struct AcpiMadtNmiSource {
+    ty: u8,
+    length: u8,
+    flags: u16,
+    gsi: u32,
+}
+
+/// Local APIC NMI Structure in the ACPI specification.
+#[repr(C, packed)]
+struct AcpiMadtLapicNmi {
+    ty: u8,
+    length: u8,
+    proc_uid: u8,
+    flags: u16,
+    lint: u8,
+}
+
+/// Local APIC Address Override Structure in the ACPI specification.
+#[repr(C, packed)]
+struct AcpiMadtLapicAddrOverride {
+    ty: u8,
+    length: u8,
+    reserved: u16,
+    lapic_addr: u64,
+}
+
 /// Represents a Processor Local APIC Structure.
 #[derive(Debug, Default, Clone, Copy)]
 pub struct MadtLapic {
@@ -268,16 +849,264 @@ impl MadtLapic {
     }
 }
 
-/// Represents the Multiple APIC Description Table (MADT).
-#[derive(Debug)]
-pub struct Madt {
-    fields: AcpiMadtFields,
+/// Represents an I/O APIC Structure.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MadtIoApic {
+    ioapic_id: u8,
+    ioapic_addr: u32,
+    gsi_base: u32,
+}
+
+impl MadtIoApic {
+    /// I/O APIC's ID.
+    pub fn ioapic_id(&self) -> u8 {
+        self.ioapic_id
+    }
 
-    lapic_entries: [MadtLapic; ACPI_MADT_ENTRIES_LEN],
-    num_lapic_entries: usize,
+    /// The 32-bit physical address at which the I/O APIC can be accessed.
+    pub fn ioapic_addr(&self) -> u32 {
+        self.ioapic_addr
+    }
+
+    /// Global System Interrupt number where the interrupt inputs of the I/O
+    /// APIC start.
+    pub fn gsi_base(&self) -> u32 {
+        self.gsi_base
+    }
 }
 
-impl Madt {
+/// Represents an Interrupt Source Override Structure. It describes how an ISA
+/// interrupt is mapped to a Global System Interrupt.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MadtInterruptOverride {
+    bus: u8,
+    source: u8,
+    gsi: u32,
+    flags: u16,
+}
+
+impl MadtInterruptOverride {
+    /// Bus of the interrupt source. It is 0 (ISA).
+    pub fn bus(&self) -> u8 {
+        self.bus
+    }
+
+    /// Bus-relative interrupt source (IRQ).
+    pub fn source(&self) -> u8 {
+        self.source
+    }
+
+    /// Global System Interrupt that the interrupt source signals.
+    pub fn gsi(&self) -> u32 {
+        self.gsi
+    }
+
+    /// MPS INTI flags.
+    ///
+    /// Bit offset | Bit length | Flag
+    /// ---------- | ---------- | ---------------
+    /// 0          | 2          | Polarity
+    /// 2          | 2          | Trigger Mode
+    /// 4          | 12         | Reserved (zero)
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+}
+
+/// Represents a Non-Maskable Interrupt (NMI) Source Structure. It describes a
+/// Global System Interrupt that must be configured as NMI.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MadtNmiSource {
+    flags: u16,
+    gsi: u32,
+}
+
+impl MadtNmiSource {
+    /// MPS INTI flags. See `MadtInterruptOverride::flags`.
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    /// Global System Interrupt that signals the NMI.
+    pub fn gsi(&self) -> u32 {
+        self.gsi
+    }
+}
+
+/// Represents a Local APIC NMI Structure. It describes the local APIC
+/// interrupt input (LINTn) the NMI is connected to.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MadtLapicNmi {
+    proc_uid: u8,
+    flags: u16,
+    lint: u8,
+}
+
+impl MadtLapicNmi {
+    /// UID of the processor the structure applies to. The value `0xff`
+    /// means all processors.
+    pub fn proc_uid(&self) -> u8 {
+        self.proc_uid
+    }
+
+    /// MPS INTI flags. See `MadtInterruptOverride::flags`.
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    /// Local APIC interrupt input (LINTn) the NMI is connected to.
+    pub fn lint(&self) -> u8 {
+        self.lint
+    }
+}
+
+/// Represents an entry of the Multiple APIC Description Table (MADT).
+#[derive(Debug, Clone, Copy)]
+pub enum MadtEntry {
+    /// Processor Local APIC Structure.
+    Lapic(MadtLapic),
+
+    /// I/O APIC Structure.
+    IoApic(MadtIoApic),
+
+    /// Interrupt Source Override Structure.
+    InterruptOverride(MadtInterruptOverride),
+
+    /// Non-Maskable Interrupt (NMI) Source Structure.
+    NmiSource(MadtNmiSource),
+
+    /// Local APIC NMI Structure.
+    LapicNmi(MadtLapicNmi),
+
+    /// Local APIC Address Override Structure. It contains the 64-bit
+    /// physical address of the local interrupt controller.
+    LapicAddrOverride(u64),
+
+    /// Structure not supported by the parser. It contains its type.
+    Unknown(u8),
+}
+
+/// Iterator over the entries of the MADT, returned by `Madt::entries`.
+#[derive(Debug, Clone)]
+pub struct MadtEntries {
+    entries: &'static [u8],
+}
+
+impl MadtEntries {
+    /// Returns the size of the fixed part of the structures of type `ty`. It
+    /// returns 2, the size of the type and the length, for unknown types.
+    fn min_length(ty: u8) -> usize {
+        match ty {
+            0 => core::mem::size_of::<AcpiMadtLapic>(),
+            1 => core::mem::size_of::<AcpiMadtIoApic>(),
+            2 => core::mem::size_of::<AcpiMadtInterruptOverride>(),
+            3 => core::mem::size_of::<AcpiMadtNmiSource>(),
+            4 => core::mem::size_of::<AcpiMadtLapicNmi>(),
+            5 => core::mem::size_of::<AcpiMadtLapicAddrOverride>(),
+            _ => 2,
+        }
+    }
+
+    /// Returns the next entry with the form `(type, entry)`. It returns
+    /// `None` and stops at the end of the table or at the first malformed
+    /// entry.
+    fn next_raw(&mut self) -> Option<(u8, &'static [u8])> {
+        let ty = *self.entries.first()?;
+        let length = *self.entries.get(1)? as usize;
+        if length < Self::min_length(ty) || length > self.entries.len() {
+            self.entries = &[];
+            return None;
+        }
+
+        let (entry, next) = self.entries.split_at(length);
+        self.entries = next;
+        Some((ty, entry))
+    }
+}
+
+impl Iterator for MadtEntries {
+    type Item = MadtEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (ty, entry) = self.next_raw()?;
+        let ptr = entry.as_ptr();
+
+        // The length of the entry was checked by `next_raw`.
+        let entry = unsafe {
+            match ty {
+                0 => {
+                    let lapic =
+                        core::ptr::read_unaligned(ptr as *const AcpiMadtLapic);
+                    MadtEntry::Lapic(MadtLapic {
+                        proc_uid: lapic.proc_uid,
+                        apic_id: lapic.apic_id,
+                        flags: lapic.flags,
+                    })
+                }
+                1 => {
+                    let ioapic = core::ptr::read_unaligned(
+                        ptr as *const AcpiMadtIoApic,
+                    );
+                    MadtEntry::IoApic(MadtIoApic {
+                        ioapic_id: ioapic.ioapic_id,
+                        ioapic_addr: ioapic.ioapic_addr,
+                        gsi_base: ioapic.gsi_base,
+                    })
+                }
+                2 => {
+                    let iso = core::ptr::read_unaligned(
+                        ptr as *const AcpiMadtInterruptOverride,
+                    );
+                    MadtEntry::InterruptOverride(MadtInterruptOverride {
+                        bus: iso.bus,
+                        source: iso.source,
+                        gsi: iso.gsi,
+                        flags: iso.flags,
+                    })
+                }
+                3 => {
+                    let nmi_source = core::ptr::read_unaligned(
+                        ptr as *const AcpiMadtNmiSource,
+                    );
+                    MadtEntry::NmiSource(MadtNmiSource {
+                        flags: nmi_source.flags,
+                        gsi: nmi_source.gsi,
+                    })
+                }
+                4 => {
+                    let lapic_nmi = core::ptr::read_unaligned(
+                        ptr as *const AcpiMadtLapicNmi,
+                    );
+                    MadtEntry::LapicNmi(MadtLapicNmi {
+                        proc_uid: lapic_nmi.proc_uid,
+                        flags: lapic_nmi.flags,
+                        lint: lapic_nmi.lint,
+                    })
+                }
+                5 => {
+                    let addr_override = core::ptr::read_unaligned(
+                        ptr as *const AcpiMadtLapicAddrOverride,
+                    );
+                    MadtEntry::LapicAddrOverride(addr_override.lapic_addr)
+                }
+                ty => MadtEntry::Unknown(ty),
+            }
+        };
+
+        Some(entry)
+    }
+}
+
+/// Represents the Multiple APIC Description Table (MADT).
+#[derive(Debug)]
+pub struct Madt {
+    fields: AcpiMadtFields,
+
+    /// Interrupt controller structures of the table.
+    entries: &'static [u8],
+}
+
+impl Madt {
     /// Creates a new `Madt` from a given pointer.
     ///
     /// # Errors
@@ -292,6 +1121,9 @@ impl Madt {
     pub unsafe fn new(madt_ptr: Ptr) -> Result<Madt, Error> {
         // Parse header.
         let hdr = AcpiSdtHeader::new(madt_ptr, SdtType::Madt)?;
+        let entries_len = (hdr.length as usize)
+            .checked_sub(ACPI_SDT_SIZE + ACPI_MADT_FIELDS_SIZE)
+            .ok_or(Error::InvalidAcpiData)?;
 
         // Parse fields.
         let fields = core::ptr::read_unaligned(
@@ -299,42 +1131,21 @@ impl Madt {
                 as *const AcpiMadtFields,
         );
 
-        // Parse entries.
-        let mut num_lapic_entries = 0;
-        let mut lapic_entries = [MadtLapic::default(); ACPI_MADT_ENTRIES_LEN];
-
-        let mut ptr = (madt_ptr.0 as *const u8)
-            .add(ACPI_SDT_SIZE + ACPI_MADT_FIELDS_SIZE);
-        let end = (madt_ptr.0 as *const u8).add(hdr.length as usize);
-
-        while ptr < end {
-            let ty = core::ptr::read_unaligned(ptr);
-            let length = core::ptr::read_unaligned(ptr.add(1));
-
-            // LAPIC.
-            if ty == 0 {
-                if num_lapic_entries >= ACPI_MADT_ENTRIES_LEN {
-                    return Err(Error::BufferTooSmall);
-                }
-
-                let lapic =
-                    core::ptr::read_unaligned(ptr as *const AcpiMadtLapic);
-                lapic_entries[num_lapic_entries] = MadtLapic {
-                    proc_uid: lapic.proc_uid,
-                    apic_id: lapic.apic_id,
-                    flags: lapic.flags,
-                };
-                num_lapic_entries += 1;
-            }
+        let entries = core::slice::from_raw_parts(
+            (madt_ptr.0 as *const u8)
+                .add(ACPI_SDT_SIZE + ACPI_MADT_FIELDS_SIZE),
+            entries_len,
+        );
 
-            ptr = ptr.add(length as usize);
+        // Check the length of the entries, so they can be iterated without
+        // further checks.
+        let mut raw_entries = MadtEntries { entries };
+        while raw_entries.next_raw().is_some() {}
+        if !raw_entries.entries.is_empty() {
+            return Err(Error::InvalidAcpiData);
         }
 
-        Ok(Madt {
-            fields,
-            lapic_entries,
-            num_lapic_entries,
-        })
+        Ok(Madt { fields, entries })
     }
 
     /// Local Interrupt Controller Address. In other words, the 32-bit physical
@@ -344,6 +1155,18 @@ impl Madt {
         self.fields.lapic_addr
     }
 
+    /// 64-bit physical address of the local interrupt controller. If the MADT
+    /// contains a Local APIC Address Override structure, its address is
+    /// returned. Otherwise, it returns the address reported by `lapic_addr`.
+    pub fn lapic_addr64(&self) -> u64 {
+        self.entries()
+            .find_map(|entry| match entry {
+                MadtEntry::LapicAddrOverride(addr) => Some(addr),
+                _ => None,
+            })
+            .unwrap_or_else(|| self.fields.lapic_addr.into())
+    }
+
     /// Multiple ACPI flags.
     ///
     /// Bit offset | Bit length | Flag
@@ -354,8 +1177,1100 @@ impl Madt {
         self.fields.flags
     }
 
-    /// Returns the detected local APIC structures.
-    pub fn lapic(&self) -> &[MadtLapic] {
-        &self.lapic_entries[..self.num_lapic_entries]
+    /// Returns an iterator over the interrupt controller structures of the
+    /// MADT.
+    pub fn entries(&self) -> MadtEntries {
+        MadtEntries {
+            entries: self.entries,
+        }
+    }
+
+    /// Returns an iterator over the local APIC structures.
+    pub fn lapic(&self) -> impl Iterator<Item = MadtLapic> {
+        self.entries().filter_map(|entry| match entry {
+            MadtEntry::Lapic(lapic) => Some(lapic),
+            _ => None,
+        })
+    }
+
+    /// Returns an iterator over the I/O APIC structures.
+    pub fn ioapics(&self) -> impl Iterator<Item = MadtIoApic> {
+        self.entries().filter_map(|entry| match entry {
+            MadtEntry::IoApic(ioapic) => Some(ioapic),
+            _ => None,
+        })
+    }
+
+    /// Returns an iterator over the Interrupt Source Override structures.
+    pub fn interrupt_overrides(
+        &self,
+    ) -> impl Iterator<Item = MadtInterruptOverride> {
+        self.entries().filter_map(|entry| match entry {
+            MadtEntry::InterruptOverride(iso) => Some(iso),
+            _ => None,
+        })
+    }
+
+    /// Returns an iterator over the NMI Source structures.
+    pub fn nmi_sources(&self) -> impl Iterator<Item = MadtNmiSource> {
+        self.entries().filter_map(|entry| match entry {
+            MadtEntry::NmiSource(nmi_source) => Some(nmi_source),
+            _ => None,
+        })
+    }
+
+    /// Returns an iterator over the Local APIC NMI structures.
+    pub fn lapic_nmis(&self) -> impl Iterator<Item = MadtLapicNmi> {
+        self.entries().filter_map(|entry| match entry {
+            MadtEntry::LapicNmi(lapic_nmi) => Some(lapic_nmi),
+            _ => None,
+        })
+    }
+}
+
+/// Generic Address Structure (GAS) in the ACPI specification.
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C, packed)]
+struct AcpiGenericAddress {
+    address_space_id: u8,
+    register_bit_width: u8,
+    register_bit_offset: u8,
+    access_size: u8,
+    address: u64,
+}
+
+/// Address space of a register described by a `GenericAddress`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AddressSpace {
+    /// System memory (MMIO).
+    SystemMemory,
+
+    /// System I/O ports.
+    SystemIo,
+
+    /// PCI configuration space.
+    PciConfig,
+
+    /// Other address spaces.
+    Other(u8),
+}
+
+/// Represents a Generic Address Structure (GAS). It describes the location of
+/// a register.
+#[derive(Debug, Clone, Copy)]
+pub struct GenericAddress {
+    gas: AcpiGenericAddress,
+}
+
+impl GenericAddress {
+    /// Address space of the register.
+    pub fn address_space(&self) -> AddressSpace {
+        match self.gas.address_space_id {
+            0 => AddressSpace::SystemMemory,
+            1 => AddressSpace::SystemIo,
+            2 => AddressSpace::PciConfig,
+            id => AddressSpace::Other(id),
+        }
+    }
+
+    /// Size in bits of the register.
+    pub fn bit_width(&self) -> u8 {
+        self.gas.register_bit_width
+    }
+
+    /// Bit offset of the register at the given address.
+    pub fn bit_offset(&self) -> u8 {
+        self.gas.register_bit_offset
+    }
+
+    /// Access size: 0 undefined, 1 byte, 2 word, 3 dword and 4 qword.
+    pub fn access_size(&self) -> u8 {
+        self.gas.access_size
+    }
+
+    /// Address of the register in its address space.
+    pub fn address(&self) -> u64 {
+        self.gas.address
+    }
+}
+
+/// Extra fields of the High Precision Event Timer Table (HPET).
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+struct AcpiHpetFields {
+    event_timer_block_id: u32,
+    base_address: AcpiGenericAddress,
+    hpet_number: u8,
+    minimum_tick: u16,
+    page_protection: u8,
+}
+
+/// Represents the High Precision Event Timer Table (HPET).
+#[derive(Debug)]
+pub struct Hpet {
+    fields: AcpiHpetFields,
+}
+
+impl Hpet {
+    /// Creates a new `Hpet` from a given pointer.
+    ///
+    /// # Errors
+    ///
+    /// This function returns error if the pointer does not point to a valid
+    /// HPET table or the timer block is not memory-mapped.
+    ///
+    /// # Safety
+    ///
+    /// The `Hpet` structure is created using a pointer. Thus, this function is
+    /// considered unsafe.
+    pub unsafe fn new(hpet_ptr: Ptr) -> Result<Hpet, Error> {
+        // Parse header.
+        let hdr = AcpiSdtHeader::new(hpet_ptr, SdtType::Hpet)?;
+        if (hdr.length as usize)
+            < ACPI_SDT_SIZE + core::mem::size_of::<AcpiHpetFields>()
+        {
+            return Err(Error::InvalidAcpiData);
+        }
+
+        // Parse fields.
+        let fields = core::ptr::read_unaligned(
+            (hpet_ptr.0 as *const u8).add(ACPI_SDT_SIZE)
+                as *const AcpiHpetFields,
+        );
+
+        // The timer block must be in the system memory address space.
+        if fields.base_address.address_space_id != 0 {
+            return Err(Error::InvalidAcpiData);
+        }
+
+        Ok(Hpet { fields })
+    }
+
+    /// The 64-bit physical address of the event timer block.
+    pub fn base_address(&self) -> u64 {
+        self.fields.base_address.address
+    }
+
+    /// HPET sequence number.
+    pub fn hpet_number(&self) -> u8 {
+        self.fields.hpet_number
+    }
+
+    /// Minimum clock tick, in periodic mode, without lost interrupts.
+    pub fn minimum_tick(&self) -> u16 {
+        self.fields.minimum_tick
+    }
+
+    /// Number of comparators in the first timer block.
+    pub fn comparator_count(&self) -> u8 {
+        ((self.fields.event_timer_block_id >> 8) & 0x1f) as u8 + 1
+    }
+
+    /// Returns `true` if the main counter is 64-bit wide.
+    pub fn counter_64bit(&self) -> bool {
+        self.fields.event_timer_block_id & (1 << 13) != 0
+    }
+
+    /// PCI vendor ID of the first timer block.
+    pub fn vendor_id(&self) -> u16 {
+        (self.fields.event_timer_block_id >> 16) as u16
+    }
+}
+
+/// Size of the reserved field that follows the header of the MCFG.
+const ACPI_MCFG_RESERVED_SIZE: usize = 8;
+
+/// Maximum number of entries in the MCFG.
+const ACPI_MCFG_ENTRIES_LEN: usize = 16;
+
+/// Configuration Space Base Address Allocation Structure of the MCFG.
+#[repr(C, packed)]
+struct AcpiMcfgAllocation {
+    base_address: u64,
+    segment: u16,
+    start_bus: u8,
+    end_bus: u8,
+    reserved: u32,
+}
+
+/// Represents a Configuration Space Base Address Allocation Structure. It
+/// describes the enhanced configuration access mechanism (ECAM) region of a
+/// range of buses of a PCI segment group.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct McfgAllocation {
+    base_address: u64,
+    segment: u16,
+    start_bus: u8,
+    end_bus: u8,
+}
+
+impl McfgAllocation {
+    /// Physical address of the enhanced configuration space. It corresponds
+    /// to bus 0, even if `start_bus` is not 0.
+    pub fn base_address(&self) -> u64 {
+        self.base_address
+    }
+
+    /// PCI segment group number.
+    pub fn segment(&self) -> u16 {
+        self.segment
+    }
+
+    /// First PCI bus number decoded by the host bridge.
+    pub fn start_bus(&self) -> u8 {
+        self.start_bus
+    }
+
+    /// Last PCI bus number decoded by the host bridge.
+    pub fn end_bus(&self) -> u8 {
+        self.end_bus
+    }
+
+    /// Returns the physical address of the configuration space of the
+    /// function `function` of the device `device` on the bus `bus`. It
+    /// returns `None` if the bus is not covered by this allocation or the
+    /// device or function numbers are out of range.
+    pub fn config_address(
+        &self,
+        bus: u8,
+        device: u8,
+        function: u8,
+    ) -> Option<u64> {
+        if bus < self.start_bus
+            || bus > self.end_bus
+            || device >= 32
+            || function >= 8
+        {
+            return None;
+        }
+
+        let offset = (u64::from(bus) << 20)
+            | (u64::from(device) << 15)
+            | (u64::from(function) << 12);
+        Some(self.base_address + offset)
+    }
+}
+
+/// Represents the PCI Express memory mapped configuration space base address
+/// description table (MCFG).
+#[derive(Debug)]
+pub struct Mcfg {
+    entries: [McfgAllocation; ACPI_MCFG_ENTRIES_LEN],
+    num_entries: usize,
+}
+
+impl Mcfg {
+    /// Creates a new `Mcfg` from a given pointer.
+    ///
+    /// # Errors
+    ///
+    /// This function returns error if the pointer does not point to a valid
+    /// MCFG.
+    ///
+    /// # Safety
+    ///
+    /// The `Mcfg` structure is created using a pointer. Thus, this function is
+    /// considered unsafe.
+    pub unsafe fn new(mcfg_ptr: Ptr) -> Result<Mcfg, Error> {
+        const ENTRY_SIZE: usize = core::mem::size_of::<AcpiMcfgAllocation>();
+
+        // Parse header.
+        let hdr = AcpiSdtHeader::new(mcfg_ptr, SdtType::Mcfg)?;
+
+        // Calculate number of entries.
+        let entries_length = (hdr.length as usize)
+            .checked_sub(ACPI_SDT_SIZE + ACPI_MCFG_RESERVED_SIZE)
+            .ok_or(Error::InvalidAcpiData)?;
+        if entries_length % ENTRY_SIZE != 0 {
+            return Err(Error::InvalidAcpiData);
+        }
+        let num_entries = entries_length / ENTRY_SIZE;
+
+        // Check that there is enough room for the entries in the fixed size
+        // array.
+        if num_entries > ACPI_MCFG_ENTRIES_LEN {
+            return Err(Error::BufferTooSmall);
+        }
+
+        // Parse entries.
+        let mut entries = [McfgAllocation::default(); ACPI_MCFG_ENTRIES_LEN];
+        for (i, it) in entries.iter_mut().take(num_entries).enumerate() {
+            let ptr = (mcfg_ptr.0 as *const u8)
+                .add(ACPI_SDT_SIZE + ACPI_MCFG_RESERVED_SIZE + i * ENTRY_SIZE)
+                as *const AcpiMcfgAllocation;
+            let allocation = core::ptr::read_unaligned(ptr);
+            *it = McfgAllocation {
+                base_address: allocation.base_address,
+                segment: allocation.segment,
+                start_bus: allocation.start_bus,
+                end_bus: allocation.end_bus,
+            };
+        }
+
+        Ok(Mcfg {
+            entries,
+            num_entries,
+        })
+    }
+
+    /// Returns the configuration space base address allocations.
+    pub fn allocations(&self) -> &[McfgAllocation] {
+        &self.entries[..self.num_entries]
+    }
+}
+
+/// Extra fields of the Fixed ACPI Description Table (FADT). Older FADTs are
+/// shorter, so the fields beyond the length of the table are zeroed.
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C, packed)]
+struct AcpiFadtFields {
+    firmware_ctrl: u32,
+    dsdt: u32,
+    reserved0: u8,
+    preferred_pm_profile: u8,
+    sci_int: u16,
+    smi_cmd: u32,
+    acpi_enable: u8,
+    acpi_disable: u8,
+    s4bios_req: u8,
+    pstate_cnt: u8,
+    pm1a_evt_blk: u32,
+    pm1b_evt_blk: u32,
+    pm1a_cnt_blk: u32,
+    pm1b_cnt_blk: u32,
+    pm2_cnt_blk: u32,
+    pm_tmr_blk: u32,
+    gpe0_blk: u32,
+    gpe1_blk: u32,
+    pm1_evt_len: u8,
+    pm1_cnt_len: u8,
+    pm2_cnt_len: u8,
+    pm_tmr_len: u8,
+    gpe0_blk_len: u8,
+    gpe1_blk_len: u8,
+    gpe1_base: u8,
+    cst_cnt: u8,
+    p_lvl2_lat: u16,
+    p_lvl3_lat: u16,
+    flush_size: u16,
+    flush_stride: u16,
+    duty_offset: u8,
+    duty_width: u8,
+    day_alrm: u8,
+    mon_alrm: u8,
+    century: u8,
+    iapc_boot_arch: u16,
+    reserved1: u8,
+    flags: u32,
+    reset_reg: AcpiGenericAddress,
+    reset_value: u8,
+    arm_boot_arch: u16,
+    fadt_minor_version: u8,
+    x_firmware_ctrl: u64,
+    x_dsdt: u64,
+    x_pm1a_evt_blk: AcpiGenericAddress,
+    x_pm1b_evt_blk: AcpiGenericAddress,
+    x_pm1a_cnt_blk: AcpiGenericAddress,
+    x_pm1b_cnt_blk: AcpiGenericAddress,
+    x_pm2_cnt_blk: AcpiGenericAddress,
+    x_pm_tmr_blk: AcpiGenericAddress,
+    x_gpe0_blk: AcpiGenericAddress,
+    x_gpe1_blk: AcpiGenericAddress,
+}
+
+/// FADT flag: the PM timer is 32-bit wide. Otherwise, it is 24-bit wide.
+const ACPI_FADT_TMR_VAL_EXT: u32 = 1 << 8;
+
+/// FADT flag: the reset register is supported.
+const ACPI_FADT_RESET_REG_SUP: u32 = 1 << 10;
+
+/// Represents the Fixed ACPI Description Table (FADT).
+#[derive(Debug)]
+pub struct Fadt {
+    fields: AcpiFadtFields,
+}
+
+impl Fadt {
+    /// Creates a new `Fadt` from a given pointer.
+    ///
+    /// # Errors
+    ///
+    /// This function returns error if the pointer does not point to a valid
+    /// FADT.
+    ///
+    /// # Safety
+    ///
+    /// The `Fadt` structure is created using a pointer. Thus, this function is
+    /// considered unsafe.
+    pub unsafe fn new(fadt_ptr: Ptr) -> Result<Fadt, Error> {
+        // Parse header.
+        let hdr = AcpiSdtHeader::new(fadt_ptr, SdtType::Fadt)?;
+
+        // Parse fields. Only the bytes present in the table are copied.
+        let len = (hdr.length as usize)
+            .checked_sub(ACPI_SDT_SIZE)
+            .ok_or(Error::InvalidAcpiData)?
+            .min(core::mem::size_of::<AcpiFadtFields>());
+        let mut fields = AcpiFadtFields::default();
+        core::ptr::copy_nonoverlapping(
+            (fadt_ptr.0 as *const u8).add(ACPI_SDT_SIZE),
+            &mut fields as *mut AcpiFadtFields as *mut u8,
+            len,
+        );
+
+        Ok(Fadt { fields })
+    }
+
+    /// System vector the SCI interrupt is wired to in 8259 mode.
+    pub fn sci_int(&self) -> u16 {
+        self.fields.sci_int
+    }
+
+    /// RTC CMOS RAM index of the century of the date. It is `None` if the
+    /// RTC does not support the century.
+    pub fn century(&self) -> Option<u8> {
+        match self.fields.century {
+            0 => None,
+            century => Some(century),
+        }
+    }
+
+    /// Returns the register of the extended field if it is present.
+    /// Otherwise, returns the legacy I/O port block `port` of `len` bytes.
+    fn register(
+        x_blk: AcpiGenericAddress,
+        port: u32,
+        len: u8,
+    ) -> Option<GenericAddress> {
+        if x_blk.address != 0 {
+            return Some(GenericAddress { gas: x_blk });
+        }
+        if port == 0 {
+            return None;
+        }
+
+        Some(GenericAddress {
+            gas: AcpiGenericAddress {
+                address_space_id: 1,
+                register_bit_width: len.saturating_mul(8),
+                register_bit_offset: 0,
+                access_size: 0,
+                address: port.into(),
+            },
+        })
+    }
+
+    /// ACPI power management timer block. It is `None` if the platform does
+    /// not have a PM timer.
+    pub fn pm_timer(&self) -> Option<GenericAddress> {
+        Fadt::register(
+            self.fields.x_pm_tmr_blk,
+            self.fields.pm_tmr_blk,
+            self.fields.pm_tmr_len,
+        )
+    }
+
+    /// Returns `true` if the PM timer is 32-bit wide. Otherwise, it is 24-bit
+    /// wide.
+    pub fn pm_timer_32bit(&self) -> bool {
+        self.fields.flags & ACPI_FADT_TMR_VAL_EXT != 0
+    }
+
+    /// PM1a control register block. It is used to enter sleep states.
+    pub fn pm1a_control_block(&self) -> Option<GenericAddress> {
+        Fadt::register(
+            self.fields.x_pm1a_cnt_blk,
+            self.fields.pm1a_cnt_blk,
+            self.fields.pm1_cnt_len,
+        )
+    }
+
+    /// Reset register and the value to write into it in order to reset the
+    /// system, with the form `(register, value)`. It is `None` if the reset
+    /// register is not supported.
+    pub fn reset_register(&self) -> Option<(GenericAddress, u8)> {
+        if self.fields.flags & ACPI_FADT_RESET_REG_SUP == 0 {
+            return None;
+        }
+
+        let reset_reg = GenericAddress {
+            gas: self.fields.reset_reg,
+        };
+        Some((reset_reg, self.fields.reset_value))
+    }
+}
+
+/// Extra fields of the DMA Remapping Reporting Table (DMAR).
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+struct AcpiDmarFields {
+    host_address_width: u8,
+    flags: u8,
+    reserved: [u8; 10],
+}
+
+/// Size of the extra fields of the DMAR.
+const ACPI_DMAR_FIELDS_SIZE: usize = core::mem::size_of::<AcpiDmarFields>();
+
+/// Header of the remapping structures of the DMAR.
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+struct AcpiDmarHeader {
+    ty: u16,
+    length: u16,
+}
+
+/// DMA Remapping Hardware Unit Definition (DRHD) structure of the DMAR.
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+struct AcpiDmarDrhd {
+    hdr: AcpiDmarHeader,
+    flags: u8,
+    size: u8,
+    segment: u16,
+    register_base: u64,
+}
+
+/// Reserved Memory Region Reporting (RMRR) structure of the DMAR.
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+struct AcpiDmarRmrr {
+    hdr: AcpiDmarHeader,
+    reserved: u16,
+    segment: u16,
+    base_address: u64,
+    limit_address: u64,
+}
+
+/// Header of the Device Scope structures of the DMAR.
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+struct AcpiDmarDeviceScope {
+    ty: u8,
+    length: u8,
+    flags: u8,
+    reserved: u8,
+    enumeration_id: u8,
+    start_bus: u8,
+}
+
+/// Represents the DMA Remapping Reporting Table (DMAR). It describes the
+/// DMA remapping hardware (IOMMU) of the platform.
+#[derive(Debug)]
+pub struct Dmar {
+    fields: AcpiDmarFields,
+
+    /// Remapping structures of the table.
+    structures: &'static [u8],
+}
+
+impl Dmar {
+    /// Creates a new `Dmar` from a given pointer.
+    ///
+    /// # Errors
+    ///
+    /// This function returns error if the pointer does not point to a valid
+    /// DMAR.
+    ///
+    /// # Safety
+    ///
+    /// The `Dmar` structure is created using a pointer. Thus, this function is
+    /// considered unsafe.
+    pub unsafe fn new(dmar_ptr: Ptr) -> Result<Dmar, Error> {
+        // Parse header.
+        let hdr = AcpiSdtHeader::new(dmar_ptr, SdtType::Dmar)?;
+        let structures_len = (hdr.length as usize)
+            .checked_sub(ACPI_SDT_SIZE + ACPI_DMAR_FIELDS_SIZE)
+            .ok_or(Error::InvalidAcpiData)?;
+
+        // Parse fields.
+        let fields = core::ptr::read_unaligned(
+            (dmar_ptr.0 as *const u8).add(ACPI_SDT_SIZE)
+                as *const AcpiDmarFields,
+        );
+
+        let structures = core::slice::from_raw_parts(
+            (dmar_ptr.0 as *const u8)
+                .add(ACPI_SDT_SIZE + ACPI_DMAR_FIELDS_SIZE),
+            structures_len,
+        );
+
+        // Check the length of the remapping structures and their device
+        // scopes, so they can be iterated without further checks.
+        let dmar = Dmar { fields, structures };
+        for (ty, structure) in dmar.structures() {
+            let scopes_off = match ty {
+                0 => core::mem::size_of::<AcpiDmarDrhd>(),
+                1 => core::mem::size_of::<AcpiDmarRmrr>(),
+                _ => continue,
+            };
+            let scopes =
+                structure.get(scopes_off..).ok_or(Error::InvalidAcpiData)?;
+            let mut scopes = DeviceScopes { scopes };
+            if scopes.any(|scope| scope.is_none()) {
+                return Err(Error::InvalidAcpiData);
+            }
+        }
+        if dmar.structures().map(|(_, s)| s.len()).sum::<usize>()
+            != structures_len
+        {
+            return Err(Error::InvalidAcpiData);
+        }
+
+        Ok(dmar)
+    }
+
+    /// Maximum DMA physical addressability supported by the platform, in
+    /// bits.
+    pub fn host_address_width(&self) -> u8 {
+        self.fields.host_address_width.saturating_add(1)
+    }
+
+    /// DMAR flags.
+    ///
+    /// Bit offset | Bit length | Flag
+    /// ---------- | ---------- | ---------------------
+    /// 0          | 1          | INTR_REMAP
+    /// 1          | 1          | X2APIC_OPT_OUT
+    /// 2          | 1          | DMA_CTRL_PLATFORM_OPT_IN
+    /// 3          | 5          | Reserved (zero)
+    pub fn flags(&self) -> u8 {
+        self.fields.flags
+    }
+
+    /// Returns an iterator over the remapping structures with the form
+    /// `(type, structure)`. It stops at the first malformed structure.
+    fn structures(&self) -> impl Iterator<Item = (u16, &'static [u8])> {
+        const HDR_SIZE: usize = core::mem::size_of::<AcpiDmarHeader>();
+
+        let mut rest = self.structures;
+        core::iter::from_fn(move || {
+            if rest.len() < HDR_SIZE {
+                return None;
+            }
+            let hdr = unsafe {
+                core::ptr::read_unaligned(
+                    rest.as_ptr() as *const AcpiDmarHeader
+                )
+            };
+            let length = hdr.length as usize;
+            if length < HDR_SIZE || length > rest.len() {
+                return None;
+            }
+            let (structure, next) = rest.split_at(length);
+            rest = next;
+            Some((hdr.ty, structure))
+        })
+    }
+
+    /// Returns an iterator over the DMA Remapping Hardware Unit Definition
+    /// (DRHD) structures.
+    pub fn drhd_units(&self) -> impl Iterator<Item = DmarDrhd> {
+        self.structures().filter_map(|(ty, structure)| {
+            if ty != 0 {
+                return None;
+            }
+            let drhd = unsafe {
+                core::ptr::read_unaligned(
+                    structure.as_ptr() as *const AcpiDmarDrhd
+                )
+            };
+            let scopes = &structure[core::mem::size_of::<AcpiDmarDrhd>()..];
+            Some(DmarDrhd { drhd, scopes })
+        })
+    }
+
+    /// Returns an iterator over the Reserved Memory Region Reporting (RMRR)
+    /// structures.
+    pub fn rmrr_regions(&self) -> impl Iterator<Item = DmarRmrr> {
+        self.structures().filter_map(|(ty, structure)| {
+            if ty != 1 {
+                return None;
+            }
+            let rmrr = unsafe {
+                core::ptr::read_unaligned(
+                    structure.as_ptr() as *const AcpiDmarRmrr
+                )
+            };
+            let scopes = &structure[core::mem::size_of::<AcpiDmarRmrr>()..];
+            Some(DmarRmrr { rmrr, scopes })
+        })
+    }
+}
+
+/// Represents a DMA Remapping Hardware Unit Definition (DRHD) structure. It
+/// describes a remapping hardware unit.
+#[derive(Debug, Clone, Copy)]
+pub struct DmarDrhd {
+    drhd: AcpiDmarDrhd,
+
+    /// Device scope structures.
+    scopes: &'static [u8],
+}
+
+impl DmarDrhd {
+    /// DRHD flags. If bit 0 (INCLUDE_PCI_ALL) is set, the unit remaps all the
+    /// PCI devices of the segment not reported by other units.
+    pub fn flags(&self) -> u8 {
+        self.drhd.flags
+    }
+
+    /// PCI segment associated with the unit.
+    pub fn segment(&self) -> u16 {
+        self.drhd.segment
+    }
+
+    /// Physical address of the remapping hardware register set.
+    pub fn register_base(&self) -> u64 {
+        self.drhd.register_base
+    }
+
+    /// Returns an iterator over the devices under the scope of the unit.
+    pub fn device_scopes(&self) -> impl Iterator<Item = DeviceScope> {
+        // The device scopes are checked when the `Dmar` is created.
+        DeviceScopes {
+            scopes: self.scopes,
+        }
+        .flatten()
+    }
+}
+
+/// Represents a Reserved Memory Region Reporting (RMRR) structure. It
+/// describes a memory region used by devices for DMA that must be kept
+/// identity-mapped.
+#[derive(Debug, Clone, Copy)]
+pub struct DmarRmrr {
+    rmrr: AcpiDmarRmrr,
+
+    /// Device scope structures.
+    scopes: &'static [u8],
+}
+
+impl DmarRmrr {
+    /// PCI segment of the devices that use the region.
+    pub fn segment(&self) -> u16 {
+        self.rmrr.segment
+    }
+
+    /// Physical address of the first byte of the region.
+    pub fn base_address(&self) -> u64 {
+        self.rmrr.base_address
+    }
+
+    /// Physical address of the last byte of the region.
+    pub fn limit_address(&self) -> u64 {
+        self.rmrr.limit_address
+    }
+
+    /// Returns the region as a `Range`.
+    pub fn range(&self) -> Result<Range, Error> {
+        Ok(Range::new(self.base_address(), self.limit_address())?)
+    }
+
+    /// Returns an iterator over the devices that use the region.
+    pub fn device_scopes(&self) -> impl Iterator<Item = DeviceScope> {
+        // The device scopes are checked when the `Dmar` is created.
+        DeviceScopes {
+            scopes: self.scopes,
+        }
+        .flatten()
+    }
+}
+
+/// Iterator over the Device Scope structures of a remapping structure. It
+/// yields `None` and stops at the first malformed structure.
+struct DeviceScopes {
+    scopes: &'static [u8],
+}
+
+impl Iterator for DeviceScopes {
+    type Item = Option<DeviceScope>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        const HDR_SIZE: usize = core::mem::size_of::<AcpiDmarDeviceScope>();
+
+        if self.scopes.is_empty() {
+            return None;
+        }
+
+        let length = match self.scopes.get(1) {
+            Some(&length) => length as usize,
+            None => 0,
+        };
+        if length < HDR_SIZE || length > self.scopes.len() {
+            self.scopes = &[];
+            return Some(None);
+        }
+
+        let scope = unsafe {
+            core::ptr::read_unaligned(
+                self.scopes.as_ptr() as *const AcpiDmarDeviceScope
+            )
+        };
+        let path = &self.scopes[HDR_SIZE..length];
+        self.scopes = &self.scopes[length..];
+
+        Some(Some(DeviceScope { scope, path }))
+    }
+}
+
+/// Represents a Device Scope structure. It identifies a PCI device, a PCI
+/// sub-hierarchy, an I/O APIC or an HPET.
+#[derive(Debug, Clone, Copy)]
+pub struct DeviceScope {
+    scope: AcpiDmarDeviceScope,
+
+    /// Path from the start bus to the device, as `(device, function)` pairs.
+    path: &'static [u8],
+}
+
+impl DeviceScope {
+    /// Type of the device scope: 1 PCI endpoint, 2 PCI sub-hierarchy, 3 I/O
+    /// APIC, 4 HPET and 5 ACPI namespace device.
+    pub fn scope_type(&self) -> u8 {
+        self.scope.ty
+    }
+
+    /// Enumeration ID of I/O APICs, HPETs and ACPI namespace devices.
+    pub fn enumeration_id(&self) -> u8 {
+        self.scope.enumeration_id
+    }
+
+    /// PCI bus number where the path starts.
+    pub fn start_bus(&self) -> u8 {
+        self.scope.start_bus
+    }
+
+    /// Returns an iterator over the path from the start bus to the device,
+    /// with the form `(device, function)`.
+    pub fn path(&self) -> impl Iterator<Item = (u8, u8)> {
+        self.path.chunks_exact(2).map(|entry| (entry[0], entry[1]))
+    }
+}
+
+/// Extra fields of the Boot Graphics Resource Table (BGRT).
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+struct AcpiBgrtFields {
+    version: u16,
+    status: u8,
+    image_type: u8,
+    image_address: u64,
+    image_offset_x: u32,
+    image_offset_y: u32,
+}
+
+/// Represents the Boot Graphics Resource Table (BGRT). It describes the image
+/// drawn on the screen by the firmware during boot (e.g. the vendor logo).
+#[derive(Debug)]
+pub struct Bgrt {
+    fields: AcpiBgrtFields,
+}
+
+impl Bgrt {
+    /// Creates a new `Bgrt` from a given pointer.
+    ///
+    /// # Errors
+    ///
+    /// This function returns error if the pointer does not point to a valid
+    /// BGRT.
+    ///
+    /// # Safety
+    ///
+    /// The `Bgrt` structure is created using a pointer. Thus, this function is
+    /// considered unsafe.
+    pub unsafe fn new(bgrt_ptr: Ptr) -> Result<Bgrt, Error> {
+        // Parse header.
+        let hdr = AcpiSdtHeader::new(bgrt_ptr, SdtType::Bgrt)?;
+        if (hdr.length as usize)
+            < ACPI_SDT_SIZE + core::mem::size_of::<AcpiBgrtFields>()
+        {
+            return Err(Error::InvalidAcpiData);
+        }
+
+        // Parse fields.
+        let fields = core::ptr::read_unaligned(
+            (bgrt_ptr.0 as *const u8).add(ACPI_SDT_SIZE)
+                as *const AcpiBgrtFields,
+        );
+
+        Ok(Bgrt { fields })
+    }
+
+    /// Version of the table. It must be 1.
+    pub fn version(&self) -> u16 {
+        self.fields.version
+    }
+
+    /// Status of the image.
+    ///
+    /// Bit offset | Bit length | Flag
+    /// ---------- | ---------- | ------------------
+    /// 0          | 1          | Displayed
+    /// 1          | 2          | Orientation offset
+    /// 3          | 5          | Reserved (zero)
+    pub fn status(&self) -> u8 {
+        self.fields.status
+    }
+
+    /// Returns `true` if the image is currently displayed on the screen.
+    pub fn displayed(&self) -> bool {
+        self.fields.status & 1 != 0
+    }
+
+    /// Type of the image. 0 means Bitmap (BMP).
+    pub fn image_type(&self) -> u8 {
+        self.fields.image_type
+    }
+
+    /// Physical address of the image. The image is stored in boot services
+    /// data memory, so it must be copied before that memory is reused.
+    pub fn image_address(&self) -> u64 {
+        self.fields.image_address
+    }
+
+    /// Position of the upper left corner of the image on the screen, with the
+    /// form `(x, y)`.
+    pub fn image_offset(&self) -> (u32, u32) {
+        (self.fields.image_offset_x, self.fields.image_offset_y)
+    }
+}
+
+/// Extra fields of the Serial Port Console Redirection Table (SPCR).
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+struct AcpiSpcrFields {
+    interface_type: u8,
+    reserved: [u8; 3],
+    base_address: AcpiGenericAddress,
+    interrupt_type: u8,
+    irq: u8,
+    gsi: u32,
+    configured_baud_rate: u8,
+    parity: u8,
+    stop_bits: u8,
+    flow_control: u8,
+    terminal_type: u8,
+}
+
+/// Represents the Serial Port Console Redirection Table (SPCR). It describes
+/// the serial port used by the firmware as console.
+#[derive(Debug)]
+pub struct Spcr {
+    fields: AcpiSpcrFields,
+}
+
+impl Spcr {
+    /// Creates a new `Spcr` from a given pointer.
+    ///
+    /// # Errors
+    ///
+    /// This function returns error if the pointer does not point to a valid
+    /// SPCR.
+    ///
+    /// # Safety
+    ///
+    /// The `Spcr` structure is created using a pointer. Thus, this function is
+    /// considered unsafe.
+    pub unsafe fn new(spcr_ptr: Ptr) -> Result<Spcr, Error> {
+        // Parse header.
+        let hdr = AcpiSdtHeader::new(spcr_ptr, SdtType::Spcr)?;
+        if (hdr.length as usize)
+            < ACPI_SDT_SIZE + core::mem::size_of::<AcpiSpcrFields>()
+        {
+            return Err(Error::InvalidAcpiData);
+        }
+
+        // Parse fields.
+        let fields = core::ptr::read_unaligned(
+            (spcr_ptr.0 as *const u8).add(ACPI_SDT_SIZE)
+                as *const AcpiSpcrFields,
+        );
+
+        Ok(Spcr { fields })
+    }
+
+    /// Type of the serial port interface. 0 means full 16550 interface and
+    /// 1 means 16450 interface (a subset of the 16550).
+    pub fn interface_type(&self) -> u8 {
+        self.fields.interface_type
+    }
+
+    /// Returns `true` if the serial port is compatible with the 8250 UART.
+    pub fn pc_compatible(&self) -> bool {
+        self.fields.interface_type <= 1
+    }
+
+    /// Base address of the registers of the serial port.
+    pub fn base_address(&self) -> GenericAddress {
+        GenericAddress {
+            gas: self.fields.base_address,
+        }
+    }
+
+    /// Returns the IO port address of the serial port, or `None` if its
+    /// registers are not in the system I/O address space.
+    pub fn io_port(&self) -> Option<u16> {
+        let base_address = self.base_address();
+        if base_address.address_space() != AddressSpace::SystemIo {
+            return None;
+        }
+        base_address.address().try_into().ok()
+    }
+
+    /// Interrupt type. If bit 0 is set, the serial port uses a PC-AT
+    /// compatible IRQ (see `irq`). If bit 1 is set, it uses an I/O APIC
+    /// interrupt (see `gsi`).
+    pub fn interrupt_type(&self) -> u8 {
+        self.fields.interrupt_type
+    }
+
+    /// PC-AT compatible IRQ used by the serial port.
+    pub fn irq(&self) -> u8 {
+        self.fields.irq
+    }
+
+    /// Global System Interrupt (GSI) used by the serial port.
+    pub fn gsi(&self) -> u32 {
+        self.fields.gsi
+    }
+
+    /// Returns the baud rate configured by the firmware, or `None` if the
+    /// firmware does not report it and the current one must be kept.
+    pub fn baud_rate(&self) -> Option<u32> {
+        match self.fields.configured_baud_rate {
+            3 => Some(9600),
+            4 => Some(19200),
+            6 => Some(57600),
+            7 => Some(115200),
+            _ => None,
+        }
+    }
+
+    /// Parity. 0 means no parity. Other values are reserved.
+    pub fn parity(&self) -> u8 {
+        self.fields.parity
+    }
+
+    /// Stop bits. 1 means 1 stop bit. Other values are reserved.
+    pub fn stop_bits(&self) -> u8 {
+        self.fields.stop_bits
+    }
+
+    /// Flow control.
+    ///
+    /// Bit offset | Bit length | Flag
+    /// ---------- | ---------- | ----------------------
+    /// 0          | 1          | DCD required
+    /// 1          | 1          | RTS/CTS hardware flow
+    /// 2          | 1          | XON/XOFF software flow
+    /// 3          | 5          | Reserved (zero)
+    pub fn flow_control(&self) -> u8 {
+        self.fields.flow_control
+    }
+
+    /// Terminal type: 0 VT100, 1 extended VT100, 2 VT-UTF8 and 3 ANSI.
+    pub fn terminal_type(&self) -> u8 {
+        self.fields.terminal_type
     }
 }
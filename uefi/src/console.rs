@@ -0,0 +1,212 @@
+//! This module provides access to the UEFI console, using the Simple Text
+//! Input and Simple Text Output protocols.
+//!
+//! The protocols are owned by the firmware. Thus, the console cannot be used
+//! after exiting the boot services.
+
+use core::fmt;
+use core::marker::PhantomData;
+
+use crate::{EfiStatus, Error, Ptr, Status, StatusError, SystemTable};
+
+/// Number of UCS-2 characters sent to the firmware in each call to
+/// `OutputString()`, including the terminating nul character.
+const OUTPUT_CHUNK_LEN: usize = 128;
+
+/// The `EFI_INPUT_KEY` type of the UEFI specification.
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+struct EfiInputKey {
+    scan_code: u16,
+    unicode_char: u16,
+}
+
+/// The `EFI_SIMPLE_TEXT_INPUT_PROTOCOL` type of the UEFI specification.
+#[repr(C)]
+struct EfiSimpleTextInputProtocol {
+    reset: Ptr,
+    read_key_stroke: extern "efiapi" fn(
+        *mut EfiSimpleTextInputProtocol,
+        *mut EfiInputKey,
+    ) -> EfiStatus,
+    wait_for_key: Ptr,
+}
+
+/// The `EFI_SIMPLE_TEXT_OUTPUT_PROTOCOL` type of the UEFI specification.
+#[repr(C)]
+struct EfiSimpleTextOutputProtocol {
+    reset: Ptr,
+    output_string: extern "efiapi" fn(
+        *mut EfiSimpleTextOutputProtocol,
+        *const u16,
+    ) -> EfiStatus,
+    test_string: Ptr,
+    query_mode: Ptr,
+    set_mode: Ptr,
+    set_attribute: Ptr,
+    clear_screen:
+        extern "efiapi" fn(*mut EfiSimpleTextOutputProtocol) -> EfiStatus,
+    set_cursor_position: Ptr,
+    enable_cursor: Ptr,
+    mode: Ptr,
+}
+
+/// Represents a key stroke read from the console.
+#[derive(Debug, Clone, Copy)]
+pub struct InputKey {
+    /// The `EFI_INPUT_KEY` structure returned by the firmware.
+    key: EfiInputKey,
+}
+
+impl InputKey {
+    /// Scan code of the key. It is 0 for printable characters.
+    pub fn scan_code(&self) -> u16 {
+        self.key.scan_code
+    }
+
+    /// Character of the key. It is `None` if the key does not produce a
+    /// printable character (e.g. arrow keys).
+    pub fn char(&self) -> Option<char> {
+        match self.key.unicode_char {
+            0 => None,
+            c => char::from_u32(c.into()),
+        }
+    }
+}
+
+/// Represents the console input device.
+pub struct TextInput<'a> {
+    /// The `EFI_SIMPLE_TEXT_INPUT_PROTOCOL` instance of the console.
+    protocol: *mut EfiSimpleTextInputProtocol,
+
+    /// The console cannot outlive the system table.
+    _system_table: PhantomData<&'a SystemTable>,
+}
+
+impl TextInput<'_> {
+    /// Creates a new `TextInput` from a given protocol pointer.
+    pub(crate) fn new(protocol: Ptr) -> Self {
+        TextInput {
+            protocol: protocol.0 as *mut EfiSimpleTextInputProtocol,
+            _system_table: PhantomData,
+        }
+    }
+
+    /// Returns the next key stroke, if any. It does not block.
+    pub fn read_key_stroke(&mut self) -> Result<Option<InputKey>, Error> {
+        let mut key = EfiInputKey::default();
+
+        // Call `EFI_SIMPLE_TEXT_INPUT_PROTOCOL.ReadKeyStroke()`.
+        let status = unsafe {
+            ((*self.protocol).read_key_stroke)(self.protocol, &mut key)
+        };
+
+        // `NotReady` means that no key stroke is available.
+        match status.into() {
+            Status::Success => {}
+            Status::Error(StatusError::NotReady) => return Ok(None),
+            Status::Warning(warn) => return Err(warn.into()),
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        Ok(Some(InputKey { key }))
+    }
+}
+
+/// Represents the console output device.
+pub struct TextOutput<'a> {
+    /// The `EFI_SIMPLE_TEXT_OUTPUT_PROTOCOL` instance of the console.
+    protocol: *mut EfiSimpleTextOutputProtocol,
+
+    /// The console cannot outlive the system table.
+    _system_table: PhantomData<&'a SystemTable>,
+}
+
+impl TextOutput<'_> {
+    /// Creates a new `TextOutput` from a given protocol pointer.
+    pub(crate) fn new(protocol: Ptr) -> Self {
+        TextOutput {
+            protocol: protocol.0 as *mut EfiSimpleTextOutputProtocol,
+            _system_table: PhantomData,
+        }
+    }
+
+    /// Writes the string `s` to the console. Line feeds are translated to
+    /// CR+LF, nul characters are skipped and characters that cannot be
+    /// represented in UCS-2 are replaced with U+FFFD.
+    pub fn output_string(&mut self, s: &str) -> Result<(), Error> {
+        let mut buf = [0u16; OUTPUT_CHUNK_LEN];
+        let mut len = 0;
+
+        for c in s.chars() {
+            // Leave room for CR+LF and the nul character.
+            if len + 3 > buf.len() {
+                buf[len] = 0;
+                self.output_ucs2(&buf)?;
+                len = 0;
+            }
+
+            match c {
+                '\0' => {}
+                '\n' => {
+                    buf[len] = b'\r' as u16;
+                    buf[len + 1] = b'\n' as u16;
+                    len += 2;
+                }
+                c if (c as u32) > 0xffff => {
+                    buf[len] = 0xfffd;
+                    len += 1;
+                }
+                c => {
+                    buf[len] = c as u16;
+                    len += 1;
+                }
+            }
+        }
+
+        if len > 0 {
+            buf[len] = 0;
+            self.output_ucs2(&buf)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the nul-terminated UCS-2 string `s` to the console.
+    fn output_ucs2(&mut self, s: &[u16]) -> Result<(), Error> {
+        // Call `EFI_SIMPLE_TEXT_OUTPUT_PROTOCOL.OutputString()`.
+        let status = unsafe {
+            ((*self.protocol).output_string)(self.protocol, s.as_ptr())
+        };
+
+        // Return with error in the case of warning and error status codes.
+        match status.into() {
+            Status::Success => {}
+            Status::Warning(warn) => return Err(warn.into()),
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        Ok(())
+    }
+
+    /// Clears the console and moves the cursor to the upper left corner.
+    pub fn clear_screen(&mut self) -> Result<(), Error> {
+        // Call `EFI_SIMPLE_TEXT_OUTPUT_PROTOCOL.ClearScreen()`.
+        let status = unsafe { ((*self.protocol).clear_screen)(self.protocol) };
+
+        // Return with error in the case of warning and error status codes.
+        match status.into() {
+            Status::Success => {}
+            Status::Warning(warn) => return Err(warn.into()),
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Write for TextOutput<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.output_string(s).or(Err(fmt::Error))
+    }
+}
@@ -0,0 +1,816 @@
+//! This module provides access to the Flattened Device Tree (FDT).
+//!
+//! Reference:
+//! - [Devicetree Specification](https://www.devicetree.org/specifications/)
+
+use crate::{Error, Ptr};
+use range::{Range, RangeSet};
+
+/// Magic number of the FDT header.
+const FDT_MAGIC: u32 = 0xd00dfeed;
+
+/// Size of the FDT header.
+const FDT_HEADER_SIZE: usize = 40;
+
+/// Last version of the FDT format the parser is compatible with.
+const FDT_COMPATIBLE_VERSION: u32 = 17;
+
+/// First version of the FDT format with the header layout expected by the
+/// parser.
+const FDT_MIN_VERSION: u32 = 16;
+
+/// Tokens of the structure block.
+const FDT_BEGIN_NODE: u32 = 0x1;
+const FDT_END_NODE: u32 = 0x2;
+const FDT_PROP: u32 = 0x3;
+const FDT_NOP: u32 = 0x4;
+
+/// Maximum depth of the nodes in the device tree.
+const FDT_MAX_DEPTH: usize = 16;
+
+/// Default values of the `#address-cells` and `#size-cells` properties.
+const FDT_DEFAULT_CELLS: (u32, u32) = (2, 1);
+
+/// Reads a big-endian `u32` at the offset `off` of `data`.
+fn be32(data: &[u8], off: usize) -> Option<u32> {
+    let bytes = data.get(off..off.checked_add(4)?)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Rounds `off` up to the next multiple of 4.
+fn align4(off: usize) -> usize {
+    (off + 3) & !3
+}
+
+/// Returns the nul-terminated string at the offset `off` of `data`, without
+/// the nul byte.
+fn cstr(data: &[u8], off: usize) -> Option<&[u8]> {
+    let data = data.get(off..)?;
+    let len = data.iter().position(|&b| b == 0)?;
+    Some(&data[..len])
+}
+
+/// Represents a Flattened Device Tree.
+#[derive(Debug, Clone, Copy)]
+pub struct Fdt<'a> {
+    /// Memory reservation block. It is not bounded by the header, so it
+    /// spans until the end of the FDT.
+    mem_rsvmap: &'a [u8],
+
+    /// Structure block.
+    struct_block: &'a [u8],
+
+    /// Strings block.
+    strings_block: &'a [u8],
+
+    /// Physical ID of the boot CPU.
+    boot_cpuid_phys: u32,
+}
+
+impl Fdt<'static> {
+    /// Creates a new `Fdt` from a given pointer.
+    ///
+    /// # Errors
+    ///
+    /// This function returns error if the pointer does not point to a valid
+    /// FDT.
+    ///
+    /// # Safety
+    ///
+    /// The `Fdt` structure is created using a pointer. Thus, this function is
+    /// considered unsafe.
+    pub unsafe fn new(fdt_ptr: Ptr) -> Result<Self, Error> {
+        // Read the magic number and the total size of the FDT before building
+        // the slice.
+        let hdr = core::slice::from_raw_parts(fdt_ptr.0 as *const u8, 8);
+        if be32(hdr, 0) != Some(FDT_MAGIC) {
+            return Err(Error::InvalidSignature);
+        }
+        let total_size = be32(hdr, 4).ok_or(Error::InvalidFdtData)? as usize;
+
+        let data =
+            core::slice::from_raw_parts(fdt_ptr.0 as *const u8, total_size);
+        Fdt::from_bytes(data)
+    }
+}
+
+impl<'a> Fdt<'a> {
+    /// Creates a new `Fdt` from a buffer containing the device tree blob.
+    ///
+    /// # Errors
+    ///
+    /// This function returns error if the buffer does not contain a valid
+    /// FDT.
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, Error> {
+        if data.len() < FDT_HEADER_SIZE {
+            return Err(Error::InvalidFdtData);
+        }
+
+        // Check FDT's magic number.
+        if be32(data, 0) != Some(FDT_MAGIC) {
+            return Err(Error::InvalidSignature);
+        }
+
+        // Parse header. Its size has already been checked.
+        let hdr = |off| be32(data, off).unwrap() as usize;
+        let total_size = hdr(4);
+        let off_dt_struct = hdr(8);
+        let off_dt_strings = hdr(12);
+        let off_mem_rsvmap = hdr(16);
+        let version = hdr(20) as u32;
+        let last_comp_version = hdr(24) as u32;
+        let boot_cpuid_phys = hdr(28) as u32;
+        let size_dt_strings = hdr(32);
+        let size_dt_struct = hdr(36);
+
+        // Check FDT's version.
+        if version < FDT_MIN_VERSION
+            || last_comp_version > FDT_COMPATIBLE_VERSION
+        {
+            return Err(Error::InvalidRevision);
+        }
+
+        // Check that the blocks are within the FDT.
+        let data = data.get(..total_size).ok_or(Error::InvalidFdtData)?;
+        let mem_rsvmap =
+            data.get(off_mem_rsvmap..).ok_or(Error::InvalidFdtData)?;
+        let struct_block = off_dt_struct
+            .checked_add(size_dt_struct)
+            .and_then(|end| data.get(off_dt_struct..end))
+            .ok_or(Error::InvalidFdtData)?;
+        let strings_block = off_dt_strings
+            .checked_add(size_dt_strings)
+            .and_then(|end| data.get(off_dt_strings..end))
+            .ok_or(Error::InvalidFdtData)?;
+
+        Ok(Fdt {
+            mem_rsvmap,
+            struct_block,
+            strings_block,
+            boot_cpuid_phys,
+        })
+    }
+
+    /// Physical ID of the boot CPU.
+    pub fn boot_cpuid_phys(&self) -> u32 {
+        self.boot_cpuid_phys
+    }
+
+    /// Returns an iterator over the nodes of the device tree in depth-first
+    /// order.
+    pub fn nodes(&self) -> Nodes<'a> {
+        Nodes {
+            fdt: *self,
+            off: 0,
+            depth: 0,
+            cells: [FDT_DEFAULT_CELLS; FDT_MAX_DEPTH],
+            done: false,
+        }
+    }
+
+    /// Returns the first node that is compatible with `compat`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Error::NotFound` if there is no compatible
+    /// node.
+    pub fn find_compatible(&self, compat: &[u8]) -> Result<Node<'a>, Error> {
+        self.nodes()
+            .find(|node| node.is_compatible(compat))
+            .ok_or(Error::NotFound)
+    }
+
+    /// Returns an iterator over the `(address, size)` pairs of the memory
+    /// reservation block. These regions must not be used by the kernel.
+    pub fn reserved_memory(&self) -> impl Iterator<Item = (u64, u64)> + 'a {
+        // Each entry is a pair of big-endian `u64`. The list is terminated by
+        // an entry with address and size 0.
+        self.mem_rsvmap
+            .chunks_exact(16)
+            .map(|entry| {
+                let be64 = |off| {
+                    let hi = be32(entry, off).unwrap() as u64;
+                    let lo = be32(entry, off + 4).unwrap() as u64;
+                    hi << 32 | lo
+                };
+                (be64(0), be64(8))
+            })
+            .take_while(|&entry| entry != (0, 0))
+    }
+
+    /// Returns a `RangeSet` with the memory described by the `memory` nodes,
+    /// excluding the regions of the memory reservation block.
+    pub fn available_memory(&self) -> Result<RangeSet, Error> {
+        let mut ret = RangeSet::new();
+
+        let memory_nodes = self.nodes().filter(|node| {
+            let device_type = match node.property(b"device_type") {
+                Some(prop) => prop.strings().next(),
+                None => None,
+            };
+            node.depth() == 1 && device_type == Some(&b"memory"[..])
+        });
+        for node in memory_nodes {
+            for (address, size) in node.reg() {
+                if let Some(range) = reg_range(address, size)? {
+                    ret.insert(range)?;
+                }
+            }
+        }
+
+        for (address, size) in self.reserved_memory() {
+            if let Some(range) = reg_range(address, size)? {
+                ret.remove(range)?;
+            }
+        }
+
+        Ok(ret)
+    }
+
+    /// Returns the `/chosen` node, which contains the parameters passed by
+    /// the firmware.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Error::NotFound` if the device tree does not
+    /// contain a `/chosen` node.
+    pub fn chosen(&self) -> Result<Chosen<'a>, Error> {
+        self.nodes()
+            .find(|node| node.depth() == 1 && node.name() == b"chosen")
+            .map(|node| Chosen { node })
+            .ok_or(Error::NotFound)
+    }
+}
+
+/// Returns the `Range` of the region with the address `address` and the size
+/// `size`, or `None` if the region is empty.
+fn reg_range(address: u64, size: u64) -> Result<Option<Range>, Error> {
+    if size == 0 {
+        return Ok(None);
+    }
+    let end = address.checked_add(size - 1).ok_or(Error::InvalidFdtData)?;
+    Ok(Some(Range::new(address, end)?))
+}
+
+/// Represents the `/chosen` node of the device tree.
+#[derive(Debug, Clone, Copy)]
+pub struct Chosen<'a> {
+    node: Node<'a>,
+}
+
+impl<'a> Chosen<'a> {
+    /// Returns the `/chosen` node.
+    pub fn node(&self) -> Node<'a> {
+        self.node
+    }
+
+    /// Command line of the kernel, without the nul byte.
+    pub fn bootargs(&self) -> Option<&'a [u8]> {
+        self.string(b"bootargs")
+    }
+
+    /// Path of the node of the device used for console output.
+    pub fn stdout_path(&self) -> Option<&'a [u8]> {
+        self.string(b"stdout-path")
+    }
+
+    /// Returns the memory region of the initial ramdisk, if any.
+    pub fn initrd(&self) -> Result<Option<Range>, Error> {
+        // The addresses can be 32-bit or 64-bit wide.
+        let addr = |name| {
+            self.node.property(name).and_then(|prop| {
+                prop.as_u64().or_else(|| prop.as_u32().map(u64::from))
+            })
+        };
+
+        match (addr(b"linux,initrd-start"), addr(b"linux,initrd-end")) {
+            (Some(start), Some(end)) if end > start => {
+                Ok(Some(Range::new(start, end - 1)?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the first string of the property with the name `name`, if any.
+    fn string(&self, name: &[u8]) -> Option<&'a [u8]> {
+        self.node.property(name)?.strings().next()
+    }
+}
+
+/// Iterator over the nodes of a device tree.
+pub struct Nodes<'a> {
+    /// Device tree.
+    fdt: Fdt<'a>,
+
+    /// Offset of the next token in the structure block.
+    off: usize,
+
+    /// Depth of the next node.
+    depth: usize,
+
+    /// `#address-cells` and `#size-cells` of the nodes being visited, indexed
+    /// by depth.
+    cells: [(u32, u32); FDT_MAX_DEPTH],
+
+    /// The end of the structure block has been reached or it is malformed.
+    done: bool,
+}
+
+impl<'a> Nodes<'a> {
+    /// Returns the next node, or `None` if the end of the structure block has
+    /// been reached or it is malformed.
+    fn next_node(&mut self) -> Option<Node<'a>> {
+        let data = self.fdt.struct_block;
+
+        loop {
+            let token = be32(data, self.off)?;
+            self.off += 4;
+
+            match token {
+                FDT_BEGIN_NODE => {
+                    let name = cstr(data, self.off)?;
+                    self.off = align4(self.off + name.len() + 1);
+
+                    if self.depth >= FDT_MAX_DEPTH {
+                        return None;
+                    }
+
+                    let parent_cells = if self.depth == 0 {
+                        FDT_DEFAULT_CELLS
+                    } else {
+                        self.cells[self.depth - 1]
+                    };
+
+                    let node = Node {
+                        fdt: self.fdt,
+                        name,
+                        depth: self.depth,
+                        props_off: self.off,
+                        address_cells: parent_cells.0,
+                        size_cells: parent_cells.1,
+                    };
+
+                    // Record the cells of this node, which are used to parse
+                    // the `reg` property of its children.
+                    let address_cells = node
+                        .property(b"#address-cells")
+                        .and_then(|prop| prop.as_u32())
+                        .unwrap_or(FDT_DEFAULT_CELLS.0);
+                    let size_cells = node
+                        .property(b"#size-cells")
+                        .and_then(|prop| prop.as_u32())
+                        .unwrap_or(FDT_DEFAULT_CELLS.1);
+                    self.cells[self.depth] = (address_cells, size_cells);
+
+                    self.depth += 1;
+                    return Some(node);
+                }
+                FDT_END_NODE => {
+                    self.depth = self.depth.checked_sub(1)?;
+                }
+                FDT_PROP => {
+                    let len = be32(data, self.off)? as usize;
+                    self.off = align4(self.off + 8 + len);
+                }
+                FDT_NOP => {}
+                _ => return None,
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for Nodes<'a> {
+    type Item = Node<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let node = self.next_node();
+        if node.is_none() {
+            self.done = true;
+        }
+        node
+    }
+}
+
+/// Represents a node of the device tree.
+#[derive(Debug, Clone, Copy)]
+pub struct Node<'a> {
+    /// Device tree the node belongs to.
+    fdt: Fdt<'a>,
+
+    /// Name of the node, including the unit address.
+    name: &'a [u8],
+
+    /// Depth of the node. The root node has depth 0.
+    depth: usize,
+
+    /// Offset of the properties of the node in the structure block.
+    props_off: usize,
+
+    /// `#address-cells` of the parent node.
+    address_cells: u32,
+
+    /// `#size-cells` of the parent node.
+    size_cells: u32,
+}
+
+impl<'a> Node<'a> {
+    /// Name of the node, including the unit address (e.g.
+    /// `serial@10000000`). The name of the root node is empty.
+    pub fn name(&self) -> &'a [u8] {
+        self.name
+    }
+
+    /// Depth of the node. The root node has depth 0.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns an iterator over the properties of the node.
+    pub fn properties(&self) -> Properties<'a> {
+        Properties {
+            fdt: self.fdt,
+            off: self.props_off,
+        }
+    }
+
+    /// Returns the property with the name `name`, if any.
+    pub fn property(&self, name: &[u8]) -> Option<Property<'a>> {
+        self.properties().find(|prop| prop.name() == name)
+    }
+
+    /// Returns `true` if the `compatible` property of the node contains
+    /// `compat`.
+    pub fn is_compatible(&self, compat: &[u8]) -> bool {
+        match self.property(b"compatible") {
+            Some(prop) => prop.strings().any(|s| s == compat),
+            None => false,
+        }
+    }
+
+    /// Returns an iterator over the `(address, size)` pairs of the `reg`
+    /// property of the node. The iterator is empty if the property does not
+    /// exist or the number of cells is not supported.
+    pub fn reg(&self) -> Reg<'a> {
+        let value = self.property(b"reg").map_or(&[][..], |p| p.value());
+        Reg {
+            value,
+            address_cells: self.address_cells as usize,
+            size_cells: self.size_cells as usize,
+        }
+    }
+
+    /// Returns an iterator over the cells of the `interrupts` property of
+    /// the node. Their meaning depends on the interrupt parent.
+    pub fn interrupts(&self) -> Cells<'a> {
+        let value =
+            self.property(b"interrupts").map_or(&[][..], |p| p.value());
+        Cells { value }
+    }
+}
+
+/// Iterator over the properties of a node.
+pub struct Properties<'a> {
+    /// Device tree the node belongs to.
+    fdt: Fdt<'a>,
+
+    /// Offset of the next token in the structure block.
+    off: usize,
+}
+
+impl<'a> Iterator for Properties<'a> {
+    type Item = Property<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let data = self.fdt.struct_block;
+
+        loop {
+            let token = be32(data, self.off)?;
+
+            match token {
+                FDT_PROP => {
+                    let len = be32(data, self.off + 4)? as usize;
+                    let name_off = be32(data, self.off + 8)? as usize;
+                    let value_off = self.off + 12;
+
+                    let name = cstr(self.fdt.strings_block, name_off)?;
+                    let value = data.get(value_off..value_off + len)?;
+
+                    self.off = align4(value_off + len);
+                    return Some(Property { name, value });
+                }
+                FDT_NOP => self.off += 4,
+                // Properties precede any subnode, so any other token marks
+                // the end of the properties of the node.
+                _ => return None,
+            }
+        }
+    }
+}
+
+/// Represents a property of a node.
+#[derive(Debug, Clone, Copy)]
+pub struct Property<'a> {
+    name: &'a [u8],
+    value: &'a [u8],
+}
+
+impl<'a> Property<'a> {
+    /// Name of the property.
+    pub fn name(&self) -> &'a [u8] {
+        self.name
+    }
+
+    /// Raw value of the property.
+    pub fn value(&self) -> &'a [u8] {
+        self.value
+    }
+
+    /// Returns the value of the property as a `u32`, or `None` if its size
+    /// does not match.
+    pub fn as_u32(&self) -> Option<u32> {
+        if self.value.len() != 4 {
+            return None;
+        }
+        be32(self.value, 0)
+    }
+
+    /// Returns the value of the property as a `u64`, or `None` if its size
+    /// does not match.
+    pub fn as_u64(&self) -> Option<u64> {
+        if self.value.len() != 8 {
+            return None;
+        }
+        let hi = be32(self.value, 0)? as u64;
+        let lo = be32(self.value, 4)? as u64;
+        Some(hi << 32 | lo)
+    }
+
+    /// Returns an iterator over the nul-terminated strings of the value of
+    /// the property.
+    pub fn strings(&self) -> impl Iterator<Item = &'a [u8]> {
+        let value = self.value.strip_suffix(&[0]).unwrap_or(self.value);
+        value.split(|&b| b == 0)
+    }
+}
+
+/// Iterator over the `(address, size)` pairs of a `reg` property.
+pub struct Reg<'a> {
+    value: &'a [u8],
+    address_cells: usize,
+    size_cells: usize,
+}
+
+impl Iterator for Reg<'_> {
+    type Item = (u64, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Only values that fit in a `u64` are supported.
+        let entry_len = (self.address_cells + self.size_cells) * 4;
+        if self.address_cells > 2 || self.size_cells > 2 || entry_len == 0 {
+            return None;
+        }
+
+        let read_cells = |data: &[u8], n: usize| {
+            (0..n).try_fold(0u64, |acc, i| {
+                Some(acc << 32 | be32(data, i * 4)? as u64)
+            })
+        };
+
+        let address = read_cells(self.value, self.address_cells)?;
+        let size = read_cells(
+            self.value.get(self.address_cells * 4..)?,
+            self.size_cells,
+        )?;
+
+        self.value = self.value.get(entry_len..)?;
+
+        Some((address, size))
+    }
+}
+
+/// Iterator over the `u32` cells of a property.
+pub struct Cells<'a> {
+    value: &'a [u8],
+}
+
+impl Iterator for Cells<'_> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let cell = be32(self.value, 0)?;
+        self.value = &self.value[4..];
+        Some(cell)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+
+    /// End token of the structure block.
+    const FDT_END: u32 = 0x9;
+
+    /// Builds the structure and strings blocks of a device tree.
+    #[derive(Default)]
+    struct Builder {
+        struct_block: Vec<u8>,
+        strings_block: Vec<u8>,
+    }
+
+    impl Builder {
+        fn token(&mut self, token: u32) -> &mut Self {
+            self.struct_block.extend_from_slice(&token.to_be_bytes());
+            self
+        }
+
+        fn begin(&mut self, name: &str) -> &mut Self {
+            self.token(FDT_BEGIN_NODE);
+            self.struct_block.extend_from_slice(name.as_bytes());
+            self.struct_block.push(0);
+            self.struct_block.resize(align4(self.struct_block.len()), 0);
+            self
+        }
+
+        fn prop(&mut self, name: &str, value: &[u8]) -> &mut Self {
+            let name_off = self.strings_block.len() as u32;
+            self.strings_block.extend_from_slice(name.as_bytes());
+            self.strings_block.push(0);
+
+            self.token(FDT_PROP);
+            self.token(value.len() as u32);
+            self.token(name_off);
+            self.struct_block.extend_from_slice(value);
+            self.struct_block.resize(align4(self.struct_block.len()), 0);
+            self
+        }
+
+        fn end(&mut self) -> &mut Self {
+            self.token(FDT_END_NODE)
+        }
+
+        /// Returns the device tree blob, with a memory reservation at
+        /// 0x1000 of size 0x1000.
+        fn build(&mut self) -> Vec<u8> {
+            self.token(FDT_END);
+
+            let mut mem_rsvmap = Vec::new();
+            for val in &[0x1000u64, 0x1000, 0, 0] {
+                mem_rsvmap.extend_from_slice(&val.to_be_bytes());
+            }
+
+            let off_mem_rsvmap = FDT_HEADER_SIZE;
+            let off_dt_struct = off_mem_rsvmap + mem_rsvmap.len();
+            let off_dt_strings = off_dt_struct + self.struct_block.len();
+            let total_size = off_dt_strings + self.strings_block.len();
+
+            let mut blob = Vec::new();
+            for val in &[
+                FDT_MAGIC,
+                total_size as u32,
+                off_dt_struct as u32,
+                off_dt_strings as u32,
+                off_mem_rsvmap as u32,
+                17,
+                16,
+                1,
+                self.strings_block.len() as u32,
+                self.struct_block.len() as u32,
+            ] {
+                blob.extend_from_slice(&val.to_be_bytes());
+            }
+            blob.extend_from_slice(&mem_rsvmap);
+            blob.extend_from_slice(&self.struct_block);
+            blob.extend_from_slice(&self.strings_block);
+            blob
+        }
+    }
+
+    /// Returns a device tree with a memory node and a `/chosen` node.
+    fn test_fdt() -> Vec<u8> {
+        Builder::default()
+            .begin("")
+            .prop("#address-cells", &2u32.to_be_bytes())
+            .prop("#size-cells", &1u32.to_be_bytes())
+            .begin("memory@0")
+            .prop("device_type", b"memory\0")
+            .prop("reg", &[0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0])
+            .end()
+            .begin("chosen")
+            .prop("bootargs", b"console=ttyS0\0")
+            .end()
+            .end()
+            .build()
+    }
+
+    /// Overwrites the header field at the offset `off` of `blob`.
+    fn set_header(blob: &mut [u8], off: usize, val: u32) {
+        blob[off..off + 4].copy_from_slice(&val.to_be_bytes());
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let blob = test_fdt();
+        let fdt = Fdt::from_bytes(&blob).unwrap();
+        assert_eq!(fdt.boot_cpuid_phys(), 1);
+
+        let names: Vec<_> = fdt.nodes().map(|node| node.name()).collect();
+        assert_eq!(names, [&b""[..], b"memory@0", b"chosen"]);
+
+        let memory = fdt.nodes().nth(1).unwrap();
+        assert_eq!(memory.depth(), 1);
+        assert_eq!(memory.reg().collect::<Vec<_>>(), [(0, 0x10000)]);
+
+        let reserved: Vec<_> = fdt.reserved_memory().collect();
+        assert_eq!(reserved, [(0x1000, 0x1000)]);
+
+        let chosen = fdt.chosen().unwrap();
+        assert_eq!(chosen.bootargs(), Some(&b"console=ttyS0"[..]));
+        assert_eq!(chosen.stdout_path(), None);
+    }
+
+    #[test]
+    fn test_from_bytes_bad_magic() {
+        let mut blob = test_fdt();
+        set_header(&mut blob, 0, 0xfeedd00d);
+        assert!(matches!(
+            Fdt::from_bytes(&blob),
+            Err(Error::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn test_from_bytes_truncated_header() {
+        let blob = test_fdt();
+        assert!(matches!(
+            Fdt::from_bytes(&blob[..FDT_HEADER_SIZE - 1]),
+            Err(Error::InvalidFdtData)
+        ));
+    }
+
+    #[test]
+    fn test_from_bytes_truncated_blob() {
+        let blob = test_fdt();
+        assert!(matches!(
+            Fdt::from_bytes(&blob[..blob.len() - 1]),
+            Err(Error::InvalidFdtData)
+        ));
+    }
+
+    #[test]
+    fn test_from_bytes_out_of_bounds() {
+        let len = test_fdt().len() as u32;
+
+        // Offset of the structure block past the end of the blob.
+        let mut blob = test_fdt();
+        set_header(&mut blob, 8, len);
+        assert!(matches!(Fdt::from_bytes(&blob), Err(Error::InvalidFdtData)));
+
+        // Strings block overflowing the blob.
+        let mut blob = test_fdt();
+        set_header(&mut blob, 32, len);
+        assert!(matches!(Fdt::from_bytes(&blob), Err(Error::InvalidFdtData)));
+
+        // Structure block larger than the blob.
+        let mut blob = test_fdt();
+        set_header(&mut blob, 8, u32::MAX);
+        set_header(&mut blob, 36, u32::MAX);
+        assert!(matches!(Fdt::from_bytes(&blob), Err(Error::InvalidFdtData)));
+    }
+
+    #[test]
+    fn test_from_bytes_version() {
+        // Versions older than 16 use a different header layout.
+        let mut blob = test_fdt();
+        set_header(&mut blob, 20, 15);
+        assert!(matches!(
+            Fdt::from_bytes(&blob),
+            Err(Error::InvalidRevision)
+        ));
+
+        // Not backwards compatible with the supported version.
+        let mut blob = test_fdt();
+        set_header(&mut blob, 24, FDT_COMPATIBLE_VERSION + 1);
+        assert!(matches!(
+            Fdt::from_bytes(&blob),
+            Err(Error::InvalidRevision)
+        ));
+    }
+
+    #[test]
+    fn test_nodes_truncated_struct_block() {
+        let mut blob = test_fdt();
+        // Cut the structure block in the middle of the name of the
+        // `memory@0` node.
+        set_header(&mut blob, 36, 48);
+        let fdt = Fdt::from_bytes(&blob).unwrap();
+        assert_eq!(fdt.nodes().count(), 1);
+    }
+}
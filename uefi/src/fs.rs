@@ -0,0 +1,394 @@
+//! This module provides access to the files of the volume the UEFI image was
+//! loaded from (e.g. the EFI System Partition), using the Simple File System
+//! protocol.
+//!
+//! The protocols are owned by the firmware. Thus, the files cannot be used
+//! after exiting the boot services.
+
+use core::marker::PhantomData;
+
+use crate::ucs2::{self, Ucs2Str};
+use crate::{
+    image, BootServices, EfiGuid, EfiStatus, EfiTime, Error, Handle, Ptr,
+    Status,
+};
+
+/// The EFI GUID of the Simple File System protocol.
+const EFI_SIMPLE_FILE_SYSTEM_PROTOCOL_GUID: EfiGuid = EfiGuid::new(
+    0x964e5b22,
+    0x6459,
+    0x11d2,
+    [0x8e, 0x39, 0x00, 0xa0, 0xc9, 0x69, 0x72, 0x3b],
+);
+
+/// The EFI GUID of the `EFI_FILE_INFO` information type.
+const EFI_FILE_INFO_ID: EfiGuid = EfiGuid::new(
+    0x09576e92,
+    0x6d3f,
+    0x11d2,
+    [0x8e, 0x39, 0x00, 0xa0, 0xc9, 0x69, 0x72, 0x3b],
+);
+
+/// Open mode used to open files for reading.
+const EFI_FILE_MODE_READ: u64 = 0x1;
+
+/// File attribute of the directories.
+const EFI_FILE_DIRECTORY: u64 = 0x10;
+
+/// Maximum length of a path or a file name in UCS-2 characters, including
+/// the terminating nul character.
+const FILE_NAME_LEN: usize = 256;
+
+/// The `EFI_SIMPLE_FILE_SYSTEM_PROTOCOL` type of the UEFI specification.
+#[repr(C)]
+struct EfiSimpleFileSystemProtocol {
+    revision: u64,
+    open_volume: extern "efiapi" fn(
+        *mut EfiSimpleFileSystemProtocol,
+        *mut *mut EfiFileProtocol,
+    ) -> EfiStatus,
+}
+
+/// The `EFI_FILE_PROTOCOL` type of the UEFI specification.
+#[repr(C)]
+struct EfiFileProtocol {
+    revision: u64,
+    open: extern "efiapi" fn(
+        *mut EfiFileProtocol,
+        *mut *mut EfiFileProtocol,
+        *const u16,
+        u64,
+        u64,
+    ) -> EfiStatus,
+    close: extern "efiapi" fn(*mut EfiFileProtocol) -> EfiStatus,
+    delete: Ptr,
+    read: extern "efiapi" fn(
+        *mut EfiFileProtocol,
+        *mut usize,
+        *mut u8,
+    ) -> EfiStatus,
+    write: Ptr,
+    get_position: Ptr,
+    set_position: extern "efiapi" fn(*mut EfiFileProtocol, u64) -> EfiStatus,
+    get_info: extern "efiapi" fn(
+        *mut EfiFileProtocol,
+        *const EfiGuid,
+        *mut usize,
+        *mut u8,
+    ) -> EfiStatus,
+    set_info: Ptr,
+    flush: Ptr,
+}
+
+/// The `EFI_FILE_INFO` type of the UEFI specification, without the file name
+/// that follows it.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct EfiFileInfo {
+    size: u64,
+    file_size: u64,
+    physical_size: u64,
+    create_time: EfiTime,
+    last_access_time: EfiTime,
+    modification_time: EfiTime,
+    attribute: u64,
+}
+
+/// Size of the buffers used to retrieve an `EFI_FILE_INFO` structure. It is
+/// big enough to hold the longest file name.
+const FILE_INFO_BUF_SIZE: usize =
+    core::mem::size_of::<EfiFileInfo>() + FILE_NAME_LEN * 2;
+
+/// Aligned buffer used to retrieve an `EFI_FILE_INFO` structure.
+#[repr(C, align(8))]
+struct FileInfoBuf([u8; FILE_INFO_BUF_SIZE]);
+
+/// Opens the root directory of the volume the image `image_handle` was loaded
+/// from.
+pub fn open_volume(
+    boot_services: &BootServices,
+    image_handle: Handle,
+) -> Result<File<'_>, Error> {
+    // Get the device the image was loaded from.
+    let loaded_image = image::loaded_image(boot_services, image_handle)?;
+
+    // Get the file system of the device.
+    let simple_file_system = boot_services.handle_protocol(
+        loaded_image.device_handle(),
+        &EFI_SIMPLE_FILE_SYSTEM_PROTOCOL_GUID,
+    )?;
+    let simple_file_system =
+        simple_file_system.0 as *mut EfiSimpleFileSystemProtocol;
+
+    // Call `EFI_SIMPLE_FILE_SYSTEM_PROTOCOL.OpenVolume()`.
+    let mut root = core::ptr::null_mut();
+    let status = unsafe {
+        ((*simple_file_system).open_volume)(simple_file_system, &mut root)
+    };
+
+    // Return with error in the case of warning and error status codes.
+    match status.into() {
+        Status::Success => {}
+        Status::Warning(warn) => return Err(warn.into()),
+        Status::Error(err) => return Err(err.into()),
+    }
+
+    Ok(File {
+        protocol: root,
+        _boot_services: PhantomData,
+    })
+}
+
+/// Represents an open file or directory. It is closed when dropped.
+pub struct File<'a> {
+    /// The `EFI_FILE_PROTOCOL` instance of the file.
+    protocol: *mut EfiFileProtocol,
+
+    /// The file cannot outlive the boot services.
+    _boot_services: PhantomData<&'a BootServices>,
+}
+
+impl<'a> File<'a> {
+    /// Opens the file or directory `path` for reading. `path` is relative to
+    /// this directory, unless it starts with a separator. Both `/` and `\`
+    /// are accepted as separators.
+    pub fn open(&self, path: &str) -> Result<File<'a>, Error> {
+        let mut name = [0u16; FILE_NAME_LEN];
+        ucs2::encode(path, &mut name)?;
+        for c in name.iter_mut().filter(|c| **c == b'/' as u16) {
+            *c = b'\\' as u16;
+        }
+
+        // Call `EFI_FILE_PROTOCOL.Open()`.
+        let mut file = core::ptr::null_mut();
+        let status = unsafe {
+            ((*self.protocol).open)(
+                self.protocol,
+                &mut file,
+                name.as_ptr(),
+                EFI_FILE_MODE_READ,
+                0,
+            )
+        };
+
+        // Return with error in the case of warning and error status codes.
+        match status.into() {
+            Status::Success => {}
+            Status::Warning(warn) => return Err(warn.into()),
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        Ok(File {
+            protocol: file,
+            _boot_services: PhantomData,
+        })
+    }
+
+    /// Reads data from the current position of the file into `buf`. It
+    /// returns the number of bytes read, which is 0 at the end of the file.
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let mut size = buf.len();
+
+        // Call `EFI_FILE_PROTOCOL.Read()`.
+        let status = unsafe {
+            ((*self.protocol).read)(self.protocol, &mut size, buf.as_mut_ptr())
+        };
+
+        // Return with error in the case of warning and error status codes.
+        match status.into() {
+            Status::Success => {}
+            Status::Warning(warn) => return Err(warn.into()),
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        Ok(size)
+    }
+
+    /// Reads data from the current position until the end of the file into
+    /// `buf`. It returns the number of bytes read.
+    ///
+    /// # Errors
+    ///
+    /// If the remaining data does not fit in `buf`, the function returns
+    /// `Error::BufferTooSmall`.
+    pub fn read_to_end(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let mut total = 0;
+        loop {
+            // Check if there is data left once the buffer is full.
+            if total == buf.len() {
+                if self.read(&mut [0u8; 1])? != 0 {
+                    return Err(Error::BufferTooSmall);
+                }
+                return Ok(total);
+            }
+
+            let n = self.read(&mut buf[total..])?;
+            if n == 0 {
+                return Ok(total);
+            }
+            total += n;
+        }
+    }
+
+    /// Sets the current position of the file. Directories can only be
+    /// rewound to the first entry with the position 0.
+    pub fn set_position(&mut self, position: u64) -> Result<(), Error> {
+        // Call `EFI_FILE_PROTOCOL.SetPosition()`.
+        let status = unsafe {
+            ((*self.protocol).set_position)(self.protocol, position)
+        };
+
+        // Return with error in the case of warning and error status codes.
+        match status.into() {
+            Status::Success => {}
+            Status::Warning(warn) => return Err(warn.into()),
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        Ok(())
+    }
+
+    /// Returns the information of the file.
+    pub fn info(&self) -> Result<FileInfo, Error> {
+        let mut buf = FileInfoBuf([0u8; FILE_INFO_BUF_SIZE]);
+        let mut size = buf.0.len();
+
+        // Call `EFI_FILE_PROTOCOL.GetInfo()`.
+        let status = unsafe {
+            ((*self.protocol).get_info)(
+                self.protocol,
+                &EFI_FILE_INFO_ID,
+                &mut size,
+                buf.0.as_mut_ptr(),
+            )
+        };
+
+        // Return with error in the case of warning and error status codes.
+        match status.into() {
+            Status::Success => {}
+            Status::Warning(warn) => return Err(warn.into()),
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        FileInfo::from_bytes(&buf.0[..size])
+    }
+
+    /// Returns an iterator over the entries of the directory, excluding `.`
+    /// and `..`. The directory is rewound first.
+    pub fn entries(&mut self) -> Result<Entries<'_, 'a>, Error> {
+        self.set_position(0)?;
+        Ok(Entries {
+            dir: self,
+            done: false,
+        })
+    }
+}
+
+impl Drop for File<'_> {
+    fn drop(&mut self) {
+        // Call `EFI_FILE_PROTOCOL.Close()`. It always succeeds.
+        unsafe { ((*self.protocol).close)(self.protocol) };
+    }
+}
+
+/// Represents the information of a file or directory.
+#[derive(Debug, Clone, Copy)]
+pub struct FileInfo {
+    file_size: u64,
+    attribute: u64,
+    name: [u16; FILE_NAME_LEN],
+    name_len: usize,
+}
+
+impl FileInfo {
+    /// Parses an `EFI_FILE_INFO` structure followed by the file name.
+    fn from_bytes(buf: &[u8]) -> Result<FileInfo, Error> {
+        const HEADER_SIZE: usize = core::mem::size_of::<EfiFileInfo>();
+
+        if buf.len() < HEADER_SIZE {
+            return Err(Error::InvalidFileData);
+        }
+        let info = unsafe {
+            core::ptr::read_unaligned(buf.as_ptr() as *const EfiFileInfo)
+        };
+        if info.size as usize > buf.len() {
+            return Err(Error::InvalidFileData);
+        }
+
+        // Copy the nul-terminated file name.
+        let mut name = [0u16; FILE_NAME_LEN];
+        let mut name_len = 0;
+        for c in buf[HEADER_SIZE..info.size as usize].chunks_exact(2) {
+            let c = u16::from_le_bytes([c[0], c[1]]);
+            if c == 0 {
+                break;
+            }
+            if name_len >= FILE_NAME_LEN - 1 {
+                return Err(Error::InvalidFileData);
+            }
+            name[name_len] = c;
+            name_len += 1;
+        }
+
+        Ok(FileInfo {
+            file_size: info.file_size,
+            attribute: info.attribute,
+            name,
+            name_len,
+        })
+    }
+
+    /// Size of the file in bytes.
+    pub fn size(&self) -> u64 {
+        self.file_size
+    }
+
+    /// Returns `true` if the entry is a directory.
+    pub fn is_directory(&self) -> bool {
+        self.attribute & EFI_FILE_DIRECTORY != 0
+    }
+
+    /// Name of the file.
+    pub fn name(&self) -> Ucs2Str<'_> {
+        Ucs2Str::from_slice(&self.name[..self.name_len])
+    }
+}
+
+/// Iterator over the entries of a directory.
+pub struct Entries<'d, 'a> {
+    dir: &'d mut File<'a>,
+
+    /// The end of the directory has been reached or an error occurred.
+    done: bool,
+}
+
+impl Iterator for Entries<'_, '_> {
+    type Item = Result<FileInfo, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            // Reading a directory returns the `EFI_FILE_INFO` of the next
+            // entry, or no data at the end of the directory.
+            let mut buf = FileInfoBuf([0u8; FILE_INFO_BUF_SIZE]);
+            let info = match self.dir.read(&mut buf.0) {
+                Ok(0) => {
+                    self.done = true;
+                    return None;
+                }
+                Ok(n) => FileInfo::from_bytes(&buf.0[..n]),
+                Err(err) => Err(err),
+            };
+
+            match info {
+                Ok(info) if info.name() == "." || info.name() == ".." => {}
+                Ok(info) => return Some(Ok(info)),
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+
+        None
+    }
+}
@@ -0,0 +1,198 @@
+//! This module provides access to the framebuffer set up by the firmware,
+//! using the Graphics Output protocol.
+//!
+//! The protocol is owned by the firmware. Thus, it cannot be used after
+//! exiting the boot services. However, the framebuffer of the current mode
+//! stays valid.
+
+use core::marker::PhantomData;
+
+use crate::{BootServices, EfiGuid, Error, Protocol, Ptr};
+
+/// The `EFI_GRAPHICS_OUTPUT_PROTOCOL` type of the UEFI specification.
+#[repr(C)]
+struct EfiGraphicsOutputProtocol {
+    query_mode: Ptr,
+    set_mode: Ptr,
+    blt: Ptr,
+    mode: *const EfiGraphicsOutputProtocolMode,
+}
+
+/// The `EFI_GRAPHICS_OUTPUT_PROTOCOL_MODE` type of the UEFI specification.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct EfiGraphicsOutputProtocolMode {
+    max_mode: u32,
+    mode: u32,
+    info: *const EfiGraphicsOutputModeInformation,
+    size_of_info: usize,
+    frame_buffer_base: u64,
+    frame_buffer_size: usize,
+}
+
+/// The `EFI_GRAPHICS_OUTPUT_MODE_INFORMATION` type of the UEFI
+/// specification.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct EfiGraphicsOutputModeInformation {
+    version: u32,
+    horizontal_resolution: u32,
+    vertical_resolution: u32,
+    pixel_format: u32,
+    pixel_information: [u32; 4],
+    pixels_per_scan_line: u32,
+}
+
+/// Layout of the pixels of the framebuffer. It is equivalent to the
+/// `EFI_GRAPHICS_PIXEL_FORMAT` type of the UEFI specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// 32-bit pixels. Byte 0 is red, byte 1 is green, byte 2 is blue and
+    /// byte 3 is reserved.
+    Rgb,
+
+    /// 32-bit pixels. Byte 0 is blue, byte 1 is green, byte 2 is red and
+    /// byte 3 is reserved.
+    Bgr,
+
+    /// The layout of the pixels is described by bit masks.
+    Bitmask,
+
+    /// There is no framebuffer. Only `Blt()` can be used.
+    BltOnly,
+
+    /// Unknown pixel format.
+    Unknown(u32),
+}
+
+impl From<u32> for PixelFormat {
+    fn from(format: u32) -> Self {
+        match format {
+            0 => PixelFormat::Rgb,
+            1 => PixelFormat::Bgr,
+            2 => PixelFormat::Bitmask,
+            3 => PixelFormat::BltOnly,
+            format => PixelFormat::Unknown(format),
+        }
+    }
+}
+
+/// Represents the current graphics mode.
+#[derive(Debug, Clone, Copy)]
+pub struct Mode {
+    /// Mode number.
+    number: u32,
+
+    /// Physical address of the framebuffer.
+    framebuffer_base: u64,
+
+    /// Size of the framebuffer in bytes.
+    framebuffer_size: u64,
+
+    /// Horizontal resolution in pixels.
+    width: u32,
+
+    /// Vertical resolution in pixels.
+    height: u32,
+
+    /// Number of pixels per scan line. It can be bigger than `width`.
+    stride: u32,
+
+    /// Layout of the pixels.
+    pixel_format: PixelFormat,
+}
+
+impl Mode {
+    /// Mode number.
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    /// Physical address of the framebuffer.
+    pub fn framebuffer_base(&self) -> u64 {
+        self.framebuffer_base
+    }
+
+    /// Size of the framebuffer in bytes.
+    pub fn framebuffer_size(&self) -> u64 {
+        self.framebuffer_size
+    }
+
+    /// Horizontal resolution in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Vertical resolution in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Number of pixels per scan line.
+    pub fn stride(&self) -> u32 {
+        self.stride
+    }
+
+    /// Layout of the pixels.
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.pixel_format
+    }
+}
+
+/// Represents the Graphics Output protocol. It is retrieved with
+/// `BootServices::locate_protocol`.
+pub struct GraphicsOutput<'a> {
+    /// The `EFI_GRAPHICS_OUTPUT_PROTOCOL` instance.
+    protocol: *mut EfiGraphicsOutputProtocol,
+
+    /// The protocol cannot outlive the boot services.
+    _boot_services: PhantomData<&'a BootServices>,
+}
+
+impl<'a> Protocol<'a> for GraphicsOutput<'a> {
+    const GUID: EfiGuid = EfiGuid::new(
+        0x9042a9de,
+        0x23dc,
+        0x4a38,
+        [0x96, 0xfb, 0x7a, 0xde, 0xd0, 0x80, 0x51, 0x6a],
+    );
+
+    unsafe fn from_interface(interface: Ptr) -> Self {
+        GraphicsOutput {
+            protocol: interface.0 as *mut EfiGraphicsOutputProtocol,
+            _boot_services: PhantomData,
+        }
+    }
+}
+
+impl GraphicsOutput<'_> {
+    /// Returns the current graphics mode.
+    pub fn mode(&self) -> Result<Mode, Error> {
+        // The mode structures are owned by the firmware and they are valid
+        // while the protocol is.
+        let (mode, info) = unsafe {
+            let mode_ptr = (*self.protocol).mode;
+            if mode_ptr.is_null() {
+                return Err(Error::NotFound);
+            }
+            let mode = core::ptr::read_unaligned(mode_ptr);
+            if mode.info.is_null()
+                || mode.size_of_info
+                    < core::mem::size_of::<EfiGraphicsOutputModeInformation>()
+            {
+                return Err(Error::NotFound);
+            }
+            (mode, core::ptr::read_unaligned(mode.info))
+        };
+
+        Ok(Mode {
+            number: mode.mode,
+            framebuffer_base: mode.frame_buffer_base,
+            framebuffer_size: mode.frame_buffer_size as u64,
+            width: info.horizontal_resolution,
+            height: info.vertical_resolution,
+            stride: info.pixels_per_scan_line,
+            pixel_format: info.pixel_format.into(),
+        })
+    }
+}
@@ -0,0 +1,241 @@
+//! This module provides information about the loaded UEFI image, using the
+//! Loaded Image protocol.
+
+use core::marker::PhantomData;
+
+use crate::ucs2::Ucs2Str;
+use crate::{
+    BootServices, EfiGuid, EfiMemoryType, Error, Handle, Protocol, Ptr,
+};
+
+/// The `EFI_LOADED_IMAGE_PROTOCOL` type of the UEFI specification.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct EfiLoadedImageProtocol {
+    revision: u32,
+    parent_handle: Handle,
+    system_table: Ptr,
+
+    // Source location of the image.
+    device_handle: Handle,
+    file_path: Ptr,
+    reserved: Ptr,
+
+    // Image's load options.
+    load_options_size: u32,
+    load_options: Ptr,
+
+    // Location where image was loaded.
+    image_base: Ptr,
+    image_size: u64,
+    image_code_type: EfiMemoryType,
+    image_data_type: EfiMemoryType,
+    unload: Ptr,
+}
+
+/// Represents a loaded UEFI image.
+#[derive(Debug, Clone, Copy)]
+pub struct LoadedImage<'a> {
+    /// The `EFI_LOADED_IMAGE_PROTOCOL` structure provided by the firmware.
+    loaded_image: EfiLoadedImageProtocol,
+
+    /// The protocol cannot outlive the boot services.
+    _boot_services: PhantomData<&'a BootServices>,
+}
+
+impl<'a> Protocol<'a> for LoadedImage<'a> {
+    const GUID: EfiGuid = EfiGuid::new(
+        0x5b1b31a1,
+        0x9562,
+        0x11d2,
+        [0x8e, 0x3f, 0x00, 0xa0, 0xc9, 0x69, 0x72, 0x3b],
+    );
+
+    unsafe fn from_interface(interface: Ptr) -> Self {
+        LoadedImage {
+            loaded_image: core::ptr::read_unaligned(
+                interface.0 as *const EfiLoadedImageProtocol,
+            ),
+            _boot_services: PhantomData,
+        }
+    }
+}
+
+impl<'a> LoadedImage<'a> {
+    /// Handle of the device the image was loaded from.
+    pub(crate) fn device_handle(&self) -> Handle {
+        self.loaded_image.device_handle
+    }
+
+    /// Base address at which the image was loaded.
+    pub fn image_base(&self) -> Ptr {
+        self.loaded_image.image_base
+    }
+
+    /// Size of the loaded image in bytes.
+    pub fn image_size(&self) -> u64 {
+        self.loaded_image.image_size
+    }
+
+    /// Returns the load options of the image as a UCS-2 string. The UEFI
+    /// Shell passes the command line, including the path of the image, and
+    /// the boot manager passes the optional data of the boot option. It
+    /// returns `None` if there are no load options or they cannot be a UCS-2
+    /// string.
+    pub fn load_options(&self) -> Option<Ucs2Str<'a>> {
+        let ptr = self.loaded_image.load_options.0 as *const u16;
+        let size = self.loaded_image.load_options_size as usize;
+        if ptr.is_null()
+            || size == 0
+            || size % 2 != 0
+            || ptr.align_offset(2) != 0
+        {
+            return None;
+        }
+
+        // The load options are valid while the image is loaded.
+        let chars = unsafe { core::slice::from_raw_parts(ptr, size / 2) };
+        Some(Ucs2Str::from_slice(chars))
+    }
+}
+
+/// Returns the `LoadedImage` of the image `image_handle`.
+pub fn loaded_image(
+    boot_services: &BootServices,
+    image_handle: Handle,
+) -> Result<LoadedImage<'_>, Error> {
+    boot_services.open_protocol(image_handle, image_handle)
+}
+
+/// Signature of the DOS header.
+const DOS_SIGNATURE: [u8; 2] = *b"MZ";
+
+/// Offset of the offset of the PE signature in the DOS header.
+const DOS_LFANEW_OFFSET: usize = 0x3c;
+
+/// PE signature.
+const PE_SIGNATURE: [u8; 4] = *b"PE\0\0";
+
+/// Size of the COFF file header.
+const COFF_HEADER_SIZE: usize = 20;
+
+/// Size of a section header.
+const SECTION_HEADER_SIZE: usize = 40;
+
+/// Section characteristic flag that is set if the section is executable.
+const SCN_MEM_EXECUTE: u32 = 0x2000_0000;
+
+/// Section characteristic flag that is set if the section is writable.
+const SCN_MEM_WRITE: u32 = 0x8000_0000;
+
+/// Represents the section table of a PE/COFF image loaded in memory.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageSections {
+    /// Address of the first section header.
+    headers: Ptr,
+
+    /// Number of sections.
+    len: usize,
+}
+
+impl ImageSections {
+    /// Parses the headers of the PE/COFF image loaded at `image_base`.
+    ///
+    /// # Safety
+    ///
+    /// `image_base` must point to a loaded image, which must not be unloaded
+    /// while the `ImageSections` is used.
+    pub unsafe fn new(image_base: Ptr) -> Result<Self, Error> {
+        let base = image_base.0 as *const u8;
+        if core::ptr::read_unaligned(base as *const [u8; 2]) != DOS_SIGNATURE {
+            return Err(Error::InvalidImageData);
+        }
+        let pe_offset = core::ptr::read_unaligned(
+            base.add(DOS_LFANEW_OFFSET) as *const u32
+        ) as usize;
+
+        let signature =
+            core::ptr::read_unaligned(base.add(pe_offset) as *const [u8; 4]);
+        if signature != PE_SIGNATURE {
+            return Err(Error::InvalidImageData);
+        }
+
+        let coff = base.add(pe_offset + PE_SIGNATURE.len());
+        let len = core::ptr::read_unaligned(coff.add(2) as *const u16);
+        let optional_header_size =
+            core::ptr::read_unaligned(coff.add(16) as *const u16);
+
+        let headers = coff
+            .add(COFF_HEADER_SIZE)
+            .add(usize::from(optional_header_size));
+        Ok(ImageSections {
+            headers: Ptr(headers as usize),
+            len: usize::from(len),
+        })
+    }
+
+    /// Returns an iterator over the sections of the image.
+    pub fn iter(&self) -> impl Iterator<Item = ImageSection> + '_ {
+        (0..self.len).map(move |idx| {
+            let header =
+                (self.headers.0 + idx * SECTION_HEADER_SIZE) as *const u8;
+
+            // The section table is part of the loaded image.
+            unsafe {
+                ImageSection {
+                    name: core::ptr::read_unaligned(header as *const [u8; 8]),
+                    virtual_size: core::ptr::read_unaligned(
+                        header.add(8) as *const u32
+                    ),
+                    virtual_address: core::ptr::read_unaligned(
+                        header.add(12) as *const u32
+                    ),
+                    characteristics: core::ptr::read_unaligned(
+                        header.add(36) as *const u32
+                    ),
+                }
+            }
+        })
+    }
+}
+
+/// Represents a section of a PE/COFF image.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageSection {
+    name: [u8; 8],
+    virtual_size: u32,
+    virtual_address: u32,
+    characteristics: u32,
+}
+
+impl ImageSection {
+    /// Name of the section, padded with zeros.
+    pub fn name(&self) -> [u8; 8] {
+        self.name
+    }
+
+    /// Offset of the section from the image base.
+    pub fn virtual_address(&self) -> u32 {
+        self.virtual_address
+    }
+
+    /// Size of the section once loaded.
+    pub fn virtual_size(&self) -> u32 {
+        self.virtual_size
+    }
+
+    /// Section characteristic flags.
+    pub fn characteristics(&self) -> u32 {
+        self.characteristics
+    }
+
+    /// Returns `true` if the section contains executable code.
+    pub fn is_executable(&self) -> bool {
+        self.characteristics & SCN_MEM_EXECUTE != 0
+    }
+
+    /// Returns `true` if the section can be written.
+    pub fn is_writable(&self) -> bool {
+        self.characteristics & SCN_MEM_WRITE != 0
+    }
+}
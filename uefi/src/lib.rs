@@ -1,14 +1,30 @@
 //! UEFI parsing primitives.
+//!
+//! Firmware interfaces are declared with the `efiapi` calling convention,
+//! which selects the UEFI ABI of the target architecture (e.g. the Microsoft
+//! x64 ABI on x86_64).
 
 #![no_std]
+#![feature(abi_efiapi)]
 
 use core::convert::{TryFrom, TryInto};
+use core::fmt;
 
 use mm::{PhysAddr, VirtAddr};
 
 pub mod acpi;
+pub mod console;
+pub mod fdt;
+pub mod fs;
+pub mod gop;
+pub mod image;
 pub mod mem;
+pub mod rng;
+pub mod smbios;
+pub mod tcg2;
+pub mod ucs2;
 mod utils;
+pub mod vars;
 
 /// Represents an UEFI error.
 #[derive(Debug)]
@@ -31,9 +47,31 @@ pub enum Error {
     /// Could not parse ACPI structures.
     InvalidAcpiData,
 
+    /// Could not parse FDT structures.
+    InvalidFdtData,
+
+    /// Could not parse SMBIOS structures.
+    InvalidSmbiosData,
+
+    /// Could not parse the EFI Memory Attributes Table.
+    InvalidMemoryAttributesData,
+
+    /// Could not parse the PE/COFF headers of an image.
+    InvalidImageData,
+
+    /// The data of a UEFI variable does not have the expected format.
+    InvalidVariableData,
+
+    /// The data returned by the file protocol does not have the expected
+    /// format.
+    InvalidFileData,
+
     /// The fixed size buffer is too small.
     BufferTooSmall,
 
+    /// The string cannot be represented in the encoding required by UEFI.
+    InvalidString,
+
     /// The entity could not be found.
     NotFound,
 
@@ -45,6 +83,125 @@ pub enum Error {
 
     /// Error related to a memory map operation.
     RangeError(range::Error),
+
+    /// A firmware table could not be parsed.
+    Parse {
+        /// Name of the table (e.g. `MADT`).
+        table: &'static str,
+
+        /// Address of the table.
+        addr: usize,
+
+        /// Error found while parsing the table.
+        source: ParseError,
+    },
+}
+
+impl Error {
+    /// Adds the name and the address of the table being parsed to the error.
+    /// Errors that are not caused by malformed data are returned unchanged.
+    pub fn context(self, table: &'static str, addr: usize) -> Error {
+        let source = match self {
+            Error::InvalidSignature => ParseError::InvalidSignature,
+            Error::InvalidCheckSum => ParseError::InvalidCheckSum,
+            Error::InvalidRevision => ParseError::InvalidRevision,
+            Error::InvalidAddressSize => ParseError::InvalidAddressSize,
+            Error::InvalidAcpiData
+            | Error::InvalidFdtData
+            | Error::InvalidSmbiosData
+            | Error::InvalidMemoryAttributesData
+            | Error::InvalidImageData => ParseError::InvalidData,
+            Error::BufferTooSmall => ParseError::BufferTooSmall,
+            err => return err,
+        };
+        Error::Parse {
+            table,
+            addr,
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidSignature => write!(f, "invalid signature"),
+            Error::InvalidCheckSum => write!(f, "invalid checksum"),
+            Error::InvalidRevision => write!(f, "unsupported revision"),
+            Error::InvalidStatusConversion => {
+                write!(f, "invalid status code conversion")
+            }
+            Error::InvalidAddressSize => {
+                write!(f, "address does not fit in a pointer")
+            }
+            Error::InvalidAcpiData => write!(f, "malformed ACPI data"),
+            Error::InvalidFdtData => write!(f, "malformed FDT data"),
+            Error::InvalidSmbiosData => write!(f, "malformed SMBIOS data"),
+            Error::InvalidMemoryAttributesData => {
+                write!(f, "malformed memory attributes table")
+            }
+            Error::InvalidImageData => write!(f, "malformed PE/COFF image"),
+            Error::InvalidVariableData => {
+                write!(f, "unexpected UEFI variable data")
+            }
+            Error::InvalidFileData => write!(f, "unexpected file data"),
+            Error::BufferTooSmall => write!(f, "buffer too small"),
+            Error::InvalidString => {
+                write!(f, "string cannot be represented in UCS-2")
+            }
+            Error::NotFound => write!(f, "not found"),
+            Error::StatusError(err) => write!(f, "EFI error: {}", err),
+            Error::StatusWarning(warn) => write!(f, "EFI warning: {}", warn),
+            Error::RangeError(err) => write!(f, "range error: {:?}", err),
+            Error::Parse {
+                table,
+                addr,
+                source,
+            } => write!(
+                f,
+                "could not parse {} at {:#x}: {}",
+                table, addr, source
+            ),
+        }
+    }
+}
+
+/// Represents the reason why a firmware table could not be parsed.
+#[derive(Debug, Clone, Copy)]
+pub enum ParseError {
+    /// The signature of the table does not match the expected one.
+    InvalidSignature,
+
+    /// The checksum of the table does not match the expected one.
+    InvalidCheckSum,
+
+    /// The revision of the table is not supported.
+    InvalidRevision,
+
+    /// An address of the table does not fit in a pointer.
+    InvalidAddressSize,
+
+    /// The table is malformed.
+    InvalidData,
+
+    /// The table does not fit in the fixed size buffers of the parser.
+    BufferTooSmall,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            ParseError::InvalidSignature => "invalid signature",
+            ParseError::InvalidCheckSum => "invalid checksum",
+            ParseError::InvalidRevision => "unsupported revision",
+            ParseError::InvalidAddressSize => {
+                "address does not fit in a pointer"
+            }
+            ParseError::InvalidData => "malformed data",
+            ParseError::BufferTooSmall => "buffer too small",
+        };
+        f.write_str(msg)
+    }
 }
 
 impl From<range::Error> for Error {
@@ -54,11 +211,12 @@ impl From<range::Error> for Error {
 }
 
 /// The `EFI_STATUS` type of the UEFI specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(transparent)]
-struct EfiStatus(usize);
+pub struct EfiStatus(pub usize);
 
 /// Represents an UEFI warning status code.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StatusWarning {
     /// The string contained one or more characters that the device could not
     /// render and were skipped.
@@ -112,6 +270,39 @@ impl TryFrom<EfiStatus> for StatusWarning {
     }
 }
 
+impl From<StatusWarning> for EfiStatus {
+    fn from(warn: StatusWarning) -> Self {
+        match warn {
+            StatusWarning::UnknownGlyph => EfiStatus(1),
+            StatusWarning::DeleteFailure => EfiStatus(2),
+            StatusWarning::WriteFailure => EfiStatus(3),
+            StatusWarning::BufferTooSmall => EfiStatus(4),
+            StatusWarning::StaleData => EfiStatus(5),
+            StatusWarning::FileSystem => EfiStatus(6),
+            StatusWarning::ResetRequired => EfiStatus(7),
+            StatusWarning::Unknown(code) => EfiStatus(code),
+        }
+    }
+}
+
+impl fmt::Display for StatusWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            StatusWarning::UnknownGlyph => "unknown glyph",
+            StatusWarning::DeleteFailure => "delete failure",
+            StatusWarning::WriteFailure => "write failure",
+            StatusWarning::BufferTooSmall => "buffer too small",
+            StatusWarning::StaleData => "stale data",
+            StatusWarning::FileSystem => "file system",
+            StatusWarning::ResetRequired => "reset required",
+            StatusWarning::Unknown(code) => {
+                return write!(f, "unknown warning {:#x}", code)
+            }
+        };
+        f.write_str(msg)
+    }
+}
+
 impl From<StatusWarning> for Error {
     fn from(warn: StatusWarning) -> Self {
         Error::StatusWarning(warn)
@@ -119,7 +310,7 @@ impl From<StatusWarning> for Error {
 }
 
 /// Represents an UEFI error status code.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StatusError {
     /// The image failed to load.
     LoadError,
@@ -280,6 +471,94 @@ impl TryFrom<EfiStatus> for StatusError {
     }
 }
 
+impl From<StatusError> for EfiStatus {
+    fn from(err: StatusError) -> Self {
+        let code = match err {
+            StatusError::LoadError => 1,
+            StatusError::InvalidParameter => 2,
+            StatusError::Unsupported => 3,
+            StatusError::BadBufferSize => 4,
+            StatusError::BufferTooSmall => 5,
+            StatusError::NotReady => 6,
+            StatusError::DeviceError => 7,
+            StatusError::WriteProtected => 8,
+            StatusError::OutOfResources => 9,
+            StatusError::VolumeCorrupted => 10,
+            StatusError::VolumeFull => 11,
+            StatusError::NoMedia => 12,
+            StatusError::MediaChanged => 13,
+            StatusError::NotFound => 14,
+            StatusError::AccessDenied => 15,
+            StatusError::NoResponse => 16,
+            StatusError::NoMapping => 17,
+            StatusError::Timeout => 18,
+            StatusError::NotStarted => 19,
+            StatusError::AlreadyStarted => 20,
+            StatusError::Aborted => 21,
+            StatusError::IcmpError => 22,
+            StatusError::TftpError => 23,
+            StatusError::ProtocolError => 24,
+            StatusError::IncompatibleVersion => 25,
+            StatusError::SecurityViolation => 26,
+            StatusError::CrcError => 27,
+            StatusError::EndOfMedia => 28,
+            StatusError::EndOfFile => 31,
+            StatusError::InvalidLanguage => 32,
+            StatusError::CompromisedData => 33,
+            StatusError::IpAddressConflict => 34,
+            StatusError::HttpError => 35,
+            // Unknown error codes already have the highest bit set.
+            StatusError::Unknown(code) => return EfiStatus(code),
+        };
+
+        EfiStatus(code | !(usize::MAX >> 1))
+    }
+}
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            StatusError::LoadError => "load error",
+            StatusError::InvalidParameter => "invalid parameter",
+            StatusError::Unsupported => "unsupported",
+            StatusError::BadBufferSize => "bad buffer size",
+            StatusError::BufferTooSmall => "buffer too small",
+            StatusError::NotReady => "not ready",
+            StatusError::DeviceError => "device error",
+            StatusError::WriteProtected => "write protected",
+            StatusError::OutOfResources => "out of resources",
+            StatusError::VolumeCorrupted => "volume corrupted",
+            StatusError::VolumeFull => "volume full",
+            StatusError::NoMedia => "no media",
+            StatusError::MediaChanged => "media changed",
+            StatusError::NotFound => "not found",
+            StatusError::AccessDenied => "access denied",
+            StatusError::NoResponse => "no response",
+            StatusError::NoMapping => "no mapping",
+            StatusError::Timeout => "timeout",
+            StatusError::NotStarted => "not started",
+            StatusError::AlreadyStarted => "already started",
+            StatusError::Aborted => "aborted",
+            StatusError::IcmpError => "ICMP error",
+            StatusError::TftpError => "TFTP error",
+            StatusError::ProtocolError => "protocol error",
+            StatusError::IncompatibleVersion => "incompatible version",
+            StatusError::SecurityViolation => "security violation",
+            StatusError::CrcError => "CRC error",
+            StatusError::EndOfMedia => "end of media",
+            StatusError::EndOfFile => "end of file",
+            StatusError::InvalidLanguage => "invalid language",
+            StatusError::CompromisedData => "compromised data",
+            StatusError::IpAddressConflict => "IP address conflict",
+            StatusError::HttpError => "HTTP error",
+            StatusError::Unknown(code) => {
+                return write!(f, "unknown error {:#x}", code)
+            }
+        };
+        f.write_str(msg)
+    }
+}
+
 impl From<StatusError> for Error {
     fn from(err: StatusError) -> Self {
         Error::StatusError(err)
@@ -287,7 +566,8 @@ impl From<StatusError> for Error {
 }
 
 /// Represents the status code returned by an EFI interface.
-enum Status {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
     /// Success status code.
     Success,
 
@@ -310,6 +590,43 @@ impl From<EfiStatus> for Status {
     }
 }
 
+impl From<Status> for EfiStatus {
+    fn from(status: Status) -> Self {
+        match status {
+            Status::Success => EfiStatus(0),
+            Status::Warning(warn) => warn.into(),
+            Status::Error(err) => err.into(),
+        }
+    }
+}
+
+impl Status {
+    /// Creates a `Status` from a raw `EFI_STATUS` code.
+    pub fn from_raw(code: usize) -> Status {
+        EfiStatus(code).into()
+    }
+
+    /// Returns the raw `EFI_STATUS` code of the `Status`.
+    pub fn to_raw(self) -> usize {
+        EfiStatus::from(self).0
+    }
+
+    /// Returns `true` if the status code is `EFI_SUCCESS`.
+    pub fn is_success(&self) -> bool {
+        matches!(self, Status::Success)
+    }
+
+    /// Returns `true` if the status code is a warning.
+    pub fn is_warning(&self) -> bool {
+        matches!(self, Status::Warning(_))
+    }
+
+    /// Returns `true` if the status code is an error.
+    pub fn is_error(&self) -> bool {
+        matches!(self, Status::Error(_))
+    }
+}
+
 /// Represents an UEFI handle. It is equivalent to the `EFI_HANDLE` type of the
 /// UEFI specification.
 #[derive(Debug, Clone, Copy)]
@@ -386,7 +703,7 @@ impl SystemTable {
     /// point.
     ///
     /// ```ignore
-    /// extern "C" fn efi_main(
+    /// extern "efiapi" fn efi_main(
     ///     image_handler: uefi::Handle,
     ///     system_table: uefi::Ptr,
     /// ) -> ! {
@@ -431,6 +748,62 @@ impl SystemTable {
         unsafe { BootServices::new(self.system_table.boot_services) }
     }
 
+    /// Returns the runtime services.
+    pub fn runtime_services(&self) -> Result<RuntimeServices, Error> {
+        // A `SystemTable` is only created after checking its signature
+        // and CRC32. Thus, we assume that the pointer to the Runtime Services
+        // Table will be valid.
+        unsafe { RuntimeServices::new(self.system_table.runtime_services) }
+    }
+
+    /// Returns the name of the firmware vendor.
+    ///
+    /// # Errors
+    ///
+    /// If the firmware does not provide a vendor name, the function returns
+    /// `Error::NotFound`.
+    pub fn firmware_vendor(&self) -> Result<ucs2::Ucs2Str<'_>, Error> {
+        if self.system_table.firmware_vendor.0 == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(unsafe {
+            ucs2::Ucs2Str::from_ptr(
+                self.system_table.firmware_vendor.0 as *const u16,
+            )
+        })
+    }
+
+    /// Revision of the firmware. Its meaning is vendor specific.
+    pub fn firmware_revision(&self) -> u32 {
+        self.system_table.firmware_revision
+    }
+
+    /// Returns the console input device.
+    ///
+    /// # Errors
+    ///
+    /// If the firmware does not provide a console input device, the function
+    /// returns `Error::NotFound`.
+    pub fn cons_in(&self) -> Result<console::TextInput<'_>, Error> {
+        if self.system_table.cons_in.0 == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(console::TextInput::new(self.system_table.cons_in))
+    }
+
+    /// Returns the console output device.
+    ///
+    /// # Errors
+    ///
+    /// If the firmware does not provide a console output device, the function
+    /// returns `Error::NotFound`.
+    pub fn cons_out(&self) -> Result<console::TextOutput<'_>, Error> {
+        if self.system_table.cons_out.0 == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(console::TextOutput::new(self.system_table.cons_out))
+    }
+
     /// Returns the configuration tables.
     pub fn configuration_tables(&self) -> Result<ConfigurationTables, Error> {
         // A `SystemTable` is only created after checking its signature
@@ -447,32 +820,36 @@ impl SystemTable {
 
 /// Represents a physical memory address. It is equivalent to the
 /// `EFI_PHYSICAL_ADDRESS` type of the UEFI specification.
+#[derive(Debug, Clone, Copy)]
 #[repr(transparent)]
 struct EfiPhysAddr(u64);
 
 impl From<EfiPhysAddr> for PhysAddr {
     fn from(addr: EfiPhysAddr) -> Self {
-        PhysAddr(addr.0)
+        PhysAddr::new(addr.0)
     }
 }
 
 /// Represents a virtual memory address. It is equivalent to the
 /// `EFI_VIRTUAL_ADDRESS` type of the UEFI specification.
+#[derive(Debug, Clone, Copy)]
 #[repr(transparent)]
 struct EfiVirtAddr(u64);
 
 impl From<EfiVirtAddr> for VirtAddr {
     fn from(addr: EfiVirtAddr) -> Self {
-        VirtAddr(addr.0)
+        VirtAddr::new(addr.0)
     }
 }
 
 /// The `EFI_MEMORY_TYPE` type of the UEFI specification.
+#[derive(Debug, Clone, Copy)]
 #[repr(transparent)]
 struct EfiMemoryType(u32);
 
 /// The type of memory.
-enum MemoryType {
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MemoryType {
     /// Not usable memory.
     ReservedMemory,
 
@@ -614,14 +991,154 @@ impl From<EfiMemoryType> for MemoryType {
     }
 }
 
+impl From<MemoryType> for EfiMemoryType {
+    fn from(mem_type: MemoryType) -> Self {
+        let ty = match mem_type {
+            MemoryType::ReservedMemory => 0,
+            MemoryType::LoaderCode => 1,
+            MemoryType::LoaderData => 2,
+            MemoryType::BootServicesCode => 3,
+            MemoryType::BootServicesData => 4,
+            MemoryType::RuntimeServicesCode => 5,
+            MemoryType::RuntimeServicesData => 6,
+            MemoryType::ConventionalMemory => 7,
+            MemoryType::UnusableMemory => 8,
+            MemoryType::ACPIReclaimMemory => 9,
+            MemoryType::ACPIMemoryNVS => 10,
+            MemoryType::MemoryMappedIO => 11,
+            MemoryType::MemoryMappedIOPortSpace => 12,
+            MemoryType::PalCode => 13,
+            MemoryType::PersistentMemory => 14,
+            MemoryType::UnacceptedMemory => 15,
+            MemoryType::Unknown(ty) => ty,
+        };
+        EfiMemoryType(ty)
+    }
+}
+
+/// Attributes of a memory region. They describe the capabilities of the
+/// region, not its current configuration. It is equivalent to the `Attribute`
+/// field of the `EFI_MEMORY_DESCRIPTOR` type of the UEFI specification.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+#[repr(transparent)]
+pub struct MemoryAttribute(u64);
+
+impl MemoryAttribute {
+    /// The memory region supports being configured as not cacheable.
+    pub const UC: MemoryAttribute = MemoryAttribute(0x1);
+
+    /// The memory region supports being configured as write combining.
+    pub const WC: MemoryAttribute = MemoryAttribute(0x2);
+
+    /// The memory region supports being configured as cacheable with a "write
+    /// through" policy.
+    pub const WT: MemoryAttribute = MemoryAttribute(0x4);
+
+    /// The memory region supports being configured as cacheable with a "write
+    /// back" policy.
+    pub const WB: MemoryAttribute = MemoryAttribute(0x8);
+
+    /// The memory region supports being configured as not cacheable,
+    /// exported, and supports the "fetch and add" semaphore mechanism.
+    pub const UCE: MemoryAttribute = MemoryAttribute(0x10);
+
+    /// The memory region supports being configured as write-protected by
+    /// system hardware.
+    pub const WP: MemoryAttribute = MemoryAttribute(0x1000);
+
+    /// The memory region supports being configured as read-protected by system
+    /// hardware.
+    pub const RP: MemoryAttribute = MemoryAttribute(0x2000);
+
+    /// The memory region supports being configured so it is protected by
+    /// system hardware from executing code.
+    pub const XP: MemoryAttribute = MemoryAttribute(0x4000);
+
+    /// The memory region refers to persistent memory.
+    pub const NV: MemoryAttribute = MemoryAttribute(0x8000);
+
+    /// The memory region provides higher reliability relative to other memory
+    /// in the system.
+    pub const MORE_RELIABLE: MemoryAttribute = MemoryAttribute(0x10000);
+
+    /// The memory region supports making this memory range read-only by
+    /// system hardware.
+    pub const RO: MemoryAttribute = MemoryAttribute(0x20000);
+
+    /// The memory region is earmarked for specific purposes such as for
+    /// specific device drivers or applications.
+    pub const SP: MemoryAttribute = MemoryAttribute(0x40000);
+
+    /// The memory region is capable of being protected with the CPU's memory
+    /// cryptographic capabilities.
+    pub const CPU_CRYPTO: MemoryAttribute = MemoryAttribute(0x80000);
+
+    /// The memory region needs to be given a virtual mapping by the operating
+    /// system when `SetVirtualAddressMap()` is called.
+    pub const RUNTIME: MemoryAttribute = MemoryAttribute(0x8000000000000000);
+
+    /// Returns a `MemoryAttribute` from its raw representation.
+    pub const fn from_bits(bits: u64) -> Self {
+        MemoryAttribute(bits)
+    }
+
+    /// Returns the raw representation of the `MemoryAttribute`.
+    pub const fn bits(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns `true` if no attribute is set.
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if all the attributes in `other` are set.
+    pub const fn contains(&self, other: MemoryAttribute) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl core::ops::BitOr for MemoryAttribute {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        MemoryAttribute(self.0 | rhs.0)
+    }
+}
+
+impl core::ops::BitAnd for MemoryAttribute {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        MemoryAttribute(self.0 & rhs.0)
+    }
+}
+
 /// The `EFI_MEMORY_DESCRIPTOR` type of the UEFI specification.
+#[derive(Debug, Clone, Copy)]
 #[repr(C)]
 struct EfiMemoryDescriptor {
     memory_type: EfiMemoryType,
     physical_start: EfiPhysAddr,
     virtual_start: EfiVirtAddr,
     number_of_pages: u64,
-    attribute: u64,
+    attribute: MemoryAttribute,
+}
+
+/// The type of page allocation performed by `BootServices::allocate_pages`.
+/// It is equivalent to the `EFI_ALLOCATE_TYPE` type of the UEFI
+/// specification.
+#[derive(Debug, Clone, Copy)]
+pub enum AllocateType {
+    /// Allocate any available range of pages that satisfies the request.
+    AnyPages,
+
+    /// Allocate any available range of pages whose uppermost address is less
+    /// than or equal to the provided address.
+    MaxAddress(PhysAddr),
+
+    /// Allocate pages at the provided address.
+    Address(PhysAddr),
 }
 
 /// The signature of an EFI Boot Services Table.
@@ -638,17 +1155,23 @@ struct EfiBootServices {
     restore_tpl: Ptr,
 
     // Memory services.
-    allocate_pages: Ptr,
-    free_pages: Ptr,
-    get_memory_map: extern "C" fn(
+    allocate_pages: extern "efiapi" fn(
+        u32,
+        EfiMemoryType,
+        usize,
+        *mut EfiPhysAddr,
+    ) -> EfiStatus,
+    free_pages: extern "efiapi" fn(EfiPhysAddr, usize) -> EfiStatus,
+    get_memory_map: extern "efiapi" fn(
         *mut usize,
         *mut u8,
         *mut usize,
         *mut usize,
         *mut u32,
     ) -> EfiStatus,
-    allocate_pool: Ptr,
-    free_pool: Ptr,
+    allocate_pool:
+        extern "efiapi" fn(EfiMemoryType, usize, *mut Ptr) -> EfiStatus,
+    free_pool: extern "efiapi" fn(Ptr) -> EfiStatus,
 
     // Event & timer services.
     create_event: Ptr,
@@ -662,7 +1185,8 @@ struct EfiBootServices {
     install_protocol_interface: Ptr,
     reinstall_protocol_interface: Ptr,
     uninstall_protocol_interface: Ptr,
-    handle_protocol: Ptr,
+    handle_protocol:
+        extern "efiapi" fn(Handle, *const EfiGuid, *mut Ptr) -> EfiStatus,
     reserved: Ptr,
     register_protocol_notify: Ptr,
     locate_handle: Ptr,
@@ -675,26 +1199,41 @@ struct EfiBootServices {
     exit: Ptr,
     unload_image: Ptr,
     exit_boot_services:
-        extern "C" fn(image_handle: Handle, map_key: usize) -> EfiStatus,
+        extern "efiapi" fn(image_handle: Handle, map_key: usize) -> EfiStatus,
 
     // Miscelaneous services.
-    get_next_monotonic_count: Ptr,
-    stall: Ptr,
-    set_watchdog_timer: Ptr,
+    get_next_monotonic_count: extern "efiapi" fn(*mut u64) -> EfiStatus,
+    stall: extern "efiapi" fn(usize) -> EfiStatus,
+    set_watchdog_timer:
+        extern "efiapi" fn(usize, u64, usize, *const u16) -> EfiStatus,
 
     // DriverSupport services.
     connect_controller: Ptr,
     disconnect_controller: Ptr,
 
     // Open and close protocol services.
-    open_protocol: Ptr,
+    open_protocol: extern "efiapi" fn(
+        Handle,
+        *const EfiGuid,
+        *mut Ptr,
+        Handle,
+        Handle,
+        u32,
+    ) -> EfiStatus,
     close_protocol: Ptr,
     open_protocol_information: Ptr,
 
     // Library services.
     protocols_per_handle: Ptr,
-    locate_handle_buffer: Ptr,
-    locate_protocol: Ptr,
+    locate_handle_buffer: extern "efiapi" fn(
+        u32,
+        *const EfiGuid,
+        Ptr,
+        *mut usize,
+        *mut *mut Handle,
+    ) -> EfiStatus,
+    locate_protocol:
+        extern "efiapi" fn(*const EfiGuid, Ptr, *mut Ptr) -> EfiStatus,
     install_multiple_protocol_interfaces: Ptr,
     uninstall_multiple_protocol_interfaces: Ptr,
 
@@ -707,6 +1246,68 @@ struct EfiBootServices {
     create_event_ex: Ptr,
 }
 
+/// `OpenProtocol()` attribute used to get the interface of a protocol without
+/// taking ownership of it.
+const EFI_OPEN_PROTOCOL_GET_PROTOCOL: u32 = 0x2;
+
+/// `LocateHandleBuffer()` search type used to look for the handles that
+/// support a protocol.
+const EFI_LOCATE_BY_PROTOCOL: u32 = 2;
+
+/// A UEFI protocol that can be retrieved using the boot services. It is
+/// implemented by the wrappers of the protocol interfaces.
+pub trait Protocol<'a>: Sized {
+    /// The EFI GUID of the protocol.
+    const GUID: EfiGuid;
+
+    /// Creates the wrapper from the interface returned by the firmware.
+    ///
+    /// # Safety
+    ///
+    /// `interface` must point to an interface of the protocol with the GUID
+    /// `Self::GUID` that is valid for the lifetime `'a`.
+    unsafe fn from_interface(interface: Ptr) -> Self;
+}
+
+/// Buffer of handles returned by `BootServices::locate_handle_buffer`. The
+/// buffer is freed when it is dropped.
+pub struct HandleBuffer<'a> {
+    /// Boot services used to free the buffer.
+    boot_services: &'a BootServices,
+
+    /// Pool allocated by the firmware.
+    buffer: *mut Handle,
+
+    /// Number of handles in the buffer.
+    len: usize,
+}
+
+impl HandleBuffer<'_> {
+    /// Returns the handles of the buffer.
+    pub fn handles(&self) -> &[Handle] {
+        if self.buffer.is_null() {
+            return &[];
+        }
+
+        // The buffer was allocated by the firmware and contains `len`
+        // handles.
+        unsafe { core::slice::from_raw_parts(self.buffer, self.len) }
+    }
+}
+
+impl Drop for HandleBuffer<'_> {
+    fn drop(&mut self) {
+        if self.buffer.is_null() {
+            return;
+        }
+
+        // The buffer was allocated by the firmware and it is not used anymore.
+        unsafe {
+            self.boot_services.free_pool(Ptr(self.buffer as usize)).ok();
+        }
+    }
+}
+
 /// Represents the EFI Boot Services Table. It provides access to the boot
 /// services.
 #[derive(Debug)]
@@ -747,6 +1348,217 @@ impl BootServices {
         Ok(BootServices { boot_services })
     }
 
+    /// Allocates `pages` contiguous 4KiB pages of type `memory_type` and
+    /// returns the physical address of the first one.
+    pub fn allocate_pages(
+        &self,
+        alloc_type: AllocateType,
+        memory_type: MemoryType,
+        pages: usize,
+    ) -> Result<PhysAddr, Error> {
+        let (alloc_type, mut memory) = match alloc_type {
+            AllocateType::AnyPages => (0, EfiPhysAddr(0)),
+            AllocateType::MaxAddress(addr) => (1, EfiPhysAddr(addr.as_u64())),
+            AllocateType::Address(addr) => (2, EfiPhysAddr(addr.as_u64())),
+        };
+
+        // Call `EFI_BOOT_SERVICES.AllocatePages()`.
+        let status = (self.boot_services.allocate_pages)(
+            alloc_type,
+            memory_type.into(),
+            pages,
+            &mut memory,
+        );
+
+        // Return with error in the case of warning and error status codes.
+        match status.into() {
+            Status::Success => {}
+            Status::Warning(warn) => return Err(warn.into()),
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        Ok(memory.into())
+    }
+
+    /// Frees `pages` contiguous pages starting at `addr`.
+    ///
+    /// # Safety
+    ///
+    /// The pages must have been allocated by `allocate_pages` and must not be
+    /// used after calling this function.
+    pub unsafe fn free_pages(
+        &self,
+        addr: PhysAddr,
+        pages: usize,
+    ) -> Result<(), Error> {
+        // Call `EFI_BOOT_SERVICES.FreePages()`.
+        let status =
+            (self.boot_services.free_pages)(EfiPhysAddr(addr.as_u64()), pages);
+
+        // Return with error in the case of warning and error status codes.
+        match status.into() {
+            Status::Success => {}
+            Status::Warning(warn) => return Err(warn.into()),
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        Ok(())
+    }
+
+    /// Allocates a pool of `size` bytes of type `memory_type` and returns a
+    /// pointer to it. The pool is 8-byte aligned.
+    pub fn allocate_pool(
+        &self,
+        memory_type: MemoryType,
+        size: usize,
+    ) -> Result<Ptr, Error> {
+        let mut buffer = Ptr::default();
+
+        // Call `EFI_BOOT_SERVICES.AllocatePool()`.
+        let status = (self.boot_services.allocate_pool)(
+            memory_type.into(),
+            size,
+            &mut buffer,
+        );
+
+        // Return with error in the case of warning and error status codes.
+        match status.into() {
+            Status::Success => {}
+            Status::Warning(warn) => return Err(warn.into()),
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        Ok(buffer)
+    }
+
+    /// Returns a pointer to the interface of the protocol `guid` supported by
+    /// `handle`.
+    pub(crate) fn handle_protocol(
+        &self,
+        handle: Handle,
+        guid: &EfiGuid,
+    ) -> Result<Ptr, Error> {
+        let mut interface = Ptr::default();
+
+        // Call `EFI_BOOT_SERVICES.HandleProtocol()`.
+        let status =
+            (self.boot_services.handle_protocol)(handle, guid, &mut interface);
+
+        // Return with error in the case of warning and error status codes.
+        match status.into() {
+            Status::Success => {}
+            Status::Warning(warn) => return Err(warn.into()),
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        Ok(interface)
+    }
+
+    /// Returns the first instance of the protocol `P` found in the handle
+    /// database.
+    pub fn locate_protocol<'a, P: Protocol<'a>>(&'a self) -> Result<P, Error> {
+        let mut interface = Ptr::default();
+
+        // Call `EFI_BOOT_SERVICES.LocateProtocol()`.
+        let status = (self.boot_services.locate_protocol)(
+            &P::GUID,
+            Ptr::default(),
+            &mut interface,
+        );
+
+        // Return with error in the case of warning and error status codes.
+        match status.into() {
+            Status::Success => {}
+            Status::Warning(warn) => return Err(warn.into()),
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        // The firmware returned an interface of the protocol `P`.
+        Ok(unsafe { P::from_interface(interface) })
+    }
+
+    /// Returns the instance of the protocol `P` supported by `handle`. The
+    /// protocol is opened on behalf of the image `agent`.
+    pub fn open_protocol<'a, P: Protocol<'a>>(
+        &'a self,
+        handle: Handle,
+        agent: Handle,
+    ) -> Result<P, Error> {
+        let mut interface = Ptr::default();
+
+        // Call `EFI_BOOT_SERVICES.OpenProtocol()`. With `GET_PROTOCOL`, the
+        // protocol does not need to be closed.
+        let status = (self.boot_services.open_protocol)(
+            handle,
+            &P::GUID,
+            &mut interface,
+            agent,
+            Handle(0),
+            EFI_OPEN_PROTOCOL_GET_PROTOCOL,
+        );
+
+        // Return with error in the case of warning and error status codes.
+        match status.into() {
+            Status::Success => {}
+            Status::Warning(warn) => return Err(warn.into()),
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        // The firmware returned an interface of the protocol `P`.
+        Ok(unsafe { P::from_interface(interface) })
+    }
+
+    /// Returns the handles that support the protocol with the GUID `guid`.
+    pub fn locate_handle_buffer(
+        &self,
+        guid: &EfiGuid,
+    ) -> Result<HandleBuffer<'_>, Error> {
+        let mut len = 0usize;
+        let mut buffer = core::ptr::null_mut();
+
+        // Call `EFI_BOOT_SERVICES.LocateHandleBuffer()`.
+        let status = (self.boot_services.locate_handle_buffer)(
+            EFI_LOCATE_BY_PROTOCOL,
+            guid,
+            Ptr::default(),
+            &mut len,
+            &mut buffer,
+        );
+
+        // Return with error in the case of warning and error status codes.
+        match status.into() {
+            Status::Success => {}
+            Status::Warning(warn) => return Err(warn.into()),
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        Ok(HandleBuffer {
+            boot_services: self,
+            buffer,
+            len,
+        })
+    }
+
+    /// Frees the pool pointed by `buffer`.
+    ///
+    /// # Safety
+    ///
+    /// The pool must have been allocated by `allocate_pool` and must not be
+    /// used after calling this function.
+    pub unsafe fn free_pool(&self, buffer: Ptr) -> Result<(), Error> {
+        // Call `EFI_BOOT_SERVICES.FreePool()`.
+        let status = (self.boot_services.free_pool)(buffer);
+
+        // Return with error in the case of warning and error status codes.
+        match status.into() {
+            Status::Success => {}
+            Status::Warning(warn) => return Err(warn.into()),
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        Ok(())
+    }
+
     /// This function must be called by the currently executing UEFI OS loader
     /// image to terminate all boot services. On success, the UEFI OS loader
     /// becomes responsible for the continued operation of the system.
@@ -754,15 +1566,351 @@ impl BootServices {
     /// A UEFI OS loader must ensure that it has the system's current memory
     /// map at the time it calls this function. This is done by passing in the
     /// current memory map's `map_key` value as returned by `get_memory_map`.
+    ///
+    /// The boot services are terminated even if the firmware returns a
+    /// warning status code, so warnings are returned as `Ok(Some(warn))`.
     pub fn exit_boot_services(
         &self,
         image_handle: Handle,
         map_key: usize,
-    ) -> Result<(), Error> {
+    ) -> Result<Option<StatusWarning>, Error> {
         // Call `EFI_BOOT_SERVICES.ExitBootServices()`.
         let status =
             (self.boot_services.exit_boot_services)(image_handle, map_key);
 
+        // Return with error in the case of error status codes.
+        match status.into() {
+            Status::Success => Ok(None),
+            Status::Warning(warn) => Ok(Some(warn)),
+            Status::Error(err) => Err(err.into()),
+        }
+    }
+
+    /// Returns the next value of the platform's monotonic counter. The upper
+    /// 32 bits are incremented on every boot, so the value is unique across
+    /// boots.
+    pub fn get_next_monotonic_count(&self) -> Result<u64, Error> {
+        let mut count = 0u64;
+
+        // Call `EFI_BOOT_SERVICES.GetNextMonotonicCount()`.
+        let status = (self.boot_services.get_next_monotonic_count)(&mut count);
+
+        // Return with error in the case of warning and error status codes.
+        match status.into() {
+            Status::Success => {}
+            Status::Warning(warn) => return Err(warn.into()),
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        Ok(count)
+    }
+
+    /// Waits for at least `microseconds` microseconds.
+    pub fn stall(&self, microseconds: usize) -> Result<(), Error> {
+        // Call `EFI_BOOT_SERVICES.Stall()`.
+        let status = (self.boot_services.stall)(microseconds);
+
+        // Return with error in the case of warning and error status codes.
+        match status.into() {
+            Status::Success => {}
+            Status::Warning(warn) => return Err(warn.into()),
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        Ok(())
+    }
+
+    /// Sets the system's watchdog timer to `seconds` seconds. If it expires,
+    /// the firmware logs `code` and resets the platform. A `seconds` value of
+    /// 0 disables the watchdog timer.
+    ///
+    /// The firmware arms a 5 minutes watchdog timer before starting the UEFI
+    /// OS loader. `ExitBootServices()` disables it.
+    ///
+    /// The codes 0x0000 to 0xffff are reserved for the firmware.
+    pub fn set_watchdog_timer(
+        &self,
+        seconds: usize,
+        code: u64,
+    ) -> Result<(), Error> {
+        // Call `EFI_BOOT_SERVICES.SetWatchdogTimer()`.
+        let status = (self.boot_services.set_watchdog_timer)(
+            seconds,
+            code,
+            0,
+            core::ptr::null(),
+        );
+
+        // Return with error in the case of warning and error status codes.
+        match status.into() {
+            Status::Success => {}
+            Status::Warning(warn) => return Err(warn.into()),
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        Ok(())
+    }
+}
+
+/// The `EFI_TIME` type of the UEFI specification.
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+struct EfiTime {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+    pad1: u8,
+    nanosecond: u32,
+    time_zone: i16,
+    daylight: u8,
+    pad2: u8,
+}
+
+/// Value of `EfiTime.time_zone` when the time is not referenced to a time
+/// zone.
+const EFI_UNSPECIFIED_TIMEZONE: i16 = 0x07ff;
+
+/// Represents the time returned by the real time clock of the platform.
+#[derive(Debug, Clone, Copy)]
+pub struct Time {
+    /// The `EFI_TIME` structure returned by the firmware.
+    time: EfiTime,
+}
+
+impl Time {
+    /// Year. From 1900 to 9999.
+    pub fn year(&self) -> u16 {
+        self.time.year
+    }
+
+    /// Month. From 1 to 12.
+    pub fn month(&self) -> u8 {
+        self.time.month
+    }
+
+    /// Day. From 1 to 31.
+    pub fn day(&self) -> u8 {
+        self.time.day
+    }
+
+    /// Hour. From 0 to 23.
+    pub fn hour(&self) -> u8 {
+        self.time.hour
+    }
+
+    /// Minute. From 0 to 59.
+    pub fn minute(&self) -> u8 {
+        self.time.minute
+    }
+
+    /// Second. From 0 to 59.
+    pub fn second(&self) -> u8 {
+        self.time.second
+    }
+
+    /// Nanosecond. From 0 to 999,999,999.
+    pub fn nanosecond(&self) -> u32 {
+        self.time.nanosecond
+    }
+
+    /// Offset of the time from UTC in minutes, or `None` if the time is local
+    /// time.
+    pub fn time_zone(&self) -> Option<i16> {
+        if self.time.time_zone == EFI_UNSPECIFIED_TIMEZONE {
+            None
+        } else {
+            Some(self.time.time_zone)
+        }
+    }
+
+    /// Daylight saving time information.
+    ///
+    /// Bit offset | Bit length | Flag
+    /// ---------- | ---------- | ---------------------
+    /// 0          | 1          | EFI_TIME_ADJUST_DAYLIGHT
+    /// 1          | 1          | EFI_TIME_IN_DAYLIGHT
+    pub fn daylight(&self) -> u8 {
+        self.time.daylight
+    }
+}
+
+/// The type of system reset. It is equivalent to the `EFI_RESET_TYPE` type of
+/// the UEFI specification.
+#[derive(Debug, Clone, Copy)]
+#[repr(u32)]
+pub enum ResetType {
+    /// System-wide reset. All circuitry within the system returns to its
+    /// initial state.
+    Cold = 0,
+
+    /// System-wide initialization. The processors are set to their initial
+    /// state, and pending cycles are not corrupted.
+    Warm = 1,
+
+    /// The system enters a power state equivalent to the ACPI G2/S5 or G3
+    /// states.
+    Shutdown = 2,
+
+    /// System-wide reset. The exact type of the reset is defined by the
+    /// platform.
+    PlatformSpecific = 3,
+}
+
+/// The signature of an EFI Runtime Services Table.
+const EFI_RUNTIME_SERVICES_SIGNATURE: u64 = 0x56524553544e5552;
+
+/// The `EFI_RUNTIME_SERVICES` type of the UEFI specification.
+#[derive(Debug, Clone)]
+#[repr(C)]
+struct EfiRuntimeServices {
+    hdr: EfiTableHeader,
+
+    // Time services.
+    get_time: extern "efiapi" fn(*mut EfiTime, *mut u8) -> EfiStatus,
+    set_time: Ptr,
+    get_wakeup_time: Ptr,
+    set_wakeup_time: Ptr,
+
+    // Virtual memory services.
+    set_virtual_address_map:
+        extern "efiapi" fn(usize, usize, u32, *mut u8) -> EfiStatus,
+    convert_pointer: Ptr,
+
+    // Variable services.
+    get_variable: extern "efiapi" fn(
+        *const u16,
+        *const EfiGuid,
+        *mut u32,
+        *mut usize,
+        *mut u8,
+    ) -> EfiStatus,
+    get_next_variable_name:
+        extern "efiapi" fn(*mut usize, *mut u16, *mut EfiGuid) -> EfiStatus,
+    set_variable: extern "efiapi" fn(
+        *const u16,
+        *const EfiGuid,
+        u32,
+        usize,
+        *const u8,
+    ) -> EfiStatus,
+
+    // Miscellaneous services.
+    get_next_high_monotonic_count: Ptr,
+    reset_system:
+        extern "efiapi" fn(ResetType, EfiStatus, usize, *const u8) -> !,
+
+    // UEFI 2.0 capsule services.
+    update_capsule: Ptr,
+    query_capsule_capabilities: Ptr,
+
+    // Miscellaneous UEFI 2.0 service.
+    query_variable_info: Ptr,
+}
+
+/// Represents the EFI Runtime Services Table. It provides access to the
+/// runtime services, which are available before and after calling
+/// `BootServices::exit_boot_services`.
+#[derive(Debug)]
+pub struct RuntimeServices {
+    /// The `EFI_RUNTIME_SERVICES` structure provided by the firmware.
+    runtime_services: EfiRuntimeServices,
+}
+
+impl RuntimeServices {
+    /// Creates a new `RuntimeServices` from a given pointer
+    /// `runtime_services_ptr`.
+    ///
+    /// # Errors
+    ///
+    /// If the signature or the CRC32 of the table do not match the expected
+    /// values the function will return an error.
+    ///
+    /// # Safety
+    ///
+    /// The Runtime Services Table is created using a pointer. Thus, this
+    /// function is considered unsafe.
+    pub unsafe fn new(runtime_services_ptr: Ptr) -> Result<Self, Error> {
+        let runtime_services_ptr =
+            runtime_services_ptr.0 as *const EfiRuntimeServices;
+        let runtime_services = core::ptr::read_unaligned(runtime_services_ptr);
+
+        // Check table's signature.
+        if runtime_services.hdr.signature != EFI_RUNTIME_SERVICES_SIGNATURE {
+            return Err(Error::InvalidSignature);
+        }
+
+        // Check table's CRC32.
+        let mut runtime_services_crc32 = runtime_services.clone();
+        runtime_services_crc32.hdr.crc32 = 0;
+        let crc32 = utils::crc32_for_value(runtime_services_crc32);
+        if crc32 != runtime_services.hdr.crc32 {
+            return Err(Error::InvalidCheckSum);
+        }
+
+        Ok(RuntimeServices { runtime_services })
+    }
+
+    /// Returns the current time from the real time clock of the platform.
+    pub fn get_time(&self) -> Result<Time, Error> {
+        let mut time = EfiTime::default();
+
+        // Call `EFI_RUNTIME_SERVICES.GetTime()`.
+        let status =
+            (self.runtime_services.get_time)(&mut time, core::ptr::null_mut());
+
+        // Return with error in the case of warning and error status codes.
+        match status.into() {
+            Status::Success => {}
+            Status::Warning(warn) => return Err(warn.into()),
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        Ok(Time { time })
+    }
+
+    /// Resets the entire platform. This function never returns.
+    pub fn reset_system(&self, reset_type: ResetType) -> ! {
+        // Call `EFI_RUNTIME_SERVICES.ResetSystem()`.
+        (self.runtime_services.reset_system)(
+            reset_type,
+            EfiStatus(0),
+            0,
+            core::ptr::null(),
+        )
+    }
+
+    /// Changes the runtime addressing mode of the firmware from physical to
+    /// virtual. `virtual_map` is a memory map, as returned by
+    /// `EFI_BOOT_SERVICES.GetMemoryMap()`, where the virtual start address of
+    /// the runtime descriptors has been set to the new virtual address.
+    ///
+    /// It can only be called once, after `BootServices::exit_boot_services`.
+    /// On success, the function pointers of this `RuntimeServices` are not
+    /// valid anymore and a new one must be created from the virtual address
+    /// of the table.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the virtual addresses in `virtual_map` are
+    /// mapped to the physical memory of the runtime descriptors. Otherwise,
+    /// any subsequent call to a runtime service can crash the system.
+    pub unsafe fn set_virtual_address_map(
+        &self,
+        virtual_map: &mut [u8],
+        descriptor_size: usize,
+        descriptor_version: u32,
+    ) -> Result<(), Error> {
+        // Call `EFI_RUNTIME_SERVICES.SetVirtualAddressMap()`.
+        let status = (self.runtime_services.set_virtual_address_map)(
+            virtual_map.len(),
+            descriptor_size,
+            descriptor_version,
+            virtual_map.as_mut_ptr(),
+        );
+
         // Return with error in the case of warning and error status codes.
         match status.into() {
             Status::Success => {}
@@ -777,13 +1925,40 @@ impl BootServices {
 /// The `EFI_GUID` type of the UEFI specification.
 #[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
 #[repr(C)]
-struct EfiGuid {
+pub struct EfiGuid {
     data1: u32,
     data2: u16,
     data3: u16,
     data4: [u8; 8],
 }
 
+impl EfiGuid {
+    /// Returns a new `EfiGuid` from its components. For instance, the GUID
+    /// `8be4df61-93ca-11d2-aa0d-00e098032b8c` is created with:
+    ///
+    /// ```ignore
+    /// EfiGuid::new(
+    ///     0x8be4df61,
+    ///     0x93ca,
+    ///     0x11d2,
+    ///     [0xaa, 0x0d, 0x00, 0xe0, 0x98, 0x03, 0x2b, 0x8c],
+    /// )
+    /// ```
+    pub const fn new(
+        data1: u32,
+        data2: u16,
+        data3: u16,
+        data4: [u8; 8],
+    ) -> Self {
+        EfiGuid {
+            data1,
+            data2,
+            data3,
+            data4,
+        }
+    }
+}
+
 /// The `EFI_CONFIGURATION_TABLE` type of the UEFI specification.
 #[derive(Debug, Default, Clone, Copy)]
 #[repr(C)]
@@ -792,14 +1967,54 @@ struct EfiConfigurationTable {
     vendor_table: Ptr,
 }
 
+/// The EFI GUID for a pointer to the ACPI 1.0 specification RSDP.
+pub const EFI_ACPI_TABLE_GUID: EfiGuid = EfiGuid {
+    data1: 0xeb9d2d30,
+    data2: 0x2d88,
+    data3: 0x11d3,
+    data4: [0x9a, 0x16, 0x00, 0x90, 0x27, 0x3f, 0xc1, 0x4d],
+};
+
 /// The EFI GUID for a pointer to the ACPI 2.0 or later specification RSDP.
-const EFI_ACPI_20_TABLE_GUID: EfiGuid = EfiGuid {
+pub const EFI_ACPI_20_TABLE_GUID: EfiGuid = EfiGuid {
     data1: 0x8868e871,
     data2: 0xe4f1,
     data3: 0x11d3,
     data4: [0xbc, 0x22, 0x00, 0x80, 0xc7, 0x3c, 0x88, 0x81],
 };
 
+/// The EFI GUID for a pointer to the Flattened Device Tree blob.
+pub const EFI_DTB_TABLE_GUID: EfiGuid = EfiGuid {
+    data1: 0xb1b621d5,
+    data2: 0xf19c,
+    data3: 0x41a5,
+    data4: [0x83, 0x0b, 0xd9, 0x15, 0x2c, 0x69, 0xaa, 0xe0],
+};
+
+/// The EFI GUID for a pointer to the SMBIOS 2.x (32-bit) entry point.
+pub const SMBIOS_TABLE_GUID: EfiGuid = EfiGuid {
+    data1: 0xeb9d2d31,
+    data2: 0x2d88,
+    data3: 0x11d3,
+    data4: [0x9a, 0x16, 0x00, 0x90, 0x27, 0x3f, 0xc1, 0x4d],
+};
+
+/// The EFI GUID for a pointer to the SMBIOS 3.x (64-bit) entry point.
+pub const SMBIOS3_TABLE_GUID: EfiGuid = EfiGuid {
+    data1: 0xf2fd1544,
+    data2: 0x9794,
+    data3: 0x4a2c,
+    data4: [0x99, 0x2e, 0xe5, 0xbb, 0xcf, 0x20, 0xe3, 0x94],
+};
+
+/// The EFI GUID for a pointer to the EFI Memory Attributes Table.
+pub const EFI_MEMORY_ATTRIBUTES_TABLE_GUID: EfiGuid = EfiGuid {
+    data1: 0xdcfa911d,
+    data2: 0x26eb,
+    data3: 0x469f,
+    data4: [0xa2, 0x20, 0x38, 0xb7, 0xdc, 0x46, 0x12, 0x20],
+};
+
 /// The maximum number of entries in `ConfigurationTables`.
 const EFI_CONFIGURATION_TABLES_LEN: usize = 32;
 
@@ -841,20 +2056,89 @@ impl ConfigurationTables {
         })
     }
 
-    /// Returns a pointer to the Root System Description Pointer (RSDP)
-    /// structure for the ACPI 2.0 or later specification.
+    /// Returns an iterator over the configuration tables. Each item has the
+    /// form `(vendor_guid, vendor_table)`.
+    pub fn iter(&self) -> impl Iterator<Item = (EfiGuid, Ptr)> + '_ {
+        self.config_tables[..self.num_entries]
+            .iter()
+            .map(|cfg_table| (cfg_table.vendor_guid, cfg_table.vendor_table))
+    }
+
+    /// Returns a pointer to the configuration table identified by `guid`.
     ///
     /// # Errors
     ///
     /// This function will return `Error::NotFound` if a configuration table
-    /// with a valid ACPI table GUID cannot be found.
-    pub fn acpi_rsdp20_ptr(&self) -> Result<Ptr, Error> {
+    /// with the provided GUID cannot be found.
+    pub fn find(&self, guid: &EfiGuid) -> Result<Ptr, Error> {
         for cfg_table in &self.config_tables[..self.num_entries] {
-            if cfg_table.vendor_guid == EFI_ACPI_20_TABLE_GUID {
+            if cfg_table.vendor_guid == *guid {
                 return Ok(cfg_table.vendor_table);
             }
         }
 
         Err(Error::NotFound)
     }
+
+    /// Returns a pointer to the Root System Description Pointer (RSDP)
+    /// structure for the ACPI 2.0 or later specification.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Error::NotFound` if a configuration table
+    /// with a valid ACPI table GUID cannot be found.
+    pub fn acpi_rsdp20_ptr(&self) -> Result<Ptr, Error> {
+        self.find(&EFI_ACPI_20_TABLE_GUID)
+    }
+
+    /// Returns a pointer to the Root System Description Pointer (RSDP)
+    /// structure for the ACPI 1.0 specification.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Error::NotFound` if a configuration table
+    /// with a valid ACPI 1.0 table GUID cannot be found.
+    pub fn acpi_rsdp_ptr(&self) -> Result<Ptr, Error> {
+        self.find(&EFI_ACPI_TABLE_GUID)
+    }
+
+    /// Returns a pointer to the Flattened Device Tree blob (DTB).
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Error::NotFound` if a configuration table
+    /// with a valid DTB GUID cannot be found.
+    pub fn dtb_ptr(&self) -> Result<Ptr, Error> {
+        self.find(&EFI_DTB_TABLE_GUID)
+    }
+
+    /// Returns a pointer to the SMBIOS 2.x (32-bit) entry point structure.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Error::NotFound` if a configuration table
+    /// with a valid SMBIOS table GUID cannot be found.
+    pub fn smbios_ptr(&self) -> Result<Ptr, Error> {
+        self.find(&SMBIOS_TABLE_GUID)
+    }
+
+    /// Returns a pointer to the SMBIOS 3.x (64-bit) entry point structure.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Error::NotFound` if a configuration table
+    /// with a valid SMBIOS3 table GUID cannot be found.
+    pub fn smbios3_ptr(&self) -> Result<Ptr, Error> {
+        self.find(&SMBIOS3_TABLE_GUID)
+    }
+
+    /// Returns a pointer to the EFI Memory Attributes Table.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Error::NotFound` if a configuration table
+    /// with a valid Memory Attributes Table GUID cannot be found.
+    pub fn memory_attributes_ptr(&self) -> Result<Ptr, Error> {
+        self.find(&EFI_MEMORY_ATTRIBUTES_TABLE_GUID)
+    }
 }
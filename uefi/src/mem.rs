@@ -1,66 +1,377 @@
 //! This module provides memory management primitives in the context of UEFI.
 
-use crate::{BootServices, EfiMemoryDescriptor, Error, MemoryType, Status};
+use crate::{
+    BootServices, EfiMemoryDescriptor, Error, Handle, MemoryAttribute,
+    MemoryType, Ptr, Status, StatusError, StatusWarning,
+};
+use mm::PhysAddr;
 use range::{Range, RangeSet};
 
+/// Number of extra descriptors the memory map buffer has room for. Allocating
+/// the buffer can split a free memory region, growing the memory map.
+const MEMORY_MAP_SLACK: usize = 8;
+
+/// Represents a memory descriptor of the UEFI memory map.
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryDescriptor {
+    /// The `EFI_MEMORY_DESCRIPTOR` structure returned by the firmware.
+    descriptor: EfiMemoryDescriptor,
+}
+
+impl MemoryDescriptor {
+    /// Type of the memory region.
+    pub fn memory_type(&self) -> MemoryType {
+        MemoryType::from(self.descriptor.memory_type)
+    }
+
+    /// Physical address of the first byte of the memory region.
+    pub fn physical_start(&self) -> PhysAddr {
+        PhysAddr::new(self.descriptor.physical_start.0)
+    }
+
+    /// Number of 4KiB pages of the memory region.
+    pub fn number_of_pages(&self) -> u64 {
+        self.descriptor.number_of_pages
+    }
+
+    /// Attributes of the memory region.
+    pub fn attribute(&self) -> MemoryAttribute {
+        self.descriptor.attribute
+    }
+
+    /// Returns the memory region as a `Range`, or `None` if it is empty or
+    /// it does not fit in the physical address space.
+    pub fn range(&self) -> Option<Range> {
+        let start = self.descriptor.physical_start.0;
+        let size = self.descriptor.number_of_pages.checked_mul(0x1000)?;
+        let end = start.checked_add(size.checked_sub(1)?)?;
+        Range::new(start, end).ok()
+    }
+}
+
+/// Header of the `EFI_MEMORY_ATTRIBUTES_TABLE` type of the UEFI specification.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct EfiMemoryAttributesTable {
+    version: u32,
+    number_of_entries: u32,
+    descriptor_size: u32,
+    flags: u32,
+}
+
+/// Latest version of the Memory Attributes Table the parser is compatible
+/// with.
+const EFI_MEMORY_ATTRIBUTES_TABLE_VERSION: u32 = 2;
+
+/// Represents the EFI Memory Attributes Table. It describes the memory
+/// protections (`RO` and `XP` attributes) that can be applied to the
+/// `RuntimeServicesCode` and `RuntimeServicesData` regions.
+#[derive(Debug)]
+pub struct MemoryAttributesTable {
+    /// Header of the table.
+    table: EfiMemoryAttributesTable,
+
+    /// Memory descriptors of the table.
+    descriptors: &'static [u8],
+}
+
+impl MemoryAttributesTable {
+    /// Creates a new `MemoryAttributesTable` from a given pointer.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Error::InvalidRevision` if the version of the
+    /// table is not supported and `Error::InvalidMemoryAttributesData` if the
+    /// size of the descriptors is not valid.
+    ///
+    /// # Safety
+    ///
+    /// The `MemoryAttributesTable` structure is created using a pointer.
+    /// Thus, this function is considered unsafe.
+    pub unsafe fn new(table_ptr: Ptr) -> Result<Self, Error> {
+        // Parse header.
+        let table = core::ptr::read_unaligned(
+            table_ptr.0 as *const EfiMemoryAttributesTable,
+        );
+
+        // Check table's version.
+        if table.version == 0
+            || table.version > EFI_MEMORY_ATTRIBUTES_TABLE_VERSION
+        {
+            return Err(Error::InvalidRevision);
+        }
+
+        // The descriptors can be bigger than `EfiMemoryDescriptor`, but not
+        // smaller.
+        let descriptor_size = table.descriptor_size as usize;
+        if descriptor_size < core::mem::size_of::<EfiMemoryDescriptor>() {
+            return Err(Error::InvalidMemoryAttributesData);
+        }
+        let descriptors_len = descriptor_size
+            .checked_mul(table.number_of_entries as usize)
+            .ok_or(Error::InvalidMemoryAttributesData)?;
+
+        let descriptors = core::slice::from_raw_parts(
+            (table_ptr.0 as *const u8)
+                .add(core::mem::size_of::<EfiMemoryAttributesTable>()),
+            descriptors_len,
+        );
+
+        Ok(MemoryAttributesTable { table, descriptors })
+    }
+
+    /// Version of the table.
+    pub fn version(&self) -> u32 {
+        self.table.version
+    }
+
+    /// Flags of the table. If bit 0 is set, the runtime code regions are
+    /// guaranteed to be separated from the runtime data regions. It is only
+    /// defined from version 2.
+    pub fn flags(&self) -> u32 {
+        self.table.flags
+    }
+
+    /// Returns an iterator over the descriptors of the table. They cover the
+    /// runtime regions of the memory map, with their `RO` and `XP`
+    /// attributes.
+    pub fn iter(&self) -> impl Iterator<Item = MemoryDescriptor> {
+        self.descriptors
+            .chunks_exact(self.table.descriptor_size as usize)
+            .map(|descriptor| {
+                // The size of the descriptors is checked when the table is
+                // created.
+                let descriptor = unsafe {
+                    core::ptr::read_unaligned(
+                        descriptor.as_ptr() as *const EfiMemoryDescriptor
+                    )
+                };
+                MemoryDescriptor { descriptor }
+            })
+    }
+}
+
+/// Represents the UEFI memory map. It is stored in a pool of type
+/// `LoaderData`, so it can be used after exiting the boot services.
+#[derive(Debug)]
+pub struct MemoryMap {
+    /// Pool holding the `EFI_MEMORY_DESCRIPTOR` structures.
+    buffer: Ptr,
+
+    /// Size of the pool in bytes.
+    buffer_size: usize,
+
+    /// Size of the memory map in bytes.
+    map_size: usize,
+
+    /// Size of each descriptor in bytes. It can be bigger than
+    /// `size_of::<EfiMemoryDescriptor>()`.
+    descriptor_size: usize,
+
+    /// Warning status code returned by the firmware when the memory map was
+    /// retrieved.
+    warning: Option<StatusWarning>,
+}
+
+impl MemoryMap {
+    /// Calls `EFI_BOOT_SERVICES.GetMemoryMap()` to fill the buffer of the
+    /// `MemoryMap`. It returns the map key of the memory map.
+    fn fill(&mut self, boot_services: &BootServices) -> Result<usize, Error> {
+        let mut map_size = self.buffer_size;
+        let mut map_key = 0usize;
+        let mut descriptor_version = 0u32;
+
+        // Call `EFI_BOOT_SERVICES.GetMemoryMap()`.
+        let status = (boot_services.boot_services.get_memory_map)(
+            &mut map_size,
+            self.buffer.0 as *mut u8,
+            &mut map_key,
+            &mut self.descriptor_size,
+            &mut descriptor_version,
+        );
+
+        // Return with error in the case of error status codes. Warnings are
+        // kept, given that the buffer is filled anyway. On `BufferTooSmall`,
+        // `map_size` is the required size.
+        match status.into() {
+            Status::Success => self.warning = None,
+            Status::Warning(warn) => self.warning = Some(warn),
+            Status::Error(StatusError::BufferTooSmall) => {
+                self.map_size = map_size;
+                return Err(StatusError::BufferTooSmall.into());
+            }
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        self.map_size = map_size;
+        Ok(map_key)
+    }
+
+    /// Returns the number of descriptors.
+    pub fn len(&self) -> usize {
+        match self.descriptor_size {
+            0 => 0,
+            descriptor_size => self.map_size / descriptor_size,
+        }
+    }
+
+    /// Returns the warning status code returned by the firmware when the
+    /// memory map was retrieved, if any.
+    pub fn warning(&self) -> Option<StatusWarning> {
+        self.warning
+    }
+
+    /// Returns `true` if the memory map does not contain descriptors.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns an iterator over the descriptors of the memory map.
+    pub fn iter(&self) -> impl Iterator<Item = MemoryDescriptor> + '_ {
+        (0..self.len()).map(move |idx| {
+            // Read the `EfiMemoryDescriptor`. The buffer is owned by the
+            // `MemoryMap` and `idx` is in bounds.
+            let descriptor = unsafe {
+                let descriptor_ptr = (self.buffer.0
+                    + idx * self.descriptor_size)
+                    as *const EfiMemoryDescriptor;
+                core::ptr::read_unaligned(descriptor_ptr)
+            };
+            MemoryDescriptor { descriptor }
+        })
+    }
+
+    /// Returns a `RangeSet` containing the memory blocks that are available
+    /// once the boot services have been exited.
+    pub fn available_memory(&self) -> Result<RangeSet, Error> {
+        let mut ret = RangeSet::new();
+
+        for descriptor in self.iter() {
+            // Add the memory block into the `RangeSet` if the memory is
+            // avaiable.
+            match descriptor.memory_type() {
+                MemoryType::BootServicesCode
+                | MemoryType::BootServicesData
+                | MemoryType::ConventionalMemory
+                | MemoryType::ACPIReclaimMemory => {
+                    // Bogus descriptors are skipped.
+                    if let Some(range) = descriptor.range() {
+                        ret.insert(range)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(ret)
+    }
+
+    /// Frees the buffer of the memory map. It must be called before exiting
+    /// the boot services. Otherwise, the buffer is leaked.
+    pub fn free(self, boot_services: &BootServices) -> Result<(), Error> {
+        // The buffer was allocated by `get_memory_map` and `self` is consumed.
+        unsafe { boot_services.free_pool(self.buffer) }
+    }
+}
+
+/// Returns a tuple with the current memory map and its map key. This tuple
+/// has the form `(memory_map, map_key)`.
+///
+/// Freeing the `MemoryMap` changes the memory map. Thus, it invalidates the
+/// map key.
+///
+/// # Errors
+///
+/// If the buffer of the memory map cannot be allocated, the function returns
+/// `Error::BufferTooSmall`.
+pub fn get_memory_map(
+    boot_services: &BootServices,
+) -> Result<(MemoryMap, usize), Error> {
+    let mut memory_map = MemoryMap {
+        buffer: Ptr::default(),
+        buffer_size: 0,
+        map_size: 0,
+        descriptor_size: 0,
+        warning: None,
+    };
+
+    // The first call, with an empty buffer, returns the required size.
+    match memory_map.fill(boot_services) {
+        Ok(map_key) => return Ok((memory_map, map_key)),
+        Err(Error::StatusError(StatusError::BufferTooSmall)) => {}
+        Err(err) => return Err(err),
+    }
+
+    loop {
+        let buffer_size = memory_map.map_size
+            + MEMORY_MAP_SLACK * memory_map.descriptor_size;
+        memory_map.buffer = boot_services
+            .allocate_pool(MemoryType::LoaderData, buffer_size)
+            .or(Err(Error::BufferTooSmall))?;
+        memory_map.buffer_size = buffer_size;
+
+        match memory_map.fill(boot_services) {
+            Ok(map_key) => return Ok((memory_map, map_key)),
+            Err(err) => {
+                // The buffer was allocated above and it is not used anymore.
+                unsafe { boot_services.free_pool(memory_map.buffer)? };
+
+                // Try again if the memory map grew.
+                if !matches!(
+                    err,
+                    Error::StatusError(StatusError::BufferTooSmall)
+                ) {
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
 /// Returns a tuple with a `RangeSet` containing the available memory
 /// blocks and the map key of the current memory map. This tuple has the
 /// form `(available_memory, map_key)`.
+///
+/// The buffer of the memory map is not freed, so the map key stays valid.
 pub fn get_available_memory(
     boot_services: &BootServices,
 ) -> Result<(RangeSet, usize), Error> {
-    // Allocate the arguments of the boot service.
-    const BUFFER_SIZE: usize = 1024 * 32;
-    let mut memory_map_size = BUFFER_SIZE;
-    let mut memory_map = [0u8; BUFFER_SIZE];
-    let mut map_key = 0usize;
-    let mut descriptor_size = 0usize;
-    let mut descriptor_version = 0u32;
-
-    // Call `EFI_BOOT_SERVICES.GetMemoryMap()`.
-    let status = (boot_services.boot_services.get_memory_map)(
-        &mut memory_map_size,
-        memory_map.as_mut_ptr(),
-        &mut map_key,
-        &mut descriptor_size,
-        &mut descriptor_version,
-    );
-
-    // Return with error in the case of warning and error status codes.
-    match status.into() {
-        Status::Success => {}
-        Status::Warning(warn) => return Err(warn.into()),
-        Status::Error(err) => return Err(err.into()),
-    }
-
-    // Fill the `RangeSet` to be returned.
-    let mut ret = RangeSet::new();
-    let mut idx = 0;
-    while (idx + 1) * descriptor_size <= memory_map_size {
-        // Read the `EfiMemoryDescriptor`.
-        let descriptor = unsafe {
-            let descriptor_ptr = memory_map.as_ptr().add(idx * descriptor_size)
-                as *const EfiMemoryDescriptor;
-            core::ptr::read(descriptor_ptr)
-        };
-
-        // Add the memory block into the `RangeSet` if the memory is
-        // avaiable.
-        match MemoryType::from(descriptor.memory_type) {
-            MemoryType::BootServicesCode
-            | MemoryType::BootServicesData
-            | MemoryType::ConventionalMemory
-            | MemoryType::ACPIReclaimMemory => {
-                let start = descriptor.physical_start.0;
-                let size = descriptor.number_of_pages * 0x1000;
-                let end = start + size - 1;
-                ret.insert(Range::new(start, end)?)?;
+    let (memory_map, map_key) = get_memory_map(boot_services)?;
+    Ok((memory_map.available_memory()?, map_key))
+}
+
+/// Maximum number of attempts to exit the boot services performed by
+/// `exit_boot_services_with_memory_map`.
+const EXIT_BOOT_SERVICES_ATTEMPTS: usize = 8;
+
+/// Exits the boot services and returns a tuple with the final memory map and
+/// the warning status code returned by `ExitBootServices()`, if any. This
+/// tuple has the form `(memory_map, warning)`.
+///
+/// The memory map can change between getting it and exiting the boot
+/// services, in which case `ExitBootServices()` fails with
+/// `InvalidParameter`. As required by the UEFI specification, the memory map
+/// is fetched again and the exit is retried.
+pub fn exit_boot_services_with_memory_map(
+    boot_services: &BootServices,
+    image_handle: Handle,
+) -> Result<(MemoryMap, Option<StatusWarning>), Error> {
+    let (mut memory_map, mut map_key) = get_memory_map(boot_services)?;
+
+    let mut attempts = 1;
+    loop {
+        match boot_services.exit_boot_services(image_handle, map_key) {
+            Ok(warning) => return Ok((memory_map, warning)),
+            Err(Error::StatusError(StatusError::InvalidParameter))
+                if attempts < EXIT_BOOT_SERVICES_ATTEMPTS =>
+            {
+                attempts += 1;
             }
-            _ => {}
+            Err(err) => return Err(err),
         }
 
-        idx += 1;
+        // After a failed `ExitBootServices()`, only `GetMemoryMap()` can be
+        // called. So, the buffer of the memory map is reused.
+        map_key = memory_map.fill(boot_services)?;
     }
-
-    Ok((ret, map_key))
 }
@@ -0,0 +1,72 @@
+//! This module provides access to the random number generator of the
+//! platform, using the EFI RNG protocol.
+//!
+//! The protocol is owned by the firmware. Thus, it cannot be used after
+//! exiting the boot services.
+
+use core::marker::PhantomData;
+
+use crate::{BootServices, EfiGuid, EfiStatus, Error, Protocol, Ptr, Status};
+
+/// The `EFI_RNG_PROTOCOL` type of the UEFI specification.
+#[repr(C)]
+struct EfiRngProtocol {
+    get_info: Ptr,
+    get_rng: extern "efiapi" fn(
+        *mut EfiRngProtocol,
+        *const EfiGuid,
+        usize,
+        *mut u8,
+    ) -> EfiStatus,
+}
+
+/// Represents the RNG protocol. It is retrieved with
+/// `BootServices::locate_protocol`.
+pub struct Rng<'a> {
+    /// The `EFI_RNG_PROTOCOL` instance.
+    protocol: *mut EfiRngProtocol,
+
+    /// The protocol cannot outlive the boot services.
+    _boot_services: PhantomData<&'a BootServices>,
+}
+
+impl<'a> Protocol<'a> for Rng<'a> {
+    const GUID: EfiGuid = EfiGuid::new(
+        0x3152bca5,
+        0xeade,
+        0x433d,
+        [0x86, 0x2e, 0xc0, 0x1c, 0xdc, 0x29, 0x1f, 0x44],
+    );
+
+    unsafe fn from_interface(interface: Ptr) -> Self {
+        Rng {
+            protocol: interface.0 as *mut EfiRngProtocol,
+            _boot_services: PhantomData,
+        }
+    }
+}
+
+impl Rng<'_> {
+    /// Fills `buf` with random bytes, using the default algorithm of the
+    /// firmware.
+    pub fn get_rng(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        // Call `EFI_RNG_PROTOCOL.GetRNG()`.
+        let status = unsafe {
+            ((*self.protocol).get_rng)(
+                self.protocol,
+                core::ptr::null(),
+                buf.len(),
+                buf.as_mut_ptr(),
+            )
+        };
+
+        // Return with error in the case of warning and error status codes.
+        match status.into() {
+            Status::Success => {}
+            Status::Warning(warn) => return Err(warn.into()),
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        Ok(())
+    }
+}
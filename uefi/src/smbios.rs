@@ -0,0 +1,457 @@
+//! This module provides access to the SMBIOS structures.
+
+use core::convert::TryInto;
+
+use crate::utils;
+use crate::{Error, Ptr};
+
+/// Anchor string of the SMBIOS 2.x (32-bit) entry point.
+const SMBIOS_ANCHOR: &[u8] = b"_SM_";
+
+/// Intermediate anchor string of the SMBIOS 2.x (32-bit) entry point.
+const SMBIOS_INTERMEDIATE_ANCHOR: &[u8] = b"_DMI_";
+
+/// Anchor string of the SMBIOS 3.x (64-bit) entry point.
+const SMBIOS3_ANCHOR: &[u8] = b"_SM3_";
+
+/// Size of the header of an SMBIOS structure.
+const SMBIOS_HEADER_SIZE: usize = core::mem::size_of::<SmbiosHeader>();
+
+/// Type of the structure that marks the end of the structure table.
+const SMBIOS_END_OF_TABLE: u8 = 127;
+
+/// SMBIOS 2.x (32-bit) entry point structure.
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+struct SmbiosEntryPoint {
+    anchor: [u8; 4],
+    checksum: u8,
+    length: u8,
+    major_version: u8,
+    minor_version: u8,
+    max_structure_size: u16,
+    entry_point_revision: u8,
+    formatted_area: [u8; 5],
+    intermediate_anchor: [u8; 5],
+    intermediate_checksum: u8,
+    table_length: u16,
+    table_address: u32,
+    number_of_structures: u16,
+    bcd_revision: u8,
+}
+
+/// SMBIOS 3.x (64-bit) entry point structure.
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+struct Smbios3EntryPoint {
+    anchor: [u8; 5],
+    checksum: u8,
+    length: u8,
+    major_version: u8,
+    minor_version: u8,
+    docrev: u8,
+    entry_point_revision: u8,
+    reserved: u8,
+    table_max_size: u32,
+    table_address: u64,
+}
+
+/// Header of an SMBIOS structure.
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+struct SmbiosHeader {
+    ty: u8,
+    length: u8,
+    handle: u16,
+}
+
+/// Represents the SMBIOS structure table.
+#[derive(Debug)]
+pub struct Smbios {
+    major_version: u8,
+    minor_version: u8,
+    table: &'static [u8],
+}
+
+impl Smbios {
+    /// Creates a new `Smbios` from a given pointer to an SMBIOS 2.x (32-bit)
+    /// entry point.
+    ///
+    /// # Errors
+    ///
+    /// This function returns error if the pointer does not point to a valid
+    /// SMBIOS 2.x entry point.
+    ///
+    /// # Safety
+    ///
+    /// The `Smbios` structure is created using a pointer. Thus, this function
+    /// is considered unsafe.
+    pub unsafe fn new(entry_point_ptr: Ptr) -> Result<Self, Error> {
+        let entry_point_ptr = entry_point_ptr.0 as *const SmbiosEntryPoint;
+        let entry_point = core::ptr::read_unaligned(entry_point_ptr);
+
+        // Check entry point's anchors.
+        if entry_point.anchor != SMBIOS_ANCHOR
+            || entry_point.intermediate_anchor != SMBIOS_INTERMEDIATE_ANCHOR
+        {
+            return Err(Error::InvalidSignature);
+        }
+
+        // Check entry point's checksum.
+        let checksum = utils::add_bytes(
+            entry_point_ptr as *const u8,
+            entry_point.length as usize,
+        );
+        if checksum != 0 {
+            return Err(Error::InvalidCheckSum);
+        }
+
+        let table_address: Ptr = entry_point.table_address.try_into()?;
+        let table = core::slice::from_raw_parts(
+            table_address.0 as *const u8,
+            entry_point.table_length as usize,
+        );
+
+        Ok(Smbios {
+            major_version: entry_point.major_version,
+            minor_version: entry_point.minor_version,
+            table,
+        })
+    }
+
+    /// Creates a new `Smbios` from a given pointer to an SMBIOS 3.x (64-bit)
+    /// entry point.
+    ///
+    /// # Errors
+    ///
+    /// This function returns error if the pointer does not point to a valid
+    /// SMBIOS 3.x entry point.
+    ///
+    /// # Safety
+    ///
+    /// The `Smbios` structure is created using a pointer. Thus, this function
+    /// is considered unsafe.
+    pub unsafe fn new64(entry_point_ptr: Ptr) -> Result<Self, Error> {
+        let entry_point_ptr = entry_point_ptr.0 as *const Smbios3EntryPoint;
+        let entry_point = core::ptr::read_unaligned(entry_point_ptr);
+
+        // Check entry point's anchor.
+        if entry_point.anchor != SMBIOS3_ANCHOR {
+            return Err(Error::InvalidSignature);
+        }
+
+        // Check entry point's checksum.
+        let checksum = utils::add_bytes(
+            entry_point_ptr as *const u8,
+            entry_point.length as usize,
+        );
+        if checksum != 0 {
+            return Err(Error::InvalidCheckSum);
+        }
+
+        // The table size is a maximum. The table ends with the end-of-table
+        // structure.
+        let table_address: Ptr = entry_point.table_address.try_into()?;
+        let table = core::slice::from_raw_parts(
+            table_address.0 as *const u8,
+            entry_point.table_max_size as usize,
+        );
+
+        Ok(Smbios {
+            major_version: entry_point.major_version,
+            minor_version: entry_point.minor_version,
+            table,
+        })
+    }
+
+    /// Returns the SMBIOS version with the form `(major, minor)`.
+    pub fn version(&self) -> (u8, u8) {
+        (self.major_version, self.minor_version)
+    }
+
+    /// Returns an iterator over the structures of the table.
+    pub fn structures(&self) -> Structures<'_> {
+        Structures {
+            table: self.table,
+            done: false,
+        }
+    }
+
+    /// Returns the BIOS information (type 0) structure.
+    pub fn bios_info(&self) -> Result<BiosInfo<'_>, Error> {
+        self.find(BiosInfo::TYPE)
+            .map(|structure| BiosInfo { structure })
+    }
+
+    /// Returns the system information (type 1) structure.
+    pub fn system_info(&self) -> Result<SystemInfo<'_>, Error> {
+        self.find(SystemInfo::TYPE)
+            .map(|structure| SystemInfo { structure })
+    }
+
+    /// Returns an iterator over the memory device (type 17) structures.
+    pub fn memory_devices(
+        &self,
+    ) -> impl Iterator<Item = Result<MemoryDevice<'_>, Error>> {
+        self.structures().filter_map(|structure| match structure {
+            Ok(structure) if structure.ty() == MemoryDevice::TYPE => {
+                Some(Ok(MemoryDevice { structure }))
+            }
+            Ok(_) => None,
+            Err(err) => Some(Err(err)),
+        })
+    }
+
+    /// Returns the first structure of type `ty`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Error::NotFound` if there is not a
+    /// structure of the provided type.
+    fn find(&self, ty: u8) -> Result<Structure<'_>, Error> {
+        for structure in self.structures() {
+            let structure = structure?;
+            if structure.ty() == ty {
+                return Ok(structure);
+            }
+        }
+
+        Err(Error::NotFound)
+    }
+}
+
+/// Iterator over the structures of the SMBIOS structure table.
+pub struct Structures<'a> {
+    /// Remaining part of the table.
+    table: &'a [u8],
+
+    /// The end of the table has been reached or an error occurred.
+    done: bool,
+}
+
+impl<'a> Iterator for Structures<'a> {
+    type Item = Result<Structure<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.table.len() < SMBIOS_HEADER_SIZE {
+            return None;
+        }
+
+        // Parse the header of the structure.
+        let hdr = unsafe {
+            core::ptr::read_unaligned(
+                self.table.as_ptr() as *const SmbiosHeader
+            )
+        };
+        let length = hdr.length as usize;
+        if length < SMBIOS_HEADER_SIZE || length > self.table.len() {
+            self.done = true;
+            return Some(Err(Error::InvalidSmbiosData));
+        }
+
+        // The formatted area is followed by the string-set, that is
+        // terminated by two nul bytes.
+        let strings_len =
+            match self.table[length..].windows(2).position(|w| w == [0, 0]) {
+                Some(pos) => pos,
+                None => {
+                    self.done = true;
+                    return Some(Err(Error::InvalidSmbiosData));
+                }
+            };
+
+        let structure = Structure {
+            hdr,
+            data: &self.table[..length],
+            strings: &self.table[length..length + strings_len],
+        };
+        self.table = &self.table[length + strings_len + 2..];
+
+        if hdr.ty == SMBIOS_END_OF_TABLE {
+            self.done = true;
+            return None;
+        }
+
+        Some(Ok(structure))
+    }
+}
+
+/// Represents an SMBIOS structure.
+#[derive(Debug, Clone, Copy)]
+pub struct Structure<'a> {
+    hdr: SmbiosHeader,
+
+    /// Formatted area of the structure, including the header.
+    data: &'a [u8],
+
+    /// String-set of the structure, without the final nul byte.
+    strings: &'a [u8],
+}
+
+impl<'a> Structure<'a> {
+    /// Type of the structure.
+    pub fn ty(&self) -> u8 {
+        self.hdr.ty
+    }
+
+    /// Handle of the structure.
+    pub fn handle(&self) -> u16 {
+        self.hdr.handle
+    }
+
+    /// Formatted area of the structure, including the header.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Returns the string with the 1-based index `idx`. It returns `None` if
+    /// `idx` is 0, the string does not exist or it is not valid UTF-8.
+    pub fn string(&self, idx: u8) -> Option<&'a str> {
+        if idx == 0 || self.strings.is_empty() {
+            return None;
+        }
+        let s = self.strings.split(|&b| b == 0).nth(idx as usize - 1)?;
+        core::str::from_utf8(s).ok()
+    }
+
+    /// Returns the byte at offset `off` of the formatted area.
+    fn u8_at(&self, off: usize) -> Option<u8> {
+        self.data.get(off).copied()
+    }
+
+    /// Returns the little-endian word at offset `off` of the formatted area.
+    fn u16_at(&self, off: usize) -> Option<u16> {
+        let bytes = self.data.get(off..off + 2)?;
+        Some(u16::from_le_bytes(bytes.try_into().ok()?))
+    }
+
+    /// Returns the little-endian dword at offset `off` of the formatted area.
+    fn u32_at(&self, off: usize) -> Option<u32> {
+        let bytes = self.data.get(off..off + 4)?;
+        Some(u32::from_le_bytes(bytes.try_into().ok()?))
+    }
+
+    /// Returns the string referenced by the byte at offset `off` of the
+    /// formatted area.
+    fn string_at(&self, off: usize) -> Option<&'a str> {
+        self.string(self.u8_at(off)?)
+    }
+}
+
+/// Represents the BIOS information (type 0) structure.
+#[derive(Debug, Clone, Copy)]
+pub struct BiosInfo<'a> {
+    structure: Structure<'a>,
+}
+
+impl<'a> BiosInfo<'a> {
+    /// Type of the BIOS information structure.
+    const TYPE: u8 = 0;
+
+    /// BIOS vendor's name.
+    pub fn vendor(&self) -> Option<&'a str> {
+        self.structure.string_at(0x04)
+    }
+
+    /// BIOS version.
+    pub fn version(&self) -> Option<&'a str> {
+        self.structure.string_at(0x05)
+    }
+
+    /// BIOS release date.
+    pub fn release_date(&self) -> Option<&'a str> {
+        self.structure.string_at(0x08)
+    }
+}
+
+/// Represents the system information (type 1) structure.
+#[derive(Debug, Clone, Copy)]
+pub struct SystemInfo<'a> {
+    structure: Structure<'a>,
+}
+
+impl<'a> SystemInfo<'a> {
+    /// Type of the system information structure.
+    const TYPE: u8 = 1;
+
+    /// System manufacturer.
+    pub fn manufacturer(&self) -> Option<&'a str> {
+        self.structure.string_at(0x04)
+    }
+
+    /// Product name.
+    pub fn product_name(&self) -> Option<&'a str> {
+        self.structure.string_at(0x05)
+    }
+
+    /// Product version.
+    pub fn version(&self) -> Option<&'a str> {
+        self.structure.string_at(0x06)
+    }
+
+    /// Serial number.
+    pub fn serial_number(&self) -> Option<&'a str> {
+        self.structure.string_at(0x07)
+    }
+
+    /// System UUID, as stored in the structure.
+    pub fn uuid(&self) -> Option<[u8; 16]> {
+        self.structure.data.get(0x08..0x18)?.try_into().ok()
+    }
+}
+
+/// Represents the memory device (type 17) structure.
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryDevice<'a> {
+    structure: Structure<'a>,
+}
+
+impl<'a> MemoryDevice<'a> {
+    /// Type of the memory device structure.
+    const TYPE: u8 = 17;
+
+    /// Size of the memory device in bytes. It is `Some(0)` if no memory
+    /// device is installed in the socket and `None` if the size is unknown.
+    pub fn size(&self) -> Option<u64> {
+        match self.structure.u16_at(0x0c)? {
+            0xffff => None,
+            // The size is stored in the Extended Size field, in MiB.
+            0x7fff => {
+                let size = self.structure.u32_at(0x1c)? & 0x7fff_ffff;
+                Some(u64::from(size) << 20)
+            }
+            // Bit 15 selects the granularity: KiB if set, MiB otherwise.
+            size if size & 0x8000 != 0 => Some(u64::from(size & 0x7fff) << 10),
+            size => Some(u64::from(size) << 20),
+        }
+    }
+
+    /// Maximum speed of the device in MT/s. It is `None` if unknown.
+    pub fn speed(&self) -> Option<u16> {
+        match self.structure.u16_at(0x15)? {
+            0 => None,
+            speed => Some(speed),
+        }
+    }
+
+    /// Physically-labeled socket or board position of the device (e.g.
+    /// `DIMM 0`).
+    pub fn device_locator(&self) -> Option<&'a str> {
+        self.structure.string_at(0x10)
+    }
+
+    /// Physically-labeled bank of the device (e.g. `Bank 0`).
+    pub fn bank_locator(&self) -> Option<&'a str> {
+        self.structure.string_at(0x11)
+    }
+
+    /// Manufacturer of the device.
+    pub fn manufacturer(&self) -> Option<&'a str> {
+        self.structure.string_at(0x17)
+    }
+
+    /// Part number of the device.
+    pub fn part_number(&self) -> Option<&'a str> {
+        self.structure.string_at(0x1a)
+    }
+}
@@ -0,0 +1,334 @@
+//! This module provides access to the TPM 2.0 device, using the EFI TCG2
+//! protocol. It allows to measure data into the PCRs and to locate the event
+//! log built by the firmware.
+//!
+//! Reference:
+//! - [TCG EFI Protocol Specification](https://trustedcomputinggroup.org/resource/tcg-efi-protocol-specification/)
+//!
+//! The protocol is owned by the firmware. Thus, it cannot be used after
+//! exiting the boot services.
+
+use core::marker::PhantomData;
+
+use mm::PhysAddr;
+
+use crate::{BootServices, EfiGuid, EfiStatus, Error, Protocol, Ptr, Status};
+
+/// Event log format of TPM 1.2 (SHA-1 only).
+pub const EVENT_LOG_FORMAT_TCG_1_2: u32 = 0x1;
+
+/// Crypto-agile event log format of TPM 2.0.
+pub const EVENT_LOG_FORMAT_TCG_2: u32 = 0x2;
+
+/// Event type used to measure the data loaded by the boot loader (e.g. the
+/// kernel).
+pub const EV_IPL: u32 = 0xd;
+
+/// Version of the event header.
+const EFI_TCG2_EVENT_HEADER_VERSION: u16 = 1;
+
+/// Maximum size of the event data passed to `hash_log_extend_event`.
+const EVENT_DATA_LEN: usize = 256;
+
+/// The `EFI_TCG2_VERSION` type of the TCG EFI Protocol Specification.
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C, packed)]
+struct EfiTcg2Version {
+    major: u8,
+    minor: u8,
+}
+
+/// The `EFI_TCG2_BOOT_SERVICE_CAPABILITY` type of the TCG EFI Protocol
+/// Specification.
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C, packed)]
+struct EfiTcg2BootServiceCapability {
+    size: u8,
+    structure_version: EfiTcg2Version,
+    protocol_version: EfiTcg2Version,
+    hash_algorithm_bitmap: u32,
+    supported_event_logs: u32,
+    tpm_present_flag: u8,
+    max_command_size: u16,
+    max_response_size: u16,
+    manufacturer_id: u32,
+    number_of_pcr_banks: u32,
+    active_pcr_banks: u32,
+}
+
+/// The `EFI_TCG2_EVENT_HEADER` type of the TCG EFI Protocol Specification.
+#[repr(C, packed)]
+struct EfiTcg2EventHeader {
+    header_size: u32,
+    header_version: u16,
+    pcr_index: u32,
+    event_type: u32,
+}
+
+/// The `EFI_TCG2_EVENT` type of the TCG EFI Protocol Specification, followed
+/// by the event data.
+#[repr(C, packed)]
+struct EfiTcg2Event {
+    size: u32,
+    header: EfiTcg2EventHeader,
+    event: [u8; EVENT_DATA_LEN],
+}
+
+/// The `EFI_TCG2_PROTOCOL` type of the TCG EFI Protocol Specification.
+#[repr(C)]
+struct EfiTcg2Protocol {
+    get_capability: extern "efiapi" fn(
+        *mut EfiTcg2Protocol,
+        *mut EfiTcg2BootServiceCapability,
+    ) -> EfiStatus,
+    get_event_log: extern "efiapi" fn(
+        *mut EfiTcg2Protocol,
+        u32,
+        *mut u64,
+        *mut u64,
+        *mut u8,
+    ) -> EfiStatus,
+    hash_log_extend_event: extern "efiapi" fn(
+        *mut EfiTcg2Protocol,
+        u64,
+        u64,
+        u64,
+        *const EfiTcg2Event,
+    ) -> EfiStatus,
+    submit_command: Ptr,
+    get_active_pcr_banks: Ptr,
+    set_active_pcr_banks: Ptr,
+    get_result_of_set_active_pcr_banks: Ptr,
+}
+
+/// Represents the capabilities of the TPM and the TCG2 protocol.
+#[derive(Debug, Clone, Copy)]
+pub struct Capability {
+    /// The `EFI_TCG2_BOOT_SERVICE_CAPABILITY` structure returned by the
+    /// firmware.
+    capability: EfiTcg2BootServiceCapability,
+}
+
+impl Capability {
+    /// Returns `true` if a TPM is present.
+    pub fn tpm_present(&self) -> bool {
+        self.capability.tpm_present_flag != 0
+    }
+
+    /// Version of the protocol with the form `(major, minor)`.
+    pub fn protocol_version(&self) -> (u8, u8) {
+        let version = self.capability.protocol_version;
+        (version.major, version.minor)
+    }
+
+    /// Bitmap of the hash algorithms supported by the TPM (e.g. bit 1 for
+    /// SHA-256).
+    pub fn hash_algorithm_bitmap(&self) -> u32 {
+        self.capability.hash_algorithm_bitmap
+    }
+
+    /// Bitmap of the supported event log formats. See
+    /// `EVENT_LOG_FORMAT_TCG_1_2` and `EVENT_LOG_FORMAT_TCG_2`.
+    pub fn supported_event_logs(&self) -> u32 {
+        self.capability.supported_event_logs
+    }
+
+    /// Vendor ID of the TPM manufacturer.
+    pub fn manufacturer_id(&self) -> u32 {
+        self.capability.manufacturer_id
+    }
+
+    /// Number of PCR banks supported by the TPM.
+    pub fn number_of_pcr_banks(&self) -> u32 {
+        self.capability.number_of_pcr_banks
+    }
+
+    /// Bitmap of the active PCR banks, using the same encoding as
+    /// `hash_algorithm_bitmap`.
+    pub fn active_pcr_banks(&self) -> u32 {
+        self.capability.active_pcr_banks
+    }
+}
+
+/// Represents the location of the event log built by the firmware. The event
+/// log is stored in boot services data memory, so it must be copied before
+/// that memory is reused.
+#[derive(Debug, Clone, Copy)]
+pub struct EventLog {
+    format: u32,
+    location: PhysAddr,
+    last_entry: PhysAddr,
+    truncated: bool,
+}
+
+impl EventLog {
+    /// Format of the event log. See `EVENT_LOG_FORMAT_TCG_1_2` and
+    /// `EVENT_LOG_FORMAT_TCG_2`.
+    pub fn format(&self) -> u32 {
+        self.format
+    }
+
+    /// Physical address of the first entry of the event log.
+    pub fn location(&self) -> PhysAddr {
+        self.location
+    }
+
+    /// Physical address of the last entry of the event log. It is 0 if the
+    /// event log is empty.
+    pub fn last_entry(&self) -> PhysAddr {
+        self.last_entry
+    }
+
+    /// Returns `true` if the event log is missing entries because it ran out
+    /// of space.
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+}
+
+/// Represents the TCG2 protocol. It is retrieved with
+/// `BootServices::locate_protocol`. If the firmware does not provide it
+/// (e.g. there is no TPM 2.0), `Error::StatusError(StatusError::NotFound)`
+/// is returned.
+pub struct Tcg2<'a> {
+    /// The `EFI_TCG2_PROTOCOL` instance.
+    protocol: *mut EfiTcg2Protocol,
+
+    /// The protocol cannot outlive the boot services.
+    _boot_services: PhantomData<&'a BootServices>,
+}
+
+impl<'a> Protocol<'a> for Tcg2<'a> {
+    const GUID: EfiGuid = EfiGuid::new(
+        0x607f766c,
+        0x7455,
+        0x42be,
+        [0x93, 0x0b, 0xe4, 0xd7, 0x6d, 0xb2, 0x72, 0x0f],
+    );
+
+    unsafe fn from_interface(interface: Ptr) -> Self {
+        Tcg2 {
+            protocol: interface.0 as *mut EfiTcg2Protocol,
+            _boot_services: PhantomData,
+        }
+    }
+}
+
+impl Tcg2<'_> {
+    /// Returns the capabilities of the TPM and the protocol.
+    pub fn capability(&self) -> Result<Capability, Error> {
+        let mut capability = EfiTcg2BootServiceCapability {
+            size: core::mem::size_of::<EfiTcg2BootServiceCapability>() as u8,
+            ..Default::default()
+        };
+
+        // Call `EFI_TCG2_PROTOCOL.GetCapability()`.
+        let status = unsafe {
+            ((*self.protocol).get_capability)(self.protocol, &mut capability)
+        };
+
+        // Return with error in the case of warning and error status codes.
+        match status.into() {
+            Status::Success => {}
+            Status::Warning(warn) => return Err(warn.into()),
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        Ok(Capability { capability })
+    }
+
+    /// Returns the location of the event log. The crypto-agile format is used
+    /// if it is supported by the firmware.
+    pub fn event_log(&self) -> Result<EventLog, Error> {
+        let format = if self.capability()?.supported_event_logs()
+            & EVENT_LOG_FORMAT_TCG_2
+            != 0
+        {
+            EVENT_LOG_FORMAT_TCG_2
+        } else {
+            EVENT_LOG_FORMAT_TCG_1_2
+        };
+
+        let mut location = 0u64;
+        let mut last_entry = 0u64;
+        let mut truncated = 0u8;
+
+        // Call `EFI_TCG2_PROTOCOL.GetEventLog()`.
+        let status = unsafe {
+            ((*self.protocol).get_event_log)(
+                self.protocol,
+                format,
+                &mut location,
+                &mut last_entry,
+                &mut truncated,
+            )
+        };
+
+        // Return with error in the case of warning and error status codes.
+        match status.into() {
+            Status::Success => {}
+            Status::Warning(warn) => return Err(warn.into()),
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        Ok(EventLog {
+            format,
+            location: PhysAddr::new(location),
+            last_entry: PhysAddr::new(last_entry),
+            truncated: truncated != 0,
+        })
+    }
+
+    /// Measures `data` into the PCR `pcr_index` and adds an event of type
+    /// `event_type`, with the event data `event`, to the event log.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Error::BufferTooSmall` if `event` is longer
+    /// than 256 bytes.
+    pub fn hash_log_extend_event(
+        &mut self,
+        pcr_index: u32,
+        event_type: u32,
+        data: &[u8],
+        event: &[u8],
+    ) -> Result<(), Error> {
+        if event.len() > EVENT_DATA_LEN {
+            return Err(Error::BufferTooSmall);
+        }
+
+        let mut tcg2_event = EfiTcg2Event {
+            size: (core::mem::size_of::<u32>()
+                + core::mem::size_of::<EfiTcg2EventHeader>()
+                + event.len()) as u32,
+            header: EfiTcg2EventHeader {
+                header_size: core::mem::size_of::<EfiTcg2EventHeader>() as u32,
+                header_version: EFI_TCG2_EVENT_HEADER_VERSION,
+                pcr_index,
+                event_type,
+            },
+            event: [0u8; EVENT_DATA_LEN],
+        };
+        tcg2_event.event[..event.len()].copy_from_slice(event);
+
+        // Call `EFI_TCG2_PROTOCOL.HashLogExtendEvent()`.
+        let status = unsafe {
+            ((*self.protocol).hash_log_extend_event)(
+                self.protocol,
+                0,
+                data.as_ptr() as u64,
+                data.len() as u64,
+                &tcg2_event,
+            )
+        };
+
+        // Return with error in the case of warning and error status codes.
+        match status.into() {
+            Status::Success => {}
+            Status::Warning(warn) => return Err(warn.into()),
+            Status::Error(err) => return Err(err.into()),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+//! This module provides helpers to work with the UCS-2 strings used by UEFI
+//! (e.g. the firmware vendor, file names and variable names).
+
+use core::fmt;
+
+use crate::Error;
+
+/// Represents a borrowed UCS-2 string, without the terminating nul
+/// character.
+#[derive(Debug, Clone, Copy)]
+pub struct Ucs2Str<'a> {
+    chars: &'a [u16],
+}
+
+impl<'a> Ucs2Str<'a> {
+    /// Creates a new `Ucs2Str` from a slice of UCS-2 characters. The string
+    /// ends at the first nul character, if any.
+    pub fn from_slice(chars: &'a [u16]) -> Self {
+        let len = chars.iter().position(|&c| c == 0).unwrap_or(chars.len());
+        Ucs2Str {
+            chars: &chars[..len],
+        }
+    }
+
+    /// Creates a new `Ucs2Str` from a pointer to a nul-terminated UCS-2
+    /// string.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a nul-terminated UCS-2 string that is valid for
+    /// the lifetime `'a`.
+    pub unsafe fn from_ptr(ptr: *const u16) -> Self {
+        let mut len = 0;
+        while core::ptr::read_unaligned(ptr.add(len)) != 0 {
+            len += 1;
+        }
+        Ucs2Str {
+            chars: core::slice::from_raw_parts(ptr, len),
+        }
+    }
+
+    /// Returns the UCS-2 characters of the string.
+    pub fn as_slice(&self) -> &'a [u16] {
+        self.chars
+    }
+
+    /// Returns the length of the string in UCS-2 characters.
+    pub fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    /// Returns `true` if the string is empty.
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    /// Returns an iterator over the characters of the string. Surrogates,
+    /// which are not valid in UCS-2, are replaced with U+FFFD.
+    pub fn chars(&self) -> impl Iterator<Item = char> + 'a {
+        self.chars.iter().map(|&c| {
+            char::from_u32(c.into()).unwrap_or(char::REPLACEMENT_CHARACTER)
+        })
+    }
+
+    /// Converts the string to UTF-8 into `buf` and returns it as a `&str`.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Error::BufferTooSmall` if `buf` cannot hold the
+    /// converted string.
+    pub fn to_str<'b>(&self, buf: &'b mut [u8]) -> Result<&'b str, Error> {
+        let mut len = 0;
+        for c in self.chars() {
+            let end = len + c.len_utf8();
+            let dst = buf.get_mut(len..end).ok_or(Error::BufferTooSmall)?;
+            c.encode_utf8(dst);
+            len = end;
+        }
+
+        // `buf[..len]` only contains complete UTF-8 sequences.
+        Ok(core::str::from_utf8(&buf[..len]).unwrap())
+    }
+}
+
+impl PartialEq<str> for Ucs2Str<'_> {
+    fn eq(&self, other: &str) -> bool {
+        let mut chars = self.chars();
+        other.chars().all(|c| chars.next() == Some(c))
+            && chars.next().is_none()
+    }
+}
+
+impl PartialEq<&str> for Ucs2Str<'_> {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl fmt::Display for Ucs2Str<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.chars().try_for_each(|c| fmt::Write::write_char(f, c))
+    }
+}
+
+/// Encodes `s` as a nul-terminated UCS-2 string into `buf` and returns the
+/// encoded string. The remaining elements of `buf` are left untouched.
+///
+/// # Errors
+///
+/// This function returns `Error::InvalidString` if `s` contains characters
+/// that cannot be represented in UCS-2 or nul characters, and
+/// `Error::BufferTooSmall` if `buf` cannot hold the string and the
+/// terminating nul character.
+pub fn encode<'b>(s: &str, buf: &'b mut [u16]) -> Result<Ucs2Str<'b>, Error> {
+    let mut len = 0;
+    for c in s.chars() {
+        // Leave room for the nul character.
+        if len + 1 >= buf.len() {
+            return Err(Error::BufferTooSmall);
+        }
+
+        let c = c as u32;
+        if c == 0 || c > 0xffff {
+            return Err(Error::InvalidString);
+        }
+
+        buf[len] = c as u16;
+        len += 1;
+    }
+
+    match buf.get_mut(len) {
+        Some(nul) => *nul = 0,
+        None => return Err(Error::BufferTooSmall),
+    }
+
+    Ok(Ucs2Str { chars: &buf[..len] })
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn test_encode_round_trip() {
+        let mut ucs2 = [0xffff; 16];
+        let s = encode("\\EFI\\expos.éfi", &mut ucs2).unwrap();
+        assert_eq!(s.len(), 14);
+        assert_eq!(s, "\\EFI\\expos.éfi");
+        assert_eq!(ucs2[14], 0);
+        assert_eq!(ucs2[15], 0xffff);
+
+        let mut utf8 = [0; 32];
+        let s = Ucs2Str::from_slice(&ucs2);
+        assert_eq!(s.to_str(&mut utf8).unwrap(), "\\EFI\\expos.éfi");
+        assert_eq!(s.to_string(), "\\EFI\\expos.éfi");
+    }
+
+    #[test]
+    fn test_encode_invalid() {
+        let mut ucs2 = [0; 16];
+        assert!(matches!(
+            encode("smile 😀", &mut ucs2),
+            Err(Error::InvalidString)
+        ));
+        assert!(matches!(
+            encode("a\0b", &mut ucs2),
+            Err(Error::InvalidString)
+        ));
+    }
+
+    #[test]
+    fn test_encode_buffer_too_small() {
+        assert!(matches!(
+            encode("abc", &mut [0; 3]),
+            Err(Error::BufferTooSmall)
+        ));
+        assert!(matches!(encode("", &mut []), Err(Error::BufferTooSmall)));
+        assert_eq!(encode("abc", &mut [0; 4]).unwrap(), "abc");
+    }
+
+    #[test]
+    fn test_surrogates() {
+        // Surrogates, paired or not, are not valid UCS-2.
+        let s = Ucs2Str::from_slice(&[0x61, 0xd800, 0x62, 0xd83d, 0xde00]);
+        let mut utf8 = [0; 32];
+        assert_eq!(s.to_str(&mut utf8).unwrap(), "a\u{fffd}b\u{fffd}\u{fffd}");
+        assert_eq!(s, "a\u{fffd}b\u{fffd}\u{fffd}");
+        assert!(s != "a\u{fffd}b😀");
+    }
+
+    #[test]
+    fn test_from_slice_nul() {
+        // Without a nul character, the string spans the whole slice.
+        let s = Ucs2Str::from_slice(&[0x61, 0x62]);
+        assert_eq!(s.len(), 2);
+        assert_eq!(s, "ab");
+
+        let s = Ucs2Str::from_slice(&[0x61, 0, 0x62]);
+        assert_eq!(s.as_slice(), &[0x61]);
+        assert_eq!(s, "a");
+
+        let s = Ucs2Str::from_slice(&[0, 0x61]);
+        assert!(s.is_empty());
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn test_from_ptr() {
+        let chars = [0x61u16, 0x62, 0];
+        let s = unsafe { Ucs2Str::from_ptr(chars.as_ptr()) };
+        assert_eq!(s, "ab");
+    }
+
+    #[test]
+    fn test_eq_length_mismatch() {
+        let s = Ucs2Str::from_slice(&[0x61, 0x62]);
+        assert!(s != "a");
+        assert!(s != "abc");
+    }
+
+    #[test]
+    fn test_to_str_buffer_too_small() {
+        // U+00E9 takes two bytes in UTF-8.
+        let s = Ucs2Str::from_slice(&[0x61, 0xe9]);
+        assert!(matches!(s.to_str(&mut [0; 2]), Err(Error::BufferTooSmall)));
+        assert_eq!(s.to_str(&mut [0; 3]).unwrap(), "aé");
+    }
+}
@@ -0,0 +1,321 @@
+//! This module provides access to the UEFI variables.
+
+use crate::ucs2::{self, Ucs2Str};
+use crate::{EfiGuid, Error, RuntimeServices, Status, StatusError};
+
+/// Vendor GUID of the variables defined by the UEFI specification (e.g.
+/// `BootOrder`).
+pub const EFI_GLOBAL_VARIABLE: EfiGuid = EfiGuid::new(
+    0x8be4df61,
+    0x93ca,
+    0x11d2,
+    [0xaa, 0x0d, 0x00, 0xe0, 0x98, 0x03, 0x2b, 0x8c],
+);
+
+/// Maximum length of a variable name in UCS-2 characters, including the
+/// terminating nul character.
+const VARIABLE_NAME_LEN: usize = 128;
+
+/// Attributes of a UEFI variable.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+#[repr(transparent)]
+pub struct VariableAttributes(u32);
+
+impl VariableAttributes {
+    /// The variable is stored in non-volatile storage.
+    pub const NON_VOLATILE: VariableAttributes = VariableAttributes(0x1);
+
+    /// The variable is accessible before `ExitBootServices()`.
+    pub const BOOTSERVICE_ACCESS: VariableAttributes = VariableAttributes(0x2);
+
+    /// The variable is accessible after `ExitBootServices()`. It requires
+    /// `BOOTSERVICE_ACCESS` to be set.
+    pub const RUNTIME_ACCESS: VariableAttributes = VariableAttributes(0x4);
+
+    /// The variable is a hardware error record.
+    pub const HARDWARE_ERROR_RECORD: VariableAttributes =
+        VariableAttributes(0x8);
+
+    /// Writes to the variable must be authenticated with a time-based
+    /// signature.
+    pub const TIME_BASED_AUTHENTICATED_WRITE_ACCESS: VariableAttributes =
+        VariableAttributes(0x20);
+
+    /// Writes to the variable append the data to the existing value.
+    pub const APPEND_WRITE: VariableAttributes = VariableAttributes(0x40);
+
+    /// Returns a `VariableAttributes` from its raw representation.
+    pub const fn from_bits(bits: u32) -> Self {
+        VariableAttributes(bits)
+    }
+
+    /// Returns the raw representation of the `VariableAttributes`.
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Returns `true` if all the attributes in `other` are set.
+    pub const fn contains(&self, other: VariableAttributes) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl core::ops::BitOr for VariableAttributes {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        VariableAttributes(self.0 | rhs.0)
+    }
+}
+
+/// Encodes `name` as a nul-terminated UCS-2 string.
+///
+/// # Errors
+///
+/// This function returns `Error::InvalidString` if `name` contains characters
+/// that cannot be represented in UCS-2 or nul characters, and
+/// `Error::BufferTooSmall` if it is too long.
+fn encode_name(name: &str) -> Result<[u16; VARIABLE_NAME_LEN], Error> {
+    let mut buf = [0u16; VARIABLE_NAME_LEN];
+    ucs2::encode(name, &mut buf)?;
+    Ok(buf)
+}
+
+/// Reads the variable `name` of the vendor `vendor` into `buf`. It returns a
+/// tuple with the form `(size, attributes)`, where `size` is the size of the
+/// data.
+///
+/// # Errors
+///
+/// If `buf` is too small, the function returns
+/// `Error::StatusError(StatusError::BufferTooSmall)`. If the variable does not
+/// exist, it returns `Error::StatusError(StatusError::NotFound)`.
+pub fn get_variable(
+    runtime_services: &RuntimeServices,
+    name: &str,
+    vendor: &EfiGuid,
+    buf: &mut [u8],
+) -> Result<(usize, VariableAttributes), Error> {
+    let name = encode_name(name)?;
+    let mut attributes = 0u32;
+    let mut data_size = buf.len();
+
+    // Call `EFI_RUNTIME_SERVICES.GetVariable()`.
+    let status = (runtime_services.runtime_services.get_variable)(
+        name.as_ptr(),
+        vendor,
+        &mut attributes,
+        &mut data_size,
+        buf.as_mut_ptr(),
+    );
+
+    // Return with error in the case of warning and error status codes.
+    match status.into() {
+        Status::Success => {}
+        Status::Warning(warn) => return Err(warn.into()),
+        Status::Error(err) => return Err(err.into()),
+    }
+
+    Ok((data_size, VariableAttributes(attributes)))
+}
+
+/// Sets the value of the variable `name` of the vendor `vendor`. If `data` is
+/// empty and `attributes` does not include `APPEND_WRITE`, the variable is
+/// deleted.
+pub fn set_variable(
+    runtime_services: &RuntimeServices,
+    name: &str,
+    vendor: &EfiGuid,
+    attributes: VariableAttributes,
+    data: &[u8],
+) -> Result<(), Error> {
+    let name = encode_name(name)?;
+
+    // Call `EFI_RUNTIME_SERVICES.SetVariable()`.
+    let status = (runtime_services.runtime_services.set_variable)(
+        name.as_ptr(),
+        vendor,
+        attributes.0,
+        data.len(),
+        data.as_ptr(),
+    );
+
+    // Return with error in the case of warning and error status codes.
+    match status.into() {
+        Status::Success => {}
+        Status::Warning(warn) => return Err(warn.into()),
+        Status::Error(err) => return Err(err.into()),
+    }
+
+    Ok(())
+}
+
+/// Represents the name of a UEFI variable and its vendor.
+#[derive(Debug, Clone, Copy)]
+pub struct VariableName {
+    name: [u16; VARIABLE_NAME_LEN],
+    len: usize,
+    vendor: EfiGuid,
+}
+
+impl VariableName {
+    /// Name of the variable.
+    pub fn name(&self) -> Ucs2Str<'_> {
+        Ucs2Str::from_slice(&self.name[..self.len])
+    }
+
+    /// Vendor GUID of the variable.
+    pub fn vendor(&self) -> &EfiGuid {
+        &self.vendor
+    }
+}
+
+/// Iterator over the names of the UEFI variables.
+pub struct VariableNames<'a> {
+    runtime_services: &'a RuntimeServices,
+
+    /// Last returned variable. An empty name starts the search.
+    current: VariableName,
+
+    /// The end of the search has been reached or an error occurred.
+    done: bool,
+}
+
+impl Iterator for VariableNames<'_> {
+    type Item = Result<VariableName, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let mut name_size = core::mem::size_of_val(&self.current.name);
+
+        // Call `EFI_RUNTIME_SERVICES.GetNextVariableName()`. It updates the
+        // name and the vendor in place.
+        let get_next_variable_name = self
+            .runtime_services
+            .runtime_services
+            .get_next_variable_name;
+        let status = get_next_variable_name(
+            &mut name_size,
+            self.current.name.as_mut_ptr(),
+            &mut self.current.vendor,
+        );
+
+        match status.into() {
+            Status::Success => {}
+            Status::Error(StatusError::NotFound) => {
+                self.done = true;
+                return None;
+            }
+            Status::Warning(warn) => {
+                self.done = true;
+                return Some(Err(warn.into()));
+            }
+            Status::Error(err) => {
+                self.done = true;
+                return Some(Err(err.into()));
+            }
+        }
+
+        self.current.len = self
+            .current
+            .name
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(VARIABLE_NAME_LEN);
+
+        Some(Ok(self.current))
+    }
+}
+
+/// Returns an iterator over the names of all the UEFI variables.
+pub fn variable_names(
+    runtime_services: &RuntimeServices,
+) -> VariableNames<'_> {
+    VariableNames {
+        runtime_services,
+        current: VariableName {
+            name: [0u16; VARIABLE_NAME_LEN],
+            len: 0,
+            vendor: EfiGuid::default(),
+        },
+        done: false,
+    }
+}
+
+/// Returns the boot option selected for the current boot (`BootCurrent`).
+pub fn boot_current(runtime_services: &RuntimeServices) -> Result<u16, Error> {
+    let mut buf = [0u8; 2];
+    let (size, _) = get_variable(
+        runtime_services,
+        "BootCurrent",
+        &EFI_GLOBAL_VARIABLE,
+        &mut buf,
+    )?;
+    if size != buf.len() {
+        return Err(Error::InvalidVariableData);
+    }
+    Ok(u16::from_le_bytes(buf))
+}
+
+/// Reads the ordered list of boot options (`BootOrder`) into `buf`. It
+/// returns the slice of `buf` that contains the boot options.
+pub fn boot_order<'a>(
+    runtime_services: &RuntimeServices,
+    buf: &'a mut [u16],
+) -> Result<&'a [u16], Error> {
+    // Read the variable directly into `buf`. A `u16` slice can always be
+    // viewed as a byte slice of twice its length.
+    let data = unsafe {
+        core::slice::from_raw_parts_mut(
+            buf.as_mut_ptr() as *mut u8,
+            buf.len() * 2,
+        )
+    };
+    let (size, _) = get_variable(
+        runtime_services,
+        "BootOrder",
+        &EFI_GLOBAL_VARIABLE,
+        data,
+    )?;
+    if size % 2 != 0 {
+        return Err(Error::InvalidVariableData);
+    }
+
+    // UEFI variables are little-endian.
+    let n = size / 2;
+    for it in buf[..n].iter_mut() {
+        *it = u16::from_le(*it);
+    }
+
+    Ok(&buf[..n])
+}
+
+/// Reads a global variable containing a single boolean byte.
+fn get_bool(
+    runtime_services: &RuntimeServices,
+    name: &str,
+) -> Result<bool, Error> {
+    let mut buf = [0u8; 1];
+    let (size, _) =
+        get_variable(runtime_services, name, &EFI_GLOBAL_VARIABLE, &mut buf)?;
+    if size != buf.len() {
+        return Err(Error::InvalidVariableData);
+    }
+    Ok(buf[0] == 1)
+}
+
+/// Returns `true` if the platform firmware is operating in secure boot mode
+/// (`SecureBoot`).
+pub fn secure_boot(runtime_services: &RuntimeServices) -> Result<bool, Error> {
+    get_bool(runtime_services, "SecureBoot")
+}
+
+/// Returns `true` if the platform firmware is operating in setup mode
+/// (`SetupMode`). In setup mode, the secure boot keys can be enrolled
+/// without authentication.
+pub fn setup_mode(runtime_services: &RuntimeServices) -> Result<bool, Error> {
+    get_bool(runtime_services, "SetupMode")
+}